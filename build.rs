@@ -0,0 +1,30 @@
+// Copyright 2021 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by an MIT
+// licence that can be found in the LICENCE file.
+
+use std::path::Path;
+use std::process::Command;
+
+// `main` makes the Git commit that `dpnd` is built from available to the
+// build as `DPND_GIT_COMMIT`. The commit is only recorded if the package
+// directory is the root of a Git repository, so that packages that are built
+// inside other repositories, such as from vendored sources, don't report the
+// commits of those repositories.
+fn main() {
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+
+    if !Path::new(".git").exists() {
+        return;
+    }
+
+    let output = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .output();
+    if let Ok(output) = output {
+        if output.status.success() {
+            let commit = String::from_utf8_lossy(&output.stdout);
+            println!("cargo:rustc-env=DPND_GIT_COMMIT={}", commit.trim());
+        }
+    }
+}
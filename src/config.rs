@@ -0,0 +1,526 @@
+// Copyright 2021 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by an MIT
+// licence that can be found in the LICENCE file.
+
+use std::fs;
+use std::io::Error as IoError;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use install::CONFLICT_POLICY_NAMES;
+use install::ConflictPolicy;
+use store;
+
+extern crate snafu;
+
+use snafu::Snafu;
+
+// `PROJ_CONFIG_PATH` is the path of the project config file, relative to the
+// directory that contains the dependency file.
+pub const PROJ_CONFIG_PATH: &str = ".dpnd/config.toml";
+
+// `Config` contains default values for `install` flags, the proxies that
+// tools use to access the network, how SSH host keys are checked, the
+// location of the store and the limits that `prune-cache` applies to it, and
+// the commands that are run before and after each dependency is fetched.
+// Config files are written in a subset of TOML, where each setting in the
+// `[install]` and `[ssh]` sections is a boolean or, for `fetch_timeout` and
+// `jobs`, a non-negative integer or, for `conflicts`, a quoted string, each
+// setting in the `[proxy]` and `[hooks]` sections is a quoted string, and the
+// `[cache]` section contains a quoted absolute path, a quoted size and a
+// number of days. A `#` at the start of a line or after whitespace starts a
+// comment:
+//
+//     [install]
+//     recursive = true
+//     fetch_timeout = 300
+//     jobs = 4
+//     conflicts = "error"
+//
+//     [proxy]
+//     https = "http://proxy.example.com:3128"
+//     no_proxy = "localhost,.example.com"
+//
+//     [ssh]
+//     accept_new_host_keys = true
+//
+//     [cache]
+//     dir = "/var/cache/dpnd"  # Shared by the CI jobs on this machine.
+//     max_size = "10G"
+//     max_age = 30
+//
+//     [hooks]
+//     pre_fetch = "echo fetching $DPND_DEP_NAME"
+//     post_fetch = "./record_fetch.sh"
+//
+// Settings that aren't defined are `None`, so that they can fall back to
+// settings defined in other config files.
+#[derive(Debug, Default)]
+pub struct Config {
+    pub recursive: Option<bool>,
+    pub vendor: Option<bool>,
+    pub deterministic_mtimes: Option<bool>,
+    // `fetch_timeout` is in seconds.
+    pub fetch_timeout: Option<u64>,
+    pub conflicts: Option<ConflictPolicy>,
+    pub keep_going: Option<bool>,
+    // `jobs` is never `Some(0)`.
+    pub jobs: Option<usize>,
+    pub http_proxy: Option<String>,
+    pub https_proxy: Option<String>,
+    pub no_proxy: Option<String>,
+    // `accept_new_host_keys` indicates whether SSH should add the keys of
+    // hosts that aren't known yet to `known_hosts` instead of prompting.
+    pub accept_new_host_keys: Option<bool>,
+    // `store_dir` is an absolute path.
+    pub store_dir: Option<PathBuf>,
+    // `cache_max_size` is in bytes.
+    pub cache_max_size: Option<u64>,
+    // `cache_max_age` is in days.
+    pub cache_max_age: Option<u64>,
+    // `pre_fetch_hook` and `post_fetch_hook` are shell commands.
+    pub pre_fetch_hook: Option<String>,
+    pub post_fetch_hook: Option<String>,
+}
+
+impl Config {
+    // `load` reads the config file at `user_config_path`, if it's defined,
+    // and the project config file in `proj_dir`. Settings in the project
+    // config file take precedence over those in the user config file. Missing
+    // config files are treated as empty.
+    pub fn load(user_config_path: Option<&Path>, proj_dir: &Path)
+        -> Result<Config, ConfigError>
+    {
+        let user_config =
+            if let Some(path) = user_config_path {
+                read_config(path)?
+            } else {
+                Config::default()
+            };
+
+        let proj_config = read_config(&proj_dir.join(PROJ_CONFIG_PATH))?;
+
+        Ok(proj_config.or(user_config))
+    }
+
+    // `ci_preset` returns the settings that `dpnd install --ci` applies. The
+    // preset is layered over the config files, so its settings take
+    // precedence over theirs, but flags passed on the command line still
+    // take precedence over the preset.
+    pub fn ci_preset() -> Config {
+        Config{
+            // A CI run should report every dependency that can't be fetched,
+            // rather than stopping at the first one.
+            keep_going: Some(true),
+            ..Config::default()
+        }
+    }
+
+    // `or` returns a `Config` that contains the settings of `self`, with
+    // settings that aren't defined in `self` taken from `other`.
+    pub fn or(self, other: Config) -> Config {
+        Config{
+            recursive: self.recursive.or(other.recursive),
+            vendor: self.vendor.or(other.vendor),
+            deterministic_mtimes:
+                self.deterministic_mtimes.or(other.deterministic_mtimes),
+            fetch_timeout: self.fetch_timeout.or(other.fetch_timeout),
+            conflicts: self.conflicts.or(other.conflicts),
+            keep_going: self.keep_going.or(other.keep_going),
+            jobs: self.jobs.or(other.jobs),
+            http_proxy: self.http_proxy.or(other.http_proxy),
+            https_proxy: self.https_proxy.or(other.https_proxy),
+            no_proxy: self.no_proxy.or(other.no_proxy),
+            accept_new_host_keys:
+                self.accept_new_host_keys.or(other.accept_new_host_keys),
+            store_dir: self.store_dir.or(other.store_dir),
+            cache_max_size: self.cache_max_size.or(other.cache_max_size),
+            cache_max_age: self.cache_max_age.or(other.cache_max_age),
+            pre_fetch_hook: self.pre_fetch_hook.or(other.pre_fetch_hook),
+            post_fetch_hook: self.post_fetch_hook.or(other.post_fetch_hook),
+        }
+    }
+}
+
+fn read_config(path: &Path) -> Result<Config, ConfigError> {
+    match fs::read_to_string(path) {
+        Ok(conts) => {
+            parse_config(path, &conts)
+        },
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            Ok(Config::default())
+        },
+        Err(source) => {
+            Err(ConfigError::ReadFailed{source, path: path.to_path_buf()})
+        },
+    }
+}
+
+// `SECTIONS` lists the sections that config files can contain, along with
+// the settings that each section supports.
+pub const SECTIONS: &[(&str, &[&str])] = &[
+    ("cache", &["dir", "max_age", "max_size"]),
+    ("hooks", &["post_fetch", "pre_fetch"]),
+    (
+        "install",
+        &[
+            "conflicts",
+            "deterministic_mtimes",
+            "fetch_timeout",
+            "jobs",
+            "keep_going",
+            "recursive",
+            "vendor",
+        ],
+    ),
+    ("proxy", &["http", "https", "no_proxy"]),
+    ("ssh", &["accept_new_host_keys"]),
+];
+
+fn parse_config(path: &Path, conts: &str) -> Result<Config, ConfigError> {
+    let mut config = Config::default();
+    let mut section: Option<&str> = None;
+
+    for (i, line) in conts.lines().enumerate() {
+        let ln_num = i + 1;
+
+        let ln = strip_comment(line).trim();
+        if ln.is_empty() {
+            continue;
+        }
+
+        if let Some(name) = ln.strip_prefix('[') {
+            if let Some(name) = name.strip_suffix(']') {
+                let name = name.trim();
+                match SECTIONS.iter().find(|(sect, _)| *sect == name) {
+                    Some((sect, _)) => {
+                        section = Some(sect);
+                    },
+                    None => {
+                        return Err(ConfigError::UnknownSection{
+                            path: path.to_path_buf(),
+                            ln_num,
+                            section: name.to_string(),
+                        });
+                    },
+                }
+                continue;
+            }
+        }
+
+        let (key, value) =
+            match ln.split_once('=') {
+                Some((key, value)) => {
+                    (key.trim(), value.trim())
+                },
+                None => {
+                    return Err(ConfigError::InvalidLine{
+                        path: path.to_path_buf(),
+                        ln_num,
+                        line: line.to_string(),
+                    });
+                },
+            };
+
+        let setting = Setting{path, ln_num, section, key, value};
+        match section {
+            Some("install") => {
+                parse_install_setting(&mut config, &setting)?;
+            },
+            Some("proxy") => {
+                parse_proxy_setting(&mut config, &setting)?;
+            },
+            Some("ssh") => {
+                parse_ssh_setting(&mut config, &setting)?;
+            },
+            Some("cache") => {
+                parse_cache_setting(&mut config, &setting)?;
+            },
+            Some("hooks") => {
+                parse_hooks_setting(&mut config, &setting)?;
+            },
+            _ => {
+                return Err(setting.unknown());
+            },
+        }
+    }
+
+    Ok(config)
+}
+
+// `strip_comment` returns `line` without its comment, if it has one. A `#`
+// only starts a comment at the start of a line or after whitespace, so that
+// values such as URLs with fragments can contain `#`.
+fn strip_comment(line: &str) -> &str {
+    let mut prev_is_space = true;
+    for (i, c) in line.char_indices() {
+        if c == '#' && prev_is_space {
+            return &line[..i];
+        }
+        prev_is_space = c.is_whitespace();
+    }
+
+    line
+}
+
+fn parse_install_setting(config: &mut Config, setting: &Setting)
+    -> Result<(), ConfigError>
+{
+    match setting.key {
+        "recursive" => {
+            config.recursive = Some(setting.bool()?);
+        },
+        "vendor" => {
+            config.vendor = Some(setting.bool()?);
+        },
+        "deterministic_mtimes" => {
+            config.deterministic_mtimes = Some(setting.bool()?);
+        },
+        "keep_going" => {
+            config.keep_going = Some(setting.bool()?);
+        },
+        "fetch_timeout" => {
+            config.fetch_timeout = Some(setting.number()?);
+        },
+        "jobs" => {
+            // At least one project must be installed at a time.
+            match setting.number()? {
+                0 => {
+                    return Err(ConfigError::InvalidJobs{
+                        path: setting.path.to_path_buf(),
+                        ln_num: setting.ln_num,
+                        key: setting.key.to_string(),
+                    });
+                },
+                n => {
+                    config.jobs = Some(n);
+                },
+            }
+        },
+        "conflicts" => {
+            let maybe_policy =
+                parse_string(setting.value).and_then(ConflictPolicy::parse);
+            match maybe_policy {
+                Some(policy) => {
+                    config.conflicts = Some(policy);
+                },
+                None => {
+                    return Err(ConfigError::InvalidChoice{
+                        path: setting.path.to_path_buf(),
+                        ln_num: setting.ln_num,
+                        key: setting.key.to_string(),
+                        value: setting.value.to_string(),
+                        choices: CONFLICT_POLICY_NAMES,
+                    });
+                },
+            }
+        },
+        _ => {
+            return Err(setting.unknown());
+        },
+    }
+
+    Ok(())
+}
+
+fn parse_proxy_setting(config: &mut Config, setting: &Setting)
+    -> Result<(), ConfigError>
+{
+    let target =
+        match setting.key {
+            "http" => {
+                &mut config.http_proxy
+            },
+            "https" => {
+                &mut config.https_proxy
+            },
+            "no_proxy" => {
+                &mut config.no_proxy
+            },
+            _ => {
+                return Err(setting.unknown());
+            },
+        };
+    *target = Some(setting.string()?.to_string());
+
+    Ok(())
+}
+
+fn parse_ssh_setting(config: &mut Config, setting: &Setting)
+    -> Result<(), ConfigError>
+{
+    match setting.key {
+        "accept_new_host_keys" => {
+            config.accept_new_host_keys = Some(setting.bool()?);
+        },
+        _ => {
+            return Err(setting.unknown());
+        },
+    }
+
+    Ok(())
+}
+
+fn parse_cache_setting(config: &mut Config, setting: &Setting)
+    -> Result<(), ConfigError>
+{
+    match setting.key {
+        "dir" => {
+            // Config files can be read from different directories, so a
+            // relative path would be ambiguous.
+            let dir = PathBuf::from(setting.string()?);
+            if !dir.is_absolute() {
+                return Err(ConfigError::RelativePath{
+                    path: setting.path.to_path_buf(),
+                    ln_num: setting.ln_num,
+                    key: setting.key.to_string(),
+                    value: setting.value.to_string(),
+                });
+            }
+            config.store_dir = Some(dir);
+        },
+        "max_size" => {
+            match parse_string(setting.value).and_then(store::parse_size) {
+                Some(size) => {
+                    config.cache_max_size = Some(size);
+                },
+                None => {
+                    return Err(ConfigError::InvalidSize{
+                        path: setting.path.to_path_buf(),
+                        ln_num: setting.ln_num,
+                        key: setting.key.to_string(),
+                        value: setting.value.to_string(),
+                    });
+                },
+            }
+        },
+        "max_age" => {
+            config.cache_max_age = Some(setting.number()?);
+        },
+        _ => {
+            return Err(setting.unknown());
+        },
+    }
+
+    Ok(())
+}
+
+fn parse_hooks_setting(config: &mut Config, setting: &Setting)
+    -> Result<(), ConfigError>
+{
+    let target =
+        match setting.key {
+            "pre_fetch" => {
+                &mut config.pre_fetch_hook
+            },
+            "post_fetch" => {
+                &mut config.post_fetch_hook
+            },
+            _ => {
+                return Err(setting.unknown());
+            },
+        };
+    *target = Some(setting.string()?.to_string());
+
+    Ok(())
+}
+
+// `Setting` is a `key = value` line of a config file, which is in `section`
+// of the config file at `path`.
+struct Setting<'a> {
+    path: &'a Path,
+    ln_num: usize,
+    section: Option<&'a str>,
+    key: &'a str,
+    value: &'a str,
+}
+
+impl<'a> Setting<'a> {
+    fn bool(&self) -> Result<bool, ConfigError> {
+        match self.value {
+            "true" => {
+                Ok(true)
+            },
+            "false" => {
+                Ok(false)
+            },
+            _ => {
+                Err(ConfigError::InvalidBool{
+                    path: self.path.to_path_buf(),
+                    ln_num: self.ln_num,
+                    key: self.key.to_string(),
+                    value: self.value.to_string(),
+                })
+            },
+        }
+    }
+
+    fn number<T: FromStr>(&self) -> Result<T, ConfigError> {
+        self.value.parse()
+            .map_err(|_| ConfigError::InvalidNumber{
+                path: self.path.to_path_buf(),
+                ln_num: self.ln_num,
+                key: self.key.to_string(),
+                value: self.value.to_string(),
+            })
+    }
+
+    fn string(&self) -> Result<&'a str, ConfigError> {
+        parse_string(self.value)
+            .ok_or_else(|| ConfigError::InvalidString{
+                path: self.path.to_path_buf(),
+                ln_num: self.ln_num,
+                key: self.key.to_string(),
+                value: self.value.to_string(),
+            })
+    }
+
+    fn unknown(&self) -> ConfigError {
+        ConfigError::UnknownSetting{
+            path: self.path.to_path_buf(),
+            ln_num: self.ln_num,
+            section: self.section.map(ToString::to_string),
+            key: self.key.to_string(),
+        }
+    }
+}
+
+// `parse_string` returns the contents of `value` if it's a quoted string.
+// Escapes aren't supported, so the contents can't contain `"`.
+fn parse_string(value: &str) -> Option<&str> {
+    let s = value.strip_prefix('"')?.strip_suffix('"')?;
+
+    if s.contains('"') {
+        None
+    } else {
+        Some(s)
+    }
+}
+
+#[derive(Debug, Snafu)]
+pub enum ConfigError {
+    ReadFailed{source: IoError, path: PathBuf},
+    InvalidLine{path: PathBuf, ln_num: usize, line: String},
+    UnknownSection{path: PathBuf, ln_num: usize, section: String},
+    UnknownSetting{
+        path: PathBuf,
+        ln_num: usize,
+        section: Option<String>,
+        key: String,
+    },
+    InvalidBool{path: PathBuf, ln_num: usize, key: String, value: String},
+    InvalidNumber{path: PathBuf, ln_num: usize, key: String, value: String},
+    InvalidJobs{path: PathBuf, ln_num: usize, key: String},
+    InvalidString{path: PathBuf, ln_num: usize, key: String, value: String},
+    InvalidSize{path: PathBuf, ln_num: usize, key: String, value: String},
+    RelativePath{path: PathBuf, ln_num: usize, key: String, value: String},
+    InvalidChoice{
+        path: PathBuf,
+        ln_num: usize,
+        key: String,
+        value: String,
+        choices: &'static [&'static str],
+    },
+}
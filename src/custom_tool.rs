@@ -0,0 +1,173 @@
+// Copyright 2021 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by an MIT
+// licence that can be found in the LICENCE file.
+
+use std::fs;
+use std::io::Error as IoError;
+use std::path::Path;
+use std::process::Command;
+use std::process::Output;
+use std::time::Instant;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use dep_tools::DepTool;
+use dep_tools::DepToolError;
+use dep_tools::FetchError;
+use dep_tools::Version;
+use dep_tools::output_until;
+
+extern crate snafu;
+
+use snafu::Snafu;
+
+// `REVISION_FILE_NAME` is the name of the file, in the output directory of a
+// dependency, that records the version that was fetched, followed by the time
+// at which it was fetched, in seconds since the Unix epoch.
+const REVISION_FILE_NAME: &str = ".dpnd_custom";
+
+// `Custom` fetches dependencies by running user-defined commands. The source
+// of a dependency is a command template, which is run by `sh` in the output
+// directory of the dependency after `{out_dir}` and `{version}` are replaced
+// with the quoted path of the output directory and the quoted version of the
+// dependency. The command should exit with a non-zero status if it fails.
+#[derive(Debug)]
+pub struct Custom;
+
+impl DepTool<DepToolError> for Custom {
+    fn name(&self) -> String {
+        "custom".to_string()
+    }
+
+    fn fetch(
+        &self,
+        src: String,
+        Version(vsn): Version,
+        _path: Option<&str>,
+        out_dir: &Path,
+        deadline: Option<Instant>,
+    )
+        -> Result<(), FetchError<DepToolError>>
+    {
+        let cmd =
+            src.replace("{out_dir}", &quote_sh(&out_dir.to_string_lossy()))
+                .replace("{version}", &quote_sh(&vsn));
+        if let Err(err) = run_sh_until(&cmd, out_dir, deadline) {
+            let source = DepToolError::CustomCmdFailed{source: err};
+            return Err(FetchError::RetrieveFailed{source});
+        }
+
+        // The time of the fetch is recorded as the revision time, because
+        // the command can't report when the fetched revision was created.
+        let now =
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs());
+        let path = out_dir.join(REVISION_FILE_NAME);
+        if let Err(source) = fs::write(&path, format!("{}\n{}\n", vsn, now)) {
+            let source = DepToolError::WriteFileFailed{source, path};
+            return Err(FetchError::RetrieveFailed{source});
+        }
+
+        Ok(())
+    }
+
+    fn resolve(&self, _src: &str, Version(vsn): &Version)
+        -> Result<String, DepToolError>
+    {
+        Ok(vsn.clone())
+    }
+
+    fn verify(&self, _out_dir: &Path, _version: &Version)
+        -> Result<(), DepToolError>
+    {
+        Err(DepToolError::VerifyUnsupported{tool_name: self.name()})
+    }
+
+    fn revision(&self, out_dir: &Path) -> Result<String, DepToolError> {
+        let (version, _) = read_revision_file(out_dir)?;
+
+        Ok(version)
+    }
+
+    fn revision_time(&self, out_dir: &Path) -> Result<u64, DepToolError> {
+        let (_, fetched) = read_revision_file(out_dir)?;
+
+        fetched.parse()
+            .map_err(|_| DepToolError::InvalidRevisionFile{
+                path: out_dir.join(REVISION_FILE_NAME),
+            })
+    }
+
+    fn metadata_paths(&self, _out_dir: &Path)
+        -> Result<Vec<String>, DepToolError>
+    {
+        Ok(vec![REVISION_FILE_NAME.to_string()])
+    }
+}
+
+// `quote_sh` returns `s` quoted for use as a single word in a `sh` command.
+fn quote_sh(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+// `read_revision_file` returns the version and fetch time that were recorded
+// when the dependency in `out_dir` was fetched.
+fn read_revision_file(out_dir: &Path)
+    -> Result<(String, String), DepToolError>
+{
+    let path = out_dir.join(REVISION_FILE_NAME);
+    let conts =
+        match fs::read_to_string(&path) {
+            Ok(conts) => {
+                conts
+            },
+            Err(source) => {
+                return Err(DepToolError::ReadFileFailed{source, path});
+            },
+        };
+
+    let mut lines = conts.lines();
+    match (lines.next(), lines.next()) {
+        (Some(version), Some(fetched)) => {
+            Ok((version.to_string(), fetched.to_string()))
+        },
+        _ => {
+            Err(DepToolError::InvalidRevisionFile{path})
+        },
+    }
+}
+
+fn run_sh_until(cmd: &str, dir: &Path, deadline: Option<Instant>)
+    -> Result<Output, CustomCmdError>
+{
+    let maybe_output = output_until(
+        Command::new("sh").args(["-c", cmd]).current_dir(dir),
+        deadline,
+    );
+
+    let output =
+        match maybe_output {
+            Ok(output) => {
+                output
+            },
+            Err(source) => {
+                return Err(CustomCmdError::StartFailed{
+                    source,
+                    cmd: cmd.to_string(),
+                });
+            },
+        };
+
+    if !output.status.success() {
+        return Err(CustomCmdError::NotSuccess{cmd: cmd.to_string(), output});
+    }
+
+    Ok(output)
+}
+
+#[derive(Debug, Snafu)]
+pub enum CustomCmdError {
+    StartFailed{source: IoError, cmd: String},
+    NotSuccess{cmd: String, output: Output},
+}
@@ -2,20 +2,51 @@
 // Use of this source code is governed by an MIT
 // licence that can be found in the LICENCE file.
 
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
 use std::error::Error;
 use std::fmt::Display;
 use std::fmt::Formatter;
 use std::fmt::Result as FmtResult;
+use std::fs;
 use std::io::Error as IoError;
+use std::io::ErrorKind;
+use std::io;
+use std::io::IsTerminal;
+use std::io::Read;
+use std::io::Write;
 use std::path::Path;
+use std::path::PathBuf;
 use std::process::Command;
 use std::process::Output;
+use std::process::Stdio;
+use std::sync::Mutex;
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use std::time::Instant;
 
 extern crate snafu;
 
+use snafu::ResultExt;
 use snafu::Snafu;
 
-pub trait DepTool<E>
+use custom_tool::CustomCmdError;
+#[cfg(feature = "docker")]
+use docker::DockerCmdError;
+use file_tool::CurlCmdError;
+use install::STATE_DIR_PATH;
+use interrupt;
+use object_store::ObjectStoreCmdError;
+use pack;
+use pack::TarCmdError;
+use plugin::PluginCmdError;
+use svn::SvnCmdError;
+use temp_dir;
+
+// Tools are `Sync` so that nested projects can be installed in parallel.
+pub trait DepTool<E>: Sync
 where
     E: Error + 'static,
 {
@@ -23,17 +54,221 @@ where
     // dependency tools.
     fn name(&self) -> String;
 
+    // `tool_version` returns the version of the program that the tool uses to
+    // fetch dependencies, or `None` if it isn't known. It's recorded in the
+    // state file when a dependency is installed.
+    fn tool_version(&self) -> Option<String> {
+        None
+    }
+
+    // `fetch` fetches `version` of `source` to `out_dir`. `path` is the value
+    // of the `path` option of the dependency, which is only defined for tools
+    // where `requires_path` returns `true`. Commands that are still running at
+    // `deadline`, if it's defined, are killed.
     fn fetch(
         &self,
         source: String,
         version: Version,
+        path: Option<&str>,
         out_dir: &Path,
+        deadline: Option<Instant>,
     ) -> Result<(), FetchError<E>>;
+
+    // `requires_path` returns whether dependencies that use this tool must
+    // define the `path` option, which selects a single file to fetch. The
+    // `path` option can't be used with tools that don't require it.
+    fn requires_path(&self) -> bool {
+        false
+    }
+
+    // `resolve` returns an identifier for the exact revision that `version` of
+    // `source` refers to, without fetching the dependency to an output
+    // directory.
+    fn resolve(&self, source: &str, version: &Version) -> Result<String, E>;
+
+    // `verify` returns an error if the revision of the dependency that was
+    // fetched to `out_dir` doesn't have a valid signature from a trusted key.
+    fn verify(&self, out_dir: &Path, version: &Version) -> Result<(), E>;
+
+    // `revision` returns an identifier for the exact revision of the
+    // dependency that was fetched to `out_dir`.
+    fn revision(&self, out_dir: &Path) -> Result<String, E>;
+
+    // `revision_time` returns the time, in seconds since the Unix epoch, at
+    // which the revision of the dependency that was fetched to `out_dir` was
+    // created.
+    fn revision_time(&self, out_dir: &Path) -> Result<u64, E>;
+
+    // `metadata_paths` returns the paths, relative to `out_dir`, that the tool
+    // uses to manage the dependency that was fetched to `out_dir`.
+    fn metadata_paths(&self, out_dir: &Path) -> Result<Vec<String>, E>;
+
+    // `export` replaces the dependency that was fetched to `out_dir` with a
+    // clean export of its files, as produced by the version control system
+    // that the tool uses, which doesn't contain tool metadata. It returns
+    // `false` if the tool can't export dependencies, in which case `out_dir`
+    // isn't changed.
+    fn export(&self, _out_dir: &Path) -> Result<bool, E> {
+        Ok(false)
+    }
+
+    // `is_modified` returns whether the dependency that was fetched to
+    // `out_dir` has been modified locally since it was fetched. Tools that
+    // can't detect modifications report dependencies as unmodified.
+    fn is_modified(&self, _out_dir: &Path) -> Result<bool, E> {
+        Ok(false)
+    }
+
+    // `changes` returns a description of each change between the revision of
+    // the dependency that was fetched from `source` to `out_dir` and
+    // `revision`, which was returned by `resolve`. Changes that are only in
+    // the fetched revision are prefixed with `<`, and changes that are only in
+    // `revision` are prefixed with `>`. `None` is returned if the tool can't
+    // list the changes for the dependency.
+    fn changes(&self, _source: &str, _out_dir: &Path, _revision: &str)
+        -> Result<Option<Vec<String>>, E>
+    {
+        Ok(None)
+    }
+
+    // `fast_forward` updates the dependency that was fetched from `source` to
+    // `out_dir` to the latest revision of `version`, which names a branch,
+    // discarding local changes. It returns `false` if the tool can't update
+    // dependencies in place, in which case the dependency must be fetched
+    // again.
+    fn fast_forward(
+        &self,
+        _source: &str,
+        _out_dir: &Path,
+        _version: &Version,
+        _deadline: Option<Instant>,
+    )
+        -> Result<bool, E>
+    {
+        Ok(false)
+    }
+
+    // `change_version` changes the dependency that was fetched from `source`
+    // to `out_dir` to `version`, reusing the files that were already fetched,
+    // and discarding local changes. It returns `false` if the tool can't
+    // change the version of dependencies in place, in which case the
+    // dependency must be fetched again.
+    fn change_version(
+        &self,
+        _source: &str,
+        _out_dir: &Path,
+        _version: &Version,
+        _deadline: Option<Instant>,
+    )
+        -> Result<bool, E>
+    {
+        Ok(false)
+    }
+
+    // `check_source` returns an error if `source` can't be reached. It should
+    // be cheap to run, and tools that can't check sources cheaply report
+    // every source as reachable.
+    fn check_source(&self, _source: &str) -> Result<(), E> {
+        Ok(())
+    }
+
+    // `latest_version` returns the newest version of `source`, which is used
+    // when upgrading a dependency without naming a version. `None` is returned
+    // if the tool can't determine the newest version of a source.
+    fn latest_version(&self, _source: &str) -> Result<Option<Version>, E> {
+        Ok(None)
+    }
+
+    // `validate_source` returns an error if `source` doesn't have a form that
+    // the tool can fetch from. Only the form of `source` is checked, so the
+    // network isn't accessed, and tools that don't know the form of their
+    // sources accept every source.
+    fn validate_source(&self, _source: &str) -> Result<(), InvalidSource> {
+        Ok(())
+    }
+}
+
+// `InvalidSource` describes why `DepTool::validate_source` rejected a source.
+#[derive(Debug)]
+pub struct InvalidSource {
+    // `char_idx` is the index of the character of the source at which the
+    // problem was found, counted in characters rather than bytes.
+    pub char_idx: usize,
+    pub reason: String,
+}
+
+// `DepToolError` is the error type returned by all dependency tools, so that
+// tools of different types can be used interchangeably.
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Snafu)]
+pub enum DepToolError {
+    GitCmdFailed{source: GitCmdError},
+    // The errors of plugins and object stores are boxed because they're
+    // larger than the errors of the other tools.
+    PluginCmdFailed{
+        #[snafu(source(from(PluginCmdError, Box::new)))]
+        source: Box<PluginCmdError>,
+    },
+    SvnCmdFailed{source: SvnCmdError},
+    CurlCmdFailed{source: CurlCmdError},
+    #[cfg(feature = "docker")]
+    DockerCmdFailed{source: DockerCmdError},
+    ObjectStoreCmdFailed{
+        #[snafu(source(from(ObjectStoreCmdError, Box::new)))]
+        source: Box<ObjectStoreCmdError>,
+    },
+    CustomCmdFailed{source: CustomCmdError},
+    CreateTempDirFailed{source: IoError, path: PathBuf},
+    TarCmdFailed{source: TarCmdError},
+    ReadDirFailed{source: IoError, path: PathBuf},
+    RemovePathFailed{source: IoError, path: PathBuf},
+    // `UntrustedSigningKey` is returned if a signature is valid but wasn't
+    // made by a trusted key. `key` is the fingerprint of the key that made
+    // the signature, if it's known.
+    UntrustedSigningKey{key: Option<String>},
+    VerifyUnsupported{tool_name: String},
+    // `PathNotFound` is returned if the `path` option of a dependency doesn't
+    // refer to a file in the fetched revision.
+    PathNotFound{path: String},
+    NoFileNameInUrl{url: String},
+    // `InvalidObjectUrl` is returned if the source of an `object-store`
+    // dependency isn't the URL of an object in a supported object store.
+    InvalidObjectUrl{url: String},
+    ReadFileFailed{source: IoError, path: PathBuf},
+    NoDownloadedFile{path: PathBuf},
+    MultipleDownloadedFiles{path: PathBuf},
+    ChecksumMismatch{file_name: String, expected: String, actual: String},
+    WriteFileFailed{source: IoError, path: PathBuf},
+    // `InvalidRevisionFile` is returned if the file that a tool uses to record
+    // the revision of a dependency can't be parsed.
+    InvalidRevisionFile{path: PathBuf},
+    // `OptionLikeSource` is returned if a source starts with `-`, because
+    // `program` would read it as an option rather than as a repository.
+    OptionLikeSource{src: String, program: String},
+    #[cfg(feature = "test-util")]
+    UnknownMockSource{src: String},
+    #[cfg(feature = "test-util")]
+    UnknownMockRevision{src: String, version: String},
 }
 
 #[derive(Clone, PartialEq)]
 pub struct Version(pub String);
 
+// `LATEST_VERSION` is a version that refers to the newest revision of a
+// source, which is the commit that the default branch refers to for Git
+// sources. It's resolved when the dependency is fetched, and the fetched
+// revision is kept until the dependency is fetched again.
+pub const LATEST_VERSION: &str = "latest";
+
+// `git_ref` returns the Git reference that `vsn` refers to.
+fn git_ref(vsn: &str) -> &str {
+    if vsn == LATEST_VERSION {
+        "HEAD"
+    } else {
+        vsn
+    }
+}
+
 impl Display for Version {
     fn fmt(&self, f: &mut Formatter) -> FmtResult {
         write!(f, "{}", self.0)
@@ -49,67 +284,1241 @@ where
     VersionChangeFailed{source: E},
 }
 
+// `EXPORT_ARCHIVE_NAME` is the name of the archive, in the `.git` directory of
+// a dependency, that `Git::export` exports the dependency to.
+const EXPORT_ARCHIVE_NAME: &str = "dpnd_export.tar";
+
 #[derive(Debug)]
-pub struct Git {}
+pub struct Git {
+    // `token`, if defined, is sent as credentials to the hosts of HTTPS
+    // sources, through the environment of Git rather than its arguments. It
+    // isn't persisted in cloned repositories, and is removed from the output
+    // of Git commands in errors.
+    pub token: Option<String>,
+    // `trusted_keys` contains the fingerprints, or suffixes of fingerprints,
+    // of the GPG keys that `verify` accepts signatures from. Signatures from
+    // any key in the GPG keyring are accepted if `trusted_keys` is empty.
+    pub trusted_keys: Vec<String>,
+    // `progress` indicates whether the progress of clones should be shown on
+    // `stderr`, which should only be the case if `stderr` is a terminal.
+    pub progress: bool,
+}
 
-impl DepTool<GitCmdError> for Git {
+impl DepTool<DepToolError> for Git {
     fn name(&self) -> String {
         "git".to_string()
     }
 
-    fn fetch(&self, src: String, Version(vsn): Version, out_dir: &Path)
-        -> Result<(), FetchError<GitCmdError>>
+    fn tool_version(&self) -> Option<String> {
+        program_version("git", &["--version"])
+    }
+
+    fn fetch(
+        &self,
+        src: String,
+        Version(vsn): Version,
+        _path: Option<&str>,
+        out_dir: &Path,
+        deadline: Option<Instant>,
+    )
+        -> Result<(), FetchError<DepToolError>>
     {
-        let gits_args = vec![
-            vec!["clone", &src, "."],
-            vec!["checkout", &vsn],
-        ];
+        check_not_option("Git", &src)
+            .context(RetrieveFailed{})?;
 
-        for (i, git_args) in gits_args.into_iter().enumerate() {
-            let maybe_output =
-                Command::new("git")
-                    .args(&git_args)
-                    .current_dir(out_dir)
-                    .output();
+        let result = self.with_auth(&src, || {
+            if self.progress {
+                let clone_args = &["clone", "--progress", "--", &src, "."];
 
-            let output = match maybe_output {
-                Ok(output) => output,
-                Err(err) => {
-                    let source = GitCmdError::StartFailed{
-                        source: err,
-                        args: owned_strs_to_strings(git_args),
-                    };
-                    if i == 0 {
-                        return Err(FetchError::RetrieveFailed{source});
+                run_git_with_progress(clone_args, out_dir, deadline, &src)
+            } else {
+                let clone_args = &["clone", "--", &src, "."];
+
+                run_git_until(clone_args, out_dir, deadline)
+            }
+        });
+        if let Err(err) = result {
+            let source = DepToolError::GitCmdFailed{source: self.redact(err)};
+            return Err(FetchError::RetrieveFailed{source});
+        }
+
+        run_git_until(&["checkout", git_ref(&vsn)], out_dir, deadline)
+            .context(GitCmdFailed{})
+            .context(VersionChangeFailed{})?;
+
+        Ok(())
+    }
+
+    fn resolve(&self, src: &str, Version(vsn): &Version)
+        -> Result<String, DepToolError>
+    {
+        check_not_option("Git", src)?;
+
+        let output =
+            self.with_auth(src, || {
+                run_git(&["ls-remote", "--", src], Path::new("."))
+            })
+            .map_err(|err| self.redact(err))
+            .context(GitCmdFailed{})?;
+
+        let vsn = git_ref(vsn);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let refs: HashMap<&str, &str> =
+            stdout.lines()
+                .filter_map(|line| {
+                    let mut parts = line.split('\t');
+
+                    match (parts.next(), parts.next()) {
+                        (Some(hash), Some(ref_name)) => {
+                            Some((ref_name, hash))
+                        },
+                        _ => {
+                            None
+                        },
                     }
-                    return Err(FetchError::VersionChangeFailed{source});
+                })
+                .collect();
+
+        // We prefer peeled references (those ending in `^{}`) because they
+        // refer to the commit that an annotated tag points to, rather than to
+        // the tag object itself.
+        let candidates = &[
+            format!("{}^{{}}", vsn),
+            vsn.to_string(),
+            format!("refs/tags/{}^{{}}", vsn),
+            format!("refs/tags/{}", vsn),
+            format!("refs/heads/{}", vsn),
+        ];
+        for candidate in candidates {
+            if let Some(hash) = refs.get(candidate.as_str()) {
+                return Ok(hash.to_string());
+            }
+        }
+
+        self.resolve_commit(src, vsn)
+    }
+
+    fn verify(&self, out_dir: &Path, Version(vsn): &Version)
+        -> Result<(), DepToolError>
+    {
+        let vsn = git_ref(vsn);
+        let tag_ref = format!("refs/tags/{}", vsn);
+        let git_args = &["rev-parse", "--verify", "--quiet", &tag_ref];
+        let is_tag = run_git(git_args, out_dir).is_ok();
+
+        // We verify the tag itself if the version is a tag, because tags are
+        // often signed when the commits that they point to aren't.
+        let verify_args =
+            if is_tag {
+                &["verify-tag", "--raw", vsn]
+            } else {
+                &["verify-commit", "--raw", "HEAD"]
+            };
+        let output = run_git(verify_args, out_dir)
+            .context(GitCmdFailed{})?;
+
+        if self.trusted_keys.is_empty() {
+            return Ok(());
+        }
+
+        // `--raw` causes GPG status lines to be written to `stderr`. The
+        // first field of a `VALIDSIG` line is the fingerprint of the key that
+        // made the signature, and the last field is the fingerprint of its
+        // primary key.
+        let status = String::from_utf8_lossy(&output.stderr);
+        let mut signing_keys = vec![];
+        for line in status.lines() {
+            if let Some(rest) = line.strip_prefix("[GNUPG:] VALIDSIG ") {
+                let fields: Vec<&str> = rest.split(' ').collect();
+                signing_keys.push(fields[0]);
+                if let Some(primary_key) = fields.last() {
+                    signing_keys.push(primary_key);
                 }
+            }
+        }
+
+        for signing_key in &signing_keys {
+            if self.trusted_keys.iter().any(|k| key_matches(k, signing_key)) {
+                return Ok(());
+            }
+        }
+
+        Err(DepToolError::UntrustedSigningKey{
+            key: signing_keys.first().map(ToString::to_string),
+        })
+    }
+
+    fn revision(&self, out_dir: &Path) -> Result<String, DepToolError> {
+        let output = run_git(&["rev-parse", "HEAD"], out_dir)
+            .context(GitCmdFailed{})?;
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    fn revision_time(&self, out_dir: &Path) -> Result<u64, DepToolError> {
+        let git_args = &["log", "--max-count=1", "--format=%ct"];
+        let output = run_git(git_args, out_dir)
+            .context(GitCmdFailed{})?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        if let Ok(t) = stdout.trim().parse() {
+            Ok(t)
+        } else {
+            let source = GitCmdError::UnexpectedOutput{
+                args: strs_to_strings(git_args),
+                output,
             };
 
-            if !output.status.success() {
-                let source = GitCmdError::NotSuccess{
-                    args: owned_strs_to_strings(git_args),
-                    output,
+            Err(DepToolError::GitCmdFailed{source})
+        }
+    }
+
+    fn metadata_paths(&self, _out_dir: &Path)
+        -> Result<Vec<String>, DepToolError>
+    {
+        Ok(vec![".git".to_string()])
+    }
+
+    fn export(&self, out_dir: &Path) -> Result<bool, DepToolError> {
+        // The archive is written to the `.git` directory so that it's removed
+        // along with the rest of the Git metadata. `git archive` applies the
+        // `export-ignore` and `export-subst` attributes of the archived
+        // revision.
+        let git_dir = out_dir.join(".git");
+        let archive_path = git_dir.join(EXPORT_ARCHIVE_NAME);
+        let archive_path_str = archive_path.to_string_lossy();
+        let git_args = &[
+            "archive",
+            "--format=tar",
+            "--output",
+            &archive_path_str,
+            "HEAD",
+        ];
+        run_git(git_args, out_dir)
+            .context(GitCmdFailed{})?;
+
+        let entries = fs::read_dir(out_dir)
+            .context(ReadDirFailed{path: out_dir})?;
+        for entry in entries {
+            let entry = entry.context(ReadDirFailed{path: out_dir})?;
+            let path = entry.path();
+            if path == git_dir {
+                continue;
+            }
+
+            // Symlinks are removed rather than followed.
+            let is_dir =
+                entry.file_type()
+                    .is_ok_and(|t| t.is_dir());
+            let result =
+                if is_dir {
+                    fs::remove_dir_all(&path)
+                } else {
+                    fs::remove_file(&path)
                 };
-                if i == 0 {
+            result.context(RemovePathFailed{path})?;
+        }
+
+        let out_dir_str = out_dir.to_string_lossy();
+        let tar_args = &[
+            "--extract",
+            "--file",
+            &archive_path_str,
+            "--directory",
+            &out_dir_str,
+        ];
+        pack::run_tar(tar_args)
+            .context(TarCmdFailed{})?;
+
+        fs::remove_dir_all(&git_dir)
+            .context(RemovePathFailed{path: git_dir})?;
+
+        Ok(true)
+    }
+
+    fn fast_forward(
+        &self,
+        src: &str,
+        out_dir: &Path,
+        Version(vsn): &Version,
+        deadline: Option<Instant>,
+    )
+        -> Result<bool, DepToolError>
+    {
+        // We fetch from `src` directly, rather than from `origin`, because
+        // the remote of the clone may have been changed locally.
+        check_not_option("Git", src)?;
+
+        let refspec = format!("+refs/heads/{0}:refs/remotes/origin/{0}", vsn);
+        let git_args = &["fetch", "--", src, &refspec];
+        self.with_auth(src, || run_git_until(git_args, out_dir, deadline))
+            .map_err(|err| self.redact(err))
+            .context(GitCmdFailed{})?;
+
+        let remote_ref = format!("refs/remotes/origin/{}", vsn);
+        run_git_until(&["reset", "--hard", &remote_ref], out_dir, deadline)
+            .context(GitCmdFailed{})?;
+
+        Ok(true)
+    }
+
+    fn change_version(
+        &self,
+        src: &str,
+        out_dir: &Path,
+        Version(vsn): &Version,
+        deadline: Option<Instant>,
+    )
+        -> Result<bool, DepToolError>
+    {
+        // Vendored dependencies don't have a clone that can be reused, and
+        // the default branch of a source is only known when it's cloned.
+        if !out_dir.join(".git").exists() || vsn == LATEST_VERSION {
+            return Ok(false);
+        }
+
+        check_not_option("Git", src)?;
+
+        let git_args = &[
+            "fetch",
+            "--quiet",
+            "--tags",
+            "--",
+            src,
+            "+refs/heads/*:refs/remotes/origin/*",
+        ];
+        self.with_auth(src, || run_git_until(git_args, out_dir, deadline))
+            .map_err(|err| self.redact(err))
+            .context(GitCmdFailed{})?;
+
+        // Branches are checked out from their remote-tracking branches,
+        // because the local branches that were created when the dependency
+        // was cloned may be out of date.
+        let remote_ref = format!("refs/remotes/origin/{}", vsn);
+        let git_args = &["rev-parse", "--verify", "--quiet", &remote_ref];
+        let is_branch = run_git(git_args, out_dir).is_ok();
+        let checkout_args: &[&str] =
+            if is_branch {
+                &["checkout", "--quiet", "--force", "-B", vsn, &remote_ref]
+            } else {
+                &["checkout", "--quiet", "--force", vsn]
+            };
+        run_git_until(checkout_args, out_dir, deadline)
+            .context(GitCmdFailed{})?;
+
+        // We remove untracked and ignored files so that the dependency has
+        // the same contents as a fresh clone.
+        run_git_until(&["clean", "-ffdx", "--quiet"], out_dir, deadline)
+            .context(GitCmdFailed{})?;
+
+        Ok(true)
+    }
+
+    fn is_modified(&self, out_dir: &Path) -> Result<bool, DepToolError> {
+        // Vendored dependencies don't have a `.git` directory, in which case
+        // `git status` would report the status of the enclosing repository,
+        // if there is one.
+        if !out_dir.join(".git").exists() {
+            return Ok(false);
+        }
+
+        // Untracked files aren't counted as modifications, because `dpnd`
+        // installs the nested dependencies of a dependency, and keeps their
+        // state, inside its output directory. The state directory is also
+        // excluded, because dependencies may commit their state files, which
+        // `dpnd` rewrites when it installs their nested dependencies.
+        let exclude_state_dir = format!(":(exclude){}", STATE_DIR_PATH);
+        let git_args = &[
+            "status",
+            "--porcelain",
+            "--untracked-files=no",
+            "--",
+            ".",
+            &exclude_state_dir,
+        ];
+        let output = run_git(git_args, out_dir)
+            .context(GitCmdFailed{})?;
+
+        Ok(!output.stdout.is_empty())
+    }
+
+    fn changes(&self, src: &str, out_dir: &Path, revision: &str)
+        -> Result<Option<Vec<String>>, DepToolError>
+    {
+        if !out_dir.join(".git").exists() {
+            return Ok(None);
+        }
+
+        // `revision` may not have existed when the dependency was fetched, so
+        // we update the branches and tags of the clone before listing the
+        // changes.
+        check_not_option("Git", src)?;
+
+        let git_args = &[
+            "fetch",
+            "--quiet",
+            "--tags",
+            "--",
+            src,
+            "+refs/heads/*:refs/remotes/origin/*",
+        ];
+        self.with_auth(src, || run_git(git_args, out_dir))
+            .map_err(|err| self.redact(err))
+            .context(GitCmdFailed{})?;
+
+        let range = format!("HEAD...{}", revision);
+        let git_args = &["log", "--left-right", "--format=%m %h %s", &range];
+        let output = run_git(git_args, out_dir)
+            .context(GitCmdFailed{})?;
+
+        let changes =
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .map(ToString::to_string)
+                .collect();
+
+        Ok(Some(changes))
+    }
+
+    fn check_source(&self, src: &str) -> Result<(), DepToolError> {
+        check_not_option("Git", src)?;
+
+        let git_args = &["ls-remote", "--", src, "HEAD"];
+        self.with_auth(src, || run_git(git_args, Path::new(".")))
+            .map_err(|err| self.redact(err))
+            .context(GitCmdFailed{})?;
+
+        Ok(())
+    }
+
+    fn latest_version(&self, src: &str)
+        -> Result<Option<Version>, DepToolError>
+    {
+        check_not_option("Git", src)?;
+
+        let git_args = &[
+            "ls-remote",
+            "--tags",
+            "--sort=-version:refname",
+            "--",
+            src,
+        ];
+        let output = self.with_auth(src, || run_git(git_args, Path::new(".")))
+            .map_err(|err| self.redact(err))
+            .context(GitCmdFailed{})?;
+
+        // Peeled references (those ending in `^{}`) are skipped because they
+        // name the same tags as the references that they follow.
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let latest_tag =
+            stdout.lines()
+                .filter_map(|line| line.split('\t').nth(1))
+                .filter(|ref_name| !ref_name.ends_with("^{}"))
+                .find_map(|ref_name| ref_name.strip_prefix("refs/tags/"));
+        if let Some(tag) = latest_tag {
+            return Ok(Some(Version(tag.to_string())));
+        }
+
+        // Sources without tags are upgraded to the commit that their default
+        // branch refers to.
+        let git_args = &["ls-remote", "--", src, "HEAD"];
+        let output = self.with_auth(src, || run_git(git_args, Path::new(".")))
+            .map_err(|err| self.redact(err))
+            .context(GitCmdFailed{})?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let head = stdout.lines().next().and_then(|ln| ln.split('\t').next());
+
+        Ok(head.map(|hash| Version(hash.to_string())))
+    }
+
+    fn validate_source(&self, src: &str) -> Result<(), InvalidSource> {
+        validate_git_source(src)
+    }
+}
+
+// `check_not_option` returns an error if `src`, a source that's passed to
+// `program`, starts with `-`. Sources are also passed to programs after `--`,
+// but this check runs before any command, including when source validation is
+// disabled, and gives a clearer error.
+pub fn check_not_option(program: &str, src: &str)
+    -> Result<(), DepToolError>
+{
+    if src.starts_with('-') {
+        return Err(DepToolError::OptionLikeSource{
+            src: src.to_string(),
+            program: program.to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+// `GIT_URL_SCHEMES` contains the URL schemes that Git can fetch from.
+// `git+ssh` and `ssh+git` are also accepted, as aliases of `ssh`.
+const GIT_URL_SCHEMES: &[&str] = &[
+    "file", "ftp", "ftps", "git", "git+ssh", "http", "https", "ssh", "ssh+git",
+];
+
+// `validate_git_source` returns an error if `src` isn't a URL, an scp-like
+// address such as `git@github.com:eZanmoto/dpnd.git`, or a local path, which
+// are the forms of repository address that Git supports.
+fn validate_git_source(src: &str) -> Result<(), InvalidSource> {
+    let invalid = |byte_idx: usize, reason: &str| InvalidSource{
+        char_idx: src[..byte_idx].chars().count(),
+        reason: reason.to_string(),
+    };
+
+    if src.is_empty() {
+        return Err(invalid(0, "it's empty"));
+    }
+    if src.starts_with('-') {
+        return Err(invalid(0, "it starts with '-', like a Git option"));
+    }
+    // Addresses of the form `<transport>::<address>` are handled by Git
+    // remote helpers, which define their own address formats.
+    if let Some((transport, _)) = src.split_once("::") {
+        let is_transport =
+            !transport.is_empty()
+                && transport.chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c));
+        if is_transport {
+            return Ok(());
+        }
+    }
+
+    if let Some((scheme, rest)) = src.split_once("://") {
+        if !GIT_URL_SCHEMES.contains(&scheme) {
+            return Err(invalid(
+                0,
+                &format!("'{}' isn't a URL scheme that Git supports", scheme),
+            ));
+        }
+        // `file` URLs usually don't have a host, as in `file:///repo`.
+        if scheme == "file" {
+            return Ok(());
+        }
+        // Local paths can contain whitespace, but URLs can't.
+        if let Some(i) = src.find(char::is_whitespace) {
+            return Err(invalid(i, "the URL contains whitespace"));
+        }
+
+        let host_start = scheme.len() + "://".len();
+        let (host, path) = rest.split_once('/').unwrap_or((rest, ""));
+        let host_name = host.rsplit('@').next().unwrap_or(host);
+        if host_name.is_empty() || host_name.starts_with(':') {
+            let host_name_start = host_start + host.len() - host_name.len();
+
+            return Err(invalid(
+                host_name_start,
+                "the URL doesn't have a host",
+            ));
+        }
+        if path.is_empty() {
+            return Err(invalid(
+                src.len(),
+                "the URL doesn't have a repository path",
+            ));
+        }
+
+        return Ok(());
+    }
+
+    // Sources such as `https//github.com/...` are local paths to Git, but are
+    // much more likely to be mistyped URLs.
+    for scheme in GIT_URL_SCHEMES {
+        if let Some(rest) = src.strip_prefix(scheme) {
+            if rest.starts_with("//") || rest.starts_with(":/") {
+                return Err(invalid(
+                    scheme.len(),
+                    &format!("'{}' must be followed by '://'", scheme),
+                ));
+            }
+        }
+    }
+
+    // Like Git, we treat sources that have a colon before their first slash
+    // as scp-like addresses.
+    let first_slash = src.find('/').unwrap_or(src.len());
+    if let Some(colon) = src[..first_slash].find(':') {
+        let host = &src[..colon];
+        let host_name = host.rsplit('@').next().unwrap_or(host);
+        if host_name.is_empty() {
+            return Err(invalid(colon, "the address doesn't have a host"));
+        }
+        if colon + 1 == src.len() {
+            return Err(invalid(
+                src.len(),
+                "the address doesn't have a repository path",
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+impl Git {
+    // `resolve_commit` returns the full hash of the commit that `vsn` refers
+    // to in `src`. Git servers only advertise references, so `src` is cloned
+    // to a temporary directory in order to resolve `vsn`.
+    fn resolve_commit(&self, src: &str, vsn: &str)
+        -> Result<String, DepToolError>
+    {
+        let dir = temp_dir::create("dpnd_resolve")
+            .context(CreateTempDirFailed{path: env::temp_dir()})?;
+
+        let clone_args = &["clone", "--bare", "--quiet", "--", src, "."];
+        let result =
+            self.with_auth(src, || run_git(clone_args, &dir))
+                .map_err(|err| self.redact(err))
+                .and_then(|_| {
+                    let rev = format!("{}^{{commit}}", vsn);
+
+                    let git_args =
+                        &["rev-parse", "--verify", "--end-of-options", &rev];
+
+                    run_git(git_args, &dir)
+                })
+                .context(GitCmdFailed{});
+
+        // We ignore errors from removing the temporary directory because they
+        // don't affect the resolved revision.
+        let _ = fs::remove_dir_all(&dir);
+
+        let output = result?;
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    // `with_auth` runs `f` with Git configured, through the environment of
+    // the commands that `f` runs, to send `self.token` as credentials to the
+    // host of `src`. This is only done if `self.token` is defined and `src`
+    // is an HTTPS URL that doesn't already contain credentials. The token
+    // isn't passed as an argument, so that it isn't visible to other users,
+    // and it isn't persisted in cloned repositories.
+    fn with_auth<T, F>(&self, src: &str, f: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        let (Some(token), Some(rest)) =
+            (&self.token, src.strip_prefix("https://"))
+        else {
+            return f();
+        };
+
+        let host_end = rest.find('/').unwrap_or(rest.len());
+        let host = &rest[..host_end];
+        if host.contains('@') {
+            return f();
+        }
+
+        let creds = base64(format!("x-access-token:{}", token).as_bytes());
+        let mut env = CMD_ENV.with(|cmd_env| cmd_env.borrow().clone());
+        add_git_config(
+            &mut env,
+            &format!("http.https://{}/.extraHeader", host),
+            &format!("Authorization: Basic {}", creds),
+        );
+
+        with_cmd_env(&env, f)
+    }
+
+    // `redact` removes `self.token` from the output of `err`.
+    fn redact(&self, err: GitCmdError) -> GitCmdError {
+        let token =
+            match &self.token {
+                Some(token) if !token.is_empty() => {
+                    token
+                },
+                _ => {
+                    return err;
+                },
+            };
+
+        let redact_bytes = |bytes: Vec<u8>| {
+            match String::from_utf8(bytes) {
+                Ok(s) => {
+                    s.replace(token.as_str(), "[REDACTED]").into_bytes()
+                },
+                Err(err) => {
+                    err.into_bytes()
+                },
+            }
+        };
+
+        match err {
+            GitCmdError::StartFailed{..} => {
+                err
+            },
+            GitCmdError::NotSuccess{args, mut output} => {
+                output.stdout = redact_bytes(output.stdout);
+                output.stderr = redact_bytes(output.stderr);
+
+                GitCmdError::NotSuccess{args, output}
+            },
+            GitCmdError::UnexpectedOutput{args, mut output} => {
+                output.stdout = redact_bytes(output.stdout);
+                output.stderr = redact_bytes(output.stderr);
+
+                GitCmdError::UnexpectedOutput{args, output}
+            },
+        }
+    }
+}
+
+// `add_git_config` adds variables to `env` that set the Git configuration
+// `key` to `value`, after any configuration that is already set through the
+// environment of Git commands.
+fn add_git_config(env: &mut Vec<(String, String)>, key: &str, value: &str) {
+    let count =
+        env.iter()
+            .rev()
+            .find(|(name, _)| name == GIT_CONFIG_COUNT_VAR)
+            .map(|(_, count)| count.clone())
+            .or_else(|| env::var(GIT_CONFIG_COUNT_VAR).ok())
+            .and_then(|count| count.parse::<usize>().ok())
+            .unwrap_or(0);
+
+    env.push((format!("GIT_CONFIG_KEY_{}", count), key.to_string()));
+    env.push((format!("GIT_CONFIG_VALUE_{}", count), value.to_string()));
+    env.push((GIT_CONFIG_COUNT_VAR.to_string(), (count + 1).to_string()));
+}
+
+const GIT_CONFIG_COUNT_VAR: &str = "GIT_CONFIG_COUNT";
+
+// `base64` returns the standard Base64 encoding of `bytes`, with padding.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] =
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n =
+            (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let idx = (n >> (18 - 6 * i)) & 0x3f;
+                encoded.push(char::from(ALPHABET[idx as usize]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+// `key_matches` returns `true` if `trusted_key` identifies the key with the
+// fingerprint `fingerprint`. `trusted_key` can be a full fingerprint or a key
+// ID, which is a suffix of a fingerprint.
+fn key_matches(trusted_key: &str, fingerprint: &str) -> bool {
+    let trusted_key = trusted_key.trim_start_matches("0x").to_uppercase();
+
+    !trusted_key.is_empty()
+        && fingerprint.to_uppercase().ends_with(&trusted_key)
+}
+
+// `GitFile` fetches a single file from a Git repository, which is selected by
+// the `path` option of the dependency. Only the commit that the version refers
+// to is fetched, without the contents of any other files, so that small files
+// can be taken from large repositories cheaply. Operations other than `fetch`
+// are delegated to `git`, because the output directory is a Git repository.
+#[derive(Debug)]
+pub struct GitFile<'a> {
+    pub git: &'a Git,
+}
+
+impl DepTool<DepToolError> for GitFile<'_> {
+    fn name(&self) -> String {
+        "git-file".to_string()
+    }
+
+    fn fetch(
+        &self,
+        src: String,
+        Version(vsn): Version,
+        path: Option<&str>,
+        out_dir: &Path,
+        deadline: Option<Instant>,
+    )
+        -> Result<(), FetchError<DepToolError>>
+    {
+        let path = path
+            .expect("`git-file` was used without the `path` option");
+        check_not_option("Git", &src)
+            .context(RetrieveFailed{})?;
+
+        let retrieve_cmds: &[&[&str]] = &[
+            &["init", "--quiet"],
+            &["remote", "add", "--", "origin", &src],
+            &[
+                "fetch",
+                "--depth",
+                "1",
+                "--filter=blob:none",
+                "--",
+                "origin",
+                git_ref(&vsn),
+            ],
+        ];
+        // `--no-cone` allows `path` to refer to a file rather than a
+        // directory.
+        let checkout_cmds: &[&[&str]] = &[
+            &["sparse-checkout", "set", "--no-cone", path],
+            &["checkout", "--quiet", "--detach", "FETCH_HEAD"],
+        ];
+        // The checkout is also authenticated, because it fetches the blobs
+        // that were filtered out of the fetch.
+        self.git.with_auth(&src, || {
+            for git_args in retrieve_cmds {
+                if let Err(err) = run_git_until(git_args, out_dir, deadline) {
+                    let source = DepToolError::GitCmdFailed{
+                        source: self.git.redact(err),
+                    };
                     return Err(FetchError::RetrieveFailed{source});
                 }
-                return Err(FetchError::VersionChangeFailed{source});
             }
+
+            for git_args in checkout_cmds {
+                run_git_until(git_args, out_dir, deadline)
+                    .map_err(|err| self.git.redact(err))
+                    .context(GitCmdFailed{})
+                    .context(VersionChangeFailed{})?;
+            }
+
+            Ok(())
+        })?;
+
+        if !out_dir.join(path).is_file() {
+            let source = DepToolError::PathNotFound{path: path.to_string()};
+            return Err(FetchError::VersionChangeFailed{source});
         }
 
         Ok(())
     }
+
+    fn requires_path(&self) -> bool {
+        true
+    }
+
+    fn resolve(&self, src: &str, version: &Version)
+        -> Result<String, DepToolError>
+    {
+        self.git.resolve(src, version)
+    }
+
+    fn verify(&self, out_dir: &Path, version: &Version)
+        -> Result<(), DepToolError>
+    {
+        self.git.verify(out_dir, version)
+    }
+
+    fn revision(&self, out_dir: &Path) -> Result<String, DepToolError> {
+        self.git.revision(out_dir)
+    }
+
+    fn revision_time(&self, out_dir: &Path) -> Result<u64, DepToolError> {
+        self.git.revision_time(out_dir)
+    }
+
+    fn metadata_paths(&self, out_dir: &Path)
+        -> Result<Vec<String>, DepToolError>
+    {
+        self.git.metadata_paths(out_dir)
+    }
+
+    fn is_modified(&self, out_dir: &Path) -> Result<bool, DepToolError> {
+        self.git.is_modified(out_dir)
+    }
+    fn check_source(&self, src: &str) -> Result<(), DepToolError> {
+        self.git.check_source(src)
+    }
+
+    fn validate_source(&self, src: &str) -> Result<(), InvalidSource> {
+        self.git.validate_source(src)
+    }
+
+    fn latest_version(&self, src: &str)
+        -> Result<Option<Version>, DepToolError>
+    {
+        self.git.latest_version(src)
+    }
+}
+
+// `run_git` runs `git` with `args` in `dir`, and returns the output of the
+// command if it ran successfully.
+pub fn run_git(args: &[&str], dir: &Path) -> Result<Output, GitCmdError> {
+    run_git_until(args, dir, None)
+}
+
+// `run_git_until` is like `run_git`, but `git` is killed if it's still running
+// at `deadline`.
+fn run_git_until(args: &[&str], dir: &Path, deadline: Option<Instant>)
+    -> Result<Output, GitCmdError>
+{
+    let mut cmd = Command::new("git");
+    cmd.args(args).current_dir(dir);
+
+    git_output(args, output_until(&mut cmd, deadline))
+}
+
+// `run_git_with_progress` is like `run_git_until`, but the progress that `git`
+// writes to `stderr` is shown on a single line of the `stderr` of `dpnd`,
+// prefixed with `label`, while `git` runs. The line is cleared when `git`
+// exits.
+fn run_git_with_progress(
+    args: &[&str],
+    dir: &Path,
+    deadline: Option<Instant>,
+    label: &str,
+)
+    -> Result<Output, GitCmdError>
+{
+    let mut cmd = Command::new("git");
+    cmd.args(args).current_dir(dir);
+    let mut progress = ProgressLine{label: label.to_string(), line: vec![]};
+    let on_stderr = Box::new(move |chunk: &[u8]| progress.write(chunk));
+    let maybe_output = output_with_stderr_until(&mut cmd, deadline, on_stderr);
+    ProgressLine::clear();
+
+    git_output(args, maybe_output)
+}
+
+// `git_output` returns the output of `git` when it was run with `args`, if it
+// ran successfully.
+fn git_output(args: &[&str], maybe_output: Result<Output, IoError>)
+    -> Result<Output, GitCmdError>
+{
+    let output = match maybe_output {
+        Ok(output) => {
+            output
+        },
+        Err(err) => {
+            return Err(GitCmdError::StartFailed{
+                source: err,
+                args: strs_to_strings(args),
+            });
+        },
+    };
+
+    if !output.status.success() {
+        return Err(GitCmdError::NotSuccess{
+            args: strs_to_strings(args),
+            output,
+        });
+    }
+
+    Ok(output)
+}
+
+// `PROGRESS_LOCK` is held while a progress line or a block of output is
+// written to `stderr`, so that the output of dependencies that are fetched in
+// parallel isn't interleaved.
+static PROGRESS_LOCK: Mutex<()> = Mutex::new(());
+
+// `ProgressLine` shows the progress lines that `git` writes to `stderr`, such
+// as `Receiving objects:  45% (450/1000)`, on a single line of the `stderr` of
+// `dpnd`, prefixed with `label`. `git` ends updates to a progress line with
+// `\r`, and ends other lines with `\n`; only lines that contain a percentage
+// are shown.
+struct ProgressLine {
+    label: String,
+    line: Vec<u8>,
+}
+
+impl ProgressLine {
+    fn write(&mut self, chunk: &[u8]) {
+        for &b in chunk {
+            if b == b'\r' || b == b'\n' {
+                self.render();
+                self.line.clear();
+            } else {
+                self.line.push(b);
+            }
+        }
+    }
+
+    fn render(&self) {
+        let line = String::from_utf8_lossy(&self.line);
+        if !line.contains('%') {
+            return;
+        }
+
+        let _lock = PROGRESS_LOCK.lock();
+        let mut stderr = io::stderr();
+        let _ = write!(stderr, "\r\x1b[K{}: {}", self.label, line.trim());
+        let _ = stderr.flush();
+    }
+
+    fn clear() {
+        let _lock = PROGRESS_LOCK.lock();
+        let mut stderr = io::stderr();
+        let _ = write!(stderr, "\r\x1b[K");
+        let _ = stderr.flush();
+    }
+}
+
+// `write_output_block` writes `output` to `stderr` as a single block, under a
+// `<dep_name>:` header, so that it isn't interleaved with the output of
+// dependencies that are fetched in parallel and can be told apart from it. A
+// progress line that's being shown on a terminal is cleared first, so that the
+// block starts at the beginning of the line.
+pub fn write_output_block(dep_name: &str, output: &[u8]) {
+    if output.is_empty() {
+        return;
+    }
+
+    let _lock = PROGRESS_LOCK.lock();
+    let mut stderr = io::stderr();
+    if stderr.is_terminal() {
+        let _ = write!(stderr, "\r\x1b[K");
+    }
+    let _ = writeln!(stderr, "{}:", dep_name);
+    let _ = stderr.write_all(output);
+    let _ = stderr.flush();
 }
 
 #[derive(Debug, Snafu)]
 pub enum GitCmdError {
     StartFailed{source: IoError, args: Vec<String>},
     NotSuccess{args: Vec<String>, output: Output},
+    UnexpectedOutput{args: Vec<String>, output: Output},
 }
 
-fn owned_strs_to_strings(strs: Vec<&str>) -> Vec<String> {
-    strs.into_iter()
-        .map(String::from)
+// `POLL_INTERVAL` is how often `output_until` checks whether a command has
+// exited.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+thread_local! {
+    // `CMD_ENV` contains the environment variables that are added to the
+    // commands run by `output_until` on the current thread. It's set by
+    // `with_cmd_env`.
+    static CMD_ENV: RefCell<Vec<(String, String)>> =
+        const { RefCell::new(Vec::new()) };
+}
+
+// `with_cmd_env` runs `f` with `env` added to the environment of the commands
+// that tools run on the current thread, so that the environment of a single
+// dependency can be customised without affecting other dependencies, which
+// may be installed in parallel.
+pub fn with_cmd_env<T, F>(env: &[(String, String)], f: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    let prev_env = CMD_ENV.with(|cmd_env| cmd_env.replace(env.to_vec()));
+    let result = f();
+    CMD_ENV.with(|cmd_env| cmd_env.replace(prev_env));
+
+    result
+}
+
+// `output_until` runs `cmd` and collects its output, like `Command::output`,
+// but if `cmd` is still running at `deadline` then it's killed and an error of
+// kind `TimedOut` is returned. `cmd` is also killed if `dpnd` is interrupted,
+// in which case an error of kind `Interrupted` is returned. The variables set
+// by `with_cmd_env` are added to the environment of `cmd`.
+pub fn output_until(cmd: &mut Command, deadline: Option<Instant>)
+    -> Result<Output, IoError>
+{
+    output_with_stderr_until(cmd, deadline, Box::new(|_| {}))
+}
+
+// `ChunkHandler` is called with each chunk of output that a command writes,
+// as it's written.
+type ChunkHandler = Box<dyn FnMut(&[u8]) + Send>;
+
+// `output_with_stderr_until` is like `output_until`, but `on_stderr` is also
+// called with each chunk of output that `cmd` writes to `stderr`, as it's
+// written.
+fn output_with_stderr_until(
+    cmd: &mut Command,
+    deadline: Option<Instant>,
+    on_stderr: ChunkHandler,
+)
+    -> Result<Output, IoError>
+{
+    CMD_ENV.with(|cmd_env| {
+        cmd.envs(cmd_env.borrow().iter().map(|(k, v)| (k, v)));
+    });
+
+    let mut child =
+        cmd.stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+    // The output of the command is read concurrently so that the command
+    // can't block on writing to a full pipe while we wait for it to exit.
+    let stdout_reader =
+        child.stdout.take()
+            .map(|r| read_to_end_in_thread(r, Box::new(|_| {})));
+    let stderr_reader =
+        child.stderr.take()
+            .map(|r| read_to_end_in_thread(r, on_stderr));
+
+    let status =
+        loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+
+            let stop_reason =
+                if interrupt::interrupted() {
+                    Some((
+                        ErrorKind::Interrupted,
+                        "the command was killed because `dpnd` was \
+                         interrupted",
+                    ))
+                } else if deadline.is_some_and(|t| Instant::now() >= t) {
+                    Some((
+                        ErrorKind::TimedOut,
+                        "the command was killed because it timed out",
+                    ))
+                } else {
+                    None
+                };
+
+            if let Some((kind, msg)) = stop_reason {
+                // We don't wait for the output readers in this case, because
+                // processes started by the command may keep its output open
+                // after it's killed.
+                child.kill()?;
+                child.wait()?;
+
+                return Err(IoError::new(kind, msg));
+            }
+
+            thread::sleep(POLL_INTERVAL);
+        };
+
+    let join = |reader: Option<JoinHandle<Vec<u8>>>| {
+        reader.and_then(|r| r.join().ok()).unwrap_or_default()
+    };
+
+    Ok(Output{
+        status,
+        stdout: join(stdout_reader),
+        stderr: join(stderr_reader),
+    })
+}
+
+// `read_to_end_in_thread` reads `reader` to the end in a new thread, calling
+// `on_chunk` with each chunk that's read, and returns the thread, which
+// returns everything that was read.
+fn read_to_end_in_thread<R>(
+    mut reader: R,
+    mut on_chunk: ChunkHandler,
+)
+    -> JoinHandle<Vec<u8>>
+where
+    R: Read + Send + 'static,
+{
+    thread::spawn(move || {
+        let mut buf = vec![];
+        let mut chunk = [0; 4096];
+        // Read errors are ignored, in the same way that they are by
+        // `Command::output`, because they can only be caused by the command.
+        loop {
+            match reader.read(&mut chunk) {
+                Ok(0) => {
+                    break;
+                },
+                Ok(n) => {
+                    on_chunk(&chunk[..n]);
+                    buf.extend_from_slice(&chunk[..n]);
+                },
+                // Interrupted reads are retried.
+                Err(err) if err.kind() == ErrorKind::Interrupted => {},
+                Err(_) => {
+                    break;
+                },
+            }
+        }
+
+        buf
+    })
+}
+
+// `program_version` returns the first word that starts with a digit on the
+// first line that `program` prints when it's run with `args`, which is how
+// most programs report their version. `None` is returned if `program` can't
+// be run successfully or doesn't print such a word.
+pub fn program_version(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    stdout.lines()
+        .next()?
+        .split_whitespace()
+        .map(|word| word.trim_end_matches(','))
+        .find(|word| word.starts_with(|c: char| c.is_ascii_digit()))
+        .map(ToString::to_string)
+}
+
+pub fn strs_to_strings(strs: &[&str]) -> Vec<String> {
+    strs.iter()
+        .map(|s| (*s).to_string())
         .collect()
 }
+
+// `parse_utc_time` returns the number of seconds since the Unix epoch that
+// `date` represents, where `date` is a UTC timestamp of the form
+// `YYYY-MM-DDTHH:MM:SS.ffffffZ`, as output by `svn info` and `docker image
+// inspect`. The `Z` can also be written as `+00:00` or `+0000`, as it is by
+// the `aws` and `gcloud` CLIs. Fractions of a second are discarded.
+pub fn parse_utc_time(date: &str) -> Option<u64> {
+    let date =
+        date.strip_suffix('Z')
+            .or_else(|| date.strip_suffix("+00:00"))
+            .or_else(|| date.strip_suffix("+0000"))?;
+    let (day, time) = date.split_once('T')?;
+    let time = time.split('.').next()?;
+
+    let day_parts: Vec<u64> =
+        day.split('-')
+            .map(|part| part.parse().ok())
+            .collect::<Option<_>>()?;
+    let time_parts: Vec<u64> =
+        time.split(':')
+            .map(|part| part.parse().ok())
+            .collect::<Option<_>>()?;
+
+    if let (&[y, m, d], &[hours, mins, secs]) =
+        (day_parts.as_slice(), time_parts.as_slice())
+    {
+        let days = days_since_epoch(y, m, d)?;
+
+        Some(days * 86_400 + hours * 3_600 + mins * 60 + secs)
+    } else {
+        None
+    }
+}
+
+// `days_since_epoch` returns the number of days between the Unix epoch and
+// the given date in the proleptic Gregorian calendar. See
+// <http://howardhinnant.github.io/date_algorithms.html#days_from_civil> for
+// details on the algorithm.
+fn days_since_epoch(y: u64, m: u64, d: u64) -> Option<u64> {
+    if y < 1970 || !(1..=12).contains(&m) || !(1..=31).contains(&d) {
+        return None;
+    }
+
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y / 400;
+    let yoe = y - era * 400;
+    let mp = (m + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+    Some(era * 146_097 + doe - 719_468)
+}
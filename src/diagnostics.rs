@@ -0,0 +1,130 @@
+// Copyright 2021 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by an MIT
+// licence that can be found in the LICENCE file.
+
+use std::env;
+use std::io;
+use std::io::IsTerminal;
+
+extern crate regex;
+
+use regex::Regex;
+
+pub const NO_COLOR_VAR: &str = "NO_COLOR";
+
+// `COLOR_CHOICE_NAMES` contains the values accepted by `--color`.
+pub const COLOR_CHOICE_NAMES: &[&str] = &["auto", "always", "never"];
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const BOLD_RED: &str = "\x1b[1;31m";
+const BOLD_YELLOW: &str = "\x1b[1;33m";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorChoice {
+    // `Auto` colors diagnostics if `stderr` is a terminal and `NO_COLOR` isn't
+    // set to a non-empty value, as described at <https://no-color.org/>.
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn parse(name: &str) -> Option<ColorChoice> {
+        match name {
+            "auto" => {
+                Some(ColorChoice::Auto)
+            },
+            "always" => {
+                Some(ColorChoice::Always)
+            },
+            "never" => {
+                Some(ColorChoice::Never)
+            },
+            _ => {
+                None
+            },
+        }
+    }
+}
+
+// `Diagnostics` writes error and warning messages to `stderr`. When colors
+// are enabled, each line is labelled with its category, and the location that
+// the line refers to, if any, is highlighted. Warnings are labelled even when
+// colors are disabled, so that they can be told apart from errors.
+pub struct Diagnostics {
+    color: bool,
+    location: Regex,
+}
+
+impl Diagnostics {
+    pub fn new(choice: ColorChoice) -> Diagnostics {
+        let color =
+            match choice {
+                ColorChoice::Always => {
+                    true
+                },
+                ColorChoice::Never => {
+                    false
+                },
+                ColorChoice::Auto => {
+                    let no_color = env::var_os(NO_COLOR_VAR)
+                        .is_some_and(|v| !v.is_empty());
+
+                    !no_color && io::stderr().is_terminal()
+                },
+            };
+
+        // Locations are paths followed by a line number and an optional
+        // column number, such as `dpnd.txt:3:10`.
+        let location =
+            Regex::new(r"^([^ ]+?:[0-9]+(?::[0-9]+)?): ").unwrap();
+
+        Diagnostics{color, location}
+    }
+
+    pub fn error(&self, msg: &str) {
+        if self.color {
+            eprintln!("{}", self.render(msg, BOLD_RED, "error"));
+        } else {
+            eprintln!("{}", msg);
+        }
+    }
+
+    pub fn warning(&self, msg: &str) {
+        if self.color {
+            eprintln!("{}", self.render(msg, BOLD_YELLOW, "warning"));
+        } else {
+            let lines: Vec<String> =
+                msg.lines().map(|line| format!("warning: {}", line)).collect();
+            eprintln!("{}", lines.join("\n"));
+        }
+    }
+
+    fn render(&self, msg: &str, color: &str, category: &str) -> String {
+        let lines: Vec<String> =
+            msg.lines()
+                .map(|line| {
+                    let label = format!("{}{}{}: ", color, category, RESET);
+                    match self.location.captures(line) {
+                        Some(caps) => {
+                            let loc = &caps[1];
+                            format!(
+                                "{}{}{}{}: {}",
+                                label,
+                                BOLD,
+                                loc,
+                                RESET,
+                                &line[caps[0].len()..],
+                            )
+                        },
+                        None => {
+                            format!("{}{}", label, line)
+                        },
+                    }
+                })
+                .collect();
+
+        lines.join("\n")
+    }
+}
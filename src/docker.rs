@@ -0,0 +1,304 @@
+// Copyright 2021 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by an MIT
+// licence that can be found in the LICENCE file.
+
+use std::fs;
+use std::io::Error as IoError;
+use std::path::Path;
+use std::process::Command;
+use std::process::Output;
+use std::time::Instant;
+
+use dep_tools::DepTool;
+use dep_tools::DepToolError;
+use dep_tools::FetchError;
+use dep_tools::Version;
+use dep_tools::output_until;
+use dep_tools::parse_utc_time;
+use dep_tools::program_version;
+use dep_tools::strs_to_strings;
+
+extern crate snafu;
+
+use snafu::Snafu;
+
+// `IMAGE_FILE_NAME` is the name of the file, in the output directory of a
+// dependency, that records the digest of the image that the dependency was
+// exported from, followed by the time at which the image was created.
+const IMAGE_FILE_NAME: &str = ".dpnd_image";
+
+// `DIGEST_PREFIX` is the prefix of image digests, which select an image by
+// its contents rather than by a tag.
+const DIGEST_PREFIX: &str = "sha256:";
+
+// `EXPORT_CMD` is the command of the containers that images are exported
+// from. Containers are created but never started, so the command only needs
+// to be defined for images that don't define a default command.
+const EXPORT_CMD: &str = "dpnd-export";
+
+// `Docker` fetches dependencies from container images, whose filesystems are
+// exported to the output directories of dependencies. The source of a
+// dependency is the name of an image, such as `alpine` or
+// `ghcr.io/owner/image`, and the version is a tag or a digest.
+#[derive(Debug)]
+pub struct Docker;
+
+impl DepTool<DepToolError> for Docker {
+    fn name(&self) -> String {
+        "docker".to_string()
+    }
+
+    fn tool_version(&self) -> Option<String> {
+        program_version("docker", &["--version"])
+    }
+
+    fn fetch(
+        &self,
+        src: String,
+        Version(vsn): Version,
+        _path: Option<&str>,
+        out_dir: &Path,
+        deadline: Option<Instant>,
+    )
+        -> Result<(), FetchError<DepToolError>>
+    {
+        let image = image_ref(&src, &vsn);
+
+        // We pull the image before creating a container from it so that
+        // failures to retrieve the image are reported as such, instead of as
+        // failures to create a container.
+        if let Err(source) = pull(&image, deadline) {
+            return Err(FetchError::RetrieveFailed{source});
+        }
+
+        let (digest, created) =
+            match inspect(&image) {
+                Ok(details) => {
+                    details
+                },
+                Err(source) => {
+                    return Err(FetchError::RetrieveFailed{source});
+                },
+            };
+
+        if let Err(source) = export(&image, out_dir, deadline) {
+            return Err(FetchError::RetrieveFailed{source});
+        }
+
+        let path = out_dir.join(IMAGE_FILE_NAME);
+        let conts = format!("{}\n{}\n", digest, created);
+        if let Err(source) = fs::write(&path, conts) {
+            let source = DepToolError::WriteFileFailed{source, path};
+            return Err(FetchError::RetrieveFailed{source});
+        }
+
+        Ok(())
+    }
+
+    fn resolve(&self, src: &str, Version(vsn): &Version)
+        -> Result<String, DepToolError>
+    {
+        if vsn.starts_with(DIGEST_PREFIX) {
+            return Ok(vsn.clone());
+        }
+
+        // Registries can't be queried for the digest of a tag without
+        // experimental `docker` subcommands, so we pull the image, which only
+        // downloads layers that aren't already stored locally.
+        let image = image_ref(src, vsn);
+        pull(&image, None)?;
+        let (digest, _) = inspect(&image)?;
+
+        Ok(digest)
+    }
+
+    fn verify(&self, _out_dir: &Path, _version: &Version)
+        -> Result<(), DepToolError>
+    {
+        Err(DepToolError::VerifyUnsupported{tool_name: self.name()})
+    }
+
+    fn revision(&self, out_dir: &Path) -> Result<String, DepToolError> {
+        let (digest, _) = read_image_file(out_dir)?;
+
+        Ok(digest)
+    }
+
+    fn revision_time(&self, out_dir: &Path) -> Result<u64, DepToolError> {
+        let (_, created) = read_image_file(out_dir)?;
+
+        if let Some(t) = parse_utc_time(&created) {
+            Ok(t)
+        } else {
+            let path = out_dir.join(IMAGE_FILE_NAME);
+
+            Err(DepToolError::InvalidRevisionFile{path})
+        }
+    }
+
+    fn metadata_paths(&self, _out_dir: &Path)
+        -> Result<Vec<String>, DepToolError>
+    {
+        Ok(vec![IMAGE_FILE_NAME.to_string()])
+    }
+}
+
+// `image_ref` returns the reference that selects `vsn` of the image named
+// `src`, where `vsn` is a tag or a digest.
+fn image_ref(src: &str, vsn: &str) -> String {
+    if vsn.starts_with(DIGEST_PREFIX) {
+        format!("{}@{}", src, vsn)
+    } else {
+        format!("{}:{}", src, vsn)
+    }
+}
+
+fn pull(image: &str, deadline: Option<Instant>) -> Result<(), DepToolError> {
+    match run_docker_until(&["pull", "--quiet", image], deadline) {
+        Ok(_) => {
+            Ok(())
+        },
+        Err(source) => {
+            Err(DepToolError::DockerCmdFailed{source})
+        },
+    }
+}
+
+// `inspect` returns the digest of the local copy of `image`, and the time at
+// which it was created.
+fn inspect(image: &str) -> Result<(String, String), DepToolError> {
+    let docker_args = &[
+        "image",
+        "inspect",
+        "--format",
+        "{{index .RepoDigests 0}} {{.Created}}",
+        image,
+    ];
+    let output =
+        match run_docker(docker_args) {
+            Ok(output) => {
+                output
+            },
+            Err(source) => {
+                return Err(DepToolError::DockerCmdFailed{source});
+            },
+        };
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let details =
+        stdout.trim()
+            .split_once(' ')
+            .and_then(|(repo_digest, created)| {
+                let (_, digest) = repo_digest.split_once('@')?;
+
+                Some((digest.to_string(), created.to_string()))
+            });
+
+    if let Some(details) = details {
+        Ok(details)
+    } else {
+        let source = DockerCmdError::UnexpectedOutput{
+            args: strs_to_strings(docker_args),
+            output,
+        };
+
+        Err(DepToolError::DockerCmdFailed{source})
+    }
+}
+
+// `export` copies the filesystem of `image` to `out_dir`, using a container
+// that's created for the copy and removed afterwards.
+fn export(image: &str, out_dir: &Path, deadline: Option<Instant>)
+    -> Result<(), DepToolError>
+{
+    let output =
+        match run_docker_until(&["create", image, EXPORT_CMD], deadline) {
+            Ok(output) => {
+                output
+            },
+            Err(source) => {
+                return Err(DepToolError::DockerCmdFailed{source});
+            },
+        };
+    let container = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+    let src_path = format!("{}:/.", container);
+    let out_path = out_dir.to_string_lossy();
+    let copy_result =
+        run_docker_until(&["cp", &src_path, &out_path], deadline);
+
+    // We remove the container even if the copy failed, so that failed
+    // fetches don't leave containers behind.
+    let remove_result = run_docker(&["rm", &container]);
+
+    if let Err(source) = copy_result.and(remove_result) {
+        return Err(DepToolError::DockerCmdFailed{source});
+    }
+
+    Ok(())
+}
+
+// `read_image_file` returns the digest and creation time that were recorded
+// when the dependency in `out_dir` was fetched.
+fn read_image_file(out_dir: &Path) -> Result<(String, String), DepToolError> {
+    let path = out_dir.join(IMAGE_FILE_NAME);
+    let conts =
+        match fs::read_to_string(&path) {
+            Ok(conts) => {
+                conts
+            },
+            Err(source) => {
+                return Err(DepToolError::ReadFileFailed{source, path});
+            },
+        };
+
+    let mut lines = conts.lines();
+    match (lines.next(), lines.next()) {
+        (Some(digest), Some(created)) => {
+            Ok((digest.to_string(), created.to_string()))
+        },
+        _ => {
+            Err(DepToolError::InvalidRevisionFile{path})
+        },
+    }
+}
+
+fn run_docker(args: &[&str]) -> Result<Output, DockerCmdError> {
+    run_docker_until(args, None)
+}
+
+fn run_docker_until(args: &[&str], deadline: Option<Instant>)
+    -> Result<Output, DockerCmdError>
+{
+    let mut cmd = Command::new("docker");
+    cmd.args(args);
+    let maybe_output = output_until(&mut cmd, deadline);
+
+    let output = match maybe_output {
+        Ok(output) => {
+            output
+        },
+        Err(err) => {
+            return Err(DockerCmdError::StartFailed{
+                source: err,
+                args: strs_to_strings(args),
+            });
+        },
+    };
+
+    if !output.status.success() {
+        return Err(DockerCmdError::NotSuccess{
+            args: strs_to_strings(args),
+            output,
+        });
+    }
+
+    Ok(output)
+}
+
+#[derive(Debug, Snafu)]
+pub enum DockerCmdError {
+    StartFailed{source: IoError, args: Vec<String>},
+    NotSuccess{args: Vec<String>, output: Output},
+    UnexpectedOutput{args: Vec<String>, output: Output},
+}
@@ -0,0 +1,197 @@
+// Copyright 2021 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by an MIT
+// licence that can be found in the LICENCE file.
+
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::Error as IoError;
+use std::path::Path;
+use std::process::Command;
+
+use store::STORE_DIR_VAR;
+
+// `PROBE_FILE_NAME` is the name of the file that `check_writable` creates to
+// check whether a directory can be written to.
+const PROBE_FILE_NAME: &str = ".dpnd_doctor_probe";
+
+// `STORE_TMP_EXT` is the extension of the directories that store entries are
+// fetched into before they're moved into place.
+const STORE_TMP_EXT: &str = "tmp";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    Ok,
+    // `Warning` describes a problem that doesn't stop dependencies from being
+    // installed.
+    Warning,
+    Problem,
+}
+
+// `Finding` is the result of a check performed by `dpnd doctor`.
+pub struct Finding {
+    pub severity: Severity,
+    pub msg: String,
+}
+
+impl Finding {
+    pub fn ok(msg: String) -> Finding {
+        Finding{severity: Severity::Ok, msg}
+    }
+
+    pub fn warning(msg: String) -> Finding {
+        Finding{severity: Severity::Warning, msg}
+    }
+
+    pub fn problem(msg: String) -> Finding {
+        Finding{severity: Severity::Problem, msg}
+    }
+}
+
+pub fn render_finding(finding: &Finding) -> String {
+    let label =
+        match finding.severity {
+            Severity::Ok => {
+                "ok"
+            },
+            Severity::Warning => {
+                "warning"
+            },
+            Severity::Problem => {
+                "problem"
+            },
+        };
+
+    format!("{}: {}", label, finding.msg)
+}
+
+// `check_git` checks that `git` can be run, because most dependencies are
+// fetched using Git.
+pub fn check_git() -> Finding {
+    match Command::new("git").arg("--version").output() {
+        Ok(output) if output.status.success() => {
+            let version = String::from_utf8_lossy(&output.stdout);
+
+            Finding::ok(format!("Found {}", version.trim()))
+        },
+        Ok(output) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+
+            Finding::problem(format!(
+                "`git --version` failed ({}): {}",
+                output.status,
+                stderr.trim(),
+            ))
+        },
+        Err(err) => {
+            Finding::problem(format!(
+                "Couldn't run `git` ({}); install Git and make sure that it's \
+                 in a directory in `PATH`",
+                err,
+            ))
+        },
+    }
+}
+
+// `check_store` checks that the store at `store_dir`, which linked
+// dependencies are fetched into, can be used.
+pub fn check_store(store_dir: Option<&Path>) -> Finding {
+    let Some(store_dir) = store_dir else {
+        return Finding::warning(format!(
+            "The location of the store isn't defined, so linked \
+             dependencies can't be installed; define `{}` or `HOME`",
+            STORE_DIR_VAR,
+        ));
+    };
+
+    if !store_dir.exists() {
+        return Finding::ok(format!(
+            "The store at '{}' will be created when a linked dependency is \
+             installed",
+            store_dir.display(),
+        ));
+    } else if !store_dir.is_dir() {
+        return Finding::problem(format!(
+            "The store at '{}' isn't a directory; remove it, or define `{}` \
+             to use a different store",
+            store_dir.display(),
+            STORE_DIR_VAR,
+        ));
+    }
+
+    if let Err(err) = check_writable(store_dir) {
+        return Finding::problem(format!(
+            "Can't write to the store at '{}' ({}); check its permissions, or \
+             define `{}` to use a different store",
+            store_dir.display(),
+            err,
+            STORE_DIR_VAR,
+        ));
+    }
+
+    let incomplete_entries =
+        match count_incomplete_store_entries(store_dir) {
+            Ok(n) => {
+                n
+            },
+            Err(err) => {
+                return Finding::problem(format!(
+                    "Couldn't list the entries of the store at '{}': {}",
+                    store_dir.display(),
+                    err,
+                ));
+            },
+        };
+
+    if incomplete_entries > 0 {
+        return Finding::warning(format!(
+            "The store at '{}' contains {} incomplete entries, left by \
+             interrupted fetches; they're replaced when their dependencies \
+             are fetched again, and can also be removed",
+            store_dir.display(),
+            incomplete_entries,
+        ));
+    }
+
+    Finding::ok(format!(
+        "The store at '{}' can be written to",
+        store_dir.display(),
+    ))
+}
+
+fn count_incomplete_store_entries(store_dir: &Path) -> Result<usize, IoError> {
+    let mut n = 0;
+    for entry in fs::read_dir(store_dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) == Some(STORE_TMP_EXT)
+        {
+            n += 1;
+        }
+    }
+
+    Ok(n)
+}
+
+// `check_writable` returns an error if a file can't be created in `path`, or,
+// if `path` doesn't exist, in its closest existing ancestor, which is where
+// `path` would be created.
+pub fn check_writable(path: &Path) -> Result<(), IoError> {
+    let mut dir = path;
+    while !dir.exists() {
+        match dir.parent() {
+            Some(parent) => {
+                dir = parent;
+            },
+            None => {
+                break;
+            },
+        }
+    }
+
+    let probe_path = dir.join(PROBE_FILE_NAME);
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe_path)?;
+
+    fs::remove_file(&probe_path)
+}
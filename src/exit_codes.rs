@@ -0,0 +1,171 @@
+// Copyright 2021 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by an MIT
+// licence that can be found in the LICENCE file.
+
+use dep_tools::DepToolError;
+use install::InstallDepsError;
+use install::InstallError;
+use install::InstallProjDepsError;
+use interrupt::INTERRUPTED_EXIT_CODE;
+
+// These are the exit codes of `dpnd`, which allow scripts to distinguish
+// between categories of failure. They're documented in `README.md`, and must
+// not be changed once they're released.
+//
+// `FAILURE` is used for failures that don't belong to a more specific
+// category, such as failures to read or write files.
+pub const FAILURE: i32 = 1;
+pub const NO_DEPS_FILE: i32 = 2;
+// `INVALID_DEPS_FILE` is used when a dependency file can't be parsed, or it
+// defines dependencies that can't be installed together, or it doesn't define
+// a dependency that was named on the command line.
+pub const INVALID_DEPS_FILE: i32 = 3;
+// `FETCH_FAILED` is used when a dependency can't be retrieved from its
+// source.
+pub const FETCH_FAILED: i32 = 4;
+// `DEPS_OUT_OF_DATE` is used when `--check-only` finds dependencies that
+// aren't up to date.
+pub const DEPS_OUT_OF_DATE: i32 = 5;
+// `DEPS_MODIFIED` is used when installed dependencies that would be changed
+// have been modified, and `--force` wasn't used.
+pub const DEPS_MODIFIED: i32 = 6;
+// `DEPS_FILE_NOT_FORMATTED` is used when `dpnd fmt --check` finds that the
+// dependency file isn't formatted.
+pub const DEPS_FILE_NOT_FORMATTED: i32 = 7;
+
+// `install_error_code` returns the exit code that `dpnd` exits with when it
+// fails with `err`.
+pub fn install_error_code(err: &InstallError<DepToolError>) -> i32 {
+    match err {
+        InstallError::NoDepsFileFound => {
+            NO_DEPS_FILE
+        },
+        InstallError::ConvDepsFileUtf8Failed{..} |
+        InstallError::ParseDepsConfFailed{..} |
+        InstallError::ConvOldDepsFileUtf8Failed{..} |
+        InstallError::ParseOldDepsConfFailed{..} |
+        InstallError::ManagedPathsOverlap{..} |
+        InstallError::NestedOutputDirOutsideDep{..} |
+        InstallError::VersionsConflict{..} |
+        InstallError::DepNotFound{..} |
+        InstallError::UndefinedDep{..} => {
+            INVALID_DEPS_FILE
+        },
+        InstallError::ResolveDepFailed{..} |
+        InstallError::GetLatestVersionFailed{..} |
+        InstallError::ListChangesFailed{..} |
+        InstallError::CheckSourceFailed{..} => {
+            FETCH_FAILED
+        },
+        InstallError::DepsFileNotFormatted{..} => {
+            DEPS_FILE_NOT_FORMATTED
+        },
+        // The code of the first error is used, so that the code is the same
+        // as if only the first dependency had failed.
+        InstallError::DepsFailed{errs} => {
+            errs.first().map_or(FAILURE, install_error_code)
+        },
+        InstallError::InstallProjDepsFailed{source, ..} => {
+            install_proj_deps_error_code(source)
+        },
+        InstallError::ReadDepsFileFailed{..} |
+        InstallError::ReadNestedDepsFileFailed{..} |
+        InstallError::ReadOldDepsFileFailed{..} |
+        InstallError::CanonicalizePathFailed{..} |
+        InstallError::OutputDirOverlapsSection{..} |
+        InstallError::DepNotInstalled{..} |
+        InstallError::NoLatestVersion{..} |
+        InstallError::WriteUpgradedDepsFileFailed{..} |
+        InstallError::RestoreUpgradedDepsFileFailed{..} |
+        InstallError::DepDefnNotFound{..} |
+        InstallError::PinnedDepOutdated{..} |
+        InstallError::PinTrackedDep{..} |
+        InstallError::NoInstalledRevision{..} |
+        InstallError::ReadIncludedDepsFileFailed{..} |
+        InstallError::WritePinnedDepsFileFailed{..} |
+        InstallError::WritePinnedStateFileFailed{..} |
+        InstallError::WriteSummaryFailed{..} |
+        InstallError::GetAnnotationRevisionFailed{..} |
+        InstallError::WriteAnnotatedDepsFileFailed{..} |
+        InstallError::WriteFormattedDepsFileFailed{..} |
+        InstallError::WriteRepairedStateFileFailed{..} |
+        InstallError::GetInstalledRevisionFailed{..} |
+        InstallError::ReadIgnoreFileFailed{..} |
+        InstallError::OutputDirNotWritable{..} |
+        InstallError::InstalledDepMissing{..} |
+        InstallError::DepInMultipleStateFiles{..} => {
+            FAILURE
+        },
+    }
+}
+
+fn install_proj_deps_error_code(err: &InstallProjDepsError<DepToolError>)
+    -> i32
+{
+    match err {
+        InstallProjDepsError::InstallDepsFailed{source} => {
+            install_deps_error_code(source)
+        },
+        InstallProjDepsError::ReadStateFileFailed{..} |
+        InstallProjDepsError::ConvStateFileUtf8Failed{..} |
+        InstallProjDepsError::ParseStateFileFailed{..} |
+        InstallProjDepsError::CreateMainOutputDirFailed{..} |
+        InstallProjDepsError::CreateStateDirFailed{..} |
+        InstallProjDepsError::MoveLegacyStateFileFailed{..} => {
+            FAILURE
+        },
+    }
+}
+
+fn install_deps_error_code(err: &InstallDepsError<DepToolError>) -> i32 {
+    match err {
+        InstallDepsError::FetchFailed{..} |
+        InstallDepsError::FetchTimedOut{..} |
+        InstallDepsError::FastForwardFailed{..} => {
+            FETCH_FAILED
+        },
+        InstallDepsError::DepsOutOfDate{..} |
+        InstallDepsError::FrozenDepsChanged{..} => {
+            DEPS_OUT_OF_DATE
+        },
+        InstallDepsError::DepsModified{..} => {
+            DEPS_MODIFIED
+        },
+        InstallDepsError::UnknownNeededDep{..} |
+        InstallDepsError::DepNeedsCycle{..} => {
+            INVALID_DEPS_FILE
+        },
+        InstallDepsError::Interrupted => {
+            INTERRUPTED_EXIT_CODE
+        },
+        InstallDepsError::WriteInitialCurDepsFailed{..} |
+        InstallDepsError::RemoveOldDepOutputDirFailed{..} |
+        InstallDepsError::WriteCurDepsAfterRemoveFailed{..} |
+        InstallDepsError::RenameDepOutputDirFailed{..} |
+        InstallDepsError::WriteCurDepsAfterRenameFailed{..} |
+        InstallDepsError::CreateDepOutputDirFailed{..} |
+        InstallDepsError::VendorLinkedDepFailed{..} |
+        InstallDepsError::NoStoreDir{..} |
+        InstallDepsError::CreateStoreDirFailed{..} |
+        InstallDepsError::LockStoreEntryFailed{..} |
+        InstallDepsError::ReadStoreDirFailed{..} |
+        InstallDepsError::CreateStoreTempDirFailed{..} |
+        InstallDepsError::MoveStoreEntryFailed{..} |
+        InstallDepsError::LinkDepOutputDirFailed{..} |
+        InstallDepsError::CopyDepFromStoreFailed{..} |
+        InstallDepsError::WriteCurDepsAfterInstallFailed{..} |
+        InstallDepsError::ReadVendorFileFailed{..} |
+        InstallDepsError::VendorDepFailed{..} |
+        InstallDepsError::GetRevisionTimeFailed{..} |
+        InstallDepsError::NormalizeMtimesFailed{..} |
+        InstallDepsError::VerifySignatureFailed{..} |
+        InstallDepsError::CheckModifiedFailed{..} |
+        InstallDepsError::GetTrackedRevisionFailed{..} |
+        InstallDepsError::WriteCurDepsAfterFastForwardFailed{..} |
+        InstallDepsError::MakeDepWritableFailed{..} |
+        InstallDepsError::MakeDepReadonlyFailed{..} |
+        InstallDepsError::SetPermissionsFailed{..} => {
+            FAILURE
+        },
+    }
+}
@@ -0,0 +1,264 @@
+// Copyright 2021 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by an MIT
+// licence that can be found in the LICENCE file.
+
+use std::fs;
+use std::io::Error as IoError;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Output;
+use std::time::Instant;
+use std::time::UNIX_EPOCH;
+
+use dep_tools::DepTool;
+use dep_tools::DepToolError;
+use dep_tools::FetchError;
+use dep_tools::Version;
+use dep_tools::check_not_option;
+use dep_tools::output_until;
+use dep_tools::program_version;
+use dep_tools::strs_to_strings;
+use sha256;
+
+extern crate snafu;
+
+use snafu::Snafu;
+
+// `USER_AGENT` identifies `dpnd` to the servers that files are downloaded
+// from.
+const USER_AGENT: &str = concat!("dpnd/", env!("CARGO_PKG_VERSION"));
+
+// `File` fetches single files over HTTP(S) (or any other protocol supported by
+// `curl`). The source of a dependency is the URL of the file, which is
+// downloaded into the output directory of the dependency using the last
+// segment of the URL path as its name. The version of a dependency is the
+// SHA-256 checksum of the file, which the downloaded file must match.
+#[derive(Debug)]
+pub struct File;
+
+impl DepTool<DepToolError> for File {
+    fn name(&self) -> String {
+        "file".to_string()
+    }
+
+    fn tool_version(&self) -> Option<String> {
+        program_version("curl", &["--version"])
+    }
+
+    fn fetch(
+        &self,
+        src: String,
+        Version(vsn): Version,
+        _path: Option<&str>,
+        out_dir: &Path,
+        deadline: Option<Instant>,
+    )
+        -> Result<(), FetchError<DepToolError>>
+    {
+        if let Err(source) = check_not_option("curl", &src) {
+            return Err(FetchError::RetrieveFailed{source});
+        }
+
+        let Some(file_name) = url_file_name(&src) else {
+            let source = DepToolError::NoFileNameInUrl{url: src};
+            return Err(FetchError::RetrieveFailed{source});
+        };
+
+        // `--remote-time` sets the modification time of the downloaded file
+        // to the time that the server reports, which is used as the revision
+        // time of the dependency.
+        let curl_args = &[
+            "--fail",
+            "--silent",
+            "--show-error",
+            "--location",
+            "--remote-time",
+            "--user-agent",
+            USER_AGENT,
+            "--output",
+            file_name,
+            "--",
+            &src,
+        ];
+        if let Err(err) = run_curl(curl_args, out_dir, deadline) {
+            let source = DepToolError::CurlCmdFailed{source: err};
+            return Err(FetchError::RetrieveFailed{source});
+        }
+
+        let path = out_dir.join(file_name);
+        let checksum =
+            match checksum_file(&path) {
+                Ok(checksum) => {
+                    checksum
+                },
+                Err(source) => {
+                    return Err(FetchError::RetrieveFailed{source});
+                },
+            };
+
+        if !checksum.eq_ignore_ascii_case(&vsn) {
+            // We remove the downloaded file so that it can't be used by
+            // mistake.
+            let _ = fs::remove_file(&path);
+
+            let source = DepToolError::ChecksumMismatch{
+                file_name: file_name.to_string(),
+                expected: vsn,
+                actual: checksum,
+            };
+
+            return Err(FetchError::VersionChangeFailed{source});
+        }
+
+        Ok(())
+    }
+
+    fn resolve(&self, _src: &str, Version(vsn): &Version)
+        -> Result<String, DepToolError>
+    {
+        Ok(vsn.to_ascii_lowercase())
+    }
+
+    fn verify(&self, _out_dir: &Path, _version: &Version)
+        -> Result<(), DepToolError>
+    {
+        Err(DepToolError::VerifyUnsupported{tool_name: self.name()})
+    }
+
+    fn revision(&self, out_dir: &Path) -> Result<String, DepToolError> {
+        checksum_file(&downloaded_file(out_dir)?)
+    }
+
+    fn revision_time(&self, out_dir: &Path) -> Result<u64, DepToolError> {
+        let path = downloaded_file(out_dir)?;
+
+        let maybe_mtime =
+            fs::metadata(&path)
+                .and_then(|metadata| metadata.modified());
+
+        match maybe_mtime {
+            Ok(mtime) => {
+                // Files older than the Unix epoch are treated as if they were
+                // modified at the epoch.
+                Ok(
+                    mtime.duration_since(UNIX_EPOCH)
+                        .map_or(0, |d| d.as_secs())
+                )
+            },
+            Err(source) => {
+                Err(DepToolError::ReadFileFailed{source, path})
+            },
+        }
+    }
+
+    fn metadata_paths(&self, _out_dir: &Path)
+        -> Result<Vec<String>, DepToolError>
+    {
+        Ok(vec![])
+    }
+}
+
+// `url_file_name` returns the last segment of the path of `url`, or `None` if
+// the path of `url` ends with a `/`.
+fn url_file_name(url: &str) -> Option<&str> {
+    let end = url.find(['?', '#']).unwrap_or(url.len());
+    let path = &url[..end];
+
+    match path.rsplit_once('/') {
+        Some((_, file_name)) if !file_name.is_empty() => Some(file_name),
+        _ => None,
+    }
+}
+
+// `downloaded_file` returns the path of the file that was downloaded into
+// `out_dir`, which must be the only file in `out_dir`.
+fn downloaded_file(out_dir: &Path) -> Result<PathBuf, DepToolError> {
+    let entries =
+        match fs::read_dir(out_dir) {
+            Ok(entries) => {
+                entries
+            },
+            Err(source) => {
+                return Err(DepToolError::ReadFileFailed{
+                    source,
+                    path: out_dir.to_path_buf(),
+                });
+            },
+        };
+
+    let mut paths = vec![];
+    for entry in entries {
+        match entry {
+            Ok(entry) => {
+                paths.push(entry.path());
+            },
+            Err(source) => {
+                return Err(DepToolError::ReadFileFailed{
+                    source,
+                    path: out_dir.to_path_buf(),
+                });
+            },
+        }
+    }
+
+    match paths.len() {
+        0 => {
+            Err(DepToolError::NoDownloadedFile{path: out_dir.to_path_buf()})
+        },
+        1 => {
+            Ok(paths.remove(0))
+        },
+        _ => {
+            Err(DepToolError::MultipleDownloadedFiles{
+                path: out_dir.to_path_buf(),
+            })
+        },
+    }
+}
+
+fn checksum_file(path: &Path) -> Result<String, DepToolError> {
+    match fs::read(path) {
+        Ok(conts) => {
+            Ok(sha256::hex_digest(&conts))
+        },
+        Err(source) => {
+            Err(DepToolError::ReadFileFailed{source, path: path.to_path_buf()})
+        },
+    }
+}
+
+fn run_curl(args: &[&str], dir: &Path, deadline: Option<Instant>)
+    -> Result<Output, CurlCmdError>
+{
+    let mut cmd = Command::new("curl");
+    cmd.args(args).current_dir(dir);
+    let maybe_output = output_until(&mut cmd, deadline);
+
+    let output = match maybe_output {
+        Ok(output) => {
+            output
+        },
+        Err(err) => {
+            return Err(CurlCmdError::StartFailed{
+                source: err,
+                args: strs_to_strings(args),
+            });
+        },
+    };
+
+    if !output.status.success() {
+        return Err(CurlCmdError::NotSuccess{
+            args: strs_to_strings(args),
+            output,
+        });
+    }
+
+    Ok(output)
+}
+
+#[derive(Debug, Snafu)]
+pub enum CurlCmdError {
+    StartFailed{source: IoError, args: Vec<String>},
+    NotSuccess{args: Vec<String>, output: Output},
+}
@@ -0,0 +1,84 @@
+// Copyright 2021 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by an MIT
+// licence that can be found in the LICENCE file.
+
+use std::fmt::Write;
+use std::path::Path;
+
+use install::DepPaths;
+
+// `CHECK_CMD` installs dependencies if they aren't up to date. The output of
+// the check is discarded because `dpnd install` reports any problems.
+const CHECK_CMD: &str =
+    "dpnd install --check-only >/dev/null 2>&1 || dpnd install";
+
+// `render_direnv_hook` renders a snippet that can be evaluated in an `.envrc`
+// file, using `eval "$(dpnd hook direnv)"`, so that dependencies are checked
+// whenever the directory is entered or the dependency file changes.
+pub fn render_direnv_hook(paths: &DepPaths) -> String {
+    let output_dir = paths.proj_dir.join(&paths.output_dir);
+
+    let mut hook = format!(
+        "watch_file {}\n{}\nexport DPND_DEPS_DIR={}\n",
+        quote_sh(&paths.deps_file_path),
+        CHECK_CMD,
+        quote_sh(&output_dir),
+    );
+    for (dep_name, dep_dir) in &paths.dep_dirs {
+        let _ = writeln!(
+            hook,
+            "export {}={}",
+            dep_env_var_name(dep_name),
+            quote_sh(&paths.proj_dir.join(dep_dir)),
+        );
+    }
+
+    hook
+}
+
+// `render_mise_hook` renders a snippet that can be added to a `mise.toml` file
+// in the project directory. Unlike the `direnv` hook, the exported paths are
+// fixed when the snippet is rendered, so the snippet must be rendered again
+// when dependencies are added or removed.
+pub fn render_mise_hook(paths: &DepPaths) -> String {
+    let output_dir = format!(
+        "{{{{config_root}}}}/{}",
+        escape_toml(&paths.output_dir.to_string_lossy()),
+    );
+
+    let mut hook = format!(
+        "[env]\nDPND_DEPS_DIR = \"{}\"\n",
+        output_dir,
+    );
+    for (dep_name, dep_dir) in &paths.dep_dirs {
+        let _ = writeln!(
+            hook,
+            "{} = \"{{{{config_root}}}}/{}\"",
+            dep_env_var_name(dep_name),
+            escape_toml(&dep_dir.to_string_lossy()),
+        );
+    }
+    let _ = write!(hook, "\n[hooks]\nenter = \"{}\"\n", CHECK_CMD);
+
+    hook
+}
+
+// `dep_env_var_name` returns the name of the environment variable that the
+// output directory of `dep_name` is exported as. Dependency names can contain
+// periods and hyphens, which aren't valid in environment variable names, so
+// these are replaced with underscores.
+fn dep_env_var_name(dep_name: &str) -> String {
+    format!(
+        "DPND_DEP_{}",
+        dep_name.to_ascii_uppercase().replace(['.', '-'], "_"),
+    )
+}
+
+fn quote_sh(path: &Path) -> String {
+    format!("'{}'", path.to_string_lossy().replace('\'', "'\\''"))
+}
+
+// `escape_toml` escapes `s` for use in a TOML basic string.
+fn escape_toml(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
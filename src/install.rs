@@ -2,26 +2,59 @@
 // Use of this source code is governed by an MIT
 // licence that can be found in the LICENCE file.
 
+use std::cmp::Reverse;
 use std::collections::HashMap;
+use std::collections::HashSet;
+use std::env;
 use std::error::Error;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fmt::Write as FmtWrite;
 use std::fs;
+use std::fs::File;
 use std::fs::OpenOptions;
 use std::io::Error as IoError;
 use std::io::ErrorKind;
 use std::io::Write;
+use std::iter;
 use std::iter::Enumerate;
+use std::mem;
+use std::ops::Range;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::fs::symlink;
 use std::path::Path;
 use std::path::PathBuf;
+use std::process::Command;
 use std::str;
 use std::str::Lines;
 use std::string::FromUtf8Error;
+use std::sync::Mutex;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
 
+use dep_tools;
 use dep_tools::DepTool;
+use dep_tools::DepToolError;
 use dep_tools::FetchError;
 use dep_tools::GitCmdError;
+use dep_tools::InvalidSource;
 use dep_tools::Version;
+use doctor;
+use interrupt;
+use logger::Level;
+use logger::Logger;
+use prompt::Prompter;
+use sha256;
+use store;
+use temp_dir;
 
 use regex::Regex;
+use snafu::OptionExt;
 use snafu::ResultExt;
 use snafu::Snafu;
 
@@ -30,494 +63,6379 @@ pub struct Installer<'a, E> {
     pub state_file_name: String,
     pub bad_dep_name_chars: Regex,
     pub tools: HashMap<String, &'a (dyn DepTool<E> + 'a)>,
+    pub logger: &'a dyn Logger,
+    // `validate_sources` indicates whether the sources of dependencies in
+    // dependency files should be checked by their tools when they're parsed,
+    // so that mistyped sources are reported before they're fetched.
+    pub validate_sources: bool,
 }
 
-impl<'a> Installer<'a, GitCmdError> {
-    pub fn install(&self, cwd: &Path, recurse: bool)
-        -> Result<(), InstallError<GitCmdError>>
+pub struct InstallOptions<'a> {
+    // `recurse` indicates whether dependencies defined in the dependency files
+    // of installed dependencies should also be installed.
+    pub recurse: bool,
+    // `mode` selects whether dependencies are installed or only checked.
+    pub mode: InstallMode,
+    // `changed_since`, if defined, restricts installation to dependencies
+    // whose definitions differ from those in the dependency file at the Git
+    // revision `changed_since`.
+    pub changed_since: Option<&'a str>,
+    // `group_filter`, if defined, restricts installation and removal to
+    // dependencies in the selected groups.
+    pub group_filter: Option<GroupFilter>,
+    // `dep_names`, if defined, restricts installation and removal to the
+    // named dependencies of the root dependency file.
+    pub dep_names: Option<Vec<String>>,
+    // `deps_spec`, if defined, is used as the contents of the root dependency
+    // file, instead of reading the dependency file of the project, in which
+    // case the current directory is used as the project directory.
+    pub deps_spec: Option<&'a [u8]>,
+    // `force` indicates whether dependencies that have been modified locally
+    // should be removed or replaced. If it's `false` then an error is
+    // returned instead, and no dependencies are changed.
+    pub force: bool,
+    // `prompter`, if defined, is asked to confirm each removal of a
+    // dependency, each downgrade of a dependency, and each change to a
+    // dependency that has been modified locally, unless `force` is set.
+    // Actions that aren't confirmed are skipped.
+    pub prompter: Option<&'a dyn Prompter>,
+    // `conflicts` selects how dependencies from different dependency files
+    // that have the same source but different versions are handled when
+    // `recurse` is set.
+    pub conflicts: ConflictPolicy,
+    // `warn`, if defined, is called with problems that don't stop
+    // installation, such as version conflicts when `conflicts` is `Warn`.
+    pub warn: Option<&'a (dyn Fn(InstallError<DepToolError>) + Sync)>,
+    pub fetch: FetchOptions<'a>,
+    pub output: OutputOptions,
+    pub state: StateOptions,
+}
+
+// `InstallMode` selects whether dependencies are installed or only checked.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InstallMode {
+    // `Install` installs the dependencies that aren't up to date.
+    Install,
+    // `CheckOnly` returns an error if any dependency isn't up to date, and
+    // doesn't modify any files.
+    CheckOnly,
+    // `Frozen` strengthens `CheckOnly`: the error that's returned if any
+    // dependency isn't up to date describes each change that would be made,
+    // and no summary is written, so that nothing outside of the process is
+    // changed.
+    Frozen,
+}
+
+impl InstallMode {
+    // `checks_only` returns whether dependencies are only checked, in which
+    // case no files are modified.
+    pub fn checks_only(self) -> bool {
+        self != InstallMode::Install
+    }
+}
+
+// `FetchOptions` controls how dependencies are fetched.
+#[derive(Clone)]
+pub struct FetchOptions<'a> {
+    // `timeout`, if defined, is the maximum time that fetching a single
+    // dependency can take, after which the fetch is stopped.
+    pub timeout: Option<Duration>,
+    // `jobs` is the maximum number of nested projects that are installed at
+    // the same time when `recurse` is set. Projects are installed one at a
+    // time if `prompter` is defined.
+    pub jobs: usize,
+    // `keep_going` indicates whether the other dependencies should still be
+    // installed if a dependency can't be fetched, in which case the errors
+    // for all of the dependencies that couldn't be fetched are returned once
+    // the other dependencies have been installed.
+    pub keep_going: bool,
+    // `store_dir`, if defined, is the directory that linked dependencies are
+    // fetched into, so that they can be shared between projects.
+    pub store_dir: Option<PathBuf>,
+    // `hook`, if defined, is called before and after each dependency is
+    // fetched, so that fetches can be recorded or reported.
+    pub hook: Option<&'a (dyn Fn(&FetchEvent) + Sync)>,
+}
+
+impl Default for FetchOptions<'_> {
+    fn default() -> Self {
+        FetchOptions{
+            timeout: None,
+            jobs: 1,
+            keep_going: false,
+            store_dir: None,
+            hook: None,
+        }
+    }
+}
+
+// `LinkMode` selects how dependencies that are fetched into the store are
+// installed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LinkMode {
+    // `Symlink` installs dependencies as symlinks to entries in the store, as
+    // if they had the `link=true` option.
+    Symlink,
+    // `Copy` installs dependencies as copies of entries in the store, as if
+    // they had the `link=copy` option.
+    Copy,
+}
+
+// `OutputOptions` controls the files that installed dependencies are written
+// to, and how they're written.
+#[derive(Clone, Debug, Default)]
+pub struct OutputOptions {
+    // `output_dir`, if defined, overrides the output directory of the
+    // dependencies of the root dependency file that aren't in a section. The
+    // state file of those dependencies is also written to `output_dir`.
+    pub output_dir: Option<PathBuf>,
+    // `link`, if defined, overrides the `link` option of all dependencies.
+    pub link: Option<LinkMode>,
+    // `vendor` indicates whether tool metadata should be removed from
+    // installed dependencies, in which case the provenance of each dependency
+    // is recorded in a vendor file in its output directory.
+    pub vendor: bool,
+    // `normalize_mtimes` indicates whether the modification times of the
+    // files of installed dependencies should be set to a fixed time, so that
+    // installed dependencies are reproducible.
+    pub normalize_mtimes: bool,
+    // `source_date_epoch`, if defined, is the time, in seconds since the Unix
+    // epoch, that modification times are normalized to. If it's not defined
+    // then the modification times of each dependency are normalized to the
+    // time of the installed revision. It's also used as the installation
+    // time that's recorded for dependencies.
+    pub source_date_epoch: Option<u64>,
+    // `readonly` indicates whether the write permissions of installed
+    // dependencies should be removed, so that they can't be edited by
+    // mistake. Dependencies are made writable again before they're changed
+    // or removed.
+    pub readonly: bool,
+}
+
+// `StateOptions` controls how the installed dependencies are recorded.
+#[derive(Clone, Debug, Default)]
+pub struct StateOptions {
+    // `no_state_file` indicates whether the installed dependencies should be
+    // derived from the output directories of the dependencies in the
+    // dependency file, instead of being read from the state file, in which
+    // case no state file is written. Directories of dependencies that aren't
+    // in the dependency file aren't removed, because they can't be told
+    // apart from other files in the output directory.
+    pub no_state_file: bool,
+    // `check_integrity` indicates whether the revision in the output
+    // directory of each installed dependency should be compared with the
+    // revision that the state file records for it, so that dependencies that
+    // were changed outside of `dpnd` are installed again.
+    pub check_integrity: bool,
+    // `annotate` indicates whether comments that record the installed
+    // revision of each dependency should be maintained in the root
+    // dependency file.
+    pub annotate: bool,
+    // `summary_path`, if defined, is the path of a Markdown file that a
+    // summary of the dependencies defined in the root dependency file is
+    // appended to.
+    pub summary_path: Option<PathBuf>,
+}
+
+// `STDIN_DEPS_FILE_PATH` is the path that's used to refer to a dependency
+// file that's read from standard input, such as in error messages. It's
+// joined to the current directory, so that the files that such a dependency
+// file includes are relative to the current directory.
+const STDIN_DEPS_FILE_PATH: &str = "<stdin>";
+
+// `CONFLICT_POLICY_NAMES` contains the values accepted by `--conflicts`.
+pub const CONFLICT_POLICY_NAMES: &[&str] = &["warn", "error", "unify"];
+
+// `ConflictPolicy` selects how version conflicts between dependency files are
+// handled. `Warn` reports each conflict and installs both versions, `Error`
+// stops installation at the first conflict, and `Unify` installs the version
+// that was defined first, so that versions in the root dependency file take
+// precedence.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConflictPolicy {
+    Warn,
+    Error,
+    Unify,
+}
+
+impl ConflictPolicy {
+    pub fn parse(name: &str) -> Option<ConflictPolicy> {
+        match name {
+            "warn" => {
+                Some(ConflictPolicy::Warn)
+            },
+            "error" => {
+                Some(ConflictPolicy::Error)
+            },
+            "unify" => {
+                Some(ConflictPolicy::Unify)
+            },
+            _ => {
+                None
+            },
+        }
+    }
+}
+
+// `GroupFilter` selects dependencies by the value of their `group` option.
+// Dependencies without a group are only selected by `Skip`.
+#[derive(Clone)]
+pub enum GroupFilter {
+    Only(Vec<String>),
+    Skip(Vec<String>),
+}
+
+impl GroupFilter {
+    fn selects(&self, group: Option<&String>) -> bool {
+        match (self, group) {
+            (GroupFilter::Only(groups), Some(group)) => {
+                groups.contains(group)
+            },
+            (GroupFilter::Only(_), None) => {
+                false
+            },
+            (GroupFilter::Skip(groups), Some(group)) => {
+                !groups.contains(group)
+            },
+            (GroupFilter::Skip(_), None) => {
+                true
+            },
+        }
+    }
+}
+
+impl<'a> Installer<'a, DepToolError> {
+    // `install` installs the dependencies defined in the dependency file for
+    // `cwd`, and returns the changes that were made, including statistics
+    // about each dependency that was fetched.
+    pub fn install(&self, cwd: &Path, opts: &InstallOptions)
+        -> Result<InstallOutcome, InstallError<DepToolError>>
     {
         let (proj_dir, deps_file_path, raw_deps_spec) =
-            match read_deps_file(cwd, &self.deps_file_name) {
-                Ok(maybe_v) => {
-                    if let Some(v) = maybe_v {
-                        v
-                    } else {
-                        return Err(InstallError::NoDepsFileFound);
-                    }
+            match opts.deps_spec {
+                Some(deps_spec) => {
+                    (
+                        cwd.to_path_buf(),
+                        cwd.join(STDIN_DEPS_FILE_PATH),
+                        deps_spec.to_vec(),
+                    )
                 },
-                Err(err) => {
-                    return Err(InstallError::ReadDepsFileFailed{source: err});
+                None => {
+                    self.read_root_deps_file(cwd)?
                 },
             };
 
-        let mut projs = vec![(proj_dir, None, deps_file_path, raw_deps_spec)];
+        // Projects are installed a level at a time: the root project, then
+        // the projects of its dependencies, and so on. Each level is
+        // prepared in order, so that version conflicts are handled in the
+        // same way on every run, and the projects of a level are then
+        // installed in parallel, because their output directories are
+        // separate.
+        let mut level =
+            vec![(proj_dir, vec![], deps_file_path, raw_deps_spec)];
+        let mut managed_paths = vec![];
+        let mut outcome = InstallOutcome::default();
+        // `decls` maps the tool and source of each dependency that has been
+        // installed to the definition that it was installed from, so that
+        // version conflicts between dependency files can be detected.
+        let mut decls: HashMap<(String, String), DepDecl> = HashMap::new();
+        // `failures` contains the errors for the dependencies that couldn't
+        // be fetched when `opts.fetch.keep_going` is set, and `failed_dirs`
+        // contains their output directories, so that their nested dependency
+        // files aren't read.
+        let mut failures = vec![];
+        let mut failed_dirs = HashSet::new();
+
+        while !level.is_empty() {
+            let mut projs = vec![];
+            for proj in level.drain(..) {
+                let proj = self.prepare_proj(
+                    proj,
+                    &mut decls,
+                    &mut managed_paths,
+                    opts,
+                )?;
+                projs.push(proj);
+            }
+
+            // Questions can only be asked about one project at a time, so
+            // projects are installed one after another if they can be asked.
+            let jobs =
+                if opts.prompter.is_some() { 1 } else { opts.fetch.jobs };
+            let results = run_bounded(&projs, jobs, |proj| {
+                self.install_proj_deps(
+                    &proj.proj_dir,
+                    &proj.conf,
+                    proj.dep_filter.as_ref(),
+                    opts,
+                )
+            });
+
+            for (proj, result) in projs.iter().zip(results) {
+                let dep_path = proj.dep_path.clone();
+                let mut proj_outcome =
+                    result.context(InstallProjDepsFailed{dep_path})?;
+                for (dir, source) in proj_outcome.failed.drain(..) {
+                    failed_dirs.insert(dir);
+                    failures.push(InstallError::InstallProjDepsFailed{
+                        source: Box::new(
+                            InstallProjDepsError::InstallDepsFailed{
+                                source: Box::new(source),
+                            },
+                        ),
+                        dep_path: proj.dep_path.clone(),
+                    });
+                }
+                outcome.add(proj_outcome);
+            }
+
+            for proj in &projs {
+                // The dependency file isn't summarized or annotated if some
+                // of its dependencies couldn't be installed.
+                if proj.dep_path.is_empty() && failures.is_empty() {
+                    update_root_proj_files(proj, opts)?;
+                }
+
+                if opts.recurse {
+                    level.extend(self.nested_projs(proj, &failed_dirs)?);
+                }
+            }
+        }
+
+        let stats = &outcome.fetches;
+        if !stats.is_empty() {
+            let total_bytes: u64 =
+                stats.iter().filter_map(|stat| stat.bytes).sum();
+            let total_time: Duration =
+                stats.iter().map(|stat| stat.duration).sum();
+            self.logger.log(Level::Info, &format!(
+                "Fetched {} dependencies ({} bytes) in {:.3}s",
+                stats.len(),
+                total_bytes,
+                total_time.as_secs_f64(),
+            ));
+        }
+
+        if failures.len() > 1 {
+            return Err(InstallError::DepsFailed{errs: failures});
+        } else if let Some(err) = failures.pop() {
+            return Err(err);
+        }
+
+        Ok(outcome)
+    }
+
+    // `validate` checks the dependency file for `cwd`, and the dependency
+    // files of installed dependencies if `recurse` is `true`, and returns the
+    // problems that were found. Unlike `install`, checking continues after a
+    // problem is found, so that all problems can be reported at once. An
+    // error is only returned if the root dependency file can't be read.
+    #[allow(clippy::type_complexity)]
+    pub fn validate(&self, cwd: &Path, recurse: bool)
+        -> Result<
+            Vec<InstallError<DepToolError>>,
+            InstallError<DepToolError>,
+        >
+    {
+        let (proj_dir, deps_file_path, raw_deps_spec) =
+            self.read_root_deps_file(cwd)?;
+
+        let mut projs =
+            vec![(proj_dir, vec![], deps_file_path, raw_deps_spec)];
+        let mut problems = vec![];
+
+        while let Some(proj) = projs.pop() {
+            let (proj_dir, dep_path, deps_file_path, raw_deps_spec) = proj;
+            let deps_spec =
+                match String::from_utf8(raw_deps_spec) {
+                    Ok(deps_spec) => {
+                        deps_spec
+                    },
+                    Err(source) => {
+                        problems.push(InstallError::ConvDepsFileUtf8Failed{
+                            source,
+                            path: deps_file_path,
+                            dep_path,
+                        });
+                        continue;
+                    },
+                };
+
+            let conf =
+                match self.parse_deps_conf(&deps_spec, &deps_file_path) {
+                    Ok(conf) => {
+                        conf
+                    },
+                    Err(source) => {
+                        problems.push(InstallError::ParseDepsConfFailed{
+                            source: Box::new(source),
+                            path: deps_file_path,
+                            dep_path,
+                        });
+                        continue;
+                    },
+                };
+
+            let mut dep_names: Vec<&String> = conf.deps.keys().collect();
+            dep_names.sort();
+
+            for name in &dep_names {
+                let dep = &conf.deps[*name];
+                let result =
+                    dep.with_env(|| dep.tool.check_source(&dep.source));
+                if let Err(source) = result {
+                    problems.push(InstallError::CheckSourceFailed{
+                        source: Box::new(source),
+                        dep_name: (*name).clone(),
+                        path: deps_file_path.clone(),
+                    });
+                }
+            }
+
+            if !recurse {
+                continue;
+            }
+
+            let ignore_file_path = proj_dir.join(IGNORE_FILE_NAME);
+            let ignored_deps =
+                match read_ignored_deps(&ignore_file_path) {
+                    Ok(ignored_deps) => {
+                        ignored_deps
+                    },
+                    Err(source) => {
+                        problems.push(InstallError::ReadIgnoreFileFailed{
+                            source,
+                            path: ignore_file_path,
+                        });
+                        continue;
+                    },
+                };
+
+            // Dependencies are pushed in reverse order so that they're
+            // checked in order.
+            for name in dep_names.into_iter().rev() {
+                if ignored_deps.contains(name) {
+                    continue;
+                }
+
+                let dep_proj_path =
+                    proj_dir.join(conf.dep_output_dir(name)).join(name);
+                let dep_deps_file_path =
+                    dep_proj_path.join(&self.deps_file_name);
+                let mut nested_dep_path = dep_path.clone();
+                nested_dep_path.push(name.clone());
+                match try_read(&dep_deps_file_path) {
+                    Ok(Some(raw_deps_spec)) => {
+                        projs.push((
+                            dep_proj_path,
+                            nested_dep_path,
+                            dep_deps_file_path,
+                            raw_deps_spec,
+                        ));
+                    },
+                    Ok(None) => {
+                    },
+                    Err(source) => {
+                        problems.push(InstallError::ReadNestedDepsFileFailed{
+                            source,
+                            path: dep_deps_file_path,
+                            dep_path: nested_dep_path,
+                            dep_proj_path,
+                        });
+                    },
+                }
+            }
+        }
+
+        Ok(problems)
+    }
+
+    // `check_installed` checks that the output directories of the project for
+    // `cwd` can be written to, and that they match their state files, and
+    // returns the problems that were found. Like `validate`, checking
+    // continues after a problem is found.
+    pub fn check_installed(&self, cwd: &Path)
+        -> Result<
+            Vec<InstallError<DepToolError>>,
+            InstallError<DepToolError>,
+        >
+    {
+        let (proj_dir, _, conf) = self.read_root_deps_conf(cwd)?;
+
+        let mut problems = vec![];
+        // `recorded_by` maps the names of installed dependencies to the state
+        // files that record them, so that dependencies that are recorded by
+        // more than one state file can be found.
+        let mut recorded_by: HashMap<String, PathBuf> = HashMap::new();
+        for output_dir in conf.output_dirs() {
+            let state_file_path = self.find_state_file(&proj_dir, output_dir);
+            let output_dir = proj_dir.join(output_dir);
+            if let Err(source) = doctor::check_writable(&output_dir) {
+                problems.push(InstallError::OutputDirNotWritable{
+                    source,
+                    path: output_dir.clone(),
+                });
+            }
+
+            let cur_deps =
+                match self.read_state_file(&state_file_path) {
+                    Ok((_, cur_deps)) => {
+                        cur_deps
+                    },
+                    Err(source) => {
+                        problems.push(InstallError::InstallProjDepsFailed{
+                            source: Box::new(source),
+                            dep_path: vec![],
+                        });
+                        continue;
+                    },
+                };
+
+            let mut dep_names: Vec<String> = cur_deps.into_keys().collect();
+            dep_names.sort();
+
+            for dep_name in dep_names {
+                if let Some(other_path) = recorded_by.get(&dep_name) {
+                    problems.push(InstallError::DepInMultipleStateFiles{
+                        dep_name: dep_name.clone(),
+                        state_file_path: state_file_path.clone(),
+                        other_state_file_path: other_path.clone(),
+                    });
+                }
+
+                // `exists` follows symlinks, so this also finds linked
+                // dependencies whose entries were removed from the store.
+                let dir = output_dir.join(&dep_name);
+                if !dir.exists() {
+                    problems.push(InstallError::InstalledDepMissing{
+                        dep_name: dep_name.clone(),
+                        path: dir,
+                        state_file_path: state_file_path.clone(),
+                    });
+                }
+
+                recorded_by.entry(dep_name)
+                    .or_insert_with(|| state_file_path.clone());
+            }
+        }
+
+        Ok(problems)
+    }
+
+    // `repair` rebuilds the state files of the project for `cwd` from the
+    // output directories, and returns the changes that were made. Entries
+    // whose directories don't exist are removed, and dependencies that are
+    // defined in the dependency file, but aren't recorded, are added if their
+    // directories contain the revision that the dependency file selects. A
+    // state file that can't be parsed is rebuilt from scratch. Added
+    // dependencies are recorded as installed at `source_date_epoch`, if it's
+    // defined.
+    pub fn repair(&self, cwd: &Path, source_date_epoch: Option<u64>)
+        -> Result<Vec<StateRepair>, InstallError<DepToolError>>
+    {
+        let (proj_dir, _, conf) = self.read_root_deps_conf(cwd)?;
+
+        let mut repairs = vec![];
+        for section_conf in conf.split_sections() {
+            let state_file_path =
+                self.prepare_state_file(&proj_dir, &section_conf.output_dir)
+                    .context(InstallProjDepsFailed{dep_path: vec![]})?;
+            let output_dir = proj_dir.join(&section_conf.output_dir);
+            let (state_file_exists, mut cur_deps, mut changed) =
+                match self.read_state_file(&state_file_path) {
+                    Ok((exists, cur_deps)) => {
+                        (exists, cur_deps, false)
+                    },
+                    Err(
+                        InstallProjDepsError::ConvStateFileUtf8Failed{..} |
+                        InstallProjDepsError::ParseStateFileFailed{..}
+                    ) => {
+                        (true, HashMap::new(), true)
+                    },
+                    Err(source) => {
+                        return Err(InstallError::InstallProjDepsFailed{
+                            source: Box::new(source),
+                            dep_path: vec![],
+                        });
+                    },
+                };
+
+            let mut dep_names: Vec<String> =
+                cur_deps.keys().cloned().collect();
+            dep_names.sort();
+            for dep_name in dep_names {
+                if !output_dir.join(&dep_name).exists() {
+                    cur_deps.remove(&dep_name);
+                    changed = true;
+                    repairs.push(StateRepair::Removed{
+                        dep_name,
+                        state_file_path: state_file_path.clone(),
+                    });
+                }
+            }
+
+            let mut dep_names: Vec<&String> =
+                section_conf.deps.keys().collect();
+            dep_names.sort();
+            for dep_name in dep_names {
+                let dir = output_dir.join(dep_name);
+                if cur_deps.contains_key(dep_name) || !dir.exists() {
+                    continue;
+                }
+
+                // Directories whose revisions can't be determined are left
+                // unrecorded, so that `dpnd install` fetches them again.
+                let dep = &section_conf.deps[dep_name];
+                let Ok(revision) = dep.tool.revision(&dir) else {
+                    continue;
+                };
+                let resolve = || dep.tool.resolve(&dep.source, &dep.version);
+                let result = dep.with_env(resolve);
+                match result {
+                    Ok(resolved) if resolved == revision => {
+                    },
+                    _ => {
+                        continue;
+                    },
+                }
+
+                let mut dep = dep.clone();
+                record_install(
+                    &mut dep,
+                    source_date_epoch,
+                    Some(revision.clone()),
+                );
+                cur_deps.insert(dep_name.clone(), dep);
+                changed = true;
+                repairs.push(StateRepair::Added{
+                    dep_name: dep_name.clone(),
+                    state_file_path: state_file_path.clone(),
+                    revision,
+                });
+            }
+
+            if changed && (state_file_exists || !cur_deps.is_empty()) {
+                write_state_file(&state_file_path, &cur_deps)
+                    .with_context(|| WriteRepairedStateFileFailed{
+                        state_file_path: state_file_path.clone(),
+                    })?;
+            }
+        }
+
+        Ok(repairs)
+    }
+
+    // `resolve` returns the exact revision of each dependency defined in the
+    // dependency file for `cwd`, without installing any dependencies. The
+    // returned dependencies are sorted by name.
+    pub fn resolve(&self, cwd: &Path)
+        -> Result<Vec<ResolvedDep>, InstallError<DepToolError>>
+    {
+        let (_, _, conf) = self.read_root_deps_conf(cwd)?;
+
+        let mut resolved_deps = vec![];
+        for (name, dep) in conf.deps {
+            let resolve = || dep.tool.resolve(&dep.source, &dep.version);
+            let revision = dep.with_env(resolve)
+                .with_context(|| ResolveDepFailed{dep_name: name.clone()})?;
+
+            resolved_deps.push(ResolvedDep{
+                name,
+                tool: dep.tool.name(),
+                source: dep.source,
+                version: dep.version,
+                revision,
+                owner: dep.opts.owner,
+            });
+        }
+        resolved_deps.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(resolved_deps)
+    }
+
+    // `diff` compares the installed revision of each dependency defined in
+    // the dependency file for `cwd` with the exact revision of its defined
+    // version, without installing any dependencies. The returned dependencies
+    // are sorted by name.
+    pub fn diff(&self, cwd: &Path)
+        -> Result<Vec<DepDiff>, InstallError<DepToolError>>
+    {
+        let (proj_dir, _, conf) = self.read_root_deps_conf(cwd)?;
+
+        let mut dep_diffs = vec![];
+        for (name, dep) in &conf.deps {
+            let resolve = || dep.tool.resolve(&dep.source, &dep.version);
+            let revision = dep.with_env(resolve)
+                .with_context(|| ResolveDepFailed{dep_name: name.clone()})?;
+
+            let dir = proj_dir.join(conf.dep_output_dir(name)).join(name);
+            let installed_revision = installed_revision(dep, &dir)
+                .with_context(|| GetInstalledRevisionFailed{
+                    dep_name: name.clone(),
+                })?;
+
+            let mut changes = None;
+            if let Some(installed_revision) = &installed_revision {
+                if *installed_revision != revision {
+                    let get_changes =
+                        || dep.tool.changes(&dep.source, &dir, &revision);
+                    changes = dep.with_env(get_changes)
+                        .with_context(|| ListChangesFailed{
+                            dep_name: name.clone(),
+                        })?;
+                }
+            }
+
+            dep_diffs.push(DepDiff{
+                name: name.clone(),
+                installed_revision,
+                revision,
+                changes,
+            });
+        }
+        dep_diffs.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(dep_diffs)
+    }
+
+    // `installed_deps` returns the dependencies that the state file of the
+    // project for `cwd` records as installed, along with the dependencies
+    // recorded by the state files of installed dependencies if `recurse` is
+    // `true`. The returned dependencies are sorted by path.
+    pub fn installed_deps(&self, cwd: &Path, recurse: bool)
+        -> Result<Vec<InstalledDep>, InstallError<DepToolError>>
+    {
+        let (proj_dir, _, conf) = self.read_root_deps_conf(cwd)?;
+
+        // Each output directory has its own state file, so a project is
+        // visited once for each of its output directories.
+        let mut projs: Vec<(PathBuf, PathBuf, Vec<String>)> =
+            conf.output_dirs()
+                .into_iter()
+                .map(|dir| (proj_dir.clone(), dir.to_path_buf(), vec![]))
+                .collect();
+        let mut installed_deps = vec![];
+
+        while let Some((proj_dir, output_dir, proj_path)) = projs.pop() {
+            let state_file_path = self.find_state_file(&proj_dir, &output_dir);
+            let output_dir = proj_dir.join(output_dir);
+            let (_, cur_deps) = self.read_state_file(&state_file_path)
+                .context(InstallProjDepsFailed{dep_path: proj_path.clone()})?;
+
+            let ignored_deps =
+                if recurse {
+                    let ignore_file_path = proj_dir.join(IGNORE_FILE_NAME);
+
+                    read_ignored_deps(&ignore_file_path)
+                        .context(ReadIgnoreFileFailed{
+                            path: ignore_file_path,
+                        })?
+                } else {
+                    HashSet::new()
+                };
+
+            for (name, dep) in cur_deps {
+                let dir = output_dir.join(&name);
+                let revision = installed_revision(&dep, &dir)
+                    .with_context(|| GetInstalledRevisionFailed{
+                        dep_name: name.clone(),
+                    })?;
+
+                let mut path = proj_path.clone();
+                path.push(name.clone());
+
+                if recurse && !ignored_deps.contains(&name) {
+                    let deps_file_path = dir.join(&self.deps_file_name);
+                    let maybe_raw_deps_spec = try_read(&deps_file_path)
+                        .with_context(|| ReadNestedDepsFileFailed{
+                            path: deps_file_path.clone(),
+                            dep_path: path.clone(),
+                            dep_proj_path: dir.clone(),
+                        })?;
+
+                    if let Some(raw_deps_spec) = maybe_raw_deps_spec {
+                        let deps_spec = String::from_utf8(raw_deps_spec)
+                            .with_context(|| ConvDepsFileUtf8Failed{
+                                path: deps_file_path.clone(),
+                                dep_path: path.clone(),
+                            })?;
+
+                        let nested_conf =
+                            self.parse_deps_conf(&deps_spec, &deps_file_path)
+                                .with_context(|| ParseDepsConfFailed{
+                                    path: deps_file_path.clone(),
+                                    dep_path: path.clone(),
+                                })?;
+
+                        for nested_output_dir in nested_conf.output_dirs() {
+                            projs.push((
+                                dir.clone(),
+                                nested_output_dir.to_path_buf(),
+                                path.clone(),
+                            ));
+                        }
+                    }
+                }
+
+                installed_deps.push(InstalledDep{
+                    path,
+                    tool: dep.tool.name(),
+                    source: dep.source,
+                    version: dep.version,
+                    revision,
+                    installed_at: dep.opts.installed_at,
+                    dpnd_version: dep.opts.dpnd_version,
+                    tool_version: dep.opts.tool_version,
+                });
+            }
+        }
+        installed_deps.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Ok(installed_deps)
+    }
+
+    // `proj_dir` returns the directory that contains the dependency file for
+    // `cwd`.
+    pub fn proj_dir(&self, cwd: &Path)
+        -> Result<PathBuf, InstallError<DepToolError>>
+    {
+        let (proj_dir, _, _) = self.read_root_deps_file(cwd)?;
+
+        Ok(proj_dir)
+    }
+
+    // `dep_paths` returns the locations that the dependencies defined in the
+    // dependency file for `cwd` are installed to.
+    pub fn dep_paths(&self, cwd: &Path)
+        -> Result<DepPaths, InstallError<DepToolError>>
+    {
+        let (proj_dir, deps_file_path, conf) = self.read_root_deps_conf(cwd)?;
+
+        let mut dep_dirs: Vec<(String, PathBuf)> =
+            conf.deps.keys()
+                .map(|name| {
+                    (name.clone(), conf.dep_output_dir(name).join(name))
+                })
+                .collect();
+        dep_dirs.sort();
+
+        let section_output_dirs =
+            conf.sections.iter()
+                .map(|section| section.output_dir.clone())
+                .collect();
+
+        Ok(DepPaths{
+            proj_dir,
+            deps_file_path,
+            output_dir: conf.output_dir,
+            section_output_dirs,
+            state_dir: PathBuf::from(STATE_DIR_PATH),
+            dep_dirs,
+        })
+    }
+
+    // `dep_owner` returns the name and owner of the dependency that `query`
+    // refers to. `query` can be the name of a dependency defined in the
+    // dependency file for `cwd`, or a path, relative to `cwd`, within the
+    // output directory of such a dependency.
+    pub fn dep_owner(&self, cwd: &Path, query: &str)
+        -> Result<(String, Option<String>), InstallError<DepToolError>>
+    {
+        let (proj_dir, _, conf) = self.read_root_deps_conf(cwd)?;
+
+        if let Some(dep) = conf.deps.get(query) {
+            return Ok((query.to_string(), dep.opts.owner.clone()));
+        }
+
+        let path = cwd.join(query);
+        for (dep_name, dep) in &conf.deps {
+            let dep_dir = proj_dir.join(conf.dep_output_dir(dep_name))
+                .join(dep_name);
+            if path.starts_with(&dep_dir) {
+                return Ok((dep_name.clone(), dep.opts.owner.clone()));
+            }
+        }
+
+        Err(InstallError::DepNotFound{query: query.to_string()})
+    }
+
+    // `installed_dep_dir` returns the output directory of the `dep_name`
+    // dependency of the project for `cwd`, if the state file records that the
+    // dependency is installed.
+    pub fn installed_dep_dir(&self, cwd: &Path, dep_name: &str)
+        -> Result<PathBuf, InstallError<DepToolError>>
+    {
+        let (proj_dir, _, conf) = self.read_root_deps_conf(cwd)?;
+        let output_dir = conf.dep_output_dir(dep_name);
+        let state_file_path = self.find_state_file(&proj_dir, output_dir);
+        let output_dir = proj_dir.join(output_dir);
+
+        let (_, cur_deps) = self.read_state_file(&state_file_path)
+            .context(InstallProjDepsFailed{dep_path: vec![]})?;
+
+        if !conf.deps.contains_key(dep_name) {
+            return Err(InstallError::UndefinedDep{
+                dep_name: dep_name.to_string(),
+            });
+        } else if !cur_deps.contains_key(dep_name) {
+            return Err(InstallError::DepNotInstalled{
+                dep_name: dep_name.to_string(),
+            });
+        }
+
+        Ok(output_dir.join(dep_name))
+    }
+
+    // `upgrade` sets the version of the `dep_name` dependency in the root
+    // dependency file for `cwd` to `version`, or to the latest version of the
+    // dependency if `version` isn't defined, and then installs the dependency
+    // without changing other dependencies. The previous and new versions of
+    // the dependency are returned.
+    pub fn upgrade(
+        &self,
+        cwd: &Path,
+        dep_name: &str,
+        version: Option<&str>,
+        opts: &InstallOptions,
+    )
+        -> Result<(Version, Version), InstallError<DepToolError>>
+    {
+        let (_, deps_file_path, deps_spec, conf) =
+            self.read_root_deps_spec(cwd)?;
+
+        let Some(dep) = conf.deps.get(dep_name) else {
+            return Err(InstallError::UndefinedDep{
+                dep_name: dep_name.to_string(),
+            });
+        };
+
+        let new_version =
+            if let Some(vsn) = version {
+                Version(vsn.to_string())
+            } else {
+                let maybe_vsn =
+                    dep.with_env(|| dep.tool.latest_version(&dep.source))
+                        .context(GetLatestVersionFailed{dep_name})?;
+
+                match maybe_vsn {
+                    Some(vsn) => {
+                        vsn
+                    },
+                    None => {
+                        return Err(InstallError::NoLatestVersion{
+                            dep_name: dep_name.to_string(),
+                            tool_name: dep.tool.name(),
+                        });
+                    },
+                }
+            };
+
+        // The dependency may be defined in a file that the root dependency
+        // file includes, in which case that file is upgraded instead.
+        let path = conf.dep_file_path(&deps_file_path, dep_name);
+        let spec =
+            if path == deps_file_path {
+                deps_spec
+            } else {
+                fs::read_to_string(path)
+                    .context(ReadIncludedDepsFileFailed{path})?
+            };
+        let included = path != deps_file_path;
+        let upgraded_spec =
+            set_dep_version(&spec, included, dep_name, &new_version)
+                .context(ParseDepsFailed{})
+                .with_context(|| ParseDepsConfFailed{
+                    dep_path: vec![],
+                    path: path.to_path_buf(),
+                })?
+                .context(DepDefnNotFound{dep_name, path})?;
+
+        fs::write(path, upgraded_spec)
+            .context(WriteUpgradedDepsFileFailed{path})?;
+
+        let upgrade_opts = InstallOptions{
+            dep_names: Some(vec![dep_name.to_string()]),
+            group_filter: opts.group_filter.clone(),
+            fetch: opts.fetch.clone(),
+            output: opts.output.clone(),
+            state: opts.state.clone(),
+            ..*opts
+        };
+        // The dependency file is restored if the installation fails, so that
+        // it still defines the version that's installed.
+        if let Err(err) = self.install(cwd, &upgrade_opts) {
+            fs::write(path, spec)
+                .context(RestoreUpgradedDepsFileFailed{path})?;
+
+            return Err(err);
+        }
+
+        Ok((dep.version.clone(), new_version))
+    }
+
+    // `pin` sets the version of each dependency in the root dependency file
+    // for `cwd`, and in the files that it includes, to the revision that the
+    // dependency is installed at, so that branches and tags are replaced by
+    // exact revisions. Dependencies must be installed at the versions that
+    // are defined for them, and the state files are updated so that the
+    // pinned dependencies are still treated as installed. The dependencies
+    // whose versions were changed are returned, sorted by name.
+    pub fn pin(&self, cwd: &Path)
+        -> Result<Vec<PinnedDep>, InstallError<DepToolError>>
+    {
+        let (proj_dir, deps_file_path, deps_spec, conf) =
+            self.read_root_deps_spec(cwd)?;
+
+        let mut state_files = HashMap::new();
+        let mut pinned_deps = vec![];
+        for (name, dep) in &conf.deps {
+            let output_dir = conf.dep_output_dir(name);
+            let state_file_path = self.state_file_path(&proj_dir, output_dir);
+            if !state_files.contains_key(&state_file_path) {
+                self.prepare_state_file(&proj_dir, output_dir)
+                    .context(InstallProjDepsFailed{dep_path: vec![]})?;
+                let (_, cur_deps) = self.read_state_file(&state_file_path)
+                    .context(InstallProjDepsFailed{dep_path: vec![]})?;
+                state_files.insert(state_file_path.clone(), cur_deps);
+            }
+
+            let Some(cur_dep) = state_files[&state_file_path].get(name) else {
+                return Err(InstallError::DepNotInstalled{
+                    dep_name: name.clone(),
+                });
+            };
+            // A dependency whose definition changed since it was installed
+            // is rejected, because pinning it to its installed revision
+            // would undo the change.
+            if dep_changed(cur_dep, dep) {
+                return Err(InstallError::PinnedDepOutdated{
+                    dep_name: name.clone(),
+                });
+            }
+            // A dependency that follows its branch would try to follow its
+            // pinned revision as a branch.
+            if dep.opts.track {
+                return Err(InstallError::PinTrackedDep{
+                    dep_name: name.clone(),
+                });
+            }
+
+            let revision =
+                if let Some(revision) = &cur_dep.opts.installed_revision {
+                    revision.clone()
+                } else {
+                    let dir = proj_dir.join(output_dir).join(name);
+
+                    read_pinned_revision(name, dep, dir, state_file_path)?
+                };
+
+            if dep.version.0 != revision {
+                pinned_deps.push(PinnedDep{
+                    name: name.clone(),
+                    version: dep.version.clone(),
+                    revision: Version(revision),
+                });
+            }
+        }
+        pinned_deps.sort_by(|a, b| a.name.cmp(&b.name));
+
+        // All of the new versions are determined before any files are
+        // written, so that files aren't partially pinned if a dependency
+        // can't be pinned.
+        let new_specs = pinned_deps_specs(
+            &conf,
+            &deps_file_path,
+            &deps_spec,
+            &pinned_deps,
+        )?;
+
+        for (path, spec) in new_specs {
+            fs::write(&path, spec)
+                .context(WritePinnedDepsFileFailed{path: &path})?;
+        }
+
+        // The versions in the state files are also updated, so that the
+        // pinned dependencies aren't fetched again by `dpnd install`.
+        let mut changed_state_file_paths = vec![];
+        for pinned_dep in &pinned_deps {
+            let state_file_path = self.state_file_path(
+                &proj_dir,
+                conf.dep_output_dir(&pinned_dep.name),
+            );
+            if let Some(cur_deps) = state_files.get_mut(&state_file_path) {
+                if let Some(cur_dep) = cur_deps.get_mut(&pinned_dep.name) {
+                    cur_dep.version = pinned_dep.revision.clone();
+                }
+            }
+            if !changed_state_file_paths.contains(&state_file_path) {
+                changed_state_file_paths.push(state_file_path);
+            }
+        }
+        for state_file_path in changed_state_file_paths {
+            write_state_file(&state_file_path, &state_files[&state_file_path])
+                .with_context(|| WritePinnedStateFileFailed{
+                    state_file_path: state_file_path.clone(),
+                })?;
+        }
+
+        Ok(pinned_deps)
+    }
+
+    // `fetch` fetches the `dep_name` dependency defined in the root
+    // dependency file for `cwd` into a new temporary directory, and returns
+    // the path of the fetched dependency. The output directories and state
+    // files of the project aren't changed, and the temporary directory is
+    // left in place so that it can be inspected.
+    pub fn fetch(&self, cwd: &Path, dep_name: &str, opts: &InstallOptions)
+        -> Result<PathBuf, InstallError<DepToolError>>
+    {
+        let (_, _, conf) = self.read_root_deps_conf(cwd)?;
+
+        let Some(dep) = conf.deps.get(dep_name) else {
+            return Err(InstallError::UndefinedDep{
+                dep_name: dep_name.to_string(),
+            });
+        };
+
+        let fetch_failed = |source| InstallError::InstallProjDepsFailed{
+            source: Box::new(InstallProjDepsError::InstallDepsFailed{
+                source: Box::new(source),
+            }),
+            dep_path: vec![],
+        };
+
+        let tmp_dir = temp_dir::create("dpnd_fetch")
+            .context(CreateDepOutputDirFailed{
+                dep_name,
+                path: env::temp_dir(),
+            })
+            .map_err(fetch_failed)?;
+        let dir = tmp_dir.join(dep_name);
+        let result = fetch_dep(dep_name, dep, &dir, opts, self.logger);
+        if let Err(source) = result {
+            // The temporary directory is only used for this fetch, so
+            // failures to remove it are ignored.
+            let _ = fs::remove_dir_all(&tmp_dir);
+
+            return Err(fetch_failed(source));
+        }
+
+        Ok(dir)
+    }
+
+    // `nested_projs` returns the projects of the dependencies of `proj` that
+    // have dependency files, excluding the dependencies that are ignored and
+    // those whose output directories are in `failed_dirs`.
+    #[allow(clippy::type_complexity)]
+    fn nested_projs(
+        &self,
+        proj: &PreparedProj,
+        failed_dirs: &HashSet<PathBuf>,
+    )
+        -> Result<
+            Vec<(PathBuf, Vec<String>, PathBuf, Vec<u8>)>,
+            InstallError<DepToolError>,
+        >
+    {
+        let PreparedProj{proj_dir, dep_path, conf, dep_filter, ..} = proj;
+
+        let ignore_file_path = proj_dir.join(IGNORE_FILE_NAME);
+        let ignored_deps = read_ignored_deps(&ignore_file_path)
+            .context(ReadIgnoreFileFailed{path: &ignore_file_path})?;
+
+        // Nested dependency files are installed in order of the names of
+        // their dependencies, so that the versions that are installed when
+        // conflicts are unified don't depend on the order of `conf.deps`.
+        let mut nested_dep_names: Vec<&String> = conf.deps.keys().collect();
+        nested_dep_names.sort();
+        let mut nested_projs = vec![];
+        for dep_name in nested_dep_names {
+            if let Some(filter) = &dep_filter {
+                if !filter.contains(dep_name) {
+                    continue;
+                }
+            }
+            if ignored_deps.contains(dep_name) {
+                continue;
+            }
+
+            let dep_proj_path =
+                proj_dir.join(conf.dep_output_dir(dep_name)).join(dep_name);
+            if failed_dirs.contains(&dep_proj_path) {
+                continue;
+            }
+            let dep_deps_file_path = dep_proj_path.join(&self.deps_file_name);
+            let mut nested_dep_path = dep_path.clone();
+            nested_dep_path.push(dep_name.clone());
+            let maybe_raw_deps_spec = try_read(&dep_deps_file_path)
+                .with_context(|| ReadNestedDepsFileFailed{
+                    path: dep_deps_file_path.clone(),
+                    dep_path: nested_dep_path.clone(),
+                    dep_proj_path: dep_proj_path.clone(),
+                })?;
+
+            if let Some(raw_deps_spec) = maybe_raw_deps_spec {
+                nested_projs.push((
+                    dep_proj_path,
+                    nested_dep_path,
+                    dep_deps_file_path,
+                    raw_deps_spec,
+                ));
+            }
+        }
+
+        Ok(nested_projs)
+    }
+
+    // `prepare_proj` parses the dependency file of `proj` and selects the
+    // dependencies to install from it. If `opts.recurse` is set then version
+    // conflicts with the dependencies in `decls`, and overlaps with the paths
+    // in `managed_paths`, are also handled, and both are updated with the
+    // dependencies of `proj`.
+    fn prepare_proj(
+        &self,
+        proj: (PathBuf, Vec<String>, PathBuf, Vec<u8>),
+        decls: &mut HashMap<(String, String), DepDecl>,
+        managed_paths: &mut Vec<ManagedPath>,
+        opts: &InstallOptions,
+    )
+        -> Result<PreparedProj<'a>, InstallError<DepToolError>>
+    {
+        let (proj_dir, dep_path, deps_file_path, raw_deps_spec) = proj;
+        let is_root = dep_path.is_empty();
+        let deps_spec = String::from_utf8(raw_deps_spec)
+            .with_context(|| ConvDepsFileUtf8Failed{
+                dep_path: dep_path.clone(),
+                path: deps_file_path.clone(),
+            })?;
+
+        let mut conf = self.parse_deps_conf(&deps_spec, &deps_file_path)
+            .with_context(|| ParseDepsConfFailed{
+                dep_path: dep_path.clone(),
+                path: deps_file_path.clone(),
+            })?;
+        self.logger.log(Level::Info, &format!(
+            "Parsed '{}', which defines {} dependencies",
+            deps_file_path.display(),
+            conf.deps.len(),
+        ));
+
+        let mut dep_filter =
+            match (is_root, opts.changed_since) {
+                (true, Some(rev)) => {
+                    Some(self.changed_deps(&proj_dir, &conf, rev)?)
+                },
+                _ => {
+                    None
+                },
+            };
+        if let (true, Some(names)) = (is_root, &opts.dep_names) {
+            // Names that aren't defined are rejected rather than ignored, so
+            // that misspelt names don't silently install nothing.
+            if let Some(name) =
+                names.iter().find(|name| !conf.deps.contains_key(*name))
+            {
+                return Err(InstallError::UndefinedDep{
+                    dep_name: name.clone(),
+                });
+            }
+
+            let names: HashSet<String> = names.iter().cloned().collect();
+            dep_filter =
+                match dep_filter {
+                    Some(filter) => {
+                        let filter = filter.intersection(&names);
+
+                        Some(filter.cloned().collect())
+                    },
+                    None => {
+                        Some(names)
+                    },
+                };
+        }
+
+        // The output directory is overridden after `changed_deps` is called,
+        // so that dependencies aren't treated as having moved.
+        if let (true, Some(output_dir)) = (is_root, &opts.output.output_dir) {
+            override_output_dir(&proj_dir, &mut conf, output_dir)?;
+        }
+
+        if opts.recurse {
+            self.check_version_conflicts(
+                decls,
+                &mut conf,
+                &deps_file_path,
+                dep_filter.as_ref(),
+                opts,
+            )?;
+
+            let proj_managed_paths =
+                proj_managed_paths(&proj_dir, &deps_file_path, &conf)?;
+            check_managed_paths(
+                &proj_dir,
+                &proj_managed_paths,
+                managed_paths,
+            )?;
+            if !is_root {
+                check_nested_output_dirs(
+                    &proj_dir,
+                    &dep_path,
+                    &proj_managed_paths,
+                )?;
+            }
+            managed_paths.extend(proj_managed_paths);
+        }
+
+        Ok(PreparedProj{
+            proj_dir,
+            dep_path,
+            deps_file_path,
+            deps_spec,
+            conf,
+            dep_filter,
+        })
+    }
+
+    // `check_version_conflicts` compares the dependencies in `conf`, which is
+    // defined in `deps_file_path`, with the dependencies in `decls` that were
+    // defined in other dependency files, and handles dependencies with the
+    // same tool and source but different versions according to
+    // `opts.conflicts`. The dependencies of `conf` are then added to `decls`.
+    fn check_version_conflicts(
+        &self,
+        decls: &mut HashMap<(String, String), DepDecl>,
+        conf: &mut DepsConf<'a, DepToolError>,
+        deps_file_path: &Path,
+        dep_filter: Option<&HashSet<String>>,
+        opts: &InstallOptions,
+    )
+        -> Result<(), InstallError<DepToolError>>
+    {
+        let mut dep_names: Vec<String> = conf.deps.keys().cloned().collect();
+        dep_names.sort();
+
+        for dep_name in dep_names {
+            if dep_filter.is_some_and(|filter| !filter.contains(&dep_name)) {
+                continue;
+            }
+
+            let dep_file_path =
+                conf.dep_file_path(deps_file_path, &dep_name).to_path_buf();
+            let dep = conf.deps.get_mut(&dep_name)
+                .unwrap_or_else(|| panic!(
+                    "dependency '{}' wasn't in the dependency file",
+                    dep_name,
+                ));
+            if let Some(filter) = &opts.group_filter {
+                if !filter.selects(dep.opts.group.as_ref()) {
+                    continue;
+                }
+            }
+
+            let decl = DepDecl{
+                dep_name: dep_name.clone(),
+                version: dep.version.0.clone(),
+                deps_file_path: dep_file_path,
+                ln_num: conf.ln_nums.get(&dep_name).copied().unwrap_or(0),
+            };
+            let key = (dep.tool.name(), dep.source.clone());
+
+            let Some(other_decl) = decls.get(&key) else {
+                decls.insert(key, decl);
+                continue;
+            };
+            // Dependencies in the same dependency file can have the same
+            // source deliberately, such as to use two versions side by side.
+            if other_decl.version == decl.version
+                || other_decl.deps_file_path == decl.deps_file_path
+            {
+                continue;
+            }
+
+            match opts.conflicts {
+                ConflictPolicy::Error => {
+                    return Err(InstallError::VersionsConflict{
+                        dep_source: dep.source.clone(),
+                        decl: Box::new(decl),
+                        other_decl: Box::new(other_decl.clone()),
+                    });
+                },
+                ConflictPolicy::Warn => {
+                    self.logger.log(Level::Warn, &format!(
+                        "'{}' in '{}' is at '{}', but '{}' in '{}' is at '{}'",
+                        decl.dep_name,
+                        decl.deps_file_path.display(),
+                        decl.version,
+                        other_decl.dep_name,
+                        other_decl.deps_file_path.display(),
+                        other_decl.version,
+                    ));
+                    if let Some(warn) = opts.warn {
+                        warn(InstallError::VersionsConflict{
+                            dep_source: dep.source.clone(),
+                            decl: Box::new(decl),
+                            other_decl: Box::new(other_decl.clone()),
+                        });
+                    }
+                },
+                ConflictPolicy::Unify => {
+                    self.logger.log(Level::Info, &format!(
+                        "Installing '{}' in '{}' at '{}' instead of '{}', to \
+                         match '{}' in '{}'",
+                        decl.dep_name,
+                        decl.deps_file_path.display(),
+                        other_decl.version,
+                        decl.version,
+                        other_decl.dep_name,
+                        other_decl.deps_file_path.display(),
+                    ));
+                    dep.version = Version(other_decl.version.clone());
+                },
+            }
+        }
+
+        Ok(())
+    }
+
+    // `format` rewrites the dependency file for `cwd` in a canonical form, as
+    // described by `format_deps_spec`, and returns whether its contents were
+    // changed. If `check` is `true` then the dependency file isn't written,
+    // and an error is returned if it isn't already in the canonical form.
+    pub fn format(&self, cwd: &Path, check: bool)
+        -> Result<bool, InstallError<DepToolError>>
+    {
+        let (_, deps_file_path, deps_spec, _) =
+            self.read_root_deps_spec(cwd)?;
+
+        let formatted = format_deps_spec(&deps_spec)
+            .context(ParseDepsFailed{})
+            .with_context(|| ParseDepsConfFailed{
+                dep_path: vec![],
+                path: deps_file_path.clone(),
+            })?;
+
+        if formatted == deps_spec {
+            return Ok(false);
+        }
+
+        if check {
+            return Err(InstallError::DepsFileNotFormatted{
+                path: deps_file_path,
+            });
+        }
+
+        fs::write(&deps_file_path, formatted)
+            .context(WriteFormattedDepsFileFailed{path: &deps_file_path})?;
+
+        Ok(true)
+    }
+
+    fn read_root_deps_conf(&self, cwd: &Path)
+        -> Result<
+            (PathBuf, PathBuf, DepsConf<'a, DepToolError>),
+            InstallError<DepToolError>,
+        >
+    {
+        let (proj_dir, deps_file_path, _, conf) =
+            self.read_root_deps_spec(cwd)?;
+
+        Ok((proj_dir, deps_file_path, conf))
+    }
+
+    // `read_root_deps_spec` is like `read_root_deps_conf`, but it also returns
+    // the contents of the root dependency file.
+    #[allow(clippy::type_complexity)]
+    fn read_root_deps_spec(&self, cwd: &Path)
+        -> Result<
+            (PathBuf, PathBuf, String, DepsConf<'a, DepToolError>),
+            InstallError<DepToolError>,
+        >
+    {
+        let (proj_dir, deps_file_path, raw_deps_spec) =
+            self.read_root_deps_file(cwd)?;
+
+        let deps_spec = String::from_utf8(raw_deps_spec)
+            .with_context(|| ConvDepsFileUtf8Failed{
+                dep_path: vec![],
+                path: deps_file_path.clone(),
+            })?;
+
+        let conf = self.parse_deps_conf(&deps_spec, &deps_file_path)
+            .with_context(|| ParseDepsConfFailed{
+                dep_path: vec![],
+                path: deps_file_path.clone(),
+            })?;
+
+        Ok((proj_dir, deps_file_path, deps_spec, conf))
+    }
+
+    fn read_root_deps_file(&self, cwd: &Path)
+        -> Result<(PathBuf, PathBuf, Vec<u8>), InstallError<DepToolError>>
+    {
+        match read_deps_file(cwd, &self.deps_file_name) {
+            Ok(Some(v)) => {
+                Ok(v)
+            },
+            Ok(None) => {
+                Err(InstallError::NoDepsFileFound)
+            },
+            Err(err) => {
+                Err(InstallError::ReadDepsFileFailed{source: err})
+            },
+        }
+    }
+
+    // `changed_deps` returns the names of the dependencies in `conf` whose
+    // definitions differ from those in the dependency file in `proj_dir` at
+    // the Git revision `rev`, along with the names of dependencies that were
+    // removed since `rev`.
+    fn changed_deps(
+        &self,
+        proj_dir: &Path,
+        conf: &DepsConf<'a, DepToolError>,
+        rev: &str,
+    )
+        -> Result<HashSet<String>, InstallError<DepToolError>>
+    {
+        let maybe_raw_old_deps_spec =
+            read_old_deps_file(proj_dir, &self.deps_file_name, rev)
+                .context(ReadOldDepsFileFailed{rev})?;
+
+        let Some(raw_old_deps_spec) = maybe_raw_old_deps_spec else {
+            return Ok(conf.deps.keys().cloned().collect());
+        };
+
+        let old_deps_spec = String::from_utf8(raw_old_deps_spec)
+            .context(ConvOldDepsFileUtf8Failed{rev})?;
+
+        // Files that the old dependency file includes are read from the
+        // working tree, rather than from `rev`, so dependencies that are
+        // only changed in included files aren't detected.
+        let old_deps_file_path = proj_dir.join(&self.deps_file_name);
+        let old_conf =
+            self.parse_deps_conf(&old_deps_spec, &old_deps_file_path)
+                .context(ParseOldDepsConfFailed{rev})?;
+
+        let mut changed = HashSet::new();
+        for (name, dep) in &conf.deps {
+            if let Some(old_dep) = old_conf.deps.get(name) {
+                let moved =
+                    old_conf.dep_output_dir(name) != conf.dep_output_dir(name);
+                if moved || dep_changed(old_dep, dep) {
+                    changed.insert(name.clone());
+                }
+            } else {
+                changed.insert(name.clone());
+            }
+        }
+        for name in old_conf.deps.keys() {
+            if !conf.deps.contains_key(name) {
+                changed.insert(name.clone());
+            }
+        }
+
+        Ok(changed)
+    }
+
+    // `install_proj_deps` installs the dependencies in `conf` to their output
+    // directories. The dependencies of each section are installed separately,
+    // and each output directory has its own state file.
+    fn install_proj_deps(
+        &self,
+        proj_dir: &Path,
+        conf: &DepsConf<'_, DepToolError>,
+        dep_filter: Option<&HashSet<String>>,
+        opts: &InstallOptions,
+    )
+        -> Result<InstallOutcome, InstallProjDepsError<DepToolError>>
+    {
+        let mut outcome = InstallOutcome::default();
+        for section_conf in conf.split_sections() {
+            let output_dir = proj_dir.join(&section_conf.output_dir);
+            let (state_file_path, state_file_exists, cur_deps) =
+                if opts.state.no_state_file {
+                    let cur_deps = inspect_installed_deps(
+                        &output_dir,
+                        &section_conf.deps,
+                    );
+
+                    (None, false, cur_deps)
+                } else {
+                    let path =
+                        if opts.mode.checks_only() {
+                            self.find_state_file(
+                                proj_dir,
+                                &section_conf.output_dir,
+                            )
+                        } else {
+                            self.prepare_state_file(
+                                proj_dir,
+                                &section_conf.output_dir,
+                            )?
+                        };
+                    let (exists, cur_deps) = self.read_state_file(&path)?;
+
+                    (Some(path), exists, cur_deps)
+                };
+
+            if !opts.mode.checks_only() {
+                fs::create_dir_all(&output_dir)
+                    .with_context(||
+                        CreateMainOutputDirFailed{path: output_dir.clone()}
+                    )?;
+            }
+
+            let section_outcome = install_deps(
+                &output_dir,
+                state_file_path,
+                state_file_exists,
+                cur_deps,
+                section_conf.deps,
+                dep_filter,
+                opts,
+                self.logger,
+            )
+                .context(InstallDepsFailed{})?;
+            outcome.add(section_outcome);
+        }
+
+        Ok(outcome)
+    }
+
+    // `parse_deps_conf` parses `conts`, which are the contents of the
+    // dependency file at `deps_file_path`. The paths of included files are
+    // relative to the directory of `deps_file_path`.
+    fn parse_deps_conf(&self, conts: &str, deps_file_path: &Path)
+        -> Result<DepsConf<'a, DepToolError>, ParseDepsConfError>
+    {
+        // The version directive is checked first so that files that use
+        // newer syntax are reported as needing a newer `dpnd`, rather than as
+        // invalid.
+        check_version_directive(conts)?;
+
+        let mut lines = conts.lines().enumerate();
+
+        let (output_dir_ln_num, output_dir) = parse_output_dir(&mut lines)
+            .context(ParseOutputDirFailed{})?;
+
+        let ParsedDeps{deps, ln_nums, sections, includes} =
+            self.parse_deps(&mut lines, self.deps_file_kind())
+                .context(ParseDepsFailed{})?;
+
+        let mut conf = DepsConf{
+            output_dir,
+            output_dir_ln_num,
+            deps,
+            ln_nums,
+            dep_files: HashMap::new(),
+            sections,
+        };
+
+        // The dependency file is included in the stack so that files that
+        // include it are reported as cycles. There's no file to include if
+        // the dependency file was read from somewhere else, such as `stdin`.
+        let mut include_stack: Vec<PathBuf> =
+            fs::canonicalize(deps_file_path).into_iter().collect();
+        for include in includes {
+            self.include_deps(
+                &mut conf,
+                deps_file_path,
+                include,
+                &mut include_stack,
+            )?;
+        }
+
+        fill_group_sections(&conf.deps, &mut conf.sections);
+
+        check_section_output_dirs(&conf.output_dir, &conf.sections)?;
+
+        Ok(conf)
+    }
+
+    // `include_deps` adds the dependencies defined in the file that `include`
+    // refers to, and in the files that it includes in turn, to `conf`.
+    // `includer_path` is the path of the file that contains `include`, and
+    // `include_stack` contains the canonical paths of the files that are
+    // being included, so that cycles can be detected.
+    fn include_deps(
+        &self,
+        conf: &mut DepsConf<'a, DepToolError>,
+        includer_path: &Path,
+        include: DepsInclude,
+        include_stack: &mut Vec<PathBuf>,
+    )
+        -> Result<(), ParseDepsConfError>
+    {
+        let DepsInclude{ln_num, path, section} = include;
+        let path =
+            includer_path.parent()
+                .unwrap_or_else(|| Path::new(""))
+                .join(path);
+
+        let read_failed = || ReadIncludedFileFailed{
+            path: includer_path.to_path_buf(),
+            ln_num,
+            included_path: path.clone(),
+        };
+        let conts = fs::read_to_string(&path)
+            .with_context(read_failed)?;
+        let canon_path = fs::canonicalize(&path)
+            .with_context(read_failed)?;
+
+        if include_stack.contains(&canon_path) {
+            return Err(ParseDepsConfError::IncludeCycle{
+                path: includer_path.to_path_buf(),
+                ln_num,
+                included_path: path,
+            });
+        }
+
+        let mut lines = conts.lines().enumerate();
+        let parsed = self.parse_deps(&mut lines, self.deps_file_kind())
+            .with_context(|| ParseIncludedFileFailed{path: path.clone()})?;
+
+        if let Some(included_section) = parsed.sections.first() {
+            return Err(ParseDepsConfError::IncludedFileHasSection{
+                path,
+                ln_num: included_section.ln_num,
+            });
+        }
+
+        // Names are checked in the order that they're defined, so that the
+        // first duplicate in the included file is reported.
+        let mut defns: Vec<(String, usize)> =
+            parsed.ln_nums.into_iter().collect();
+        defns.sort_by_key(|(_, defn_ln_num)| *defn_ln_num);
+        for (dep_name, defn_ln_num) in &defns {
+            if let Some(orig_ln_num) = conf.ln_nums.get(dep_name) {
+                return Err(ParseDepsConfError::DupIncludedDepName{
+                    path,
+                    ln_num: *defn_ln_num,
+                    dep_name: dep_name.clone(),
+                    orig_path: conf.dep_files.get(dep_name).cloned(),
+                    orig_ln_num: *orig_ln_num,
+                });
+            }
+        }
+
+        for (dep_name, defn_ln_num) in defns {
+            conf.dep_files.insert(dep_name.clone(), path.clone());
+            conf.ln_nums.insert(dep_name, defn_ln_num);
+        }
+        if let Some(i) = section {
+            conf.sections[i].dep_names.extend(parsed.deps.keys().cloned());
+        }
+        conf.deps.extend(parsed.deps);
+
+        include_stack.push(canon_path);
+        for nested_include in parsed.includes {
+            // Included files can't contain sections, so the dependencies of
+            // nested included files are in the section of `include`.
+            let nested_include = DepsInclude{section, ..nested_include};
+            self.include_deps(conf, &path, nested_include, include_stack)?;
+        }
+        include_stack.pop();
+
+        Ok(())
+    }
+
+    // `state_file_path` returns the path of the state file of `output_dir`,
+    // which is relative to `proj_dir`. Output directories given by
+    // `--output-dir` are absolute, and are scratch locations rather than part
+    // of the project, so they keep their state files in themselves.
+    fn state_file_path(&self, proj_dir: &Path, output_dir: &Path) -> PathBuf {
+        if output_dir.is_absolute() {
+            return output_dir.join(&self.state_file_name);
+        }
+
+        proj_dir.join(STATE_DIR_PATH)
+            .join(output_dir)
+            .join(&self.state_file_name)
+    }
+
+    // `find_state_file` returns the path of the state file of `output_dir`,
+    // which is relative to `proj_dir`. This is the path in the output
+    // directory itself, where older versions of `dpnd` wrote state files, if
+    // only that path exists.
+    fn find_state_file(&self, proj_dir: &Path, output_dir: &Path) -> PathBuf {
+        let state_file_path = self.state_file_path(proj_dir, output_dir);
+        let legacy_path =
+            proj_dir.join(output_dir).join(&self.state_file_name);
+
+        if !state_file_path.exists() && legacy_path.exists() {
+            legacy_path
+        } else {
+            state_file_path
+        }
+    }
+
+    // `prepare_state_file` creates the directory of the state file of
+    // `output_dir`, which is relative to `proj_dir`, and moves the state file
+    // there from the output directory if it was written by an older version
+    // of `dpnd`. It returns the path of the state file.
+    fn prepare_state_file(&self, proj_dir: &Path, output_dir: &Path)
+        -> Result<PathBuf, InstallProjDepsError<DepToolError>>
+    {
+        let state_file_path = self.state_file_path(proj_dir, output_dir);
+        let found_path = self.find_state_file(proj_dir, output_dir);
+
+        if let Some(state_dir) = state_file_path.parent() {
+            fs::create_dir_all(state_dir)
+                .context(CreateStateDirFailed{path: state_dir})?;
+        }
+
+        if found_path != state_file_path {
+            fs::rename(&found_path, &state_file_path)
+                .context(MoveLegacyStateFileFailed{
+                    src: &found_path,
+                    dest: &state_file_path,
+                })?;
+        }
+
+        Ok(state_file_path)
+    }
+
+    // `read_state_file` returns whether the state file at `state_file_path`
+    // exists, along with the dependencies that it records as installed.
+    #[allow(clippy::type_complexity)]
+    fn read_state_file(&self, state_file_path: &Path)
+        -> Result<
+            (bool, HashMap<String, Dependency<'a, DepToolError>>),
+            InstallProjDepsError<DepToolError>,
+        >
+    {
+        let (state_file_exists, state_file_conts) =
+            match try_read(state_file_path) {
+                Ok(maybe_conts) => {
+                    if let Some(conts) = maybe_conts {
+                        (true, conts)
+                    } else {
+                        (false, vec![])
+                    }
+                },
+                Err(err) => {
+                    return Err(InstallProjDepsError::ReadStateFileFailed{
+                        source: err,
+                        path: state_file_path.to_path_buf(),
+                    });
+                },
+            };
+
+        let state_spec = String::from_utf8(state_file_conts)
+            .context(ConvStateFileUtf8Failed{path: state_file_path})?;
+
+        // State files don't contain sections, because each section has its
+        // own state file.
+        let parsed_deps =
+            self.parse_deps(
+                &mut state_spec.lines().enumerate(),
+                DepsSpecKind::StateFile,
+            )
+                .context(ParseStateFileFailed{path: state_file_path})?;
+
+        Ok((state_file_exists, parsed_deps.deps))
+    }
+
+    // `deps_file_kind` returns the kind of dependency specification that
+    // dependency files, and the files that they include, are parsed as.
+    fn deps_file_kind(&self) -> DepsSpecKind {
+        DepsSpecKind::DepsFile{validate_sources: self.validate_sources}
+    }
+
+    // `parse_deps` returns the dependencies defined in `lines`, along with
+    // the sections that `lines` is divided into. Dependencies that are
+    // defined before the first section header aren't in a section. `kind`
+    // determines whether the sources of dependencies are checked by their
+    // tools, and which options the dependencies can use.
+    fn parse_deps(&self, lines: &mut Enumerate<Lines>, kind: DepsSpecKind)
+        -> Result<ParsedDeps<'a>, ParseDepsError>
+    {
+        let mut dep_defns: Vec<(String, Dependency<'a, DepToolError>, usize)> =
+            vec![];
+        let mut sections: Vec<DepsSection> = vec![];
+        let mut includes: Vec<DepsInclude> = vec![];
+        // We collect errors instead of returning the first one, so that all
+        // of the problems in a dependency file can be fixed in one pass.
+        let mut errs = vec![];
+
+        while let Some((i, line)) = lines.next() {
+            let ln_num = i + 1;
+
+            let ln = line.trim_start();
+            if conf_line_is_skippable(ln) {
+                continue;
+            }
+
+            let has_deps = !dep_defns.is_empty();
+            let maybe_section =
+                self.parse_section_line(ln_num, ln, has_deps, &sections);
+            if let Some(result) = maybe_section {
+                match result {
+                    Ok(section) => {
+                        sections.push(section);
+                    },
+                    Err(err) => {
+                        errs.push(err);
+                    },
+                }
+                continue;
+            }
+
+            let words =
+                match split_continued_fields(lines, ln_num, line) {
+                    Ok(words) => {
+                        words
+                    },
+                    Err(err) => {
+                        errs.push(err);
+                        continue;
+                    },
+                };
+
+            if let Some(path) = include_path(&words) {
+                // Like dependencies, included files are in the section that
+                // the include directive follows.
+                let section =
+                    sections.len()
+                        .checked_sub(1)
+                        .filter(|&i| sections[i].group.is_none());
+                includes.push(DepsInclude{
+                    ln_num,
+                    path: path.to_string(),
+                    section,
+                });
+                continue;
+            }
+
+            let maybe_dep =
+                self.parse_dep_defn(ln_num, ln, &words, &dep_defns, kind)
+                    .and_then(|(local_name, dep)| {
+                        if kind.validates_sources() {
+                            check_dep(
+                                ln_num,
+                                line,
+                                &words,
+                                &local_name,
+                                &dep,
+                            )?;
+                        }
+
+                        Ok((local_name, dep))
+                    });
+            match maybe_dep {
+                Ok((local_name, dep)) => {
+                    // Group directives come before the dependencies, so
+                    // dependencies don't follow them like they follow
+                    // section headers.
+                    let section = sections.last_mut()
+                        .filter(|section| section.group.is_none());
+                    if let Some(section) = section {
+                        section.dep_names.insert(local_name.clone());
+                    }
+                    dep_defns.push((local_name, dep, ln_num));
+                },
+                Err(err) => {
+                    errs.push(err);
+                },
+            }
+        }
+
+        if errs.len() == 1 {
+            return Err(errs.remove(0));
+        } else if !errs.is_empty() {
+            return Err(ParseDepsError::Multiple{errs});
+        }
+
+        // Conditions are only evaluated after all dependencies have been
+        // parsed, so that errors in the dependency file are reported
+        // regardless of the environment that `dpnd` is run in.
+        let mut deps = HashMap::new();
+        let mut ln_nums = HashMap::new();
+        for (local_name, dep, ln_num) in dep_defns {
+            let holds =
+                match &dep.opts.condition {
+                    Some(cond) => cond.holds(),
+                    None => true,
+                };
+            ln_nums.insert(local_name.clone(), ln_num);
+            if holds {
+                deps.insert(local_name, dep);
+            }
+        }
+
+        for section in &mut sections {
+            section.dep_names.retain(|name| deps.contains_key(name));
+        }
+
+        Ok(ParsedDeps{deps, ln_nums, sections, includes})
+    }
+
+    // `parse_section_header` parses `ln`, which has the form
+    // `[name] output_dir`, into a section that doesn't contain any
+    // dependencies yet.
+    fn parse_section_header(
+        &self,
+        ln_num: usize,
+        ln: &str,
+        sections: &[DepsSection],
+    )
+        -> Result<DepsSection, ParseDepsError>
+    {
+        let invalid_header = || {
+            ParseDepsError::InvalidSectionHeader{
+                ln_num,
+                line: ln.trim_end().to_string(),
+            }
+        };
+
+        let (name, output_dir) =
+            ln[SECTION_HEADER_START.len_utf8()..]
+                .split_once(SECTION_HEADER_END)
+                .ok_or_else(invalid_header)?;
+        let output_dir = output_dir.trim();
+        if name.is_empty()
+            || self.bad_dep_name_chars.is_match(name)
+            || output_dir.is_empty()
+        {
+            return Err(invalid_header());
+        }
+
+        for section in sections {
+            if section.name == name {
+                return Err(ParseDepsError::DupSectionName{
+                    ln_num,
+                    section: name.to_string(),
+                    orig_ln_num: section.ln_num,
+                });
+            }
+        }
+
+        let output_dir = parse_output_dir_line(ln_num, output_dir)
+            .context(ParseSectionOutputDirFailed{section: name})?;
+
+        Ok(DepsSection{
+            name: name.to_string(),
+            ln_num,
+            output_dir,
+            dep_names: HashSet::new(),
+            group: None,
+        })
+    }
+
+    // `parse_section_line` parses `ln` if it's a group directive or a
+    // section header, and returns `None` otherwise. `has_deps` is `true` if
+    // dependencies are defined before `ln`, and `sections` are the sections
+    // that precede it.
+    fn parse_section_line(
+        &self,
+        ln_num: usize,
+        ln: &str,
+        has_deps: bool,
+        sections: &[DepsSection],
+    )
+        -> Option<Result<DepsSection, ParseDepsError>>
+    {
+        if ln.starts_with(GROUP_DIR_PREFIX) {
+            // Group directives must come before the dependencies and
+            // sections, so that they can be found at a glance.
+            if has_deps || sections.iter().any(|s| s.group.is_none()) {
+                return Some(Err(ParseDepsError::GroupDirNotInHeader{ln_num}));
+            }
+
+            return Some(Self::parse_group_dir(ln_num, ln, sections));
+        }
+
+        if ln.starts_with(SECTION_HEADER_START) {
+            return Some(self.parse_section_header(ln_num, ln, sections));
+        }
+
+        None
+    }
+
+    // `parse_group_dir` parses `ln`, which has the form
+    // `group=name output_dir`, into a section for the dependencies in the
+    // group. The section is named after the directive, such as `group=test`,
+    // so that its name can't be the same as that of a section with a header.
+    fn parse_group_dir(
+        ln_num: usize,
+        ln: &str,
+        sections: &[DepsSection],
+    )
+        -> Result<DepsSection, ParseDepsError>
+    {
+        let invalid_group_dir = || {
+            ParseDepsError::InvalidGroupDir{
+                ln_num,
+                line: ln.trim_end().to_string(),
+            }
+        };
+
+        let (fields, continues) = split_fields(ln_num, ln)?;
+        let (name, output_dir) =
+            match fields.as_slice() {
+                [name, output_dir] if !continues => {
+                    (name, output_dir)
+                },
+                _ => {
+                    return Err(invalid_group_dir());
+                },
+            };
+        let group = &name[GROUP_DIR_PREFIX.len()..];
+        if group.is_empty() {
+            return Err(invalid_group_dir());
+        }
+
+        for section in sections {
+            if section.name == *name {
+                return Err(ParseDepsError::DupSectionName{
+                    ln_num,
+                    section: name.clone(),
+                    orig_ln_num: section.ln_num,
+                });
+            }
+        }
+
+        let output_dir = parse_output_dir_line(ln_num, output_dir)
+            .context(ParseSectionOutputDirFailed{section: name})?;
+
+        Ok(DepsSection{
+            name: name.clone(),
+            ln_num,
+            output_dir,
+            dep_names: HashSet::new(),
+            group: Some(group.to_string()),
+        })
+    }
+
+    fn parse_dep_defn(
+        &self,
+        ln_num: usize,
+        ln: &str,
+        words: &[String],
+        dep_defns: &[(String, Dependency<'a, DepToolError>, usize)],
+        kind: DepsSpecKind,
+    )
+        -> Result<(String, Dependency<'a, DepToolError>), ParseDepsError>
+    {
+        // The tool of a dependency can be omitted if its source has a scheme
+        // that selects a tool, in which case the specification is expanded to
+        // the form that names the tool.
+        let expanded_words;
+        let words =
+            match words.get(1).and_then(|src| split_scheme_tool(src)) {
+                Some((tool_name, src)) => {
+                    expanded_words = [
+                        vec![
+                            words[0].clone(),
+                            tool_name.to_string(),
+                            src.to_string(),
+                        ],
+                        words[2..].to_vec(),
+                    ]
+                        .concat();
+
+                    expanded_words.as_slice()
+                },
+                None => {
+                    words
+                },
+            };
+
+        if words.len() < 4 {
+            return Err(ParseDepsError::InvalidDepSpec{
+                ln_num,
+                line: ln.to_string(),
+            });
+        }
+
+        let local_name = words[0].clone();
+        if let Some(found) = self.bad_dep_name_chars.find(&local_name) {
+            // `found.start()` is a byte offset, so we convert it to a
+            // character offset in case the name contains multibyte
+            // characters.
+            let bad_char_idx = local_name[..found.start()].chars().count();
+
+            return Err(ParseDepsError::DepNameContainsInvalidChar{
+                ln_num,
+                dep_name: local_name.clone(),
+                bad_char_idx,
+            });
+        } else if local_name == self.state_file_name {
+            return Err(ParseDepsError::ReservedDepName{
+                ln_num,
+                dep_name: local_name.clone(),
+            });
+        }
+
+        for (dep_local_name, _dep, defn_ln_num) in dep_defns {
+            if *dep_local_name == local_name {
+                return Err(ParseDepsError::DupDepName{
+                    ln_num,
+                    dep_name: local_name,
+                    orig_ln_num: *defn_ln_num,
+                });
+            }
+        }
+
+        let opts =
+            parse_dep_opts(ln_num, &local_name, ln, &words[4..], kind)?;
+
+        let tool_name = words[1].clone();
+        let tool = match self.tools.get(&tool_name) {
+            Some(tool) => *tool,
+            None => return Err(ParseDepsError::UnknownTool{
+                ln_num,
+                dep_name: local_name,
+                tool_name,
+            }),
+        };
+
+        match (tool.requires_path(), &opts.path) {
+            (true, None) => {
+                return Err(ParseDepsError::MissingPathOption{
+                    ln_num,
+                    dep_name: local_name,
+                    tool_name,
+                });
+            },
+            (false, Some(_)) => {
+                return Err(ParseDepsError::UnexpectedPathOption{
+                    ln_num,
+                    dep_name: local_name,
+                    tool_name,
+                });
+            },
+            _ => {
+            },
+        }
+
+        // A scheme that selects the tool of the dependency is also accepted
+        // when the tool is named.
+        let source =
+            match split_scheme_tool(&words[2]) {
+                Some((scheme_tool, src)) if scheme_tool == tool_name => {
+                    src.to_string()
+                },
+                _ => {
+                    words[2].clone()
+                },
+            };
+
+        let dep = Dependency{
+            tool,
+            source,
+            version: Version(words[3].clone()),
+            opts,
+        };
+
+        Ok((local_name, dep))
+    }
+}
+
+#[derive(Debug, Snafu)]
+pub enum InstallError<E>
+where
+    E: Error + 'static
+{
+    NoDepsFileFound,
+    ReadDepsFileFailed{source: ReadDepsFileError},
+    // The `dep_path` of an error contains the names of the nested
+    // dependencies that lead from the root project to the project that the
+    // error occurred in, and is empty for the root project.
+    ConvDepsFileUtf8Failed{
+        source: FromUtf8Error,
+        path: PathBuf,
+        dep_path: Vec<String>,
+    },
+    // Parse errors are boxed because they're much larger than the other
+    // errors, which would make every `InstallError` as large as them.
+    ParseDepsConfFailed{
+        #[snafu(source(from(ParseDepsConfError, Box::new)))]
+        source: Box<ParseDepsConfError>,
+        path: PathBuf,
+        dep_path: Vec<String>,
+    },
+    InstallProjDepsFailed{
+        #[snafu(source(from(InstallProjDepsError<E>, Box::new)))]
+        source: Box<InstallProjDepsError<E>>,
+        dep_path: Vec<String>,
+    },
+    ReadNestedDepsFileFailed{
+        source: IoError,
+        path: PathBuf,
+        dep_path: Vec<String>,
+        dep_proj_path: PathBuf,
+    },
+    ReadOldDepsFileFailed{source: GitCmdError, rev: String},
+    ConvOldDepsFileUtf8Failed{source: FromUtf8Error, rev: String},
+    ParseOldDepsConfFailed{
+        #[snafu(source(from(ParseDepsConfError, Box::new)))]
+        source: Box<ParseDepsConfError>,
+        rev: String,
+    },
+    CanonicalizePathFailed{source: IoError, path: PathBuf},
+    OutputDirOverlapsSection{
+        output_dir: PathBuf,
+        section: String,
+        section_output_dir: PathBuf,
+    },
+    // `ManagedPath`s are boxed because they're much larger than the other
+    // fields of `InstallError`.
+    ManagedPathsOverlap{path: Box<ManagedPath>, other_path: Box<ManagedPath>},
+    NestedOutputDirOutsideDep{
+        path: Box<ManagedPath>,
+        canon_path: PathBuf,
+        dep_path: Vec<String>,
+        dep_proj_path: PathBuf,
+    },
+    ResolveDepFailed{source: E, dep_name: String},
+    DepNotFound{query: String},
+    UndefinedDep{dep_name: String},
+    DepNotInstalled{dep_name: String},
+    GetLatestVersionFailed{source: E, dep_name: String},
+    NoLatestVersion{dep_name: String, tool_name: String},
+    WriteUpgradedDepsFileFailed{source: IoError, path: PathBuf},
+    RestoreUpgradedDepsFileFailed{source: IoError, path: PathBuf},
+    DepDefnNotFound{dep_name: String, path: PathBuf},
+    PinnedDepOutdated{dep_name: String},
+    PinTrackedDep{dep_name: String},
+    NoInstalledRevision{dep_name: String},
+    ReadIncludedDepsFileFailed{source: IoError, path: PathBuf},
+    WritePinnedDepsFileFailed{source: IoError, path: PathBuf},
+    WritePinnedStateFileFailed{
+        source: WriteStateFileError,
+        state_file_path: PathBuf,
+    },
+    WriteSummaryFailed{source: IoError, path: PathBuf},
+    GetAnnotationRevisionFailed{source: E, dep_name: String},
+    WriteAnnotatedDepsFileFailed{source: IoError, path: PathBuf},
+    GetInstalledRevisionFailed{
+        source: InstalledRevisionError<E>,
+        dep_name: String,
+    },
+    ListChangesFailed{source: E, dep_name: String},
+    ReadIgnoreFileFailed{source: IoError, path: PathBuf},
+    CheckSourceFailed{source: Box<E>, dep_name: String, path: PathBuf},
+    OutputDirNotWritable{source: IoError, path: PathBuf},
+    InstalledDepMissing{
+        dep_name: String,
+        path: PathBuf,
+        state_file_path: PathBuf,
+    },
+    DepInMultipleStateFiles{
+        dep_name: String,
+        state_file_path: PathBuf,
+        other_state_file_path: PathBuf,
+    },
+    VersionsConflict{
+        dep_source: String,
+        decl: Box<DepDecl>,
+        other_decl: Box<DepDecl>,
+    },
+    DepsFileNotFormatted{path: PathBuf},
+    WriteFormattedDepsFileFailed{source: IoError, path: PathBuf},
+    WriteRepairedStateFileFailed{
+        source: WriteStateFileError,
+        state_file_path: PathBuf,
+    },
+    // `errs` contains at least two errors, in the order that the
+    // dependencies were installed.
+    DepsFailed{errs: Vec<InstallError<E>>},
+}
+
+// `StateRepair` is a change that `Installer::repair` made to a state file.
+pub enum StateRepair {
+    Removed{dep_name: String, state_file_path: PathBuf},
+    Added{dep_name: String, state_file_path: PathBuf, revision: String},
+}
+
+// `DepDecl` is the definition of a dependency in a dependency file.
+#[derive(Clone, Debug)]
+pub struct DepDecl {
+    pub dep_name: String,
+    pub version: String,
+    pub deps_file_path: PathBuf,
+    // `ln_num` is the line of `deps_file_path` that defines the dependency.
+    pub ln_num: usize,
+}
+
+#[derive(Debug, Snafu)]
+pub enum InstalledRevisionError<E>
+where
+    E: Error + 'static
+{
+    ReadInstalledVendorFileFailed{source: IoError, path: PathBuf},
+    GetInstalledRevisionFromToolFailed{source: E},
+}
+
+// `InstallOutcome` describes the changes that installation made to the
+// installed dependencies.
+#[derive(Default)]
+pub struct InstallOutcome {
+    // `fetches` describes each dependency that was fetched.
+    pub fetches: Vec<FetchStats>,
+    // `installed`, `updated` and `removed` are the numbers of dependencies
+    // that were installed, changed and removed, and `unchanged` is the number
+    // of installed dependencies that were left as they were.
+    pub installed: usize,
+    pub updated: usize,
+    pub removed: usize,
+    pub unchanged: usize,
+    // `failed` contains the output directory of each dependency that
+    // couldn't be fetched when `keep_going` was set, paired with the error.
+    // It's always empty in the outcome of `Installer::install`, which returns
+    // these errors instead.
+    failed: Vec<(PathBuf, InstallDepsError<DepToolError>)>,
+}
+
+impl InstallOutcome {
+    // `add` adds the changes described by `other` to those described by
+    // `self`.
+    pub fn add(&mut self, other: InstallOutcome) {
+        self.fetches.extend(other.fetches);
+        self.installed += other.installed;
+        self.updated += other.updated;
+        self.removed += other.removed;
+        self.unchanged += other.unchanged;
+        self.failed.extend(other.failed);
+    }
+}
+
+// `FetchStats` describes the fetch of a single dependency.
+pub struct FetchStats {
+    pub dep_name: String,
+    // `dir` is the output directory of the dependency.
+    pub dir: PathBuf,
+    // `bytes` is the total size of the files of the dependency after it was
+    // fetched, or `None` if the size couldn't be measured.
+    pub bytes: Option<u64>,
+    pub duration: Duration,
+}
+
+// `FetchEvent` describes the fetch of a single dependency, and is passed to
+// `FetchOptions::hook`.
+pub struct FetchEvent<'a> {
+    pub dep_name: &'a str,
+    pub source: &'a str,
+    pub version: &'a str,
+    // `dir` is the output directory of the dependency.
+    pub dir: &'a Path,
+    // `outcome` is `None` if the dependency is about to be fetched.
+    pub outcome: Option<FetchOutcome>,
+}
+
+pub struct FetchOutcome {
+    pub duration: Duration,
+    // `succeeded` is `false` if the fetch failed, in which case installation
+    // stops after the hook returns.
+    pub succeeded: bool,
+}
+
+// `DepPaths` describes the locations that the dependencies defined in a
+// dependency file are installed to.
+pub struct DepPaths {
+    pub proj_dir: PathBuf,
+    pub deps_file_path: PathBuf,
+    // `output_dir` is the output directory of the dependencies that aren't in
+    // a section, and is relative to `proj_dir`.
+    pub output_dir: PathBuf,
+    // `section_output_dirs` contains the output directories of the sections
+    // of the dependency file, relative to `proj_dir`.
+    pub section_output_dirs: Vec<PathBuf>,
+    // `state_dir` is the directory that contains the state files of the
+    // output directories, relative to `proj_dir`.
+    pub state_dir: PathBuf,
+    // `dep_dirs` pairs the name of each dependency with its output
+    // directory, relative to `proj_dir`, and is sorted by name.
+    pub dep_dirs: Vec<(String, PathBuf)>,
+}
+
+// `ResolvedDep` is a dependency whose version has been resolved to an exact
+// revision.
+pub struct ResolvedDep {
+    pub name: String,
+    pub tool: String,
+    pub source: String,
+    pub version: Version,
+    pub revision: String,
+    pub owner: Option<String>,
+}
+
+// `InstalledDep` is a dependency that a state file records as installed.
+pub struct InstalledDep {
+    // `path` contains the names of the nested dependencies that lead to the
+    // project that defines the dependency, followed by the name of the
+    // dependency.
+    pub path: Vec<String>,
+    pub tool: String,
+    pub source: String,
+    pub version: Version,
+    // `revision` is `None` if the installed revision of the dependency isn't
+    // known, such as when it was vendored without a recorded revision.
+    pub revision: Option<String>,
+    // `installed_at`, `dpnd_version` and `tool_version` are `None` if they
+    // weren't recorded when the dependency was installed, such as when it
+    // was installed by an older version of `dpnd`.
+    pub installed_at: Option<u64>,
+    pub dpnd_version: Option<String>,
+    pub tool_version: Option<String>,
+}
+
+// `DepDiff` compares the installed revision of a dependency with the exact
+// revision of its defined version.
+pub struct DepDiff {
+    pub name: String,
+    // `installed_revision` is `None` if the dependency isn't installed.
+    pub installed_revision: Option<String>,
+    pub revision: String,
+    // `changes` is `None` if the changes between the installed revision and
+    // `revision` couldn't be listed, or if they're the same.
+    pub changes: Option<Vec<String>>,
+}
+
+// `PinnedDep` is a dependency whose version was changed by `Installer::pin`
+// to the revision that it's installed at.
+pub struct PinnedDep {
+    pub name: String,
+    pub version: Version,
+    pub revision: Version,
+}
+
+// `ManagedPath` is a path that `dpnd` writes to; either the output directory
+// defined in a dependency file, or the output directory of a dependency.
+#[derive(Clone, Debug)]
+pub struct ManagedPath {
+    pub path: PathBuf,
+    // `dep_name` is the name of the dependency that is output to `path`, or
+    // `None` if `path` is an output directory defined in a dependency file.
+    pub dep_name: Option<String>,
+    pub deps_file_path: PathBuf,
+    // `ln_num` is the line of `deps_file_path` that defines `path`.
+    pub ln_num: usize,
+    canon_path: PathBuf,
+}
+
+// `proj_managed_paths` returns the paths that will be written to when
+// installing `conf`.
+fn proj_managed_paths(
+    proj_dir: &Path,
+    deps_file_path: &Path,
+    conf: &DepsConf<'_, DepToolError>,
+)
+    -> Result<Vec<ManagedPath>, InstallError<DepToolError>>
+{
+    let mut managed_paths = vec![];
+    for section_conf in conf.split_sections() {
+        let output_dir = proj_dir.join(&section_conf.output_dir);
+        let canon_output_dir = canonicalize_lenient(&output_dir)
+            .with_context(|| {
+                CanonicalizePathFailed{path: output_dir.clone()}
+            })?;
+
+        managed_paths.push(ManagedPath{
+            path: output_dir.clone(),
+            dep_name: None,
+            deps_file_path: deps_file_path.to_path_buf(),
+            ln_num: section_conf.output_dir_ln_num,
+            canon_path: canon_output_dir.clone(),
+        });
+        for dep_name in section_conf.deps.keys() {
+            managed_paths.push(ManagedPath{
+                path: output_dir.join(dep_name),
+                dep_name: Some(dep_name.clone()),
+                deps_file_path:
+                    section_conf.dep_file_path(deps_file_path, dep_name)
+                        .to_path_buf(),
+                ln_num: section_conf.ln_nums[dep_name],
+                canon_path: canon_output_dir.join(dep_name),
+            });
+        }
+    }
+
+    Ok(managed_paths)
+}
+
+// `override_output_dir` replaces the output directory of the dependencies of
+// `conf` that aren't in a section with `output_dir`, which must not overlap
+// with the output directory of any section of `conf`.
+fn override_output_dir(
+    proj_dir: &Path,
+    conf: &mut DepsConf<'_, DepToolError>,
+    output_dir: &Path,
+)
+    -> Result<(), InstallError<DepToolError>>
+{
+    let canon_output_dir = canonicalize_lenient(output_dir)
+        .with_context(|| {
+            CanonicalizePathFailed{path: output_dir.to_path_buf()}
+        })?;
+
+    for section in &conf.sections {
+        let section_dir = proj_dir.join(&section.output_dir);
+        let canon_section_dir = canonicalize_lenient(&section_dir)
+            .with_context(|| CanonicalizePathFailed{path: section_dir})?;
+
+        let overlaps =
+            canon_section_dir.starts_with(&canon_output_dir)
+                || canon_output_dir.starts_with(&canon_section_dir);
+        if overlaps {
+            return Err(InstallError::OutputDirOverlapsSection{
+                output_dir: output_dir.to_path_buf(),
+                section: section.name.clone(),
+                section_output_dir: section.output_dir.clone(),
+            });
+        }
+    }
+
+    // `output_dir` is absolute, so joining it to the project directory, as
+    // is done for output directories defined in dependency files, returns
+    // it unchanged.
+    conf.output_dir = canon_output_dir;
+
+    Ok(())
+}
+
+// `canonicalize_lenient` returns the canonical form of `path`, where `path`
+// doesn't need to exist; the longest existing prefix of `path` is
+// canonicalized, and the remaining components are appended to the result.
+fn canonicalize_lenient(path: &Path) -> Result<PathBuf, IoError> {
+    let mut prefix = path.to_path_buf();
+    let mut suffix = vec![];
+    loop {
+        match prefix.canonicalize() {
+            Ok(mut canon_path) => {
+                while let Some(part) = suffix.pop() {
+                    canon_path.push(part);
+                }
+                return Ok(canon_path);
+            },
+            Err(err) => {
+                if err.kind() != ErrorKind::NotFound {
+                    return Err(err);
+                }
+                if let Some(part) = prefix.file_name() {
+                    suffix.push(part.to_os_string());
+                } else {
+                    return Err(err);
+                }
+                prefix.pop();
+            },
+        }
+    }
+}
+
+// `check_managed_paths` returns an error if any path in `new_paths`, which
+// were defined by the project in `proj_dir`, overlaps with any path in
+// `managed_paths`. Paths in `new_paths` may be nested in paths in
+// `managed_paths` that `proj_dir` is nested in, because this is the case for
+// nested dependencies.
+fn check_managed_paths(
+    proj_dir: &Path,
+    new_paths: &[ManagedPath],
+    managed_paths: &[ManagedPath],
+)
+    -> Result<(), InstallError<DepToolError>>
+{
+    let canon_proj_dir = canonicalize_lenient(proj_dir)
+        .with_context(||
+            CanonicalizePathFailed{path: proj_dir.to_path_buf()}
+        )?;
+
+    for new_path in new_paths {
+        let new = &new_path.canon_path;
+        for managed_path in managed_paths {
+            let managed = &managed_path.canon_path;
+            let overlaps =
+                new == managed
+                    || managed.starts_with(new)
+                    || (
+                        new.starts_with(managed)
+                            && !canon_proj_dir.starts_with(managed)
+                    );
+
+            if overlaps {
+                return Err(InstallError::ManagedPathsOverlap{
+                    path: Box::new(new_path.clone()),
+                    other_path: Box::new(managed_path.clone()),
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// `check_nested_output_dirs` returns an error if any output directory in
+// `new_paths`, which were defined by the project of the nested dependency at
+// `dep_path` in `proj_dir`, resolves to a path outside `proj_dir`, such as
+// through a symbolic link in the dependency. Output directories can't contain
+// `..`, but a nested dependency file could otherwise write outside of its
+// dependency.
+fn check_nested_output_dirs(
+    proj_dir: &Path,
+    dep_path: &[String],
+    new_paths: &[ManagedPath],
+)
+    -> Result<(), InstallError<DepToolError>>
+{
+    let canon_proj_dir = canonicalize_lenient(proj_dir)
+        .with_context(||
+            CanonicalizePathFailed{path: proj_dir.to_path_buf()}
+        )?;
+
+    for new_path in new_paths {
+        let is_output_dir = new_path.dep_name.is_none();
+        if is_output_dir && !new_path.canon_path.starts_with(&canon_proj_dir) {
+            return Err(InstallError::NestedOutputDirOutsideDep{
+                path: Box::new(new_path.clone()),
+                canon_path: new_path.canon_path.clone(),
+                dep_path: dep_path.to_vec(),
+                dep_proj_path: proj_dir.to_path_buf(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+// `read_old_deps_file` returns the contents of the file named `deps_file_name`
+// in `proj_dir` at the Git revision `rev`, or `None` if the file didn't exist
+// at that revision.
+fn read_old_deps_file(proj_dir: &Path, deps_file_name: &str, rev: &str)
+    -> Result<Option<Vec<u8>>, GitCmdError>
+{
+    let ls_output = dep_tools::run_git(
+        &["ls-tree", "--name-only", rev, "--", deps_file_name],
+        proj_dir,
+    )?;
+
+    if ls_output.stdout.is_empty() {
+        return Ok(None);
+    }
+
+    let rev_path = format!("{}:./{}", rev, deps_file_name);
+    let show_output = dep_tools::run_git(&["show", &rev_path], proj_dir)?;
+
+    Ok(Some(show_output.stdout))
+}
+
+// `try_read` returns the contents of the file at `path`, or `None` if it
+// doesn't exist, or an error if one occurred.
+fn try_read<P: AsRef<Path>>(path: P) -> Result<Option<Vec<u8>>, IoError> {
+    match fs::read(path) {
+        Ok(conts) => {
+            Ok(Some(conts))
+        },
+        Err(err) => {
+            if err.kind() == ErrorKind::NotFound {
+                Ok(None)
+            } else {
+                Err(err)
+            }
+        },
+    }
+}
+
+// `IGNORE_FILE_NAME` is the name of the file that lists the dependencies of a
+// project that aren't installed recursively.
+const IGNORE_FILE_NAME: &str = ".dpndignore";
+
+// `read_ignored_deps` returns the names of the dependencies listed in the
+// ignore file at `path`, which has one name per line. Empty lines and lines
+// that start with `#` are ignored. An empty set is returned if the ignore file
+// doesn't exist.
+fn read_ignored_deps(path: &Path) -> Result<HashSet<String>, IoError> {
+    let Some(conts) = try_read(path)? else {
+        return Ok(HashSet::new());
+    };
+
+    let dep_names =
+        String::from_utf8_lossy(&conts)
+            .lines()
+            .map(str::trim)
+            .filter(|ln| !ln.is_empty() && !ln.starts_with('#'))
+            .map(ToString::to_string)
+            .collect();
+
+    Ok(dep_names)
+}
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Snafu)]
+pub enum InstallProjDepsError<E>
+where
+    E: Error + 'static
+{
+    ReadStateFileFailed{source: IoError, path: PathBuf},
+    ConvStateFileUtf8Failed{source: FromUtf8Error, path: PathBuf},
+    ParseStateFileFailed{
+        #[snafu(source(from(ParseDepsError, Box::new)))]
+        source: Box<ParseDepsError>,
+        path: PathBuf,
+    },
+    CreateMainOutputDirFailed{source: IoError, path: PathBuf},
+    CreateStateDirFailed{source: IoError, path: PathBuf},
+    MoveLegacyStateFileFailed{source: IoError, src: PathBuf, dest: PathBuf},
+    InstallDepsFailed{
+        #[snafu(source(from(InstallDepsError<E>, Box::new)))]
+        source: Box<InstallDepsError<E>>,
+    },
+}
+
+// `read_deps_file` reads the file named `deps_file_name` in `start` or the
+// deepest of `start`s ancestor directories that contains a file named
+// `deps_file_name`.
+fn read_deps_file(start: &Path, deps_file_name: &str)
+    -> Result<Option<(PathBuf, PathBuf, Vec<u8>)>, ReadDepsFileError>
+{
+    let mut dir = start.to_path_buf();
+    loop {
+        let deps_file_path = dir.clone().join(deps_file_name);
+
+        match try_read(&deps_file_path) {
+            Ok(Some(conts)) => {
+                return Ok(Some((dir, deps_file_path, conts)));
+            },
+            Ok(None) => {
+            },
+            Err(err) => {
+                return Err(ReadDepsFileError::ReadFailed{
+                    source: err,
+                    deps_file_path,
+                });
+            },
+        }
+
+        if !dir.pop() {
+            return Ok(None);
+        }
+    }
+}
+
+#[derive(Debug, Snafu)]
+pub enum ReadDepsFileError {
+    ReadFailed{source: IoError, deps_file_path: PathBuf},
+}
+
+#[derive(Debug, Snafu)]
+pub enum ParseDepsConfError {
+    ParseOutputDirFailed{source: ParseOutputDirError},
+    ParseDepsFailed{source: ParseDepsError},
+    // `other_section` is `None` if the other output directory is the output
+    // directory of the dependency file.
+    SectionOutputDirsOverlap{
+        ln_num: usize,
+        section: String,
+        output_dir: PathBuf,
+        other_section: Option<String>,
+        other_output_dir: PathBuf,
+    },
+    InvalidVersionDirective{directive: String},
+    DpndTooOld{min_version: String},
+    // `path` is the path of the file that contains the include directive on
+    // line `ln_num`.
+    ReadIncludedFileFailed{
+        source: IoError,
+        path: PathBuf,
+        ln_num: usize,
+        included_path: PathBuf,
+    },
+    IncludeCycle{path: PathBuf, ln_num: usize, included_path: PathBuf},
+    // `path` is the path of the included file.
+    ParseIncludedFileFailed{
+        #[snafu(source(from(ParseDepsError, Box::new)))]
+        source: Box<ParseDepsError>,
+        path: PathBuf,
+    },
+    IncludedFileHasSection{path: PathBuf, ln_num: usize},
+    // `orig_path` is `None` if the dependency was originally defined in the
+    // dependency file, rather than in an included file.
+    DupIncludedDepName{
+        path: PathBuf,
+        ln_num: usize,
+        dep_name: String,
+        orig_path: Option<PathBuf>,
+        orig_ln_num: usize,
+    },
+}
+
+// `VERSION_DIRECTIVE_PREFIX` starts the optional first line of a dependency
+// file that specifies the minimum version of `dpnd` that the file requires,
+// such as `#!dpnd >=0.3`. The directive is a comment, so it's ignored by
+// versions of `dpnd` that don't support it.
+const VERSION_DIRECTIVE_PREFIX: &str = "#!dpnd";
+
+// `update_root_proj_files` writes the summary of the dependencies of `proj`,
+// which is the root project, and annotates its dependency file, if `opts`
+// requires them.
+fn update_root_proj_files(proj: &PreparedProj, opts: &InstallOptions)
+    -> Result<(), InstallError<DepToolError>>
+{
+    let PreparedProj{proj_dir, deps_file_path, deps_spec, conf, ..} = proj;
+
+    if opts.mode != InstallMode::Frozen {
+        if let Some(path) = &opts.state.summary_path {
+            write_summary(path, conf).context(WriteSummaryFailed{path})?;
+        }
+    }
+
+    if opts.state.annotate && !opts.mode.checks_only() {
+        let now = opts.output.source_date_epoch.unwrap_or_else(unix_now);
+        let annotated = annotate_deps_spec(deps_spec, proj_dir, conf, now)?;
+
+        if annotated != *deps_spec {
+            fs::write(deps_file_path, annotated)
+                .context(WriteAnnotatedDepsFileFailed{path: deps_file_path})?;
+        }
+    }
+
+    Ok(())
+}
+
+// `PreparedProj` is a project whose dependency file has been parsed, and
+// whose dependencies are ready to be installed.
+struct PreparedProj<'a> {
+    proj_dir: PathBuf,
+    // `dep_path` contains the names of the nested dependencies that lead to
+    // the project, and is empty for the root project.
+    dep_path: Vec<String>,
+    deps_file_path: PathBuf,
+    deps_spec: String,
+    conf: DepsConf<'a, DepToolError>,
+    dep_filter: Option<HashSet<String>>,
+}
+
+// `SECTION_HEADER_START` and `SECTION_HEADER_END` enclose the name of a
+// section in a dependency file, which is followed by the output directory of
+// the section.
+const SECTION_HEADER_START: char = '[';
+const SECTION_HEADER_END: char = ']';
+
+// `GROUP_DIR_PREFIX` starts a line in the header of a dependency file, such
+// as `group=test test/deps`, that installs the dependencies in a group to
+// their own output directory.
+const GROUP_DIR_PREFIX: &str = "group=";
+
+// `INCLUDE_DIR_KEYWORD` starts a line in a dependency file, such as
+// `include deps/test.txt`, that defines the dependencies in another file.
+const INCLUDE_DIR_KEYWORD: &str = "include";
+
+// `SCHEME_TOOL_PREFIXES` pairs prefixes of URL schemes, such as the `git+` of
+// `git+https://`, with the tools that they select. A dependency whose source
+// starts with one of these prefixes can omit its tool, and the prefix is
+// removed from the source before it's passed to the tool.
+const SCHEME_TOOL_PREFIXES: &[(&str, &str)] = &[("git+", "git")];
+
+// `split_scheme_tool` returns the name of the tool selected by the scheme of
+// `src`, and `src` without the prefix that selected the tool, or `None` if the
+// scheme of `src` doesn't select a tool.
+fn split_scheme_tool(src: &str) -> Option<(&'static str, &str)> {
+    for (prefix, tool_name) in SCHEME_TOOL_PREFIXES {
+        if let Some(url) = src.strip_prefix(prefix) {
+            if url.contains("://") {
+                return Some((tool_name, url));
+            }
+        }
+    }
+
+    None
+}
+
+struct DepsConf<'a, E> {
+    // `output_dir` is the output directory of the dependencies that aren't in
+    // a section.
+    output_dir: PathBuf,
+    output_dir_ln_num: usize,
+    // `deps` contains the dependencies of all sections.
+    deps: HashMap<String, Dependency<'a, E>>,
+    // `ln_nums` maps the name of each dependency that's defined, including
+    // those whose conditions don't hold, to the line that it's defined on.
+    ln_nums: HashMap<String, usize>,
+    // `dep_files` maps the name of each dependency that's defined in an
+    // included file to the path of that file.
+    dep_files: HashMap<String, PathBuf>,
+    sections: Vec<DepsSection>,
+}
+
+impl<'a, E> DepsConf<'a, E> {
+    // `dep_file_path` returns the path of the file that defines the
+    // `dep_name` dependency, where `deps_file_path` is the path of the
+    // dependency file that `self` was parsed from.
+    fn dep_file_path<'b>(&'b self, deps_file_path: &'b Path, dep_name: &str)
+        -> &'b Path
+    {
+        self.dep_files.get(dep_name)
+            .map_or(deps_file_path, PathBuf::as_path)
+    }
+
+    // `dep_output_dir` returns the output directory, relative to the project
+    // directory, that the `dep_name` dependency is installed to.
+    fn dep_output_dir(&self, dep_name: &str) -> &Path {
+        self.sections.iter()
+            .find(|section| section.dep_names.contains(dep_name))
+            .map_or(&self.output_dir, |section| section.output_dir.as_path())
+    }
+
+    // `output_dirs` returns `output_dir`, followed by the output directories
+    // of the sections of the dependency file.
+    fn output_dirs(&self) -> Vec<&Path> {
+        self.output_dir_defns()
+            .into_iter()
+            .map(|(_, output_dir)| output_dir)
+            .collect()
+    }
+
+    // `output_dir_defns` is like `output_dirs`, but each output directory is
+    // paired with the line that it's defined on.
+    fn output_dir_defns(&self) -> Vec<(usize, &Path)> {
+        iter::once((self.output_dir_ln_num, self.output_dir.as_path()))
+            .chain(
+                self.sections.iter()
+                    .map(|s| (s.ln_num, s.output_dir.as_path())),
+            )
+            .collect()
+    }
+
+    // `split_sections` returns a configuration without sections for each
+    // output directory in `output_dirs`, in the same order, so that the
+    // dependencies of each output directory can be installed separately.
+    fn split_sections(&self) -> Vec<DepsConf<'a, E>> {
+        self.output_dir_defns()
+            .into_iter()
+            .map(|(output_dir_ln_num, output_dir)| {
+                let mut deps = HashMap::new();
+                let mut ln_nums = HashMap::new();
+                let mut dep_files = HashMap::new();
+                for (name, dep) in &self.deps {
+                    if self.dep_output_dir(name) == output_dir {
+                        deps.insert(name.clone(), dep.clone());
+                        ln_nums.insert(name.clone(), self.ln_nums[name]);
+                        if let Some(path) = self.dep_files.get(name) {
+                            dep_files.insert(name.clone(), path.clone());
+                        }
+                    }
+                }
+
+                DepsConf{
+                    output_dir: output_dir.to_path_buf(),
+                    output_dir_ln_num,
+                    deps,
+                    ln_nums,
+                    dep_files,
+                    sections: vec![],
+                }
+            })
+            .collect()
+    }
+}
+
+// `ParsedDeps` contains the dependencies defined in a dependency file or a
+// state file.
+struct ParsedDeps<'a> {
+    deps: HashMap<String, Dependency<'a, DepToolError>>,
+    // `ln_nums` maps the name of each dependency that's defined, including
+    // those whose conditions don't hold, to the line that it's defined on.
+    ln_nums: HashMap<String, usize>,
+    sections: Vec<DepsSection>,
+    includes: Vec<DepsInclude>,
+}
+
+// `DepsInclude` is an include directive in a dependency file, such as
+// `include deps/test.txt`, which defines the dependencies in another file as
+// if they were defined in the dependency file.
+struct DepsInclude {
+    ln_num: usize,
+    // `path` is relative to the directory of the file that contains the
+    // directive.
+    path: String,
+    // `section` is the index of the section that the directive is in, if
+    // any.
+    section: Option<usize>,
+}
+
+// `DepsSection` is a named part of a dependency file whose dependencies are
+// installed to their own output directory.
+struct DepsSection {
+    name: String,
+    ln_num: usize,
+    // `output_dir` is relative to the project directory.
+    output_dir: PathBuf,
+    dep_names: HashSet<String>,
+    // `group` is defined if the section was declared by a group directive,
+    // in which case it contains the dependencies in the group that aren't in
+    // another section.
+    group: Option<String>,
+}
+
+// `check_version_directive` returns an error if the first line of `conts` is
+// a version directive that the running version of `dpnd` doesn't satisfy.
+fn check_version_directive(conts: &str) -> Result<(), ParseDepsConfError> {
+    let first_line = conts.lines().next().unwrap_or("").trim_end();
+    let Some(directive) =
+        first_line.strip_prefix(VERSION_DIRECTIVE_PREFIX)
+    else {
+        return Ok(());
+    };
+
+    let min_version = directive.trim().strip_prefix(">=").map(str::trim);
+    let maybe_min_parts = min_version.and_then(numeric_version);
+    let (Some(min_version), Some(min_parts)) =
+        (min_version, maybe_min_parts)
+    else {
+        return Err(ParseDepsConfError::InvalidVersionDirective{
+            directive: first_line.to_string(),
+        });
+    };
+
+    let cur_parts = numeric_version(env!("CARGO_PKG_VERSION"))
+        .expect("the version of `dpnd` isn't numeric");
+    if !version_at_least(&cur_parts, &min_parts) {
+        return Err(ParseDepsConfError::DpndTooOld{
+            min_version: min_version.to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+// `version_at_least` returns whether `vsn` is the same as, or later than,
+// `min`, where missing parts are treated as `0`, so that `0.3` is the same as
+// `0.3.0`.
+fn version_at_least(vsn: &[u64], min: &[u64]) -> bool {
+    let n = vsn.len().max(min.len());
+    let padded = |parts: &[u64]| -> Vec<u64> {
+        (0..n).map(|i| parts.get(i).copied().unwrap_or(0)).collect()
+    };
+
+    padded(vsn) >= padded(min)
+}
+
+// `parse_output_dir` returns the output directory at the start of `lines`,
+// along with the line that it's defined on.
+fn parse_output_dir(lines: &mut Enumerate<Lines>)
+    -> Result<(usize, PathBuf), ParseOutputDirError>
+{
+    for (i, line) in lines {
+        let ln = line.trim_start();
+        if ln.starts_with(SECTION_HEADER_START) {
+            // The output directory must come before the first section.
+            break;
+        }
+        if !conf_line_is_skippable(ln) {
+            let path = parse_output_dir_line(i + 1, ln)?;
+
+            return Ok((i + 1, path));
+        }
+    }
+
+    Err(ParseOutputDirError::MissingOutputDir)
+}
+
+// `parse_output_dir_line` parses `ln`, which is line `ln_num` of a dependency
+// file, as an output directory.
+fn parse_output_dir_line(ln_num: usize, ln: &str)
+    -> Result<PathBuf, ParseOutputDirError>
+{
+    if is_absolute_path(ln) {
+        return Err(ParseOutputDirError::AbsolutePath{
+            ln_num,
+            path: ln.trim_end().to_string(),
+        });
+    }
+
+    // We treat `\` as a path separator as well as `/`, so that `..` is
+    // rejected whichever separator precedes it, and so that output
+    // directories that are written with `\`, such as `target\deps`, don't
+    // become a single oddly named directory. This is the extent of the
+    // handling of Windows paths; `dpnd` only runs on Unix-like systems.
+    let mut path = PathBuf::new();
+    for part in ln.split(['/', '\\']) {
+        if part == "." || part == ".." {
+            return Err(ParseOutputDirError::InvalidPart{
+                ln_num,
+                part: part.to_string(),
+            });
+        }
+        path.push(part);
+    }
+
+    Ok(path)
+}
+
+// `check_section_output_dirs` returns an error if the output directory of a
+// section in `sections` is the same as, or contains or is contained by,
+// `output_dir` or the output directory of another section, because each
+// output directory has its own state file.
+fn check_section_output_dirs(output_dir: &Path, sections: &[DepsSection])
+    -> Result<(), ParseDepsConfError>
+{
+    for (i, section) in sections.iter().enumerate() {
+        let others =
+            iter::once((None, output_dir))
+                .chain(
+                    sections[..i].iter()
+                        .map(|other| {
+                            (Some(&other.name), other.output_dir.as_path())
+                        }),
+                );
+
+        for (other_section, other_output_dir) in others {
+            let overlaps =
+                section.output_dir.starts_with(other_output_dir)
+                    || other_output_dir.starts_with(&section.output_dir);
+
+            if overlaps {
+                return Err(ParseDepsConfError::SectionOutputDirsOverlap{
+                    ln_num: section.ln_num,
+                    section: section.name.clone(),
+                    output_dir: section.output_dir.clone(),
+                    other_section: other_section.cloned(),
+                    other_output_dir: other_output_dir.to_path_buf(),
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// `is_absolute_path` returns `true` if `path` starts with a path separator or
+// a Windows drive prefix, such as `C:`, regardless of the current platform.
+fn is_absolute_path(path: &str) -> bool {
+    let mut chars = path.chars();
+
+    match (chars.next(), chars.next()) {
+        (Some('/' | '\\'), _) => {
+            true
+        },
+        (Some(c), Some(':')) => {
+            c.is_ascii_alphabetic()
+        },
+        _ => {
+            false
+        },
+    }
+}
+
+// `fill_group_sections` adds the dependencies in each group that has its own
+// output directory to the section for the group, unless they're already in a
+// section.
+fn fill_group_sections<E>(
+    deps: &HashMap<String, Dependency<E>>,
+    sections: &mut [DepsSection],
+) {
+    let sectioned: HashSet<String> =
+        sections.iter()
+            .flat_map(|section| section.dep_names.iter().cloned())
+            .collect();
+    for section in sections {
+        if let Some(group) = &section.group {
+            section.dep_names =
+                deps.iter()
+                    .filter(|(name, dep)| {
+                        !sectioned.contains(*name)
+                            && dep.opts.group.as_ref() == Some(group)
+                    })
+                    .map(|(name, _)| name.clone())
+                    .collect();
+        }
+    }
+}
+
+// `include_path` returns the path in `words` if they're the fields of an
+// include directive, which has the form `include <path>`. Dependency
+// specifications have at least four fields, so they aren't mistaken for
+// include directives.
+fn include_path(words: &[String]) -> Option<&str> {
+    match words {
+        [keyword, path] if keyword == INCLUDE_DIR_KEYWORD => {
+            Some(path)
+        },
+        _ => {
+            None
+        },
+    }
+}
+
+fn conf_line_is_skippable(ln: &str) -> bool {
+    ln.is_empty() || ln.starts_with('#')
+}
+
+// `read_pinned_revision` returns the revision that `dep`, which is recorded
+// as installed at `dir` in the state file at `state_file_path`, is installed
+// at.
+fn read_pinned_revision(
+    dep_name: &str,
+    dep: &Dependency<'_, DepToolError>,
+    dir: PathBuf,
+    state_file_path: PathBuf,
+)
+    -> Result<String, InstallError<DepToolError>>
+{
+    let maybe_revision = installed_revision(dep, &dir)
+        .context(GetInstalledRevisionFailed{dep_name})?;
+
+    match maybe_revision {
+        Some(revision) => {
+            Ok(revision)
+        },
+        None if !dir.exists() => {
+            Err(InstallError::InstalledDepMissing{
+                dep_name: dep_name.to_string(),
+                path: dir,
+                state_file_path,
+            })
+        },
+        None => {
+            Err(InstallError::NoInstalledRevision{
+                dep_name: dep_name.to_string(),
+            })
+        },
+    }
+}
+
+// `pinned_deps_specs` returns the new contents of the root dependency file at
+// `deps_file_path`, whose contents are `deps_spec`, and of the files that it
+// includes, with the versions of `pinned_deps` replaced by their revisions.
+// Only the files that define `pinned_deps` are returned.
+fn pinned_deps_specs(
+    conf: &DepsConf<'_, DepToolError>,
+    deps_file_path: &Path,
+    deps_spec: &str,
+    pinned_deps: &[PinnedDep],
+)
+    -> Result<Vec<(PathBuf, String)>, InstallError<DepToolError>>
+{
+    let mut new_specs: Vec<(PathBuf, String)> = vec![];
+    for pinned_dep in pinned_deps {
+        let path = conf.dep_file_path(deps_file_path, &pinned_dep.name);
+        let existing = new_specs.iter().position(|(p, _)| p == path);
+        let i =
+            if let Some(i) = existing {
+                i
+            } else {
+                let spec =
+                    if path == deps_file_path {
+                        deps_spec.to_string()
+                    } else {
+                        fs::read_to_string(path)
+                            .context(ReadIncludedDepsFileFailed{path})?
+                    };
+                new_specs.push((path.to_path_buf(), spec));
+
+                new_specs.len() - 1
+            };
+
+        let (path, spec) = &mut new_specs[i];
+        let included = *path != deps_file_path;
+        *spec =
+            set_dep_version(
+                spec,
+                included,
+                &pinned_dep.name,
+                &pinned_dep.revision,
+            )
+                .context(ParseDepsFailed{})
+                .with_context(|| ParseDepsConfFailed{
+                    dep_path: vec![],
+                    path: path.clone(),
+                })?
+                .context(DepDefnNotFound{
+                    dep_name: &pinned_dep.name,
+                    path: path.clone(),
+                })?;
+    }
+
+    Ok(new_specs)
+}
+
+// `set_dep_version` returns `deps_spec` with the version of the `dep_name`
+// dependency replaced by `version`, or `None` if `deps_spec` doesn't define
+// `dep_name`. Other fields, comments and whitespace are kept as they are.
+// `included` is `true` if `deps_spec` is the contents of an included file,
+// which doesn't start with an output directory.
+fn set_dep_version(
+    deps_spec: &str,
+    included: bool,
+    dep_name: &str,
+    version: &Version,
+)
+    -> Result<Option<String>, ParseDepsError>
+{
+    // Each line is paired with the offset of its first byte in `deps_spec`.
+    let mut offset = 0;
+    let mut lines =
+        deps_spec.split_inclusive('\n')
+            .map(|line| {
+                let line_offset = offset;
+                offset += line.len();
+
+                (line_offset, line.trim_end_matches(['\r', '\n']))
+            })
+            .enumerate();
+
+    let mut output_dir_found = included;
+    while let Some((i, (line_offset, line))) = lines.next() {
+        if conf_line_is_skippable(line.trim_start()) {
+            continue;
+        }
+        if !output_dir_found {
+            output_dir_found = true;
+            continue;
+        }
+
+        // Fields are paired with the offset of the line that they're on, so
+        // that the version can be found if the dependency spans more than
+        // one line.
+        let (fields, mut continues) = split_field_spans(i + 1, line)?;
+        let mut fields: Vec<(usize, String, Range<usize>)> =
+            fields.into_iter()
+                .map(|(field, span)| (line_offset, field, span))
+                .collect();
+        while continues {
+            let Some((next_i, (next_offset, next_line))) = lines.next() else {
+                break;
+            };
+
+            let (next_fields, next_continues) =
+                split_field_spans(next_i + 1, next_line)?;
+            fields.extend(
+                next_fields.into_iter()
+                    .map(|(field, span)| (next_offset, field, span)),
+            );
+            continues = next_continues;
+        }
+
+        if fields.first().map(|(_, name, _)| name.as_str()) != Some(dep_name) {
+            continue;
+        }
+
+        // The version is the third field if the tool of the dependency is
+        // selected by the scheme of its source.
+        let version_idx =
+            match fields.get(1) {
+                Some((_, src, _)) if split_scheme_tool(src).is_some() => {
+                    2
+                },
+                _ => {
+                    3
+                },
+            };
+
+        if let Some((line_offset, _, span)) = fields.get(version_idx) {
+            let start = line_offset + span.start;
+            let end = line_offset + span.end;
+
+            return Ok(Some(format!(
+                "{}{}{}",
+                &deps_spec[..start],
+                quote_field(&version.0),
+                &deps_spec[end..],
+            )));
+        }
+    }
+
+    Ok(None)
+}
+
+// `format_deps_spec` returns `deps_spec`, which must be a valid dependency
+// file, in a canonical form:
+//
+// * Indentation and trailing whitespace are removed, runs of empty lines are
+//   replaced by a single empty line, and the file ends with a single newline.
+// * Section headers, group directives and include directives are kept in
+//   place, with a single space before their output directories or paths.
+// * Dependencies in each group of consecutive lines are sorted by name, and
+//   their names, tools, sources and versions are aligned in columns. Fields
+//   are otherwise separated by a single space, and keep their quoting.
+// * Comments are kept. Comments at the start of a group stay there, and other
+//   comments on the lines before a dependency, including annotations, are
+//   moved with it when dependencies are sorted.
+fn format_deps_spec(deps_spec: &str) -> Result<String, ParseDepsError> {
+    let mut out_lines: Vec<String> = vec![];
+    let mut group: Vec<FormatEntry> = vec![];
+    let mut comments: Vec<String> = vec![];
+    let mut seen_output_dir = false;
+
+    let mut lines = deps_spec.lines().enumerate();
+    while let Some((i, line)) = lines.next() {
+        let ln = line.trim();
+        if ln.starts_with('#') {
+            comments.push(ln.to_string());
+            continue;
+        }
+        if ln.is_empty() || ln.starts_with(SECTION_HEADER_START)
+            || ln.starts_with(GROUP_DIR_PREFIX) || !seen_output_dir
+        {
+            flush_format_group(&mut out_lines, &mut group, &mut comments);
+            if ln.is_empty() {
+                // Runs of empty lines, and empty lines at the start of the
+                // file, are dropped.
+                if out_lines.last().is_some_and(|l| !l.is_empty()) {
+                    out_lines.push(String::new());
+                }
+            } else if let Some(hdr) = ln.strip_prefix(SECTION_HEADER_START) {
+                match hdr.split_once(SECTION_HEADER_END) {
+                    Some((name, output_dir)) => {
+                        out_lines.push(format!(
+                            "{}{}{} {}",
+                            SECTION_HEADER_START,
+                            name,
+                            SECTION_HEADER_END,
+                            output_dir.trim(),
+                        ));
+                    },
+                    None => {
+                        out_lines.push(ln.to_string());
+                    },
+                }
+            } else if seen_output_dir && ln.starts_with(GROUP_DIR_PREFIX) {
+                match ln.split_once(char::is_whitespace) {
+                    Some((name, output_dir)) => {
+                        out_lines.push(
+                            format!("{} {}", name, output_dir.trim()),
+                        );
+                    },
+                    None => {
+                        out_lines.push(ln.to_string());
+                    },
+                }
+            } else {
+                seen_output_dir = true;
+                out_lines.push(ln.to_string());
+            }
+            continue;
+        }
+
+        let is_include =
+            split_fields(i + 1, line)
+                .ok()
+                .is_some_and(|(words, _)| include_path(&words).is_some());
+        if is_include {
+            flush_format_group(&mut out_lines, &mut group, &mut comments);
+            let path = ln[INCLUDE_DIR_KEYWORD.len()..].trim();
+            out_lines.push(format!("{} {}", INCLUDE_DIR_KEYWORD, path));
+            continue;
+        }
+
+        let mut entry_lines = vec![];
+        let mut cur = (i, line);
+        loop {
+            let (cur_i, cur_line) = cur;
+            let entry_line = FormatLine::split(cur_i + 1, cur_line)?;
+            let continues = entry_line.continues;
+            entry_lines.push(entry_line);
+            if !continues {
+                break;
+            }
+            match lines.next() {
+                Some(next) => {
+                    cur = next;
+                },
+                None => {
+                    break;
+                },
+            }
+        }
+
+        group.push(FormatEntry{
+            comments: mem::take(&mut comments),
+            lines: entry_lines,
+        });
+    }
+    flush_format_group(&mut out_lines, &mut group, &mut comments);
+
+    while out_lines.last().is_some_and(String::is_empty) {
+        out_lines.pop();
+    }
+
+    let mut formatted = out_lines.join("\n");
+    formatted.push('\n');
+
+    Ok(formatted)
+}
+
+// `FORMAT_ALIGNED_FIELDS` is the number of fields of a dependency that are
+// padded by `format_deps_spec`, so that the field after them is aligned.
+// These are the name, tool and source of the dependency, so that versions are
+// also aligned.
+const FORMAT_ALIGNED_FIELDS: usize = 3;
+
+// `FORMAT_CONTINUATION_INDENT` is the indentation of the lines that continue
+// the specification of a dependency.
+const FORMAT_CONTINUATION_INDENT: &str = "    ";
+
+// `FormatEntry` is a dependency specification, along with the comments on the
+// lines before it.
+struct FormatEntry {
+    comments: Vec<String>,
+    lines: Vec<FormatLine>,
+}
+
+impl FormatEntry {
+    fn name(&self) -> &str {
+        self.lines.first()
+            .and_then(|line| line.names.first())
+            .map_or("", String::as_str)
+    }
+}
+
+// `FormatLine` is a line of a dependency specification. `fields` contains the
+// text of each field as it's written in the dependency file, including any
+// quotes, and `names` contains the value of each field.
+struct FormatLine {
+    fields: Vec<String>,
+    names: Vec<String>,
+    continues: bool,
+    comment: Option<String>,
+}
+
+impl FormatLine {
+    fn split(ln_num: usize, line: &str) -> Result<FormatLine, ParseDepsError> {
+        let (field_spans, continues) = split_field_spans(ln_num, line)?;
+
+        let end = field_spans.last().map_or(0, |(_, span)| span.end);
+        let comment =
+            line[end..].find('#')
+                .map(|start| line[end + start..].trim_end().to_string());
+
+        let mut fields = vec![];
+        let mut names = vec![];
+        for (name, span) in field_spans {
+            fields.push(line[span].to_string());
+            names.push(name);
+        }
+
+        Ok(FormatLine{fields, names, continues, comment})
+    }
+}
+
+// `flush_format_group` sorts the dependencies in `group` by name and appends
+// them to `out_lines`, along with the comments before them, and then appends
+// `comments`, which aren't followed by a dependency in the group.
+fn flush_format_group(
+    out_lines: &mut Vec<String>,
+    group: &mut Vec<FormatEntry>,
+    comments: &mut Vec<String>,
+) {
+    // Comments at the start of the group describe the whole group, except
+    // for the annotation of the first dependency.
+    if let Some(first) = group.first_mut() {
+        let num_group_comments =
+            first.comments.iter()
+                .rposition(|c| !c.starts_with(ANNOTATION_PREFIX))
+                .map_or(0, |i| i + 1);
+        out_lines.extend(first.comments.drain(..num_group_comments));
+    }
+
+    group.sort_by(|a, b| a.name().cmp(b.name()));
+
+    // Fields are only padded if another field follows them, so that lines
+    // don't end with whitespace.
+    let mut widths = [0; FORMAT_ALIGNED_FIELDS];
+    for entry in group.iter() {
+        if let Some(line) = entry.lines.first() {
+            let num_padded = line.fields.len().saturating_sub(1);
+            let padded = line.fields.iter().take(num_padded);
+            for (width, field) in widths.iter_mut().zip(padded) {
+                *width = (*width).max(field.chars().count());
+            }
+        }
+    }
+
+    for entry in group.drain(..) {
+        out_lines.extend(entry.comments);
+
+        for (i, line) in entry.lines.into_iter().enumerate() {
+            let num_fields = line.fields.len();
+            let mut parts = vec![];
+            for (j, mut field) in line.fields.into_iter().enumerate() {
+                if i == 0 && j < FORMAT_ALIGNED_FIELDS && j + 1 < num_fields {
+                    let width = field.chars().count();
+                    field.push_str(&" ".repeat(widths[j] - width));
+                }
+                parts.push(field);
+            }
+            if line.continues {
+                parts.push("\\".to_string());
+            }
+            parts.extend(line.comment);
+
+            let indent = if i == 0 { "" } else { FORMAT_CONTINUATION_INDENT };
+            let out_line = format!("{}{}", indent, parts.join(" "));
+            out_lines.push(out_line.trim_end().to_string());
+        }
+    }
+
+    out_lines.append(comments);
+}
+
+// `split_continued_fields` is like `split_fields`, but it also splits the
+// lines that continue the specification on `line`, which are taken from
+// `lines`, and returns the fields of all of them.
+fn split_continued_fields(
+    lines: &mut Enumerate<Lines>,
+    ln_num: usize,
+    line: &str,
+)
+    -> Result<Vec<String>, ParseDepsError>
+{
+    let (mut words, mut continues) = split_fields(ln_num, line)?;
+    while continues {
+        let Some((next_i, next_line)) = lines.next() else {
+            break;
+        };
+
+        let (next_words, next_continues) =
+            split_fields(next_i + 1, next_line)?;
+        words.extend(next_words);
+        continues = next_continues;
+    }
+
+    Ok(words)
+}
+
+// `split_fields` splits the dependency specification on `line` into
+// whitespace-separated fields. A field can contain whitespace by enclosing it,
+// or part of it, in double quotes; `\"` and `\\` can be used within quotes to
+// include a double quote or a backslash. An unquoted `#` at the start of a
+// field starts a comment that runs to the end of the line. The returned
+// `bool` is `true` if the last field is an unquoted `\`, which continues the
+// specification on the next line; this field isn't returned.
+fn split_fields(ln_num: usize, line: &str)
+    -> Result<(Vec<String>, bool), ParseDepsError>
+{
+    let (fields, continues) = split_field_spans(ln_num, line)?;
+
+    Ok((fields.into_iter().map(|(field, _)| field).collect(), continues))
+}
+
+// `split_field_spans` is like `split_fields`, but each field is returned with
+// the range of bytes in `line` that it was read from, including any quotes.
+#[allow(clippy::type_complexity)]
+fn split_field_spans(ln_num: usize, line: &str)
+    -> Result<(Vec<(String, Range<usize>)>, bool), ParseDepsError>
+{
+    let mut fields = vec![];
+    let mut last_field_quoted = false;
+    // Columns are counted in characters, starting from 1.
+    let mut chars =
+        line.char_indices()
+            .enumerate()
+            .map(|(i, (offset, c))| (i + 1, offset, c))
+            .peekable();
+
+    loop {
+        while chars.next_if(|(_, _, c)| c.is_ascii_whitespace()).is_some() {
+        }
+
+        let start =
+            match chars.peek() {
+                Some((_, _, '#')) | None => {
+                    break;
+                },
+                Some((_, offset, _)) => {
+                    *offset
+                },
+            };
+
+        let mut field = String::new();
+        let mut quoted = false;
+        while let Some((col, _, c)) =
+            chars.next_if(|(_, _, c)| !c.is_ascii_whitespace())
+        {
+            if c != '"' {
+                field.push(c);
+                continue;
+            }
+
+            quoted = true;
+            loop {
+                match chars.next() {
+                    Some((_, _, '"')) => {
+                        break;
+                    },
+                    Some((esc_col, _, '\\')) => {
+                        match chars.next() {
+                            Some((_, _, c)) if c == '"' || c == '\\' => {
+                                field.push(c);
+                            },
+                            _ => {
+                                return Err(ParseDepsError::InvalidEscape{
+                                    ln_num,
+                                    col: esc_col,
+                                });
+                            },
+                        }
+                    },
+                    Some((_, _, c)) => {
+                        field.push(c);
+                    },
+                    None => {
+                        return Err(ParseDepsError::UnterminatedQuote{
+                            ln_num,
+                            col,
+                        });
+                    },
+                }
+            }
+        }
+
+        let end = chars.peek().map_or(line.len(), |(_, offset, _)| *offset);
+        fields.push((field, start..end));
+        last_field_quoted = quoted;
+    }
+
+    let continues =
+        !last_field_quoted
+            && fields.last().map(|(field, _)| field.as_str()) == Some("\\");
+    if continues {
+        fields.pop();
+    }
+
+    Ok((fields, continues))
+}
+
+// `check_dep` returns an error if the tool of `dep`, which is defined as
+// `dep_name` by `line` and the lines that continue it, rejects its source or
+// one of its mirrors. `words` are the fields of the specification of `dep`.
+fn check_dep(
+    ln_num: usize,
+    line: &str,
+    words: &[String],
+    dep_name: &str,
+    dep: &Dependency<DepToolError>,
+)
+    -> Result<(), ParseDepsError>
+{
+    check_dep_source(ln_num, line, words, dep)
+        .map_err(|(col, invalid)| {
+            ParseDepsError::InvalidDepSource{
+                ln_num,
+                col,
+                dep_name: dep_name.to_string(),
+                tool_name: dep.tool.name(),
+                reason: invalid.reason,
+            }
+        })?;
+
+    check_dep_mirrors(dep)
+        .map_err(|(mirror, invalid)| {
+            ParseDepsError::InvalidDepMirror{
+                ln_num,
+                dep_name: dep_name.to_string(),
+                tool_name: dep.tool.name(),
+                mirror,
+                reason: invalid.reason,
+            }
+        })
+}
+
+// `check_dep_source` returns an error if the tool of `dep` rejects its
+// source, along with the column of `line` at which the problem was found.
+// `line` is the first line of the specification of `dep`, and `words` are the
+// fields of the specification.
+fn check_dep_source(
+    ln_num: usize,
+    line: &str,
+    words: &[String],
+    dep: &Dependency<DepToolError>,
+)
+    -> Result<(), (Option<usize>, InvalidSource)>
+{
+    let invalid =
+        match dep.tool.validate_source(&dep.source) {
+            Ok(()) => {
+                return Ok(());
+            },
+            Err(invalid) => {
+                invalid
+            },
+        };
+
+    // The source is the second field if the tool is selected by the scheme
+    // of the source, as in `parse_dep_defn`.
+    let src_field_idx =
+        match words.get(1).and_then(|src| split_scheme_tool(src)) {
+            Some(_) => {
+                1
+            },
+            None => {
+                2
+            },
+        };
+    let col =
+        split_field_spans(ln_num, line)
+            .ok()
+            .and_then(|(fields, _)| fields.into_iter().nth(src_field_idx))
+            .map(|(field, span)| {
+                let mut start = span.start;
+                if line[start..].starts_with('"') {
+                    start += 1;
+                }
+                // The source may follow a scheme that selects its tool,
+                // which isn't part of `dep.source`.
+                let prefix_len =
+                    field.chars().count()
+                        .saturating_sub(dep.source.chars().count());
+
+                line[..start].chars().count() + prefix_len
+                    + invalid.char_idx + 1
+            });
+
+    Err((col, invalid))
+}
+
+// `check_dep_mirrors` returns an error if the tool of `dep` rejects one of its
+// mirrors, along with the mirror that was rejected.
+fn check_dep_mirrors(dep: &Dependency<DepToolError>)
+    -> Result<(), (String, InvalidSource)>
+{
+    for mirror in &dep.opts.mirrors {
+        dep.tool.validate_source(mirror)
+            .map_err(|invalid| (mirror.clone(), invalid))?;
+    }
+
+    Ok(())
+}
+
+// `quote_field` returns `field` in a form that `split_fields` parses as a
+// single field with the value `field`.
+fn quote_field(field: &str) -> String {
+    let needs_quotes =
+        field.is_empty()
+            || field.starts_with('#')
+            || field == "\\"
+            || field.chars().any(|c| c.is_ascii_whitespace() || c == '"');
+
+    if !needs_quotes {
+        return field.to_string();
+    }
+
+    let escaped = field.replace('\\', "\\\\").replace('"', "\\\"");
+
+    format!("\"{}\"", escaped)
+}
+
+#[derive(Debug, Snafu)]
+pub enum ParseOutputDirError {
+    MissingOutputDir,
+    InvalidPart{ln_num: usize, part: String},
+    AbsolutePath{ln_num: usize, path: String},
+}
+
+struct Dependency<'a, E> {
+    tool: &'a (dyn DepTool<E> + 'a),
+    source: String,
+    version: Version,
+    opts: DepOptions,
+}
+
+impl<E> Dependency<'_, E> {
+    // `with_env` runs `f`, which should run the tool of the dependency, with
+    // the environment variables defined by the `env.<VAR>` options of the
+    // dependency.
+    fn with_env<T, F>(&self, f: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        dep_tools::with_cmd_env(&self.opts.env, f)
+    }
+}
+
+impl<E> Clone for Dependency<'_, E> {
+    fn clone(&self) -> Self {
+        Dependency{
+            tool: self.tool,
+            source: self.source.clone(),
+            version: self.version.clone(),
+            opts: self.opts.clone(),
+        }
+    }
+}
+
+// `DepOptions` are the optional `key=value` settings that can follow the
+// version of a dependency in a dependency file.
+#[derive(Clone, Default)]
+struct DepOptions {
+    // `owner` identifies the team or person responsible for the dependency.
+    // It doesn't affect how the dependency is installed, so it isn't recorded
+    // in the state file.
+    owner: Option<String>,
+    // `verify` indicates whether the signature of the dependency should be
+    // verified after it's fetched.
+    verify: bool,
+    // `path` selects a single file to fetch, for tools that support it.
+    path: Option<String>,
+    // `group` is the name of the group that the dependency belongs to, which
+    // can be used to select dependencies to install. It's recorded in the
+    // state file so that the groups of installed dependencies are known.
+    group: Option<String>,
+    // `mirrors` are sources that are tried, in order, if the dependency can't
+    // be retrieved from its source.
+    mirrors: Vec<String>,
+    // `fetched_from` is the source that the dependency was retrieved from,
+    // if it has mirrors. It's recorded in the state file by `dpnd`, rather
+    // than being defined in dependency files, so it doesn't affect whether
+    // the dependency has changed.
+    fetched_from: Option<String>,
+    // `link` indicates whether the dependency should be fetched into the
+    // store, and if so, whether it should be installed as a symlink to its
+    // entry in the store or as a copy of it. Like a symlink, the copy is
+    // replaced rather than changed in place.
+    link: Option<LinkMode>,
+    // `archive` indicates whether the dependency should be installed as a
+    // clean export of its fetched revision, without tool metadata, like a
+    // vendored dependency.
+    archive: bool,
+    // `track` indicates that the version of the dependency is a branch, and
+    // that installed copies of the dependency should be fast-forwarded to the
+    // latest commit on the branch on every run.
+    track: bool,
+    // `tracked_revision` is the revision that a tracking dependency was last
+    // fetched or fast-forwarded to. Like `fetched_from`, it's recorded in the
+    // state file by `dpnd`, so it doesn't affect whether the dependency has
+    // changed.
+    tracked_revision: Option<String>,
+    // `installed_at` is the time, in seconds since the Unix epoch, at which
+    // the dependency was last installed or fast-forwarded, and
+    // `dpnd_version` and `tool_version` are the versions of `dpnd` and of the
+    // program used by the tool of the dependency at that time. Like
+    // `fetched_from`, they're recorded in the state file by `dpnd`, so they
+    // don't affect whether the dependency has changed.
+    installed_at: Option<u64>,
+    dpnd_version: Option<String>,
+    tool_version: Option<String>,
+    // `installed_revision` is the revision that the dependency was last
+    // installed or fast-forwarded to, if its tool could report it. Like
+    // `fetched_from`, it's recorded in the state file by `dpnd`, so it
+    // doesn't affect whether the dependency has changed.
+    installed_revision: Option<String>,
+    // `condition` restricts the dependency to environments that satisfy it.
+    // Dependencies whose conditions don't hold are treated as if they weren't
+    // defined.
+    condition: Option<DepCondition>,
+    // `env` contains the environment variables, defined by `env.<VAR>`
+    // options, that are added to the commands that the tool of the dependency
+    // runs. Like `owner`, it doesn't affect the installed files, and it may
+    // contain secrets, so it isn't recorded in the state file.
+    env: Vec<(String, String)>,
+    // `needs` contains the names of dependencies in the same output directory
+    // that must be installed before this dependency, such as because fetch
+    // hooks use them. Like `owner`, it doesn't affect the installed files,
+    // so it isn't recorded in the state file.
+    needs: Vec<String>,
+    // `chmod` contains the permission rules, defined by `chmod` options, that
+    // are applied to the files of the dependency after it's fetched.
+    chmod: Vec<PermRule>,
+}
+
+// `PermRule` sets the permissions of the regular files of a dependency that
+// match `glob` to `mode`, or of all of its regular files if `glob` is `None`.
+// Globs that contain a `/` are matched against the paths of files relative to
+// the output directory of the dependency, and other globs are matched against
+// the names of files.
+#[derive(Clone, PartialEq)]
+struct PermRule {
+    mode: u32,
+    glob: Option<String>,
+}
+
+impl PermRule {
+    fn parse(rule: &str) -> Option<Self> {
+        let (mode, glob) =
+            match rule.split_once(':') {
+                Some((mode, glob)) => (mode, Some(glob.to_string())),
+                None => (rule, None),
+            };
+
+        let is_octal = mode.chars().all(|c| ('0'..='7').contains(&c));
+        if !is_octal || !(3..=4).contains(&mode.len()) {
+            return None;
+        }
+        if glob.as_deref() == Some("") {
+            return None;
+        }
+
+        let mode = u32::from_str_radix(mode, 8).ok()?;
+
+        Some(PermRule{mode, glob})
+    }
+
+    fn matches(&self, rel_path: &str) -> bool {
+        match &self.glob {
+            Some(glob) if glob.contains('/') => {
+                glob_matches(glob, rel_path)
+            },
+            Some(glob) => {
+                let name = rel_path.rsplit('/').next().unwrap_or(rel_path);
+
+                glob_matches(glob, name)
+            },
+            None => {
+                true
+            },
+        }
+    }
+}
+
+impl fmt::Display for PermRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.glob {
+            Some(glob) => write!(f, "{:03o}:{}", self.mode, glob),
+            None => write!(f, "{:03o}", self.mode),
+        }
+    }
+}
+
+// `glob_matches` returns whether `glob` matches all of `s`. `*` in `glob`
+// matches any sequence of characters other than `/`, and `?` matches any
+// character other than `/`.
+fn glob_matches(glob: &str, s: &str) -> bool {
+    let glob: Vec<char> = glob.chars().collect();
+    let s: Vec<char> = s.chars().collect();
+
+    glob_matches_chars(&glob, &s)
+}
+
+fn glob_matches_chars(glob: &[char], s: &[char]) -> bool {
+    match (glob.first(), s.first()) {
+        (None, _) => {
+            s.is_empty()
+        },
+        (Some('*'), _) => {
+            glob_matches_chars(&glob[1..], s)
+                || (!s.is_empty()
+                    && s[0] != '/'
+                    && glob_matches_chars(glob, &s[1..]))
+        },
+        (Some('?'), Some(c)) if *c != '/' => {
+            glob_matches_chars(&glob[1..], &s[1..])
+        },
+        (Some(g), Some(c)) if g == c => {
+            glob_matches_chars(&glob[1..], &s[1..])
+        },
+        _ => {
+            false
+        },
+    }
+}
+
+// `ENV_OPT_PREFIX` is the prefix of dependency options that set environment
+// variables for the tool of the dependency.
+const ENV_OPT_PREFIX: &str = "env.";
+
+// `DepCondition` is a condition on the environment that `dpnd` is run in. It
+// holds if `var` is set to `value`, or, if `value` is `None`, if `var` is set
+// to a non-empty value.
+#[derive(Clone)]
+struct DepCondition {
+    var: String,
+    value: Option<String>,
+}
+
+impl DepCondition {
+    fn parse(cond: &str) -> Option<Self> {
+        let (var, value) =
+            match cond.split_once('=') {
+                Some((var, value)) => (var, Some(value.to_string())),
+                None => (cond, None),
+            };
+
+        if var.is_empty() {
+            return None;
+        }
+
+        Some(DepCondition{var: var.to_string(), value})
+    }
+
+    fn holds(&self) -> bool {
+        match (env::var_os(&self.var), &self.value) {
+            (Some(actual), Some(expected)) => actual == OsStr::new(expected),
+            (Some(actual), None) => !actual.is_empty(),
+            (None, _) => false,
+        }
+    }
+}
+
+fn parse_dep_opts(
+    ln_num: usize,
+    dep_name: &str,
+    line: &str,
+    words: &[String],
+    kind: DepsSpecKind,
+)
+    -> Result<DepOptions, ParseDepsError>
+{
+    let mut opts = DepOptions::default();
+
+    for word in words {
+        let (key, value) =
+            match word.split_once('=') {
+                Some((key, value)) if !key.is_empty() && !value.is_empty() => {
+                    (key, value)
+                },
+                _ => {
+                    return Err(ParseDepsError::InvalidDepSpec{
+                        ln_num,
+                        line: line.to_string(),
+                    });
+                },
+            };
+
+        if STATE_ONLY_DEP_OPTIONS.contains(&key) {
+            if !matches!(kind, DepsSpecKind::StateFile) {
+                return Err(ParseDepsError::StateOnlyDepOption{
+                    ln_num,
+                    dep_name: dep_name.to_string(),
+                    option: key.to_string(),
+                });
+            }
+            set_state_dep_opt(&mut opts, ln_num, line, key, value)?;
+        } else {
+            set_dep_opt(&mut opts, ln_num, dep_name, line, key, value)?;
+        }
+    }
+
+    Ok(opts)
+}
+
+// `set_state_dep_opt` sets the option of `opts` named by `key`, which must be
+// in `STATE_ONLY_DEP_OPTIONS`, to `value`, which were parsed from `line`.
+fn set_state_dep_opt(
+    opts: &mut DepOptions,
+    ln_num: usize,
+    line: &str,
+    key: &str,
+    value: &str,
+)
+    -> Result<(), ParseDepsError>
+{
+    match key {
+        "fetched_from" => {
+            opts.fetched_from = Some(value.to_string());
+        },
+        "tracked_revision" => {
+            opts.tracked_revision = Some(value.to_string());
+        },
+        "installed_at" => {
+            let t = value.parse()
+                .map_err(|_| ParseDepsError::InvalidDepSpec{
+                    ln_num,
+                    line: line.to_string(),
+                })?;
+            opts.installed_at = Some(t);
+        },
+        "dpnd_version" => {
+            opts.dpnd_version = Some(value.to_string());
+        },
+        "tool_version" => {
+            opts.tool_version = Some(value.to_string());
+        },
+        "installed_revision" => {
+            opts.installed_revision = Some(value.to_string());
+        },
+        _ => {
+            panic!("'{}' isn't a state-only dependency option", key);
+        },
+    }
+
+    Ok(())
+}
+
+// `set_dep_opt` sets the option of `opts` named by `key` to `value`, which
+// were parsed from `line`.
+fn set_dep_opt(
+    opts: &mut DepOptions,
+    ln_num: usize,
+    dep_name: &str,
+    line: &str,
+    key: &str,
+    value: &str,
+)
+    -> Result<(), ParseDepsError>
+{
+    let invalid_dep_spec = || {
+        ParseDepsError::InvalidDepSpec{ln_num, line: line.to_string()}
+    };
+
+    match key {
+        "owner" => {
+            opts.owner = Some(value.to_string());
+        },
+        "group" => {
+            opts.group = Some(value.to_string());
+        },
+        "path" => {
+            opts.path = Some(value.to_string());
+        },
+        "mirrors" => {
+            let mirrors: Vec<&str> = value.split(',').collect();
+            if mirrors.iter().any(|mirror| mirror.is_empty()) {
+                return Err(invalid_dep_spec());
+            }
+            opts.mirrors =
+                mirrors.into_iter()
+                    .map(ToString::to_string)
+                    .collect();
+        },
+        "needs" => {
+            opts.needs =
+                value.split(',')
+                    .map(ToString::to_string)
+                    .collect();
+        },
+        "chmod" => {
+            let rule = PermRule::parse(value).ok_or_else(invalid_dep_spec)?;
+            opts.chmod.push(rule);
+        },
+        "link" => {
+            match value {
+                "true" => {
+                    opts.link = Some(LinkMode::Symlink);
+                },
+                "copy" => {
+                    opts.link = Some(LinkMode::Copy);
+                },
+                "false" => {
+                    opts.link = None;
+                },
+                _ => {
+                    return Err(invalid_dep_spec());
+                },
+            }
+        },
+        "archive" => {
+            match value {
+                "true" => {
+                    opts.archive = true;
+                },
+                "false" => {
+                    opts.archive = false;
+                },
+                _ => {
+                    return Err(invalid_dep_spec());
+                },
+            }
+        },
+        "track" => {
+            if value != "branch" {
+                return Err(invalid_dep_spec());
+            }
+            opts.track = true;
+        },
+        "if" => {
+            let cond =
+                DepCondition::parse(value).ok_or_else(invalid_dep_spec)?;
+            opts.condition = Some(cond);
+        },
+        "verify" => {
+            if value != "gpg" {
+                return Err(ParseDepsError::UnknownVerifyMethod{
+                    ln_num,
+                    dep_name: dep_name.to_string(),
+                    method: value.to_string(),
+                });
+            }
+            opts.verify = true;
+        },
+        _ if key.starts_with(ENV_OPT_PREFIX) => {
+            let var = &key[ENV_OPT_PREFIX.len()..];
+            if var.is_empty() {
+                return Err(invalid_dep_spec());
+            }
+            opts.env.push((var.to_string(), value.to_string()));
+        },
+        _ => {
+            return Err(ParseDepsError::UnknownDepOption{
+                ln_num,
+                dep_name: dep_name.to_string(),
+                option: key.to_string(),
+            });
+        },
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Snafu)]
+pub enum ParseDepsError {
+    DupDepName{ln_num: usize, dep_name: String, orig_ln_num: usize},
+    // `bad_char_idx` is the index of the first invalid character in
+    // `dep_name`, counted in characters rather than bytes.
+    DepNameContainsInvalidChar{
+        ln_num: usize,
+        dep_name: String,
+        bad_char_idx: usize,
+    },
+    ReservedDepName{ln_num: usize, dep_name: String},
+    InvalidDepSpec{ln_num: usize, line: String},
+    UnknownTool{ln_num: usize, dep_name: String, tool_name: String},
+    UnknownDepOption{ln_num: usize, dep_name: String, option: String},
+    StateOnlyDepOption{ln_num: usize, dep_name: String, option: String},
+    UnknownVerifyMethod{ln_num: usize, dep_name: String, method: String},
+    MissingPathOption{ln_num: usize, dep_name: String, tool_name: String},
+    UnexpectedPathOption{ln_num: usize, dep_name: String, tool_name: String},
+    UnterminatedQuote{ln_num: usize, col: usize},
+    InvalidEscape{ln_num: usize, col: usize},
+    // `col` is `None` if the source isn't on line `ln_num`, because the
+    // dependency specification continues onto other lines.
+    InvalidDepSource{
+        ln_num: usize,
+        col: Option<usize>,
+        dep_name: String,
+        tool_name: String,
+        reason: String,
+    },
+    InvalidDepMirror{
+        ln_num: usize,
+        dep_name: String,
+        tool_name: String,
+        mirror: String,
+        reason: String,
+    },
+    InvalidSectionHeader{ln_num: usize, line: String},
+    InvalidGroupDir{ln_num: usize, line: String},
+    GroupDirNotInHeader{ln_num: usize},
+    DupSectionName{ln_num: usize, section: String, orig_ln_num: usize},
+    ParseSectionOutputDirFailed{
+        source: ParseOutputDirError,
+        section: String,
+    },
+    // `errs` contains at least two errors, in the order that they were found
+    // in the dependency file.
+    Multiple{errs: Vec<ParseDepsError>},
+}
+
+// `DepsSpecKind` is the kind of file that dependency specifications are
+// parsed from.
+#[derive(Clone, Copy)]
+enum DepsSpecKind {
+    // `validate_sources` indicates whether the sources and mirrors of
+    // dependencies are checked by their tools.
+    DepsFile{validate_sources: bool},
+    // The sources of dependencies in state files aren't validated, because
+    // they were accepted when the dependencies were installed. State files
+    // can also contain the options in `STATE_ONLY_DEP_OPTIONS`.
+    StateFile,
+}
+
+impl DepsSpecKind {
+    fn validates_sources(self) -> bool {
+        matches!(self, DepsSpecKind::DepsFile{validate_sources: true})
+    }
+}
+
+// `STATE_ONLY_DEP_OPTIONS` are the dependency options that `dpnd` records in
+// state files, and that can't be used in dependency files.
+const STATE_ONLY_DEP_OPTIONS: &[&str] = &[
+    "fetched_from",
+    "tracked_revision",
+    "installed_at",
+    "dpnd_version",
+    "tool_version",
+    "installed_revision",
+];
+
+#[allow(clippy::too_many_arguments)]
+fn install_deps<'a>(
+    output_dir: &Path,
+    state_file_path: Option<PathBuf>,
+    state_file_exists: bool,
+    mut cur_deps: HashMap<String, Dependency<'a, DepToolError>>,
+    mut new_deps: HashMap<String, Dependency<'a, DepToolError>>,
+    dep_filter: Option<&HashSet<String>>,
+    opts: &InstallOptions,
+    logger: &dyn Logger,
+)
+    -> Result<InstallOutcome, InstallDepsError<DepToolError>>
+{
+    if let Some(link) = opts.output.link {
+        for new_dep in new_deps.values_mut() {
+            // Dependencies with `link=copy` are still installed as copies.
+            if new_dep.opts.link != Some(LinkMode::Copy) {
+                new_dep.opts.link = Some(link);
+            }
+        }
+    }
+
+    // The `needs` options are checked before anything is changed, so that
+    // cycles are reported even if no dependency needs to be installed.
+    let needs_depths = needs_depths(&new_deps)?;
+
+    let mut actions = plan_actions(
+        output_dir,
+        &cur_deps,
+        &new_deps,
+        dep_filter,
+        opts,
+        logger,
+    )?;
+
+    logger.log(Level::Info, &render_plan(output_dir, &actions));
+
+    if opts.mode.checks_only() {
+        return check_actions(actions, &cur_deps, &new_deps, opts.mode);
+    }
+
+    let num_fast_forwarded = fast_forward_tracked_deps(
+        output_dir,
+        state_file_path.as_deref(),
+        &mut cur_deps,
+        &new_deps,
+        &mut actions,
+        dep_filter,
+        opts,
+        logger,
+    )?;
+
+    confirm_changes(
+        output_dir,
+        &cur_deps,
+        &new_deps,
+        &mut actions,
+        opts,
+        logger,
+    )?;
+
+    rename_deps(
+        output_dir,
+        state_file_path.as_deref(),
+        &mut cur_deps,
+        &new_deps,
+        &mut actions,
+        logger,
+    )?;
+
+    // Dependencies that are installed and that have no actions are left as
+    // they are, unless they were fast-forwarded.
+    let num_unchanged =
+        new_deps.keys()
+            .filter(|dep_name| {
+                cur_deps.contains_key(*dep_name)
+                    && !actions.iter().any(|(_, name)| name == *dep_name)
+            })
+            .count();
+    let mut outcome = InstallOutcome{
+        updated: num_fast_forwarded,
+        unchanged: num_unchanged.saturating_sub(num_fast_forwarded),
+        ..InstallOutcome::default()
+    };
+
+    if actions.is_empty() {
+        if let (false, Some(state_file_path)) =
+            (state_file_exists, state_file_path)
+        {
+            write_state_file(&state_file_path, &cur_deps)
+                .context(WriteInitialCurDepsFailed{state_file_path})?;
+        }
+        return Ok(outcome);
+    }
+
+    // Actions are taken from the end of `actions`, so dependencies are sorted
+    // by decreasing depth in order to install the dependencies that others
+    // need first. The sort is stable, and removed dependencies have no depth,
+    // so the order of other actions is kept.
+    actions.sort_by_key(|(_, dep_name)| {
+        Reverse(needs_depths.get(dep_name).copied().unwrap_or(0))
+    });
+
+    outcome.add(take_actions(
+        output_dir,
+        state_file_path.as_deref(),
+        cur_deps,
+        new_deps,
+        actions,
+        opts,
+        logger,
+    )?);
+
+    Ok(outcome)
+}
+
+// `plan_actions` returns the actions needed to install `new_deps` over
+// `cur_deps`, including the actions that repair installed dependencies, and
+// limited to the dependencies selected by `dep_filter` and `opts`.
+fn plan_actions<'a>(
+    output_dir: &Path,
+    cur_deps: &HashMap<String, Dependency<'a, DepToolError>>,
+    new_deps: &HashMap<String, Dependency<'a, DepToolError>>,
+    dep_filter: Option<&HashSet<String>>,
+    opts: &InstallOptions,
+    logger: &dyn Logger,
+)
+    -> Result<Vec<(Action, String)>, InstallDepsError<DepToolError>>
+{
+    let mut actions = actions(cur_deps, new_deps);
+    add_missing_dep_actions(
+        output_dir,
+        cur_deps,
+        new_deps,
+        &mut actions,
+        logger,
+    );
+    add_unverified_dep_actions(
+        output_dir,
+        cur_deps,
+        new_deps,
+        &mut actions,
+        opts,
+        logger,
+    )?;
+    if let Some(filter) = dep_filter {
+        actions.retain(|(_, dep_name)| filter.contains(dep_name));
+    }
+    if let Some(filter) = &opts.group_filter {
+        // The group of a dependency that is being removed is taken from the
+        // state file.
+        actions.retain(|(_, dep_name)| {
+            let dep = new_deps.get(dep_name)
+                .or_else(|| cur_deps.get(dep_name))
+                .unwrap_or_else(|| panic!(
+                    "dependency '{}' wasn't in the current or new \
+                     dependencies",
+                    dep_name,
+                ));
+
+            filter.selects(dep.opts.group.as_ref())
+        });
+    }
+
+    Ok(actions)
+}
+
+// `add_missing_dep_actions` adds an action to install each dependency in
+// `new_deps` that is installed and unchanged, but whose output directory
+// doesn't exist.
+fn add_missing_dep_actions<'a>(
+    output_dir: &Path,
+    cur_deps: &HashMap<String, Dependency<'a, DepToolError>>,
+    new_deps: &HashMap<String, Dependency<'a, DepToolError>>,
+    actions: &mut Vec<(Action, String)>,
+    logger: &dyn Logger,
+) {
+    // The state file can drift from the output directory, for example if a
+    // dependency was removed by hand, or if the store entry of a linked
+    // dependency was removed by `dpnd prune-cache`. Dependencies whose
+    // directories don't exist are treated as not installed. `exists` follows
+    // symlinks, so this also finds dangling links.
+    for dep_name in new_deps.keys() {
+        let unchanged = cur_deps.contains_key(dep_name)
+            && !actions.iter().any(|(_, name)| name == dep_name);
+        let dir = output_dir.join(dep_name);
+        if unchanged && !dir.exists() {
+            logger.log(Level::Warn, &format!(
+                "'{}' is recorded as installed, but '{}' doesn't exist, so \
+                 it will be installed again",
+                dep_name,
+                dir.display(),
+            ));
+            actions.push((Action::Install, dep_name.clone()));
+        }
+    }
+}
+
+// `add_unverified_dep_actions` adds an action to install each dependency in
+// `new_deps` that is installed and unchanged, but whose output directory
+// doesn't match its vendor file, if `opts` vendors dependencies, or its
+// recorded revision, if `opts` checks the integrity of dependencies.
+fn add_unverified_dep_actions<'a>(
+    output_dir: &Path,
+    cur_deps: &HashMap<String, Dependency<'a, DepToolError>>,
+    new_deps: &HashMap<String, Dependency<'a, DepToolError>>,
+    actions: &mut Vec<(Action, String)>,
+    opts: &InstallOptions,
+    logger: &dyn Logger,
+)
+    -> Result<(), InstallDepsError<DepToolError>>
+{
+    if opts.output.vendor {
+        for (dep_name, new_dep) in new_deps {
+            let unchanged = cur_deps.contains_key(dep_name)
+                && !actions.iter().any(|(_, name)| name == dep_name);
+            if !unchanged {
+                continue;
+            }
+
+            let vendor_file_path =
+                output_dir.join(dep_name).join(VENDOR_FILE_NAME);
+            let verified = vendor_file_matches(&vendor_file_path, new_dep)
+                .context(ReadVendorFileFailed{
+                    dep_name: dep_name.clone(),
+                    path: vendor_file_path,
+                })?;
+
+            if !verified {
+                actions.push((Action::Install, dep_name.clone()));
+            }
+        }
+    }
+    if opts.state.check_integrity {
+        for (dep_name, new_dep) in new_deps {
+            let unchanged = cur_deps.contains_key(dep_name)
+                && !actions.iter().any(|(_, name)| name == dep_name);
+            let maybe_recorded_revision =
+                cur_deps.get(dep_name)
+                    .and_then(|dep| dep.opts.installed_revision.as_ref());
+            let recorded_revision =
+                match maybe_recorded_revision {
+                    Some(revision) if unchanged => {
+                        revision
+                    },
+                    _ => {
+                        continue;
+                    },
+                };
+
+            // A revision that can't be read, such as because the tool
+            // metadata of the dependency was removed, is treated as a
+            // mismatch, so that the dependency is repaired.
+            let dir = output_dir.join(dep_name);
+            let revision = installed_revision(new_dep, &dir).ok().flatten();
+            if revision.as_ref() != Some(recorded_revision) {
+                logger.log(Level::Warn, &format!(
+                    "'{}' is recorded as installed at '{}', but '{}' is at \
+                     '{}', so it will be installed again",
+                    dep_name,
+                    recorded_revision,
+                    dir.display(),
+                    revision.as_deref().unwrap_or("an unknown revision"),
+                ));
+                actions.push((Action::Install, dep_name.clone()));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// `check_actions` returns an error describing `actions` if there are any, for
+// modes that only check whether the dependencies are up to date.
+fn check_actions<'a>(
+    actions: Vec<(Action, String)>,
+    cur_deps: &HashMap<String, Dependency<'a, DepToolError>>,
+    new_deps: &HashMap<String, Dependency<'a, DepToolError>>,
+    mode: InstallMode,
+)
+    -> Result<InstallOutcome, InstallDepsError<DepToolError>>
+{
+    if actions.is_empty() {
+        return Ok(InstallOutcome::default());
+    }
+
+    if mode == InstallMode::Frozen {
+        let changes = planned_changes(&actions, cur_deps, new_deps);
+
+        return Err(InstallDepsError::FrozenDepsChanged{changes});
+    }
+
+    let mut dep_names: Vec<String> =
+        actions.into_iter()
+            .map(|(_, dep_name)| dep_name)
+            .collect();
+    dep_names.sort();
+
+    Err(InstallDepsError::DepsOutOfDate{dep_names})
+}
+
+// `confirm_changes` asks `opts.prompter` to confirm the actions that discard
+// local modifications to dependencies, and then those that `confirm_actions`
+// asks about, and removes the actions that aren't confirmed. Actions that
+// discard local modifications fail if there's no prompter, unless
+// `opts.force` is set.
+fn confirm_changes<'a>(
+    output_dir: &Path,
+    cur_deps: &HashMap<String, Dependency<'a, DepToolError>>,
+    new_deps: &HashMap<String, Dependency<'a, DepToolError>>,
+    actions: &mut Vec<(Action, String)>,
+    opts: &InstallOptions,
+    logger: &dyn Logger,
+)
+    -> Result<(), InstallDepsError<DepToolError>>
+{
+    // `confirmed` holds the dependencies whose actions have already been
+    // confirmed, so that the user isn't asked about them twice.
+    let mut confirmed = HashSet::new();
+    if !opts.force {
+        let mut dep_names = vec![];
+        for (_, dep_name) in actions.iter() {
+            let Some(cur_dep) = cur_deps.get(dep_name) else {
+                continue;
+            };
+
+            let dir = output_dir.join(dep_name);
+            if !dir.exists() {
+                continue;
+            }
+
+            let modified = cur_dep.tool.is_modified(&dir)
+                .with_context(||
+                    CheckModifiedFailed{dep_name: dep_name.clone()}
+                )?;
+            if modified {
+                dep_names.push(dep_name.clone());
+            }
+        }
+
+        if !dep_names.is_empty() {
+            dep_names.sort();
+
+            let Some(prompter) = opts.prompter else {
+                return Err(InstallDepsError::DepsModified{dep_names});
+            };
+            for dep_name in dep_names {
+                let question = format!(
+                    "'{}' has been modified locally; discard the changes?",
+                    output_dir.join(&dep_name).display(),
+                );
+                if prompter.confirm(&question) {
+                    confirmed.insert(dep_name);
+                } else {
+                    skip_action(actions, &dep_name, logger);
+                }
+            }
+        }
+    }
+
+    if let Some(prompter) = opts.prompter {
+        confirm_actions(
+            output_dir,
+            cur_deps,
+            new_deps,
+            actions,
+            &confirmed,
+            prompter,
+            logger,
+        );
+    }
+
+    Ok(())
+}
+
+// `take_actions` takes `actions`, from the last to the first, and returns the
+// changes that were made. The state file at `state_file_path`, if any, is
+// written after each action.
+fn take_actions<'a>(
+    output_dir: &Path,
+    state_file_path: Option<&Path>,
+    mut cur_deps: HashMap<String, Dependency<'a, DepToolError>>,
+    mut new_deps: HashMap<String, Dependency<'a, DepToolError>>,
+    mut actions: Vec<(Action, String)>,
+    opts: &InstallOptions,
+    logger: &dyn Logger,
+)
+    -> Result<InstallOutcome, InstallDepsError<DepToolError>>
+{
+    let mut outcome = InstallOutcome::default();
+    while let Some((act, dep_name)) = actions.pop() {
+        // The state file is written after each action, so stopping between
+        // actions leaves it consistent with the output directory.
+        if interrupt::interrupted() {
+            return Err(InstallDepsError::Interrupted);
+        }
+
+        let dir = output_dir.join(&dep_name);
+        // Dependencies whose directories are missing are counted as
+        // installed rather than updated.
+        let replaced =
+            cur_deps.contains_key(&dep_name)
+                && fs::symlink_metadata(&dir).is_ok();
+        let reusable = remove_for_action(
+            &act,
+            &dep_name,
+            &dir,
+            &mut cur_deps,
+            &new_deps,
+            state_file_path,
+            logger,
+        )?;
+
+        if act != Action::Install {
+            outcome.removed += 1;
+            continue;
+        }
+
+        let mut new_dep = new_deps.remove(&dep_name)
+            .unwrap_or_else(|| panic!(
+                "dependency '{}' wasn't in the map of current dependencies",
+                dep_name,
+            ));
+
+        let fetch_start = Instant::now();
+        let fetch_result = fetch_for_install(
+            &dep_name,
+            &new_dep,
+            &dir,
+            reusable,
+            opts,
+            logger,
+        );
+        let (content_dir, fetched_from) =
+            match fetch_result {
+                Ok(fetched) => {
+                    fetched
+                },
+                Err(InstallDepsError::Interrupted) => {
+                    return Err(InstallDepsError::Interrupted);
+                },
+                Err(err) if opts.fetch.keep_going => {
+                    // The state file doesn't list the dependency at this
+                    // point, so it will be installed again by the next run.
+                    logger.log(Level::Warn, &format!(
+                        "Couldn't fetch '{}'; installing the other \
+                         dependencies",
+                        dep_name,
+                    ));
+                    outcome.failed.push((dir, err));
+                    continue;
+                },
+                Err(err) => {
+                    return Err(err);
+                },
+            };
+        // Dependencies that were already in the store weren't fetched, so
+        // they don't have a source.
+        if fetched_from.is_some() {
+            let duration = fetch_start.elapsed();
+            outcome.fetches.push(
+                fetch_stats(&dep_name, &dir, &content_dir, duration, logger),
+            );
+        }
+        if !new_dep.opts.mirrors.is_empty() {
+            new_dep.opts.fetched_from = fetched_from;
+        }
+
+        let installed_revision = install_fetched_dep(
+            &dep_name,
+            &mut new_dep,
+            &dir,
+            &content_dir,
+            opts,
+        )?;
+
+        record_install(
+            &mut new_dep,
+            opts.output.source_date_epoch,
+            installed_revision,
+        );
+        cur_deps.insert(dep_name.clone(), new_dep);
+
+        if let Some(state_file_path) = state_file_path {
+            write_state_file(state_file_path, &cur_deps)
+                .with_context(|| WriteCurDepsAfterInstallFailed{
+                    dep_name: dep_name.clone(),
+                    state_file_path: state_file_path.to_path_buf(),
+                })?;
+        }
+
+        if replaced {
+            outcome.updated += 1;
+        } else {
+            outcome.installed += 1;
+        }
+    }
+
+    Ok(outcome)
+}
+
+// `remove_for_action` removes `dep_name` from `cur_deps` before `act` is
+// taken, and removes `dir`, its output directory, unless `act` can change
+// the version of the dependency in place. It returns whether `dir` was kept
+// so that it can be changed in place.
+fn remove_for_action<'a>(
+    act: &Action,
+    dep_name: &str,
+    dir: &Path,
+    cur_deps: &mut HashMap<String, Dependency<'a, DepToolError>>,
+    new_deps: &HashMap<String, Dependency<'a, DepToolError>>,
+    state_file_path: Option<&Path>,
+    logger: &dyn Logger,
+)
+    -> Result<bool, InstallDepsError<DepToolError>>
+{
+    // The dependency may have been installed with `--readonly`, in which
+    // case its directories must be made writable before their contents can
+    // be removed.
+    if let Err(source) = set_writable(dir, true) {
+        if source.kind() != ErrorKind::NotFound {
+            return Err(InstallDepsError::MakeDepWritableFailed{
+                source,
+                dep_name: dep_name.to_string(),
+                path: dir.to_path_buf(),
+            });
+        }
+    }
+    // Dependencies whose version can be changed in place are only removed if
+    // changing their version fails.
+    let reusable = *act == Action::Install && can_change_version(
+        cur_deps.get(dep_name),
+        new_deps.get(dep_name),
+        dir,
+    );
+    if !reusable {
+        remove_dep_dir(dep_name, dir, logger)?;
+    }
+    cur_deps.remove(dep_name);
+
+    if let Some(state_file_path) = state_file_path {
+        write_state_file(state_file_path, cur_deps)
+            .with_context(|| WriteCurDepsAfterRemoveFailed{
+                dep_name: dep_name.to_string(),
+                state_file_path: state_file_path.to_path_buf(),
+            })?;
+    }
+
+    Ok(reusable)
+}
+
+// `fetch_for_install` fetches `new_dep`, and returns the directory that it
+// was fetched to and the source that it was fetched from. `dir`, the output
+// directory of the dependency, is changed in place if it's `reusable`. The
+// fetch is reported to the hook in `opts`, if any.
+fn fetch_for_install(
+    dep_name: &str,
+    new_dep: &Dependency<'_, DepToolError>,
+    dir: &Path,
+    reusable: bool,
+    opts: &InstallOptions,
+    logger: &dyn Logger,
+)
+    -> Result<(PathBuf, Option<String>), InstallDepsError<DepToolError>>
+{
+    let fetch_event = |outcome| FetchEvent{
+        dep_name,
+        source: &new_dep.source,
+        version: &new_dep.version.0,
+        dir,
+        outcome,
+    };
+    if let Some(hook) = opts.fetch.hook {
+        hook(&fetch_event(None));
+    }
+
+    let fetch_start = Instant::now();
+    let fetch_result = (|| {
+        let changed_in_place =
+            reusable && change_version_in_place(
+                dep_name,
+                new_dep,
+                dir,
+                opts,
+                logger,
+            )?;
+        if changed_in_place {
+            Ok((dir.to_path_buf(), Some(new_dep.source.clone())))
+        } else if new_dep.opts.link.is_some() {
+            fetch_into_store(dep_name, new_dep, opts, logger)
+        } else {
+            if reusable {
+                remove_dep_dir(dep_name, dir, logger)?;
+            }
+            let fetched_from =
+                fetch_dep(dep_name, new_dep, dir, opts, logger)?;
+
+            Ok((dir.to_path_buf(), fetched_from))
+        }
+    })();
+
+    if let Some(hook) = opts.fetch.hook {
+        hook(&fetch_event(Some(FetchOutcome{
+            duration: fetch_start.elapsed(),
+            succeeded: fetch_result.is_ok(),
+        })));
+    }
+
+    fetch_result
+}
+
+// `fetch_stats` returns the statistics of the fetch of `dep_name` into
+// `content_dir`, which took `duration`, and logs them.
+fn fetch_stats(
+    dep_name: &str,
+    dir: &Path,
+    content_dir: &Path,
+    duration: Duration,
+    logger: &dyn Logger,
+)
+    -> FetchStats
+{
+    // The size of a dependency is only used for reporting, so a failure to
+    // measure it doesn't stop the dependency from being installed.
+    let bytes =
+        match dir_size(content_dir) {
+            Ok(bytes) => {
+                logger.log(Level::Info, &format!(
+                    "Fetched '{}' in {:.3}s ({} bytes)",
+                    dep_name,
+                    duration.as_secs_f64(),
+                    bytes,
+                ));
+
+                Some(bytes)
+            },
+            Err(err) => {
+                logger.log(Level::Warn, &format!(
+                    "Fetched '{}' in {:.3}s, but couldn't measure its size: \
+                     {}",
+                    dep_name,
+                    duration.as_secs_f64(),
+                    err,
+                ));
+
+                None
+            },
+        };
+
+    FetchStats{
+        dep_name: dep_name.to_string(),
+        dir: dir.to_path_buf(),
+        bytes,
+        duration,
+    }
+}
+
+// `install_fetched_dep` verifies and prepares `new_dep`, which was fetched to
+// `content_dir`, according to its options and `opts`, and installs it to
+// `dir` if it was fetched into the store. It returns the revision that the
+// dependency was fetched at, if its tool can report it.
+fn install_fetched_dep(
+    dep_name: &str,
+    new_dep: &mut Dependency<'_, DepToolError>,
+    dir: &Path,
+    content_dir: &Path,
+    opts: &InstallOptions,
+)
+    -> Result<Option<String>, InstallDepsError<DepToolError>>
+{
+    if new_dep.opts.track {
+        let revision = new_dep.tool.revision(content_dir)
+            .with_context(||
+                GetTrackedRevisionFailed{dep_name: dep_name.to_string()}
+            )?;
+        new_dep.opts.tracked_revision = Some(revision);
+    }
+    // The revision is taken before the dependency is vendored, which removes
+    // the tool metadata that it's read from. Dependencies whose tools can't
+    // report their revisions aren't checked by `check_integrity`.
+    let installed_revision = new_dep.tool.revision(content_dir).ok();
+
+    if new_dep.opts.verify {
+        let result = new_dep.tool.verify(content_dir, &new_dep.version);
+        if let Err(source) = result {
+            // We remove the unverified dependency so that it can't be used by
+            // mistake. The state file doesn't list the dependency at this
+            // point, so a failure to remove it will be handled by the next
+            // run.
+            let _ = fs::remove_dir_all(content_dir);
+
+            return Err(InstallDepsError::VerifySignatureFailed{
+                source,
+                dep_name: dep_name.to_string(),
+            });
+        }
+    }
+
+    let mut mtime = None;
+    if opts.output.normalize_mtimes {
+        if let Some(t) = opts.output.source_date_epoch {
+            mtime = Some(t);
+        } else {
+            let t = new_dep.tool.revision_time(content_dir)
+                .with_context(||
+                    GetRevisionTimeFailed{dep_name: dep_name.to_string()}
+                )?;
+            mtime = Some(t);
+        }
+    }
+
+    if opts.output.vendor || new_dep.opts.archive {
+        vendor_dep(new_dep, dir)
+            .context(VendorDepFailed{dep_name: dep_name.to_string()})?;
+    }
+
+    if !new_dep.opts.chmod.is_empty() {
+        apply_perm_rules(content_dir, "", &new_dep.opts.chmod)
+            .context(SetPermissionsFailed{dep_name, path: content_dir})?;
+    }
+
+    if let Some(t) = mtime {
+        let time = UNIX_EPOCH + Duration::from_secs(t);
+        set_mtimes(content_dir, time)
+            .context(NormalizeMtimesFailed{dep_name: dep_name.to_string()})?;
+    }
+
+    if opts.output.readonly {
+        set_writable(content_dir, false)
+            .context(MakeDepReadonlyFailed{dep_name, path: content_dir})?;
+    }
+
+    match new_dep.opts.link {
+        Some(LinkMode::Copy) => {
+            copy_dir(content_dir, dir)
+                .context(CopyDepFromStoreFailed{dep_name, path: dir})?;
+        },
+        Some(LinkMode::Symlink) => {
+            symlink(content_dir, dir)
+                .context(LinkDepOutputDirFailed{dep_name, path: dir})?;
+        },
+        None => {},
+    }
+
+    Ok(installed_revision)
+}
+
+// `remove_dep_dir` removes `dir`, the output directory of `dep_name`, if it
+// exists.
+fn remove_dep_dir(dep_name: &str, dir: &Path, logger: &dyn Logger)
+    -> Result<(), InstallDepsError<DepToolError>>
+{
+    logger.log(Level::Debug, &format!("Removing '{}'", dir.display()));
+    if let Err(source) = fs::remove_dir_all(dir) {
+        if source.kind() != ErrorKind::NotFound {
+            return Err(InstallDepsError::RemoveOldDepOutputDirFailed{
+                source,
+                dep_name: dep_name.to_string(),
+                path: dir.to_path_buf(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+// `can_change_version` returns whether the version of `cur_dep`, which is
+// installed in `dir`, can be changed in place to that of `new_dep`, which is
+// only the case if both are fetched in the same way from the same source.
+fn can_change_version<'a>(
+    cur_dep: Option<&Dependency<'a, DepToolError>>,
+    new_dep: Option<&Dependency<'a, DepToolError>>,
+    dir: &Path,
+)
+    -> bool
+{
+    let (Some(cur_dep), Some(new_dep)) = (cur_dep, new_dep) else {
+        return false;
+    };
+
+    // Linked dependencies are symlinks to entries in the store, which may be
+    // shared with other projects, so they're never changed in place.
+    let is_dir =
+        fs::symlink_metadata(dir)
+            .is_ok_and(|md| md.is_dir());
+
+    is_dir
+        && cur_dep.opts.link.is_none()
+        && new_dep.opts.link.is_none()
+        && !cur_dep.opts.archive
+        && !new_dep.opts.archive
+        && cur_dep.tool.name() == new_dep.tool.name()
+        && cur_dep.source == new_dep.source
+        && cur_dep.opts.path == new_dep.opts.path
+}
+
+// `change_version_in_place` changes the version of the dependency in `dir` to
+// the version of `dep`. It returns `false` if the version couldn't be changed,
+// in which case the dependency must be fetched again.
+fn change_version_in_place(
+    dep_name: &str,
+    dep: &Dependency<'_, DepToolError>,
+    dir: &Path,
+    opts: &InstallOptions,
+    logger: &dyn Logger,
+)
+    -> Result<bool, InstallDepsError<DepToolError>>
+{
+    let deadline = opts.fetch.timeout.map(|t| Instant::now() + t);
+    let result = dep.with_env(|| {
+        dep.tool.change_version(&dep.source, dir, &dep.version, deadline)
+    });
+
+    match result {
+        Ok(true) => {
+            logger.log(Level::Info, &format!(
+                "Changed the version of '{}' to '{}' in place",
+                dep_name,
+                dep.version,
+            ));
+
+            Ok(true)
+        },
+        Ok(false) => {
+            Ok(false)
+        },
+        Err(_) => {
+            // The dependency isn't listed in the state file at this point,
+            // so the next run removes it and fetches it again.
+            if interrupt::interrupted() {
+                return Err(InstallDepsError::Interrupted);
+            }
+
+            logger.log(Level::Warn, &format!(
+                "Couldn't change the version of '{}' in place, so it will be \
+                 fetched again",
+                dep_name,
+            ));
+
+            Ok(false)
+        },
+    }
+}
+
+// `confirm_actions` asks `prompter` to confirm each action in `actions` that
+// removes a dependency or downgrades it, and removes the actions that aren't
+// confirmed. The actions of the dependencies in `confirmed` aren't confirmed
+// again.
+#[allow(clippy::too_many_arguments)]
+fn confirm_actions<'a>(
+    output_dir: &Path,
+    cur_deps: &HashMap<String, Dependency<'a, DepToolError>>,
+    new_deps: &HashMap<String, Dependency<'a, DepToolError>>,
+    actions: &mut Vec<(Action, String)>,
+    confirmed: &HashSet<String>,
+    prompter: &dyn Prompter,
+    logger: &dyn Logger,
+) {
+    // We ask about dependencies in order of name so that the questions are
+    // asked in the same order on each run.
+    let mut questions: Vec<(String, String)> =
+        actions.iter()
+            .filter(|(_, dep_name)| !confirmed.contains(dep_name))
+            .filter_map(|(act, dep_name)| {
+                let dir = output_dir.join(dep_name);
+                let cur_dep = cur_deps.get(dep_name)?;
+
+                match act {
+                    Action::Remove => {
+                        Some(format!(
+                            "Remove the dependency '{}' ('{}')?",
+                            dep_name,
+                            dir.display(),
+                        ))
+                    },
+                    Action::Install => {
+                        let new_dep = new_deps.get(dep_name)?;
+                        if !is_downgrade(&cur_dep.version, &new_dep.version) {
+                            return None;
+                        }
+
+                        Some(format!(
+                            "Downgrade '{}' from '{}' to '{}'?",
+                            dep_name,
+                            cur_dep.version,
+                            new_dep.version,
+                        ))
+                    },
+                }
+                    .map(|question| (dep_name.clone(), question))
+            })
+            .collect();
+    questions.sort();
+
+    for (dep_name, question) in questions {
+        if !prompter.confirm(&question) {
+            skip_action(actions, &dep_name, logger);
+        }
+    }
+}
+
+fn skip_action(
+    actions: &mut Vec<(Action, String)>,
+    dep_name: &str,
+    logger: &dyn Logger,
+) {
+    actions.retain(|(_, name)| name != dep_name);
+    logger.log(Level::Info, &format!(
+        "Skipped '{}' because the action wasn't confirmed",
+        dep_name,
+    ));
+}
+
+// `is_downgrade` returns whether `new` is an earlier version than `old`. Only
+// versions that consist of numbers separated by dots, optionally prefixed
+// with `v`, such as `v1.2.3`, can be compared; other versions, such as
+// commit hashes, are never considered to be downgrades.
+fn is_downgrade(old: &Version, new: &Version) -> bool {
+    match (numeric_version(&old.0), numeric_version(&new.0)) {
+        (Some(old), Some(new)) => {
+            new < old
+        },
+        _ => {
+            false
+        },
+    }
+}
+
+fn numeric_version(vsn: &str) -> Option<Vec<u64>> {
+    let vsn = vsn.strip_prefix('v').unwrap_or(vsn);
+
+    vsn.split('.')
+        .map(|part| part.parse().ok())
+        .collect()
+}
+
+// `fast_forward_tracked_deps` fast-forwards the installed dependencies that
+// track a branch, and that aren't otherwise being changed, to the latest
+// commit on their branch, and records the new revisions in the state file,
+// if `state_file_path` is defined.
+// Dependencies whose tools can't fast-forward them, and vendored
+// dependencies, are added to `actions` so that they're fetched again. Linked
+// dependencies are skipped because their store entries are shared. The number
+// of dependencies that were fast-forwarded is returned.
+#[allow(clippy::too_many_arguments)]
+fn fast_forward_tracked_deps<'a>(
+    output_dir: &Path,
+    state_file_path: Option<&Path>,
+    cur_deps: &mut HashMap<String, Dependency<'a, DepToolError>>,
+    new_deps: &HashMap<String, Dependency<'a, DepToolError>>,
+    actions: &mut Vec<(Action, String)>,
+    dep_filter: Option<&HashSet<String>>,
+    opts: &InstallOptions,
+    logger: &dyn Logger,
+)
+    -> Result<usize, InstallDepsError<DepToolError>>
+{
+    let mut dep_names: Vec<&String> =
+        new_deps.iter()
+            .filter(|(dep_name, new_dep)| {
+                new_dep.opts.track
+                    && new_dep.opts.link.is_none()
+                    && cur_deps.contains_key(*dep_name)
+                    && !actions.iter().any(|(_, name)| name == *dep_name)
+                    && dep_filter.is_none_or(|f| f.contains(*dep_name))
+                    && opts.group_filter.as_ref()
+                        .is_none_or(|f| f.selects(new_dep.opts.group.as_ref()))
+            })
+            .map(|(dep_name, _)| dep_name)
+            .collect();
+    dep_names.sort();
+
+    let mut num_updated = 0;
+    for dep_name in dep_names {
+        let new_dep = &new_deps[dep_name];
+        let dir = output_dir.join(dep_name);
+        if !dir.exists() {
+            continue;
+        }
+
+        if !opts.force {
+            let modified = new_dep.tool.is_modified(&dir)
+                .with_context(||
+                    CheckModifiedFailed{dep_name: dep_name.clone()}
+                )?;
+            if modified {
+                let Some(prompter) = opts.prompter else {
+                    return Err(InstallDepsError::DepsModified{
+                        dep_names: vec![dep_name.clone()],
+                    });
+                };
+
+                let question = format!(
+                    "'{}' has been modified locally; discard the changes?",
+                    dir.display(),
+                );
+                if !prompter.confirm(&question) {
+                    logger.log(Level::Info, &format!(
+                        "Skipped '{}' because the action wasn't confirmed",
+                        dep_name,
+                    ));
+                    continue;
+                }
+            }
+        }
+
+        let fast_forwarded =
+            !opts.output.vendor
+                && !new_dep.opts.archive
+                && fast_forward_dep(dep_name, new_dep, &dir, opts, logger)?;
+        if !fast_forwarded {
+            actions.push((Action::Install, dep_name.clone()));
+            continue;
+        }
+
+        let revision = new_dep.tool.revision(&dir)
+            .with_context(||
+                GetTrackedRevisionFailed{dep_name: dep_name.clone()}
+            )?;
+        if !new_dep.opts.chmod.is_empty() {
+            apply_perm_rules(&dir, "", &new_dep.opts.chmod)
+                .context(SetPermissionsFailed{
+                    dep_name: dep_name.clone(),
+                    path: &dir,
+                })?;
+        }
+        if opts.output.readonly {
+            set_writable(&dir, false)
+                .context(MakeDepReadonlyFailed{
+                    dep_name: dep_name.clone(),
+                    path: &dir,
+                })?;
+        }
+        if let Some(cur_dep) = cur_deps.get_mut(dep_name) {
+            if cur_dep.opts.tracked_revision.as_ref() != Some(&revision) {
+                cur_dep.opts.tracked_revision = Some(revision.clone());
+                record_install(
+                    cur_dep,
+                    opts.output.source_date_epoch,
+                    Some(revision),
+                );
+                num_updated += 1;
+            }
+        }
+    }
+
+    if let (true, Some(state_file_path)) = (num_updated > 0, state_file_path)
+    {
+        write_state_file(state_file_path, cur_deps)
+            .context(WriteCurDepsAfterFastForwardFailed{state_file_path})?;
+    }
+
+    Ok(num_updated)
+}
+
+// `fast_forward_dep` fast-forwards `new_dep`, which is installed at `dir`, to
+// the latest commit on its branch, and returns whether its tool could
+// fast-forward it.
+fn fast_forward_dep(
+    dep_name: &str,
+    new_dep: &Dependency<'_, DepToolError>,
+    dir: &Path,
+    opts: &InstallOptions,
+    logger: &dyn Logger,
+)
+    -> Result<bool, InstallDepsError<DepToolError>>
+{
+    logger.log(Level::Info, &format!(
+        "Fast-forwarding '{}' to the latest commit on '{}'",
+        dep_name,
+        new_dep.version,
+    ));
+    let deadline = opts.fetch.timeout.map(|t| Instant::now() + t);
+
+    set_writable(dir, true)
+        .context(MakeDepWritableFailed{dep_name, path: dir})?;
+    let result = new_dep.with_env(|| {
+        new_dep.tool.fast_forward(
+            &new_dep.source,
+            dir,
+            &new_dep.version,
+            deadline,
+        )
+    });
+    // The dependency stays in the state file, so a partial fast-forward is
+    // completed by the next run.
+    if result.is_err() && interrupt::interrupted() {
+        return Err(InstallDepsError::Interrupted);
+    }
 
-        while let Some(proj) = projs.pop() {
-            let (proj_dir, dep_name, deps_file_path, raw_deps_spec) = proj;
-            let deps_spec = String::from_utf8(raw_deps_spec)
-                .with_context(|| ConvDepsFileUtf8Failed{
-                    dep_name: dep_name.clone(),
-                    path: deps_file_path.clone(),
-                })?;
+    result.context(FastForwardFailed{dep_name})
+}
 
-            let conf = &self.parse_deps_conf(&deps_spec)
-                .with_context(|| ParseDepsConfFailed{
-                    dep_name: dep_name.clone(),
-                    path: deps_file_path.clone(),
-                })?;
+// `record_install` records the time at which `dep` was installed, the
+// versions of `dpnd` and of the program used by its tool, and the `revision`
+// that was installed, in the options of `dep`, so that they're written to the
+// state file. The time is taken from `SOURCE_DATE_EPOCH`, if it's defined, so
+// that state files are reproducible.
+fn record_install(
+    dep: &mut Dependency<'_, DepToolError>,
+    source_date_epoch: Option<u64>,
+    revision: Option<String>,
+) {
+    dep.opts.installed_at = Some(source_date_epoch.unwrap_or_else(unix_now));
+    dep.opts.dpnd_version = Some(env!("CARGO_PKG_VERSION").to_string());
+    dep.opts.tool_version = dep.tool.tool_version();
+    dep.opts.installed_revision = revision;
+}
 
-            self.install_proj_deps(&proj_dir, conf)
-                .context(InstallProjDepsFailed{dep_name})?;
+// `fetch_dep` creates `dir` and fetches `dep` into it, trying the mirrors of
+// `dep` if it can't be retrieved from its source. It returns the source that
+// `dep` was fetched from.
+fn fetch_dep(
+    dep_name: &str,
+    dep: &Dependency<'_, DepToolError>,
+    dir: &Path,
+    opts: &InstallOptions,
+    logger: &dyn Logger,
+)
+    -> Result<Option<String>, InstallDepsError<DepToolError>>
+{
+    fs::create_dir(dir)
+        .context(CreateDepOutputDirFailed{dep_name, path: dir})?;
 
-            if !recurse {
-                break;
-            }
+    let fetch_start = Instant::now();
+    let deadline = opts.fetch.timeout.map(|t| fetch_start + t);
 
-            for dep_name in conf.deps.keys() {
-                let dep_proj_path =
-                    proj_dir.join(&conf.output_dir).join(dep_name);
-                let dep_deps_file_path =
-                    dep_proj_path.join(&self.deps_file_name);
-                let maybe_raw_deps_spec = try_read(&dep_deps_file_path)
-                    .with_context(|| ReadNestedDepsFileFailed{
-                        path: dep_deps_file_path.clone(),
-                        dep_name,
-                        dep_proj_path: dep_proj_path.clone(),
-                    })?;
+    // Mirrors are only tried if the source couldn't be retrieved from the
+    // sources before them; other failures, such as a missing version, would
+    // be the same for every source.
+    let sources = iter::once(&dep.source).chain(&dep.opts.mirrors);
+    let mut fetch_result = Ok(());
+    let mut fetched_from = None;
+    for (i, src) in sources.enumerate() {
+        if i > 0 {
+            fs::remove_dir_all(dir)
+                .and_then(|()| fs::create_dir(dir))
+                .context(CreateDepOutputDirFailed{dep_name, path: dir})?;
+        }
 
-                if let Some(raw_deps_spec) = maybe_raw_deps_spec {
-                    projs.push((
-                        dep_proj_path,
-                        Some(dep_name.to_string()),
-                        dep_deps_file_path,
-                        raw_deps_spec,
-                    ));
-                }
-            }
+        logger.log(Level::Info, &format!(
+            "Fetching '{}' from '{}' at '{}' using '{}'",
+            dep_name,
+            src,
+            dep.version,
+            dep.tool.name(),
+        ));
+        fetch_result = dep.with_env(|| {
+            dep.tool.fetch(
+                src.clone(),
+                dep.version.clone(),
+                dep.opts.path.as_deref(),
+                dir,
+                deadline,
+            )
+        });
+        if fetch_result.is_err() && interrupt::interrupted() {
+            // The dependency isn't listed in the state file at this point,
+            // so a failure to remove it will be handled by the next run.
+            let _ = fs::remove_dir_all(dir);
+
+            return Err(InstallDepsError::Interrupted);
+        }
+        let retrieve_failed =
+            matches!(fetch_result, Err(FetchError::RetrieveFailed{..}));
+        if !retrieve_failed {
+            fetched_from = Some(src.clone());
+            break;
+        }
+        logger.log(
+            Level::Warn,
+            &format!("Couldn't retrieve '{}' from '{}'", dep_name, src),
+        );
+    }
+    if let Err(source) = fetch_result {
+        // Tools kill the commands that they're running when the deadline is
+        // reached, so a failure after the deadline is reported as a timeout
+        // rather than as the failure of the killed command.
+        if deadline.is_some_and(|t| Instant::now() >= t) {
+            return Err(InstallDepsError::FetchTimedOut{
+                dep_name: dep_name.to_string(),
+                elapsed: fetch_start.elapsed(),
+            });
         }
 
-        Ok(())
+        return Err(InstallDepsError::FetchFailed{
+            source: Box::new(source),
+            dep_name: dep_name.to_string(),
+            owner: dep.opts.owner.clone(),
+        });
     }
+    Ok(fetched_from)
+}
 
-    fn install_proj_deps<'b>(
-        &self,
-        proj_dir: &Path,
-        conf: &DepsConf<'b, GitCmdError>,
-    )
-        -> Result<(), InstallProjDepsError<GitCmdError>>
-    {
-        let output_dir = proj_dir.join(&conf.output_dir);
-        let state_file_path = output_dir.join(&self.state_file_name);
-        let (state_file_exists, state_file_conts) =
-            match try_read(&state_file_path) {
-                Ok(maybe_conts) => {
-                    if let Some(conts) = maybe_conts {
-                        (true, conts)
+// `copy_dir` copies the directory `src` to `dst`, which must not exist,
+// keeping the modes, modification times and symbolic links of its files. On
+// filesystems that support it, such as Btrfs, XFS and APFS, the files of `dst`
+// are copy-on-write clones of the files of `src`, so that they don't take up
+// extra space.
+fn copy_dir(src: &Path, dst: &Path) -> Result<(), IoError> {
+    // GNU `cp` falls back to a regular copy if files can't be cloned, but
+    // `cp -c` on macOS fails instead, so a regular copy is tried after it.
+    let flag_sets: &[&[&str]] =
+        if cfg!(target_os = "macos") {
+            &[&["-c", "-pPR"], &["-pPR"]]
+        } else {
+            &[&["-pPR", "--reflink=auto"]]
+        };
+
+    let mut result = Ok(());
+    for flags in flag_sets {
+        if result.is_err() {
+            fs::remove_dir_all(dst)
+                .or_else(|err| {
+                    if err.kind() == ErrorKind::NotFound {
+                        Ok(())
                     } else {
-                        (false, vec![])
+                        Err(err)
                     }
-                },
-                Err(err) => {
-                    return Err(InstallProjDepsError::ReadStateFileFailed{
-                        source: err,
-                        path: state_file_path,
-                    });
-                },
-            };
-
-        let state_spec = String::from_utf8(state_file_conts)
-            .with_context(
-                || ConvStateFileUtf8Failed{path: state_file_path.clone()}
-            )?;
+                })?;
+        }
 
-        let cur_deps = self.parse_deps(&mut state_spec.lines().enumerate())
-            .with_context(||
-                ParseStateFileFailed{path: state_file_path.clone()}
-            )?;
+        let output = Command::new("cp")
+            .args(*flags)
+            .arg(src)
+            .arg(dst)
+            .output()?;
 
-        fs::create_dir_all(&output_dir)
-            .with_context(||
-                CreateMainOutputDirFailed{path: output_dir.clone()}
-            )?;
+        if output.status.success() {
+            return Ok(());
+        }
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        result = Err(IoError::other(stderr.trim().to_string()));
+    }
 
-        install_deps(
-            &output_dir,
-            state_file_path,
-            state_file_exists,
-            cur_deps,
-            conf.deps.clone(),
-        )
-            .context(InstallDepsFailed{})?;
+    result
+}
 
-        Ok(())
+// `fetch_into_store` fetches `dep` into its entry in the store, unless the
+// entry already exists, and returns the path of the entry along with the
+// source that `dep` was fetched from.
+fn fetch_into_store(
+    dep_name: &str,
+    dep: &Dependency<'_, DepToolError>,
+    opts: &InstallOptions,
+    logger: &dyn Logger,
+)
+    -> Result<(PathBuf, Option<String>), InstallDepsError<DepToolError>>
+{
+    if opts.output.vendor || dep.opts.archive {
+        return Err(InstallDepsError::VendorLinkedDepFailed{
+            dep_name: dep_name.to_string(),
+        });
     }
 
-    fn parse_deps_conf(&self, conts: &str)
-        -> Result<DepsConf<'a, GitCmdError>, ParseDepsConfError>
-    {
-        let mut lines = conts.lines().enumerate();
+    let Some(store_dir) = &opts.fetch.store_dir else {
+        return Err(InstallDepsError::NoStoreDir{
+            dep_name: dep_name.to_string(),
+        });
+    };
 
-        let output_dir = parse_output_dir(&mut lines)
-            .context(ParseOutputDirFailed{})?;
+    fs::create_dir_all(store_dir)
+        .context(CreateStoreDirFailed{path: store_dir})?;
 
-        let deps = self.parse_deps(&mut lines)
-            .context(ParseDepsFailed{})?;
+    // The store may be shared with other runs of `dpnd`, and nested projects
+    // are installed in parallel, so the entry is locked while it's looked up
+    // and fetched, to stop it from being fetched twice at the same time.
+    // Only the entry is locked, so that different entries can be fetched in
+    // parallel.
+    let key = store_key(dep);
+    let entry = store_dir.join(&key);
+    let _entry_lock = store::lock_entry(&entry)
+        .context(LockStoreEntryFailed{dep_name, path: &entry})?;
 
-        Ok(DepsConf{output_dir, deps})
+    if entry.is_dir() {
+        logger.log(Level::Info, &format!(
+            "Using '{}' from the store at '{}'",
+            dep_name,
+            entry.display(),
+        ));
+        record_store_use(&entry, logger);
+
+        return Ok((entry, None));
     }
 
-    fn parse_deps(&self, lines: &mut Enumerate<Lines>)
-        -> Result<HashMap<String, Dependency<'a, GitCmdError>>, ParseDepsError>
-    {
-        let mut dep_defns: Vec<(String, Dependency<'a, GitCmdError>, usize)> =
-            vec![];
+    // The dependency is fetched into a new temporary directory in the store
+    // and then moved into place, so that an interrupted fetch doesn't leave an
+    // incomplete entry in the store. Temporary directories that were left for
+    // the entry by interrupted fetches are removed first; they can't be in
+    // use, because the entry is locked.
+    let tmp_dir_prefix = format!("{}.tmp", key);
+    remove_stale_fetch_dirs(dep_name, store_dir, &tmp_dir_prefix)?;
+    let tmp_dir = temp_dir::create_in(store_dir, &tmp_dir_prefix)
+        .context(CreateStoreTempDirFailed{dep_name, path: store_dir})?;
+    let fetch_dir = tmp_dir.join(&key);
 
-        for (i, line) in lines {
-            let ln_num = i + 1;
+    let result = fetch_dep(dep_name, dep, &fetch_dir, opts, logger)
+        .and_then(|fetched_from| {
+            move_store_entry(dep_name, &fetch_dir, &entry)?;
 
-            let ln = line.trim_start();
-            if conf_line_is_skippable(ln) {
-                continue;
-            }
+            Ok(fetched_from)
+        });
+    // The temporary directory is left in the store if it can't be removed,
+    // and is removed by the next fetch of the entry.
+    if let Err(err) = fs::remove_dir_all(&tmp_dir) {
+        logger.log(Level::Warn, &format!(
+            "Couldn't remove '{}': {}",
+            tmp_dir.display(),
+            err,
+        ));
+    }
+    let fetched_from = result?;
+    record_store_use(&entry, logger);
 
-            let words: Vec<&str> = ln.split_ascii_whitespace().collect();
-            if words.len() != 4 {
-                return Err(ParseDepsError::InvalidDepSpec{
-                    ln_num,
-                    line: ln.to_string(),
-                });
-            }
+    Ok((entry, fetched_from))
+}
 
-            let local_name = words[0].to_string();
-            if let Some(found) = self.bad_dep_name_chars.find(&local_name) {
-                return Err(ParseDepsError::DepNameContainsInvalidChar{
-                    ln_num,
-                    dep_name: local_name.clone(),
-                    bad_char_idx: found.start(),
-                });
-            } else if local_name == self.state_file_name {
-                return Err(ParseDepsError::ReservedDepName{
-                    ln_num,
-                    dep_name: local_name.clone(),
-                });
-            }
+// `remove_stale_fetch_dirs` removes the temporary directories in `store_dir`
+// whose names start with `prefix`.
+fn remove_stale_fetch_dirs(dep_name: &str, store_dir: &Path, prefix: &str)
+    -> Result<(), InstallDepsError<DepToolError>>
+{
+    let dir_entries = fs::read_dir(store_dir)
+        .context(ReadStoreDirFailed{dep_name, path: store_dir})?;
+    for maybe_dir_entry in dir_entries {
+        let dir_entry = maybe_dir_entry
+            .context(ReadStoreDirFailed{dep_name, path: store_dir})?;
+        let is_stale = dir_entry.file_name()
+            .to_str()
+            .is_some_and(|name| name.starts_with(&format!("{}_", prefix)));
+        if is_stale {
+            let path = dir_entry.path();
+            fs::remove_dir_all(&path)
+                .context(RemoveOldDepOutputDirFailed{dep_name, path})?;
+        }
+    }
 
-            for (dep_local_name, _dep, defn_ln_num) in &dep_defns {
-                if *dep_local_name == local_name {
-                    return Err(ParseDepsError::DupDepName{
-                        ln_num,
-                        dep_name: local_name,
-                        orig_ln_num: *defn_ln_num,
-                    });
-                }
-            }
+    Ok(())
+}
 
-            let tool_name = words[1].to_string();
-            let tool = match self.tools.get(&tool_name) {
-                Some(tool) => *tool,
-                None => return Err(ParseDepsError::UnknownTool{
-                    ln_num,
-                    dep_name: local_name,
-                    tool_name,
-                }),
-            };
+// `move_store_entry` moves the fetched `dir` to `entry`. If `entry` was added
+// in the meantime, by a run of `dpnd` that didn't lock it, then that entry is
+// used instead.
+fn move_store_entry(dep_name: &str, dir: &Path, entry: &Path)
+    -> Result<(), InstallDepsError<DepToolError>>
+{
+    match fs::rename(dir, entry) {
+        Ok(()) => {
+            Ok(())
+        },
+        Err(_) if entry.is_dir() => {
+            Ok(())
+        },
+        Err(source) => {
+            Err(InstallDepsError::MoveStoreEntryFailed{
+                source,
+                dep_name: dep_name.to_string(),
+                path: entry.to_path_buf(),
+            })
+        },
+    }
+}
 
-            dep_defns.push((
-                local_name,
-                Dependency{
-                    tool,
-                    source: words[2].to_string(),
-                    version: Version(words[3].to_string()),
-                },
-                ln_num,
-            ));
+// `run_bounded` returns the results of calling `f` with each of `items`, in
+// the order of `items`. At most `jobs` calls are made at the same time.
+fn run_bounded<T, R, F>(items: &[T], jobs: usize, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    if jobs <= 1 || items.len() <= 1 {
+        return items.iter().map(f).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<R>>> =
+        Mutex::new(items.iter().map(|_| None).collect());
+    thread::scope(|scope| {
+        for _ in 0..jobs.min(items.len()) {
+            scope.spawn(|| {
+                loop {
+                    let i = next.fetch_add(1, Ordering::SeqCst);
+                    if i >= items.len() {
+                        break;
+                    }
+
+                    let result = f(&items[i]);
+                    results.lock()
+                        .expect("another worker panicked")[i] = Some(result);
+                }
+            });
         }
+    });
 
-        let deps =
-            dep_defns.into_iter()
-                .map(|(local_name, dep, _)| {
-                    (local_name, dep)
-                })
-                .collect();
+    results.into_inner()
+        .expect("a worker panicked")
+        .into_iter()
+        .map(|result| result.expect("an item wasn't processed"))
+        .collect()
+}
 
-        Ok(deps)
+// `record_store_use` records that the store entry at `entry` was used, so
+// that `dpnd prune-cache` removes the least recently used entries first. The
+// record only affects pruning, so a failure to write it doesn't stop the
+// dependency from being installed.
+fn record_store_use(entry: &Path, logger: &dyn Logger) {
+    if let Err(err) = store::record_use(entry) {
+        logger.log(Level::Warn, &format!(
+            "Couldn't record the use of '{}': {}",
+            entry.display(),
+            err,
+        ));
     }
 }
 
+// `store_key` returns the name of the entry in the store that `dep` is
+// fetched into. Dependencies that would be fetched to the same contents share
+// an entry, so mirrors don't affect the key.
+fn store_key(dep: &Dependency<'_, DepToolError>) -> String {
+    let mut id = format!(
+        "{}\0{}\0{}\0{}",
+        dep.tool.name(),
+        dep.source,
+        dep.version.0,
+        dep.opts.path.as_deref().unwrap_or(""),
+    );
+    // Permission rules are only added to the key if they're defined, so that
+    // the keys of existing entries don't change.
+    for rule in &dep.opts.chmod {
+        let _ = write!(id, "\0chmod={}", rule);
+    }
+
+    sha256::hex_digest(id.as_bytes())
+}
+
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, Snafu)]
-pub enum InstallError<E>
+pub enum InstallDepsError<E>
 where
     E: Error + 'static
 {
-    NoDepsFileFound,
-    ReadDepsFileFailed{source: ReadDepsFileError},
-    ConvDepsFileUtf8Failed{
-        source: FromUtf8Error,
+    WriteInitialCurDepsFailed{
+        source: WriteStateFileError,
+        state_file_path: PathBuf,
+    },
+    RemoveOldDepOutputDirFailed{
+        source: IoError,
+        dep_name: String,
         path: PathBuf,
-        dep_name: Option<String>,
     },
-    ParseDepsConfFailed{
-        source: ParseDepsConfError,
+    WriteCurDepsAfterRemoveFailed{
+        source: WriteStateFileError,
+        dep_name: String,
+        state_file_path: PathBuf,
+    },
+    RenameDepOutputDirFailed{
+        source: IoError,
+        old_dep_name: String,
+        dep_name: String,
         path: PathBuf,
-        dep_name: Option<String>,
     },
-    InstallProjDepsFailed{
-        source: InstallProjDepsError<E>,
-        dep_name: Option<String>,
+    WriteCurDepsAfterRenameFailed{
+        source: WriteStateFileError,
+        dep_name: String,
+        state_file_path: PathBuf,
     },
-    ReadNestedDepsFileFailed{
+    CreateDepOutputDirFailed{source: IoError, dep_name: String, path: PathBuf},
+    VendorLinkedDepFailed{dep_name: String},
+    NoStoreDir{dep_name: String},
+    CreateStoreDirFailed{source: IoError, path: PathBuf},
+    LockStoreEntryFailed{source: IoError, dep_name: String, path: PathBuf},
+    ReadStoreDirFailed{source: IoError, dep_name: String, path: PathBuf},
+    CreateStoreTempDirFailed{
         source: IoError,
+        dep_name: String,
         path: PathBuf,
+    },
+    MoveStoreEntryFailed{source: IoError, dep_name: String, path: PathBuf},
+    LinkDepOutputDirFailed{source: IoError, dep_name: String, path: PathBuf},
+    CopyDepFromStoreFailed{source: IoError, dep_name: String, path: PathBuf},
+    WriteCurDepsAfterInstallFailed{
+        source: WriteStateFileError,
         dep_name: String,
-        dep_proj_path: PathBuf,
+        state_file_path: PathBuf,
+    },
+    FetchFailed{
+        #[snafu(source(from(FetchError<E>, Box::new)))]
+        source: Box<FetchError<E>>,
+        dep_name: String,
+        owner: Option<String>,
     },
+    ReadVendorFileFailed{source: IoError, dep_name: String, path: PathBuf},
+    VendorDepFailed{
+        #[snafu(source(from(VendorDepError<E>, Box::new)))]
+        source: Box<VendorDepError<E>>,
+        dep_name: String,
+    },
+    GetRevisionTimeFailed{source: E, dep_name: String},
+    NormalizeMtimesFailed{source: SetMtimesError, dep_name: String},
+    VerifySignatureFailed{source: E, dep_name: String},
+    DepsOutOfDate{dep_names: Vec<String>},
+    FrozenDepsChanged{changes: Vec<PlannedChange>},
+    FetchTimedOut{dep_name: String, elapsed: Duration},
+    CheckModifiedFailed{source: E, dep_name: String},
+    DepsModified{dep_names: Vec<String>},
+    UnknownNeededDep{dep_name: String, needed_dep_name: String},
+    // `dep_names` starts and ends with the same dependency, and each
+    // dependency in it needs the next one.
+    DepNeedsCycle{dep_names: Vec<String>},
+    FastForwardFailed{source: E, dep_name: String},
+    GetTrackedRevisionFailed{source: E, dep_name: String},
+    WriteCurDepsAfterFastForwardFailed{
+        source: WriteStateFileError,
+        state_file_path: PathBuf,
+    },
+    Interrupted,
+    MakeDepWritableFailed{source: IoError, dep_name: String, path: PathBuf},
+    MakeDepReadonlyFailed{source: IoError, dep_name: String, path: PathBuf},
+    SetPermissionsFailed{source: IoError, dep_name: String, path: PathBuf},
 }
 
-// `try_read` returns the contents of the file at `path`, or `None` if it
-// doesn't exist, or an error if one occurred.
-fn try_read<P: AsRef<Path>>(path: P) -> Result<Option<Vec<u8>>, IoError> {
-    match fs::read(path) {
-        Ok(conts) => {
-            Ok(Some(conts))
-        },
-        Err(err) => {
-            if err.kind() == ErrorKind::NotFound {
-                Ok(None)
-            } else {
-                Err(err)
-            }
-        },
+// `dir_size` returns the total size, in bytes, of the files under `path`.
+// Symbolic links aren't followed.
+pub fn dir_size(path: &Path) -> Result<u64, IoError> {
+    let md = fs::symlink_metadata(path)?;
+    if !md.is_dir() {
+        return Ok(md.len());
     }
-}
 
-#[allow(clippy::enum_variant_names)]
-#[derive(Debug, Snafu)]
-pub enum InstallProjDepsError<E>
-where
-    E: Error + 'static
-{
-    ReadStateFileFailed{source: IoError, path: PathBuf},
-    ConvStateFileUtf8Failed{source: FromUtf8Error, path: PathBuf},
-    ParseStateFileFailed{source: ParseDepsError, path: PathBuf},
-    CreateMainOutputDirFailed{source: IoError, path: PathBuf},
-    InstallDepsFailed{source: InstallDepsError<E>},
+    let mut size = 0;
+    for maybe_entry in fs::read_dir(path)? {
+        size += dir_size(&maybe_entry?.path())?;
+    }
+
+    Ok(size)
 }
 
-// `read_deps_file` reads the file named `deps_file_name` in `start` or the
-// deepest of `start`s ancestor directories that contains a file named
-// `deps_file_name`.
-fn read_deps_file(start: &Path, deps_file_name: &str)
-    -> Result<Option<(PathBuf, PathBuf, Vec<u8>)>, ReadDepsFileError>
-{
-    let mut dir = start.to_path_buf();
-    loop {
-        let deps_file_path = dir.clone().join(deps_file_name);
+// `set_mtimes` sets the modification time of `path`, and of all files under
+// `path` if it's a directory, to `time`. Symbolic links aren't followed, and
+// their modification times aren't changed.
+fn set_mtimes(path: &Path, time: SystemTime) -> Result<(), SetMtimesError> {
+    let md = fs::symlink_metadata(path)
+        .context(GetMetadataFailed{path})?;
 
-        match try_read(&deps_file_path) {
-            Ok(Some(conts)) => {
-                return Ok(Some((dir, deps_file_path, conts)));
-            },
-            Ok(None) => {
-            },
-            Err(err) => {
-                return Err(ReadDepsFileError::ReadFailed{
-                    source: err,
-                    deps_file_path,
-                });
-            },
-        }
+    if md.file_type().is_symlink() {
+        return Ok(());
+    }
 
-        if !dir.pop() {
-            return Ok(None);
+    if md.is_dir() {
+        let entries = fs::read_dir(path)
+            .context(ReadDirFailed{path})?;
+
+        for maybe_entry in entries {
+            let entry = maybe_entry
+                .context(ReadDirFailed{path})?;
+
+            set_mtimes(&entry.path(), time)?;
         }
     }
+
+    File::open(path)
+        .and_then(|file| file.set_modified(time))
+        .context(SetMtimeFailed{path})?;
+
+    Ok(())
 }
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, Snafu)]
-pub enum ReadDepsFileError {
-    ReadFailed{source: IoError, deps_file_path: PathBuf},
+pub enum SetMtimesError {
+    GetMetadataFailed{source: IoError, path: PathBuf},
+    ReadDirFailed{source: IoError, path: PathBuf},
+    SetMtimeFailed{source: IoError, path: PathBuf},
 }
 
-#[derive(Debug, Snafu)]
-pub enum ParseDepsConfError {
-    ParseOutputDirFailed{source: ParseOutputDirError},
-    ParseDepsFailed{source: ParseDepsError},
+// `set_writable` gives the owner of `path`, and of all files under `path` if
+// it's a directory, permission to write to them if `writable` is `true`, and
+// otherwise removes all write permissions from them. Symbolic links aren't
+// followed.
+pub fn set_writable(path: &Path, writable: bool) -> Result<(), IoError> {
+    let md = fs::symlink_metadata(path)?;
+
+    if md.file_type().is_symlink() {
+        return Ok(());
+    }
+
+    if md.is_dir() {
+        for maybe_entry in fs::read_dir(path)? {
+            set_writable(&maybe_entry?.path(), writable)?;
+        }
+    }
+
+    let mut perms = md.permissions();
+    let mode = perms.mode();
+    perms.set_mode(if writable { mode | 0o200 } else { mode & !0o222 });
+
+    fs::set_permissions(path, perms)
 }
 
-struct DepsConf<'a, E> {
-    output_dir: PathBuf,
-    deps: HashMap<String, Dependency<'a, E>>,
+// `apply_perm_rules` sets the permissions of `path`, if it's a regular file,
+// and of all regular files under `path`, if it's a directory, to the mode of
+// the last rule in `rules` that matches the file. `rel_path` is the path of
+// `path` relative to the output directory of the dependency. Symbolic links
+// aren't followed, and directories keep their permissions.
+fn apply_perm_rules(path: &Path, rel_path: &str, rules: &[PermRule])
+    -> Result<(), IoError>
+{
+    let md = fs::symlink_metadata(path)?;
+
+    if md.is_dir() {
+        for maybe_entry in fs::read_dir(path)? {
+            let entry = maybe_entry?;
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            let entry_rel_path =
+                if rel_path.is_empty() {
+                    name.to_string()
+                } else {
+                    format!("{}/{}", rel_path, name)
+                };
+
+            apply_perm_rules(&entry.path(), &entry_rel_path, rules)?;
+        }
+
+        return Ok(());
+    }
+
+    if !md.is_file() {
+        return Ok(());
+    }
+
+    if let Some(rule) = rules.iter().rev().find(|r| r.matches(rel_path)) {
+        let mut perms = md.permissions();
+        perms.set_mode(rule.mode);
+
+        fs::set_permissions(path, perms)?;
+    }
+
+    Ok(())
 }
 
-fn parse_output_dir(lines: &mut Enumerate<Lines>)
-    -> Result<PathBuf, ParseOutputDirError>
+const VENDOR_FILE_NAME: &str = "VENDOR.txt";
+
+// `vendor_dep` removes the tool metadata from the output directory of `dep`,
+// `dir`, and writes a vendor file to `dir` that records the provenance of
+// `dep`. If `dep` uses `archive=true` then `dir` is replaced with an export of
+// `dep` by its tool, instead of only having its tool metadata removed.
+fn vendor_dep(dep: &Dependency<'_, DepToolError>, dir: &Path)
+    -> Result<(), VendorDepError<DepToolError>>
 {
-    for (i, line) in lines {
-        let ln = line.trim_start();
-        if !conf_line_is_skippable(ln) {
-            let mut path = PathBuf::new();
-            for part in ln.split('/') {
-                if part == "." || part == ".." {
-                    return Err(ParseOutputDirError::InvalidPart{
-                        ln_num: i + 1,
-                        part: part.to_string(),
-                    });
-                }
-                path.push(part);
-            }
-            return Ok(path);
+    let revision = dep.tool.revision(dir)
+        .context(GetRevisionFailed{})?;
+
+    if dep.opts.archive {
+        let exported = dep.tool.export(dir)
+            .context(ExportFailed{})?;
+        if !exported {
+            return Err(VendorDepError::ExportUnsupported{
+                tool_name: dep.tool.name(),
+            });
+        }
+    } else {
+        let metadata_paths = dep.tool.metadata_paths(dir)
+            .context(GetMetadataPathsFailed{})?;
+
+        for metadata_path in metadata_paths {
+            let path = dir.join(&metadata_path);
+            check_metadata_path(dep, dir, &path)?;
+            fs::remove_dir_all(&path)
+                .context(RemoveMetadataFailed{path})?;
         }
     }
 
-    Err(ParseOutputDirError::MissingOutputDir)
-}
+    let path = dir.join(VENDOR_FILE_NAME);
+    let conts = format!("{}revision: {}\n", render_provenance(dep), revision);
+    fs::write(&path, conts)
+        .context(WriteVendorFileFailed{path})?;
 
-fn conf_line_is_skippable(ln: &str) -> bool {
-    ln.is_empty() || ln.starts_with('#')
+    Ok(())
 }
 
-#[derive(Debug, Snafu)]
-pub enum ParseOutputDirError {
-    MissingOutputDir,
-    InvalidPart{ln_num: usize, part: String},
+// `check_metadata_path` returns an error unless `path`, which is a metadata
+// path reported by the tool of `dep`, resolves to a path inside `dir`, the
+// output directory of `dep`. Metadata paths can be reported by plugins, so
+// they're checked before they're removed, to prevent a path such as `..` from
+// removing files outside of the output directory.
+fn check_metadata_path(
+    dep: &Dependency<'_, DepToolError>,
+    dir: &Path,
+    path: &Path,
+)
+    -> Result<(), VendorDepError<DepToolError>>
+{
+    let canon_dir = fs::canonicalize(dir)
+        .context(CanonicalizeMetadataPathFailed{path: dir})?;
+    let canon_path = fs::canonicalize(path)
+        .context(CanonicalizeMetadataPathFailed{path})?;
+
+    if canon_path == canon_dir || !canon_path.starts_with(&canon_dir) {
+        return Err(VendorDepError::MetadataPathOutsideDep{
+            tool_name: dep.tool.name(),
+            path: path.to_path_buf(),
+        });
+    }
+
+    Ok(())
 }
 
-struct Dependency<'a, E> {
-    tool: &'a (dyn DepTool<E> + 'a),
-    source: String,
-    version: Version,
+// `vendor_file_matches` returns `true` if the vendor file at `path` exists and
+// records `dep` as the provenance of the dependency.
+fn vendor_file_matches(path: &Path, dep: &Dependency<'_, DepToolError>)
+    -> Result<bool, IoError>
+{
+    let Some(conts) = try_read(path)? else {
+        return Ok(false);
+    };
+
+    Ok(conts.starts_with(render_provenance(dep).as_bytes()))
 }
 
-impl<'a, E> Clone for Dependency<'a, E> {
-    fn clone(&self) -> Self {
-        Dependency{
-            tool: self.tool,
-            source: self.source.clone(),
-            version: self.version.clone(),
-        }
+// `installed_revision` returns the revision of `dep` that is installed in
+// `dir`, or `None` if `dep` isn't installed. The revision of a vendored
+// dependency is taken from its vendor file, because its tool metadata has been
+// removed.
+fn installed_revision(dep: &Dependency<'_, DepToolError>, dir: &Path)
+    -> Result<Option<String>, InstalledRevisionError<DepToolError>>
+{
+    if !dir.exists() {
+        return Ok(None);
     }
-}
 
-#[derive(Debug, Snafu)]
-pub enum ParseDepsError {
-    DupDepName{ln_num: usize, dep_name: String, orig_ln_num: usize},
-    DepNameContainsInvalidChar{
-        ln_num: usize,
-        dep_name: String,
-        bad_char_idx: usize,
-    },
-    ReservedDepName{ln_num: usize, dep_name: String},
-    InvalidDepSpec{ln_num: usize, line: String},
-    UnknownTool{ln_num: usize, dep_name: String, tool_name: String},
+    let vendor_file_path = dir.join(VENDOR_FILE_NAME);
+    let maybe_conts = try_read(&vendor_file_path)
+        .context(ReadInstalledVendorFileFailed{path: &vendor_file_path})?;
+
+    if let Some(conts) = maybe_conts {
+        let revision =
+            String::from_utf8_lossy(&conts)
+                .lines()
+                .find_map(|line| line.strip_prefix("revision: "))
+                .map(ToString::to_string);
+
+        return Ok(revision);
+    }
+
+    let revision = dep.tool.revision(dir)
+        .context(GetInstalledRevisionFromToolFailed{})?;
+
+    Ok(Some(revision))
 }
 
-fn install_deps<'a>(
+// `inspect_installed_deps` returns the dependencies in `deps` that are
+// installed in `output_dir`, as they would be recorded in a state file. A
+// dependency whose directory contains the revision that its version resolves
+// to is returned unchanged. Otherwise it's returned with the revision in its
+// directory as its version, or an empty version if the revision can't be
+// determined, so that it's changed to the version in `deps`.
+fn inspect_installed_deps<'a>(
     output_dir: &Path,
-    state_file_path: PathBuf,
-    state_file_exists: bool,
-    mut cur_deps: HashMap<String, Dependency<'a, GitCmdError>>,
-    mut new_deps: HashMap<String, Dependency<'a, GitCmdError>>,
+    deps: &HashMap<String, Dependency<'a, DepToolError>>,
 )
-    -> Result<(), InstallDepsError<GitCmdError>>
+    -> HashMap<String, Dependency<'a, DepToolError>>
 {
-    let mut actions = actions(&cur_deps, &new_deps);
+    let mut installed_deps = HashMap::new();
+    for (dep_name, dep) in deps {
+        // `symlink_metadata` is used so that dangling links are returned, and
+        // so replaced.
+        let dir = output_dir.join(dep_name);
+        if fs::symlink_metadata(&dir).is_err() {
+            continue;
+        }
 
-    if actions.is_empty() {
-        if !state_file_exists {
-            write_state_file(&state_file_path, &cur_deps)
-                .context(WriteInitialCurDepsFailed{state_file_path})?;
+        let revision = installed_revision(dep, &dir).ok().flatten();
+        let resolve = || dep.tool.resolve(&dep.source, &dep.version);
+        let resolved = dep.with_env(resolve).ok();
+
+        let mut installed_dep = dep.clone();
+        if revision.is_none() || revision != resolved {
+            installed_dep.version = Version(revision.unwrap_or_default());
         }
-        return Ok(());
+        installed_deps.insert(dep_name.clone(), installed_dep);
     }
 
-    while let Some((act, dep_name)) = actions.pop() {
-        let dir = output_dir.join(&dep_name);
-        if let Err(source) = fs::remove_dir_all(&dir) {
-            if source.kind() != ErrorKind::NotFound {
-                return Err(InstallDepsError::RemoveOldDepOutputDirFailed{
-                    source,
-                    dep_name,
-                    path: dir,
-                });
-            }
-        }
-        cur_deps.remove(&dep_name);
+    installed_deps
+}
 
-        write_state_file(&state_file_path, &cur_deps)
-            .with_context(|| WriteCurDepsAfterRemoveFailed{
-                dep_name: dep_name.clone(),
-                state_file_path: state_file_path.clone(),
-            })?;
+fn render_provenance(dep: &Dependency<'_, DepToolError>) -> String {
+    format!(
+        "tool: {}\nsource: {}\nversion: {}\n",
+        dep.tool.name(),
+        dep.source,
+        dep.version,
+    )
+}
 
-        if act != Action::Install {
-            continue;
-        }
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Snafu)]
+pub enum VendorDepError<E>
+where
+    E: Error + 'static
+{
+    GetRevisionFailed{source: E},
+    GetMetadataPathsFailed{source: E},
+    CanonicalizeMetadataPathFailed{source: IoError, path: PathBuf},
+    MetadataPathOutsideDep{tool_name: String, path: PathBuf},
+    RemoveMetadataFailed{source: IoError, path: PathBuf},
+    ExportFailed{source: E},
+    ExportUnsupported{tool_name: String},
+    WriteVendorFileFailed{source: IoError, path: PathBuf},
+}
 
-        let new_dep = new_deps.remove(&dep_name)
-            .unwrap_or_else(|| panic!(
-                "dependency '{}' wasn't in the map of current dependencies",
-                dep_name,
-            ));
+// `render_plan` describes the actions that will be taken on the dependencies
+// in `output_dir`, for logging.
+fn render_plan(output_dir: &Path, actions: &[(Action, String)]) -> String {
+    if actions.is_empty() {
+        return format!(
+            "The dependencies in '{}' are up to date",
+            output_dir.display(),
+        );
+    }
 
-        let dir = output_dir.join(&dep_name);
-        fs::create_dir(&dir)
-            .context(CreateDepOutputDirFailed{
-                dep_name: dep_name.clone(),
-                path: &dir,
-            })?;
+    let rendered_actions: Vec<String> =
+        actions.iter()
+            .map(|(act, dep_name)| format!("{:?} '{}'", act, dep_name))
+            .collect();
 
-        new_dep.tool.fetch(
-            new_dep.source.clone(),
-            new_dep.version.clone(),
-            &dir,
-        )
-            .context(FetchFailed{dep_name: dep_name.clone()})?;
-        cur_deps.insert(dep_name.clone(), new_dep);
+    format!(
+        "Planned actions for '{}': {}",
+        output_dir.display(),
+        rendered_actions.join(", "),
+    )
+}
 
-        write_state_file(&state_file_path, &cur_deps)
-            .with_context(|| WriteCurDepsAfterInstallFailed{
-                dep_name: dep_name.clone(),
-                state_file_path: state_file_path.clone(),
-            })?;
+// `needs_depths` returns the depth of each dependency in `deps` in the graph
+// formed by their `needs` options. Dependencies that don't need any others
+// have a depth of `0`, and other dependencies are deeper than all of the
+// dependencies that they need. An error is returned if a dependency needs one
+// that isn't in `deps`, or if the graph contains a cycle.
+fn needs_depths(deps: &HashMap<String, Dependency<'_, DepToolError>>)
+    -> Result<HashMap<String, usize>, InstallDepsError<DepToolError>>
+{
+    let mut depths = HashMap::new();
+    // Dependencies are visited in order of their names, so that the same
+    // cycle is reported on every run.
+    let mut dep_names: Vec<&String> = deps.keys().collect();
+    dep_names.sort();
+    for dep_name in dep_names {
+        needs_depth(deps, dep_name, &mut vec![], &mut depths)?;
     }
 
-    Ok(())
+    Ok(depths)
 }
 
-#[allow(clippy::enum_variant_names)]
-#[derive(Debug, Snafu)]
-pub enum InstallDepsError<E>
-where
-    E: Error + 'static
+// `needs_depth` returns the depth of `dep_name`, recording it and the depths
+// of the dependencies that it needs in `depths`. `path` contains the
+// dependencies that are being visited, each of which needs the next.
+fn needs_depth(
+    deps: &HashMap<String, Dependency<'_, DepToolError>>,
+    dep_name: &str,
+    path: &mut Vec<String>,
+    depths: &mut HashMap<String, usize>,
+)
+    -> Result<usize, InstallDepsError<DepToolError>>
 {
-    WriteInitialCurDepsFailed{
-        source: WriteStateFileError,
-        state_file_path: PathBuf,
-    },
-    RemoveOldDepOutputDirFailed{
-        source: IoError,
-        dep_name: String,
-        path: PathBuf,
-    },
-    WriteCurDepsAfterRemoveFailed{
-        source: WriteStateFileError,
-        dep_name: String,
-        state_file_path: PathBuf,
-    },
-    CreateDepOutputDirFailed{source: IoError, dep_name: String, path: PathBuf},
-    WriteCurDepsAfterInstallFailed{
-        source: WriteStateFileError,
-        dep_name: String,
-        state_file_path: PathBuf,
-    },
-    FetchFailed{source: FetchError<E>, dep_name: String},
+    if let Some(depth) = depths.get(dep_name) {
+        return Ok(*depth);
+    }
+    if let Some(i) = path.iter().position(|name| name == dep_name) {
+        let mut dep_names = path[i..].to_vec();
+        dep_names.push(dep_name.to_string());
+
+        return Err(InstallDepsError::DepNeedsCycle{dep_names});
+    }
+
+    path.push(dep_name.to_string());
+    let mut depth = 0;
+    for needed_dep_name in &deps[dep_name].opts.needs {
+        if !deps.contains_key(needed_dep_name) {
+            return Err(InstallDepsError::UnknownNeededDep{
+                dep_name: dep_name.to_string(),
+                needed_dep_name: needed_dep_name.clone(),
+            });
+        }
+        let needed_depth = needs_depth(deps, needed_dep_name, path, depths)?;
+        depth = depth.max(needed_depth + 1);
+    }
+    path.pop();
+
+    depths.insert(dep_name.to_string(), depth);
+
+    Ok(depth)
 }
 
 // `actions` returns the actions that must be taken to transform `cur_deps`
 // into `new_deps`.
 fn actions<'a>(
-    cur_deps: &HashMap<String, Dependency<'a, GitCmdError>>,
-    new_deps: &HashMap<String, Dependency<'a, GitCmdError>>,
+    cur_deps: &HashMap<String, Dependency<'a, DepToolError>>,
+    new_deps: &HashMap<String, Dependency<'a, DepToolError>>,
 )
     -> Vec<(Action, String)>
 {
@@ -525,9 +6443,7 @@ fn actions<'a>(
 
     for (new_dep_name, new_dep) in new_deps {
         if let Some(cur_dep) = cur_deps.get(new_dep_name) {
-            if cur_dep.tool.name() != new_dep.tool.name()
-                    || cur_dep.source != new_dep.source
-                    || cur_dep.version != new_dep.version {
+            if dep_changed(cur_dep, new_dep) {
                 actions.push((Action::Install, new_dep_name.clone()));
             }
         } else {
@@ -544,15 +6460,213 @@ fn actions<'a>(
     actions
 }
 
+// `rename_deps` handles dependencies that were renamed in the dependency file
+// without otherwise being changed: if `actions` removes a dependency and
+// installs a new dependency with the same definition, then the output
+// directory of the removed dependency is renamed instead, and both actions
+// are dropped.
+fn rename_deps<'a>(
+    output_dir: &Path,
+    state_file_path: Option<&Path>,
+    cur_deps: &mut HashMap<String, Dependency<'a, DepToolError>>,
+    new_deps: &HashMap<String, Dependency<'a, DepToolError>>,
+    actions: &mut Vec<(Action, String)>,
+    logger: &dyn Logger,
+)
+    -> Result<(), InstallDepsError<DepToolError>>
+{
+    let mut removed_names: Vec<String> =
+        actions.iter()
+            .filter(|(act, _)| *act == Action::Remove)
+            .map(|(_, dep_name)| dep_name.clone())
+            .collect();
+    let mut installed_names: Vec<String> =
+        actions.iter()
+            .filter(|(act, dep_name)| {
+                *act == Action::Install && !cur_deps.contains_key(dep_name)
+            })
+            .map(|(_, dep_name)| dep_name.clone())
+            .collect();
+    // Names are sorted so that dependencies with the same definition are
+    // paired in the same way on every run.
+    removed_names.sort();
+    installed_names.sort();
+
+    for new_name in installed_names {
+        let new_dep = &new_deps[&new_name];
+        let maybe_old_name =
+            removed_names.iter()
+                .position(|old_name| {
+                    let old_dir = output_dir.join(old_name);
+
+                    !dep_changed(&cur_deps[old_name], new_dep)
+                        && fs::symlink_metadata(old_dir).is_ok()
+                })
+                .map(|i| removed_names.remove(i));
+        let Some(old_name) = maybe_old_name else {
+            continue;
+        };
+
+        let old_dir = output_dir.join(&old_name);
+        let new_dir = output_dir.join(&new_name);
+        logger.log(Level::Info, &format!(
+            "Renaming '{}' to '{}'",
+            old_dir.display(),
+            new_dir.display(),
+        ));
+        fs::rename(&old_dir, &new_dir)
+            .context(RenameDepOutputDirFailed{
+                old_dep_name: &old_name,
+                dep_name: &new_name,
+                path: &new_dir,
+            })?;
+
+        let dep = cur_deps.remove(&old_name)
+            .unwrap_or_else(|| panic!(
+                "dependency '{}' wasn't in the map of current dependencies",
+                old_name,
+            ));
+        cur_deps.insert(new_name.clone(), dep);
+        if let Some(state_file_path) = state_file_path {
+            write_state_file(state_file_path, cur_deps)
+                .with_context(|| WriteCurDepsAfterRenameFailed{
+                    dep_name: new_name.clone(),
+                    state_file_path: state_file_path.to_path_buf(),
+                })?;
+        }
+
+        actions.retain(|(_, dep_name)| {
+            *dep_name != old_name && *dep_name != new_name
+        });
+    }
+
+    Ok(())
+}
+
+fn dep_changed<'a>(
+    old_dep: &Dependency<'a, DepToolError>,
+    new_dep: &Dependency<'a, DepToolError>,
+)
+    -> bool
+{
+    old_dep.tool.name() != new_dep.tool.name()
+        || old_dep.source != new_dep.source
+        || old_dep.version != new_dep.version
+        || old_dep.opts.verify != new_dep.opts.verify
+        || old_dep.opts.group != new_dep.opts.group
+        || old_dep.opts.path != new_dep.opts.path
+        || old_dep.opts.mirrors != new_dep.opts.mirrors
+        || old_dep.opts.link != new_dep.opts.link
+        || old_dep.opts.archive != new_dep.opts.archive
+        || old_dep.opts.track != new_dep.opts.track
+        || old_dep.opts.chmod != new_dep.opts.chmod
+}
+
+// `PlannedChange` is a change that installation would make to a dependency,
+// which is described to the user in `InstallMode::Frozen`. Dependencies are
+// described by their tools, sources and versions.
+#[derive(Debug)]
+pub enum PlannedChange {
+    Install{dep_name: String, dep_spec: String},
+    Update{dep_name: String, old_spec: String, new_spec: String},
+    // `Reinstall` is a change to a dependency whose definition hasn't
+    // changed, but whose installed copy is missing or out of date.
+    Reinstall{dep_name: String},
+    Remove{dep_name: String},
+}
+
+// `planned_changes` returns the changes that `actions` would make, sorted by
+// the names of the dependencies that they change.
+fn planned_changes<'a>(
+    actions: &[(Action, String)],
+    cur_deps: &HashMap<String, Dependency<'a, DepToolError>>,
+    new_deps: &HashMap<String, Dependency<'a, DepToolError>>,
+)
+    -> Vec<PlannedChange>
+{
+    let render_spec = |dep: &Dependency<'a, DepToolError>| {
+        format!("{} {} {}", dep.tool.name(), dep.source, dep.version)
+    };
+
+    let mut changes: Vec<PlannedChange> =
+        actions.iter()
+            .map(|(action, dep_name)| {
+                let dep_name = dep_name.clone();
+                let cur_dep = cur_deps.get(&dep_name);
+                let new_dep = new_deps.get(&dep_name);
+                match (action, cur_dep, new_dep) {
+                    (Action::Install, None, Some(new_dep)) => {
+                        let dep_spec = render_spec(new_dep);
+
+                        PlannedChange::Install{dep_name, dep_spec}
+                    },
+                    (Action::Install, Some(cur_dep), Some(new_dep))
+                        if dep_changed(cur_dep, new_dep) =>
+                    {
+                        PlannedChange::Update{
+                            dep_name,
+                            old_spec: render_spec(cur_dep),
+                            new_spec: render_spec(new_dep),
+                        }
+                    },
+                    (Action::Install, _, _) => {
+                        PlannedChange::Reinstall{dep_name}
+                    },
+                    (Action::Remove, _, _) => {
+                        PlannedChange::Remove{dep_name}
+                    },
+                }
+            })
+            .collect();
+
+    changes.sort_by(|a, b| a.dep_name().cmp(b.dep_name()));
+
+    changes
+}
+
+impl PlannedChange {
+    pub fn dep_name(&self) -> &str {
+        match self {
+            PlannedChange::Install{dep_name, ..} |
+            PlannedChange::Update{dep_name, ..} |
+            PlannedChange::Reinstall{dep_name} |
+            PlannedChange::Remove{dep_name} => {
+                dep_name
+            },
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 enum Action {
     Install,
     Remove,
 }
 
+// `STATE_DIR_PATH` is the path of the directory that contains the state
+// files of a project, relative to the directory that contains the dependency
+// file. The state file of each output directory is kept at the same relative
+// path within it as the output directory, so that output directories only
+// contain dependencies.
+pub const STATE_DIR_PATH: &str = ".dpnd/state";
+
+// `STATE_FILE_FORMAT_VERSION` is the version of the format of state files,
+// which is recorded in their headers so that later versions of `dpnd` can
+// recognise state files written in older formats.
+pub const STATE_FILE_FORMAT_VERSION: u32 = 1;
+
+// `DEPS_FILE_FORMAT_VERSION` is the version of the format of dependency files
+// that this version of `dpnd` reads. Dependency files don't record their
+// format, so it's only reported, so that environments whose versions of `dpnd`
+// read different formats can be told apart.
+pub const DEPS_FILE_FORMAT_VERSION: u32 = 1;
+
+// `write_state_file` writes `cur_deps` to `state_file_path`, after a header
+// comment. Dependencies are written in order of their names, so that state
+// files that are committed don't change when their dependencies don't.
 fn write_state_file<'a>(
     state_file_path: &Path,
-    cur_deps: &HashMap<String, Dependency<'a, GitCmdError>>,
+    cur_deps: &HashMap<String, Dependency<'a, DepToolError>>,
 )
     -> Result<(), WriteStateFileError>
 {
@@ -560,16 +6674,82 @@ fn write_state_file<'a>(
         .create(true)
         .truncate(true)
         .write(true)
-        .open(&state_file_path)
+        .open(state_file_path)
         .context(OpenFailed)?;
 
+    let header = format!(
+        "# This file is maintained by `dpnd`, and records the dependencies \
+         that are\n\
+         # installed in this directory.\n\
+         # format: {}\n",
+        STATE_FILE_FORMAT_VERSION,
+    );
+    file.write_all(header.as_bytes())
+        .context(WriteHeaderFailed)?;
+
+    let mut cur_deps: Vec<(&String, &Dependency<'a, DepToolError>)> =
+        cur_deps.iter().collect();
+    cur_deps.sort_by_key(|(name, _)| *name);
+
     for (cur_dep_name, cur_dep) in cur_deps {
+        let mut opts = String::new();
+        if cur_dep.opts.verify {
+            opts.push_str(" verify=gpg");
+        }
+        if let Some(group) = &cur_dep.opts.group {
+            let _ = write!(opts, " group={}", quote_field(group));
+        }
+        if let Some(path) = &cur_dep.opts.path {
+            let _ = write!(opts, " path={}", quote_field(path));
+        }
+        if !cur_dep.opts.mirrors.is_empty() {
+            let mirrors = cur_dep.opts.mirrors.join(",");
+            let _ = write!(opts, " mirrors={}", quote_field(&mirrors));
+        }
+        if let Some(src) = &cur_dep.opts.fetched_from {
+            let _ = write!(opts, " fetched_from={}", quote_field(src));
+        }
+        match cur_dep.opts.link {
+            Some(LinkMode::Copy) => {
+                opts.push_str(" link=copy");
+            },
+            Some(LinkMode::Symlink) => {
+                opts.push_str(" link=true");
+            },
+            None => {},
+        }
+        if cur_dep.opts.archive {
+            opts.push_str(" archive=true");
+        }
+        if cur_dep.opts.track {
+            opts.push_str(" track=branch");
+        }
+        for rule in &cur_dep.opts.chmod {
+            let _ = write!(opts, " chmod={}", quote_field(&rule.to_string()));
+        }
+        if let Some(rev) = &cur_dep.opts.tracked_revision {
+            let _ = write!(opts, " tracked_revision={}", quote_field(rev));
+        }
+        if let Some(t) = cur_dep.opts.installed_at {
+            let _ = write!(opts, " installed_at={}", t);
+        }
+        if let Some(vsn) = &cur_dep.opts.dpnd_version {
+            let _ = write!(opts, " dpnd_version={}", quote_field(vsn));
+        }
+        if let Some(vsn) = &cur_dep.opts.tool_version {
+            let _ = write!(opts, " tool_version={}", quote_field(vsn));
+        }
+        if let Some(rev) = &cur_dep.opts.installed_revision {
+            let _ = write!(opts, " installed_revision={}", quote_field(rev));
+        }
+
         file.write(format!(
-            "{} {} {} {}\n",
+            "{} {} {} {}{}\n",
             cur_dep_name,
             cur_dep.tool.name(),
-            cur_dep.source,
-            cur_dep.version,
+            quote_field(&cur_dep.source),
+            quote_field(&cur_dep.version.0),
+            opts,
         ).as_bytes())
             .context(WriteDepLineFailed)?;
     }
@@ -577,8 +6757,194 @@ fn write_state_file<'a>(
     Ok(())
 }
 
+// `ANNOTATION_PREFIX` starts the comments that `annotate_deps_spec` maintains
+// in dependency files. Lines that start with `ANNOTATION_PREFIX` are owned by
+// `dpnd` and may be rewritten or removed.
+const ANNOTATION_PREFIX: &str = "# dpnd: revision ";
+
+// `annotate_deps_spec` returns `deps_spec` with a comment before each
+// installed dependency in `conf` that records its revision and the date that
+// the revision was resolved on. The previous annotation of a dependency is
+// kept if its revision hasn't changed, so that the dependency file only
+// changes when revisions do, and it's also kept if the dependency isn't
+// installed. Other lines are kept as they are.
+fn annotate_deps_spec(
+    deps_spec: &str,
+    proj_dir: &Path,
+    conf: &DepsConf<DepToolError>,
+    now: u64,
+)
+    -> Result<String, InstallError<DepToolError>>
+{
+    let mut annotated = String::new();
+    let mut prev_annotation: Option<&str> = None;
+    let mut seen_output_dir = false;
+    let mut continues = false;
+
+    for line in deps_spec.split_inclusive('\n') {
+        let ln = line.trim_start();
+        if continues {
+            // This line continues the previous dependency specification, so
+            // it doesn't start a new one.
+            continues = line_continues(line);
+            annotated.push_str(line);
+            continue;
+        }
+        if ln.starts_with(ANNOTATION_PREFIX) {
+            prev_annotation = Some(line);
+            continue;
+        }
+        if conf_line_is_skippable(ln.trim_end()) {
+            annotated.push_str(line);
+            continue;
+        }
+        if !seen_output_dir {
+            seen_output_dir = true;
+            annotated.push_str(line);
+            continue;
+        }
+
+        let maybe_dep =
+            ln.split_ascii_whitespace()
+                .next()
+                .and_then(|name| conf.deps.get_key_value(name));
+
+        if let Some((dep_name, dep)) = maybe_dep {
+            let dep_dir =
+                proj_dir.join(conf.dep_output_dir(dep_name)).join(dep_name);
+            if dep_dir.is_dir() {
+                let revision = dep.tool.revision(&dep_dir)
+                    .context(GetAnnotationRevisionFailed{
+                        dep_name: dep_name.clone(),
+                    })?;
+                let indent = &line[..line.len() - ln.len()];
+
+                match prev_annotation {
+                    Some(prev) if annotation_revision(prev) == revision => {
+                        annotated.push_str(prev);
+                    },
+                    _ => {
+                        let _ = writeln!(
+                            annotated,
+                            "{}{}{}, resolved {}",
+                            indent,
+                            ANNOTATION_PREFIX,
+                            revision,
+                            utc_date(now),
+                        );
+                    },
+                }
+            } else if let Some(prev) = prev_annotation {
+                annotated.push_str(prev);
+            }
+        }
+
+        prev_annotation = None;
+        continues = line_continues(line);
+        annotated.push_str(line);
+    }
+
+    Ok(annotated)
+}
+
+// `line_continues` returns whether the dependency specification on `line`
+// continues on the next line. Lines that can't be split into fields are
+// treated as complete, because they're reported when the dependency file is
+// parsed.
+fn line_continues(line: &str) -> bool {
+    split_fields(0, line)
+        .is_ok_and(|(_, continues)| continues)
+}
+
+// `annotation_revision` returns the revision recorded in `annotation`, which
+// is a line that starts with `ANNOTATION_PREFIX`.
+fn annotation_revision(annotation: &str) -> &str {
+    let rest =
+        annotation.trim_start()
+            .trim_start_matches(ANNOTATION_PREFIX);
+
+    rest.split(',').next().unwrap_or("").trim()
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+// `utc_date` returns the UTC date, in the form `YYYY-MM-DD`, of the time
+// `secs` seconds after the Unix epoch. See
+// <http://howardhinnant.github.io/date_algorithms.html#civil_from_days> for
+// details on the algorithm.
+fn utc_date(secs: u64) -> String {
+    let z = secs / 86_400 + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + u64::from(m <= 2);
+
+    format!("{:04}-{:02}-{:02}", y, m, d)
+}
+
+// `utc_time` returns the UTC time, in the form `YYYY-MM-DDTHH:MM:SSZ`, of the
+// time `secs` seconds after the Unix epoch.
+pub fn utc_time(secs: u64) -> String {
+    let secs_of_day = secs % 86_400;
+
+    format!(
+        "{}T{:02}:{:02}:{:02}Z",
+        utc_date(secs),
+        secs_of_day / 3_600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60,
+    )
+}
+
+const SUMMARY_HEADER: &str = "### Dependencies\n\n\
+                              | Name | Tool | Source | Version |\n\
+                              | --- | --- | --- | --- |\n";
+
+// `write_summary` appends a Markdown table that describes the dependencies in
+// `conf` to the file at `path`, creating the file if it doesn't exist.
+fn write_summary(path: &Path, conf: &DepsConf<DepToolError>)
+    -> Result<(), IoError>
+{
+    let mut dep_names: Vec<&String> = conf.deps.keys().collect();
+    dep_names.sort();
+
+    let mut summary = SUMMARY_HEADER.to_string();
+    for dep_name in dep_names {
+        let dep = &conf.deps[dep_name];
+        let _ = writeln!(
+            summary,
+            "| {} | {} | {} | {} |",
+            escape_md_cell(dep_name),
+            escape_md_cell(&dep.tool.name()),
+            escape_md_cell(&dep.source),
+            escape_md_cell(&dep.version.0),
+        );
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+
+    file.write_all(summary.as_bytes())
+}
+
+fn escape_md_cell(s: &str) -> String {
+    s.replace('|', "\\|")
+}
+
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, Snafu)]
 pub enum WriteStateFileError {
     OpenFailed{source: IoError},
+    WriteHeaderFailed{source: IoError},
     WriteDepLineFailed{source: IoError},
 }
@@ -0,0 +1,55 @@
+// Copyright 2021 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by an MIT
+// licence that can be found in the LICENCE file.
+
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+
+// `INTERRUPTED_EXIT_CODE` is the exit code of `dpnd` when it stops because it
+// was interrupted. It follows the shell convention of `128 + SIGINT`.
+pub const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+// `catch_interrupts` stops `SIGINT` and `SIGTERM` from terminating `dpnd`
+// immediately. Instead, `interrupted` starts returning `true`, so that
+// long-running operations can kill the commands that they're running and
+// clean up before exiting. Interrupts are only caught on Unix.
+pub fn catch_interrupts() {
+    #[cfg(unix)]
+    unsafe {
+        // `signal` can only fail if the signal number is invalid, which isn't
+        // the case here.
+        sys::signal(sys::SIGINT, handle_interrupt);
+        sys::signal(sys::SIGTERM, handle_interrupt);
+    }
+}
+
+// `interrupted` returns whether `dpnd` has received an interrupt since
+// `catch_interrupts` was called.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+// `handle_interrupt` is run in a signal handler, so it must only perform
+// async-signal-safe operations, such as storing to an atomic.
+#[cfg(unix)]
+extern "C" fn handle_interrupt(_signum: sys::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+#[cfg(unix)]
+mod sys {
+    pub use std::os::raw::c_int;
+
+    // These signal numbers are the same on all Unix platforms that Rust
+    // supports.
+    pub const SIGINT: c_int = 2;
+    pub const SIGTERM: c_int = 15;
+
+    extern "C" {
+        // The return value of `signal` is the previous handler, which is
+        // represented as a `usize` because it isn't used.
+        pub fn signal(signum: c_int, handler: extern "C" fn(c_int)) -> usize;
+    }
+}
@@ -0,0 +1,136 @@
+// Copyright 2021 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by an MIT
+// licence that can be found in the LICENCE file.
+
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::Error as IoError;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+// `Logger` records the actions that `dpnd` takes, so that runs can be
+// diagnosed after the fact. Loggers don't return errors, because a failure to
+// record an action shouldn't stop the action from being taken. Loggers can be
+// shared between threads, because nested projects can be installed in
+// parallel.
+pub trait Logger: Sync {
+    fn log(&self, level: Level, msg: &str);
+}
+
+// `Level` is the importance of a log message. Levels are ordered from most to
+// least important, so a logger that records messages at one level also
+// records messages at all lower levels.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+// `LEVEL_NAMES` contains the names of the log levels, ordered from most to
+// least important.
+pub const LEVEL_NAMES: &[&str] = &["error", "warn", "info", "debug"];
+
+impl Level {
+    pub fn parse(name: &str) -> Option<Level> {
+        match name {
+            "error" => {
+                Some(Level::Error)
+            },
+            "warn" => {
+                Some(Level::Warn)
+            },
+            "info" => {
+                Some(Level::Info)
+            },
+            "debug" => {
+                Some(Level::Debug)
+            },
+            _ => {
+                None
+            },
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Level::Error => {
+                "ERROR"
+            },
+            Level::Warn => {
+                "WARN"
+            },
+            Level::Info => {
+                "INFO"
+            },
+            Level::Debug => {
+                "DEBUG"
+            },
+        }
+    }
+}
+
+// `NopLogger` discards all messages.
+pub struct NopLogger;
+
+impl Logger for NopLogger {
+    fn log(&self, _level: Level, _msg: &str) {
+    }
+}
+
+// `FileLogger` appends messages at `max_level` and lower levels to a file.
+// Each message is written on its own line, prefixed with the time, in seconds
+// since the Unix epoch, and the level of the message.
+pub struct FileLogger {
+    file: Mutex<File>,
+    max_level: Level,
+}
+
+impl FileLogger {
+    pub fn open(path: &Path, max_level: Level) -> Result<FileLogger, IoError> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+
+        Ok(FileLogger{file: Mutex::new(file), max_level})
+    }
+}
+
+impl Logger for FileLogger {
+    fn log(&self, level: Level, msg: &str) {
+        if level > self.max_level {
+            return;
+        }
+
+        let now =
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+
+        // A poisoned lock only means that another thread panicked while
+        // logging, in which case the file can still be appended to.
+        let mut file =
+            match self.file.lock() {
+                Ok(file) => {
+                    file
+                },
+                Err(err) => {
+                    err.into_inner()
+                },
+            };
+
+        let _ = writeln!(
+            file,
+            "{}.{:03} {} {}",
+            now.as_secs(),
+            now.subsec_millis(),
+            level.name(),
+            msg,
+        );
+    }
+}
@@ -2,101 +2,424 @@
 // Use of this source code is governed by an MIT
 // licence that can be found in the LICENCE file.
 
+use std::cmp::Reverse;
 use std::collections::HashMap;
 use std::env;
+use std::ffi::OsString;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::io::Error as IoError;
+use std::io::IsTerminal;
+use std::io::Read;
+use std::num::NonZeroUsize;
+use std::num::ParseIntError;
+use std::path::Path;
+use std::path::PathBuf;
 use std::process;
+use std::process::ExitStatus;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
 
+mod config;
+mod custom_tool;
 mod dep_tools;
+mod diagnostics;
+#[cfg(feature = "docker")]
+mod docker;
+mod doctor;
+mod exit_codes;
+mod file_tool;
+mod hooks;
 mod install;
+mod interrupt;
+mod logger;
+#[cfg(feature = "test-util")]
+mod mock_tool;
+mod object_store;
+mod pack;
+mod plugin;
+mod prompt;
 mod render_errors;
+mod sha256;
+mod store;
+mod svn;
+mod temp_dir;
+mod workspace;
 
+use config::Config;
+use custom_tool::Custom;
 use dep_tools::DepTool;
+use dep_tools::DepToolError;
 use dep_tools::Git;
-use dep_tools::GitCmdError;
+use dep_tools::GitFile;
+use diagnostics::COLOR_CHOICE_NAMES;
+use diagnostics::ColorChoice;
+use diagnostics::Diagnostics;
+#[cfg(feature = "docker")]
+use docker::Docker;
+use doctor::Finding;
+use doctor::Severity;
+use file_tool::File;
+use install::CONFLICT_POLICY_NAMES;
+use install::ConflictPolicy;
+use install::DEPS_FILE_FORMAT_VERSION;
+use install::DepDiff;
+use install::FetchEvent;
+use install::FetchOptions;
+use install::FetchStats;
+use install::GroupFilter;
+use install::InstallError;
+use install::InstallMode;
+use install::InstallOptions;
+use install::InstallOutcome;
+use install::InstalledDep;
 use install::Installer;
+use install::LinkMode;
+use install::OutputOptions;
+use install::ResolvedDep;
+use install::STATE_FILE_FORMAT_VERSION;
+use install::StateOptions;
+use install::StateRepair;
+use install::unix_now;
+use install::utc_time;
+use interrupt::INTERRUPTED_EXIT_CODE;
+use logger::FileLogger;
+use logger::LEVEL_NAMES;
+use logger::Level;
+use logger::Logger;
+use logger::NopLogger;
+#[cfg(feature = "test-util")]
+use mock_tool::MockDepTool;
+use object_store::ObjectStore;
+use prompt::Prompter;
+use prompt::TerminalPrompter;
+use store::PrunePolicy;
+use svn::Svn;
 
 extern crate clap;
 extern crate regex;
+extern crate serde_json;
 extern crate snafu;
 
 use clap::App;
 use clap::AppSettings;
 use clap::Arg;
+use clap::ArgMatches;
 use clap::SubCommand;
 use regex::Regex;
+use serde_json::Value as JsonValue;
 
 fn main() {
     let deps_file_name = "dpnd.txt";
 
-    let install_about: &str = &format!(
-        "Install dependencies defined in '{}'",
+    let abouts = subcommand_abouts(deps_file_name);
+    let app = app(&abouts);
+    let args = app.clone().get_matches();
+
+    // `--version-output` doesn't need a subcommand, so
+    // `AppSettings::SubcommandRequiredElseHelp` is only applied to other
+    // command lines, by parsing them again with the setting.
+    if args.subcommand_name().is_none() && !args.is_present(VERSION_OUTPUT_OPT)
+    {
+        app.setting(AppSettings::SubcommandRequiredElseHelp)
+            .get_matches_from_safe(env::args_os())
+            .expect_err("`clap` accepted a command line without a subcommand")
+            .exit();
+    }
+
+    let color_choice = args.value_of(COLOR_OPT)
+        .and_then(ColorChoice::parse)
+        .expect("`--color` was validated by `clap`");
+    let diag = Diagnostics::new(color_choice);
+
+    let cwd = change_dir(&args, &diag);
+
+    let git = new_git(&args, &diag);
+    let git_file = GitFile{git: &git};
+
+    let path_var = env::var_os("PATH").unwrap_or_default();
+    let plugins = plugin::find_plugins(&path_var);
+
+    let mut tools: HashMap<String, &dyn DepTool<DepToolError>> =
+        HashMap::new();
+    for plugin in &plugins {
+        tools.insert(plugin.name.clone(), plugin);
+    }
+    // Built-in tools take precedence over plugins with the same name.
+    tools.insert("git".to_string(), &git);
+    tools.insert("svn".to_string(), &Svn);
+    tools.insert("file".to_string(), &File);
+    tools.insert("git-file".to_string(), &git_file);
+    tools.insert("object-store".to_string(), &ObjectStore);
+    tools.insert("custom".to_string(), &Custom);
+    #[cfg(feature = "docker")]
+    tools.insert("docker".to_string(), &Docker);
+    #[cfg(feature = "test-util")]
+    let mock_tool = load_mock_tool();
+    #[cfg(feature = "test-util")]
+    {
+        if let Some(mock_tool) = &mock_tool {
+            tools.insert("mock".to_string(), mock_tool);
+        }
+    }
+
+    if let Some(format) = args.value_of(VERSION_OUTPUT_OPT) {
+        let mut tool_names: Vec<&str> =
+            tools.keys().map(String::as_str).collect();
+        tool_names.sort_unstable();
+        println!("{}", render_version_output(format, &tool_names));
+
+        return;
+    }
+
+    let file_logger = open_log_file(&args, &diag);
+    let logger: &dyn Logger =
+        match &file_logger {
+            Some(file_logger) => {
+                file_logger
+            },
+            None => {
+                &NopLogger
+            },
+        };
+    let cmd_line: Vec<String> = env::args().collect();
+    logger.log(Level::Info, &format!("Running `{}`", cmd_line.join(" ")));
+
+    // Dependency names are restricted to ASCII so that names that look the
+    // same can't differ by Unicode normalization form, which matters because
+    // names are used as directory names, and some filesystems normalize them.
+    let bad_dep_name_chars = Regex::new(r"[^a-zA-Z0-9._-]").unwrap();
+    let installer = Installer{
+        deps_file_name: deps_file_name.to_string(),
+        state_file_name: format!("current_{}", deps_file_name),
+        bad_dep_name_chars,
+        tools,
+        logger,
+        validate_sources: !args.is_present(NO_VALIDATE_SOURCES_FLAG),
+    };
+
+    let config_dir = installer.proj_dir(&cwd).unwrap_or_else(|_| cwd.clone());
+    let ctx = Context{
         deps_file_name,
-    );
-    let install_recursive_flag = "recursive";
-
-    let args =
-        App::new("dpnd")
-            .version(env!("CARGO_PKG_VERSION"))
-            .author(env!("CARGO_PKG_AUTHORS"))
-            .about(env!("CARGO_PKG_DESCRIPTION"))
-            .settings(&[
-                AppSettings::SubcommandRequiredElseHelp,
-                AppSettings::VersionlessSubcommands,
-            ])
-            .subcommands(vec![
-                SubCommand::with_name("install")
-                    .about(install_about)
-                    .args(&[
-                        Arg::with_name(install_recursive_flag)
-                            .short("r")
-                            .long("recursive")
-                            .help(
-                                "Install dependencies found in dependencies",
-                            ),
-                    ]),
-            ])
-            .get_matches();
+        cwd,
+        diag,
+        installer,
+        logger,
+        user_config_path: user_config_path(),
+        config_dir,
+    };
+    configure_network(&ctx, &args);
+
+    if let Err((code, msg)) = run(&ctx, &args) {
+        ctx.diag.error(&msg);
+        process::exit(code);
+    }
+}
+
+// `Context` holds the state that's shared by the subcommands.
+struct Context<'a> {
+    deps_file_name: &'a str,
+    cwd: PathBuf,
+    diag: Diagnostics,
+    installer: Installer<'a, DepToolError>,
+    logger: &'a dyn Logger,
+    user_config_path: Option<PathBuf>,
+    // `config_dir` is the directory that the project config file is loaded
+    // from, which is the project directory if `cwd` is in a project.
+    config_dir: PathBuf,
+}
+
+impl Context<'_> {
+    // `install_error` returns the exit code for `err`, and `err` rendered
+    // relative to `self.cwd`.
+    fn install_error(&self, err: InstallError<DepToolError>) -> (i32, String) {
+        let code = exit_codes::install_error_code(&err);
+        let msg = render_errors::render_install_error(
+            err,
+            &self.cwd,
+            self.deps_file_name,
+        );
+
+        (code, msg)
+    }
+
+    // `warn` reports problems that don't stop installation.
+    fn warn(&self, err: InstallError<DepToolError>) {
+        let msg = render_errors::render_install_error(
+            err,
+            &self.cwd,
+            self.deps_file_name,
+        );
+        self.diag.warning(&msg);
+    }
+}
+
+// `change_dir` changes the working directory of the process to each directory
+// given by `--cwd` in turn, like `git -C`, so that relative paths given as
+// arguments are also relative to the new directory, and returns the new
+// working directory. It exits the process if the directory can't be changed.
+fn change_dir(args: &ArgMatches, diag: &Diagnostics) -> PathBuf {
+    for dir in args.values_of(CWD_OPT).into_iter().flatten() {
+        if let Err(err) = env::set_current_dir(dir) {
+            diag.error(&format!(
+                "Couldn't change the working directory to '{}': {}",
+                dir,
+                err,
+            ));
+            process::exit(exit_codes::FAILURE);
+        }
+    }
+
+    match env::current_dir() {
+        Ok(dir) => {
+            dir
+        },
+        Err(err) => {
+            let msg = format!("Couldn't get the current directory: {}", err);
+            diag.error(&msg);
+            process::exit(exit_codes::FAILURE);
+        },
+    }
+}
+
+// `new_git` returns the `git` tool, configured by `args` and the environment.
+// It exits the process if the Git token file can't be read.
+fn new_git(args: &ArgMatches, diag: &Diagnostics) -> Git {
+    let git_token = match read_git_token() {
+        Ok(token) => {
+            token
+        },
+        Err(err) => {
+            diag.error(&format!(
+                "Couldn't read the Git token file defined by `{}`: {}",
+                GIT_TOKEN_FILE_VAR,
+                err,
+            ));
+            process::exit(exit_codes::FAILURE);
+        },
+    };
+    // The progress of clones is only shown on terminals, and `install
+    // --quiet` hides it along with the summary.
+    let quiet =
+        args.subcommand_matches("install")
+            .is_some_and(|sub_args| sub_args.is_present(INSTALL_QUIET_FLAG));
+
+    Git{
+        token: git_token,
+        trusted_keys: read_trusted_keys(),
+        progress: io::stderr().is_terminal() && !quiet,
+    }
+}
 
+// `open_log_file` opens the log file given by `--log-file`, if any. It exits
+// the process if the log file can't be opened.
+fn open_log_file(args: &ArgMatches, diag: &Diagnostics) -> Option<FileLogger> {
+    let path = args.value_of(LOG_FILE_OPT)?;
+    let max_level = args.value_of(LOG_LEVEL_OPT)
+        .and_then(Level::parse)
+        .expect("`--log-level` was validated by `clap`");
+
+    match FileLogger::open(Path::new(path), max_level) {
+        Ok(file_logger) => {
+            Some(file_logger)
+        },
+        Err(err) => {
+            diag.error(&format!(
+                "Couldn't open the log file '{}': {}",
+                path,
+                err,
+            ));
+            process::exit(exit_codes::FAILURE);
+        },
+    }
+}
+
+// `configure_network` configures proxies and SSH before any command is run,
+// because most commands access the network. Errors in config files are
+// reported by the commands that use the other settings, so they're ignored
+// here.
+fn configure_network(ctx: &Context, args: &ArgMatches) {
+    let network_config =
+        Config::load(ctx.user_config_path.as_deref(), &ctx.config_dir)
+            .unwrap_or_default();
+    apply_proxy_config(&network_config);
+    let accept_new_host_keys =
+        args.is_present(ACCEPT_NEW_HOST_KEYS_FLAG)
+            || network_config.accept_new_host_keys.unwrap_or(false);
+    if accept_new_host_keys {
+        apply_accept_new_host_keys(&ctx.diag);
+    }
+}
+
+// `run` runs the subcommand given in `args`, and returns an exit code and a
+// rendered error message if it fails. Each subcommand is run by the `run_`
+// function named after it.
+fn run(ctx: &Context, args: &ArgMatches) -> Result<(), (i32, String)> {
     match args.subcommand() {
         ("install", Some(sub_args)) => {
-            let cwd = match env::current_dir() {
-                Ok(dir) => {
-                    dir
-                },
-                Err(err) => {
-                    eprintln!("Couldn't get the current directory: {}", err);
-                    process::exit(1);
-                },
-            };
-
-            let mut tools: HashMap<String, &dyn DepTool<GitCmdError>> =
-                HashMap::new();
-            tools.insert("git".to_string(), &Git{});
-
-            let bad_dep_name_chars = Regex::new(r"[^a-zA-Z0-9._-]").unwrap();
-            let installer = &Installer{
-                deps_file_name: deps_file_name.to_string(),
-                state_file_name: format!("current_{}", deps_file_name),
-                bad_dep_name_chars,
-                tools,
-            };
-            let install_result = installer.install(
-                &cwd,
-                sub_args.is_present(install_recursive_flag),
-            );
-            if let Err(err) = install_result {
-                let msg = render_errors::render_install_error(
-                    err,
-                    &cwd,
-                    deps_file_name,
-                );
-                eprintln!("{}", msg);
-                process::exit(1);
-            }
+            run_install(ctx, sub_args)
+        },
+        ("resolve", Some(sub_args)) => {
+            run_resolve(ctx, sub_args)
+        },
+        ("hook", Some(sub_args)) => {
+            run_hook(ctx, sub_args)
+        },
+        ("owners", Some(sub_args)) => {
+            run_owners(ctx, sub_args)
+        },
+        ("pack", Some(sub_args)) => {
+            run_pack(ctx, sub_args)
+        },
+        ("unpack", Some(sub_args)) => {
+            run_unpack(ctx, sub_args)
+        },
+        ("prune-cache", Some(sub_args)) => {
+            run_prune_cache(ctx, sub_args)
+        },
+        ("diff", _) => {
+            run_diff(ctx)
+        },
+        ("upgrade", Some(sub_args)) => {
+            run_upgrade(ctx, sub_args)
+        },
+        ("fetch", Some(sub_args)) => {
+            run_fetch(ctx, sub_args)
+        },
+        ("pin", _) => {
+            run_pin(ctx)
+        },
+        ("fmt", Some(sub_args)) => {
+            run_fmt(ctx, sub_args)
+        },
+        ("validate", Some(sub_args)) => {
+            run_validate(ctx, sub_args)
+        },
+        ("repair", Some(_)) => {
+            run_repair(ctx)
+        },
+        ("doctor", Some(_)) => {
+            run_doctor(ctx);
+
+            Ok(())
+        },
+        ("exec", Some(sub_args)) => {
+            run_exec(ctx, sub_args)
+        },
+        ("list", Some(sub_args)) => {
+            run_list(ctx, sub_args)
+        },
+        ("sbom", Some(sub_args)) => {
+            run_sbom(ctx, sub_args)
+        },
+        ("graph", Some(sub_args)) => {
+            run_graph(ctx, sub_args)
         },
         (arg_name, sub_args) => {
-            // All subcommands defined in `args_defn` should be handled here,
-            // so matching an unhandled command shouldn't happen.
+            // All subcommands defined in `app` should be handled here, so
+            // matching an unhandled command shouldn't happen.
             panic!(
                 "unexpected command '{}' (arguments: '{:?}')",
                 arg_name,
@@ -105,3 +428,2444 @@ fn main() {
         },
     }
 }
+
+// `InstallArgs` holds the settings of `install` that are given on the command
+// line. Settings that can also be defined in config files are `None` if they
+// aren't given, so that flags take precedence over config files.
+struct InstallArgs<'a> {
+    sub_args: &'a ArgMatches<'a>,
+    recurse: Option<bool>,
+    vendor: Option<bool>,
+    normalize_mtimes: Option<bool>,
+    keep_going: Option<bool>,
+    fetch_timeout: Option<u64>,
+    conflicts: Option<ConflictPolicy>,
+    mode: InstallMode,
+    link: Option<LinkMode>,
+    jobs: Option<usize>,
+    group_filter: Option<GroupFilter>,
+    dep_names: Option<Vec<String>>,
+    // `deps_spec` is the dependency file that was read from `stdin`, if
+    // `--stdin` was given.
+    deps_spec: Option<Vec<u8>>,
+}
+
+// `parse_install_args` returns the settings of `install` that are given in
+// `sub_args`, or an exit code and an error message if they're invalid.
+fn parse_install_args<'a>(sub_args: &'a ArgMatches<'a>)
+    -> Result<InstallArgs<'a>, (i32, String)>
+{
+    let fetch_timeout =
+        sub_args.value_of(INSTALL_FETCH_TIMEOUT_OPT)
+            .map(str::parse::<u64>)
+            .transpose()
+            .map_err(|err| (
+                exit_codes::FAILURE,
+                format!(
+                    "`--fetch-timeout` isn't a valid number of seconds: {}",
+                    err,
+                ),
+            ))?;
+
+    let jobs =
+        sub_args.value_of(INSTALL_JOBS_OPT)
+            .map(str::parse::<NonZeroUsize>)
+            .transpose()
+            .map_err(|err| (
+                exit_codes::FAILURE,
+                format!("`--jobs` isn't a valid number of jobs: {}", err),
+            ))?
+            .map(NonZeroUsize::get);
+
+    let to_groups = |groups: clap::Values| {
+        groups.map(ToString::to_string).collect()
+    };
+    let group_filter =
+        if let Some(groups) = sub_args.values_of(INSTALL_ONLY_OPT) {
+            Some(GroupFilter::Only(to_groups(groups)))
+        } else {
+            sub_args.values_of(INSTALL_SKIP_OPT)
+                .map(|groups| GroupFilter::Skip(to_groups(groups)))
+        };
+
+    let mode =
+        if sub_args.is_present(INSTALL_FROZEN_FLAG) {
+            InstallMode::Frozen
+        } else if sub_args.is_present(INSTALL_CHECK_ONLY_FLAG) {
+            InstallMode::CheckOnly
+        } else {
+            InstallMode::Install
+        };
+    let link =
+        if sub_args.is_present(INSTALL_LINK_COPY_FLAG) {
+            Some(LinkMode::Copy)
+        } else if sub_args.is_present(INSTALL_LINK_FLAG) {
+            Some(LinkMode::Symlink)
+        } else {
+            None
+        };
+
+    let mut deps_spec = None;
+    if sub_args.is_present(INSTALL_STDIN_FLAG) {
+        let mut spec = vec![];
+        if let Err(err) = io::stdin().read_to_end(&mut spec) {
+            return Err((
+                exit_codes::FAILURE,
+                format!(
+                    "Couldn't read the dependency file from standard input: \
+                     {}",
+                    err,
+                ),
+            ));
+        }
+        deps_spec = Some(spec);
+    }
+
+    Ok(InstallArgs{
+        sub_args,
+        recurse: flag_setting(
+            sub_args,
+            INSTALL_RECURSIVE_FLAG,
+            INSTALL_NO_RECURSIVE_FLAG,
+        ),
+        vendor: flag_setting(
+            sub_args,
+            INSTALL_VENDOR_FLAG,
+            INSTALL_NO_VENDOR_FLAG,
+        ),
+        normalize_mtimes: flag_setting(
+            sub_args,
+            INSTALL_DETERMINISTIC_MTIMES_FLAG,
+            INSTALL_NO_DETERMINISTIC_MTIMES_FLAG,
+        ),
+        keep_going: flag_setting(
+            sub_args,
+            INSTALL_KEEP_GOING_FLAG,
+            INSTALL_FAIL_FAST_FLAG,
+        ),
+        fetch_timeout,
+        conflicts: sub_args.value_of(INSTALL_CONFLICTS_OPT)
+            .and_then(ConflictPolicy::parse),
+        mode,
+        link,
+        jobs,
+        group_filter,
+        dep_names: sub_args.values_of(INSTALL_DEPS_ARG)
+            .map(|names| names.map(ToString::to_string).collect()),
+        deps_spec,
+    })
+}
+
+fn run_install(ctx: &Context, sub_args: &ArgMatches)
+    -> Result<(), (i32, String)>
+{
+    // Interrupts are only caught while installing, because other commands
+    // don't leave partial results behind.
+    interrupt::catch_interrupts();
+
+    let install_args = parse_install_args(sub_args)?;
+
+    // Questions can't be answered if `stdin` isn't a terminal, in which case
+    // `--interactive` has no effect.
+    let terminal_prompter =
+        if sub_args.is_present(INSTALL_INTERACTIVE_FLAG) {
+            TerminalPrompter::new()
+        } else {
+            None
+        };
+    let prompter = terminal_prompter.as_ref().map(|p| p as &dyn Prompter);
+
+    let start = Instant::now();
+    let mut outcome = InstallOutcome::default();
+    // `failure_code` is the exit code of the first failed installation, if
+    // any.
+    let mut failure_code = None;
+    if sub_args.is_present(INSTALL_WORKSPACE_FLAG) {
+        let members = workspace::read_members(&ctx.cwd)
+            .map_err(|err| (
+                exit_codes::FAILURE,
+                render_errors::render_workspace_error(err, &ctx.cwd),
+            ))?;
+
+        // A failure to install the dependencies of one member doesn't stop
+        // the dependencies of other members from being installed, so that
+        // all failures are reported at once. The result for each member is
+        // written to `stderr`, like the summary, so that `stdout` stays empty
+        // on success.
+        let quiet = sub_args.is_present(INSTALL_QUIET_FLAG);
+        for member in members {
+            match install_in(ctx, &install_args, prompter, &member.dir) {
+                Ok(member_outcome) => {
+                    if !quiet {
+                        eprintln!("{}: ok", member.name);
+                    }
+                    outcome.add(member_outcome);
+                },
+                Err((code, msg)) => {
+                    if !quiet {
+                        eprintln!("{}: failed", member.name);
+                    }
+                    ctx.diag.error(&format!("{}: {}", member.name, msg));
+                    failure_code = failure_code.or(Some(code));
+                    if interrupt::interrupted() {
+                        break;
+                    }
+                },
+            }
+        }
+    } else {
+        match install_in(ctx, &install_args, prompter, &ctx.cwd) {
+            Ok(proj_outcome) => {
+                outcome = proj_outcome;
+            },
+            Err((code, msg)) => {
+                ctx.diag.error(&msg);
+                failure_code = Some(code);
+            },
+        }
+    }
+
+    // The report is written even if an installation failed, in which case it
+    // only covers the projects that were installed.
+    if let Some(path) = sub_args.value_of(INSTALL_REPORT_OPT) {
+        let report = render_fetch_stats_json(&outcome.fetches, &ctx.cwd);
+        if let Err(err) = fs::write(ctx.cwd.join(path), report) {
+            return Err((
+                exit_codes::FAILURE,
+                format!("Couldn't write the report to '{}': {}", path, err),
+            ));
+        }
+    }
+    if interrupt::interrupted() {
+        process::exit(INTERRUPTED_EXIT_CODE);
+    }
+    if let Some(code) = failure_code {
+        process::exit(code);
+    }
+
+    // The summary is written to `stderr` so that scripts that expect `dpnd
+    // install` to have no output on success aren't affected. Checks don't
+    // change anything, so they aren't summarized.
+    let check_only = install_args.mode.checks_only();
+    if !check_only && !sub_args.is_present(INSTALL_QUIET_FLAG) {
+        eprintln!("{}", render_install_outcome(&outcome, start.elapsed()));
+        if sub_args.is_present(INSTALL_CI_FLAG) {
+            eprint!("{}", render_fetch_times(&outcome.fetches));
+        }
+    }
+
+    Ok(())
+}
+
+// `install_in` installs the dependencies of the project in `dir`, using the
+// config files of that project, and returns an exit code and the rendered
+// error if the installation fails.
+fn install_in(
+    ctx: &Context,
+    install_args: &InstallArgs,
+    prompter: Option<&dyn Prompter>,
+    dir: &Path,
+)
+    -> Result<InstallOutcome, (i32, String)>
+{
+    let sub_args = install_args.sub_args;
+
+    // A dependency file that's read from `stdin` doesn't have a project
+    // directory, so `dir` is used instead.
+    let config =
+        if install_args.deps_spec.is_some() {
+            load_proj_config(dir, dir)?
+        } else {
+            load_config(&ctx.installer, dir)?
+        };
+    let config =
+        if sub_args.is_present(INSTALL_CI_FLAG) {
+            Config::ci_preset().or(config)
+        } else {
+            config
+        };
+    let fetch_hook = |event: &FetchEvent| {
+        run_fetch_hook(&config, event, &ctx.diag);
+    };
+    let warn = |err| ctx.warn(err);
+    let normalize_mtimes =
+        install_args.normalize_mtimes
+            .or(config.deterministic_mtimes)
+            .unwrap_or(false);
+    let source_date_epoch = source_date_epoch_setting(normalize_mtimes)?;
+
+    let mut opts = InstallOptions{
+        recurse: install_args.recurse.or(config.recursive).unwrap_or(false),
+        mode: install_args.mode,
+        changed_since: sub_args.value_of(INSTALL_CHANGED_SINCE_OPT),
+        group_filter: install_args.group_filter.clone(),
+        dep_names: install_args.dep_names.clone(),
+        deps_spec: install_args.deps_spec.as_deref(),
+        force: sub_args.is_present(INSTALL_FORCE_FLAG),
+        prompter,
+        conflicts:
+            install_args.conflicts
+                .or(config.conflicts)
+                .unwrap_or(ConflictPolicy::Warn),
+        warn: Some(&warn),
+        fetch: FetchOptions{
+            timeout:
+                install_args.fetch_timeout
+                    .or(config.fetch_timeout)
+                    .map(Duration::from_secs),
+            jobs:
+                install_args.jobs
+                    .or(config.jobs)
+                    .unwrap_or_else(default_jobs),
+            keep_going:
+                install_args.keep_going
+                    .or(config.keep_going)
+                    .unwrap_or(false),
+            store_dir: store::dir(config.store_dir.as_deref()),
+            hook: Some(&fetch_hook),
+        },
+        output: OutputOptions{
+            output_dir:
+                sub_args.value_of(INSTALL_OUTPUT_DIR_OPT)
+                    .map(|dir| ctx.cwd.join(dir)),
+            link: install_args.link,
+            vendor: install_args.vendor.or(config.vendor).unwrap_or(false),
+            normalize_mtimes,
+            source_date_epoch,
+            readonly: sub_args.is_present(INSTALL_READONLY_FLAG),
+        },
+        state: StateOptions{
+            no_state_file: sub_args.is_present(INSTALL_NO_STATE_FILE_FLAG),
+            check_integrity:
+                sub_args.is_present(INSTALL_CHECK_INTEGRITY_FLAG),
+            annotate: sub_args.is_present(INSTALL_ANNOTATE_FLAG),
+            summary_path: None,
+        },
+    };
+    if sub_args.is_present(INSTALL_CI_FLAG) {
+        apply_ci_preset(&mut opts);
+    }
+
+    let result =
+        ctx.installer.install(dir, &opts)
+            .map_err(|err| ctx.install_error(err));
+    if let Err((_, msg)) = &result {
+        ctx.logger.log(Level::Error, msg);
+    }
+
+    result
+}
+
+fn run_resolve(ctx: &Context, sub_args: &ArgMatches)
+    -> Result<(), (i32, String)>
+{
+    let resolved_deps =
+        ctx.installer.resolve(&ctx.cwd)
+            .map_err(|err| ctx.install_error(err))?;
+
+    if sub_args.value_of(RESOLVE_FORMAT_OPT) == Some("json") {
+        println!("{}", render_resolved_deps_json(&resolved_deps));
+    } else {
+        for dep in resolved_deps {
+            println!(
+                "{} {} {} {}",
+                dep.name,
+                dep.tool,
+                dep.source,
+                dep.revision,
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn run_hook(ctx: &Context, sub_args: &ArgMatches)
+    -> Result<(), (i32, String)>
+{
+    let dep_paths =
+        ctx.installer.dep_paths(&ctx.cwd)
+            .map_err(|err| ctx.install_error(err))?;
+
+    if sub_args.value_of(HOOK_MANAGER_ARG) == Some("mise") {
+        print!("{}", hooks::render_mise_hook(&dep_paths));
+    } else {
+        print!("{}", hooks::render_direnv_hook(&dep_paths));
+    }
+
+    Ok(())
+}
+
+fn run_owners(ctx: &Context, sub_args: &ArgMatches)
+    -> Result<(), (i32, String)>
+{
+    let query = sub_args.value_of(OWNERS_DEP_ARG)
+        .expect("`owners` was run without a dependency");
+
+    let (dep_name, maybe_owner) =
+        ctx.installer.dep_owner(&ctx.cwd, query)
+            .map_err(|err| ctx.install_error(err))?;
+
+    if let Some(owner) = maybe_owner {
+        println!("{}", owner);
+
+        Ok(())
+    } else {
+        Err((
+            exit_codes::FAILURE,
+            format!("The '{}' dependency doesn't have an owner", dep_name),
+        ))
+    }
+}
+
+fn run_pack(ctx: &Context, sub_args: &ArgMatches)
+    -> Result<(), (i32, String)>
+{
+    let archive_path = sub_args.value_of(ARCHIVE_ARG)
+        .expect("`pack` was run without an archive");
+
+    let mtime =
+        read_source_date_epoch()
+            .map_err(|err| (
+                exit_codes::FAILURE,
+                format!(
+                    "`{}` isn't a valid number of seconds: {}",
+                    SOURCE_DATE_EPOCH_VAR,
+                    err,
+                ),
+            ))?
+            .unwrap_or(0);
+
+    // Only dependencies that are up to date are packed, so that the archive
+    // matches the dependency file that it contains.
+    let check_opts = InstallOptions{
+        recurse: false,
+        mode: InstallMode::CheckOnly,
+        changed_since: None,
+        group_filter: None,
+        dep_names: None,
+        deps_spec: None,
+        force: false,
+        prompter: None,
+        conflicts: ConflictPolicy::Warn,
+        warn: None,
+        fetch: FetchOptions::default(),
+        output: OutputOptions::default(),
+        state: StateOptions::default(),
+    };
+    let installer = &ctx.installer;
+    let (dep_paths, installed_deps) =
+        installer.install(&ctx.cwd, &check_opts)
+            .and_then(|_| {
+                let paths = installer.dep_paths(&ctx.cwd)?;
+                let deps = installer.installed_deps(&ctx.cwd, true)?;
+
+                Ok((paths, deps))
+            })
+            .map_err(|err| ctx.install_error(err))?;
+
+    let provenance = render_provenance_json(&installed_deps);
+    pack::pack(&dep_paths, &ctx.cwd.join(archive_path), mtime, &provenance)
+        .map_err(|err| (
+            exit_codes::FAILURE,
+            render_errors::render_pack_error(err, &ctx.cwd),
+        ))
+}
+
+fn run_unpack(ctx: &Context, sub_args: &ArgMatches)
+    -> Result<(), (i32, String)>
+{
+    let archive_path = sub_args.value_of(ARCHIVE_ARG)
+        .expect("`unpack` was run without an archive");
+
+    let dep_paths =
+        ctx.installer.dep_paths(&ctx.cwd)
+            .map_err(|err| ctx.install_error(err))?;
+
+    pack::unpack(&dep_paths, &ctx.cwd.join(archive_path))
+        .map_err(|err| (
+            exit_codes::FAILURE,
+            render_errors::render_pack_error(err, &ctx.cwd),
+        ))
+}
+
+fn run_prune_cache(ctx: &Context, sub_args: &ArgMatches)
+    -> Result<(), (i32, String)>
+{
+    let config =
+        Config::load(ctx.user_config_path.as_deref(), &ctx.config_dir)
+            .map_err(|err| (
+                exit_codes::FAILURE,
+                render_errors::render_config_error(err, &ctx.cwd),
+            ))?;
+
+    let Some(store_dir) = store::dir(config.store_dir.as_deref()) else {
+        return Err((
+            exit_codes::FAILURE,
+            format!(
+                "The location of the store is unknown; set `{}`, or `dir` \
+                 in the `[cache]` section of a config file, to the \
+                 directory used as the store",
+                store::STORE_DIR_VAR,
+            ),
+        ));
+    };
+
+    let max_size =
+        match sub_args.value_of(PRUNE_CACHE_MAX_SIZE_OPT) {
+            Some(size) => {
+                let bytes = store::parse_size(size)
+                    .ok_or_else(|| (
+                        exit_codes::FAILURE,
+                        format!("`--max-size` isn't a valid size: '{}'", size),
+                    ))?;
+
+                Some(bytes)
+            },
+            None => {
+                config.cache_max_size
+            },
+        };
+
+    let max_age_days =
+        sub_args.value_of(PRUNE_CACHE_MAX_AGE_OPT)
+            .map(str::parse::<u64>)
+            .transpose()
+            .map_err(|err| (
+                exit_codes::FAILURE,
+                format!("`--max-age` isn't a valid number of days: {}", err),
+            ))?
+            .or(config.cache_max_age);
+
+    // If no limits are defined then every entry is removed, so that
+    // `prune-cache` can be used to clear the store.
+    let max_size =
+        if max_size.is_none() && max_age_days.is_none() {
+            Some(0)
+        } else {
+            max_size
+        };
+    let policy = PrunePolicy{
+        max_size,
+        max_age: max_age_days.map(|days| days * 86_400),
+    };
+    let dry_run = sub_args.is_present(PRUNE_CACHE_DRY_RUN_FLAG);
+
+    let pruned =
+        store::prune(&store_dir, &policy, unix_now(), dry_run)
+            .map_err(|err| (
+                exit_codes::FAILURE,
+                render_errors::render_prune_error(err, &ctx.cwd),
+            ))?;
+
+    let action = if dry_run { "Would remove" } else { "Removed" };
+    for entry in pruned {
+        println!(
+            "{} '{}' ({} bytes, last used {})",
+            action,
+            entry.path.display(),
+            entry.size,
+            utc_time(entry.last_used),
+        );
+    }
+
+    Ok(())
+}
+
+fn run_diff(ctx: &Context) -> Result<(), (i32, String)> {
+    let dep_diffs =
+        ctx.installer.diff(&ctx.cwd)
+            .map_err(|err| ctx.install_error(err))?;
+
+    for dep_diff in dep_diffs {
+        print!("{}", render_dep_diff(&dep_diff));
+    }
+
+    Ok(())
+}
+
+fn run_upgrade(ctx: &Context, sub_args: &ArgMatches)
+    -> Result<(), (i32, String)>
+{
+    let dep_name = sub_args.value_of(UPGRADE_DEP_ARG)
+        .expect("`upgrade` was run without a dependency");
+    let version = sub_args.value_of(UPGRADE_VERSION_ARG);
+
+    let config = load_config(&ctx.installer, &ctx.cwd)?;
+    let fetch_hook = |event: &FetchEvent| {
+        run_fetch_hook(&config, event, &ctx.diag);
+    };
+    let warn = |err| ctx.warn(err);
+    let normalize_mtimes = config.deterministic_mtimes.unwrap_or(false);
+    let source_date_epoch = source_date_epoch_setting(normalize_mtimes)?;
+
+    let opts = InstallOptions{
+        recurse: config.recursive.unwrap_or(false),
+        mode: InstallMode::Install,
+        changed_since: None,
+        group_filter: None,
+        dep_names: None,
+        deps_spec: None,
+        force: false,
+        prompter: None,
+        conflicts: config.conflicts.unwrap_or(ConflictPolicy::Warn),
+        warn: Some(&warn),
+        fetch: FetchOptions{
+            timeout: config.fetch_timeout.map(Duration::from_secs),
+            jobs: config.jobs.unwrap_or_else(default_jobs),
+            keep_going: false,
+            store_dir: store::dir(config.store_dir.as_deref()),
+            hook: Some(&fetch_hook),
+        },
+        output: OutputOptions{
+            vendor: config.vendor.unwrap_or(false),
+            normalize_mtimes,
+            source_date_epoch,
+            ..OutputOptions::default()
+        },
+        state: StateOptions::default(),
+    };
+    let (old_version, new_version) =
+        ctx.installer.upgrade(&ctx.cwd, dep_name, version, &opts)
+            .map_err(|err| ctx.install_error(err))?;
+
+    println!("{}: {} -> {}", dep_name, old_version, new_version);
+
+    Ok(())
+}
+
+fn run_fetch(ctx: &Context, sub_args: &ArgMatches)
+    -> Result<(), (i32, String)>
+{
+    let dep_name = sub_args.value_of(FETCH_DEP_ARG)
+        .expect("`fetch` was run without a dependency");
+
+    let config = load_config(&ctx.installer, &ctx.cwd)?;
+    let warn = |err| ctx.warn(err);
+
+    let opts = InstallOptions{
+        recurse: false,
+        mode: InstallMode::Install,
+        changed_since: None,
+        group_filter: None,
+        dep_names: None,
+        deps_spec: None,
+        force: false,
+        prompter: None,
+        conflicts: ConflictPolicy::Warn,
+        warn: Some(&warn),
+        fetch: FetchOptions{
+            timeout: config.fetch_timeout.map(Duration::from_secs),
+            ..FetchOptions::default()
+        },
+        output: OutputOptions::default(),
+        state: StateOptions::default(),
+    };
+    let dir =
+        ctx.installer.fetch(&ctx.cwd, dep_name, &opts)
+            .map_err(|err| ctx.install_error(err))?;
+
+    println!("{}", dir.display());
+
+    Ok(())
+}
+
+fn run_pin(ctx: &Context) -> Result<(), (i32, String)> {
+    let pinned_deps =
+        ctx.installer.pin(&ctx.cwd)
+            .map_err(|err| ctx.install_error(err))?;
+
+    for dep in pinned_deps {
+        println!("{}: {} -> {}", dep.name, dep.version, dep.revision);
+    }
+
+    Ok(())
+}
+
+fn run_fmt(ctx: &Context, sub_args: &ArgMatches)
+    -> Result<(), (i32, String)>
+{
+    let check = sub_args.is_present(FMT_CHECK_FLAG);
+
+    ctx.installer.format(&ctx.cwd, check)
+        .map_err(|err| ctx.install_error(err))?;
+
+    Ok(())
+}
+
+fn run_validate(ctx: &Context, sub_args: &ArgMatches)
+    -> Result<(), (i32, String)>
+{
+    let recurse = sub_args.is_present(VALIDATE_RECURSIVE_FLAG);
+    let problems =
+        ctx.installer.validate(&ctx.cwd, recurse)
+            .map_err(|err| ctx.install_error(err))?;
+
+    // The exit code is that of the first problem, so that it doesn't depend
+    // on how many problems were found.
+    if let Some(first) = problems.first() {
+        let code = exit_codes::install_error_code(first);
+        for problem in problems {
+            let (_, msg) = ctx.install_error(problem);
+            ctx.diag.error(&msg);
+        }
+        process::exit(code);
+    }
+
+    Ok(())
+}
+
+fn run_repair(ctx: &Context) -> Result<(), (i32, String)> {
+    // `repair` doesn't install files, so an invalid `SOURCE_DATE_EPOCH` is
+    // ignored, like in `source_date_epoch_setting`.
+    let source_date_epoch = read_source_date_epoch().unwrap_or(None);
+
+    let repairs =
+        ctx.installer.repair(&ctx.cwd, source_date_epoch)
+            .map_err(|err| ctx.install_error(err))?;
+
+    if repairs.is_empty() {
+        println!("The state files match the installed dependencies");
+    }
+    for repair in &repairs {
+        println!("{}", render_state_repair(repair, &ctx.cwd));
+    }
+
+    Ok(())
+}
+
+fn run_doctor(ctx: &Context) {
+    // Config files are only read for the location of the store, so an
+    // invalid config file is reported by the commands that use it instead.
+    let configured_store_dir =
+        Config::load(ctx.user_config_path.as_deref(), &ctx.config_dir)
+            .ok()
+            .and_then(|config| config.store_dir);
+    let store_dir = store::dir(configured_store_dir.as_deref());
+    let mut findings = vec![
+        doctor::check_git(),
+        doctor::check_store(store_dir.as_deref()),
+    ];
+
+    let render_install_error = |err| ctx.install_error(err).1;
+    // Sources are checked by `validate`, so that unreachable sources are
+    // reported in the same way by both commands.
+    match ctx.installer.validate(&ctx.cwd, false) {
+        Ok(problems) if problems.is_empty() => {
+            findings.push(Finding::ok(format!(
+                "'{}' is valid, and the sources of its dependencies can be \
+                 reached",
+                ctx.deps_file_name,
+            )));
+        },
+        Ok(problems) => {
+            for problem in problems {
+                let msg = render_install_error(problem);
+                findings.push(Finding::problem(msg));
+            }
+        },
+        Err(err) => {
+            findings.push(Finding::problem(render_install_error(err)));
+        },
+    }
+
+    match ctx.installer.check_installed(&ctx.cwd) {
+        Ok(problems) if problems.is_empty() => {
+            findings.push(Finding::ok(
+                "The output directories can be written to, and match their \
+                 state files".to_string(),
+            ));
+        },
+        Ok(problems) => {
+            for problem in problems {
+                let msg = render_install_error(problem);
+                findings.push(Finding::problem(msg));
+            }
+        },
+        Err(_) => {
+            // Errors that stop the dependency file from being read are also
+            // found by `validate`, so they've already been reported.
+        },
+    }
+
+    for finding in &findings {
+        println!("{}", doctor::render_finding(finding));
+    }
+
+    let problem_found =
+        findings.iter()
+            .any(|finding| finding.severity == Severity::Problem);
+    if problem_found {
+        process::exit(exit_codes::FAILURE);
+    }
+}
+
+fn run_exec(ctx: &Context, sub_args: &ArgMatches)
+    -> Result<(), (i32, String)>
+{
+    let dep_name = sub_args.value_of(EXEC_DEP_ARG)
+        .expect("`exec` was run without a dependency");
+    let cmd: Vec<&str> = sub_args.values_of(EXEC_CMD_ARG)
+        .expect("`exec` was run without a command")
+        .collect();
+
+    let dep_dir =
+        ctx.installer.installed_dep_dir(&ctx.cwd, dep_name)
+            .map_err(|err| ctx.install_error(err))?;
+
+    ctx.logger.log(Level::Info, &format!(
+        "Running `{}` in '{}'",
+        cmd.join(" "),
+        dep_dir.display(),
+    ));
+    let status =
+        process::Command::new(cmd[0])
+            .args(&cmd[1..])
+            .current_dir(&dep_dir)
+            .status()
+            .map_err(|err| (
+                exit_codes::FAILURE,
+                format!("Couldn't run `{}`: {}", cmd.join(" "), err),
+            ))?;
+
+    // A command that was killed by a signal doesn't have an exit code, so we
+    // report it as a general failure.
+    process::exit(status.code().unwrap_or(1));
+}
+
+fn run_list(ctx: &Context, sub_args: &ArgMatches)
+    -> Result<(), (i32, String)>
+{
+    let recurse = sub_args.is_present(LIST_RECURSIVE_FLAG);
+    let installed_deps =
+        ctx.installer.installed_deps(&ctx.cwd, recurse)
+            .map_err(|err| ctx.install_error(err))?;
+
+    let verbose = sub_args.is_present(LIST_VERBOSE_FLAG);
+    for dep in installed_deps {
+        println!("{}", render_installed_dep(&dep, verbose));
+    }
+
+    Ok(())
+}
+
+fn run_sbom(ctx: &Context, sub_args: &ArgMatches)
+    -> Result<(), (i32, String)>
+{
+    let recurse = sub_args.is_present(SBOM_RECURSIVE_FLAG);
+    let (proj_name, installed_deps) = installed_proj_deps(ctx, recurse)?;
+
+    println!("{}", render_cyclonedx_sbom(&proj_name, &installed_deps));
+
+    Ok(())
+}
+
+fn run_graph(ctx: &Context, sub_args: &ArgMatches)
+    -> Result<(), (i32, String)>
+{
+    let recurse = sub_args.is_present(GRAPH_RECURSIVE_FLAG);
+    let (proj_name, installed_deps) = installed_proj_deps(ctx, recurse)?;
+
+    // `--format` has a default value, so it's always present.
+    let rendered = match sub_args.value_of(GRAPH_FORMAT_OPT) {
+        Some("json") => {
+            render_graph_json(&proj_name, &installed_deps)
+        },
+        _ => {
+            render_graph_dot(&proj_name, &installed_deps)
+        },
+    };
+    println!("{}", rendered);
+
+    Ok(())
+}
+
+// `installed_proj_deps` returns the name of the project that contains
+// `ctx.cwd`, and the dependencies installed for it.
+fn installed_proj_deps(ctx: &Context, recurse: bool)
+    -> Result<(String, Vec<InstalledDep>), (i32, String)>
+{
+    let (proj_dir, installed_deps) =
+        ctx.installer.proj_dir(&ctx.cwd)
+            .and_then(|proj_dir| {
+                let deps = ctx.installer.installed_deps(&ctx.cwd, recurse)?;
+
+                Ok((proj_dir, deps))
+            })
+            .map_err(|err| ctx.install_error(err))?;
+
+    let proj_name =
+        proj_dir.file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+    Ok((proj_name, installed_deps))
+}
+
+// The following constants are the names of the arguments of the command line,
+// which are used both to define the command line in `app` and to read it in
+// the subcommands.
+const INSTALL_RECURSIVE_FLAG: &str = "recursive";
+const INSTALL_NO_RECURSIVE_FLAG: &str = "no-recursive";
+const INSTALL_CHANGED_SINCE_OPT: &str = "changed-since";
+const INSTALL_VENDOR_FLAG: &str = "vendor";
+const INSTALL_NO_VENDOR_FLAG: &str = "no-vendor";
+const INSTALL_DETERMINISTIC_MTIMES_FLAG: &str = "deterministic-mtimes";
+const INSTALL_NO_DETERMINISTIC_MTIMES_FLAG: &str = "no-deterministic-mtimes";
+const INSTALL_CHECK_ONLY_FLAG: &str = "check-only";
+const INSTALL_FROZEN_FLAG: &str = "frozen";
+const INSTALL_CI_FLAG: &str = "ci";
+const INSTALL_ANNOTATE_FLAG: &str = "annotate";
+const INSTALL_FORCE_FLAG: &str = "force";
+const INSTALL_INTERACTIVE_FLAG: &str = "interactive";
+const INSTALL_FETCH_TIMEOUT_OPT: &str = "fetch-timeout";
+const INSTALL_JOBS_OPT: &str = "jobs";
+const INSTALL_OUTPUT_DIR_OPT: &str = "output-dir";
+const INSTALL_STDIN_FLAG: &str = "stdin";
+const INSTALL_LINK_FLAG: &str = "link";
+const INSTALL_LINK_COPY_FLAG: &str = "link-copy";
+const INSTALL_READONLY_FLAG: &str = "readonly";
+const INSTALL_CHECK_INTEGRITY_FLAG: &str = "check-integrity";
+const INSTALL_NO_STATE_FILE_FLAG: &str = "no-state-file";
+const INSTALL_KEEP_GOING_FLAG: &str = "keep-going";
+const INSTALL_FAIL_FAST_FLAG: &str = "fail-fast";
+const INSTALL_QUIET_FLAG: &str = "quiet";
+const INSTALL_REPORT_OPT: &str = "report";
+const INSTALL_WORKSPACE_FLAG: &str = "workspace";
+const INSTALL_ONLY_OPT: &str = "only";
+const INSTALL_SKIP_OPT: &str = "skip";
+const INSTALL_DEPS_ARG: &str = "deps";
+const INSTALL_CONFLICTS_OPT: &str = "conflicts";
+const RESOLVE_FORMAT_OPT: &str = "format";
+const HOOK_MANAGER_ARG: &str = "manager";
+const OWNERS_DEP_ARG: &str = "dep";
+const UPGRADE_DEP_ARG: &str = "dep";
+const UPGRADE_VERSION_ARG: &str = "version";
+const FETCH_DEP_ARG: &str = "dep";
+const VALIDATE_RECURSIVE_FLAG: &str = "recursive";
+const FMT_CHECK_FLAG: &str = "check";
+const EXEC_DEP_ARG: &str = "dep";
+const EXEC_CMD_ARG: &str = "cmd";
+const LIST_RECURSIVE_FLAG: &str = "recursive";
+const LIST_VERBOSE_FLAG: &str = "verbose";
+const SBOM_FORMAT_OPT: &str = "format";
+const SBOM_RECURSIVE_FLAG: &str = "recursive";
+const GRAPH_FORMAT_OPT: &str = "format";
+const GRAPH_RECURSIVE_FLAG: &str = "recursive";
+const ARCHIVE_ARG: &str = "archive";
+const PRUNE_CACHE_MAX_SIZE_OPT: &str = "max-size";
+const PRUNE_CACHE_MAX_AGE_OPT: &str = "max-age";
+const PRUNE_CACHE_DRY_RUN_FLAG: &str = "dry-run";
+const LOG_FILE_OPT: &str = "log-file";
+const LOG_LEVEL_OPT: &str = "log-level";
+const COLOR_OPT: &str = "color";
+const NO_VALIDATE_SOURCES_FLAG: &str = "no-validate-sources";
+const ACCEPT_NEW_HOST_KEYS_FLAG: &str = "accept-new-host-keys";
+const CWD_OPT: &str = "cwd";
+const VERSION_OUTPUT_OPT: &str = "version-output";
+
+// `subcommand_abouts` returns the description of each subcommand, indexed by
+// the name of the subcommand.
+fn subcommand_abouts(deps_file_name: &str) -> HashMap<&'static str, String> {
+    // Each `{}` in a description is replaced by `deps_file_name`.
+    let abouts = [
+        ("install", "Install dependencies defined in '{}'"),
+        (
+            "resolve",
+            "Print the exact revision of each dependency defined in '{}', \
+             without installing them",
+        ),
+        (
+            "hook",
+            "Print a snippet that integrates '{}' with an environment \
+             manager",
+        ),
+        ("owners", "Print the owner of a dependency defined in '{}'"),
+        (
+            "upgrade",
+            "Change the version of a dependency in '{}' and install it",
+        ),
+        (
+            "fetch",
+            "Fetch a dependency defined in '{}' into a temporary directory \
+             and print its path",
+        ),
+        (
+            "pin",
+            "Change the versions of dependencies in '{}' to their installed \
+             revisions",
+        ),
+        (
+            "validate",
+            "Check '{}' for problems, without installing any dependencies",
+        ),
+        (
+            "fmt",
+            "Rewrite '{}' with sorted dependencies and aligned columns",
+        ),
+        (
+            "doctor",
+            "Check the environment that dependencies from '{}' are installed \
+             in for problems",
+        ),
+        (
+            "repair",
+            "Rebuild the state files of the dependencies from '{}' to match \
+             the installed dependencies",
+        ),
+        (
+            "exec",
+            "Run a command in the output directory of a dependency installed \
+             from '{}'",
+        ),
+        ("list", "Print the dependencies installed from '{}'"),
+        (
+            "sbom",
+            "Print a software bill of materials for the dependencies \
+             installed from '{}'",
+        ),
+        (
+            "graph",
+            "Print the graph of the dependencies installed from '{}'",
+        ),
+        (
+            "diff",
+            "Print the changes between the installed revision of each \
+             dependency and the version defined in '{}'",
+        ),
+        (
+            "pack",
+            "Package '{}', the installed dependencies and their provenance \
+             into a reproducible tar archive",
+        ),
+        (
+            "unpack",
+            "Restore installed dependencies from an archive created by \
+             `pack` for the same '{}'",
+        ),
+        (
+            "prune-cache",
+            "Remove the least recently used entries from the store of linked \
+             dependencies",
+        ),
+    ];
+
+    abouts.iter()
+        .map(|(name, about)| (*name, about.replace("{}", deps_file_name)))
+        .collect()
+}
+
+// `app` returns the definition of the command line of `dpnd`. `abouts`
+// contains the description of each subcommand, as returned by
+// `subcommand_abouts`.
+fn app<'a>(abouts: &'a HashMap<&str, String>) -> App<'a, 'a> {
+    let about = |name: &str| abouts[name].as_str();
+
+    App::new("dpnd")
+        .version(env!("CARGO_PKG_VERSION"))
+        .author(env!("CARGO_PKG_AUTHORS"))
+        .about(env!("CARGO_PKG_DESCRIPTION"))
+        .settings(&[
+            AppSettings::VersionlessSubcommands,
+        ])
+        .args(&global_args())
+        .subcommands(vec![
+            install_subcommand(about("install")),
+            resolve_subcommand(about("resolve")),
+            hook_subcommand(about("hook")),
+            owners_subcommand(about("owners")),
+            SubCommand::with_name("diff")
+                .about(about("diff")),
+            upgrade_subcommand(about("upgrade")),
+            fetch_subcommand(about("fetch")),
+            SubCommand::with_name("pin")
+                .about(about("pin")),
+            validate_subcommand(about("validate")),
+            fmt_subcommand(about("fmt")),
+            SubCommand::with_name("doctor")
+                .about(about("doctor")),
+            SubCommand::with_name("repair")
+                .about(about("repair")),
+            exec_subcommand(about("exec")),
+            list_subcommand(about("list")),
+            sbom_subcommand(about("sbom")),
+            graph_subcommand(about("graph")),
+            SubCommand::with_name("pack")
+                .visible_alias("export")
+                .about(about("pack"))
+                .args(&[
+                Arg::with_name(ARCHIVE_ARG)
+                    .required(true)
+                    .value_name("ARCHIVE")
+                    .help("The path of the archive to create"),
+                ]),
+            SubCommand::with_name("unpack")
+                .visible_alias("import")
+                .about(about("unpack"))
+                .args(&[
+                Arg::with_name(ARCHIVE_ARG)
+                    .required(true)
+                    .value_name("ARCHIVE")
+                    .help("The path of the archive to restore"),
+                ]),
+            prune_cache_subcommand(about("prune-cache")),
+        ])
+}
+
+// `global_args` returns the arguments that can be given to any subcommand.
+fn global_args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name(CWD_OPT)
+            .short("C")
+            .long("cwd")
+            .takes_value(true)
+            .value_name("DIR")
+            .multiple(true)
+            .number_of_values(1)
+            .global(true)
+            .help(
+                "Run as if `dpnd` was started in DIR; if it's given \
+                 more than once then each relative DIR is relative \
+                 to the previous one",
+            ),
+        Arg::with_name(LOG_FILE_OPT)
+            .long("log-file")
+            .takes_value(true)
+            .value_name("PATH")
+            .global(true)
+            .help("Append a log of the actions taken to PATH"),
+        Arg::with_name(LOG_LEVEL_OPT)
+            .long("log-level")
+            .takes_value(true)
+            .value_name("LEVEL")
+            .possible_values(LEVEL_NAMES)
+            .default_value("info")
+            .global(true)
+            .help("The least important messages to log"),
+        Arg::with_name(COLOR_OPT)
+            .long("color")
+            .takes_value(true)
+            .value_name("WHEN")
+            .possible_values(COLOR_CHOICE_NAMES)
+            .default_value("auto")
+            .global(true)
+            .help(
+                "Whether to color error messages; 'auto' colors them \
+                 if stderr is a terminal and `NO_COLOR` isn't set",
+            ),
+        Arg::with_name(NO_VALIDATE_SOURCES_FLAG)
+            .long("no-validate-sources")
+            .global(true)
+            .help(
+                "Don't check the form of dependency sources when \
+                 parsing dependency files",
+            ),
+        Arg::with_name(ACCEPT_NEW_HOST_KEYS_FLAG)
+            .long("accept-new-host-keys")
+            .global(true)
+            .help(
+                "Make SSH add the keys of unknown hosts to \
+                 `known_hosts` instead of prompting for them",
+            ),
+        Arg::with_name(VERSION_OUTPUT_OPT)
+            .long("version-output")
+            .takes_value(true)
+            .value_name("FORMAT")
+            .possible_values(VERSION_OUTPUT_FORMATS)
+            .help(
+                "Print the version of `dpnd`, the commit that it was \
+                 built from, and the tools and file formats that it \
+                 supports, in FORMAT",
+            ),
+    ]
+}
+
+fn install_subcommand(about: &str) -> App<'_, '_> {
+    SubCommand::with_name("install")
+        .about(about)
+        .args(&install_selection_args())
+        .args(&install_fetch_args())
+        .args(&install_output_args())
+}
+
+// `install_selection_args` returns the arguments of `install` that select the
+// dependencies that are installed, and where they're installed to.
+fn install_selection_args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name(INSTALL_RECURSIVE_FLAG)
+            .short("r")
+            .long("recursive")
+            .overrides_with(INSTALL_NO_RECURSIVE_FLAG)
+            .help(
+                "Install dependencies found in dependencies",
+            ),
+        Arg::with_name(INSTALL_NO_RECURSIVE_FLAG)
+            .long("no-recursive")
+            .overrides_with(INSTALL_RECURSIVE_FLAG)
+            .help(
+                "Don't install dependencies found in \
+                 dependencies, even if the config enables it",
+            ),
+        Arg::with_name(INSTALL_CHANGED_SINCE_OPT)
+            .long("changed-since")
+            .takes_value(true)
+            .value_name("REV")
+            .help(
+                "Only install dependencies whose \
+                 definitions changed since the Git revision \
+                 REV",
+            ),
+        Arg::with_name(INSTALL_WORKSPACE_FLAG)
+            .long("workspace")
+            .help(
+                "Install the dependencies of each project \
+                 listed in the nearest workspace file",
+            ),
+        Arg::with_name(INSTALL_OUTPUT_DIR_OPT)
+            .long("output-dir")
+            .takes_value(true)
+            .value_name("DIR")
+            .conflicts_with(INSTALL_WORKSPACE_FLAG)
+            .help(
+                "Install the dependencies that aren't in a \
+                 section to DIR, instead of to the output \
+                 directory defined in the dependency file",
+            ),
+        Arg::with_name(INSTALL_STDIN_FLAG)
+            .long("stdin")
+            .conflicts_with_all(&[
+                INSTALL_WORKSPACE_FLAG,
+                INSTALL_ANNOTATE_FLAG,
+                INSTALL_CHANGED_SINCE_OPT,
+                INSTALL_INTERACTIVE_FLAG,
+            ])
+            .help(
+                "Read the dependency file from standard \
+                 input, and install the dependencies in the \
+                 current directory",
+            ),
+        Arg::with_name(INSTALL_ONLY_OPT)
+            .long("only")
+            .takes_value(true)
+            .value_name("GROUP")
+            .multiple(true)
+            .number_of_values(1)
+            .conflicts_with(INSTALL_SKIP_OPT)
+            .help(
+                "Only install and remove dependencies in \
+                 GROUP",
+            ),
+        Arg::with_name(INSTALL_SKIP_OPT)
+            .long("skip")
+            .takes_value(true)
+            .value_name("GROUP")
+            .multiple(true)
+            .number_of_values(1)
+            .help(
+                "Don't install or remove dependencies in \
+                 GROUP",
+            ),
+        Arg::with_name(INSTALL_CONFLICTS_OPT)
+            .long("conflicts")
+            .takes_value(true)
+            .value_name("MODE")
+            .possible_values(CONFLICT_POLICY_NAMES)
+            .help(
+                "How to handle dependencies of nested \
+                 dependency files that have the same source \
+                 as other dependencies but different \
+                 versions; defaults to `warn`",
+            ),
+        Arg::with_name(INSTALL_DEPS_ARG)
+            .multiple(true)
+            .value_name("DEP")
+            .conflicts_with(INSTALL_WORKSPACE_FLAG)
+            .help(
+                "Only install the named dependencies; \
+                 defaults to all dependencies",
+            ),
+    ]
+}
+
+// `install_fetch_args` returns the arguments of `install` that control how
+// dependencies are fetched, and when installed dependencies are replaced.
+fn install_fetch_args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name(INSTALL_FETCH_TIMEOUT_OPT)
+            .long("fetch-timeout")
+            .takes_value(true)
+            .value_name("SECS")
+            .help(
+                "Stop fetching a dependency if it takes \
+                 longer than SECS seconds",
+            ),
+        Arg::with_name(INSTALL_JOBS_OPT)
+            .short("j")
+            .long("jobs")
+            .takes_value(true)
+            .value_name("N")
+            .help(
+                "Install the dependencies of at most N nested \
+                 projects at the same time with \
+                 `--recursive`; defaults to the number of \
+                 CPUs",
+            ),
+        Arg::with_name(INSTALL_KEEP_GOING_FLAG)
+            .long("keep-going")
+            .overrides_with(INSTALL_FAIL_FAST_FLAG)
+            .help(
+                "Install the other dependencies if a \
+                 dependency can't be fetched, and report all \
+                 of the failures at the end",
+            ),
+        Arg::with_name(INSTALL_FAIL_FAST_FLAG)
+            .long("fail-fast")
+            .overrides_with(INSTALL_KEEP_GOING_FLAG)
+            .help(
+                "Stop at the first dependency that can't be \
+                 fetched, even if the config enables \
+                 `--keep-going`",
+            ),
+        Arg::with_name(INSTALL_LINK_FLAG)
+            .long("link")
+            .conflicts_with(INSTALL_VENDOR_FLAG)
+            .help(
+                "Install dependencies as symlinks to a store \
+                 that is shared between projects",
+            ),
+        Arg::with_name(INSTALL_LINK_COPY_FLAG)
+            .long("link-copy")
+            .conflicts_with(INSTALL_VENDOR_FLAG)
+            .conflicts_with(INSTALL_LINK_FLAG)
+            .help(
+                "Install dependencies as copies of entries in \
+                 the store, using copy-on-write copies where \
+                 the filesystem supports them",
+            ),
+        Arg::with_name(INSTALL_CHECK_INTEGRITY_FLAG)
+            .long("check-integrity")
+            .help(
+                "Install dependencies again if their \
+                 revisions differ from those recorded when \
+                 they were installed",
+            ),
+        Arg::with_name(INSTALL_NO_STATE_FILE_FLAG)
+            .long("no-state-file")
+            .help(
+                "Inspect the output directories of \
+                 dependencies instead of reading and writing \
+                 state files",
+            ),
+        Arg::with_name(INSTALL_FORCE_FLAG)
+            .long("force")
+            .help(
+                "Remove or replace dependencies even if they \
+                 have been modified locally",
+            ),
+        Arg::with_name(INSTALL_INTERACTIVE_FLAG)
+            .long("interactive")
+            .help(
+                "Ask before removing or downgrading \
+                 dependencies, or discarding local \
+                 modifications; has no effect if stdin \
+                 isn't a terminal",
+            ),
+    ]
+}
+
+// `install_output_args` returns the arguments of `install` that control the
+// files that are written by installation, and the output of `dpnd`.
+fn install_output_args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name(INSTALL_VENDOR_FLAG)
+            .long("vendor")
+            .overrides_with(INSTALL_NO_VENDOR_FLAG)
+            .help(
+                "Remove tool metadata from installed \
+                 dependencies and record their provenance \
+                 instead",
+            ),
+        Arg::with_name(INSTALL_NO_VENDOR_FLAG)
+            .long("no-vendor")
+            .overrides_with(INSTALL_VENDOR_FLAG)
+            .help(
+                "Keep tool metadata in installed \
+                 dependencies, even if the config enables \
+                 `vendor`",
+            ),
+        Arg::with_name(INSTALL_DETERMINISTIC_MTIMES_FLAG)
+            .long("deterministic-mtimes")
+            .overrides_with(
+                INSTALL_NO_DETERMINISTIC_MTIMES_FLAG,
+            )
+            .help(
+                "Set the modification times of installed \
+                 files to `SOURCE_DATE_EPOCH`, or to the \
+                 time of the installed revision if \
+                 `SOURCE_DATE_EPOCH` isn't set",
+            ),
+        Arg::with_name(INSTALL_NO_DETERMINISTIC_MTIMES_FLAG)
+            .long("no-deterministic-mtimes")
+            .overrides_with(INSTALL_DETERMINISTIC_MTIMES_FLAG)
+            .help(
+                "Keep the modification times of installed \
+                 files, even if the config enables \
+                 `deterministic_mtimes`",
+            ),
+        Arg::with_name(INSTALL_READONLY_FLAG)
+            .long("readonly")
+            .help(
+                "Remove the write permissions of installed \
+                 dependencies",
+            ),
+        Arg::with_name(INSTALL_ANNOTATE_FLAG)
+            .long("annotate")
+            .help(
+                "Record the installed revision of each \
+                 dependency in a comment in the dependency \
+                 file",
+            ),
+        Arg::with_name(INSTALL_CHECK_ONLY_FLAG)
+            .long("check-only")
+            .help(
+                "Fail if any dependency isn't up to date, \
+                 instead of installing it",
+            ),
+        Arg::with_name(INSTALL_FROZEN_FLAG)
+            .long("frozen")
+            .help(
+                "Like `--check-only`, but also describe each \
+                 change that would be made, and don't write \
+                 any files, including the CI summary",
+            ),
+        Arg::with_name(INSTALL_CI_FLAG)
+            .long("ci")
+            .conflicts_with_all(&[
+                INSTALL_INTERACTIVE_FLAG,
+                INSTALL_ANNOTATE_FLAG,
+            ])
+            .help(
+                "Apply settings suited to CI: tools don't \
+                 prompt for input, `--keep-going` is enabled \
+                 unless `--fail-fast` is used, the fetch time \
+                 of each dependency is printed, and a summary \
+                 of dependencies is appended to \
+                 `GITHUB_STEP_SUMMARY` if it's set",
+            ),
+        Arg::with_name(INSTALL_QUIET_FLAG)
+            .long("quiet")
+            .short("q")
+            .help(
+                "Don't print the progress of clones, or a \
+                 summary of the changes to the dependencies \
+                 after installing them",
+            ),
+        Arg::with_name(INSTALL_REPORT_OPT)
+            .long("report")
+            .takes_value(true)
+            .value_name("PATH")
+            .help(
+                "Write the size and fetch time of each \
+                 fetched dependency to PATH as JSON",
+            ),
+    ]
+}
+
+fn resolve_subcommand(about: &str) -> App<'_, '_> {
+    SubCommand::with_name("resolve")
+        .about(about)
+        .args(&[
+            Arg::with_name(RESOLVE_FORMAT_OPT)
+                .long("format")
+                .takes_value(true)
+                .value_name("FORMAT")
+                .possible_values(&["text", "json"])
+                .default_value("text")
+                .help("The format to print dependencies in"),
+        ])
+}
+
+fn hook_subcommand(about: &str) -> App<'_, '_> {
+    SubCommand::with_name("hook")
+        .about(about)
+        .args(&[
+            Arg::with_name(HOOK_MANAGER_ARG)
+                .required(true)
+                .possible_values(&["direnv", "mise"])
+                .help("The environment manager to integrate with"),
+        ])
+}
+
+fn owners_subcommand(about: &str) -> App<'_, '_> {
+    SubCommand::with_name("owners")
+        .about(about)
+        .args(&[
+            Arg::with_name(OWNERS_DEP_ARG)
+                .required(true)
+                .value_name("NAME_OR_PATH")
+                .help(
+                    "The name of a dependency, or a path in the \
+                     output directory of a dependency",
+                ),
+        ])
+}
+
+fn upgrade_subcommand(about: &str) -> App<'_, '_> {
+    SubCommand::with_name("upgrade")
+        .about(about)
+        .args(&[
+            Arg::with_name(UPGRADE_DEP_ARG)
+                .required(true)
+                .value_name("NAME")
+                .help("The name of the dependency to upgrade"),
+            Arg::with_name(UPGRADE_VERSION_ARG)
+                .value_name("VERSION")
+                .help(
+                    "The version to upgrade to; defaults to the \
+                     latest version",
+                ),
+        ])
+}
+
+fn fetch_subcommand(about: &str) -> App<'_, '_> {
+    SubCommand::with_name("fetch")
+        .about(about)
+        .args(&[
+            Arg::with_name(FETCH_DEP_ARG)
+                .required(true)
+                .value_name("NAME")
+                .help("The name of the dependency to fetch"),
+        ])
+}
+
+fn validate_subcommand(about: &str) -> App<'_, '_> {
+    SubCommand::with_name("validate")
+        .about(about)
+        .args(&[
+            Arg::with_name(VALIDATE_RECURSIVE_FLAG)
+                .short("r")
+                .long("recursive")
+                .help(
+                    "Also check the dependency files of installed \
+                     dependencies",
+                ),
+        ])
+}
+
+fn fmt_subcommand(about: &str) -> App<'_, '_> {
+    SubCommand::with_name("fmt")
+        .about(about)
+        .args(&[
+            Arg::with_name(FMT_CHECK_FLAG)
+                .long("check")
+                .help(
+                    "Fail if the dependency file isn't \
+                     formatted, instead of formatting it",
+                ),
+        ])
+}
+
+fn exec_subcommand(about: &str) -> App<'_, '_> {
+    SubCommand::with_name("exec")
+        .about(about)
+        .args(&[
+            Arg::with_name(EXEC_DEP_ARG)
+                .required(true)
+                .value_name("NAME")
+                .help("The name of an installed dependency"),
+            Arg::with_name(EXEC_CMD_ARG)
+                .required(true)
+                .multiple(true)
+                .last(true)
+                .value_name("CMD")
+                .help("The command to run, and its arguments"),
+        ])
+}
+
+fn list_subcommand(about: &str) -> App<'_, '_> {
+    SubCommand::with_name("list")
+        .about(about)
+        .args(&[
+            Arg::with_name(LIST_RECURSIVE_FLAG)
+                .short("r")
+                .long("recursive")
+                .help(
+                    "Also list the dependencies installed for \
+                     installed dependencies",
+                ),
+            Arg::with_name(LIST_VERBOSE_FLAG)
+                .short("v")
+                .long("verbose")
+                .help(
+                    "Also print the installed revision of each \
+                     dependency, when it was installed, and the \
+                     versions of `dpnd` and the tool that \
+                     installed it",
+                ),
+        ])
+}
+
+fn sbom_subcommand(about: &str) -> App<'_, '_> {
+    SubCommand::with_name("sbom")
+        .about(about)
+        .args(&[
+            Arg::with_name(SBOM_FORMAT_OPT)
+                .long("format")
+                .takes_value(true)
+                .value_name("FORMAT")
+                .possible_values(&["cyclonedx"])
+                .default_value("cyclonedx")
+                .help("The format to print the SBOM in"),
+            Arg::with_name(SBOM_RECURSIVE_FLAG)
+                .short("r")
+                .long("recursive")
+                .help(
+                    "Also list the dependencies installed for \
+                     installed dependencies",
+                ),
+        ])
+}
+
+fn graph_subcommand(about: &str) -> App<'_, '_> {
+    SubCommand::with_name("graph")
+        .about(about)
+        .args(&[
+            Arg::with_name(GRAPH_FORMAT_OPT)
+                .long("format")
+                .takes_value(true)
+                .value_name("FORMAT")
+                .possible_values(&["dot", "json"])
+                .default_value("dot")
+                .help("The format to print the graph in"),
+            Arg::with_name(GRAPH_RECURSIVE_FLAG)
+                .short("r")
+                .long("recursive")
+                .help(
+                    "Also include the dependencies installed for \
+                     installed dependencies",
+                ),
+        ])
+}
+
+fn prune_cache_subcommand(about: &str) -> App<'_, '_> {
+    SubCommand::with_name("prune-cache")
+        .about(about)
+        .args(&[
+            Arg::with_name(PRUNE_CACHE_MAX_SIZE_OPT)
+                .long("max-size")
+                .takes_value(true)
+                .value_name("SIZE")
+                .help(
+                    "Remove the least recently used entries until \
+                     the store is at most SIZE bytes; SIZE may \
+                     end with 'K', 'M', 'G' or 'T'",
+                ),
+            Arg::with_name(PRUNE_CACHE_MAX_AGE_OPT)
+                .long("max-age")
+                .takes_value(true)
+                .value_name("DAYS")
+                .help(
+                    "Remove entries that haven't been used in \
+                     more than DAYS days",
+                ),
+            Arg::with_name(PRUNE_CACHE_DRY_RUN_FLAG)
+                .long("dry-run")
+                .help(
+                    "Print the entries that would be removed, \
+                     without removing them",
+                ),
+        ])
+}
+
+// `flag_setting` returns `Some(true)` if `on_flag` is present in `args`,
+// `Some(false)` if `off_flag` is present, and `None` if neither is present.
+fn flag_setting(args: &ArgMatches, on_flag: &str, off_flag: &str)
+    -> Option<bool>
+{
+    if args.is_present(on_flag) {
+        Some(true)
+    } else if args.is_present(off_flag) {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+// `load_config` loads the user and project config files for the project that
+// contains `cwd`. It returns an exit code and a rendered error message if the
+// config files can't be loaded.
+fn load_config(installer: &Installer<DepToolError>, cwd: &Path)
+    -> Result<Config, (i32, String)>
+{
+    let proj_dir =
+        match installer.proj_dir(cwd) {
+            Ok(dir) => {
+                dir
+            },
+            Err(err) => {
+                let code = exit_codes::install_error_code(&err);
+                let msg = render_errors::render_install_error(
+                    err,
+                    cwd,
+                    &installer.deps_file_name,
+                );
+
+                return Err((code, msg));
+            },
+        };
+
+    load_proj_config(&proj_dir, cwd)
+}
+
+// `load_proj_config` loads the user config file and the project config file
+// of the project in `proj_dir`. Paths in error messages are rendered relative
+// to `cwd`.
+fn load_proj_config(proj_dir: &Path, cwd: &Path)
+    -> Result<Config, (i32, String)>
+{
+    let user_config_path = user_config_path();
+
+    Config::load(user_config_path.as_deref(), proj_dir)
+        .map_err(|err| {
+            let msg = render_errors::render_config_error(err, cwd);
+
+            (exit_codes::FAILURE, msg)
+        })
+}
+
+// `run_fetch_hook` runs the hook in `config` that's defined for `event`, if
+// any, with details of the fetch in its environment. Hooks are used for
+// reporting, so failures are reported as warnings instead of stopping
+// installation. The output of hooks is written to `stderr`, so that it's kept
+// apart from the output of `dpnd`.
+fn run_fetch_hook(config: &Config, event: &FetchEvent, diag: &Diagnostics) {
+    let (hook_name, maybe_script) =
+        match event.outcome {
+            None => {
+                ("pre_fetch", &config.pre_fetch_hook)
+            },
+            Some(_) => {
+                ("post_fetch", &config.post_fetch_hook)
+            },
+        };
+    let Some(script) = maybe_script else {
+        return;
+    };
+
+    let mut cmd = process::Command::new("sh");
+    cmd.arg("-c")
+        .arg(script)
+        .env("DPND_DEP_NAME", event.dep_name)
+        .env("DPND_DEP_SOURCE", event.source)
+        .env("DPND_DEP_VERSION", event.version)
+        .env("DPND_DEP_DIR", event.dir)
+        .stdin(process::Stdio::null());
+    if let Some(outcome) = &event.outcome {
+        let result = if outcome.succeeded { "success" } else { "failure" };
+        cmd.env("DPND_FETCH_RESULT", result)
+            .env(
+                "DPND_FETCH_DURATION_MS",
+                outcome.duration.as_millis().to_string(),
+            );
+    }
+
+    match status_with_buffered_output(event.dep_name, cmd) {
+        Ok(status) if status.success() => {
+        },
+        Ok(status) => {
+            diag.warning(&format!(
+                "The `{}` hook failed for the '{}' dependency ({})",
+                hook_name,
+                event.dep_name,
+                status,
+            ));
+        },
+        Err(err) => {
+            diag.warning(&format!(
+                "Couldn't run the `{}` hook for the '{}' dependency: {}",
+                hook_name,
+                event.dep_name,
+                err,
+            ));
+        },
+    }
+}
+
+// `status_with_buffered_output` runs `cmd` and returns its exit status. The
+// output that `cmd` writes to `stdout` and `stderr` is collected, in the order
+// that it's written, and is written to the `stderr` of `dpnd` as a single
+// block under a `<dep_name>:` header when `cmd` exits, so that the output of
+// hooks that are run for nested projects that are installed in parallel isn't
+// interleaved.
+fn status_with_buffered_output(dep_name: &str, mut cmd: process::Command)
+    -> Result<ExitStatus, IoError>
+{
+    let (mut reader, writer) = io::pipe()?;
+    cmd.stdout(writer.try_clone()?).stderr(writer);
+    let mut child = cmd.spawn()?;
+    // `cmd` holds the write ends of the pipe, so it's dropped before the pipe
+    // is read, so that reading stops when the hook exits.
+    drop(cmd);
+
+    let mut output = vec![];
+    let read_result = reader.read_to_end(&mut output);
+    dep_tools::write_output_block(dep_name, &output);
+    let status = child.wait()?;
+    read_result?;
+
+    Ok(status)
+}
+
+const XDG_CONFIG_HOME_VAR: &str = "XDG_CONFIG_HOME";
+
+// `user_config_path` returns the path of the user config file, which is
+// `dpnd/config.toml` in `XDG_CONFIG_HOME`, or in `$HOME/.config` if
+// `XDG_CONFIG_HOME` isn't set. It returns `None` if neither variable is set.
+fn user_config_path() -> Option<PathBuf> {
+    let config_dir =
+        match env::var_os(XDG_CONFIG_HOME_VAR) {
+            Some(dir) if !dir.is_empty() => {
+                PathBuf::from(dir)
+            },
+            _ => {
+                let home = env::var_os("HOME").filter(|h| !h.is_empty())?;
+
+                PathBuf::from(home).join(".config")
+            },
+        };
+
+    Some(config_dir.join("dpnd").join("config.toml"))
+}
+
+// `render_dep_diff` renders `dep_diff` as a summary line, followed by an
+// indented line for each change.
+fn render_dep_diff(dep_diff: &DepDiff) -> String {
+    let mut rendered =
+        match &dep_diff.installed_revision {
+            None => {
+                format!(
+                    "{}: not installed (defined: {})\n",
+                    dep_diff.name,
+                    dep_diff.revision,
+                )
+            },
+            Some(revision) if *revision == dep_diff.revision => {
+                format!("{}: up to date ({})\n", dep_diff.name, revision)
+            },
+            Some(revision) => {
+                format!(
+                    "{}: {} -> {}\n",
+                    dep_diff.name,
+                    revision,
+                    dep_diff.revision,
+                )
+            },
+        };
+
+    if let Some(changes) = &dep_diff.changes {
+        for change in changes {
+            let _ = writeln!(rendered, "    {}", change);
+        }
+    }
+
+    rendered
+}
+
+// `render_installed_dep` renders `dep` as its path, tool, source and version.
+// If `verbose` is `true` then the installed revision of `dep`, the time at
+// which it was installed, and the versions of `dpnd` and of the program used
+// by its tool are appended as `key=value` fields, which are omitted if they
+// aren't known.
+fn render_installed_dep(dep: &InstalledDep, verbose: bool) -> String {
+    let mut rendered = format!(
+        "{} {} {} {}",
+        dep.path.join("/"),
+        dep.tool,
+        dep.source,
+        dep.version,
+    );
+
+    if verbose {
+        if let Some(revision) = &dep.revision {
+            let _ = write!(rendered, " revision={}", revision);
+        }
+        if let Some(t) = dep.installed_at {
+            let _ = write!(rendered, " installed_at={}", utc_time(t));
+        }
+        if let Some(vsn) = &dep.dpnd_version {
+            let _ = write!(rendered, " dpnd_version={}", vsn);
+        }
+        if let Some(vsn) = &dep.tool_version {
+            let _ = write!(rendered, " tool_version={}", vsn);
+        }
+    }
+
+    rendered
+}
+
+// `render_state_repair` renders `repair` as a sentence that describes the
+// change that was made to a state file.
+fn render_state_repair(repair: &StateRepair, cwd: &Path) -> String {
+    match repair {
+        StateRepair::Removed{dep_name, state_file_path} => {
+            let path =
+                state_file_path.strip_prefix(cwd).unwrap_or(state_file_path);
+
+            format!(
+                "Removed '{}' from '{}', because it isn't installed",
+                dep_name,
+                path.display(),
+            )
+        },
+        StateRepair::Added{dep_name, state_file_path, revision} => {
+            let path =
+                state_file_path.strip_prefix(cwd).unwrap_or(state_file_path);
+
+            format!(
+                "Added '{}' to '{}', because revision '{}' is installed",
+                dep_name,
+                path.display(),
+                revision,
+            )
+        },
+    }
+}
+
+fn render_resolved_deps_json(deps: &[ResolvedDep]) -> String {
+    let json_deps: Vec<JsonValue> =
+        deps.iter()
+            .map(|dep| {
+                serde_json::json!({
+                    "name": dep.name,
+                    "tool": dep.tool,
+                    "source": dep.source,
+                    "version": dep.version.to_string(),
+                    "revision": dep.revision,
+                    "owner": dep.owner,
+                })
+            })
+            .collect();
+
+    // `to_string_pretty` can only fail if a map in the value has non-string
+    // keys, which isn't the case here.
+    serde_json::to_string_pretty(&json_deps)
+        .expect("couldn't render dependencies as JSON")
+}
+
+// `render_provenance_json` renders the provenance of `deps`, which are
+// stored in archives created by `pack`, so that the origin of each packed
+// dependency can be audited without network access.
+fn render_provenance_json(deps: &[InstalledDep]) -> String {
+    let json_deps: Vec<JsonValue> =
+        deps.iter()
+            .map(|dep| {
+                serde_json::json!({
+                    "path": dep.path.join("/"),
+                    "tool": dep.tool,
+                    "source": dep.source,
+                    "version": dep.version.to_string(),
+                    "revision": dep.revision,
+                })
+            })
+            .collect();
+
+    let provenance = serde_json::json!({
+        "dpnd_version": env!("CARGO_PKG_VERSION"),
+        "dependencies": json_deps,
+    });
+
+    // `to_string_pretty` can only fail if a map in the value has non-string
+    // keys, which isn't the case here.
+    let mut rendered = serde_json::to_string_pretty(&provenance)
+        .expect("couldn't render provenance as JSON");
+    rendered.push('\n');
+
+    rendered
+}
+
+// `render_cyclonedx_sbom` renders `deps`, which were installed for the
+// `proj_name` project, as a CycloneDX 1.5 JSON document. See
+// <https://cyclonedx.org/docs/1.5/json/> for more details. The document
+// doesn't contain a timestamp or serial number, so that rendering the same
+// dependencies always produces the same document.
+fn render_cyclonedx_sbom(proj_name: &str, deps: &[InstalledDep]) -> String {
+    let components: Vec<JsonValue> =
+        deps.iter()
+            .map(|dep| {
+                let name = dep.path.last()
+                    .expect("installed dependency has an empty path");
+                let version =
+                    dep.revision.clone()
+                        .unwrap_or_else(|| dep.version.to_string());
+                let ref_type =
+                    match dep.tool.as_str() {
+                        "git" | "git-file" | "svn" => {
+                            "vcs"
+                        },
+                        _ => {
+                            "distribution"
+                        },
+                    };
+
+                serde_json::json!({
+                    "type": "library",
+                    "bom-ref": dep.path.join("/"),
+                    "name": name,
+                    "version": version,
+                    "externalReferences": [
+                        {"type": ref_type, "url": dep.source},
+                    ],
+                    "properties": [
+                        {"name": "dpnd:tool", "value": dep.tool},
+                        {
+                            "name": "dpnd:version",
+                            "value": dep.version.to_string(),
+                        },
+                    ],
+                })
+            })
+            .collect();
+
+    let sbom = serde_json::json!({
+        "bomFormat": "CycloneDX",
+        "specVersion": "1.5",
+        "version": 1,
+        "metadata": {
+            "tools": [
+                {"name": "dpnd", "version": env!("CARGO_PKG_VERSION")},
+            ],
+            "component": {
+                "type": "application",
+                "bom-ref": proj_name,
+                "name": proj_name,
+            },
+        },
+        "components": components,
+    });
+
+    // `to_string_pretty` can only fail if a map in the value has non-string
+    // keys, which isn't the case here.
+    serde_json::to_string_pretty(&sbom)
+        .expect("couldn't render SBOM as JSON")
+}
+
+// `GRAPH_ROOT_ID` is the ID of the node that represents the project in the
+// graphs rendered by `graph`. The ID of a dependency is its path, joined by
+// `/`, which is never empty, so it can't clash with the root ID.
+const GRAPH_ROOT_ID: &str = "";
+
+// `graph_parent_id` returns the ID of the node that `dep` is a dependency of.
+fn graph_parent_id(dep: &InstalledDep) -> String {
+    if dep.path.len() > 1 {
+        dep.path[..dep.path.len() - 1].join("/")
+    } else {
+        GRAPH_ROOT_ID.to_string()
+    }
+}
+
+// `render_graph_dot` renders `deps`, which were installed for the `proj_name`
+// project, as a Graphviz DOT graph, with an edge from each project to each of
+// its dependencies.
+fn render_graph_dot(proj_name: &str, deps: &[InstalledDep]) -> String {
+    let mut lines = vec![
+        format!("digraph {} {{", dot_quote(proj_name)),
+        format!(
+            "    {} [label={}];",
+            dot_quote(GRAPH_ROOT_ID),
+            dot_quote(proj_name),
+        ),
+    ];
+
+    for dep in deps {
+        let id = dep.path.join("/");
+        let name = dep.path.last()
+            .expect("installed dependency has an empty path");
+        let spec = format!("{} {} {}", dep.tool, dep.source, dep.version);
+        lines.push(format!(
+            "    {} [label=\"{}\\n{}\"];",
+            dot_quote(&id),
+            dot_escape(name),
+            dot_escape(&spec),
+        ));
+    }
+
+    for dep in deps {
+        lines.push(format!(
+            "    {} -> {};",
+            dot_quote(&graph_parent_id(dep)),
+            dot_quote(&dep.path.join("/")),
+        ));
+    }
+    lines.push("}".to_string());
+
+    lines.join("\n")
+}
+
+// `dot_quote` renders `s` as a quoted DOT ID.
+fn dot_quote(s: &str) -> String {
+    format!("\"{}\"", dot_escape(s))
+}
+
+// `dot_escape` escapes `s` so that it can be used inside a quoted DOT ID.
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// `render_graph_json` renders `deps`, which were installed for the
+// `proj_name` project, as a JSON object that lists the nodes and edges of the
+// dependency graph.
+fn render_graph_json(proj_name: &str, deps: &[InstalledDep]) -> String {
+    let mut nodes = vec![
+        serde_json::json!({
+            "id": GRAPH_ROOT_ID,
+            "name": proj_name,
+        }),
+    ];
+    let mut edges = vec![];
+
+    for dep in deps {
+        let id = dep.path.join("/");
+        let name = dep.path.last()
+            .expect("installed dependency has an empty path");
+        nodes.push(serde_json::json!({
+            "id": id,
+            "name": name,
+            "tool": dep.tool,
+            "source": dep.source,
+            "version": dep.version.to_string(),
+            "revision": dep.revision,
+        }));
+        edges.push(serde_json::json!({
+            "from": graph_parent_id(dep),
+            "to": id,
+        }));
+    }
+
+    let graph = serde_json::json!({
+        "nodes": nodes,
+        "edges": edges,
+    });
+
+    // `to_string_pretty` can only fail if a map in the value has non-string
+    // keys, which isn't the case here.
+    serde_json::to_string_pretty(&graph)
+        .expect("couldn't render graph as JSON")
+}
+
+// `VERSION_OUTPUT_FORMATS` contains the values accepted by
+// `--version-output`.
+const VERSION_OUTPUT_FORMATS: &[&str] = &["text", "json"];
+
+// `GIT_COMMIT` is the Git commit that `dpnd` was built from, which is set by
+// the build script if `dpnd` is built from a Git repository.
+const GIT_COMMIT: Option<&str> = option_env!("DPND_GIT_COMMIT");
+
+// `render_version_output` renders the version of `dpnd`, the commit that it
+// was built from, `tool_names` and the supported file formats in `format`,
+// which is one of `VERSION_OUTPUT_FORMATS`.
+fn render_version_output(format: &str, tool_names: &[&str]) -> String {
+    if format == "json" {
+        let output = serde_json::json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "commit": GIT_COMMIT,
+            "tools": tool_names,
+            "deps_file_formats": [DEPS_FILE_FORMAT_VERSION],
+            "state_file_formats": [STATE_FILE_FORMAT_VERSION],
+        });
+
+        // `to_string_pretty` can only fail if a map in the value has
+        // non-string keys, which isn't the case here.
+        return serde_json::to_string_pretty(&output)
+            .expect("couldn't render version output as JSON");
+    }
+
+    format!(
+        "dpnd {}\n\
+         commit: {}\n\
+         tools: {}\n\
+         dependency file formats: {}\n\
+         state file formats: {}",
+        env!("CARGO_PKG_VERSION"),
+        GIT_COMMIT.unwrap_or("unknown"),
+        tool_names.join(", "),
+        DEPS_FILE_FORMAT_VERSION,
+        STATE_FILE_FORMAT_VERSION,
+    )
+}
+
+// `render_install_outcome` renders a one-line summary of `outcome`, such as
+// `3 installed, 1 updated, 2 removed, 4 unchanged in 12.3s`.
+fn render_install_outcome(outcome: &InstallOutcome, elapsed: Duration)
+    -> String
+{
+    format!(
+        "{} installed, {} updated, {} removed, {} unchanged in {:.1}s",
+        outcome.installed,
+        outcome.updated,
+        outcome.removed,
+        outcome.unchanged,
+        elapsed.as_secs_f64(),
+    )
+}
+
+// `render_fetch_times` renders the fetch time of each dependency in `stats`,
+// one per line and slowest first, so that slow dependencies stand out in CI
+// logs.
+fn render_fetch_times(stats: &[FetchStats]) -> String {
+    let mut stats: Vec<&FetchStats> = stats.iter().collect();
+    stats.sort_by_key(|stat| Reverse(stat.duration));
+
+    let mut rendered = String::new();
+    for stat in stats {
+        let _ = writeln!(
+            rendered,
+            "  {} fetched in {:.1}s",
+            stat.dep_name,
+            stat.duration.as_secs_f64(),
+        );
+    }
+
+    rendered
+}
+
+// `render_fetch_stats_json` renders `stats` as a JSON object that lists the
+// size and fetch time of each dependency, along with their totals. Paths are
+// rendered relative to `cwd` where possible.
+fn render_fetch_stats_json(stats: &[FetchStats], cwd: &Path) -> String {
+    let json_deps: Vec<JsonValue> =
+        stats.iter()
+            .map(|stat| {
+                let path = stat.dir.strip_prefix(cwd).unwrap_or(&stat.dir);
+
+                serde_json::json!({
+                    "name": stat.dep_name,
+                    "path": path.display().to_string(),
+                    "bytes": stat.bytes,
+                    "seconds": stat.duration.as_secs_f64(),
+                })
+            })
+            .collect();
+
+    let total_bytes: u64 = stats.iter().filter_map(|stat| stat.bytes).sum();
+    let total_time: Duration = stats.iter().map(|stat| stat.duration).sum();
+    let report = serde_json::json!({
+        "deps": json_deps,
+        "total_bytes": total_bytes,
+        "total_seconds": total_time.as_secs_f64(),
+    });
+
+    // `to_string_pretty` can only fail if a map in the value has non-string
+    // keys, which isn't the case here.
+    serde_json::to_string_pretty(&report)
+        .expect("couldn't render fetch statistics as JSON")
+}
+
+const GIT_TOKEN_VAR: &str = "DPND_GIT_TOKEN";
+const GIT_TOKEN_FILE_VAR: &str = "DPND_GIT_TOKEN_FILE";
+
+// `read_git_token` returns the value of `GIT_TOKEN_VAR` if it's defined,
+// otherwise it returns the first line of the file at `GIT_TOKEN_FILE_VAR` if
+// it's defined. Empty tokens are treated as undefined.
+fn read_git_token() -> Result<Option<String>, IoError> {
+    if let Ok(token) = env::var(GIT_TOKEN_VAR) {
+        if !token.is_empty() {
+            return Ok(Some(token));
+        }
+    }
+
+    let Some(path) = env::var_os(GIT_TOKEN_FILE_VAR) else {
+        return Ok(None);
+    };
+
+    let conts = fs::read_to_string(path)?;
+    let token = conts.lines().next().unwrap_or("").trim();
+
+    if token.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(token.to_string()))
+}
+
+const TRUSTED_KEYS_VAR: &str = "DPND_TRUSTED_KEYS";
+
+// `read_trusted_keys` returns the GPG key fingerprints listed in
+// `TRUSTED_KEYS_VAR`, which can be separated by commas or whitespace.
+fn read_trusted_keys() -> Vec<String> {
+    env::var(TRUSTED_KEYS_VAR)
+        .unwrap_or_default()
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|key| !key.is_empty())
+        .map(ToString::to_string)
+        .collect()
+}
+
+// `load_mock_tool` returns the mock dependency tool defined by
+// `DPND_MOCK_SOURCES`, if it's defined, and exits if it can't be loaded.
+#[cfg(feature = "test-util")]
+fn load_mock_tool() -> Option<MockDepTool> {
+    match MockDepTool::from_env() {
+        Ok(mock_tool) => {
+            mock_tool
+        },
+        Err(err) => {
+            let msg = render_errors::render_load_mock_sources_error(err);
+            eprintln!("{}", msg);
+            process::exit(exit_codes::FAILURE);
+        },
+    }
+}
+
+const SOURCE_DATE_EPOCH_VAR: &str = "SOURCE_DATE_EPOCH";
+
+// `source_date_epoch_setting` returns the value of `SOURCE_DATE_EPOCH_VAR`,
+// along with an exit code and a rendered error if it's invalid. An invalid
+// value is only an error if `normalize_mtimes` is `true`, because the
+// modification times of installed files are then set to it; otherwise it's
+// ignored, and only the installation times recorded in state files fall back
+// to the current time.
+fn source_date_epoch_setting(normalize_mtimes: bool)
+    -> Result<Option<u64>, (i32, String)>
+{
+    match read_source_date_epoch() {
+        Ok(t) => {
+            Ok(t)
+        },
+        Err(_) if !normalize_mtimes => {
+            Ok(None)
+        },
+        Err(err) => {
+            Err((
+                exit_codes::FAILURE,
+                format!(
+                    "`{}` isn't a valid number of seconds: {}",
+                    SOURCE_DATE_EPOCH_VAR,
+                    err,
+                ),
+            ))
+        },
+    }
+}
+
+// `read_source_date_epoch` returns the value of `SOURCE_DATE_EPOCH_VAR` if
+// it's defined and non-empty. See <https://reproducible-builds.org/specs/
+// source-date-epoch/> for more details.
+fn read_source_date_epoch() -> Result<Option<u64>, ParseIntError> {
+    match env::var(SOURCE_DATE_EPOCH_VAR) {
+        Ok(t) if !t.is_empty() => {
+            Ok(Some(t.parse()?))
+        },
+        _ => {
+            Ok(None)
+        },
+    }
+}
+
+const GITHUB_STEP_SUMMARY_VAR: &str = "GITHUB_STEP_SUMMARY";
+
+// `apply_proxy_config` sets the proxy environment variables that tools read,
+// from the environment or from `config`. Both the lowercase and uppercase
+// forms of each variable are set, because tools differ in which form they
+// read; `curl`, and so Git, ignores `HTTP_PROXY`, for example. Variables that
+// are already set take precedence over `config`, so that proxies can be
+// changed for a single run.
+fn apply_proxy_config(config: &Config) {
+    let settings = [
+        ("http_proxy", "HTTP_PROXY", &config.http_proxy),
+        ("https_proxy", "HTTPS_PROXY", &config.https_proxy),
+        ("no_proxy", "NO_PROXY", &config.no_proxy),
+    ];
+
+    for (lower_var, upper_var, setting) in settings {
+        let non_empty_var = |name| {
+            env::var_os(name).filter(|value| !value.is_empty())
+        };
+        let maybe_value =
+            non_empty_var(lower_var)
+                .or_else(|| non_empty_var(upper_var))
+                .or_else(|| setting.as_ref().map(OsString::from));
+
+        if let Some(value) = maybe_value {
+            env::set_var(lower_var, &value);
+            env::set_var(upper_var, &value);
+        }
+    }
+}
+
+const GIT_SSH_COMMAND_VAR: &str = "GIT_SSH_COMMAND";
+
+// `apply_accept_new_host_keys` makes SSH, when it's run by Git, add the keys
+// of hosts that aren't known yet to `known_hosts` instead of prompting for
+// them, so that first-time clones over SSH work in environments that can't
+// answer prompts. The option is added to the end of the command that Git
+// would otherwise use, so a `StrictHostKeyChecking` option that's already in
+// the command takes precedence, because SSH uses the first value of each
+// option. Git doesn't pass options to `GIT_SSH`, which might not be OpenSSH,
+// so nothing is changed if Git would use `GIT_SSH`.
+fn apply_accept_new_host_keys(diag: &Diagnostics) {
+    let non_empty_var = |name| env::var(name).ok().filter(|v| !v.is_empty());
+
+    // Git uses `GIT_SSH_COMMAND`, then `core.sshCommand`, then `GIT_SSH`.
+    let ssh_cmd =
+        match non_empty_var(GIT_SSH_COMMAND_VAR).or_else(git_ssh_cmd_config) {
+            Some(ssh_cmd) => {
+                ssh_cmd
+            },
+            None if non_empty_var("GIT_SSH").is_some() => {
+                diag.warning(
+                    "The keys of unknown hosts can't be accepted \
+                     automatically, because `GIT_SSH` is defined; use \
+                     `GIT_SSH_COMMAND` instead",
+                );
+                return;
+            },
+            None => {
+                "ssh".to_string()
+            },
+        };
+
+    env::set_var(
+        GIT_SSH_COMMAND_VAR,
+        format!("{} -o StrictHostKeyChecking=accept-new", ssh_cmd),
+    );
+}
+
+// `git_ssh_cmd_config` returns the value of the `core.sshCommand` Git setting,
+// if it's defined.
+fn git_ssh_cmd_config() -> Option<String> {
+    let output = process::Command::new("git")
+        .args(["config", "--get", "core.sshCommand"])
+        .output()
+        .ok()?;
+    let ssh_cmd = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+    if output.status.success() && !ssh_cmd.is_empty() {
+        Some(ssh_cmd)
+    } else {
+        None
+    }
+}
+
+// `default_jobs` returns the number of nested projects that are installed at
+// the same time if `--jobs` isn't used, which is the number of CPUs.
+fn default_jobs() -> usize {
+    thread::available_parallelism()
+        .map_or(1, NonZeroUsize::get)
+}
+
+// `apply_ci_preset` adjusts `opts`, and the environment that tools are run in,
+// for running in CI. The settings that can also be set in config files are
+// applied by `Config::ci_preset` instead.
+fn apply_ci_preset(opts: &mut InstallOptions) {
+    // Nobody can respond to a prompt in CI, so we make Git fail instead of
+    // waiting for credentials. `svn` is always run non-interactively.
+    env::set_var("GIT_TERMINAL_PROMPT", "0");
+
+    match env::var_os(GITHUB_STEP_SUMMARY_VAR) {
+        Some(path) if !path.is_empty() => {
+            opts.state.summary_path = Some(PathBuf::from(path));
+        },
+        _ => {
+        },
+    }
+}
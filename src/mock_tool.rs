@@ -0,0 +1,215 @@
+// Copyright 2021 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by an MIT
+// licence that can be found in the LICENCE file.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::Error as IoError;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Instant;
+
+use dep_tools::DepTool;
+use dep_tools::DepToolError;
+use dep_tools::FetchError;
+use dep_tools::Version;
+
+extern crate serde_json;
+extern crate snafu;
+
+use serde_json::Error as JsonError;
+use serde_json::Value as JsonValue;
+use snafu::ResultExt;
+use snafu::Snafu;
+
+pub const MOCK_SOURCES_VAR: &str = "DPND_MOCK_SOURCES";
+
+const REVISION_FILE_NAME: &str = ".mock_revision";
+
+// `MockDepTool` fetches dependencies from revisions that are held in memory,
+// so that installation can be tested without starting servers or accessing
+// the network. The source and version of a dependency select a revision,
+// which is a set of files that are written to the output directory of the
+// dependency.
+#[derive(Debug, Default)]
+pub struct MockDepTool {
+    revisions: HashMap<(String, String), HashMap<String, String>>,
+}
+
+impl MockDepTool {
+    // `add_revision` makes `files`, which maps file paths to file contents,
+    // available as `version` of `source`.
+    pub fn add_revision(
+        &mut self,
+        source: &str,
+        version: &str,
+        files: HashMap<String, String>,
+    ) {
+        let key = (source.to_string(), version.to_string());
+        self.revisions.insert(key, files);
+    }
+
+    // `from_env` returns the `MockDepTool` described by the JSON file named by
+    // `DPND_MOCK_SOURCES`, or `None` if `DPND_MOCK_SOURCES` isn't defined. The
+    // file contains an object that maps sources to objects, which map
+    // versions to objects, which map file paths to file contents.
+    pub fn from_env() -> Result<Option<MockDepTool>, LoadMockSourcesError> {
+        let path =
+            match env::var_os(MOCK_SOURCES_VAR) {
+                Some(path) => {
+                    PathBuf::from(path)
+                },
+                None => {
+                    return Ok(None);
+                },
+            };
+
+        let conts = fs::read_to_string(&path)
+            .context(ReadFailed{path: path.clone()})?;
+        let value: JsonValue = serde_json::from_str(&conts)
+            .context(ParseFailed{path: path.clone()})?;
+
+        let invalid = || {
+            LoadMockSourcesError::InvalidFormat{path: path.clone()}
+        };
+        let mut tool = MockDepTool::default();
+        for (source, versions) in value.as_object().ok_or_else(invalid)? {
+            let versions = versions.as_object().ok_or_else(invalid)?;
+            for (version, files) in versions {
+                let files = files.as_object().ok_or_else(invalid)?;
+                let mut revision = HashMap::new();
+                for (file_path, conts) in files {
+                    let conts = conts.as_str().ok_or_else(invalid)?;
+                    revision.insert(file_path.clone(), conts.to_string());
+                }
+                tool.add_revision(source, version, revision);
+            }
+        }
+
+        Ok(Some(tool))
+    }
+
+    fn revision_files(&self, src: &str, vsn: &str)
+        -> Result<&HashMap<String, String>, DepToolError>
+    {
+        let key = (src.to_string(), vsn.to_string());
+
+        self.revisions.get(&key)
+            .ok_or(DepToolError::UnknownMockRevision{
+                src: src.to_string(),
+                version: vsn.to_string(),
+            })
+    }
+}
+
+impl DepTool<DepToolError> for MockDepTool {
+    fn name(&self) -> String {
+        "mock".to_string()
+    }
+
+    fn fetch(
+        &self,
+        src: String,
+        Version(vsn): Version,
+        _path: Option<&str>,
+        out_dir: &Path,
+        _deadline: Option<Instant>,
+    )
+        -> Result<(), FetchError<DepToolError>>
+    {
+        let files =
+            match self.revision_files(&src, &vsn) {
+                Ok(files) => {
+                    files
+                },
+                Err(source) => {
+                    return Err(FetchError::RetrieveFailed{source});
+                },
+            };
+
+        for (file_path, file_conts) in files {
+            let path = out_dir.join(file_path);
+            if let Err(source) = write_file(&path, file_conts) {
+                return Err(FetchError::RetrieveFailed{source});
+            }
+        }
+
+        let revision_path = out_dir.join(REVISION_FILE_NAME);
+        if let Err(source) = write_file(&revision_path, &vsn) {
+            return Err(FetchError::RetrieveFailed{source});
+        }
+
+        Ok(())
+    }
+
+    fn resolve(&self, src: &str, Version(vsn): &Version)
+        -> Result<String, DepToolError>
+    {
+        self.revision_files(src, vsn)?;
+
+        Ok(vsn.clone())
+    }
+
+    fn verify(&self, _out_dir: &Path, _version: &Version)
+        -> Result<(), DepToolError>
+    {
+        Err(DepToolError::VerifyUnsupported{tool_name: self.name()})
+    }
+
+    fn revision(&self, out_dir: &Path) -> Result<String, DepToolError> {
+        let path = out_dir.join(REVISION_FILE_NAME);
+
+        match fs::read_to_string(&path) {
+            Ok(revision) => {
+                Ok(revision)
+            },
+            Err(source) => {
+                Err(DepToolError::ReadFileFailed{source, path})
+            },
+        }
+    }
+
+    fn revision_time(&self, _out_dir: &Path) -> Result<u64, DepToolError> {
+        Ok(0)
+    }
+
+    fn metadata_paths(&self, _out_dir: &Path)
+        -> Result<Vec<String>, DepToolError>
+    {
+        Ok(vec![REVISION_FILE_NAME.to_string()])
+    }
+
+    fn check_source(&self, src: &str) -> Result<(), DepToolError> {
+        let known = self.revisions.keys().any(|(source, _)| source == src);
+        if !known {
+            return Err(DepToolError::UnknownMockSource{src: src.to_string()});
+        }
+
+        Ok(())
+    }
+}
+
+fn write_file(path: &Path, conts: &str) -> Result<(), DepToolError> {
+    let result =
+        match path.parent() {
+            Some(parent) => {
+                fs::create_dir_all(parent)
+                    .and_then(|()| fs::write(path, conts))
+            },
+            None => {
+                fs::write(path, conts)
+            },
+        };
+
+    result.map_err(|source| {
+        DepToolError::WriteFileFailed{source, path: path.to_path_buf()}
+    })
+}
+
+#[derive(Debug, Snafu)]
+pub enum LoadMockSourcesError {
+    ReadFailed{source: IoError, path: PathBuf},
+    ParseFailed{source: JsonError, path: PathBuf},
+    InvalidFormat{path: PathBuf},
+}
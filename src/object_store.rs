@@ -0,0 +1,463 @@
+// Copyright 2021 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by an MIT
+// licence that can be found in the LICENCE file.
+
+use std::env;
+use std::fs;
+use std::io::Error as IoError;
+use std::path::Path;
+use std::process::Command;
+use std::process::Output;
+use std::time::Instant;
+
+use dep_tools::DepTool;
+use dep_tools::DepToolError;
+use dep_tools::FetchError;
+use dep_tools::Version;
+use dep_tools::output_until;
+use dep_tools::parse_utc_time;
+use dep_tools::strs_to_strings;
+
+extern crate serde_json;
+extern crate snafu;
+
+use serde_json::Value as JsonValue;
+use snafu::Snafu;
+
+// `REVISION_FILE_NAME` is the name of the file, in the output directory of a
+// dependency, that records the version of the object that was downloaded,
+// followed by the time at which the object was last modified.
+const REVISION_FILE_NAME: &str = ".dpnd_object";
+
+// `GCS_CREDENTIALS_VAR` is the standard variable that names the credentials
+// file of a Google Cloud service account. `gcloud` doesn't read it, so we pass
+// its value to `gcloud` using `GCLOUD_CREDENTIALS_VAR`.
+const GCS_CREDENTIALS_VAR: &str = "GOOGLE_APPLICATION_CREDENTIALS";
+const GCLOUD_CREDENTIALS_VAR: &str = "CLOUDSDK_AUTH_CREDENTIAL_FILE_OVERRIDE";
+
+// `ARCHIVE_EXTS` are the file name extensions of the archives that are
+// extracted after they're downloaded, along with the kind of each archive.
+const ARCHIVE_EXTS: &[(&str, ArchiveKind)] = &[
+    (".tar", ArchiveKind::Tar),
+    (".tar.gz", ArchiveKind::Tar),
+    (".tgz", ArchiveKind::Tar),
+    (".tar.bz2", ArchiveKind::Tar),
+    (".tar.xz", ArchiveKind::Tar),
+    (".zip", ArchiveKind::Zip),
+];
+
+// `ObjectStore` fetches artifacts from cloud object stores. The source of a
+// dependency is the URL of an object, of the form `s3://<bucket>/<key>` for
+// Amazon S3 or `gs://<bucket>/<key>` for Google Cloud Storage. The version
+// is the ETag of an S3 object, or the generation of a GCS object. Objects
+// that are archives are extracted into the output directory of the
+// dependency, and other objects are downloaded into it using the last
+// segment of the key as the file name.
+//
+// Objects are downloaded using the `aws` and `gcloud` CLIs, which read
+// credentials from their standard environment variables, such as
+// `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY`.
+#[derive(Debug)]
+pub struct ObjectStore;
+
+impl DepTool<DepToolError> for ObjectStore {
+    fn name(&self) -> String {
+        "object-store".to_string()
+    }
+
+    fn fetch(
+        &self,
+        src: String,
+        Version(vsn): Version,
+        _path: Option<&str>,
+        out_dir: &Path,
+        deadline: Option<Instant>,
+    )
+        -> Result<(), FetchError<DepToolError>>
+    {
+        let Some(url) = ObjectUrl::parse(&src) else {
+            let source = DepToolError::InvalidObjectUrl{url: src};
+            return Err(FetchError::RetrieveFailed{source});
+        };
+
+        let file_name = url.file_name();
+        let path = out_dir.join(file_name);
+        let modified =
+            match url.download(&vsn, &path, deadline) {
+                Ok(modified) => {
+                    modified
+                },
+                Err(source) => {
+                    return Err(FetchError::RetrieveFailed{source});
+                },
+            };
+
+        if let Some(kind) = archive_kind(file_name) {
+            if let Err(source) = extract(kind, &path, out_dir, deadline) {
+                return Err(FetchError::RetrieveFailed{source});
+            }
+
+            if let Err(source) = fs::remove_file(&path) {
+                let source = DepToolError::WriteFileFailed{source, path};
+                return Err(FetchError::RetrieveFailed{source});
+            }
+        }
+
+        let path = out_dir.join(REVISION_FILE_NAME);
+        let conts = format!("{}\n{}\n", normalize_version(&vsn), modified);
+        if let Err(source) = fs::write(&path, conts) {
+            let source = DepToolError::WriteFileFailed{source, path};
+            return Err(FetchError::RetrieveFailed{source});
+        }
+
+        Ok(())
+    }
+
+    fn resolve(&self, _src: &str, Version(vsn): &Version)
+        -> Result<String, DepToolError>
+    {
+        Ok(normalize_version(vsn))
+    }
+
+    fn verify(&self, _out_dir: &Path, _version: &Version)
+        -> Result<(), DepToolError>
+    {
+        Err(DepToolError::VerifyUnsupported{tool_name: self.name()})
+    }
+
+    fn revision(&self, out_dir: &Path) -> Result<String, DepToolError> {
+        let (version, _) = read_revision_file(out_dir)?;
+
+        Ok(version)
+    }
+
+    fn revision_time(&self, out_dir: &Path) -> Result<u64, DepToolError> {
+        let (_, modified) = read_revision_file(out_dir)?;
+
+        if let Some(t) = parse_utc_time(&modified) {
+            Ok(t)
+        } else {
+            let path = out_dir.join(REVISION_FILE_NAME);
+
+            Err(DepToolError::InvalidRevisionFile{path})
+        }
+    }
+
+    fn metadata_paths(&self, _out_dir: &Path)
+        -> Result<Vec<String>, DepToolError>
+    {
+        Ok(vec![REVISION_FILE_NAME.to_string()])
+    }
+
+    fn check_source(&self, src: &str) -> Result<(), DepToolError> {
+        let url =
+            ObjectUrl::parse(src)
+                .ok_or_else(|| {
+                    DepToolError::InvalidObjectUrl{url: src.to_string()}
+                })?;
+
+        url.latest_version()?;
+
+        Ok(())
+    }
+
+    fn latest_version(&self, src: &str)
+        -> Result<Option<Version>, DepToolError>
+    {
+        let url =
+            ObjectUrl::parse(src)
+                .ok_or_else(|| {
+                    DepToolError::InvalidObjectUrl{url: src.to_string()}
+                })?;
+
+        Ok(Some(Version(url.latest_version()?)))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Store {
+    S3,
+    Gcs,
+}
+
+struct ObjectUrl<'a> {
+    store: Store,
+    bucket: &'a str,
+    key: &'a str,
+}
+
+impl<'a> ObjectUrl<'a> {
+    // `parse` returns the object that `url` refers to, or `None` if `url`
+    // isn't an `s3://` or `gs://` URL with a bucket and a key.
+    fn parse(url: &'a str) -> Option<ObjectUrl<'a>> {
+        let (store, path) =
+            if let Some(path) = url.strip_prefix("s3://") {
+                (Store::S3, path)
+            } else if let Some(path) = url.strip_prefix("gs://") {
+                (Store::Gcs, path)
+            } else {
+                return None;
+            };
+
+        let (bucket, key) = path.split_once('/')?;
+        if bucket.is_empty() || key.is_empty() || key.ends_with('/') {
+            return None;
+        }
+
+        Some(ObjectUrl{store, bucket, key})
+    }
+
+    fn file_name(&self) -> &'a str {
+        self.key.rsplit('/').next().unwrap_or(self.key)
+    }
+
+    fn gcs_url(&self) -> String {
+        format!("gs://{}/{}", self.bucket, self.key)
+    }
+
+    // `download` downloads `vsn` of the object to `path`, and returns the
+    // time at which that version of the object was last modified.
+    fn download(&self, vsn: &str, path: &Path, deadline: Option<Instant>)
+        -> Result<String, DepToolError>
+    {
+        let path = path.to_string_lossy();
+
+        match self.store {
+            Store::S3 => {
+                // S3 requires the ETag in `If-Match` to be quoted.
+                let etag = format!("\"{}\"", normalize_version(vsn));
+                let aws_args = &[
+                    "s3api",
+                    "get-object",
+                    "--bucket",
+                    self.bucket,
+                    "--key",
+                    self.key,
+                    "--if-match",
+                    &etag,
+                    &path,
+                ];
+                let output = run_cli_until("aws", aws_args, deadline)?;
+
+                json_field("aws", aws_args, output, "LastModified")
+            },
+            Store::Gcs => {
+                let versioned_url =
+                    format!("{}#{}", self.gcs_url(), normalize_version(vsn));
+                let gcloud_args = &["storage", "cp", &versioned_url, &path];
+                run_cli_until("gcloud", gcloud_args, deadline)?;
+
+                let gcloud_args = &[
+                    "storage",
+                    "objects",
+                    "describe",
+                    &versioned_url,
+                    "--format=json",
+                ];
+                let output = run_cli_until("gcloud", gcloud_args, deadline)?;
+
+                json_field("gcloud", gcloud_args, output, "update_time")
+            },
+        }
+    }
+
+    // `latest_version` returns the current ETag of an S3 object, or the
+    // current generation of a GCS object.
+    fn latest_version(&self) -> Result<String, DepToolError> {
+        match self.store {
+            Store::S3 => {
+                let aws_args = &[
+                    "s3api",
+                    "head-object",
+                    "--bucket",
+                    self.bucket,
+                    "--key",
+                    self.key,
+                ];
+                let output = run_cli_until("aws", aws_args, None)?;
+                let etag = json_field("aws", aws_args, output, "ETag")?;
+
+                Ok(normalize_version(&etag))
+            },
+            Store::Gcs => {
+                let url = self.gcs_url();
+                let gcloud_args =
+                    &["storage", "objects", "describe", &url, "--format=json"];
+                let output = run_cli_until("gcloud", gcloud_args, None)?;
+
+                json_field("gcloud", gcloud_args, output, "generation")
+            },
+        }
+    }
+}
+
+// `normalize_version` removes the quotes that surround ETags, so that ETags
+// can be written with or without them.
+fn normalize_version(vsn: &str) -> String {
+    vsn.trim_matches('"').to_string()
+}
+
+// `json_field` returns the value of `field` in the JSON object that `prog`
+// printed when run with `args`. Numbers are returned in their decimal form.
+fn json_field(prog: &str, args: &[&str], output: Output, field: &str)
+    -> Result<String, DepToolError>
+{
+    let value: Option<JsonValue> = serde_json::from_slice(&output.stdout).ok();
+    let maybe_field =
+        value.as_ref()
+            .and_then(|value| value.get(field))
+            .and_then(|value| {
+                match value {
+                    JsonValue::String(s) => {
+                        Some(s.clone())
+                    },
+                    JsonValue::Number(n) => {
+                        Some(n.to_string())
+                    },
+                    _ => {
+                        None
+                    },
+                }
+            });
+
+    if let Some(field) = maybe_field {
+        Ok(field)
+    } else {
+        let source = ObjectStoreCmdError::UnexpectedOutput{
+            prog: prog.to_string(),
+            args: strs_to_strings(args),
+            output,
+        };
+
+        Err(DepToolError::ObjectStoreCmdFailed{
+            source: Box::new(source),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum ArchiveKind {
+    Tar,
+    Zip,
+}
+
+fn archive_kind(file_name: &str) -> Option<ArchiveKind> {
+    ARCHIVE_EXTS.iter()
+        .find(|(ext, _)| file_name.ends_with(ext))
+        .map(|(_, kind)| *kind)
+}
+
+// `extract` extracts the archive at `path` into `out_dir`.
+fn extract(
+    kind: ArchiveKind,
+    path: &Path,
+    out_dir: &Path,
+    deadline: Option<Instant>,
+)
+    -> Result<(), DepToolError>
+{
+    let path = path.to_string_lossy();
+    let out_dir = out_dir.to_string_lossy();
+
+    // `tar` detects the compression of archives that it extracts, so the same
+    // arguments are used for each kind of tar archive. Both tools restore
+    // symbolic links, and `--preserve-permissions` stops `tar` from applying
+    // the umask to the modes of extracted files, which `unzip` doesn't do.
+    match kind {
+        ArchiveKind::Tar => {
+            let tar_args = &[
+                "--extract",
+                "--preserve-permissions",
+                "--file",
+                &path,
+                "--directory",
+                &out_dir,
+            ];
+            run_cli_until("tar", tar_args, deadline)?;
+        },
+        ArchiveKind::Zip => {
+            let unzip_args = &["-q", &path, "-d", &out_dir];
+            run_cli_until("unzip", unzip_args, deadline)?;
+        },
+    }
+
+    Ok(())
+}
+
+// `read_revision_file` returns the version and modification time that were
+// recorded when the dependency in `out_dir` was fetched.
+fn read_revision_file(out_dir: &Path)
+    -> Result<(String, String), DepToolError>
+{
+    let path = out_dir.join(REVISION_FILE_NAME);
+    let conts =
+        match fs::read_to_string(&path) {
+            Ok(conts) => {
+                conts
+            },
+            Err(source) => {
+                return Err(DepToolError::ReadFileFailed{source, path});
+            },
+        };
+
+    let mut lines = conts.lines();
+    match (lines.next(), lines.next()) {
+        (Some(version), Some(modified)) => {
+            Ok((version.to_string(), modified.to_string()))
+        },
+        _ => {
+            Err(DepToolError::InvalidRevisionFile{path})
+        },
+    }
+}
+
+fn run_cli_until(prog: &str, args: &[&str], deadline: Option<Instant>)
+    -> Result<Output, DepToolError>
+{
+    let mut cmd = Command::new(prog);
+    cmd.args(args);
+    if env::var_os(GCLOUD_CREDENTIALS_VAR).is_none() {
+        if let Some(path) = env::var_os(GCS_CREDENTIALS_VAR) {
+            cmd.env(GCLOUD_CREDENTIALS_VAR, path);
+        }
+    }
+    let maybe_output = output_until(&mut cmd, deadline);
+
+    let output = match maybe_output {
+        Ok(output) => {
+            output
+        },
+        Err(err) => {
+            let source = ObjectStoreCmdError::StartFailed{
+                source: err,
+                prog: prog.to_string(),
+                args: strs_to_strings(args),
+            };
+
+            return Err(DepToolError::ObjectStoreCmdFailed{
+                source: Box::new(source),
+            });
+        },
+    };
+
+    if !output.status.success() {
+        let source = ObjectStoreCmdError::NotSuccess{
+            prog: prog.to_string(),
+            args: strs_to_strings(args),
+            output,
+        };
+
+        return Err(DepToolError::ObjectStoreCmdFailed{
+            source: Box::new(source),
+        });
+    }
+
+    Ok(output)
+}
+
+#[derive(Debug, Snafu)]
+pub enum ObjectStoreCmdError {
+    StartFailed{source: IoError, prog: String, args: Vec<String>},
+    NotSuccess{prog: String, args: Vec<String>, output: Output},
+    UnexpectedOutput{prog: String, args: Vec<String>, output: Output},
+}
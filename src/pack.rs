@@ -0,0 +1,225 @@
+// Copyright 2021 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by an MIT
+// licence that can be found in the LICENCE file.
+
+use std::env;
+use std::fs;
+use std::io::Error as IoError;
+use std::iter;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Output;
+
+use dep_tools::strs_to_strings;
+use install::DepPaths;
+use temp_dir;
+
+extern crate snafu;
+
+use snafu::ResultExt;
+use snafu::Snafu;
+
+// `PROVENANCE_FILE_NAME` is the name of the archive entry that records the
+// provenance of the packed dependencies.
+pub const PROVENANCE_FILE_NAME: &str = "dpnd_provenance.json";
+
+// `pack` writes the dependency file, the output directories and the state
+// directory described by `dep_paths` to a tar archive at `archive_path`, along
+// with `provenance`, which is stored in the archive as `PROVENANCE_FILE_NAME`.
+// If `tar` is GNU `tar` then the archive is reproducible: entries are sorted
+// by name, and their modification times, owners and permissions are
+// normalized, with modification times set to `mtime`. Other implementations of
+// `tar` don't support these options, so their archives aren't normalized.
+pub fn pack(
+    dep_paths: &DepPaths,
+    archive_path: &Path,
+    mtime: u64,
+    provenance: &str,
+)
+    -> Result<(), PackError>
+{
+    // `tar` can only add entries from files, so the provenance is written to
+    // a temporary directory that's removed once the archive is created.
+    let tmp_dir = temp_dir::create("dpnd_pack")
+        .context(CreateTmpDirFailed{path: env::temp_dir()})?;
+
+    let result = pack_with_provenance(
+        dep_paths,
+        archive_path,
+        mtime,
+        &tmp_dir,
+        provenance,
+    );
+
+    // The temporary directory is only used for this archive, so failures to
+    // remove it are ignored.
+    let _ = fs::remove_dir_all(&tmp_dir);
+
+    result
+}
+
+fn pack_with_provenance(
+    dep_paths: &DepPaths,
+    archive_path: &Path,
+    mtime: u64,
+    tmp_dir: &Path,
+    provenance: &str,
+)
+    -> Result<(), PackError>
+{
+    let provenance_path = tmp_dir.join(PROVENANCE_FILE_NAME);
+    fs::write(&provenance_path, provenance)
+        .context(WriteProvenanceFileFailed{path: &provenance_path})?;
+
+    let (deps_file_name, mut output_dirs) = archive_members(dep_paths);
+    // The state directory doesn't exist if the state files were last written
+    // by a version of `dpnd` that kept them in the output directories.
+    if dep_paths.proj_dir.join(&dep_paths.state_dir).exists() {
+        output_dirs.push(dep_paths.state_dir.to_string_lossy().to_string());
+    }
+    let archive_path = archive_path.to_string_lossy();
+    let mtime_arg = format!("--mtime=@{}", mtime);
+    let proj_dir = dep_paths.proj_dir.to_string_lossy();
+    let tmp_dir = tmp_dir.to_string_lossy();
+
+    let mut tar_args = vec!["-c", "-f", &archive_path];
+    if is_gnu_tar() {
+        tar_args.extend(&[
+            "--format=gnu",
+            "--sort=name",
+            &mtime_arg,
+            "--owner=0",
+            "--group=0",
+            "--numeric-owner",
+            "--mode=u=rwX,go=rX",
+        ]);
+    }
+    tar_args.extend(&["-C", &proj_dir, &deps_file_name]);
+    tar_args.extend(output_dirs.iter().map(String::as_str));
+    tar_args.extend(&["-C", &tmp_dir, PROVENANCE_FILE_NAME]);
+    run_tar(&tar_args).context(TarCmdFailed{})?;
+
+    Ok(())
+}
+
+// `unpack` restores the output directories and the state directory described
+// by `dep_paths` from the tar archive at `archive_path`, which must have been
+// created by `pack` from the same dependency file. The output directories
+// must not already exist, so that files that aren't in the archive can't be
+// mixed with those that are.
+pub fn unpack(dep_paths: &DepPaths, archive_path: &Path)
+    -> Result<(), PackError>
+{
+    let (deps_file_name, mut output_dirs) = archive_members(dep_paths);
+    let archive_path = archive_path.to_string_lossy();
+
+    for output_dir in &output_dirs {
+        let abs_output_dir = dep_paths.proj_dir.join(output_dir);
+        if abs_output_dir.exists() {
+            return Err(PackError::OutputDirExists{path: abs_output_dir});
+        }
+    }
+
+    let tar_args = &["-x", "-O", "-f", &archive_path, &deps_file_name];
+    let output = run_tar(tar_args).context(TarCmdFailed{})?;
+
+    let deps_file_conts = fs::read(&dep_paths.deps_file_path)
+        .context(ReadDepsFileFailed{path: &dep_paths.deps_file_path})?;
+    if output.stdout != deps_file_conts {
+        return Err(PackError::DepsFileMismatch{
+            path: dep_paths.deps_file_path.clone(),
+        });
+    }
+
+    let output = run_tar(&["-t", "-f", &archive_path])
+        .context(TarCmdFailed{})?;
+    let state_dir = dep_paths.state_dir.to_string_lossy().to_string();
+    let has_state_dir =
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .any(|member| member.trim_end_matches('/') == state_dir);
+    if has_state_dir {
+        output_dirs.push(state_dir);
+    }
+
+    let proj_dir = dep_paths.proj_dir.to_string_lossy();
+    let mut tar_args = vec!["-x", "-f", &archive_path, "-C", &proj_dir];
+    tar_args.extend(output_dirs.iter().map(String::as_str));
+    run_tar(&tar_args).context(TarCmdFailed{})?;
+
+    Ok(())
+}
+
+// `archive_members` returns the paths of the dependency file and the output
+// directories described by `dep_paths`, relative to the project directory.
+fn archive_members(dep_paths: &DepPaths) -> (String, Vec<String>) {
+    let deps_file_name =
+        dep_paths.deps_file_path.strip_prefix(&dep_paths.proj_dir)
+            .unwrap_or(&dep_paths.deps_file_path)
+            .to_string_lossy()
+            .to_string();
+
+    let output_dirs =
+        iter::once(&dep_paths.output_dir)
+            .chain(&dep_paths.section_output_dirs)
+            .map(|dir| dir.to_string_lossy().to_string())
+            .collect();
+
+    (deps_file_name, output_dirs)
+}
+
+// `is_gnu_tar` returns whether `tar` is GNU `tar`, which supports the options
+// that `pack` uses to normalize archives.
+fn is_gnu_tar() -> bool {
+    Command::new("tar")
+        .arg("--version")
+        .output()
+        .is_ok_and(|output| {
+            String::from_utf8_lossy(&output.stdout).contains("GNU tar")
+        })
+}
+
+pub fn run_tar(args: &[&str]) -> Result<Output, TarCmdError> {
+    let maybe_output =
+        Command::new("tar")
+            .args(args)
+            .output();
+
+    let output = match maybe_output {
+        Ok(output) => {
+            output
+        },
+        Err(err) => {
+            return Err(TarCmdError::StartFailed{
+                source: err,
+                args: strs_to_strings(args),
+            });
+        },
+    };
+
+    if !output.status.success() {
+        return Err(TarCmdError::NotSuccess{
+            args: strs_to_strings(args),
+            output,
+        });
+    }
+
+    Ok(output)
+}
+
+#[derive(Debug, Snafu)]
+pub enum PackError {
+    TarCmdFailed{source: TarCmdError},
+    CreateTmpDirFailed{source: IoError, path: PathBuf},
+    WriteProvenanceFileFailed{source: IoError, path: PathBuf},
+    OutputDirExists{path: PathBuf},
+    ReadDepsFileFailed{source: IoError, path: PathBuf},
+    DepsFileMismatch{path: PathBuf},
+}
+
+#[derive(Debug, Snafu)]
+pub enum TarCmdError {
+    StartFailed{source: IoError, args: Vec<String>},
+    NotSuccess{args: Vec<String>, output: Output},
+}
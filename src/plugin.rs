@@ -0,0 +1,314 @@
+// Copyright 2021 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by an MIT
+// licence that can be found in the LICENCE file.
+
+use std::collections::HashSet;
+use std::env;
+use std::ffi::OsStr;
+use std::io::Error as IoError;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Output;
+use std::time::Instant;
+
+use dep_tools::DepTool;
+use dep_tools::DepToolError;
+use dep_tools::FetchError;
+use dep_tools::Version;
+use dep_tools::output_until;
+use dep_tools::strs_to_strings;
+
+extern crate serde_json;
+extern crate snafu;
+
+use serde_json::Value as JsonValue;
+use snafu::Snafu;
+
+pub const PLUGIN_PREFIX: &str = "dpnd-tool-";
+
+// `Plugin` is a dependency tool that is implemented by an external executable
+// named `dpnd-tool-<name>`. Each operation is performed by running the
+// executable in the output directory of the dependency with one of the
+// following sets of arguments:
+//
+// * `fetch <source>`: Retrieve `<source>` into the (empty) current directory.
+// * `change-version <version>`: Change the dependency in the current
+//   directory to `<version>`.
+// * `verify <version>`: Exit with a non-zero status if the dependency in the
+//   current directory doesn't have a valid signature from a trusted key.
+// * `revision`: Print a JSON object of the form `{"revision": "<id>", "time":
+//   <secs>}` describing the exact revision of the dependency in the current
+//   directory, and the time at which it was created.
+// * `metadata-paths`: Print a JSON array of the paths, relative to the current
+//   directory, that the plugin uses to manage the dependency.
+// * `resolve <source> <version>`: Print a JSON object of the form
+//   `{"revision": "<id>"}` describing the exact revision that `<version>` of
+//   `<source>` refers to. Unlike the other operations, this is run in the
+//   current directory of `dpnd`, and not in an output directory.
+//
+// An operation is considered to have failed if the executable exits with a
+// non-zero status.
+#[derive(Debug)]
+pub struct Plugin {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+impl DepTool<DepToolError> for Plugin {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn fetch(
+        &self,
+        src: String,
+        Version(vsn): Version,
+        _path: Option<&str>,
+        out_dir: &Path,
+        deadline: Option<Instant>,
+    )
+        -> Result<(), FetchError<DepToolError>>
+    {
+        if let Err(err) = self.run_until(&["fetch", &src], out_dir, deadline) {
+            let source = DepToolError::PluginCmdFailed{source: Box::new(err)};
+            return Err(FetchError::RetrieveFailed{source});
+        }
+
+        let args = &["change-version", &vsn];
+        if let Err(err) = self.run_until(args, out_dir, deadline) {
+            let source = DepToolError::PluginCmdFailed{source: Box::new(err)};
+            return Err(FetchError::VersionChangeFailed{source});
+        }
+
+        Ok(())
+    }
+
+    fn resolve(&self, src: &str, Version(vsn): &Version)
+        -> Result<String, DepToolError>
+    {
+        match self.resolve_info(src, vsn) {
+            Ok(revision) => {
+                Ok(revision)
+            },
+            Err(source) => {
+                Err(DepToolError::PluginCmdFailed{source: Box::new(source)})
+            },
+        }
+    }
+
+    fn verify(&self, out_dir: &Path, Version(vsn): &Version)
+        -> Result<(), DepToolError>
+    {
+        match self.run(&["verify", vsn], out_dir) {
+            Ok(_) => {
+                Ok(())
+            },
+            Err(source) => {
+                Err(DepToolError::PluginCmdFailed{source: Box::new(source)})
+            },
+        }
+    }
+
+    fn revision(&self, out_dir: &Path) -> Result<String, DepToolError> {
+        match self.revision_info(out_dir) {
+            Ok((revision, _)) => {
+                Ok(revision)
+            },
+            Err(source) => {
+                Err(DepToolError::PluginCmdFailed{source: Box::new(source)})
+            },
+        }
+    }
+
+    fn revision_time(&self, out_dir: &Path) -> Result<u64, DepToolError> {
+        match self.revision_info(out_dir) {
+            Ok((_, time)) => {
+                Ok(time)
+            },
+            Err(source) => {
+                Err(DepToolError::PluginCmdFailed{source: Box::new(source)})
+            },
+        }
+    }
+
+    fn metadata_paths(&self, out_dir: &Path)
+        -> Result<Vec<String>, DepToolError>
+    {
+        match self.metadata_paths_info(out_dir) {
+            Ok(paths) => {
+                Ok(paths)
+            },
+            Err(source) => {
+                Err(DepToolError::PluginCmdFailed{source: Box::new(source)})
+            },
+        }
+    }
+}
+
+impl Plugin {
+    fn resolve_info(&self, src: &str, vsn: &str)
+        -> Result<String, PluginCmdError>
+    {
+        let args = &["resolve", src, vsn];
+        let (json, output) = self.run_json(args, Path::new("."))?;
+
+        if let Some(revision) = json["revision"].as_str() {
+            Ok(revision.to_string())
+        } else {
+            Err(self.unexpected_output(args, output))
+        }
+    }
+
+    fn metadata_paths_info(&self, out_dir: &Path)
+        -> Result<Vec<String>, PluginCmdError>
+    {
+        let args = &["metadata-paths"];
+        let (json, output) = self.run_json(args, out_dir)?;
+
+        let maybe_paths: Option<Vec<String>> =
+            json.as_array()
+                .and_then(|values| {
+                    values.iter()
+                        .map(|v| v.as_str().map(ToString::to_string))
+                        .collect()
+                });
+
+        if let Some(paths) = maybe_paths {
+            Ok(paths)
+        } else {
+            Err(self.unexpected_output(args, output))
+        }
+    }
+
+    fn revision_info(&self, out_dir: &Path)
+        -> Result<(String, u64), PluginCmdError>
+    {
+        let args = &["revision"];
+        let (json, output) = self.run_json(args, out_dir)?;
+
+        let revision = json["revision"].as_str();
+        let time = json["time"].as_u64();
+
+        if let (Some(revision), Some(time)) = (revision, time) {
+            Ok((revision.to_string(), time))
+        } else {
+            Err(self.unexpected_output(args, output))
+        }
+    }
+
+    fn run_json(&self, args: &[&str], dir: &Path)
+        -> Result<(JsonValue, Output), PluginCmdError>
+    {
+        let output = self.run(args, dir)?;
+
+        match serde_json::from_slice(&output.stdout) {
+            Ok(json) => {
+                Ok((json, output))
+            },
+            Err(_) => {
+                Err(self.unexpected_output(args, output))
+            },
+        }
+    }
+
+    fn unexpected_output(&self, args: &[&str], output: Output)
+        -> PluginCmdError
+    {
+        PluginCmdError::UnexpectedOutput{
+            prog: self.prog(),
+            args: strs_to_strings(args),
+            output,
+        }
+    }
+
+    fn run(&self, args: &[&str], dir: &Path)
+        -> Result<Output, PluginCmdError>
+    {
+        self.run_until(args, dir, None)
+    }
+
+    fn run_until(&self, args: &[&str], dir: &Path, deadline: Option<Instant>)
+        -> Result<Output, PluginCmdError>
+    {
+        let mut cmd = Command::new(&self.path);
+        cmd.args(args).current_dir(dir);
+        let maybe_output = output_until(&mut cmd, deadline);
+
+        let output = match maybe_output {
+            Ok(output) => {
+                output
+            },
+            Err(err) => {
+                return Err(PluginCmdError::StartFailed{
+                    source: err,
+                    prog: self.prog(),
+                    args: strs_to_strings(args),
+                });
+            },
+        };
+
+        if !output.status.success() {
+            return Err(PluginCmdError::NotSuccess{
+                prog: self.prog(),
+                args: strs_to_strings(args),
+                output,
+            });
+        }
+
+        Ok(output)
+    }
+
+    fn prog(&self) -> String {
+        format!("{}{}", PLUGIN_PREFIX, self.name)
+    }
+}
+
+#[derive(Debug, Snafu)]
+pub enum PluginCmdError {
+    StartFailed{source: IoError, prog: String, args: Vec<String>},
+    NotSuccess{prog: String, args: Vec<String>, output: Output},
+    UnexpectedOutput{prog: String, args: Vec<String>, output: Output},
+}
+
+// `find_plugins` returns a `Plugin` for each file in the directories of
+// `path_var` whose name starts with `PLUGIN_PREFIX`. If more than one such
+// file defines the same plugin then only the first is returned, in the same
+// way as executables are resolved by shells.
+pub fn find_plugins(path_var: &OsStr) -> Vec<Plugin> {
+    let mut names = HashSet::new();
+    let mut plugins = vec![];
+
+    for dir in env::split_paths(path_var) {
+        let Ok(entries) = dir.read_dir() else {
+            continue;
+        };
+
+        for entry in entries.flatten() {
+            let file_name = entry.file_name();
+            let maybe_name =
+                file_name.to_str()
+                    .and_then(|s| s.strip_prefix(PLUGIN_PREFIX));
+
+            let name =
+                match maybe_name {
+                    Some(name) if !name.is_empty() => {
+                        name.to_string()
+                    },
+                    _ => {
+                        continue;
+                    },
+                };
+
+            let path = entry.path();
+            if !path.is_file() || names.contains(&name) {
+                continue;
+            }
+
+            names.insert(name.clone());
+            plugins.push(Plugin{name, path});
+        }
+    }
+
+    plugins
+}
@@ -0,0 +1,49 @@
+// Copyright 2021 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by an MIT
+// licence that can be found in the LICENCE file.
+
+use std::io;
+use std::io::BufRead;
+use std::io::IsTerminal;
+use std::io::Write;
+
+// `Prompter` asks the user to confirm actions that can't be undone, such as
+// removing a dependency that has been modified locally.
+pub trait Prompter: Sync {
+    // `confirm` returns whether the user accepted `question`.
+    fn confirm(&self, question: &str) -> bool;
+}
+
+// `TerminalPrompter` writes questions to `stderr` and reads answers from
+// `stdin`.
+pub struct TerminalPrompter;
+
+impl TerminalPrompter {
+    // `new` returns `None` if `stdin` or `stderr` isn't a terminal, because
+    // nobody can answer questions in that case.
+    pub fn new() -> Option<TerminalPrompter> {
+        if io::stdin().is_terminal() && io::stderr().is_terminal() {
+            Some(TerminalPrompter)
+        } else {
+            None
+        }
+    }
+}
+
+impl Prompter for TerminalPrompter {
+    fn confirm(&self, question: &str) -> bool {
+        eprint!("{} [y/N] ", question);
+        let _ = io::stderr().flush();
+
+        // Failures to read an answer are treated as refusals, so that nothing
+        // is changed without confirmation.
+        let mut answer = String::new();
+        if io::stdin().lock().read_line(&mut answer).is_err() {
+            return false;
+        }
+
+        let answer = answer.trim().to_ascii_lowercase();
+
+        answer == "y" || answer == "yes"
+    }
+}
@@ -4,21 +4,59 @@
 
 use std::path::Path;
 use std::path::PathBuf;
+use std::process::Output;
 use std::str;
 
+use config;
+use config::ConfigError;
+use custom_tool::CustomCmdError;
+use dep_tools::DepToolError;
 use dep_tools::FetchError;
 use dep_tools::GitCmdError;
+#[cfg(feature = "docker")]
+use docker::DockerCmdError;
+use file_tool::CurlCmdError;
 use install::InstallDepsError;
 use install::InstallError;
 use install::InstallProjDepsError;
+use install::InstalledRevisionError;
+use install::ManagedPath;
 use install::ParseDepsConfError;
 use install::ParseDepsError;
 use install::ParseOutputDirError;
+use install::PlannedChange;
 use install::ReadDepsFileError;
+use install::SetMtimesError;
+use install::VendorDepError;
 use install::WriteStateFileError;
+#[cfg(feature = "test-util")]
+use mock_tool::LoadMockSourcesError;
+#[cfg(feature = "test-util")]
+use mock_tool::MOCK_SOURCES_VAR;
+use object_store::ObjectStoreCmdError;
+use pack::PackError;
+use pack::TarCmdError;
+use plugin::PLUGIN_PREFIX;
+use plugin::PluginCmdError;
+use store::PruneError;
+use store::STORE_DIR_VAR;
+use svn::SvnCmdError;
+use workspace::WORKSPACE_FILE_NAME;
+use workspace::WorkspaceError;
+
+// `BUILT_IN_TOOL_NAMES` lists the names of the dependency tools that are
+// built into `dpnd`, for error messages. The `mock` tool isn't listed because
+// it's only used for testing.
+#[cfg(not(feature = "docker"))]
+const BUILT_IN_TOOL_NAMES: &str =
+    "'custom', 'file', 'git', 'git-file', 'object-store' and 'svn'";
+#[cfg(feature = "docker")]
+const BUILT_IN_TOOL_NAMES: &str =
+    "'custom', 'docker', 'file', 'git', 'git-file', 'object-store' and \
+     'svn'";
 
 pub fn render_install_error(
-    err: InstallError<GitCmdError>,
+    err: InstallError<DepToolError>,
     cwd: &Path,
     deps_file_name: &str,
 )
@@ -32,6 +70,85 @@ pub fn render_install_error(
                 deps_file_name,
             )
         },
+        InstallError::ReadDepsFileFailed{..} |
+        InstallError::ConvDepsFileUtf8Failed{..} |
+        InstallError::ParseDepsConfFailed{..} |
+        InstallError::ReadNestedDepsFileFailed{..} |
+        InstallError::ReadIncludedDepsFileFailed{..} |
+        InstallError::ReadOldDepsFileFailed{..} |
+        InstallError::ConvOldDepsFileUtf8Failed{..} |
+        InstallError::ParseOldDepsConfFailed{..} => {
+            render_read_deps_file_error(err, cwd, deps_file_name)
+        },
+        InstallError::InstallProjDepsFailed{..} |
+        InstallError::CanonicalizePathFailed{..} |
+        InstallError::OutputDirOverlapsSection{..} |
+        InstallError::ManagedPathsOverlap{..} |
+        InstallError::NestedOutputDirOutsideDep{..} |
+        InstallError::VersionsConflict{..} |
+        InstallError::OutputDirNotWritable{..} => {
+            render_deps_layout_error(err, cwd)
+        },
+        InstallError::ResolveDepFailed{..} |
+        InstallError::DepNotFound{..} |
+        InstallError::UndefinedDep{..} |
+        InstallError::DepNotInstalled{..} |
+        InstallError::GetLatestVersionFailed{..} |
+        InstallError::NoLatestVersion{..} |
+        InstallError::ListChangesFailed{..} |
+        InstallError::CheckSourceFailed{..} => {
+            render_dep_cmd_error(err, cwd, deps_file_name)
+        },
+        InstallError::PinnedDepOutdated{..} |
+        InstallError::PinTrackedDep{..} |
+        InstallError::NoInstalledRevision{..} |
+        InstallError::GetInstalledRevisionFailed{..} |
+        InstallError::WritePinnedDepsFileFailed{..} |
+        InstallError::WritePinnedStateFileFailed{..} => {
+            render_pin_error(err, cwd, deps_file_name)
+        },
+        InstallError::WriteUpgradedDepsFileFailed{..} |
+        InstallError::RestoreUpgradedDepsFileFailed{..} |
+        InstallError::DepDefnNotFound{..} |
+        InstallError::GetAnnotationRevisionFailed{..} |
+        InstallError::WriteAnnotatedDepsFileFailed{..} |
+        InstallError::DepsFileNotFormatted{..} |
+        InstallError::WriteFormattedDepsFileFailed{..} => {
+            render_write_deps_file_error(err, cwd)
+        },
+        InstallError::InstalledDepMissing{..} |
+        InstallError::DepInMultipleStateFiles{..} |
+        InstallError::WriteRepairedStateFileFailed{..} |
+        InstallError::ReadIgnoreFileFailed{..} |
+        InstallError::WriteSummaryFailed{..} => {
+            render_state_file_error(err, cwd)
+        },
+        InstallError::DepsFailed{errs} => {
+            let num_errs = errs.len();
+            let msgs: Vec<String> =
+                errs.into_iter()
+                    .map(|err| render_install_error(err, cwd, deps_file_name))
+                    .collect();
+
+            format!(
+                "{}\n{} dependencies couldn't be installed",
+                msgs.join("\n"),
+                num_errs,
+            )
+        },
+    }
+}
+
+// `render_read_deps_file_error` renders `err`, which is an error in reading or
+// parsing a dependency file, as described by `render_install_error`.
+fn render_read_deps_file_error(
+    err: InstallError<DepToolError>,
+    cwd: &Path,
+    deps_file_name: &str,
+)
+    -> String
+{
+    match err {
         InstallError::ReadDepsFileFailed{
             source: ReadDepsFileError::ReadFailed{source, deps_file_path},
         } => {
@@ -41,352 +158,2039 @@ pub fn render_install_error(
                 source,
             )
         },
-        InstallError::ConvDepsFileUtf8Failed{source, path, dep_name} => {
-            if let Some(name) = dep_name {
-                format!(
-                    "{}: This nested dependency file (for '{}') contains an \
-                     invalid UTF-8 sequence after byte {}",
-                    render_rel_path_else_abs(cwd, &path),
-                    source.utf8_error().valid_up_to(),
-                    name,
-                )
-            } else {
-                format!(
-                    "{}: This dependency file contains an invalid UTF-8 \
-                     sequence after byte {}",
-                    render_rel_path_else_abs(cwd, &path),
-                    source.utf8_error().valid_up_to(),
-                )
-            }
-        },
-        InstallError::ParseDepsConfFailed{source, path, dep_name} => {
-            render_parse_deps_conf_error(source, cwd, &path, dep_name)
+        InstallError::ConvDepsFileUtf8Failed{source, path, dep_path} => {
+            format!(
+                "{}: {} contains an invalid UTF-8 sequence after byte {}",
+                render_rel_path_else_abs(cwd, &path),
+                render_deps_file_descr(render_dep_path(&dep_path).as_deref()),
+                source.utf8_error().valid_up_to(),
+            )
         },
-        InstallError::InstallProjDepsFailed{source, dep_name} => {
-            let dep_descr =
-                if let Some(n) = dep_name {
-                    format!(" in the nested dependency '{}'", n)
-                } else {
-                    "".to_string()
-                };
-            render_install_proj_deps_error(source, cwd, &dep_descr)
+        InstallError::ParseDepsConfFailed{source, path, dep_path} => {
+            let dep_name = render_dep_path(&dep_path);
+
+            render_parse_deps_conf_error(
+                *source,
+                cwd,
+                &path,
+                dep_name.as_deref(),
+            )
         },
         InstallError::ReadNestedDepsFileFailed{
             source,
             path,
-            dep_name,
+            dep_path,
             dep_proj_path,
         } => {
             format!(
                 "Couldn't read the dependency file ('{}') for the nested \
-                 dependency '{}' ('{}'): {}",
+                 dependency {} ('{}'): {}",
                 render_rel_path_else_abs(cwd, &path),
-                dep_name,
+                render_dep_path(&dep_path).unwrap_or_default(),
                 render_rel_path_else_abs(cwd, &dep_proj_path),
                 source,
             )
         },
+        InstallError::ReadIncludedDepsFileFailed{source, path} => {
+            format!(
+                "Couldn't read the included dependency file '{}': {}",
+                render_rel_path_else_abs(cwd, &path),
+                source,
+            )
+        },
+        InstallError::ReadOldDepsFileFailed{source, rev} => {
+            format!(
+                "Couldn't read the dependency file at revision '{}': {}",
+                rev,
+                render_git_cmd_err(source),
+            )
+        },
+        InstallError::ConvOldDepsFileUtf8Failed{source, rev} => {
+            format!(
+                "The dependency file at revision '{}' contains an invalid \
+                 UTF-8 sequence after byte {}",
+                rev,
+                source.utf8_error().valid_up_to(),
+            )
+        },
+        InstallError::ParseOldDepsConfFailed{source, rev} => {
+            format!(
+                "The dependency file at revision '{}' is invalid: {}",
+                rev,
+                render_parse_deps_conf_error(
+                    *source,
+                    cwd,
+                    Path::new(deps_file_name),
+                    None,
+                ),
+            )
+        },
+        _ => {
+            unreachable!(
+                "`render_install_error` passed an unexpected error: {:?}",
+                err,
+            );
+        },
     }
 }
 
-fn render_install_proj_deps_error(
-    err: InstallProjDepsError<GitCmdError>,
+// `render_deps_layout_error` renders `err`, which is an error in where the
+// dependencies of the project are installed, or in how their versions fit
+// together, as described by `render_install_error`.
+fn render_deps_layout_error(
+    err: InstallError<DepToolError>,
     cwd: &Path,
-    dep_descr: &str,
 )
     -> String
 {
     match err {
-        InstallProjDepsError::ReadStateFileFailed{source, path} =>
+        InstallError::InstallProjDepsFailed{source, dep_path} => {
+            let dep_descr =
+                if let Some(n) = render_dep_path(&dep_path) {
+                    format!(" in the nested dependency {}", n)
+                } else {
+                    String::new()
+                };
+            render_install_proj_deps_error(*source, cwd, &dep_descr)
+        },
+        InstallError::CanonicalizePathFailed{source, path} => {
             format!(
-                "Couldn't read the state file ('{}'): {}",
+                "Couldn't resolve '{}': {}",
                 render_rel_path_else_abs(cwd, &path),
                 source,
-            ),
-        InstallProjDepsError::ConvStateFileUtf8Failed{source, path} =>
+            )
+        },
+        InstallError::OutputDirOverlapsSection{
+            output_dir,
+            section,
+            section_output_dir,
+        } => {
             format!(
-                "The state file ('{}') contains an invalid UTF-8 sequence \
-                 after byte {}",
-                render_rel_path_else_abs(cwd, &path),
-                source.utf8_error().valid_up_to(),
-            ),
-        InstallProjDepsError::ParseStateFileFailed{source, path} =>
+                "The output directory given by `--output-dir` ('{}') overlaps \
+                 with the output directory of the '{}' section ('{}'); each \
+                 section must have a separate output directory",
+                render_rel_path_else_abs(cwd, &output_dir),
+                section,
+                section_output_dir.display(),
+            )
+        },
+        InstallError::ManagedPathsOverlap{path, other_path} => {
             format!(
-                "The state file ('{}') is invalid ({}), please remove this \
-                 file and try again",
-                render_rel_path_else_abs(cwd, &path),
-                render_parse_deps_error(source, cwd, &path, None),
-            ),
-        InstallProjDepsError::CreateMainOutputDirFailed{source, path} =>
+                "{}:{}: The {} ('{}') overlaps with the {} ('{}'), which is \
+                 defined on line {} of '{}'",
+                render_rel_path_else_abs(cwd, &path.deps_file_path),
+                path.ln_num,
+                render_managed_path_descr(&path),
+                render_rel_path_else_abs(cwd, &path.path),
+                render_managed_path_descr(&other_path),
+                render_rel_path_else_abs(cwd, &other_path.path),
+                other_path.ln_num,
+                render_rel_path_else_abs(cwd, &other_path.deps_file_path),
+            )
+        },
+        InstallError::NestedOutputDirOutsideDep{
+            path,
+            canon_path,
+            dep_path,
+            dep_proj_path,
+        } => {
             format!(
-                "Couldn't create {}, the main output directory: {}",
+                "{}:{}: The output directory ('{}') resolves to '{}', which \
+                 is outside of the directory of the {} dependency ('{}')",
+                render_rel_path_else_abs(cwd, &path.deps_file_path),
+                path.ln_num,
+                render_rel_path_else_abs(cwd, &path.path),
+                render_rel_path_else_abs(cwd, &canon_path),
+                render_dep_path(&dep_path).unwrap_or_default(),
+                render_rel_path_else_abs(cwd, &dep_proj_path),
+            )
+        },
+        InstallError::VersionsConflict{dep_source, decl, other_decl} => {
+            format!(
+                "{}:{}: The '{}' dependency ('{}' at '{}') conflicts with the \
+                 '{}' dependency (at '{}'), which is defined on line {} of \
+                 '{}'",
+                render_rel_path_else_abs(cwd, &decl.deps_file_path),
+                decl.ln_num,
+                decl.dep_name,
+                dep_source,
+                decl.version,
+                other_decl.dep_name,
+                other_decl.version,
+                other_decl.ln_num,
+                render_rel_path_else_abs(cwd, &other_decl.deps_file_path),
+            )
+        },
+        InstallError::OutputDirNotWritable{source, path} => {
+            format!(
+                "Can't write to the output directory '{}' ({}); check the \
+                 permissions of the directory, or of its closest existing \
+                 parent",
                 render_rel_path_else_abs(cwd, &path),
                 source,
-            ),
-        InstallProjDepsError::InstallDepsFailed{source} =>
-            render_install_deps_error(source, cwd, dep_descr),
+            )
+        },
+        _ => {
+            unreachable!(
+                "`render_install_error` passed an unexpected error: {:?}",
+                err,
+            );
+        },
     }
 }
 
-fn render_install_deps_error(
-    err: InstallDepsError<GitCmdError>,
+// `render_dep_cmd_error` renders `err`, which is an error in a command that
+// acts on a single dependency, as described by `render_install_error`.
+fn render_dep_cmd_error(
+    err: InstallError<DepToolError>,
     cwd: &Path,
-    dep_descr: &str,
+    deps_file_name: &str,
 )
     -> String
 {
     match err {
-        InstallDepsError::RemoveOldDepOutputDirFailed{
-            source,
-            dep_name,
-            path,
-        } =>
+        InstallError::ResolveDepFailed{source, dep_name} => {
             format!(
-                "Couldn't remove '{}', the output directory for the '{}' \
-                 dependency: {}",
-                render_rel_path_else_abs(cwd, &path),
+                "Couldn't resolve the version of the '{}' dependency: {}",
                 dep_name,
-                source,
-            ),
-        InstallDepsError::WriteCurDepsAfterRemoveFailed{
-            source,
-            dep_name,
-            state_file_path,
-        } =>
-            render_write_cur_deps_err(
-                source,
-                cwd,
-                &state_file_path,
-                &format!("removing '{}'", dep_name),
-            ),
-        InstallDepsError::CreateDepOutputDirFailed{source, dep_name, path} =>
+                render_dep_tool_err(source),
+            )
+        },
+        InstallError::DepNotFound{query} => {
             format!(
-                "Couldn't create '{}', the output directory for the '{}' \
-                 dependency: {}",
+                "'{}' isn't the name of a dependency, or a path in the output \
+                 directory of a dependency",
+                query,
+            )
+        },
+        InstallError::UndefinedDep{dep_name} => {
+            format!(
+                "'{}' isn't the name of a dependency in '{}'",
+                dep_name,
+                deps_file_name,
+            )
+        },
+        InstallError::DepNotInstalled{dep_name} => {
+            format!(
+                "The '{}' dependency isn't installed, run `dpnd install` to \
+                 install it",
+                dep_name,
+            )
+        },
+        InstallError::GetLatestVersionFailed{source, dep_name} => {
+            format!(
+                "Couldn't get the latest version of the '{}' dependency: {}",
+                dep_name,
+                render_dep_tool_err(source),
+            )
+        },
+        InstallError::NoLatestVersion{dep_name, tool_name} => {
+            format!(
+                "The '{}' tool can't find the latest version of the '{}' \
+                 dependency; specify the version to upgrade to",
+                tool_name,
+                dep_name,
+            )
+        },
+        InstallError::ListChangesFailed{source, dep_name} => {
+            format!(
+                "Couldn't list the changes to the '{}' dependency: {}",
+                dep_name,
+                render_dep_tool_err(source),
+            )
+        },
+        InstallError::CheckSourceFailed{source, dep_name, path} => {
+            format!(
+                "{}: Couldn't reach the source of the '{}' dependency: {}",
                 render_rel_path_else_abs(cwd, &path),
                 dep_name,
-                source,
-            ),
-        InstallDepsError::WriteCurDepsAfterInstallFailed{
-            source,
-            dep_name,
-            state_file_path,
-        } =>
-            render_write_cur_deps_err(
-                source,
-                cwd,
-                &state_file_path,
-                &format!("installing '{}'", dep_name),
-            ),
-        InstallDepsError::WriteInitialCurDepsFailed{source, state_file_path} =>
-            render_write_cur_deps_err(
-                source,
-                cwd,
-                &state_file_path,
-                "updating dependencies",
-            ),
-        InstallDepsError::FetchFailed{source, dep_name} =>
-            match source {
-                FetchError::RetrieveFailed{source} =>
-                    format!(
-                        "Couldn't retrieve the source for the dependency \
-                         '{}'{}: {}",
-                        dep_name,
-                        dep_descr,
-                        render_git_cmd_err(source),
-                    ),
-                FetchError::VersionChangeFailed{source} =>
-                    format!(
-                        "Couldn't change the version for the '{}' dependency: \
-                         {}",
-                        dep_name,
-                        render_git_cmd_err(source),
-                    ),
-            },
-    }
-}
-
-fn render_parse_deps_conf_error(
-    err: ParseDepsConfError,
-    cwd: &Path,
-    deps_file_path: &Path,
-    dep_name: Option<String>,
-)
-    -> String
-{
-    match err {
-        ParseDepsConfError::ParseOutputDirFailed{source} =>
-            match source {
-                ParseOutputDirError::MissingOutputDir =>
-                    if let Some(name) = dep_name {
-                        format!(
-                            "{}: This nested dependency file (for '{}') \
-                             doesn't contain an output directory",
-                            render_rel_path_else_abs(cwd, deps_file_path),
-                            name,
-                        )
-                    } else {
-                        format!(
-                            "{}: This dependency file doesn't contain an \
-                             output directory",
-                            render_rel_path_else_abs(cwd, deps_file_path),
-                        )
-                    },
-                ParseOutputDirError::InvalidPart{ln_num, part} =>
-                    if let Some(name) = dep_name {
-                        format!(
-                            "{}:{}: This nested dependency file (for '{}') \
-                             contains an invalid component ('{}') in its \
-                             output directory",
-                            render_rel_path_else_abs(cwd, deps_file_path),
-                            ln_num,
-                            name,
-                            part,
-                        )
-                    } else {
-                        format!(
-                            "{}:{}: This dependency file contains an invalid \
-                             component ('{}') in its output directory",
-                            render_rel_path_else_abs(cwd, deps_file_path),
-                            ln_num,
-                            part,
-                        )
-                    },
-            },
-        ParseDepsConfError::ParseDepsFailed{source} =>
-            render_parse_deps_error(source, cwd, deps_file_path, dep_name),
+                render_dep_tool_err(*source),
+            )
+        },
+        _ => {
+            unreachable!(
+                "`render_install_error` passed an unexpected error: {:?}",
+                err,
+            );
+        },
     }
 }
 
-fn render_parse_deps_error(
-    err: ParseDepsError,
+// `render_pin_error` renders `err`, which is an error in pinning a
+// dependency, as described by `render_install_error`.
+fn render_pin_error(
+    err: InstallError<DepToolError>,
     cwd: &Path,
-    file_path: &Path,
-    proj_name: Option<String>,
+    deps_file_name: &str,
 )
     -> String
 {
     match err {
-        ParseDepsError::DupDepName{ln_num, dep_name, orig_ln_num} => {
-            if let Some(name) = proj_name {
-                format!(
-                    "{}:{}: A dependency named '{}' is already defined on \
-                     line {} in the nested dependency '{}'",
-                    render_rel_path_else_abs(cwd, file_path),
-                    ln_num,
-                    dep_name,
-                    orig_ln_num,
-                    name,
-                )
-            } else {
-                format!(
-                    "{}:{}: A dependency named '{}' is already defined on \
-                     line {}",
-                    render_rel_path_else_abs(cwd, file_path),
-                    ln_num,
-                    dep_name,
-                    orig_ln_num,
-                )
-            }
+        InstallError::PinnedDepOutdated{dep_name} => {
+            format!(
+                "The '{}' dependency has changed in '{}' since it was \
+                 installed; run `dpnd install` before pinning it",
+                dep_name,
+                deps_file_name,
+            )
         },
-        ParseDepsError::ReservedDepName{ln_num, dep_name} => {
+        InstallError::PinTrackedDep{dep_name} => {
             format!(
-                "{}:{}: '{}' is a reserved name and can't be used as a \
-                 dependency name",
-                render_rel_path_else_abs(cwd, file_path),
-                ln_num,
+                "The '{}' dependency follows its branch, so it can't be \
+                 pinned; remove its `track=branch` option to pin it",
                 dep_name,
             )
         },
-        ParseDepsError::DepNameContainsInvalidChar{
-            ln_num,
-            dep_name,
-            bad_char_idx,
-        } => {
-            let mut bad_char = "".to_string();
-            if let Some(chr) = dep_name.chars().nth(bad_char_idx) {
-                bad_char = format!(" ('{}')", chr);
-            }
+        InstallError::NoInstalledRevision{dep_name} => {
             format!(
-                "{}:{}: '{}' contains an invalid character{} at position {}; \
-                 dependency names can only contain numbers, letters, hyphens, \
-                 underscores and periods",
-                render_rel_path_else_abs(cwd, file_path),
-                ln_num,
+                "The installed revision of the '{}' dependency isn't known, \
+                 so it can't be pinned",
                 dep_name,
-                bad_char,
-                bad_char_idx + 1,
             )
         },
-        ParseDepsError::InvalidDepSpec{ln_num, line} => {
-            if let Some(name) = proj_name {
-                format!(
-                    "{}:{}: Invalid dependency specification in nested \
-                     dependency '{}': '{}'",
-                    render_rel_path_else_abs(cwd, file_path),
-                    ln_num,
-                    name,
-                    line,
-                )
-            } else {
-                format!(
-                    "{}:{}: Invalid dependency specification: '{}'",
-                    render_rel_path_else_abs(cwd, file_path),
-                    ln_num,
-                    line,
-                )
-            }
+        InstallError::GetInstalledRevisionFailed{source, dep_name} => {
+            format!(
+                "Couldn't get the installed revision of the '{}' dependency: \
+                 {}",
+                dep_name,
+                render_installed_revision_err(source),
+            )
         },
-        ParseDepsError::UnknownTool{ln_num, dep_name, tool_name} => {
-            if let Some(name) = proj_name {
-                format!(
-                    "{}:{}: The dependency '{}' of the nested dependency '{}' \
-                     specifies an invalid tool name ('{}'); the supported \
-                     tool is 'git'",
-                    render_rel_path_else_abs(cwd, file_path),
-                    ln_num,
-                    dep_name,
-                    name,
-                    tool_name,
-                )
-            } else {
-                format!(
-                    "{}:{}: The dependency '{}' specifies an invalid tool \
-                     name ('{}'); the supported tool is 'git'",
-                    render_rel_path_else_abs(cwd, file_path),
-                    ln_num,
-                    dep_name,
-                    tool_name,
-                )
-            }
+        InstallError::WritePinnedDepsFileFailed{source, path} => {
+            format!(
+                "Couldn't write the pinned dependency file to '{}': {}",
+                render_path(&path),
+                source,
+            )
+        },
+        InstallError::WritePinnedStateFileFailed{
+            source,
+            state_file_path,
+        } => {
+            render_write_cur_deps_err(
+                source,
+                cwd,
+                &state_file_path,
+                "pinning dependencies",
+            )
+        },
+        _ => {
+            unreachable!(
+                "`render_install_error` passed an unexpected error: {:?}",
+                err,
+            );
         },
     }
 }
 
-fn render_write_cur_deps_err(
-    err: WriteStateFileError,
+// `render_write_deps_file_error` renders `err`, which is an error in
+// rewriting a dependency file, as described by `render_install_error`.
+fn render_write_deps_file_error(
+    err: InstallError<DepToolError>,
     cwd: &Path,
-    state_file_path: &Path,
-    action: &str,
 )
     -> String
 {
     match err {
-        WriteStateFileError::OpenFailed{source} =>
+        InstallError::WriteUpgradedDepsFileFailed{source, path} => {
             format!(
-                "Couldn't open the state file ('{}') for writing after {}: {}",
-                render_rel_path_else_abs(cwd, state_file_path),
-                action,
+                "Couldn't write the upgraded dependency file to '{}': {}",
+                render_path(&path),
                 source,
-            ),
-        WriteStateFileError::WriteDepLineFailed{source} =>
+            )
+        },
+        InstallError::RestoreUpgradedDepsFileFailed{source, path} => {
             format!(
-                "Couldn't write to the state file ('{}') after {}: {}",
-                render_rel_path_else_abs(cwd, state_file_path),
-                action,
+                "Couldn't restore the dependency file '{}' after the upgrade \
+                 failed, so it still defines the new version: {}",
+                render_path(&path),
                 source,
-            ),
-    }
+            )
+        },
+        InstallError::DepDefnNotFound{dep_name, path} => {
+            format!(
+                "Couldn't find the definition of the '{}' dependency in '{}'",
+                dep_name,
+                render_path(&path),
+            )
+        },
+        InstallError::GetAnnotationRevisionFailed{source, dep_name} => {
+            format!(
+                "Couldn't get the installed revision of the '{}' dependency \
+                 to annotate the dependency file: {}",
+                dep_name,
+                render_dep_tool_err(source),
+            )
+        },
+        InstallError::WriteAnnotatedDepsFileFailed{source, path} => {
+            format!(
+                "Couldn't write the annotated dependency file to '{}': {}",
+                render_path(&path),
+                source,
+            )
+        },
+        InstallError::DepsFileNotFormatted{path} => {
+            format!(
+                "'{}' isn't formatted; run `dpnd fmt` to format it",
+                render_rel_path_else_abs(cwd, &path),
+            )
+        },
+        InstallError::WriteFormattedDepsFileFailed{source, path} => {
+            format!(
+                "Couldn't write the formatted dependency file to '{}': {}",
+                render_path(&path),
+                source,
+            )
+        },
+        _ => {
+            unreachable!(
+                "`render_install_error` passed an unexpected error: {:?}",
+                err,
+            );
+        },
+    }
+}
+
+// `render_state_file_error` renders `err`, which is an error in the state
+// files or the other files that `dpnd` reads and writes alongside the
+// dependencies, as described by `render_install_error`.
+fn render_state_file_error(
+    err: InstallError<DepToolError>,
+    cwd: &Path,
+)
+    -> String
+{
+    match err {
+        InstallError::InstalledDepMissing{dep_name, path, state_file_path} => {
+            format!(
+                "'{}' records that '{}' is installed, but '{}' doesn't exist; \
+                 run `dpnd install` to install it again, or `dpnd repair` to \
+                 remove it from the state file",
+                render_rel_path_else_abs(cwd, &state_file_path),
+                dep_name,
+                render_rel_path_else_abs(cwd, &path),
+            )
+        },
+        InstallError::DepInMultipleStateFiles{
+            dep_name,
+            state_file_path,
+            other_state_file_path,
+        } => {
+            format!(
+                "Both '{}' and '{}' record that '{}' is installed; run `dpnd \
+                 install` to remove it from the output directory that it's no \
+                 longer defined for",
+                render_rel_path_else_abs(cwd, &other_state_file_path),
+                render_rel_path_else_abs(cwd, &state_file_path),
+                dep_name,
+            )
+        },
+        InstallError::WriteRepairedStateFileFailed{
+            source,
+            state_file_path,
+        } => {
+            render_write_cur_deps_err(
+                source,
+                cwd,
+                &state_file_path,
+                "repairing it",
+            )
+        },
+        InstallError::ReadIgnoreFileFailed{source, path} => {
+            format!(
+                "Couldn't read the ignore file at '{}': {}",
+                render_rel_path_else_abs(cwd, &path),
+                source,
+            )
+        },
+        InstallError::WriteSummaryFailed{source, path} => {
+            format!(
+                "Couldn't write the summary of dependencies to '{}': {}",
+                render_path(&path),
+                source,
+            )
+        },
+        _ => {
+            unreachable!(
+                "`render_install_error` passed an unexpected error: {:?}",
+                err,
+            );
+        },
+    }
+}
+
+// `render_installed_revision_err` renders `err`, which was returned when
+// getting the installed revision of a dependency.
+fn render_installed_revision_err(
+    err: InstalledRevisionError<DepToolError>,
+)
+    -> String
+{
+    match err {
+        InstalledRevisionError::ReadInstalledVendorFileFailed{
+            source,
+            path,
+        } => {
+            format!(
+                "Couldn't read the vendor file at '{}': {}",
+                render_path(&path),
+                source,
+            )
+        },
+        InstalledRevisionError::GetInstalledRevisionFromToolFailed{source} => {
+            render_dep_tool_err(source)
+        },
+    }
+}
+
+fn render_managed_path_descr(path: &ManagedPath) -> String {
+    if let Some(dep_name) = &path.dep_name {
+        format!("output directory for the '{}' dependency", dep_name)
+    } else {
+        "output directory".to_string()
+    }
+}
+
+fn render_install_proj_deps_error(
+    err: InstallProjDepsError<DepToolError>,
+    cwd: &Path,
+    dep_descr: &str,
+)
+    -> String
+{
+    match err {
+        InstallProjDepsError::ReadStateFileFailed{source, path} =>
+            format!(
+                "Couldn't read the state file ('{}'): {}",
+                render_rel_path_else_abs(cwd, &path),
+                source,
+            ),
+        InstallProjDepsError::ConvStateFileUtf8Failed{source, path} =>
+            format!(
+                "The state file ('{}') contains an invalid UTF-8 sequence \
+                 after byte {}",
+                render_rel_path_else_abs(cwd, &path),
+                source.utf8_error().valid_up_to(),
+            ),
+        InstallProjDepsError::ParseStateFileFailed{source, path} =>
+            format!(
+                "The state file ('{}') is invalid ({}), please remove this \
+                 file and try again",
+                render_rel_path_else_abs(cwd, &path),
+                render_parse_deps_error(*source, cwd, &path, None),
+            ),
+        InstallProjDepsError::CreateMainOutputDirFailed{source, path} =>
+            format!(
+                "Couldn't create {}, the main output directory: {}",
+                render_rel_path_else_abs(cwd, &path),
+                source,
+            ),
+        InstallProjDepsError::CreateStateDirFailed{source, path} =>
+            format!(
+                "Couldn't create {}, the directory of the state file: {}",
+                render_rel_path_else_abs(cwd, &path),
+                source,
+            ),
+        InstallProjDepsError::MoveLegacyStateFileFailed{source, src, dest} =>
+            format!(
+                "Couldn't move the state file from '{}' to '{}': {}",
+                render_rel_path_else_abs(cwd, &src),
+                render_rel_path_else_abs(cwd, &dest),
+                source,
+            ),
+        InstallProjDepsError::InstallDepsFailed{source} =>
+            render_install_deps_error(*source, cwd, dep_descr),
+    }
+}
+
+fn render_install_deps_error(
+    err: InstallDepsError<DepToolError>,
+    cwd: &Path,
+    dep_descr: &str,
+)
+    -> String
+{
+    match err {
+        InstallDepsError::RemoveOldDepOutputDirFailed{..} |
+        InstallDepsError::RenameDepOutputDirFailed{..} |
+        InstallDepsError::CreateDepOutputDirFailed{..} |
+        InstallDepsError::ReadVendorFileFailed{..} |
+        InstallDepsError::MakeDepWritableFailed{..} |
+        InstallDepsError::MakeDepReadonlyFailed{..} |
+        InstallDepsError::SetPermissionsFailed{..} =>
+            render_dep_dir_error(err, cwd),
+        InstallDepsError::WriteInitialCurDepsFailed{..} |
+        InstallDepsError::WriteCurDepsAfterRemoveFailed{..} |
+        InstallDepsError::WriteCurDepsAfterRenameFailed{..} |
+        InstallDepsError::WriteCurDepsAfterInstallFailed{..} |
+        InstallDepsError::WriteCurDepsAfterFastForwardFailed{..} =>
+            render_write_state_error(err, cwd),
+        InstallDepsError::VendorLinkedDepFailed{..} |
+        InstallDepsError::NoStoreDir{..} |
+        InstallDepsError::CreateStoreDirFailed{..} |
+        InstallDepsError::LockStoreEntryFailed{..} |
+        InstallDepsError::ReadStoreDirFailed{..} |
+        InstallDepsError::CreateStoreTempDirFailed{..} |
+        InstallDepsError::MoveStoreEntryFailed{..} |
+        InstallDepsError::LinkDepOutputDirFailed{..} |
+        InstallDepsError::CopyDepFromStoreFailed{..} =>
+            render_store_error(err, cwd),
+        InstallDepsError::DepsOutOfDate{..} |
+        InstallDepsError::FrozenDepsChanged{..} |
+        InstallDepsError::CheckModifiedFailed{..} |
+        InstallDepsError::DepsModified{..} |
+        InstallDepsError::UnknownNeededDep{..} |
+        InstallDepsError::DepNeedsCycle{..} |
+        InstallDepsError::FastForwardFailed{..} |
+        InstallDepsError::GetTrackedRevisionFailed{..} =>
+            render_dep_check_error(err, dep_descr),
+        InstallDepsError::FetchTimedOut{dep_name, elapsed} =>
+            format!(
+                "Fetching the '{}' dependency{} timed out after {}s",
+                dep_name,
+                dep_descr,
+                elapsed.as_secs(),
+            ),
+        InstallDepsError::FetchFailed{source, dep_name, owner} => {
+            let owner_descr =
+                if let Some(owner) = owner {
+                    format!(" (owned by {})", owner)
+                } else {
+                    String::new()
+                };
+
+            render_fetch_err(*source, &dep_name, &owner_descr, dep_descr)
+        },
+        InstallDepsError::VerifySignatureFailed{source, dep_name} =>
+            format!(
+                "Couldn't verify the signature of the '{}' dependency: {}",
+                dep_name,
+                render_dep_tool_err(source),
+            ),
+        InstallDepsError::GetRevisionTimeFailed{source, dep_name} =>
+            format!(
+                "Couldn't get the revision time of the '{}' dependency: {}",
+                dep_name,
+                render_dep_tool_err(source),
+            ),
+        InstallDepsError::NormalizeMtimesFailed{source, dep_name} =>
+            render_set_mtimes_err(source, cwd, &dep_name),
+        InstallDepsError::VendorDepFailed{source, dep_name} =>
+            render_vendor_dep_err(*source, cwd, &dep_name),
+        InstallDepsError::Interrupted =>
+            "Interrupted; partly fetched dependencies were removed, and \
+             other dependencies were left as they were"
+                .to_string(),
+    }
+}
+
+// `render_dep_dir_error` renders `err`, which is an error in changing the
+// output directory of a dependency, as described by
+// `render_install_deps_error`.
+fn render_dep_dir_error(
+    err: InstallDepsError<DepToolError>,
+    cwd: &Path,
+)
+    -> String
+{
+    match err {
+        InstallDepsError::RemoveOldDepOutputDirFailed{
+            source,
+            dep_name,
+            path,
+        } =>
+            format!(
+                "Couldn't remove '{}', the output directory for the '{}' \
+                 dependency: {}",
+                render_rel_path_else_abs(cwd, &path),
+                dep_name,
+                source,
+            ),
+        InstallDepsError::RenameDepOutputDirFailed{
+            source,
+            old_dep_name,
+            dep_name,
+            path,
+        } =>
+            format!(
+                "Couldn't rename the output directory of the '{}' dependency \
+                 to '{}', the output directory for the '{}' dependency: {}",
+                old_dep_name,
+                render_rel_path_else_abs(cwd, &path),
+                dep_name,
+                source,
+            ),
+        InstallDepsError::CreateDepOutputDirFailed{source, dep_name, path} =>
+            format!(
+                "Couldn't create '{}', the output directory for the '{}' \
+                 dependency: {}",
+                render_rel_path_else_abs(cwd, &path),
+                dep_name,
+                source,
+            ),
+        InstallDepsError::ReadVendorFileFailed{source, dep_name, path} =>
+            format!(
+                "Couldn't read the vendor file ('{}') for the '{}' \
+                 dependency: {}",
+                render_rel_path_else_abs(cwd, &path),
+                dep_name,
+                source,
+            ),
+        InstallDepsError::MakeDepWritableFailed{source, dep_name, path} =>
+            format!(
+                "Couldn't make '{}', the output directory for the '{}' \
+                 dependency, writable: {}",
+                render_rel_path_else_abs(cwd, &path),
+                dep_name,
+                source,
+            ),
+        InstallDepsError::MakeDepReadonlyFailed{source, dep_name, path} =>
+            format!(
+                "Couldn't make '{}', the output directory for the '{}' \
+                 dependency, read-only: {}",
+                render_rel_path_else_abs(cwd, &path),
+                dep_name,
+                source,
+            ),
+        InstallDepsError::SetPermissionsFailed{source, dep_name, path} =>
+            format!(
+                "Couldn't set the permissions of the files in '{}', the \
+                 output directory for the '{}' dependency: {}",
+                render_rel_path_else_abs(cwd, &path),
+                dep_name,
+                source,
+            ),
+        _ => {
+            unreachable!(
+                "`render_install_deps_error` passed an unexpected error: {:?}",
+                err,
+            );
+        },
+    }
+}
+
+// `render_write_state_error` renders `err`, which is an error in writing a
+// state file, as described by `render_install_deps_error`.
+fn render_write_state_error(
+    err: InstallDepsError<DepToolError>,
+    cwd: &Path,
+)
+    -> String
+{
+    match err {
+        InstallDepsError::WriteInitialCurDepsFailed{source, state_file_path} =>
+            render_write_cur_deps_err(
+                source,
+                cwd,
+                &state_file_path,
+                "updating dependencies",
+            ),
+        InstallDepsError::WriteCurDepsAfterRemoveFailed{
+            source,
+            dep_name,
+            state_file_path,
+        } =>
+            render_write_cur_deps_err(
+                source,
+                cwd,
+                &state_file_path,
+                &format!("removing '{}'", dep_name),
+            ),
+        InstallDepsError::WriteCurDepsAfterRenameFailed{
+            source,
+            dep_name,
+            state_file_path,
+        } =>
+            render_write_cur_deps_err(
+                source,
+                cwd,
+                &state_file_path,
+                &format!("renaming '{}'", dep_name),
+            ),
+        InstallDepsError::WriteCurDepsAfterInstallFailed{
+            source,
+            dep_name,
+            state_file_path,
+        } =>
+            render_write_cur_deps_err(
+                source,
+                cwd,
+                &state_file_path,
+                &format!("installing '{}'", dep_name),
+            ),
+        InstallDepsError::WriteCurDepsAfterFastForwardFailed{
+            source,
+            state_file_path,
+        } =>
+            render_write_cur_deps_err(
+                source,
+                cwd,
+                &state_file_path,
+                "fast-forwarding dependencies",
+            ),
+        _ => {
+            unreachable!(
+                "`render_install_deps_error` passed an unexpected error: {:?}",
+                err,
+            );
+        },
+    }
+}
+
+// `render_store_error` renders `err`, which is an error in linking a
+// dependency to the store, as described by `render_install_deps_error`.
+fn render_store_error(
+    err: InstallDepsError<DepToolError>,
+    cwd: &Path,
+)
+    -> String
+{
+    match err {
+        InstallDepsError::VendorLinkedDepFailed{dep_name} =>
+            format!(
+                "The '{}' dependency can't be vendored because it's linked to \
+                 the store",
+                dep_name,
+            ),
+        InstallDepsError::NoStoreDir{dep_name} =>
+            format!(
+                "Couldn't link the '{}' dependency because the location of \
+                 the store is unknown; set `{}` to the directory to use as \
+                 the store",
+                dep_name,
+                STORE_DIR_VAR,
+            ),
+        InstallDepsError::CreateStoreDirFailed{source, path} =>
+            format!(
+                "Couldn't create '{}', the store directory: {}",
+                render_path(&path),
+                source,
+            ),
+        InstallDepsError::LockStoreEntryFailed{source, dep_name, path} =>
+            format!(
+                "Couldn't lock '{}', the store entry for the '{}' dependency: \
+                 {}",
+                render_path(&path),
+                dep_name,
+                source,
+            ),
+        InstallDepsError::ReadStoreDirFailed{source, dep_name, path} =>
+            format!(
+                "Couldn't read '{}', the store directory, to fetch the '{}' \
+                 dependency: {}",
+                render_path(&path),
+                dep_name,
+                source,
+            ),
+        InstallDepsError::CreateStoreTempDirFailed{source, dep_name, path} =>
+            format!(
+                "Couldn't create a temporary directory in '{}', the store \
+                 directory, to fetch the '{}' dependency: {}",
+                render_path(&path),
+                dep_name,
+                source,
+            ),
+        InstallDepsError::MoveStoreEntryFailed{source, dep_name, path} =>
+            format!(
+                "Couldn't move the '{}' dependency to '{}' in the store: {}",
+                dep_name,
+                render_path(&path),
+                source,
+            ),
+        InstallDepsError::LinkDepOutputDirFailed{source, dep_name, path} =>
+            format!(
+                "Couldn't link '{}', the output directory for the '{}' \
+                 dependency, to the store: {}",
+                render_rel_path_else_abs(cwd, &path),
+                dep_name,
+                source,
+            ),
+        InstallDepsError::CopyDepFromStoreFailed{source, dep_name, path} =>
+            format!(
+                "Couldn't copy the store entry of the '{}' dependency to \
+                 '{}': {}",
+                dep_name,
+                render_rel_path_else_abs(cwd, &path),
+                source,
+            ),
+        _ => {
+            unreachable!(
+                "`render_install_deps_error` passed an unexpected error: {:?}",
+                err,
+            );
+        },
+    }
+}
+
+// `render_dep_check_error` renders `err`, which is an error in checking the
+// state of the dependencies, as described by `render_install_deps_error`.
+fn render_dep_check_error(
+    err: InstallDepsError<DepToolError>,
+    dep_descr: &str,
+)
+    -> String
+{
+    match err {
+        InstallDepsError::DepsOutOfDate{dep_names} =>
+            format!(
+                "The following dependencies{} aren't up to date: {}",
+                dep_descr,
+                render_quoted_names(&dep_names, ", "),
+            ),
+        InstallDepsError::FrozenDepsChanged{changes} => {
+            let rendered_changes: Vec<String> =
+                changes.iter()
+                    .map(|c| format!("  {}", render_planned_change(c)))
+                    .collect();
+
+            format!(
+                "`--frozen` was used, but the following changes would be made \
+                 to the dependencies{}:\n{}",
+                dep_descr,
+                rendered_changes.join("\n"),
+            )
+        },
+        InstallDepsError::CheckModifiedFailed{source, dep_name} =>
+            format!(
+                "Couldn't check whether the '{}' dependency was modified: {}",
+                dep_name,
+                render_dep_tool_err(source),
+            ),
+        InstallDepsError::DepsModified{dep_names} =>
+            format!(
+                "The following dependencies{} have been modified locally, \
+                 so they weren't changed (use `--force` to discard the \
+                 local modifications): {}",
+                dep_descr,
+                render_quoted_names(&dep_names, ", "),
+            ),
+        InstallDepsError::UnknownNeededDep{dep_name, needed_dep_name} =>
+            format!(
+                "The '{}' dependency{} needs '{}', which isn't a dependency \
+                 in the same output directory",
+                dep_name,
+                dep_descr,
+                needed_dep_name,
+            ),
+        InstallDepsError::DepNeedsCycle{dep_names} =>
+            format!(
+                "The `needs` options of the dependencies{} form a cycle: {}",
+                dep_descr,
+                render_quoted_names(&dep_names, " -> "),
+            ),
+        InstallDepsError::FastForwardFailed{source, dep_name} =>
+            format!(
+                "Couldn't fast-forward the '{}' dependency: {}",
+                dep_name,
+                render_dep_tool_err(source),
+            ),
+        InstallDepsError::GetTrackedRevisionFailed{source, dep_name} =>
+            format!(
+                "Couldn't get the revision of the '{}' dependency: {}",
+                dep_name,
+                render_dep_tool_err(source),
+            ),
+        _ => {
+            unreachable!(
+                "`render_install_deps_error` passed an unexpected error: {:?}",
+                err,
+            );
+        },
+    }
+}
+
+// `render_fetch_err` renders `err`, which was returned when fetching the
+// dependency `dep_name`, where `owner_descr` and `dep_descr` describe the
+// owner of the dependency and the nested dependency that it belongs to.
+fn render_fetch_err(
+    err: FetchError<DepToolError>,
+    dep_name: &str,
+    owner_descr: &str,
+    dep_descr: &str,
+)
+    -> String
+{
+    match err {
+        FetchError::RetrieveFailed{source} =>
+            format!(
+                "Couldn't retrieve the source for the dependency \
+                 '{}'{}{}: {}",
+                dep_name,
+                owner_descr,
+                dep_descr,
+                render_dep_tool_err(source),
+            ),
+        FetchError::VersionChangeFailed{source} =>
+            format!(
+                "Couldn't change the version for the '{}' \
+                 dependency{}: {}",
+                dep_name,
+                owner_descr,
+                render_dep_tool_err(source),
+            ),
+    }
+}
+
+// `render_set_mtimes_err` renders `err`, which was returned when normalizing
+// the modification times of the dependency `dep_name`.
+fn render_set_mtimes_err(
+    err: SetMtimesError,
+    cwd: &Path,
+    dep_name: &str,
+)
+    -> String
+{
+    let (action, path, source) =
+        match err {
+            SetMtimesError::GetMetadataFailed{source, path} => {
+                ("get the metadata of", path, source)
+            },
+            SetMtimesError::ReadDirFailed{source, path} => {
+                ("read the directory", path, source)
+            },
+            SetMtimesError::SetMtimeFailed{source, path} => {
+                ("set the modification time of", path, source)
+            },
+        };
+
+    format!(
+        "Couldn't {} '{}' while normalizing the modification times \
+         of the '{}' dependency: {}",
+        action,
+        render_rel_path_else_abs(cwd, &path),
+        dep_name,
+        source,
+    )
+}
+
+// `render_vendor_dep_err` renders `err`, which was returned when vendoring the
+// dependency `dep_name`.
+fn render_vendor_dep_err(
+    err: VendorDepError<DepToolError>,
+    cwd: &Path,
+    dep_name: &str,
+)
+    -> String
+{
+    match err {
+        VendorDepError::GetRevisionFailed{source} =>
+            format!(
+                "Couldn't get the revision of the '{}' dependency: {}",
+                dep_name,
+                render_dep_tool_err(source),
+            ),
+        VendorDepError::GetMetadataPathsFailed{source} =>
+            format!(
+                "Couldn't get the tool metadata paths of the '{}' \
+                 dependency: {}",
+                dep_name,
+                render_dep_tool_err(source),
+            ),
+        VendorDepError::CanonicalizeMetadataPathFailed{source, path} =>
+            format!(
+                "Couldn't resolve the tool metadata path ('{}') of the '{}' \
+                 dependency: {}",
+                render_rel_path_else_abs(cwd, &path),
+                dep_name,
+                source,
+            ),
+        VendorDepError::MetadataPathOutsideDep{tool_name, path} =>
+            format!(
+                "The '{}' tool reported a metadata path ('{}') that isn't \
+                 inside the output directory of the '{}' dependency, so it \
+                 wasn't removed",
+                tool_name,
+                render_rel_path_else_abs(cwd, &path),
+                dep_name,
+            ),
+        VendorDepError::RemoveMetadataFailed{source, path} =>
+            format!(
+                "Couldn't remove the tool metadata ('{}') from the \
+                 '{}' dependency: {}",
+                render_rel_path_else_abs(cwd, &path),
+                dep_name,
+                source,
+            ),
+        VendorDepError::ExportFailed{source} =>
+            format!(
+                "Couldn't export the '{}' dependency: {}",
+                dep_name,
+                render_dep_tool_err(source),
+            ),
+        VendorDepError::ExportUnsupported{tool_name} =>
+            format!(
+                "The '{}' dependency can't be installed with \
+                 `archive=true` because the '{}' tool doesn't \
+                 support exporting dependencies",
+                dep_name,
+                tool_name,
+            ),
+        VendorDepError::WriteVendorFileFailed{source, path} =>
+            format!(
+                "Couldn't write the vendor file ('{}') for the '{}' \
+                 dependency: {}",
+                render_rel_path_else_abs(cwd, &path),
+                dep_name,
+                source,
+            ),
+    }
+}
+
+// `render_quoted_names` returns the quoted names in `names`, separated by
+// `sep`.
+fn render_quoted_names(names: &[String], sep: &str) -> String {
+    let names: Vec<String> =
+        names.iter()
+            .map(|name| format!("'{}'", name))
+            .collect();
+
+    names.join(sep)
+}
+
+// `render_dep_path` returns the quoted names in `dep_path`, which lead from
+// the root project to a nested dependency, separated by arrows, or `None` if
+// `dep_path` is empty.
+fn render_dep_path(dep_path: &[String]) -> Option<String> {
+    if dep_path.is_empty() {
+        return None;
+    }
+
+    let names: Vec<String> =
+        dep_path.iter()
+            .map(|name| format!("'{}'", name))
+            .collect();
+
+    Some(names.join(" -> "))
+}
+
+// `render_parse_deps_conf_error` renders `err`, where `dep_name` is the
+// rendered path of the nested dependency that `deps_file_path` belongs to, if
+// any.
+fn render_parse_deps_conf_error(
+    err: ParseDepsConfError,
+    cwd: &Path,
+    deps_file_path: &Path,
+    dep_name: Option<&str>,
+)
+    -> String
+{
+    match err {
+        ParseDepsConfError::ParseOutputDirFailed{source} => {
+            render_output_dir_error(
+                source,
+                &render_rel_path_else_abs(cwd, deps_file_path),
+                &render_deps_file_descr(dep_name),
+            )
+        },
+        ParseDepsConfError::ParseDepsFailed{source} =>
+            render_parse_deps_error(source, cwd, deps_file_path, dep_name),
+        ParseDepsConfError::SectionOutputDirsOverlap{
+            ln_num,
+            section,
+            output_dir,
+            other_section,
+            other_output_dir,
+        } => {
+            let other_descr =
+                match other_section {
+                    Some(other) => format!("the '{}' section", other),
+                    None => "the dependency file".to_string(),
+                };
+
+            format!(
+                "{}:{}: The output directory of the '{}' section ('{}') \
+                 overlaps with the output directory of {} ('{}'); each \
+                 section must have a separate output directory",
+                render_rel_path_else_abs(cwd, deps_file_path),
+                ln_num,
+                section,
+                output_dir.display(),
+                other_descr,
+                other_output_dir.display(),
+            )
+        },
+        ParseDepsConfError::InvalidVersionDirective{directive} => {
+            format!(
+                "{}:1: This dependency file has an invalid version directive \
+                 ('{}'); version directives must have the form \
+                 `#!dpnd >=<version>`",
+                render_rel_path_else_abs(cwd, deps_file_path),
+                directive,
+            )
+        },
+        ParseDepsConfError::DpndTooOld{min_version} => {
+            format!(
+                "{}:1: {} requires a newer version of `dpnd` ({} or later, \
+                 but this is {})",
+                render_rel_path_else_abs(cwd, deps_file_path),
+                render_deps_file_descr(dep_name),
+                min_version,
+                env!("CARGO_PKG_VERSION"),
+            )
+        },
+        ParseDepsConfError::ParseIncludedFileFailed{source, path} =>
+            render_parse_deps_error(*source, cwd, &path, dep_name),
+        ParseDepsConfError::ReadIncludedFileFailed{..} |
+        ParseDepsConfError::IncludeCycle{..} |
+        ParseDepsConfError::IncludedFileHasSection{..} |
+        ParseDepsConfError::DupIncludedDepName{..} => {
+            render_include_error(err, cwd, deps_file_path)
+        },
+    }
+}
+
+// `render_include_error` renders `err`, which is an error in a file included
+// by the dependency file at `deps_file_path`, as described by
+// `render_parse_deps_conf_error`.
+fn render_include_error(
+    err: ParseDepsConfError,
+    cwd: &Path,
+    deps_file_path: &Path,
+)
+    -> String
+{
+    match err {
+        ParseDepsConfError::ReadIncludedFileFailed{
+            source,
+            path,
+            ln_num,
+            included_path,
+        } => {
+            format!(
+                "{}:{}: Couldn't read the included file ('{}'): {}",
+                render_rel_path_else_abs(cwd, &path),
+                ln_num,
+                render_rel_path_else_abs(cwd, &included_path),
+                source,
+            )
+        },
+        ParseDepsConfError::IncludeCycle{path, ln_num, included_path} => {
+            format!(
+                "{}:{}: The included file ('{}') is already being included; \
+                 files can't include themselves, directly or indirectly",
+                render_rel_path_else_abs(cwd, &path),
+                ln_num,
+                render_rel_path_else_abs(cwd, &included_path),
+            )
+        },
+        ParseDepsConfError::IncludedFileHasSection{path, ln_num} => {
+            format!(
+                "{}:{}: Included files can't contain section headers or \
+                 group directives; they can only define dependencies and \
+                 include other files",
+                render_rel_path_else_abs(cwd, &path),
+                ln_num,
+            )
+        },
+        ParseDepsConfError::DupIncludedDepName{
+            path,
+            ln_num,
+            dep_name,
+            orig_path,
+            orig_ln_num,
+        } => {
+            let orig_path = orig_path.as_deref().unwrap_or(deps_file_path);
+
+            format!(
+                "{}:{}: A dependency named '{}' is already defined at {}:{}",
+                render_rel_path_else_abs(cwd, &path),
+                ln_num,
+                dep_name,
+                render_rel_path_else_abs(cwd, orig_path),
+                orig_ln_num,
+            )
+        },
+        _ => {
+            unreachable!(
+                "`render_parse_deps_conf_error` passed an unexpected error: \
+                 {:?}",
+                err,
+            );
+        },
+    }
+}
+
+// `render_output_dir_error` renders `err`, which was returned when parsing
+// the output directory of the dependency file at `loc`, which is described by
+// `deps_file_descr`.
+fn render_output_dir_error(
+    err: ParseOutputDirError,
+    loc: &str,
+    deps_file_descr: &str,
+)
+    -> String
+{
+    match err {
+        ParseOutputDirError::MissingOutputDir => {
+            format!(
+                "{}: {} doesn't contain an output directory",
+                loc,
+                deps_file_descr,
+            )
+        },
+        ParseOutputDirError::InvalidPart{ln_num, part} => {
+            format!(
+                "{}:{}: {} contains an invalid component ('{}') in its output \
+                 directory",
+                loc,
+                ln_num,
+                deps_file_descr,
+                part,
+            )
+        },
+        ParseOutputDirError::AbsolutePath{ln_num, path} => {
+            format!(
+                "{}:{}: {} has an absolute output directory ('{}'); output \
+                 directories must be relative to the project",
+                loc,
+                ln_num,
+                deps_file_descr,
+                path,
+            )
+        },
+    }
+}
+
+// `render_deps_file_descr` describes the dependency file of the nested
+// dependency at the rendered path `dep_name`, or the root dependency file if
+// `dep_name` is `None`, at the start of a sentence.
+fn render_deps_file_descr(dep_name: Option<&str>) -> String {
+    match dep_name {
+        Some(name) => {
+            format!("This nested dependency file (for {})", name)
+        },
+        None => {
+            "This dependency file".to_string()
+        },
+    }
+}
+
+// `render_nested_descr` returns a suffix that names the nested dependency
+// at the rendered path `proj_name`, or an empty string if `proj_name` is
+// `None`.
+fn render_nested_descr(proj_name: Option<&str>) -> String {
+    match proj_name {
+        Some(name) => {
+            format!(" in nested dependency {}", name)
+        },
+        None => {
+            String::new()
+        },
+    }
+}
+
+// `render_dep_descr` describes the dependency `dep_name`, of the nested
+// dependency at the rendered path `proj_name` if it's defined, at the start
+// of a sentence.
+fn render_dep_descr(dep_name: &str, proj_name: Option<&str>) -> String {
+    match proj_name {
+        Some(name) => {
+            format!(
+                "The dependency '{}' of the nested dependency {}",
+                dep_name,
+                name,
+            )
+        },
+        None => {
+            format!("The dependency '{}'", dep_name)
+        },
+    }
+}
+
+// `render_parse_deps_error` renders `err`, which was returned when parsing
+// `file_path`, where `proj_name` is the rendered path of the nested
+// dependency that `file_path` belongs to, if any. Errors in sections and in
+// dependency definitions are rendered by `render_parse_section_error`,
+// `render_parse_dep_defn_error` and `render_parse_dep_tool_error`.
+fn render_parse_deps_error(
+    err: ParseDepsError,
+    cwd: &Path,
+    file_path: &Path,
+    proj_name: Option<&str>,
+)
+    -> String
+{
+    let loc = render_rel_path_else_abs(cwd, file_path);
+
+    match err {
+        ParseDepsError::Multiple{errs} => {
+            let msgs: Vec<String> =
+                errs.into_iter()
+                    .map(|err| {
+                        render_parse_deps_error(err, cwd, file_path, proj_name)
+                    })
+                    .collect();
+
+            msgs.join("\n")
+        },
+        ParseDepsError::InvalidDepSpec{ln_num, line} => {
+            format!(
+                "{}:{}: Invalid dependency specification{}: '{}'",
+                loc,
+                ln_num,
+                render_nested_descr(proj_name),
+                line,
+            )
+        },
+        ParseDepsError::UnterminatedQuote{ln_num, col} => {
+            format!(
+                "{}:{}:{}: Unterminated quote{}",
+                loc,
+                ln_num,
+                col,
+                render_nested_descr(proj_name),
+            )
+        },
+        ParseDepsError::InvalidEscape{ln_num, col} => {
+            format!(
+                "{}:{}:{}: Invalid escape sequence{} (only '\\\"' and \
+                 '\\\\' can be used within quotes)",
+                loc,
+                ln_num,
+                col,
+                render_nested_descr(proj_name),
+            )
+        },
+        ParseDepsError::InvalidSectionHeader{..} |
+        ParseDepsError::InvalidGroupDir{..} |
+        ParseDepsError::GroupDirNotInHeader{..} |
+        ParseDepsError::DupSectionName{..} |
+        ParseDepsError::ParseSectionOutputDirFailed{..} => {
+            render_parse_section_error(err, &loc, proj_name)
+        },
+        ParseDepsError::DupDepName{..} |
+        ParseDepsError::ReservedDepName{..} |
+        ParseDepsError::DepNameContainsInvalidChar{..} |
+        ParseDepsError::UnknownDepOption{..} |
+        ParseDepsError::StateOnlyDepOption{..} |
+        ParseDepsError::UnknownVerifyMethod{..} => {
+            render_parse_dep_defn_error(err, &loc, proj_name)
+        },
+        ParseDepsError::MissingPathOption{..} |
+        ParseDepsError::UnexpectedPathOption{..} |
+        ParseDepsError::UnknownTool{..} |
+        ParseDepsError::InvalidDepSource{..} |
+        ParseDepsError::InvalidDepMirror{..} => {
+            render_parse_dep_tool_error(err, &loc, proj_name)
+        },
+    }
+}
+
+// `render_parse_dep_defn_error` renders `err`, which is an error in the name
+// or the options of a dependency in the dependency file at `loc`, as
+// described by `render_parse_deps_error`.
+fn render_parse_dep_defn_error(
+    err: ParseDepsError,
+    loc: &str,
+    proj_name: Option<&str>,
+)
+    -> String
+{
+    match err {
+        ParseDepsError::DupDepName{ln_num, dep_name, orig_ln_num} => {
+            let nested_descr =
+                proj_name
+                    .map(|name| format!(" in the nested dependency {}", name))
+                    .unwrap_or_default();
+
+            format!(
+                "{}:{}: A dependency named '{}' is already defined on line \
+                 {}{}",
+                loc,
+                ln_num,
+                dep_name,
+                orig_ln_num,
+                nested_descr,
+            )
+        },
+        ParseDepsError::ReservedDepName{ln_num, dep_name} => {
+            format!(
+                "{}:{}: '{}' is a reserved name and can't be used as a \
+                 dependency name",
+                loc,
+                ln_num,
+                dep_name,
+            )
+        },
+        ParseDepsError::DepNameContainsInvalidChar{
+            ln_num,
+            dep_name,
+            bad_char_idx,
+        } => {
+            let mut bad_char = String::new();
+            if let Some(chr) = dep_name.chars().nth(bad_char_idx) {
+                bad_char = format!(" ('{}')", chr);
+            }
+            format!(
+                "{}:{}: '{}' contains an invalid character{} at position {}; \
+                 dependency names can only contain ASCII numbers, letters, \
+                 hyphens, underscores and periods",
+                loc,
+                ln_num,
+                dep_name,
+                bad_char,
+                bad_char_idx + 1,
+            )
+        },
+        ParseDepsError::UnknownDepOption{ln_num, dep_name, option} => {
+            format!(
+                "{}:{}: {} specifies an unknown option ('{}'); the supported \
+                 options are 'env.<VAR>', 'group', 'if', 'link', 'mirrors', \
+                 'owner', 'path', 'track' and 'verify'",
+                loc,
+                ln_num,
+                render_dep_descr(&dep_name, proj_name),
+                option,
+            )
+        },
+        ParseDepsError::StateOnlyDepOption{ln_num, dep_name, option} => {
+            format!(
+                "{}:{}: The dependency '{}'{} specifies the '{}' option, \
+                 which `dpnd` records in state files and which can't be \
+                 used in dependency files",
+                loc,
+                ln_num,
+                dep_name,
+                render_nested_descr(proj_name),
+                option,
+            )
+        },
+        ParseDepsError::UnknownVerifyMethod{ln_num, dep_name, method} => {
+            format!(
+                "{}:{}: {} specifies an unknown verification method ('{}'); \
+                 the supported method is 'gpg'",
+                loc,
+                ln_num,
+                render_dep_descr(&dep_name, proj_name),
+                method,
+            )
+        },
+        _ => {
+            unreachable!(
+                "`render_parse_deps_error` passed an unexpected error: {:?}",
+                err,
+            );
+        },
+    }
+}
+
+// `render_parse_dep_tool_error` renders `err`, which is an error in the tool,
+// source or mirrors of a dependency in the dependency file at `loc`, as
+// described by `render_parse_deps_error`.
+fn render_parse_dep_tool_error(
+    err: ParseDepsError,
+    loc: &str,
+    proj_name: Option<&str>,
+)
+    -> String
+{
+    match err {
+        ParseDepsError::MissingPathOption{ln_num, dep_name, tool_name} => {
+            format!(
+                "{}:{}: {} uses the '{}' tool, which requires the 'path' \
+                 option",
+                loc,
+                ln_num,
+                render_dep_descr(&dep_name, proj_name),
+                tool_name,
+            )
+        },
+        ParseDepsError::UnexpectedPathOption{ln_num, dep_name, tool_name} => {
+            format!(
+                "{}:{}: {} specifies the 'path' option, which isn't \
+                 supported by the '{}' tool",
+                loc,
+                ln_num,
+                render_dep_descr(&dep_name, proj_name),
+                tool_name,
+            )
+        },
+        ParseDepsError::UnknownTool{ln_num, dep_name, tool_name} => {
+            format!(
+                "{}:{}: {} specifies an invalid tool name ('{}'); the \
+                 built-in tools are {}, and no `{}{}` plugin was found in \
+                 `PATH`",
+                loc,
+                ln_num,
+                render_dep_descr(&dep_name, proj_name),
+                tool_name,
+                BUILT_IN_TOOL_NAMES,
+                PLUGIN_PREFIX,
+                tool_name,
+            )
+        },
+        ParseDepsError::InvalidDepSource{
+            ln_num,
+            col,
+            dep_name,
+            tool_name,
+            reason,
+        } => {
+            let pos =
+                match col {
+                    Some(col) => format!("{}:{}", ln_num, col),
+                    None => ln_num.to_string(),
+                };
+
+            format!(
+                "{}:{}: The source of '{}'{} isn't a valid '{}' source: {} \
+                 (use `--no-validate-sources` to skip this check)",
+                loc,
+                pos,
+                dep_name,
+                render_nested_descr(proj_name),
+                tool_name,
+                reason,
+            )
+        },
+        ParseDepsError::InvalidDepMirror{
+            ln_num,
+            dep_name,
+            tool_name,
+            mirror,
+            reason,
+        } => {
+            format!(
+                "{}:{}: The mirror '{}' of '{}'{} isn't a valid '{}' source: \
+                 {} (use `--no-validate-sources` to skip this check)",
+                loc,
+                ln_num,
+                mirror,
+                dep_name,
+                render_nested_descr(proj_name),
+                tool_name,
+                reason,
+            )
+        },
+        _ => {
+            unreachable!(
+                "`render_parse_deps_error` passed an unexpected error: {:?}",
+                err,
+            );
+        },
+    }
+}
+
+// `render_parse_section_error` renders `err`, which is an error in a section
+// header or a group directive in the dependency file at `loc`, as described
+// by `render_parse_deps_error`.
+fn render_parse_section_error(
+    err: ParseDepsError,
+    loc: &str,
+    proj_name: Option<&str>,
+)
+    -> String
+{
+    match err {
+        ParseDepsError::InvalidSectionHeader{ln_num, line} => {
+            format!(
+                "{}:{}: Invalid section header{}: '{}'; section headers \
+                 must have the form '[name] output_dir'",
+                loc,
+                ln_num,
+                render_nested_descr(proj_name),
+                line,
+            )
+        },
+        ParseDepsError::InvalidGroupDir{ln_num, line} => {
+            format!(
+                "{}:{}: Invalid group directive{}: '{}'; group directives \
+                 must have the form 'group=name output_dir'",
+                loc,
+                ln_num,
+                render_nested_descr(proj_name),
+                line,
+            )
+        },
+        ParseDepsError::GroupDirNotInHeader{ln_num} => {
+            format!(
+                "{}:{}: Group directives must come before the dependencies \
+                 and sections",
+                loc,
+                ln_num,
+            )
+        },
+        ParseDepsError::DupSectionName{ln_num, section, orig_ln_num} => {
+            format!(
+                "{}:{}: A section named '{}' is already defined on line {}",
+                loc,
+                ln_num,
+                section,
+                orig_ln_num,
+            )
+        },
+        ParseDepsError::ParseSectionOutputDirFailed{source, section} => {
+            match source {
+                ParseOutputDirError::MissingOutputDir => {
+                    format!(
+                        "{}: The '{}' section doesn't have an output \
+                         directory",
+                        loc,
+                        section,
+                    )
+                },
+                ParseOutputDirError::InvalidPart{ln_num, part} => {
+                    format!(
+                        "{}:{}: The output directory of the '{}' section \
+                         contains an invalid component ('{}')",
+                        loc,
+                        ln_num,
+                        section,
+                        part,
+                    )
+                },
+                ParseOutputDirError::AbsolutePath{ln_num, path} => {
+                    format!(
+                        "{}:{}: The '{}' section has an absolute output \
+                         directory ('{}'); output directories must be \
+                         relative to the project",
+                        loc,
+                        ln_num,
+                        section,
+                        path,
+                    )
+                },
+            }
+        },
+        _ => {
+            unreachable!(
+                "`render_parse_deps_error` passed an unexpected error: {:?}",
+                err,
+            );
+        },
+    }
+}
+
+fn render_planned_change(change: &PlannedChange) -> String {
+    match change {
+        PlannedChange::Install{dep_name, dep_spec} => {
+            format!("install '{}' ({})", dep_name, dep_spec)
+        },
+        PlannedChange::Update{dep_name, old_spec, new_spec} => {
+            format!(
+                "update '{}' from ({}) to ({})",
+                dep_name,
+                old_spec,
+                new_spec,
+            )
+        },
+        PlannedChange::Reinstall{dep_name} => {
+            format!(
+                "reinstall '{}', because its output directory is missing or \
+                 doesn't match the state file",
+                dep_name,
+            )
+        },
+        PlannedChange::Remove{dep_name} => {
+            format!("remove '{}'", dep_name)
+        },
+    }
+}
+
+fn render_write_cur_deps_err(
+    err: WriteStateFileError,
+    cwd: &Path,
+    state_file_path: &Path,
+    action: &str,
+)
+    -> String
+{
+    match err {
+        WriteStateFileError::OpenFailed{source} =>
+            format!(
+                "Couldn't open the state file ('{}') for writing after {}: {}",
+                render_rel_path_else_abs(cwd, state_file_path),
+                action,
+                source,
+            ),
+        WriteStateFileError::WriteHeaderFailed{source} |
+        WriteStateFileError::WriteDepLineFailed{source} =>
+            format!(
+                "Couldn't write to the state file ('{}') after {}: {}",
+                render_rel_path_else_abs(cwd, state_file_path),
+                action,
+                source,
+            ),
+    }
+}
+
+pub fn render_pack_error(err: PackError, cwd: &Path) -> String {
+    match err {
+        PackError::TarCmdFailed{source} => {
+            format!(
+                "Couldn't process the archive: {}",
+                render_tar_cmd_err(source),
+            )
+        },
+        PackError::CreateTmpDirFailed{source, path} => {
+            format!(
+                "Couldn't create a temporary directory ('{}'): {}",
+                render_rel_path_else_abs(cwd, &path),
+                source,
+            )
+        },
+        PackError::WriteProvenanceFileFailed{source, path} => {
+            format!(
+                "Couldn't write the provenance of the dependencies to '{}': \
+                 {}",
+                render_rel_path_else_abs(cwd, &path),
+                source,
+            )
+        },
+        PackError::OutputDirExists{path} => {
+            format!(
+                "The output directory ('{}') already exists; remove it before \
+                 unpacking dependencies into it",
+                render_rel_path_else_abs(cwd, &path),
+            )
+        },
+        PackError::ReadDepsFileFailed{source, path} => {
+            format!(
+                "Couldn't read '{}': {}",
+                render_rel_path_else_abs(cwd, &path),
+                source,
+            )
+        },
+        PackError::DepsFileMismatch{path} => {
+            format!(
+                "The archive was packed from a different version of '{}'",
+                render_rel_path_else_abs(cwd, &path),
+            )
+        },
+    }
+}
+
+fn render_tar_cmd_err(err: TarCmdError) -> String {
+    match err {
+        TarCmdError::StartFailed{source, args} => {
+            format!("couldn't start `tar {}`: {}", args.join(" "), source)
+        },
+        TarCmdError::NotSuccess{args, output} => {
+            format!(
+                "`tar {}` failed with the following output:\n\n{}",
+                args.join(" "),
+                render_cmd_output(&output),
+            )
+        },
+    }
+}
+
+pub fn render_config_error(err: ConfigError, cwd: &Path) -> String {
+    match err {
+        ConfigError::ReadFailed{source, path} => {
+            format!(
+                "Couldn't read the config file '{}': {}",
+                render_rel_path_else_abs(cwd, &path),
+                source,
+            )
+        },
+        ConfigError::InvalidLine{path, ln_num, line} => {
+            format!(
+                "{}:{}: Invalid line: '{}'",
+                render_rel_path_else_abs(cwd, &path),
+                ln_num,
+                line,
+            )
+        },
+        ConfigError::UnknownSection{path, ln_num, section} => {
+            let sections: Vec<&str> =
+                config::SECTIONS.iter().map(|(sect, _)| *sect).collect();
+
+            format!(
+                "{}:{}: Unknown section ('{}'); the supported sections are {}",
+                render_rel_path_else_abs(cwd, &path),
+                ln_num,
+                section,
+                render_quoted_list(&sections),
+            )
+        },
+        ConfigError::UnknownSetting{path, ln_num, key, section: None} => {
+            format!(
+                "{}:{}: The '{}' setting must be in a section",
+                render_rel_path_else_abs(cwd, &path),
+                ln_num,
+                key,
+            )
+        },
+        ConfigError::UnknownSetting{path, ln_num, key, section: Some(s)} => {
+            let keys =
+                config::SECTIONS.iter()
+                    .find(|(name, _)| *name == s)
+                    .map_or(&[][..], |(_, keys)| *keys);
+
+            format!(
+                "{}:{}: Unknown setting ('{}'); the supported settings in the \
+                 '{}' section are {}",
+                render_rel_path_else_abs(cwd, &path),
+                ln_num,
+                key,
+                s,
+                render_quoted_list(keys),
+            )
+        },
+        ConfigError::InvalidBool{path, ln_num, key, value} => {
+            let expected = "'true' or 'false'";
+
+            render_invalid_value(cwd, &path, ln_num, &key, expected, &value)
+        },
+        ConfigError::InvalidNumber{path, ln_num, key, value} => {
+            let expected = "a whole number";
+
+            render_invalid_value(cwd, &path, ln_num, &key, expected, &value)
+        },
+        ConfigError::InvalidJobs{path, ln_num, key} => {
+            format!(
+                "{}:{}: The value of '{}' must be greater than 0",
+                render_rel_path_else_abs(cwd, &path),
+                ln_num,
+                key,
+            )
+        },
+        ConfigError::InvalidString{path, ln_num, key, value} => {
+            let expected = "a quoted string";
+
+            render_invalid_value(cwd, &path, ln_num, &key, expected, &value)
+        },
+        ConfigError::InvalidSize{path, ln_num, key, value} => {
+            let expected =
+                "a quoted number of bytes, \
+                 optionally followed by 'K', 'M', 'G' or 'T'";
+
+            render_invalid_value(cwd, &path, ln_num, &key, expected, &value)
+        },
+        ConfigError::RelativePath{path, ln_num, key, value} => {
+            let expected = "a quoted absolute path";
+
+            render_invalid_value(cwd, &path, ln_num, &key, expected, &value)
+        },
+        ConfigError::InvalidChoice{path, ln_num, key, value, choices} => {
+            let quoted: Vec<String> =
+                choices.iter().map(|c| format!("\"{}\"", c)).collect();
+            let expected = format!("one of {}", quoted.join(", "));
+
+            render_invalid_value(cwd, &path, ln_num, &key, &expected, &value)
+        },
+    }
+}
+
+// `render_invalid_value` renders an error for the `value` of the `key`
+// setting, on line `ln_num` of the config file at `path`, which isn't
+// `expected`.
+fn render_invalid_value(
+    cwd: &Path,
+    path: &Path,
+    ln_num: usize,
+    key: &str,
+    expected: &str,
+    value: &str,
+)
+    -> String
+{
+    format!(
+        "{}:{}: The value of '{}' must be {}, not '{}'",
+        render_rel_path_else_abs(cwd, path),
+        ln_num,
+        key,
+        expected,
+        value,
+    )
+}
+
+// `render_quoted_list` renders `items` as a quoted list, such as `'a', 'b' and
+// 'c'`.
+fn render_quoted_list(items: &[&str]) -> String {
+    let quoted: Vec<String> =
+        items.iter().map(|item| format!("'{}'", item)).collect();
+
+    match quoted.split_last() {
+        Some((last, init)) if !init.is_empty() => {
+            format!("{} and {}", init.join(", "), last)
+        },
+        _ => {
+            quoted.join("")
+        },
+    }
+}
+
+pub fn render_prune_error(err: PruneError, cwd: &Path) -> String {
+    match err {
+        PruneError::ReadStoreDirFailed{source, path} => {
+            format!(
+                "Couldn't read '{}', the store directory: {}",
+                render_rel_path_else_abs(cwd, &path),
+                source,
+            )
+        },
+        PruneError::MeasureEntryFailed{source, path} => {
+            format!(
+                "Couldn't measure the store entry '{}': {}",
+                render_rel_path_else_abs(cwd, &path),
+                source,
+            )
+        },
+        PruneError::RemoveEntryFailed{source, path} => {
+            format!(
+                "Couldn't remove the store entry '{}': {}",
+                render_rel_path_else_abs(cwd, &path),
+                source,
+            )
+        },
+    }
+}
+
+#[cfg(feature = "test-util")]
+pub fn render_load_mock_sources_error(err: LoadMockSourcesError) -> String {
+    match err {
+        LoadMockSourcesError::ReadFailed{source, path} => {
+            format!(
+                "Couldn't read the mock sources file '{}': {}",
+                render_path(&path),
+                source,
+            )
+        },
+        LoadMockSourcesError::ParseFailed{source, path} => {
+            format!(
+                "Couldn't parse the mock sources file '{}': {}",
+                render_path(&path),
+                source,
+            )
+        },
+        LoadMockSourcesError::InvalidFormat{path} => {
+            format!(
+                "The mock sources file '{}' (named by `{}`) must map sources \
+                 to versions to file paths to file contents",
+                render_path(&path),
+                MOCK_SOURCES_VAR,
+            )
+        },
+    }
+}
+
+pub fn render_workspace_error(err: WorkspaceError, cwd: &Path) -> String {
+    match err {
+        WorkspaceError::NoWorkspaceFileFound => {
+            format!(
+                "Couldn't find the workspace file '{}' in the current \
+                 directory or parent directories",
+                WORKSPACE_FILE_NAME,
+            )
+        },
+        WorkspaceError::ReadFailed{source, path} => {
+            format!(
+                "Couldn't read the workspace file '{}': {}",
+                render_rel_path_else_abs(cwd, &path),
+                source,
+            )
+        },
+        WorkspaceError::MemberNotFound{path, ln_num, member} => {
+            format!(
+                "{}:{}: The workspace member '{}' isn't a directory",
+                render_rel_path_else_abs(cwd, &path),
+                ln_num,
+                member,
+            )
+        },
+    }
 }
 
 // `render_rel_path_else_abs` renders `path` with `pre` stripped if `path` is a
@@ -406,6 +2210,9 @@ fn render_rel_path_else_abs(pre: &Path, path: &Path) -> String {
     render_path(&path_parts.collect::<PathBuf>())
 }
 
+// `Debug` is used to render paths that aren't valid UTF-8 because it escapes
+// the invalid bytes, where `Display` would replace them.
+#[allow(clippy::unnecessary_debug_formatting)]
 fn render_path(path: &Path) -> String {
     if let Some(s) = path.to_str() {
         s.to_string()
@@ -414,39 +2221,316 @@ fn render_path(path: &Path) -> String {
     }
 }
 
+fn render_dep_tool_err(err: DepToolError) -> String {
+    match err {
+        DepToolError::GitCmdFailed{source} => {
+            render_git_cmd_err(source)
+        },
+        DepToolError::PluginCmdFailed{source} => {
+            render_plugin_cmd_err(*source)
+        },
+        DepToolError::SvnCmdFailed{source} => {
+            render_svn_cmd_err(source)
+        },
+        DepToolError::CurlCmdFailed{source} => {
+            render_curl_cmd_err(source)
+        },
+        #[cfg(feature = "docker")]
+        DepToolError::DockerCmdFailed{source} => {
+            render_docker_cmd_err(source)
+        },
+        DepToolError::ObjectStoreCmdFailed{source} => {
+            render_object_store_cmd_err(*source)
+        },
+        DepToolError::CustomCmdFailed{source} => {
+            render_custom_cmd_err(source)
+        },
+        DepToolError::CreateTempDirFailed{..} |
+        DepToolError::TarCmdFailed{..} |
+        DepToolError::ReadDirFailed{..} |
+        DepToolError::RemovePathFailed{..} |
+        DepToolError::ReadFileFailed{..} |
+        DepToolError::WriteFileFailed{..} |
+        DepToolError::NoDownloadedFile{..} |
+        DepToolError::MultipleDownloadedFiles{..} |
+        DepToolError::InvalidRevisionFile{..} => {
+            render_dep_tool_fs_err(err)
+        },
+        DepToolError::UntrustedSigningKey{key} => {
+            if let Some(key) = key {
+                format!("the signing key ('{}') isn't trusted", key)
+            } else {
+                "the signature wasn't made by a trusted key".to_string()
+            }
+        },
+        DepToolError::VerifyUnsupported{tool_name} => {
+            format!(
+                "the '{}' tool doesn't support signature verification",
+                tool_name,
+            )
+        },
+        DepToolError::PathNotFound{path} => {
+            format!("'{}' doesn't exist in the fetched revision", path)
+        },
+        DepToolError::NoFileNameInUrl{url} => {
+            format!("the URL '{}' doesn't end with a file name", url)
+        },
+        DepToolError::InvalidObjectUrl{url} => {
+            format!(
+                "'{}' isn't an `s3://` or `gs://` URL that names an object",
+                url,
+            )
+        },
+        DepToolError::ChecksumMismatch{file_name, expected, actual} => {
+            format!(
+                "the SHA-256 checksum of '{}' is '{}', but the version of the \
+                 dependency is '{}'",
+                file_name,
+                actual,
+                expected,
+            )
+        },
+        DepToolError::OptionLikeSource{src, program} => {
+            format!(
+                "the source '{}' starts with '-', so {} would read it as an \
+                 option",
+                src,
+                program,
+            )
+        },
+        #[cfg(feature = "test-util")]
+        DepToolError::UnknownMockSource{src} => {
+            format!("the mock source '{}' isn't defined", src)
+        },
+        #[cfg(feature = "test-util")]
+        DepToolError::UnknownMockRevision{src, version} => {
+            format!(
+                "version '{}' of the mock source '{}' isn't defined",
+                version,
+                src,
+            )
+        },
+    }
+}
+
+// `render_dep_tool_fs_err` renders `err`, which is an error in accessing the
+// local files of a dependency, as described by `render_dep_tool_err`.
+fn render_dep_tool_fs_err(err: DepToolError) -> String {
+    match err {
+        DepToolError::CreateTempDirFailed{source, path} => {
+            format!(
+                "couldn't create the temporary directory '{}': {}",
+                render_path(&path),
+                source,
+            )
+        },
+        DepToolError::TarCmdFailed{source} => {
+            format!(
+                "couldn't extract the exported files: {}",
+                render_tar_cmd_err(source),
+            )
+        },
+        DepToolError::ReadDirFailed{source, path} |
+        DepToolError::ReadFileFailed{source, path} => {
+            format!("couldn't read '{}': {}", render_path(&path), source)
+        },
+        DepToolError::RemovePathFailed{source, path} => {
+            format!("couldn't remove '{}': {}", render_path(&path), source)
+        },
+        DepToolError::WriteFileFailed{source, path} => {
+            format!("couldn't write '{}': {}", render_path(&path), source)
+        },
+        DepToolError::NoDownloadedFile{path} => {
+            format!("'{}' doesn't contain a file", render_path(&path))
+        },
+        DepToolError::MultipleDownloadedFiles{path} => {
+            format!("'{}' contains more than one file", render_path(&path))
+        },
+        DepToolError::InvalidRevisionFile{path} => {
+            format!(
+                "'{}' doesn't record a revision and the time it was created",
+                render_path(&path),
+            )
+        },
+        _ => {
+            unreachable!(
+                "`render_dep_tool_err` passed an unexpected error: {:?}",
+                err,
+            );
+        },
+    }
+}
+
 fn render_git_cmd_err(err: GitCmdError) -> String {
     match err {
         GitCmdError::StartFailed{source, args} => {
             format!("couldn't start `git {}`: {}", args.join(" "), source)
         },
         GitCmdError::NotSuccess{args, output} => {
-            let render_output = |bytes, name, prefix| {
-                if let Ok(s) = str::from_utf8(bytes) {
-                    prefix_lines(s, prefix)
-                } else {
-                    format!("{} (not UTF-8): {:?}", name, bytes)
-                }
-            };
+            format!(
+                "`git {}` failed with the following output:\n\n{}",
+                args.join(" "),
+                render_cmd_output(&output),
+            )
+        },
+        GitCmdError::UnexpectedOutput{args, output} => {
+            format!(
+                "`git {}` produced unexpected output:\n\n{}",
+                args.join(" "),
+                render_cmd_output(&output),
+            )
+        },
+    }
+}
+
+fn render_curl_cmd_err(err: CurlCmdError) -> String {
+    match err {
+        CurlCmdError::StartFailed{source, args} => {
+            format!("couldn't start `curl {}`: {}", args.join(" "), source)
+        },
+        CurlCmdError::NotSuccess{args, output} => {
+            format!(
+                "`curl {}` failed with the following output:\n\n{}",
+                args.join(" "),
+                render_cmd_output(&output),
+            )
+        },
+    }
+}
+
+fn render_svn_cmd_err(err: SvnCmdError) -> String {
+    match err {
+        SvnCmdError::StartFailed{source, args} => {
+            format!("couldn't start `svn {}`: {}", args.join(" "), source)
+        },
+        SvnCmdError::NotSuccess{args, output} => {
+            format!(
+                "`svn {}` failed with the following output:\n\n{}",
+                args.join(" "),
+                render_cmd_output(&output),
+            )
+        },
+        SvnCmdError::UnexpectedOutput{args, output} => {
+            format!(
+                "`svn {}` produced unexpected output:\n\n{}",
+                args.join(" "),
+                render_cmd_output(&output),
+            )
+        },
+    }
+}
+
+#[cfg(feature = "docker")]
+fn render_docker_cmd_err(err: DockerCmdError) -> String {
+    match err {
+        DockerCmdError::StartFailed{source, args} => {
+            format!("couldn't start `docker {}`: {}", args.join(" "), source)
+        },
+        DockerCmdError::NotSuccess{args, output} => {
+            format!(
+                "`docker {}` failed with the following output:\n\n{}",
+                args.join(" "),
+                render_cmd_output(&output),
+            )
+        },
+        DockerCmdError::UnexpectedOutput{args, output} => {
+            format!(
+                "`docker {}` produced unexpected output:\n\n{}",
+                args.join(" "),
+                render_cmd_output(&output),
+            )
+        },
+    }
+}
+
+fn render_custom_cmd_err(err: CustomCmdError) -> String {
+    match err {
+        CustomCmdError::StartFailed{source, cmd} => {
+            format!("couldn't start `{}`: {}", cmd, source)
+        },
+        CustomCmdError::NotSuccess{cmd, output} => {
+            format!(
+                "`{}` failed with the following output:\n\n{}",
+                cmd,
+                render_cmd_output(&output),
+            )
+        },
+    }
+}
+
+fn render_object_store_cmd_err(err: ObjectStoreCmdError) -> String {
+    match err {
+        ObjectStoreCmdError::StartFailed{source, prog, args} => {
+            format!("couldn't start `{} {}`: {}", prog, args.join(" "), source)
+        },
+        ObjectStoreCmdError::NotSuccess{prog, args, output} => {
+            format!(
+                "`{} {}` failed with the following output:\n\n{}",
+                prog,
+                args.join(" "),
+                render_cmd_output(&output),
+            )
+        },
+        ObjectStoreCmdError::UnexpectedOutput{prog, args, output} => {
+            format!(
+                "`{} {}` produced unexpected output:\n\n{}",
+                prog,
+                args.join(" "),
+                render_cmd_output(&output),
+            )
+        },
+    }
+}
 
+fn render_plugin_cmd_err(err: PluginCmdError) -> String {
+    match err {
+        PluginCmdError::StartFailed{source, prog, args} => {
+            format!("couldn't start `{} {}`: {}", prog, args.join(" "), source)
+        },
+        PluginCmdError::NotSuccess{prog, args, output} => {
+            format!(
+                "`{} {}` failed with the following output:\n\n{}",
+                prog,
+                args.join(" "),
+                render_cmd_output(&output),
+            )
+        },
+        PluginCmdError::UnexpectedOutput{prog, args, output} => {
             format!(
-                "`git {}` failed with the following output:\n\n{}{}",
+                "`{} {}` produced unexpected output:\n\n{}",
+                prog,
                 args.join(" "),
-                render_output(&output.stdout, "STDOUT", "[>] "),
-                render_output(&output.stderr, "STDERR", "[!] "),
+                render_cmd_output(&output),
             )
         },
     }
 }
 
+fn render_cmd_output(output: &Output) -> String {
+    let render_output = |bytes, name, prefix| {
+        if let Ok(s) = str::from_utf8(bytes) {
+            prefix_lines(s, prefix)
+        } else {
+            format!("{} (not UTF-8): {:?}", name, bytes)
+        }
+    };
+
+    format!(
+        "{}{}",
+        render_output(&output.stdout, "STDOUT", "[>] "),
+        render_output(&output.stderr, "STDERR", "[!] "),
+    )
+}
+
 fn prefix_lines(src: &str, pre: &str) -> String {
     if src.is_empty() {
-        return "".to_string();
+        return String::new();
     }
 
     let tgt = format!(
         "{}{}",
         pre,
-        &src.replace("\n", &format!("\n{}", pre)),
+        &src.replace('\n', &format!("\n{}", pre)),
     );
 
     if src.ends_with('\n') {
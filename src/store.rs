@@ -0,0 +1,247 @@
+// Copyright 2021 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by an MIT
+// licence that can be found in the LICENCE file.
+
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::Error as IoError;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+
+use install::dir_size;
+use install::set_writable;
+
+extern crate snafu;
+
+use snafu::ResultExt;
+use snafu::Snafu;
+
+// `STORE_DIR_VAR` is the environment variable that overrides the location of
+// the store.
+pub const STORE_DIR_VAR: &str = "DPND_STORE";
+
+// `dir` returns the directory that linked dependencies are fetched into. This
+// is `STORE_DIR_VAR` if it's defined and non-empty, and otherwise it's
+// `configured_dir`, which is set by config files, or `dpnd/store` in the
+// user's cache directory, as defined by the XDG Base Directory Specification.
+// `None` is returned if none of these are defined.
+pub fn dir(configured_dir: Option<&Path>) -> Option<PathBuf> {
+    let non_empty_var = |name| {
+        env::var_os(name).filter(|value| !value.is_empty())
+    };
+
+    if let Some(dir) = non_empty_var(STORE_DIR_VAR) {
+        return Some(PathBuf::from(dir));
+    }
+    if let Some(dir) = configured_dir {
+        return Some(dir.to_path_buf());
+    }
+
+    let cache_dir =
+        match non_empty_var("XDG_CACHE_HOME") {
+            Some(dir) => {
+                PathBuf::from(dir)
+            },
+            None => {
+                PathBuf::from(non_empty_var("HOME")?).join(".cache")
+            },
+        };
+
+    Some(cache_dir.join("dpnd").join("store"))
+}
+
+// `LAST_USED_SUFFIX` is appended to the name of an entry in the store to get
+// the name of the file whose modification time is the time at which the
+// entry was last used. The modification time of the entry itself can't be
+// used, because it may be normalized by `--deterministic-mtimes`.
+const LAST_USED_SUFFIX: &str = ".last_used";
+
+// `LOCK_SUFFIX` is appended to the name of an entry in the store to get the
+// name of the file that's locked while the entry is looked up or fetched.
+const LOCK_SUFFIX: &str = ".lock";
+
+// `lock_entry` blocks until it has an exclusive lock on the store entry at
+// `entry`, and returns the file that holds the lock; the lock is released
+// when the file is closed. The lock is advisory, so it only stops other runs
+// of `dpnd`, and other threads of this run, that also lock the entry.
+pub fn lock_entry(entry: &Path) -> Result<File, IoError> {
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(suffixed_path(entry, LOCK_SUFFIX))?;
+    file.lock()?;
+
+    Ok(file)
+}
+
+// `record_use` records that the store entry at `entry` was used now.
+pub fn record_use(entry: &Path) -> Result<(), IoError> {
+    fs::write(last_used_path(entry), "")
+}
+
+fn last_used_path(entry: &Path) -> PathBuf {
+    suffixed_path(entry, LAST_USED_SUFFIX)
+}
+
+fn suffixed_path(entry: &Path, suffix: &str) -> PathBuf {
+    let mut path = entry.as_os_str().to_os_string();
+    path.push(suffix);
+
+    PathBuf::from(path)
+}
+
+// `PrunePolicy` selects the entries of the store that are removed by `prune`.
+// Entries that were last used more than `max_age` seconds ago are removed,
+// and then the least recently used entries are removed until the total size
+// of the store is at most `max_size` bytes. Limits that are `None` aren't
+// applied.
+pub struct PrunePolicy {
+    pub max_size: Option<u64>,
+    pub max_age: Option<u64>,
+}
+
+pub struct StoreEntry {
+    pub path: PathBuf,
+    pub size: u64,
+    // `last_used` is in seconds since the Unix epoch.
+    pub last_used: u64,
+}
+
+// `prune` removes the entries of the store in `store_dir` that are selected
+// by `policy`, and returns them, ordered from least to most recently used.
+// If `dry_run` is `true` then the selected entries are returned but not
+// removed. A store that doesn't exist is treated as empty.
+pub fn prune(store_dir: &Path, policy: &PrunePolicy, now: u64, dry_run: bool)
+    -> Result<Vec<StoreEntry>, PruneError>
+{
+    let mut entries = read_entries(store_dir)?;
+    entries.sort_by_key(|entry| entry.last_used);
+
+    let mut total_size: u64 = entries.iter().map(|entry| entry.size).sum();
+    let mut pruned = vec![];
+    for entry in entries {
+        let age = now.saturating_sub(entry.last_used);
+        let too_old = policy.max_age
+            .is_some_and(|max_age| age > max_age);
+        let too_big = policy.max_size
+            .is_some_and(|max_size| total_size > max_size);
+        if !too_old && !too_big {
+            continue;
+        }
+
+        if !dry_run {
+            remove_entry(&entry.path)?;
+        }
+        total_size -= entry.size;
+        pruned.push(entry);
+    }
+
+    Ok(pruned)
+}
+
+fn read_entries(store_dir: &Path) -> Result<Vec<StoreEntry>, PruneError> {
+    let dir_entries =
+        match fs::read_dir(store_dir) {
+            Ok(dir_entries) => {
+                dir_entries
+            },
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                return Ok(vec![]);
+            },
+            Err(source) => {
+                return Err(PruneError::ReadStoreDirFailed{
+                    source,
+                    path: store_dir.to_path_buf(),
+                });
+            },
+        };
+
+    let mut entries = vec![];
+    for maybe_dir_entry in dir_entries {
+        let dir_entry = maybe_dir_entry
+            .context(ReadStoreDirFailed{path: store_dir})?;
+        let path = dir_entry.path();
+
+        // Entries are directories named after their keys, so other files,
+        // such as the records of when entries were last used and temporary
+        // directories of fetches that are in progress, are skipped.
+        let is_entry =
+            path.extension().is_none()
+                && dir_entry.file_type().is_ok_and(|t| t.is_dir());
+        if !is_entry {
+            continue;
+        }
+
+        let size = dir_size(&path)
+            .context(MeasureEntryFailed{path: &path})?;
+
+        // Entries that were created before their use was recorded are
+        // treated as if they were last used when they were created.
+        let last_used_md =
+            fs::metadata(last_used_path(&path))
+                .or_else(|_| fs::metadata(&path))
+                .context(MeasureEntryFailed{path: &path})?;
+        let last_used =
+            last_used_md.modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_secs());
+
+        entries.push(StoreEntry{path, size, last_used});
+    }
+
+    Ok(entries)
+}
+
+fn remove_entry(path: &Path) -> Result<(), PruneError> {
+    // Entries may have been installed with `--readonly`, in which case their
+    // directories must be made writable before their contents can be
+    // removed.
+    set_writable(path, true)
+        .context(RemoveEntryFailed{path})?;
+    fs::remove_dir_all(path)
+        .context(RemoveEntryFailed{path})?;
+
+    if let Err(source) = fs::remove_file(last_used_path(path)) {
+        if source.kind() != ErrorKind::NotFound {
+            return Err(PruneError::RemoveEntryFailed{
+                source,
+                path: path.to_path_buf(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+// `parse_size` parses `size`, a number of bytes that may be followed by one
+// of the binary unit suffixes `K`, `M`, `G` or `T`.
+pub fn parse_size(size: &str) -> Option<u64> {
+    let (digits, multiplier) =
+        match size.char_indices().last()? {
+            (i, 'K') => (&size[..i], 1 << 10),
+            (i, 'M') => (&size[..i], 1 << 20),
+            (i, 'G') => (&size[..i], 1 << 30),
+            (i, 'T') => (&size[..i], 1 << 40),
+            _ => (size, 1),
+        };
+
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    digits.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Snafu)]
+pub enum PruneError {
+    ReadStoreDirFailed{source: IoError, path: PathBuf},
+    MeasureEntryFailed{source: IoError, path: PathBuf},
+    RemoveEntryFailed{source: IoError, path: PathBuf},
+}
@@ -0,0 +1,224 @@
+// Copyright 2021 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by an MIT
+// licence that can be found in the LICENCE file.
+
+use std::io::Error as IoError;
+use std::path::Path;
+use std::process::Command;
+use std::process::Output;
+use std::time::Instant;
+
+use dep_tools::DepTool;
+use dep_tools::DepToolError;
+use dep_tools::FetchError;
+use dep_tools::Version;
+use dep_tools::check_not_option;
+use dep_tools::output_until;
+use dep_tools::parse_utc_time;
+use dep_tools::program_version;
+use dep_tools::strs_to_strings;
+use install::STATE_DIR_PATH;
+
+extern crate snafu;
+
+use snafu::Snafu;
+
+// `Svn` fetches dependencies from Subversion repositories. The version of a
+// dependency is a revision number, or any other revision keyword that `svn`
+// accepts, such as `HEAD`.
+#[derive(Debug)]
+pub struct Svn;
+
+impl DepTool<DepToolError> for Svn {
+    fn name(&self) -> String {
+        "svn".to_string()
+    }
+
+    fn tool_version(&self) -> Option<String> {
+        program_version("svn", &["--version", "--quiet"])
+    }
+
+    fn fetch(
+        &self,
+        src: String,
+        Version(vsn): Version,
+        _path: Option<&str>,
+        out_dir: &Path,
+        deadline: Option<Instant>,
+    )
+        -> Result<(), FetchError<DepToolError>>
+    {
+        if let Err(source) = check_not_option("Subversion", &src) {
+            return Err(FetchError::RetrieveFailed{source});
+        }
+
+        // We check out an empty working copy first so that failures to
+        // retrieve the source can be distinguished from failures to change
+        // the version, without fetching the contents of the dependency twice.
+        let svn_args = &["checkout", "--depth", "empty", "--", &src, "."];
+        if let Err(err) = run_svn_until(svn_args, out_dir, deadline) {
+            let source = DepToolError::SvnCmdFailed{source: err};
+            return Err(FetchError::RetrieveFailed{source});
+        }
+
+        let svn_args =
+            &["update", "--set-depth", "infinity", "--revision", &vsn];
+        if let Err(err) = run_svn_until(svn_args, out_dir, deadline) {
+            let source = DepToolError::SvnCmdFailed{source: err};
+            return Err(FetchError::VersionChangeFailed{source});
+        }
+
+        Ok(())
+    }
+
+    fn resolve(&self, src: &str, Version(vsn): &Version)
+        -> Result<String, DepToolError>
+    {
+        check_not_option("Subversion", src)?;
+
+        let svn_args = &[
+            "info",
+            "--show-item",
+            "revision",
+            "--revision",
+            vsn,
+            "--",
+            src,
+        ];
+
+        match run_svn(svn_args, Path::new(".")) {
+            Ok(output) => {
+                Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+            },
+            Err(source) => {
+                Err(DepToolError::SvnCmdFailed{source})
+            },
+        }
+    }
+
+    fn verify(&self, _out_dir: &Path, _version: &Version)
+        -> Result<(), DepToolError>
+    {
+        Err(DepToolError::VerifyUnsupported{tool_name: self.name()})
+    }
+
+    fn revision(&self, out_dir: &Path) -> Result<String, DepToolError> {
+        let svn_args = &["info", "--show-item", "revision"];
+
+        match run_svn(svn_args, out_dir) {
+            Ok(output) => {
+                Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+            },
+            Err(source) => {
+                Err(DepToolError::SvnCmdFailed{source})
+            },
+        }
+    }
+
+    fn revision_time(&self, out_dir: &Path) -> Result<u64, DepToolError> {
+        let svn_args = &["info", "--show-item", "last-changed-date"];
+        let output =
+            match run_svn(svn_args, out_dir) {
+                Ok(output) => {
+                    output
+                },
+                Err(source) => {
+                    return Err(DepToolError::SvnCmdFailed{source});
+                },
+            };
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        if let Some(t) = parse_utc_time(stdout.trim()) {
+            Ok(t)
+        } else {
+            let source = SvnCmdError::UnexpectedOutput{
+                args: strs_to_strings(svn_args),
+                output,
+            };
+
+            Err(DepToolError::SvnCmdFailed{source})
+        }
+    }
+
+    fn metadata_paths(&self, _out_dir: &Path)
+        -> Result<Vec<String>, DepToolError>
+    {
+        Ok(vec![".svn".to_string()])
+    }
+
+    fn is_modified(&self, out_dir: &Path) -> Result<bool, DepToolError> {
+        if !out_dir.join(".svn").exists() {
+            return Ok(false);
+        }
+
+        // As with Git dependencies, unversioned files aren't counted as
+        // modifications, because `dpnd` installs the nested dependencies of a
+        // dependency, and keeps their state, inside its output directory.
+        // Changes to the state directory are also skipped, because
+        // dependencies may commit their state files, which `dpnd` rewrites
+        // when it installs their nested dependencies.
+        let svn_args = &["status", "--quiet", "--ignore-externals"];
+        match run_svn(svn_args, out_dir) {
+            Ok(output) => {
+                let stdout = String::from_utf8_lossy(&output.stdout);
+
+                Ok(stdout.lines().any(|line| !is_state_dir_status(line)))
+            },
+            Err(source) => {
+                Err(DepToolError::SvnCmdFailed{source})
+            },
+        }
+    }
+}
+
+// `is_state_dir_status` returns `true` if `line`, a line of the output of
+// `svn status`, is the status of a path in the state directory. Paths start
+// after the seven status columns and a space.
+fn is_state_dir_status(line: &str) -> bool {
+    line.get(8..)
+        .is_some_and(|path| Path::new(path).starts_with(STATE_DIR_PATH))
+}
+
+// `run_svn` runs `svn` with `args` in `dir`, and returns the output of the
+// command if it ran successfully. `svn` is run in non-interactive mode so that
+// it fails instead of prompting for credentials.
+fn run_svn(args: &[&str], dir: &Path) -> Result<Output, SvnCmdError> {
+    run_svn_until(args, dir, None)
+}
+
+fn run_svn_until(args: &[&str], dir: &Path, deadline: Option<Instant>)
+    -> Result<Output, SvnCmdError>
+{
+    let mut cmd = Command::new("svn");
+    cmd.arg("--non-interactive").args(args).current_dir(dir);
+    let maybe_output = output_until(&mut cmd, deadline);
+
+    let output = match maybe_output {
+        Ok(output) => {
+            output
+        },
+        Err(err) => {
+            return Err(SvnCmdError::StartFailed{
+                source: err,
+                args: strs_to_strings(args),
+            });
+        },
+    };
+
+    if !output.status.success() {
+        return Err(SvnCmdError::NotSuccess{
+            args: strs_to_strings(args),
+            output,
+        });
+    }
+
+    Ok(output)
+}
+
+#[derive(Debug, Snafu)]
+pub enum SvnCmdError {
+    StartFailed{source: IoError, args: Vec<String>},
+    NotSuccess{args: Vec<String>, output: Output},
+    UnexpectedOutput{args: Vec<String>, output: Output},
+}
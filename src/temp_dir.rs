@@ -0,0 +1,67 @@
+// Copyright 2021 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by an MIT
+// licence that can be found in the LICENCE file.
+
+use std::env;
+use std::fmt::Write;
+use std::fs::DirBuilder;
+use std::fs::File;
+use std::io::Error as IoError;
+use std::io::ErrorKind;
+use std::io::Read;
+use std::os::unix::fs::DirBuilderExt;
+use std::path::Path;
+use std::path::PathBuf;
+
+// `MAX_ATTEMPTS` is the number of names that `create` tries before it gives
+// up. Names are random, so a name should only be taken if another process is
+// trying to predict it.
+const MAX_ATTEMPTS: usize = 16;
+
+// `create` creates a new directory in the system's temporary directory, whose
+// name is `prefix` followed by a random suffix, and returns its path. The
+// directory is only accessible by the current user. Unlike a directory with a
+// predictable name, the directory can't have been created in advance by
+// another user, and it isn't shared with other runs of `dpnd`.
+pub fn create(prefix: &str) -> Result<PathBuf, IoError> {
+    create_in(&env::temp_dir(), prefix)
+}
+
+// `create_in` is like `create`, but the directory is created in `parent`.
+pub fn create_in(parent: &Path, prefix: &str) -> Result<PathBuf, IoError> {
+    let mut builder = DirBuilder::new();
+    builder.mode(0o700);
+
+    let mut last_err = None;
+    for _ in 0..MAX_ATTEMPTS {
+        let path = parent.join(format!("{}_{}", prefix, suffix()?));
+
+        match builder.create(&path) {
+            Ok(()) => {
+                return Ok(path);
+            },
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+                last_err = Some(err);
+            },
+            Err(err) => {
+                return Err(err);
+            },
+        }
+    }
+
+    Err(last_err.unwrap_or_else(|| IoError::from(ErrorKind::AlreadyExists)))
+}
+
+// `suffix` returns 16 random hexadecimal digits.
+fn suffix() -> Result<String, IoError> {
+    let mut bytes = [0; 8];
+    File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+
+    let hex = bytes.iter().fold(String::new(), |mut hex, b| {
+        let _ = write!(hex, "{:02x}", b);
+
+        hex
+    });
+
+    Ok(hex)
+}
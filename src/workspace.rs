@@ -0,0 +1,83 @@
+// Copyright 2021 Sean Kelleher. All rights reserved.
+// Use of this source code is governed by an MIT
+// licence that can be found in the LICENCE file.
+
+use std::fs;
+use std::io::Error as IoError;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::path::PathBuf;
+
+extern crate snafu;
+
+use snafu::Snafu;
+
+// `WORKSPACE_FILE_NAME` is the name of the file that lists the members of a
+// workspace.
+pub const WORKSPACE_FILE_NAME: &str = "dpnd_workspace.txt";
+
+// `Member` is a project in a workspace. `name` is the path of the project, as
+// written in the workspace file, and `dir` is the absolute path of the
+// project.
+pub struct Member {
+    pub name: String,
+    pub dir: PathBuf,
+}
+
+// `read_members` returns the members listed in the workspace file in `cwd`,
+// or in the closest ancestor of `cwd` that contains a workspace file. Each
+// line of the workspace file is the path of a member directory, relative to
+// the directory that contains the workspace file. Empty lines and lines that
+// start with `#` are ignored.
+pub fn read_members(cwd: &Path) -> Result<Vec<Member>, WorkspaceError> {
+    let (workspace_dir, conts) = read_workspace_file(cwd)?;
+    let path = workspace_dir.join(WORKSPACE_FILE_NAME);
+
+    let mut members = vec![];
+    for (i, line) in conts.lines().enumerate() {
+        let ln = line.trim();
+        if ln.is_empty() || ln.starts_with('#') {
+            continue;
+        }
+
+        let dir = workspace_dir.join(ln);
+        if !dir.is_dir() {
+            return Err(WorkspaceError::MemberNotFound{
+                path,
+                ln_num: i + 1,
+                member: ln.to_string(),
+            });
+        }
+
+        members.push(Member{name: ln.to_string(), dir});
+    }
+
+    Ok(members)
+}
+
+fn read_workspace_file(cwd: &Path)
+    -> Result<(PathBuf, String), WorkspaceError>
+{
+    for dir in cwd.ancestors() {
+        let path = dir.join(WORKSPACE_FILE_NAME);
+        match fs::read_to_string(&path) {
+            Ok(conts) => {
+                return Ok((dir.to_path_buf(), conts));
+            },
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+            },
+            Err(source) => {
+                return Err(WorkspaceError::ReadFailed{source, path});
+            },
+        }
+    }
+
+    Err(WorkspaceError::NoWorkspaceFileFound)
+}
+
+#[derive(Debug, Snafu)]
+pub enum WorkspaceError {
+    NoWorkspaceFileFound,
+    ReadFailed{source: IoError, path: PathBuf},
+    MemberNotFound{path: PathBuf, ln_num: usize, member: String},
+}
@@ -3,12 +3,21 @@
 // licence that can be found in the LICENCE file.
 
 use std::fs;
+use std::path::Path;
+use std::process::Stdio;
+use std::thread;
+use std::time::Duration;
 
 extern crate assert_cmd;
 
+use self::assert_cmd::assert::Assert;
 use self::assert_cmd::Command as AssertCommand;
 
 use crate::test_setup;
+use crate::test_setup::Layout;
+
+use super::BUILT_IN_TOOL_NAMES;
+use super::success;
 
 #[test]
 // Given the dependency file doesn't exist
@@ -22,7 +31,7 @@ fn missing_deps_file() {
     let cmd_result = cmd.assert();
 
     cmd_result
-        .code(1)
+        .code(2)
         .stdout("")
         .stderr(
             "Couldn't find the dependency file 'dpnd.txt' in the current \
@@ -61,7 +70,7 @@ fn empty_deps_file() {
     let cmd_result = cmd.assert();
 
     cmd_result
-        .code(1)
+        .code(3)
         .stdout("")
         .stderr(
             "dpnd.txt: This dependency file doesn't contain an output \
@@ -69,6 +78,36 @@ fn empty_deps_file() {
         );
 }
 
+#[test]
+// Given the dependency file is in a Git repository
+// When the command is run with `--changed-since` and an unknown revision
+// Then the command fails with the output of the Git command
+fn changed_since_unknown_rev() {
+    let root_test_dir =
+        test_setup::create_root_dir("changed_since_unknown_rev");
+    let test_proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    fs::write(format!("{}/dpnd.txt", test_proj_dir), "deps\n")
+        .expect("couldn't write dependency file");
+    test_setup::init_git_repo(&test_proj_dir);
+    test_setup::commit_all(&test_proj_dir);
+    let mut cmd = test_setup::new_test_cmd(test_proj_dir);
+    cmd.args(["--changed-since", "bad_rev"]);
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(1)
+        .stdout("")
+        .stderr(indoc!{"
+            Couldn't read the dependency file at revision 'bad_rev': `git \
+             ls-tree --name-only bad_rev -- dpnd.txt` failed with the \
+             following output:
+
+            [!] fatal: Not a valid object name bad_rev
+
+        "});
+}
+
 fn setup_test_with_deps_file<C: AsRef<[u8]>>(
     root_test_dir_name: &str,
     conts: C,
@@ -96,7 +135,7 @@ fn deps_file_invalid_utf8() {
     let cmd_result = cmd.assert();
 
     cmd_result
-        .code(1)
+        .code(3)
         .stdout("")
         .stderr(
             "dpnd.txt: This dependency file contains an invalid UTF-8 \
@@ -121,7 +160,7 @@ fn deps_file_invalid_dep() {
     let cmd_result = cmd.assert();
 
     cmd_result
-        .code(1)
+        .code(3)
         .stdout("")
         .stderr(
             "dpnd.txt:3: Invalid dependency specification: 'proj tool source \
@@ -130,183 +169,167 @@ fn deps_file_invalid_dep() {
 }
 
 #[test]
-// Given the dependency file contains an unknown tool
+// Given the dependency file contains a field with an unterminated quote
 // When the command is run
-// Then the command fails with an error
-fn deps_file_invalid_tool() {
+// Then the command fails with an error that includes the column of the quote
+fn deps_file_unterminated_quote() {
     let mut cmd = setup_test_with_deps_file(
-        "deps_file_invalid_tool",
-        indoc!{"
+        "deps_file_unterminated_quote",
+        indoc!{r#"
             deps
 
-            proj tool source version
-        "},
+            proj git "/my scripts.git master
+        "#},
     );
 
     let cmd_result = cmd.assert();
 
     cmd_result
-        .code(1)
+        .code(3)
         .stdout("")
-        .stderr(
-            "dpnd.txt:3: The dependency 'proj' specifies an invalid tool name \
-             ('tool'); the supported tool is 'git'\n",
-        );
+        .stderr("dpnd.txt:3:10: Unterminated quote\n");
 }
 
 #[test]
-// Given the dependency file specifies a Git dependency that is unavailable
+// Given the dependency file contains several invalid lines
 // When the command is run
-// Then the command fails with an error
-fn unavailable_git_proj_src() {
+// Then the command fails with an error for each invalid line
+fn deps_file_multiple_errors() {
     let mut cmd = setup_test_with_deps_file(
-        "unavailable_git_proj_src",
-        indoc!{"
+        "deps_file_multiple_errors",
+        indoc!{r#"
             deps
 
-            proj git git://localhost/my_scripts.git master
-        "},
+            proj tool source version extra
+            good git source master
+            bad git "source master
+            good git source master
+        "#},
     );
 
     let cmd_result = cmd.assert();
 
     cmd_result
-        .code(1)
+        .code(3)
         .stdout("")
-        .stderr(indoc!{"
-            Couldn't retrieve the source for the dependency 'proj': `git \
-             clone git://localhost/my_scripts.git .` failed with the \
-             following output:
-
-            [!] Cloning into '.'...
-            [!] fatal: unable to connect to localhost:
-            [!] localhost[0: 127.0.0.1]: errno=Connection refused
-            [!] localhost[1: ::1]: errno=Cannot assign requested address
-            [!] 
-
-        "});
+        .stderr(
+            "dpnd.txt:3: Invalid dependency specification: 'proj tool source \
+             version extra'\n\
+             dpnd.txt:5:9: Unterminated quote\n\
+             dpnd.txt:6: A dependency named 'good' is already defined on \
+             line 4\n",
+        );
 }
 
 #[test]
-// Given the dependency file specifies a Git version that is unavailable
+// Given the dependency file contains an invalid section header, and defines a
+//     dependency in a section with the same name as a dependency outside it
 // When the command is run
-// Then the command fails with the output of the Git command
-fn unavailable_git_proj_vsn() {
-    let root_test_dir =
-        test_setup::create_root_dir("unavailable_git_proj_vsn");
-    let dep_dir =
-        test_setup::create_dir(root_test_dir.clone(), "my_scripts.git");
-    let scratch_dir = test_setup::create_dir(root_test_dir.clone(), "scratch");
-    test_setup::create_bare_git_repo(
-        &dep_dir,
-        &scratch_dir,
-        &[
-            hashmap!{"script.sh" => "echo 'hello, world!'"},
-        ],
-    );
-    let test_proj_dir = test_setup::create_dir(root_test_dir.clone(), "proj");
-    let deps_file_conts = indoc!{"
-        deps
+// Then the command fails with an error for each problem
+fn deps_file_section_errors() {
+    let mut cmd = setup_test_with_deps_file(
+        "deps_file_section_errors",
+        indoc!{"
+            deps
 
-        my_scripts git git://localhost/my_scripts.git bad_commit
-    "};
-    let cmd_result = test_setup::with_git_server(
-        root_test_dir,
-        || {
-            fs::write(
-                test_proj_dir.to_string() + "/dpnd.txt",
-                &deps_file_conts,
-            )
-                .expect("couldn't write dependency file");
-            let mut cmd = test_setup::new_test_cmd(test_proj_dir.clone());
+            good git source master
 
-            cmd.assert()
-        },
+            [tools
+            [tools] tools
+            good git source master
+        "},
     );
 
+    let cmd_result = cmd.assert();
+
     cmd_result
-        .code(1)
+        .code(3)
         .stdout("")
-        .stderr(indoc!{"
-            Couldn't change the version for the 'my_scripts' dependency: `git \
-             checkout bad_commit` failed with the following output:
-
-            [!] error: pathspec 'bad_commit' did not match any file(s) known \
-             to git
-
-        "});
+        .stderr(
+            "dpnd.txt:5: Invalid section header: '[tools'; section headers \
+             must have the form '[name] output_dir'\n\
+             dpnd.txt:7: A dependency named 'good' is already defined on \
+             line 3\n",
+        );
 }
 
 #[test]
-// Given the main output directory is a file
+// Given the dependency file contains an invalid group directive, a duplicate
+//     group directive, and a group directive after a dependency
 // When the command is run
-// Then the command fails with an error
-fn main_output_dir_is_file() {
-    let root_test_dir = test_setup::create_root_dir("main_output_dir_is_file");
-    let test_proj_dir = test_setup::create_dir(root_test_dir, "proj");
-    fs::write(test_proj_dir.to_string() + "/deps", "")
-        .expect("couldn't write dummy target file");
-    let deps_file_conts = "deps\n";
-    fs::write(test_proj_dir.to_string() + "/dpnd.txt", &deps_file_conts)
-        .expect("couldn't write dependency file");
-    let mut cmd = test_setup::new_test_cmd(test_proj_dir);
+// Then the command fails with an error for each directive
+fn deps_file_group_dir_errors() {
+    let mut cmd = setup_test_with_deps_file(
+        "deps_file_group_dir_errors",
+        indoc!{"
+            deps
+            group=test
+            group=docs docs
+            group=docs other_docs
+
+            good git source master
+            group=build build
+        "},
+    );
 
     let cmd_result = cmd.assert();
 
     cmd_result
-        .code(1)
+        .code(3)
         .stdout("")
         .stderr(
-            "Couldn't read the state file ('deps/current_dpnd.txt'): Not a \
-             directory (os error 20)\n",
+            "dpnd.txt:2: Invalid group directive: 'group=test'; group \
+             directives must have the form 'group=name output_dir'\n\
+             dpnd.txt:4: A section named 'group=docs' is already defined on \
+             line 3\n\
+             dpnd.txt:7: Group directives must come before the dependencies \
+             and sections\n",
         );
 }
 
 #[test]
-// Given the output directory for a dependency is a file
+// Given the output directory of a section in the dependency file is within
+//     the output directory of the dependency file
 // When the command is run
 // Then the command fails with an error
-fn dep_output_dir_is_file() {
-    let root_test_dir = test_setup::create_root_dir("dep_output_dir_is_file");
-    let test_proj_dir = test_setup::create_dir(root_test_dir, "proj");
-    let test_proj_deps_dir =
-        test_setup::create_dir(test_proj_dir.clone(), "deps");
-    fs::write(test_proj_deps_dir + "/my_scripts", "")
-        .expect("couldn't write dummy target file");
-    let deps_file_conts = indoc!{"
-        deps
+fn section_output_dirs_overlap() {
+    let mut cmd = setup_test_with_deps_file(
+        "section_output_dirs_overlap",
+        indoc!{"
+            deps
 
-        my_scripts git git://localhost/my_scripts.git master
-    "};
-    fs::write(test_proj_dir.to_string() + "/dpnd.txt", &deps_file_conts)
-        .expect("couldn't write dependency file");
-    let mut cmd = test_setup::new_test_cmd(test_proj_dir);
+            [tools] deps/tools
+        "},
+    );
 
     let cmd_result = cmd.assert();
 
     cmd_result
-        .code(1)
+        .code(3)
         .stdout("")
         .stderr(
-            "Couldn't remove 'deps/my_scripts', the output directory for the \
-             'my_scripts' dependency: Not a directory (os error 20)\n",
+            "dpnd.txt:3: The output directory of the 'tools' section \
+             ('deps/tools') overlaps with the output directory of the \
+             dependency file ('deps'); each section must have a separate \
+             output directory\n",
         );
 }
 
 #[test]
-// Given the dependency file contains two dependencies with the same name
-// When the command is run
+// Given the dependency file defines a section
+// When the command is run with `--output-dir` naming the output directory of
+//     the section
 // Then the command fails with an error
-fn dup_dep_names() {
+fn output_dir_flag_overlaps_section() {
     let mut cmd = setup_test_with_deps_file(
-        "dup_dep_names",
+        "output_dir_flag_overlaps_section",
         indoc!{"
             deps
 
-            my_scripts git git://localhost/my_scripts.git master
-            my_scripts git git://localhost/my_scripts.git master
+            [tools] tools
         "},
     );
+    cmd.args(["--output-dir", "tools/deps"]);
 
     let cmd_result = cmd.assert();
 
@@ -314,24 +337,43 @@ fn dup_dep_names() {
         .code(1)
         .stdout("")
         .stderr(
-            "dpnd.txt:4: A dependency named 'my_scripts' is already defined \
-             on line 3\n",
+            "The output directory given by `--output-dir` ('tools/deps') \
+             overlaps with the output directory of the 'tools' section \
+             ('tools'); each section must have a separate output directory\n",
         );
 }
 
 #[test]
-// Given the dependency file contains a dependency with an invalid name
-// When the command is run
-// Then the command fails with an error
-fn invalid_dep_name() {
-    let mut cmd = setup_test_with_deps_file(
-        "invalid_dep_name",
-        indoc!{"
-            deps
+// Given a directory without a dependency file
+// When the command is run with `--stdin`, and an invalid dependency
+//     specification is written to `stdin`
+// Then the command fails with an error that refers to `stdin`
+fn stdin_spec_invalid() {
+    let root_test_dir = test_setup::create_root_dir("stdin_spec_invalid");
+    let mut cmd = test_setup::new_test_cmd(root_test_dir);
+    cmd.arg("--stdin");
+    cmd.write_stdin(indoc!{"
+        deps
 
-            my_scripts? git git://localhost/my_scripts.git master
-        "},
-    );
+        my_scripts
+    "});
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(3)
+        .stdout("")
+        .stderr("<stdin>:3: Invalid dependency specification: 'my_scripts'\n");
+}
+
+#[test]
+// Given a directory that doesn't exist
+// When the command is run with `-C` naming the directory
+// Then the command fails with an error
+fn cwd_flag_missing_dir() {
+    let root_test_dir = test_setup::create_root_dir("cwd_flag_missing_dir");
+    let mut cmd = test_setup::new_test_cmd(root_test_dir);
+    cmd.args(["-C", "missing"]);
 
     let cmd_result = cmd.assert();
 
@@ -339,24 +381,19 @@ fn invalid_dep_name() {
         .code(1)
         .stdout("")
         .stderr(
-            "dpnd.txt:3: 'my_scripts?' contains an invalid character ('?') at \
-             position 11; dependency names can only contain numbers, letters, \
-             hyphens, underscores and periods\n",
+            "Couldn't change the working directory to 'missing': No such \
+             file or directory (os error 2)\n",
         );
 }
 
 #[test]
-// Given the dependency file specifies an output directory that starts with a
-//     relative reference
-// When the command is run
+// Given a project
+// When the command is run with `--jobs 0`
 // Then the command fails with an error
-fn output_dir_starts_with_relative_ref() {
-    let mut cmd = setup_test_with_deps_file(
-        "output_dir_starts_with_relative_ref",
-        indoc!{"
-            ./deps
-        "},
-    );
+fn zero_jobs() {
+    let root_test_dir = test_setup::create_root_dir("zero_jobs");
+    let mut cmd = test_setup::new_test_cmd(root_test_dir);
+    cmd.args(["--jobs", "0"]);
 
     let cmd_result = cmd.assert();
 
@@ -364,79 +401,2197 @@ fn output_dir_starts_with_relative_ref() {
         .code(1)
         .stdout("")
         .stderr(
-            "dpnd.txt:1: This dependency file contains an invalid component \
-             ('.') in its output directory\n",
+            "`--jobs` isn't a valid number of jobs: number would be zero for \
+             non-zero type\n",
         );
 }
 
 #[test]
-// Given the dependency file specifies an output directory that starts with a
-//     backwards reference
-// When the command is run
+// Given the cache size limit in the project config file isn't a valid size
+// When `prune-cache` is run
 // Then the command fails with an error
-fn output_dir_starts_with_back_ref() {
+fn prune_cache_invalid_max_size() {
+    let root_test_dir =
+        test_setup::create_root_dir("prune_cache_invalid_max_size");
+    let config_dir = test_setup::create_dir(root_test_dir.clone(), ".dpnd");
+    fs::write(
+        format!("{}/config.toml", config_dir),
+        "[cache]\nmax_size = \"10 GB\"\n",
+    )
+        .expect("couldn't write config file");
+    let mut cmd = test_setup::new_test_subcmd(root_test_dir, "prune-cache");
+    cmd.env("DPND_STORE", "store");
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(1)
+        .stdout("")
+        .stderr(
+            ".dpnd/config.toml:2: The value of 'max_size' must be a quoted \
+             number of bytes, optionally followed by 'K', 'M', 'G' or 'T', \
+             not '\"10 GB\"'\n",
+        );
+}
+
+#[test]
+// Given the dependency file contains several invalid lines
+// When the command is run with `--color always`
+// Then the command fails with an error for each invalid line, where each line
+//     is labelled and its location is highlighted
+fn deps_file_errors_colored() {
     let mut cmd = setup_test_with_deps_file(
-        "output_dir_starts_with_back_ref",
-        indoc!{"
-            ../deps
-        "},
+        "deps_file_errors_colored",
+        indoc!{r#"
+            deps
+
+            proj tool source version extra
+            bad git "source master
+        "#},
     );
+    cmd.args(["--color", "always"]);
 
     let cmd_result = cmd.assert();
 
     cmd_result
-        .code(1)
+        .code(3)
         .stdout("")
         .stderr(
-            "dpnd.txt:1: This dependency file contains an invalid component \
-             ('..') in its output directory\n",
+            "\x1b[1;31merror\x1b[0m: \x1b[1mdpnd.txt:3\x1b[0m: Invalid \
+             dependency specification: 'proj tool source version extra'\n\
+             \x1b[1;31merror\x1b[0m: \x1b[1mdpnd.txt:4:9\x1b[0m: \
+             Unterminated quote\n",
         );
 }
 
 #[test]
-// Given the dependency file specifies an output directory with a relative
-//     reference
+// Given a workspace file that lists a project with an invalid dependency file,
+//     followed by a valid project
+// When the command is run with `--workspace`
+// Then the command fails with an error for the invalid project, after
+//     installing the dependencies of the valid project
+fn workspace_member_fails() {
+    let root_test_dir = test_setup::create_root_dir("workspace_member_fails");
+    fs::write(
+        format!("{}/dpnd_workspace.txt", root_test_dir),
+        "bad\ngood\n",
+    )
+        .expect("couldn't write workspace file");
+    let bad_dir = test_setup::create_dir(root_test_dir.clone(), "bad");
+    fs::write(format!("{}/dpnd.txt", bad_dir), "deps\n\nproj tool\n")
+        .expect("couldn't write dependency file");
+    let good_dir = test_setup::create_dir(root_test_dir.clone(), "good");
+    fs::write(format!("{}/dpnd.txt", good_dir), "deps\n")
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_cmd(root_test_dir);
+    cmd.arg("--workspace");
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(3)
+        .stdout("")
+        .stderr(
+            "bad: bad/dpnd.txt:3: Invalid dependency specification: 'proj \
+             tool'\n",
+        );
+    assert!(Path::new(&format!("{}/deps", good_dir)).exists());
+}
+
+#[test]
+// Given the dependency file contains an unknown tool
 // When the command is run
 // Then the command fails with an error
-fn output_dir_contains_relative_ref() {
+fn deps_file_invalid_tool() {
     let mut cmd = setup_test_with_deps_file(
-        "output_dir_contains_relative_ref",
+        "deps_file_invalid_tool",
         indoc!{"
-            target/./deps
+            deps
+
+            proj tool source version
         "},
     );
 
     let cmd_result = cmd.assert();
 
     cmd_result
-        .code(1)
+        .code(3)
         .stdout("")
-        .stderr(
-            "dpnd.txt:1: This dependency file contains an invalid component \
-             ('.') in its output directory\n",
-        );
+        .stderr(format!(
+            "dpnd.txt:3: The dependency 'proj' specifies an invalid tool name \
+             ('tool'); the built-in tools are {}, and no `dpnd-tool-tool` \
+             plugin was found in `PATH`\n",
+            BUILT_IN_TOOL_NAMES,
+        ));
 }
 
 #[test]
-// Given the dependency file specifies an output directory with a backwards
-//     reference
+// Given the dependency file contains a dependency with an unknown option
 // When the command is run
 // Then the command fails with an error
-fn output_dir_contains_back_ref() {
+fn deps_file_unknown_dep_option() {
     let mut cmd = setup_test_with_deps_file(
-        "output_dir_contains_back_ref",
+        "deps_file_unknown_dep_option",
         indoc!{"
-            target/../deps
+            deps
+
+            proj git source version owner=@build colour=red
         "},
     );
 
     let cmd_result = cmd.assert();
 
     cmd_result
-        .code(1)
+        .code(3)
         .stdout("")
         .stderr(
-            "dpnd.txt:1: This dependency file contains an invalid component \
-             ('..') in its output directory\n",
+            "dpnd.txt:3: The dependency 'proj' specifies an unknown option \
+             ('colour'); the supported options are 'env.<VAR>', 'group', \
+             'if', 'link', 'mirrors', 'owner', 'path', 'track' and \
+             'verify'\n",
         );
 }
+
+#[test]
+// Given the dependency file defines a dependency that hasn't been installed
+// When the `exec` command is run with the dependency
+// Then the command fails with an error
+fn exec_uninstalled_dep() {
+    let root_test_dir = test_setup::create_root_dir("exec_uninstalled_dep");
+    let test_proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    fs::write(
+        format!("{}/dpnd.txt", test_proj_dir),
+        indoc!{"
+            deps
+
+            proj git source version
+        "},
+    )
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_subcmd(test_proj_dir, "exec");
+    cmd.args(["proj", "--", "true"]);
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(1)
+        .stdout("")
+        .stderr(
+            "The 'proj' dependency isn't installed, run `dpnd install` to \
+             install it\n",
+        );
+}
+
+#[test]
+// Given the dependency file doesn't define a dependency named `unknown`
+// When the `owners` command is run with `unknown`
+// Then the command fails with an error
+fn owners_unknown_dep() {
+    let root_test_dir = test_setup::create_root_dir("owners_unknown_dep");
+    let test_proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    fs::write(
+        format!("{}/dpnd.txt", test_proj_dir),
+        indoc!{"
+            deps
+
+            proj git source version owner=@build
+        "},
+    )
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_subcmd(test_proj_dir, "owners");
+    cmd.arg("unknown");
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(3)
+        .stdout("")
+        .stderr(
+            "'unknown' isn't the name of a dependency, or a path in the \
+             output directory of a dependency\n",
+        );
+}
+
+#[test]
+// Given the dependency file doesn't define a dependency named `unknown`
+// When the command is run with `unknown`
+// Then the command fails with an error
+fn install_unknown_dep() {
+    let mut cmd = setup_test_with_deps_file(
+        "install_unknown_dep",
+        indoc!{"
+            deps
+
+            proj git source version
+        "},
+    );
+    cmd.arg("unknown");
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(3)
+        .stdout("")
+        .stderr("'unknown' isn't the name of a dependency in 'dpnd.txt'\n");
+}
+
+#[test]
+// Given the dependency file specifies signature verification for a dependency
+//     whose version isn't signed
+// When the command is run
+// Then the command fails with an error and the dependency isn't installed
+fn unsigned_dep_with_verify() {
+    let root_test_dir =
+        test_setup::create_root_dir("unsigned_dep_with_verify");
+    let dep_dir =
+        test_setup::create_dir(root_test_dir.clone(), "my_scripts.git");
+    let scratch_dir = test_setup::create_dir(root_test_dir.clone(), "scratch");
+    test_setup::create_bare_git_repo(
+        &dep_dir,
+        &scratch_dir,
+        &[
+            hashmap!{"script.sh" => "echo 'hello, world!'"},
+        ],
+    );
+    let test_proj_dir = test_setup::create_dir(root_test_dir.clone(), "proj");
+    fs::write(
+        test_proj_dir.to_string() + "/dpnd.txt",
+        indoc!{"
+            deps
+
+            my_scripts git git://localhost/my_scripts.git master verify=gpg
+        "},
+    )
+        .expect("couldn't write dependency file");
+    let cmd_result = test_setup::with_git_server(
+        root_test_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(test_proj_dir.clone());
+
+            cmd.assert()
+        },
+    );
+
+    cmd_result
+        .code(1)
+        .stdout("")
+        .stderr(indoc!{"
+            Couldn't verify the signature of the 'my_scripts' dependency: \
+             `git verify-commit --raw HEAD` failed with the following \
+             output:
+
+
+        "});
+    let dep_output_dir = test_proj_dir + "/deps/my_scripts";
+    assert!(!Path::new(&dep_output_dir).exists());
+}
+
+#[test]
+// Given the dependency file defines dependencies that haven't been installed
+// When the command is run with `--check-only`
+// Then the command fails with an error and no files are created
+fn check_only_with_missing_deps() {
+    let mut cmd = setup_test_with_deps_file(
+        "check_only_with_missing_deps",
+        indoc!{"
+            deps
+
+            your_scripts git git://localhost/your_scripts.git master
+            my_scripts git git://localhost/my_scripts.git master
+        "},
+    );
+    cmd.arg("--check-only");
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(5)
+        .stdout("")
+        .stderr(
+            "The following dependencies aren't up to date: 'my_scripts', \
+             'your_scripts'\n",
+        );
+    let deps_dir =
+        format!("{}/check_only_with_missing_deps/proj/deps", env!("TEST_DIR"));
+    assert!(!Path::new(&deps_dir).exists());
+}
+
+#[test]
+// Given the tool was just run, and then one dependency was upgraded, one was
+//     removed, one was added and the output directory of one was deleted
+// When the command is run with `--frozen` in CI
+// Then the command fails with an error that describes each change, and no
+//     files are changed or created
+fn frozen_with_changed_deps() {
+    let test_deps = success::test_deps();
+    let Layout{proj_dir, deps_commit_hashes, deps_file, ..} =
+        success::create_test_setup_and_run_tool(
+            "frozen_with_changed_deps",
+            &test_deps,
+            hashmap!{
+                "all_scripts" => 0,
+                "my_scripts" => 0,
+                "your_scripts" => 0,
+            },
+        );
+    let my_scripts_vsns = &deps_commit_hashes["my_scripts"];
+    fs::write(
+        &deps_file,
+        format!(
+            "deps\n\n\
+             all_scripts git git://localhost/all_scripts.git {}\n\
+             my_scripts git git://localhost/my_scripts.git {}\n\
+             their_scripts git git://localhost/their_scripts.git master\n",
+            deps_commit_hashes["all_scripts"][0],
+            my_scripts_vsns[1],
+        ),
+    )
+        .expect("couldn't write dependency file");
+    fs::remove_dir_all(format!("{}/deps/all_scripts", proj_dir))
+        .expect("couldn't remove dependency");
+    let state_file_path =
+        format!("{}/.dpnd/state/deps/current_dpnd.txt", proj_dir);
+    let state_conts = fs::read_to_string(&state_file_path)
+        .expect("couldn't read state file");
+    let summary_path = format!("{}/summary.md", proj_dir);
+    let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+    cmd.args(["--frozen", "--ci"]);
+    cmd.env("GITHUB_STEP_SUMMARY", &summary_path);
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(5)
+        .stdout("")
+        .stderr(formatdoc!{
+            "
+                `--frozen` was used, but the following changes would be made \
+                to the dependencies:
+                  reinstall 'all_scripts', because its output directory is \
+                missing or doesn't match the state file
+                  update 'my_scripts' from (git \
+                git://localhost/my_scripts.git {}) to (git \
+                git://localhost/my_scripts.git {})
+                  install 'their_scripts' (git \
+                git://localhost/their_scripts.git master)
+                  remove 'your_scripts'
+            ",
+            my_scripts_vsns[0],
+            my_scripts_vsns[1],
+        });
+    let new_state_conts = fs::read_to_string(&state_file_path)
+        .expect("couldn't read state file");
+    assert_eq!(new_state_conts, state_conts);
+    assert!(!Path::new(&format!("{}/deps/their_scripts", proj_dir)).exists());
+    assert!(!Path::new(&summary_path).exists());
+}
+
+#[test]
+// Given the tool was just run with an old version of a dependency in the
+//     depencency file and then the dependency was upgraded
+// When the `pin` command is run
+// Then the command fails with an error and the dependency file isn't changed
+fn pin_outdated_dep() {
+    let test_deps = success::test_deps();
+    let Layout{proj_dir, deps_commit_hashes, deps_file, ..} =
+        success::create_test_setup_and_run_tool(
+            "pin_outdated_dep",
+            &test_deps,
+            hashmap!{"my_scripts" => 0},
+        );
+    test_setup::write_test_deps_file(
+        &deps_file,
+        &deps_commit_hashes,
+        &hashmap!{"my_scripts" => 1},
+    );
+    let deps_file_conts =
+        fs::read_to_string(&deps_file).expect("couldn't read dependency file");
+    let mut cmd = test_setup::new_test_subcmd(proj_dir, "pin");
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(1)
+        .stdout("")
+        .stderr(
+            "The 'my_scripts' dependency has changed in 'dpnd.txt' since it \
+             was installed; run `dpnd install` before pinning it\n",
+        );
+    let new_deps_file_conts =
+        fs::read_to_string(&deps_file).expect("couldn't read dependency file");
+    assert_eq!(new_deps_file_conts, deps_file_conts);
+}
+
+#[test]
+// Given the tool was just run with a dependency whose new version can't be
+//     fetched
+// When the `upgrade` command is run with the dependency and the new version
+// Then the command fails with an error and the dependency file isn't changed
+fn upgrade_failed_restores_deps_file() {
+    let root_test_dir =
+        test_setup::create_root_dir("upgrade_failed_restores_deps_file");
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    let deps_file = format!("{}/dpnd.txt", proj_dir);
+    let deps_file_conts = indoc!{r#"
+        deps
+
+        tool custom "test {version} = 1.0.0" 1.0.0
+    "#};
+    fs::write(&deps_file, deps_file_conts)
+        .expect("couldn't write dependency file");
+    test_setup::new_test_cmd(proj_dir.clone()).assert().code(0);
+    let mut cmd = test_setup::new_test_subcmd(proj_dir.clone(), "upgrade");
+    cmd.args(["tool", "2.0.0"]);
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(4)
+        .stdout("")
+        .stderr(
+            "Couldn't retrieve the source for the dependency 'tool': `test \
+             '2.0.0' = 1.0.0` failed with the following output:\n\n\n",
+        );
+    let new_deps_file_conts =
+        fs::read_to_string(&deps_file).expect("couldn't read dependency file");
+    assert_eq!(new_deps_file_conts, deps_file_conts);
+}
+
+#[test]
+// Given the tool was just run with an old version of a dependency in the
+//     depencency file, the dependency was modified locally and then the
+//     dependency was upgraded
+// When the command is run
+// Then the command fails with an error and the dependency isn't changed
+fn upgrade_modified_dep() {
+    let test_deps = success::test_deps();
+    let Layout{dep_srcs_dir, proj_dir, deps_commit_hashes, deps_file, ..} =
+        success::create_test_setup_and_run_tool(
+            "upgrade_modified_dep",
+            &test_deps,
+            hashmap!{"my_scripts" => 0},
+        );
+    let script_path = format!("{}/deps/my_scripts/script.sh", proj_dir);
+    fs::write(&script_path, "local").expect("couldn't modify dependency");
+    test_setup::write_test_deps_file(
+        &deps_file,
+        &deps_commit_hashes,
+        &hashmap!{"my_scripts" => 1},
+    );
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+
+            cmd.assert()
+        },
+    );
+
+    cmd_result
+        .code(6)
+        .stdout("")
+        .stderr(
+            "The following dependencies have been modified locally, so they \
+             weren't changed (use `--force` to discard the local \
+             modifications): 'my_scripts'\n",
+        );
+    let script_conts =
+        fs::read_to_string(&script_path).expect("couldn't read dependency");
+    assert_eq!(script_conts, "local");
+}
+
+#[test]
+// Given the tool was just run with an old version of a dependency in the
+//     depencency file, the dependency was modified locally and then the
+//     dependency was upgraded
+// When the command is run with `--interactive` and `stdin` isn't a terminal
+// Then the command fails with the same error as it does without
+//     `--interactive`, and the dependency isn't changed
+fn upgrade_modified_dep_interactive_without_terminal() {
+    let test_deps = success::test_deps();
+    let Layout{dep_srcs_dir, proj_dir, deps_commit_hashes, deps_file, ..} =
+        success::create_test_setup_and_run_tool(
+            "upgrade_modified_dep_interactive_without_terminal",
+            &test_deps,
+            hashmap!{"my_scripts" => 0},
+        );
+    let script_path = format!("{}/deps/my_scripts/script.sh", proj_dir);
+    fs::write(&script_path, "local").expect("couldn't modify dependency");
+    test_setup::write_test_deps_file(
+        &deps_file,
+        &deps_commit_hashes,
+        &hashmap!{"my_scripts" => 1},
+    );
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+            cmd.arg("--interactive");
+
+            cmd.assert()
+        },
+    );
+
+    cmd_result
+        .code(6)
+        .stdout("")
+        .stderr(
+            "The following dependencies have been modified locally, so they \
+             weren't changed (use `--force` to discard the local \
+             modifications): 'my_scripts'\n",
+        );
+    let script_conts =
+        fs::read_to_string(&script_path).expect("couldn't read dependency");
+    assert_eq!(script_conts, "local");
+}
+
+#[test]
+// Given the dependency file specifies a Git dependency that is unavailable
+// When the command is run
+// Then the command fails with an error
+fn unavailable_git_proj_src() {
+    let mut cmd = setup_test_with_deps_file(
+        "unavailable_git_proj_src",
+        indoc!{"
+            deps
+
+            proj git git://localhost/my_scripts.git master
+        "},
+    );
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(4)
+        .stdout("")
+        .stderr(indoc!{"
+            Couldn't retrieve the source for the dependency 'proj': `git \
+             clone -- git://localhost/my_scripts.git .` failed with the \
+             following output:
+
+            [!] Cloning into '.'...
+            [!] fatal: unable to connect to localhost:
+            [!] localhost[0: 127.0.0.1]: errno=Connection refused
+            [!] localhost[1: ::1]: errno=Cannot assign requested address
+            [!] 
+
+        "});
+}
+
+#[cfg(feature = "docker")]
+#[test]
+// Given the dependency file specifies a `docker` dependency
+// When the command is run without `docker` in `PATH`
+// Then the command fails with an error
+fn docker_not_found() {
+    let mut cmd = setup_test_with_deps_file(
+        "docker_not_found",
+        indoc!{"
+            deps
+
+            assets docker alpine 3.12
+        "},
+    );
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(4)
+        .stdout("")
+        .stderr(
+            "Couldn't retrieve the source for the dependency 'assets': \
+             couldn't start `docker pull --quiet alpine:3.12`: No such file \
+             or directory (os error 2)\n",
+        );
+}
+
+#[test]
+// Given the dependency file specifies an `object-store` dependency whose
+//     source isn't the URL of an object
+// When the command is run
+// Then the command fails with an error
+fn object_store_dep_invalid_url() {
+    let mut cmd = setup_test_with_deps_file(
+        "object_store_dep_invalid_url",
+        indoc!{"
+            deps
+
+            artifact object-store https://example.com/app.tar.gz abc123
+        "},
+    );
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(4)
+        .stdout("")
+        .stderr(
+            "Couldn't retrieve the source for the dependency 'artifact': \
+             'https://example.com/app.tar.gz' isn't an `s3://` or `gs://` URL \
+             that names an object\n",
+        );
+}
+
+#[test]
+#[cfg(unix)]
+// Given the dependency file specifies a dependency that uses a plugin tool
+//     that fails to fetch the dependency
+// When the command is run
+// Then the command fails with an error that contains the output of the plugin
+fn plugin_fetch_fails() {
+    let mut cmd = setup_test_with_deps_file(
+        "plugin_fetch_fails",
+        indoc!{"
+            deps
+
+            proj bad_tool some_source v1
+        "},
+    );
+    let plugins_dir = test_setup::create_dir(
+        format!("{}/plugin_fetch_fails", env!("TEST_DIR")),
+        "plugins",
+    );
+    test_setup::create_plugin(
+        &plugins_dir,
+        "bad_tool",
+        indoc!{r#"
+            #!/bin/sh
+            echo "couldn't fetch '$2'" >&2
+            exit 1
+        "#},
+    );
+    cmd.env("PATH", plugins_dir);
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(4)
+        .stdout("")
+        .stderr(indoc!{"
+            Couldn't retrieve the source for the dependency 'proj': \
+             `dpnd-tool-bad_tool fetch some_source` failed with the \
+             following output:
+
+            [!] couldn't fetch 'some_source'
+
+        "});
+}
+
+#[test]
+#[cfg(unix)]
+// Given the dependency file specifies a dependency that uses a plugin tool
+//     that reports a metadata path outside of the output directory
+// When the command is run with `--vendor`
+// Then the command fails with an error, and the files outside of the output
+//     directory aren't removed
+fn plugin_metadata_path_outside_dep() {
+    let mut cmd = setup_test_with_deps_file(
+        "plugin_metadata_path_outside_dep",
+        indoc!{"
+            deps
+
+            proj escape some_source v1
+        "},
+    );
+    let root_test_dir =
+        format!("{}/plugin_metadata_path_outside_dep", env!("TEST_DIR"));
+    let plugins_dir = test_setup::create_dir(root_test_dir.clone(), "plugins");
+    test_setup::create_plugin(
+        &plugins_dir,
+        "escape",
+        indoc!{r#"
+            #!/bin/sh
+            case "$1" in
+                revision)
+                    echo '{"revision": "rev1", "time": 1000000000}'
+                    ;;
+                metadata-paths)
+                    echo '["../.."]'
+                    ;;
+            esac
+        "#},
+    );
+    cmd.env("PATH", format!("{}:/bin:/usr/bin", plugins_dir));
+    cmd.arg("--vendor");
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(1)
+        .stdout("")
+        .stderr(
+            "The 'escape' tool reported a metadata path ('deps/proj/../..') \
+             that isn't inside the output directory of the 'proj' \
+             dependency, so it wasn't removed\n",
+        );
+    let deps_file = format!("{}/proj/dpnd.txt", root_test_dir);
+    assert!(Path::new(&deps_file).exists());
+}
+
+#[test]
+#[cfg(unix)]
+// Given the dependency file specifies a dependency that uses a plugin tool
+//     whose fetch doesn't finish
+// When the command is sent `SIGTERM` during the fetch
+// Then the command exits with the interrupted exit code, and the partly
+//     fetched dependency is removed and isn't recorded in the state file
+fn interrupted_fetch_cleaned_up() {
+    let root_test_dir =
+        test_setup::create_root_dir("interrupted_fetch_cleaned_up");
+    let plugins_dir = test_setup::create_dir(root_test_dir.clone(), "plugins");
+    test_setup::create_plugin(
+        &plugins_dir,
+        "slow",
+        indoc!{"
+            #!/bin/sh
+            touch partial.txt
+            exec sleep 30
+        "},
+    );
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        "deps\n\nproj slow some_source v1\n",
+    )
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_process(proj_dir.clone(), "install");
+    cmd.env("PATH", format!("{}:/bin:/usr/bin", plugins_dir))
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let child = cmd.spawn()
+        .expect("couldn't start command");
+    let partial_file = format!("{}/deps/proj/partial.txt", proj_dir);
+    for _ in 0..100 {
+        if Path::new(&partial_file).exists() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    test_setup::run_cmd(&proj_dir, "kill", ["-TERM", &child.id().to_string()]);
+
+    let output = child.wait_with_output()
+        .expect("couldn't wait for command");
+    assert_eq!(output.status.code(), Some(130));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Interrupted; partly fetched dependencies were removed, and other \
+         dependencies were left as they were\n",
+    );
+    assert!(!Path::new(&format!("{}/deps/proj", proj_dir)).exists());
+    let state_conts = fs::read_to_string(
+        format!("{}/.dpnd/state/deps/current_dpnd.txt", proj_dir),
+    )
+        .expect("couldn't read state file");
+    assert!(
+        state_conts.lines().all(|l| l.starts_with('#')),
+        "unexpected state file:\n{}",
+        state_conts,
+    );
+}
+
+#[test]
+#[cfg(unix)]
+// Given the dependency file specifies a dependency that uses a plugin tool
+//     that doesn't finish fetching the dependency
+// When the command is run with `--fetch-timeout`
+// Then the command fails with an error after the timeout
+fn plugin_fetch_times_out() {
+    let mut cmd = setup_test_with_deps_file(
+        "plugin_fetch_times_out",
+        indoc!{"
+            deps
+
+            proj slow_tool some_source v1
+        "},
+    );
+    let plugins_dir = test_setup::create_dir(
+        format!("{}/plugin_fetch_times_out", env!("TEST_DIR")),
+        "plugins",
+    );
+    test_setup::create_plugin(
+        &plugins_dir,
+        "slow_tool",
+        indoc!{"
+            #!/bin/sh
+            exec /bin/sleep 60
+        "},
+    );
+    cmd.env("PATH", plugins_dir);
+    cmd.args(["--fetch-timeout", "1"]);
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(4)
+        .stdout("")
+        .stderr("Fetching the 'proj' dependency timed out after 1s\n");
+}
+
+#[test]
+// Given the project config file contains an unknown setting
+// When the command is run
+// Then the command fails with an error
+fn config_file_unknown_setting() {
+    let mut cmd = setup_test_with_deps_file(
+        "config_file_unknown_setting",
+        indoc!{"
+            deps
+
+            proj git git://localhost/my_scripts.git master
+        "},
+    );
+    let config_dir = test_setup::create_dir(
+        format!("{}/config_file_unknown_setting/proj", env!("TEST_DIR")),
+        ".dpnd",
+    );
+    fs::write(
+        format!("{}/config.toml", config_dir),
+        indoc!{"
+            [install]
+            shallow = true
+        "},
+    )
+        .expect("couldn't write config file");
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(1)
+        .stdout("")
+        .stderr(
+            ".dpnd/config.toml:2: Unknown setting ('shallow'); the supported \
+             settings in the 'install' section are 'conflicts', \
+             'deterministic_mtimes', 'fetch_timeout', 'jobs', 'keep_going', \
+             'recursive' and 'vendor'\n",
+        );
+}
+
+#[test]
+// Given the project config file sets `jobs` to 0
+// When the command is run
+// Then the command fails with an error
+fn config_file_zero_jobs() {
+    let cmd_result = check_config_file_error(
+        "config_file_zero_jobs",
+        indoc!{"
+            [install]
+            jobs = 0
+        "},
+    );
+
+    cmd_result
+        .code(1)
+        .stdout("")
+        .stderr(
+            ".dpnd/config.toml:2: The value of 'jobs' must be greater than \
+             0\n",
+        );
+}
+
+#[test]
+// Given the project config file sets the store directory to a relative path
+// When the command is run
+// Then the command fails with an error
+fn config_file_relative_store_dir() {
+    let cmd_result = check_config_file_error(
+        "config_file_relative_store_dir",
+        indoc!{r#"
+            [cache]
+            dir = "store"
+        "#},
+    );
+
+    cmd_result
+        .code(1)
+        .stdout("")
+        .stderr(
+            ".dpnd/config.toml:2: The value of 'dir' must be a quoted \
+             absolute path, not '\"store\"'\n",
+        );
+}
+
+// `check_config_file_error` runs `dpnd install` in a project whose project
+// config file contains `config_conts`.
+fn check_config_file_error(root_test_dir_name: &str, config_conts: &str)
+    -> Assert
+{
+    let root_test_dir = test_setup::create_root_dir(root_test_dir_name);
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        "deps\n\ntool custom \"true\" 1.0.0\n",
+    )
+        .expect("couldn't write dependency file");
+    let config_dir = test_setup::create_dir(proj_dir.clone(), ".dpnd");
+    fs::write(format!("{}/config.toml", config_dir), config_conts)
+        .expect("couldn't write config file");
+    let mut cmd = test_setup::new_test_cmd(proj_dir);
+
+    cmd.assert()
+}
+
+#[test]
+// Given the dependency file specifies a `git-file` dependency without the
+//     `path` option
+// When the command is run
+// Then the command fails with an error
+fn git_file_dep_without_path() {
+    let mut cmd = setup_test_with_deps_file(
+        "git_file_dep_without_path",
+        indoc!{"
+            deps
+
+            proj git-file git://localhost/my_scripts.git master
+        "},
+    );
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(3)
+        .stdout("")
+        .stderr(
+            "dpnd.txt:3: The dependency 'proj' uses the 'git-file' tool, \
+             which requires the 'path' option\n",
+        );
+}
+
+#[test]
+// Given the dependency file specifies a `file` dependency whose version
+//     doesn't match the checksum of the file
+// When the command is run
+// Then the command fails with an error and the file isn't installed
+fn file_dep_checksum_mismatch() {
+    let root_test_dir =
+        test_setup::create_root_dir("file_dep_checksum_mismatch");
+    let files_dir = test_setup::create_dir(root_test_dir.clone(), "files");
+    fs::write(format!("{}/script.sh", files_dir), "echo hello\n")
+        .expect("couldn't write test file");
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        format!("deps\n\nscript file file://{}/script.sh abc123\n", files_dir),
+    )
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+
+    let checksum =
+        "5dbad7dd0b9b122dcd9956884390f4aac4738caba8ff53498a7ab6718b176c30";
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(4)
+        .stdout("")
+        .stderr(format!(
+            "Couldn't change the version for the 'script' dependency: the \
+             SHA-256 checksum of 'script.sh' is '{}', but the version of the \
+             dependency is 'abc123'\n",
+            checksum,
+        ));
+    assert!(
+        !Path::new(&format!("{}/deps/script/script.sh", proj_dir)).exists(),
+        "the downloaded file wasn't removed",
+    );
+}
+
+#[test]
+// Given the dependency file specifies a `file` dependency that was installed,
+//     and then another file was added to its output directory
+// When the command is run with `--annotate`
+// Then the command fails with an error, because the downloaded file can't be
+//     identified
+fn file_dep_with_extra_file_not_annotated() {
+    let root_test_dir =
+        test_setup::create_root_dir("file_dep_with_extra_file_not_annotated");
+    let files_dir = test_setup::create_dir(root_test_dir.clone(), "files");
+    fs::write(format!("{}/script.sh", files_dir), "echo hello\n")
+        .expect("couldn't write test file");
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        format!(
+            "deps\n\nscript file file://{}/script.sh {}\n",
+            files_dir,
+            "5dbad7dd0b9b122dcd9956884390f4aac4738caba8ff53498a7ab6718b176c30",
+        ),
+    )
+        .expect("couldn't write dependency file");
+    test_setup::new_test_cmd(proj_dir.clone()).assert().code(0);
+    fs::write(format!("{}/deps/script/extra.txt", proj_dir), "extra")
+        .expect("couldn't write extra file");
+    let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+    cmd.arg("--annotate");
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(1)
+        .stdout("")
+        .stderr(format!(
+            "Couldn't get the installed revision of the 'script' dependency \
+             to annotate the dependency file: '{}/deps/script' contains more \
+             than one file\n",
+            proj_dir,
+        ));
+}
+
+#[test]
+// Given the dependency file contains a `file` dependency whose source starts
+//     with `-`
+// When the command is run with `--no-validate-sources`
+// Then the command fails with an error, and the source isn't passed to `curl`
+fn option_like_file_source_not_validated() {
+    let mut cmd = setup_test_with_deps_file(
+        "option_like_file_source_not_validated",
+        "deps\n\nscript file --config=curlrc/script.sh abc123\n",
+    );
+    cmd.arg("--no-validate-sources");
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(4)
+        .stdout("")
+        .stderr(
+            "Couldn't retrieve the source for the dependency 'script': the \
+             source '--config=curlrc/script.sh' starts with '-', so curl \
+             would read it as an option\n",
+        );
+}
+
+#[test]
+// Given a Git token is defined and the dependency file specifies an HTTPS Git
+//     dependency that is unavailable
+// When the command is run
+// Then the command fails with an error that doesn't contain the token
+fn unavailable_https_git_proj_src_with_token() {
+    let mut cmd = setup_test_with_deps_file(
+        "unavailable_https_git_proj_src_with_token",
+        indoc!{"
+            deps
+
+            proj git https://localhost:1/my_scripts.git master
+        "},
+    );
+    cmd.env("DPND_GIT_TOKEN", "secret_token");
+
+    let cmd_result = cmd.assert();
+
+    let cmd_result = cmd_result.code(4).stdout("");
+    let stderr = String::from_utf8(cmd_result.get_output().stderr.clone())
+        .expect("couldn't convert STDERR to a `String`");
+    assert!(
+        stderr.starts_with(
+            "Couldn't retrieve the source for the dependency 'proj': `git \
+             clone -- https://localhost:1/my_scripts.git .` failed with the \
+             following output:",
+        ),
+        "unexpected STDERR:\n{}",
+        stderr,
+    );
+    assert!(
+        !stderr.contains("secret_token"),
+        "STDERR contained the Git token:\n{}",
+        stderr,
+    );
+}
+
+#[test]
+// Given the Git token is defined but empty, and the dependency file specifies
+//     an HTTPS Git dependency that is unavailable
+// When the command is run
+// Then the command fails with an error that isn't redacted
+fn unavailable_https_git_proj_src_with_empty_token() {
+    let mut cmd = setup_test_with_deps_file(
+        "unavailable_https_git_proj_src_with_empty_token",
+        indoc!{"
+            deps
+
+            proj git https://localhost:1/my_scripts.git master
+        "},
+    );
+    cmd.env("DPND_GIT_TOKEN", "");
+
+    let cmd_result = cmd.assert();
+
+    let cmd_result = cmd_result.code(4).stdout("");
+    let stderr = String::from_utf8(cmd_result.get_output().stderr.clone())
+        .expect("couldn't convert STDERR to a `String`");
+    assert!(
+        stderr.contains("fatal: unable to access"),
+        "unexpected STDERR:\n{}",
+        stderr,
+    );
+    assert!(
+        !stderr.contains("[REDACTED]"),
+        "STDERR was redacted:\n{}",
+        stderr,
+    );
+}
+
+#[test]
+// Given a dependency was installed and its output directory was then removed
+// When `doctor` is run
+// Then the command fails and reports that the dependency is missing
+fn doctor_finds_missing_dep() {
+    let test_deps = success::test_deps();
+    let Layout{dep_srcs_dir, proj_dir, ..} =
+        test_setup::create(
+            "doctor_finds_missing_dep",
+            &test_deps,
+            &hashmap!{"my_scripts" => 1},
+        );
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+            cmd.assert()
+                .code(0)
+                .stdout("")
+                .stderr("");
+            fs::remove_dir_all(format!("{}/deps/my_scripts", proj_dir))
+                .expect("couldn't remove dependency");
+
+            let mut cmd = test_setup::new_test_subcmd(
+                proj_dir.clone(),
+                "doctor",
+            );
+
+            cmd.assert()
+        },
+    );
+
+    let output = cmd_result.code(1).stderr("").get_output().stdout.clone();
+    let stdout = String::from_utf8(output)
+        .expect("couldn't convert STDOUT to a `String`");
+    assert!(
+        stdout.ends_with(
+            "problem: '.dpnd/state/deps/current_dpnd.txt' records that \
+             'my_scripts' is installed, but 'deps/my_scripts' doesn't exist; \
+             run `dpnd install` to install it again, or `dpnd repair` to \
+             remove it from the state file\n",
+        ),
+        "unexpected STDOUT:\n{}",
+        stdout,
+    );
+}
+
+#[test]
+// Given the dependency file specifies a Git version that is unavailable
+// When the command is run
+// Then the command fails with the output of the Git command
+fn unavailable_git_proj_vsn() {
+    let root_test_dir =
+        test_setup::create_root_dir("unavailable_git_proj_vsn");
+    let dep_dir =
+        test_setup::create_dir(root_test_dir.clone(), "my_scripts.git");
+    let scratch_dir = test_setup::create_dir(root_test_dir.clone(), "scratch");
+    test_setup::create_bare_git_repo(
+        &dep_dir,
+        &scratch_dir,
+        &[
+            hashmap!{"script.sh" => "echo 'hello, world!'"},
+        ],
+    );
+    let test_proj_dir = test_setup::create_dir(root_test_dir.clone(), "proj");
+    let deps_file_conts = indoc!{"
+        deps
+
+        my_scripts git git://localhost/my_scripts.git bad_commit
+    "};
+    let cmd_result = test_setup::with_git_server(
+        root_test_dir,
+        || {
+            fs::write(
+                test_proj_dir.to_string() + "/dpnd.txt",
+                &deps_file_conts,
+            )
+                .expect("couldn't write dependency file");
+            let mut cmd = test_setup::new_test_cmd(test_proj_dir.clone());
+
+            cmd.assert()
+        },
+    );
+
+    cmd_result
+        .code(4)
+        .stdout("")
+        .stderr(indoc!{"
+            Couldn't change the version for the 'my_scripts' dependency: `git \
+             checkout bad_commit` failed with the following output:
+
+            [!] error: pathspec 'bad_commit' did not match any file(s) known \
+             to git
+
+        "});
+}
+
+#[test]
+// Given the main output directory is a file
+// When the command is run
+// Then the command fails with an error
+fn main_output_dir_is_file() {
+    let root_test_dir = test_setup::create_root_dir("main_output_dir_is_file");
+    let test_proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    fs::write(test_proj_dir.to_string() + "/deps", "")
+        .expect("couldn't write dummy target file");
+    let deps_file_conts = "deps\n";
+    fs::write(test_proj_dir.to_string() + "/dpnd.txt", &deps_file_conts)
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_cmd(test_proj_dir);
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(1)
+        .stdout("")
+        .stderr(
+            "Couldn't create deps, the main output directory: File exists \
+             (os error 17)\n",
+        );
+}
+
+#[test]
+// Given the output directory for a dependency is a file
+// When the command is run
+// Then the command fails with an error
+fn dep_output_dir_is_file() {
+    let root_test_dir = test_setup::create_root_dir("dep_output_dir_is_file");
+    let test_proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    let test_proj_deps_dir =
+        test_setup::create_dir(test_proj_dir.clone(), "deps");
+    fs::write(test_proj_deps_dir + "/my_scripts", "")
+        .expect("couldn't write dummy target file");
+    let deps_file_conts = indoc!{"
+        deps
+
+        my_scripts git git://localhost/my_scripts.git master
+    "};
+    fs::write(test_proj_dir.to_string() + "/dpnd.txt", &deps_file_conts)
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_cmd(test_proj_dir);
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(1)
+        .stdout("")
+        .stderr(
+            "Couldn't remove 'deps/my_scripts', the output directory for the \
+             'my_scripts' dependency: Not a directory (os error 20)\n",
+        );
+}
+
+#[test]
+// Given the dependency file contains two dependencies with the same name
+// When the command is run
+// Then the command fails with an error
+fn dup_dep_names() {
+    let mut cmd = setup_test_with_deps_file(
+        "dup_dep_names",
+        indoc!{"
+            deps
+
+            my_scripts git git://localhost/my_scripts.git master
+            my_scripts git git://localhost/my_scripts.git master
+        "},
+    );
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(3)
+        .stdout("")
+        .stderr(
+            "dpnd.txt:4: A dependency named 'my_scripts' is already defined \
+             on line 3\n",
+        );
+}
+
+#[test]
+// Given the dependency file includes a file that defines a dependency with
+//     the same name as a dependency in the dependency file
+// When the command is run
+// Then the command fails with an error that cites both definitions
+fn dup_dep_names_in_included_file() {
+    let root_test_dir =
+        test_setup::create_root_dir("dup_dep_names_in_included_file");
+    let test_proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    let deps_file_conts = indoc!{"
+        deps
+
+        my_scripts git git://localhost/my_scripts.git master
+        include more_deps.txt
+    "};
+    fs::write(format!("{}/dpnd.txt", test_proj_dir), deps_file_conts)
+        .expect("couldn't write dependency file");
+    let included_file_conts = indoc!{"
+        your_scripts git git://localhost/your_scripts.git master
+        my_scripts git git://localhost/my_scripts.git master
+    "};
+    fs::write(
+        format!("{}/more_deps.txt", test_proj_dir),
+        included_file_conts,
+    )
+        .expect("couldn't write included file");
+    let mut cmd = test_setup::new_test_cmd(test_proj_dir);
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(3)
+        .stdout("")
+        .stderr(
+            "more_deps.txt:2: A dependency named 'my_scripts' is already \
+             defined at dpnd.txt:3\n",
+        );
+}
+
+#[test]
+// Given the dependency file includes itself
+// When the command is run
+// Then the command fails with an error
+fn deps_file_includes_itself() {
+    let mut cmd = setup_test_with_deps_file(
+        "deps_file_includes_itself",
+        indoc!{"
+            deps
+
+            include dpnd.txt
+        "},
+    );
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(3)
+        .stdout("")
+        .stderr(
+            "dpnd.txt:3: The included file ('dpnd.txt') is already being \
+             included; files can't include themselves, directly or \
+             indirectly\n",
+        );
+}
+
+#[test]
+// Given the dependency file contains Git dependencies whose sources have an
+//     unknown URL scheme, a mistyped URL scheme, no host and no repository
+//     path
+// When the command is run
+// Then the command fails with an error for each source that cites the
+//     column of the problem
+fn invalid_git_sources() {
+    let mut cmd = setup_test_with_deps_file(
+        "invalid_git_sources",
+        indoc!{"
+            deps
+
+            a git htps://github.com/eZanmoto/dpnd.git master
+            b git https//github.com/eZanmoto/dpnd.git master
+            c   git   ssh://git@/dpnd.git master
+            d git git@github.com: master
+        "},
+    );
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(3)
+        .stdout("")
+        .stderr(
+            "dpnd.txt:3:7: The source of 'a' isn't a valid 'git' source: \
+             'htps' isn't a URL scheme that Git supports (use \
+             `--no-validate-sources` to skip this check)\n\
+             dpnd.txt:4:12: The source of 'b' isn't a valid 'git' source: \
+             'https' must be followed by '://' (use `--no-validate-sources` \
+             to skip this check)\n\
+             dpnd.txt:5:21: The source of 'c' isn't a valid 'git' source: \
+             the URL doesn't have a host (use `--no-validate-sources` to \
+             skip this check)\n\
+             dpnd.txt:6:22: The source of 'd' isn't a valid 'git' source: \
+             the address doesn't have a repository path (use \
+             `--no-validate-sources` to skip this check)\n",
+        );
+}
+
+#[test]
+// Given `SOURCE_DATE_EPOCH` isn't a valid number of seconds
+// When the command is run with `--deterministic-mtimes`
+// Then the command fails with an error
+fn invalid_source_date_epoch_with_deterministic_mtimes() {
+    let mut cmd = setup_test_with_deps_file(
+        "invalid_source_date_epoch_with_deterministic_mtimes",
+        indoc!{"
+            deps
+
+            proj git git://localhost/my_scripts.git master
+        "},
+    );
+    cmd.arg("--deterministic-mtimes");
+    cmd.env("SOURCE_DATE_EPOCH", "yesterday");
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(1)
+        .stdout("")
+        .stderr(
+            "`SOURCE_DATE_EPOCH` isn't a valid number of seconds: invalid \
+             digit found in string\n",
+        );
+}
+
+#[test]
+// Given the dependency file contains Git dependencies with an invalid mirror,
+//     an empty mirror and a `fetched_from` option
+// When the command is run
+// Then the command fails with an error for each dependency
+fn invalid_mirrors_and_state_only_option() {
+    let mut cmd = setup_test_with_deps_file(
+        "invalid_mirrors_and_state_only_option",
+        indoc!{"
+            deps
+
+            a git https://example.com/a.git master mirrors=--upload-pack=x
+            b git https://example.com/b.git master mirrors=https://a.com/b,
+            c git https://example.com/c.git master fetched_from=https://a.com/c
+        "},
+    );
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(3)
+        .stdout("")
+        .stderr(
+            "dpnd.txt:3: The mirror '--upload-pack=x' of 'a' isn't a valid \
+             'git' source: it starts with '-', like a Git option (use \
+             `--no-validate-sources` to skip this check)\n\
+             dpnd.txt:4: Invalid dependency specification: 'b git \
+             https://example.com/b.git master mirrors=https://a.com/b,'\n\
+             dpnd.txt:5: The dependency 'c' specifies the 'fetched_from' \
+             option, which `dpnd` records in state files and which can't be \
+             used in dependency files\n",
+        );
+}
+
+#[test]
+// Given the dependency file contains a Git dependency whose source starts with
+//     `-`
+// When the command is run
+// Then the command fails with an error, and the source isn't passed to Git
+fn option_like_git_source() {
+    let marker_path = option_like_git_source_marker("option_like_git_source");
+    let mut cmd = setup_test_with_deps_file(
+        "option_like_git_source",
+        format!(
+            "deps\n\nproj git --upload-pack=touch${{IFS}}{} master\n",
+            marker_path,
+        ),
+    );
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(3)
+        .stdout("")
+        .stderr(
+            "dpnd.txt:3:10: The source of 'proj' isn't a valid 'git' \
+             source: it starts with '-', like a Git option (use \
+             `--no-validate-sources` to skip this check)\n",
+        );
+    assert!(!Path::new(&marker_path).exists());
+}
+
+#[test]
+// Given the dependency file contains a Git dependency whose source starts with
+//     `-`
+// When the command is run with `--no-validate-sources`
+// Then the command fails with an error, and the source isn't passed to Git
+fn option_like_git_source_not_validated() {
+    let marker_path = option_like_git_source_marker(
+        "option_like_git_source_not_validated",
+    );
+    let src = format!("--upload-pack=touch${{IFS}}{}", marker_path);
+    let mut cmd = setup_test_with_deps_file(
+        "option_like_git_source_not_validated",
+        format!("deps\n\nproj git {} master\n", src),
+    );
+    cmd.arg("--no-validate-sources");
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(4)
+        .stdout("")
+        .stderr(format!(
+            "Couldn't retrieve the source for the dependency 'proj': the \
+             source '{}' starts with '-', so Git would read it as an option\n",
+            src,
+        ));
+    assert!(!Path::new(&marker_path).exists());
+}
+
+#[test]
+#[cfg(unix)]
+// Given the dependency file specifies an `svn` dependency that was installed,
+//     the dependency was modified locally and then the dependency was
+//     upgraded
+// When the command is run
+// Then the command fails with an error and the dependency isn't changed
+fn modified_svn_dep_not_upgraded() {
+    let layout =
+        success::create_svn_test_setup("modified_svn_dep_not_upgraded");
+    success::new_svn_test_cmd(&layout).assert().code(0);
+    let dep_dir = format!("{}/deps/lib", layout.proj_dir);
+    fs::write(format!("{}/.svn/status", dep_dir), "M       lib.txt\n")
+        .expect("couldn't write status file");
+    fs::write(
+        format!("{}/dpnd.txt", layout.proj_dir),
+        format!("deps\n\nlib svn {} 2\n", layout.repo_url),
+    )
+        .expect("couldn't write dependency file");
+    let mut cmd = success::new_svn_test_cmd(&layout);
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(6)
+        .stdout("")
+        .stderr(
+            "The following dependencies have been modified locally, so they \
+             weren't changed (use `--force` to discard the local \
+             modifications): 'lib'\n",
+        );
+    let conts = fs::read_to_string(format!("{}/lib.txt", dep_dir))
+        .expect("couldn't read dependency file");
+    assert_eq!(conts, "r1");
+}
+
+#[test]
+#[cfg(unix)]
+// Given the dependency file specifies an `svn` dependency whose repository
+//     doesn't exist
+// When the command is run
+// Then the command fails with an error that contains the output of `svn`
+fn svn_dep_unavailable_src() {
+    let layout = success::create_svn_test_setup("svn_dep_unavailable_src");
+    fs::write(
+        format!("{}/dpnd.txt", layout.proj_dir),
+        "deps\n\nlib svn file:///no_repo 1\n",
+    )
+        .expect("couldn't write dependency file");
+    let mut cmd = success::new_svn_test_cmd(&layout);
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(4)
+        .stdout("")
+        .stderr(
+            "Couldn't retrieve the source for the dependency 'lib': `svn \
+             checkout --depth empty -- file:///no_repo .` failed with the \
+             following output:\n\
+             \n\
+             [!] svn: E170000: URL 'file:///no_repo' doesn't exist\n\n",
+        );
+}
+
+#[test]
+#[cfg(unix)]
+// Given the dependency file specifies an `svn` dependency at a revision that
+//     doesn't exist
+// When the command is run
+// Then the command fails with an error that contains the output of `svn`
+fn svn_dep_unavailable_revision() {
+    let layout =
+        success::create_svn_test_setup("svn_dep_unavailable_revision");
+    fs::write(
+        format!("{}/dpnd.txt", layout.proj_dir),
+        format!("deps\n\nlib svn {} 3\n", layout.repo_url),
+    )
+        .expect("couldn't write dependency file");
+    let mut cmd = success::new_svn_test_cmd(&layout);
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(4)
+        .stdout("")
+        .stderr(
+            "Couldn't change the version for the 'lib' dependency: `svn \
+             update --set-depth infinity --revision 3` failed with the \
+             following output:\n\
+             \n\
+             [!] svn: E160006: No such revision 3\n\n",
+        );
+}
+
+#[test]
+#[cfg(unix)]
+// Given the dependency file specifies an `svn` dependency, and `svn` isn't
+//     installed
+// When the command is run
+// Then the command fails with an error
+fn svn_not_installed() {
+    let layout = success::create_svn_test_setup("svn_not_installed");
+    let mut cmd = test_setup::new_test_cmd(layout.proj_dir.clone());
+    cmd.env("PATH", "/no_bin");
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(4)
+        .stdout("")
+        .stderr(format!(
+            "Couldn't retrieve the source for the dependency 'lib': couldn't \
+             start `svn checkout --depth empty -- {} .`: No such file or \
+             directory (os error 2)\n",
+            layout.repo_url,
+        ));
+}
+
+#[test]
+#[cfg(unix)]
+// Given the dependency file contains an `svn` dependency whose source starts
+//     with `-`
+// When the command is run
+// Then the command fails with an error, and the source isn't passed to
+//     Subversion
+fn option_like_svn_source() {
+    let layout = success::create_svn_test_setup("option_like_svn_source");
+    fs::write(
+        format!("{}/dpnd.txt", layout.proj_dir),
+        "deps\n\nlib svn --config-dir=config 1\n",
+    )
+        .expect("couldn't write dependency file");
+    let mut cmd = success::new_svn_test_cmd(&layout);
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(4)
+        .stdout("")
+        .stderr(
+            "Couldn't retrieve the source for the dependency 'lib': the \
+             source '--config-dir=config' starts with '-', so Subversion \
+             would read it as an option\n",
+        );
+    let working_copy = format!("{}/deps/lib/.svn", layout.proj_dir);
+    assert!(!Path::new(&working_copy).exists());
+}
+
+// `option_like_git_source_marker` returns the path of a file that Git would
+// create if the source used by `root_test_dir_name` were passed to it as an
+// option.
+fn option_like_git_source_marker(root_test_dir_name: &str) -> String {
+    format!("{}/{}/pwned", env!("TEST_DIR"), root_test_dir_name)
+}
+
+#[test]
+// Given the dependency file contains a dependency with an invalid name
+// When the command is run
+// Then the command fails with an error
+fn invalid_dep_name() {
+    let mut cmd = setup_test_with_deps_file(
+        "invalid_dep_name",
+        indoc!{"
+            deps
+
+            my_scripts? git git://localhost/my_scripts.git master
+        "},
+    );
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(3)
+        .stdout("")
+        .stderr(
+            "dpnd.txt:3: 'my_scripts?' contains an invalid character ('?') at \
+             position 11; dependency names can only contain ASCII numbers, \
+             letters, hyphens, underscores and periods\n",
+        );
+}
+
+#[test]
+// Given the dependency file contains a dependency name with a non-ASCII
+//     letter
+// When the command is run
+// Then the command fails with an error that gives the position of the letter
+//     in characters
+fn non_ascii_dep_name() {
+    let mut cmd = setup_test_with_deps_file(
+        "non_ascii_dep_name",
+        indoc!{"
+            deps
+
+            café_scripts git git://localhost/my_scripts.git master
+        "},
+    );
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(3)
+        .stdout("")
+        .stderr(
+            "dpnd.txt:3: 'café_scripts' contains an invalid character ('é') \
+             at position 4; dependency names can only contain ASCII numbers, \
+             letters, hyphens, underscores and periods\n",
+        );
+}
+
+#[test]
+// Given the dependency file specifies an output directory that starts with a
+//     relative reference
+// When the command is run
+// Then the command fails with an error
+fn output_dir_starts_with_relative_ref() {
+    let mut cmd = setup_test_with_deps_file(
+        "output_dir_starts_with_relative_ref",
+        indoc!{"
+            ./deps
+        "},
+    );
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(3)
+        .stdout("")
+        .stderr(
+            "dpnd.txt:1: This dependency file contains an invalid component \
+             ('.') in its output directory\n",
+        );
+}
+
+#[test]
+// Given the dependency file specifies an output directory that starts with a
+//     backwards reference
+// When the command is run
+// Then the command fails with an error
+fn output_dir_starts_with_back_ref() {
+    let mut cmd = setup_test_with_deps_file(
+        "output_dir_starts_with_back_ref",
+        indoc!{"
+            ../deps
+        "},
+    );
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(3)
+        .stdout("")
+        .stderr(
+            "dpnd.txt:1: This dependency file contains an invalid component \
+             ('..') in its output directory\n",
+        );
+}
+
+#[test]
+// Given the dependency file specifies an output directory with a relative
+//     reference
+// When the command is run
+// Then the command fails with an error
+fn output_dir_contains_relative_ref() {
+    let mut cmd = setup_test_with_deps_file(
+        "output_dir_contains_relative_ref",
+        indoc!{"
+            target/./deps
+        "},
+    );
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(3)
+        .stdout("")
+        .stderr(
+            "dpnd.txt:1: This dependency file contains an invalid component \
+             ('.') in its output directory\n",
+        );
+}
+
+#[test]
+// Given the dependency file specifies an output directory with a backwards
+//     reference
+// When the command is run
+// Then the command fails with an error
+fn output_dir_contains_back_ref() {
+    let mut cmd = setup_test_with_deps_file(
+        "output_dir_contains_back_ref",
+        indoc!{"
+            target/../deps
+        "},
+    );
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(3)
+        .stdout("")
+        .stderr(
+            "dpnd.txt:1: This dependency file contains an invalid component \
+             ('..') in its output directory\n",
+        );
+}
+
+#[test]
+// Given the dependency file specifies an output directory with a backwards
+//     reference that uses a backslash as a path separator
+// When the command is run
+// Then the command fails with an error
+fn output_dir_contains_backslash_back_ref() {
+    let mut cmd = setup_test_with_deps_file(
+        "output_dir_contains_backslash_back_ref",
+        indoc!{"
+            target\\..\\deps
+        "},
+    );
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(3)
+        .stdout("")
+        .stderr(
+            "dpnd.txt:1: This dependency file contains an invalid component \
+             ('..') in its output directory\n",
+        );
+}
+
+#[test]
+// Given the output directory in the dependency file is an absolute path
+// When the command is run
+// Then the command fails with an error
+fn output_dir_is_absolute() {
+    let mut cmd = setup_test_with_deps_file(
+        "output_dir_is_absolute",
+        indoc!{"
+            /tmp/deps
+        "},
+    );
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(3)
+        .stdout("")
+        .stderr(
+            "dpnd.txt:1: This dependency file has an absolute output \
+             directory ('/tmp/deps'); output directories must be relative to \
+             the project\n",
+        );
+}
+
+#[test]
+// Given the output directory in the dependency file starts with a Windows
+//     drive prefix
+// When the command is run
+// Then the command fails with an error
+fn output_dir_has_drive_prefix() {
+    let mut cmd = setup_test_with_deps_file(
+        "output_dir_has_drive_prefix",
+        indoc!{"
+            C:\\deps
+        "},
+    );
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(3)
+        .stdout("")
+        .stderr(
+            "dpnd.txt:1: This dependency file has an absolute output \
+             directory ('C:\\deps'); output directories must be relative to \
+             the project\n",
+        );
+}
+
+#[test]
+// Given the dependency file contains unaligned dependencies
+// When the `fmt` command is run with `--check`
+// Then the command fails with an error and the dependency file isn't changed
+fn fmt_check_unformatted_deps_file() {
+    let root_test_dir =
+        test_setup::create_root_dir("fmt_check_unformatted_deps_file");
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    let deps_file_conts = indoc!{"
+        deps
+
+        my_scripts git git://localhost/my_scripts.git master
+        a git git://localhost/a.git master
+    "};
+    fs::write(format!("{}/dpnd.txt", proj_dir), deps_file_conts)
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_subcmd(proj_dir.clone(), "fmt");
+    cmd.arg("--check");
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(7)
+        .stdout("")
+        .stderr("'dpnd.txt' isn't formatted; run `dpnd fmt` to format it\n");
+    let conts = fs::read_to_string(format!("{}/dpnd.txt", proj_dir))
+        .expect("couldn't read dependency file");
+    assert_eq!(conts, deps_file_conts);
+}
+
+#[test]
+// Given the dependency file starts with a version directive that requires a
+//     newer version of `dpnd`, followed by syntax that isn't valid
+// When the command is run
+// Then the command fails with an error that reports the required version,
+//     instead of the invalid syntax
+fn version_directive_requires_newer_dpnd() {
+    let mut cmd = setup_test_with_deps_file(
+        "version_directive_requires_newer_dpnd",
+        indoc!{"
+            #!dpnd >=999.0
+            deps
+
+            proj git source version new_option=true
+        "},
+    );
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(3)
+        .stdout("")
+        .stderr(format!(
+            "dpnd.txt:1: This dependency file requires a newer version of \
+             `dpnd` (999.0 or later, but this is {})\n",
+            env!("CARGO_PKG_VERSION"),
+        ));
+}
+
+#[test]
+// Given the dependency file starts with a version directive that doesn't
+//     contain a version
+// When the command is run
+// Then the command fails with an error
+fn version_directive_invalid() {
+    let mut cmd = setup_test_with_deps_file(
+        "version_directive_invalid",
+        indoc!{"
+            #!dpnd latest
+            deps
+        "},
+    );
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(3)
+        .stdout("")
+        .stderr(
+            "dpnd.txt:1: This dependency file has an invalid version \
+             directive ('#!dpnd latest'); version directives must have the \
+             form `#!dpnd >=<version>`\n",
+        );
+}
+
+#[test]
+// Given the dependency file specifies two Git dependencies whose sources don't
+//     exist
+// When the `validate` command is run
+// Then the command fails with an error for each dependency
+fn validate_reports_all_unreachable_sources() {
+    let root_test_dir = test_setup::create_root_dir(
+        "validate_reports_all_unreachable_sources",
+    );
+    let test_proj_dir = test_setup::create_dir(root_test_dir.clone(), "proj");
+    fs::write(
+        format!("{}/dpnd.txt", test_proj_dir),
+        formatdoc!{
+            "
+                deps
+
+                proj_a git {root}/missing_a.git master
+                proj_b git {root}/missing_b.git master
+            ",
+            root = root_test_dir,
+        },
+    )
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_subcmd(test_proj_dir, "validate");
+
+    let cmd_result = cmd.assert();
+
+    let mut expected_stderr = String::new();
+    for name in &["a", "b"] {
+        expected_stderr.push_str(&formatdoc!{
+            "
+                dpnd.txt: Couldn't reach the source of the 'proj_{name}' \
+                 dependency: `git ls-remote -- {root}/missing_{name}.git \
+                 HEAD` failed with the following output:
+
+                [!] fatal: '{root}/missing_{name}.git' does not appear to be \
+                 a git repository
+                [!] fatal: Could not read from remote repository.
+                [!] 
+                [!] Please make sure you have the correct access rights
+                [!] and the repository exists.
+
+            ",
+            name = name,
+            root = root_test_dir,
+        });
+    }
+    cmd_result.code(4).stdout("").stderr(expected_stderr);
+}
+
+#[test]
+// Given the dependency file defines dependencies whose `needs` options form a
+//     cycle
+// When the command is run
+// Then the command fails with an error that describes the cycle
+fn needs_cycle() {
+    let mut cmd = setup_test_with_deps_file(
+        "needs_cycle",
+        indoc!{"
+            deps
+
+            a git git://localhost/a.git master needs=c
+            b git git://localhost/b.git master needs=a
+            c git git://localhost/c.git master needs=b
+        "},
+    );
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(3)
+        .stdout("")
+        .stderr(
+            "The `needs` options of the dependencies form a cycle: 'a' -> \
+             'c' -> 'b' -> 'a'\n",
+        );
+}
+
+#[test]
+// Given the dependency file contains two dependencies that can't be fetched
+//     and one that can
+// When the command is run with `--keep-going`
+// Then the command fails with an error for each dependency that couldn't be
+//     fetched, and the other dependency is installed
+fn keep_going_installs_other_deps() {
+    let root_test_dir =
+        test_setup::create_root_dir("keep_going_installs_other_deps");
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        indoc!{r#"
+            deps
+
+            bad1 custom "exit 1" 1.0.0
+            good custom "printf %s {version} > version.txt" 1.2.3
+            bad2 custom "exit 2" 1.0.0
+        "#},
+    )
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+    cmd.arg("--keep-going");
+
+    let cmd_result = cmd.assert();
+
+    // Dependencies are fetched in an unspecified order, so the errors are
+    // checked separately.
+    let stderr = cmd_result.code(4).stdout("").get_output().stderr.clone();
+    let stderr = String::from_utf8(stderr).expect("stderr isn't UTF-8");
+    for (dep_name, code) in &[("bad1", 1), ("bad2", 2)] {
+        let msg = format!(
+            "Couldn't retrieve the source for the dependency '{}': `exit {}` \
+             failed with the following output:\n",
+            dep_name,
+            code,
+        );
+        assert!(stderr.contains(&msg), "{}", stderr);
+    }
+    assert!(
+        stderr.ends_with("\n2 dependencies couldn't be installed\n"),
+        "{}",
+        stderr,
+    );
+    let version = fs::read_to_string(
+        format!("{}/deps/good/version.txt", proj_dir),
+    )
+        .expect("couldn't read version file");
+    assert_eq!(version, "1.2.3");
+}
+
+#[test]
+// Given the dependency file contains a dependency
+// When the command is run with `--ci` and `--interactive`
+// Then the command fails because the flags can't be combined, and nothing is
+//     installed
+fn ci_preset_rejects_interactive() {
+    let root_test_dir =
+        test_setup::create_root_dir("ci_preset_rejects_interactive");
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        indoc!{r#"
+            deps
+
+            tool custom "true" 1.0.0
+        "#},
+    )
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+    cmd.args(["--ci", "--interactive"]);
+
+    let cmd_result = cmd.assert();
+
+    let stderr = cmd_result.code(1).stdout("").get_output().stderr.clone();
+    let stderr = String::from_utf8(stderr).expect("stderr isn't UTF-8");
+    assert!(stderr.contains("cannot be used with"), "{}", stderr);
+    assert!(!Path::new(&format!("{}/deps", proj_dir)).exists());
+}
+
+#[test]
+// Given the dependency file contains two dependencies that can't be fetched
+//     and one that can
+// When the command is run with `--ci`
+// Then the command fails with an error for each dependency that couldn't be
+//     fetched, and the other dependency is installed
+fn ci_preset_keeps_going() {
+    let root_test_dir = test_setup::create_root_dir("ci_preset_keeps_going");
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        indoc!{r#"
+            deps
+
+            bad1 custom "exit 1" 1.0.0
+            good custom "printf %s {version} > version.txt" 1.2.3
+            bad2 custom "exit 2" 1.0.0
+        "#},
+    )
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+    cmd.env_remove("GITHUB_STEP_SUMMARY");
+    cmd.arg("--ci");
+
+    let cmd_result = cmd.assert();
+
+    let stderr = cmd_result.code(4).stdout("").get_output().stderr.clone();
+    let stderr = String::from_utf8(stderr).expect("stderr isn't UTF-8");
+    assert!(
+        stderr.ends_with("\n2 dependencies couldn't be installed\n"),
+        "{}",
+        stderr,
+    );
+    let version = fs::read_to_string(
+        format!("{}/deps/good/version.txt", proj_dir),
+    )
+        .expect("couldn't read version file");
+    assert_eq!(version, "1.2.3");
+}
@@ -2,6 +2,16 @@
 // Use of this source code is governed by an MIT
 // licence that can be found in the LICENCE file.
 
+// `BUILT_IN_TOOL_NAMES` lists the built-in dependency tools, as they're named
+// in errors about unknown tools.
+#[cfg(not(feature = "docker"))]
+const BUILT_IN_TOOL_NAMES: &str =
+    "'custom', 'file', 'git', 'git-file', 'object-store' and 'svn'";
+#[cfg(feature = "docker")]
+const BUILT_IN_TOOL_NAMES: &str =
+    "'custom', 'docker', 'file', 'git', 'git-file', 'object-store' and \
+     'svn'";
+
 mod errors;
 mod nested_errors;
 mod nested_success;
@@ -3,12 +3,17 @@
 // licence that can be found in the LICENCE file.
 
 use std::fs;
+#[cfg(unix)]
+use std::os::unix::fs as unix_fs;
+use std::path::Path;
 
 use crate::fs_check;
 use crate::fs_check::Node;
 use crate::test_setup;
 use crate::test_setup::Layout;
 
+use super::BUILT_IN_TOOL_NAMES;
+use super::nested_success;
 use super::success;
 
 #[test]
@@ -33,7 +38,7 @@ fn empty_deps_file_in_nested_dep() {
     );
 
     cmd_result
-        .code(1)
+        .code(3)
         .stdout("")
         .stderr(
             "deps/bad_dep/dpnd.txt: This nested dependency file (for \
@@ -93,9 +98,9 @@ fn assert_nested_dep_contents(
     fs_check::assert_contents(
         proj_dir,
         &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
             "dpnd.txt" => Node::File(deps_file_conts),
             "deps" => Node::Dir(hashmap!{
-                "current_dpnd.txt" => Node::AnyFile,
                 "bad_dep" => Node::Dir(hashmap!{
                     ".git" => Node::AnyDir,
                     "dpnd.txt" => Node::File(nested_deps_file_conts),
@@ -133,7 +138,7 @@ fn deps_file_invalid_dep_in_nested_dep() {
     );
 
     cmd_result
-        .code(1)
+        .code(3)
         .stdout("")
         .stderr(
             "deps/bad_dep/dpnd.txt:3: Invalid dependency specification in \
@@ -172,13 +177,15 @@ fn deps_file_invalid_tool_in_nested_dep() {
     );
 
     cmd_result
-        .code(1)
+        .code(3)
         .stdout("")
-        .stderr(
+        .stderr(format!(
             "deps/bad_dep/dpnd.txt:3: The dependency 'proj' of the nested \
              dependency 'bad_dep' specifies an invalid tool name ('tool'); \
-             the supported tool is 'git'\n",
-        );
+             the built-in tools are {}, and no `dpnd-tool-tool` plugin was \
+             found in `PATH`\n",
+            BUILT_IN_TOOL_NAMES,
+        ));
     assert_nested_dep_contents(
         &proj_dir,
         &deps_file_conts,
@@ -213,11 +220,11 @@ fn unavailable_git_proj_src_in_nested_dep() {
     );
 
     cmd_result
-        .code(1)
+        .code(4)
         .stdout("")
         .stderr(indoc!{"
             Couldn't retrieve the source for the dependency 'proj' in the \
-             nested dependency 'bad_dep': `git clone \
+             nested dependency 'bad_dep': `git clone -- \
              git://localhost/no_scripts.git .` failed with the following \
              output:
 
@@ -257,7 +264,7 @@ fn dup_dep_names_in_nested_dep() {
     );
 
     cmd_result
-        .code(1)
+        .code(3)
         .stdout("")
         .stderr(
             "deps/bad_dep/dpnd.txt:4: A dependency named 'my_scripts' is \
@@ -292,12 +299,13 @@ fn invalid_dep_name_in_nested_dep() {
     );
 
     cmd_result
-        .code(1)
+        .code(3)
         .stdout("")
         .stderr(
             "deps/bad_dep/dpnd.txt:3: 'my_scripts?' contains an invalid \
              character ('?') at position 11; dependency names can only \
-             contain numbers, letters, hyphens, underscores and periods\n",
+             contain ASCII numbers, letters, hyphens, underscores and \
+             periods\n",
         );
 }
 
@@ -328,10 +336,238 @@ fn reserved_dep_name_in_nested_dep() {
     );
 
     cmd_result
-        .code(1)
+        .code(3)
         .stdout("")
         .stderr(
             "deps/bad_dep/dpnd.txt:3: 'current_dpnd.txt' is a reserved name \
              and can't be used as a dependency name\n",
         );
 }
+
+#[test]
+#[cfg(unix)]
+// Given the output directory of a nested dependency is a symbolic link to the
+//     output directory of another dependency
+// When the command is run with `--recursive`
+// Then the command fails with an error
+fn nested_output_dir_overlaps_dep_output_dir() {
+    let test_deps = success::test_deps();
+    let Layout{dep_srcs_dir, proj_dir, ..} = test_setup::create(
+        "nested_output_dir_overlaps_dep_output_dir",
+        &test_deps,
+        &hashmap!{},
+    );
+    let scratch_dir = test_setup::create_root_dir(
+        "nested_output_dir_overlaps_dep_output_dir_scratch",
+    );
+    fs::write(
+        format!("{}/dpnd.txt", scratch_dir),
+        indoc!{"
+            vendor
+
+            my_scripts git git://localhost/my_scripts.git master
+        "},
+    )
+        .expect("couldn't write nested dependency file");
+    unix_fs::symlink("../your_scripts", format!("{}/vendor", scratch_dir))
+        .expect("couldn't create symbolic link");
+    test_setup::init_git_repo(&scratch_dir);
+    test_setup::commit_all(&scratch_dir);
+    test_setup::run_cmd(
+        &scratch_dir,
+        "git",
+        [
+            "clone",
+            "--bare",
+            &scratch_dir,
+            &format!("{}/bad_dep.git", dep_srcs_dir),
+        ],
+    );
+    let deps_file_conts = indoc!{"
+        deps
+
+        bad_dep git git://localhost/bad_dep.git master
+        your_scripts git git://localhost/your_scripts.git master
+    "};
+    fs::write(format!("{}/dpnd.txt", proj_dir), deps_file_conts)
+        .expect("couldn't write dependency file");
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+            cmd.arg("--recursive");
+
+            cmd.assert()
+        },
+    );
+
+    cmd_result
+        .code(3)
+        .stdout("")
+        .stderr(
+            "deps/bad_dep/dpnd.txt:1: The output directory \
+             ('deps/bad_dep/vendor') overlaps with the output directory for \
+             the 'your_scripts' dependency ('deps/your_scripts'), which is \
+             defined on line 4 of 'dpnd.txt'\n",
+        );
+}
+
+#[test]
+#[cfg(unix)]
+// Given the output directory of a nested dependency is a symbolic link to a
+//     directory outside of the dependency
+// When the command is run with `--recursive`
+// Then the command fails with an error
+fn nested_output_dir_outside_dep() {
+    let test_deps = success::test_deps();
+    let Layout{dep_srcs_dir, proj_dir, ..} = test_setup::create(
+        "nested_output_dir_outside_dep",
+        &test_deps,
+        &hashmap!{},
+    );
+    test_setup::create_dir(proj_dir.clone(), "tools");
+    let scratch_dir =
+        test_setup::create_root_dir("nested_output_dir_outside_dep_scratch");
+    fs::write(
+        format!("{}/dpnd.txt", scratch_dir),
+        indoc!{"
+            vendor
+
+            my_scripts git git://localhost/my_scripts.git master
+        "},
+    )
+        .expect("couldn't write nested dependency file");
+    unix_fs::symlink("../../tools", format!("{}/vendor", scratch_dir))
+        .expect("couldn't create symbolic link");
+    test_setup::init_git_repo(&scratch_dir);
+    test_setup::commit_all(&scratch_dir);
+    test_setup::run_cmd(
+        &scratch_dir,
+        "git",
+        [
+            "clone",
+            "--bare",
+            &scratch_dir,
+            &format!("{}/bad_dep.git", dep_srcs_dir),
+        ],
+    );
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        "deps\n\nbad_dep git git://localhost/bad_dep.git master\n",
+    )
+        .expect("couldn't write dependency file");
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+            cmd.arg("--recursive");
+
+            cmd.assert()
+        },
+    );
+
+    cmd_result
+        .code(3)
+        .stdout("")
+        .stderr(
+            "deps/bad_dep/dpnd.txt:1: The output directory \
+             ('deps/bad_dep/vendor') resolves to 'tools', which is outside of \
+             the directory of the 'bad_dep' dependency ('deps/bad_dep')\n",
+        );
+    assert!(!Path::new(&format!("{}/tools/my_scripts", proj_dir)).exists());
+}
+
+#[test]
+// Given the dependency file defines a dependency at one version, and a
+//     nested dependency file defines a dependency with the same source at
+//     another version
+// When the command is run with `--recursive` and `--conflicts error`
+// Then the command fails with an error
+fn conflicting_nested_dep_vsns() {
+    let (Layout{dep_srcs_dir, proj_dir, ..}, old_hash) =
+        nested_success::create_conflicting_vsns_test_setup(
+            "conflicting_nested_dep_vsns",
+        );
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+            cmd.args(["--recursive", "--conflicts", "error"]);
+
+            cmd.assert()
+        },
+    );
+
+    cmd_result
+        .code(3)
+        .stdout("")
+        .stderr(format!(
+            "deps/all_scripts/dpnd.txt:3: The 'my_scripts' dependency \
+             ('git://localhost/my_scripts.git' at 'master') conflicts with \
+             the 'my_scripts' dependency (at '{}'), which is defined on line \
+             4 of 'dpnd.txt'\n",
+            old_hash,
+        ));
+}
+
+#[test]
+// Given the dependency file of a doubly nested dependency contains an invalid
+//     dependency specification
+// When the command is run with `--recursive`
+// Then the command fails with an error that names the nested dependencies
+//     that lead to the invalid dependency file
+fn deps_file_invalid_dep_in_doubly_nested_dep() {
+    let mut test_deps = success::test_deps();
+    test_deps.insert(
+        "outer_dep",
+        vec![hashmap!{
+            "dpnd.txt" => indoc!{"
+                deps
+
+                bad_dep git git://localhost/bad_dep.git master
+            "},
+        }],
+    );
+    test_deps.insert(
+        "bad_dep",
+        vec![hashmap!{
+            "dpnd.txt" => indoc!{"
+                deps
+
+                proj tool source version extra
+            "},
+        }],
+    );
+    let Layout{dep_srcs_dir, proj_dir, deps_file, ..} = test_setup::create(
+        "deps_file_invalid_dep_in_doubly_nested_dep",
+        &test_deps,
+        &hashmap!{},
+    );
+    fs::write(
+        &deps_file,
+        indoc!{"
+            deps
+
+            outer_dep git git://localhost/outer_dep.git master
+        "},
+    )
+        .expect("couldn't write dependency file");
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+            cmd.arg("--recursive");
+
+            cmd.assert()
+        },
+    );
+
+    cmd_result
+        .code(3)
+        .stdout("")
+        .stderr(
+            "deps/outer_dep/deps/bad_dep/dpnd.txt:3: Invalid dependency \
+             specification in nested dependency 'outer_dep' -> 'bad_dep': \
+             'proj tool source version extra'\n",
+        );
+}
@@ -2,8 +2,11 @@
 // Use of this source code is governed by an MIT
 // licence that can be found in the LICENCE file.
 
+use std::fmt::Write;
 use std::fs;
 
+use serde_json::Value as JsonValue;
+
 use crate::fs_check;
 use crate::fs_check::Node;
 use crate::test_setup;
@@ -19,11 +22,14 @@ use super::success;
 fn nested_deps_pulled_correctly_with_long_flag() {
     check_nested_deps_pulled_correctly(
         "nested_deps_pulled_correctly_with_long_flag",
-        "--recursive",
+        &["--recursive"],
     );
 }
 
-fn check_nested_deps_pulled_correctly(root_test_dir_name: &str, flag: &str) {
+fn check_nested_deps_pulled_correctly(
+    root_test_dir_name: &str,
+    args: &[&str],
+) {
     let test_deps = success::test_deps();
     let Layout{dep_srcs_dir, proj_dir, ..} =
         test_setup::create(root_test_dir_name, &test_deps, &hashmap!{});
@@ -39,7 +45,7 @@ fn check_nested_deps_pulled_correctly(root_test_dir_name: &str, flag: &str) {
         dep_srcs_dir,
         || {
             let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
-            cmd.arg(flag);
+            cmd.args(args);
 
             cmd.assert()
         },
@@ -49,15 +55,15 @@ fn check_nested_deps_pulled_correctly(root_test_dir_name: &str, flag: &str) {
     fs_check::assert_contents(
         &proj_dir,
         &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
             "dpnd.txt" => Node::File(deps_file_conts),
             "deps" => Node::Dir(hashmap!{
-                "current_dpnd.txt" => Node::AnyFile,
                 "all_scripts" => Node::Dir(hashmap!{
                     ".git" => Node::AnyDir,
+                    ".dpnd" => Node::AnyDir,
                     "dpnd.txt" => Node::AnyFile,
                     "script.sh" => Node::File("echo 'hello, all!'"),
                     "deps" => Node::Dir(hashmap!{
-                        "current_dpnd.txt" => Node::AnyFile,
                         "my_scripts" => Node::Dir(hashmap!{
                             ".git" => Node::AnyDir,
                             "script.sh" => Node::File("echo 'hello, world!'"),
@@ -81,10 +87,129 @@ fn check_nested_deps_pulled_correctly(root_test_dir_name: &str, flag: &str) {
 fn nested_deps_pulled_correctly_with_short_flag() {
     check_nested_deps_pulled_correctly(
         "nested_deps_pulled_correctly_with_short_flag",
-        "-r",
+        &["-r"],
     );
 }
 
+#[test]
+// Given the dependency file contains nested dependencies
+// When the command is run with `--recursive` and `--jobs 1`
+// Then the nested dependencies are pulled to the correct locations with the
+//     correct contents
+fn nested_deps_pulled_correctly_with_one_job() {
+    check_nested_deps_pulled_correctly(
+        "nested_deps_pulled_correctly_with_one_job",
+        &["--recursive", "--jobs", "1"],
+    );
+}
+
+#[test]
+// Given the dependency file contains two nested projects whose dependency
+//     files each define a different `link=true` dependency, and each of those
+//     dependencies can only be fetched while the other is being fetched
+// When the command is run with `--recursive` and `--jobs 2`
+// Then the linked dependencies are fetched in parallel and installed
+fn linked_nested_deps_fetched_in_parallel() {
+    let (proj_dir, store_dir) = create_mutually_waiting_linked_deps(
+        "linked_nested_deps_fetched_in_parallel",
+    );
+    let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+    cmd.env("DPND_STORE", &store_dir)
+        .env("PATH", "/bin:/usr/bin")
+        .args(["--recursive", "--jobs", "2"]);
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+    assert_linked_into_store(&proj_dir, &store_dir);
+}
+
+#[test]
+// Given the dependency file contains two nested projects whose dependency
+//     files each define a different `link=true` dependency, and each of those
+//     dependencies can only be fetched while the other is being fetched
+// And the project config file sets `recursive`, `jobs` and the store
+//     directory
+// When the command is run
+// Then the linked dependencies are fetched in parallel into the store
+//     directory from the config file
+fn linked_nested_deps_use_config_settings() {
+    let (proj_dir, store_dir) = create_mutually_waiting_linked_deps(
+        "linked_nested_deps_use_config_settings",
+    );
+    let config_dir = test_setup::create_dir(proj_dir.clone(), ".dpnd");
+    fs::write(
+        format!("{}/config.toml", config_dir),
+        formatdoc!{
+            r#"
+                [install]
+                recursive = true
+                jobs = 2  # Both dependencies must be fetched at once.
+
+                [cache]
+                dir = "{}"
+            "#,
+            store_dir,
+        },
+    )
+        .expect("couldn't write config file");
+    let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+    cmd.env("PATH", "/bin:/usr/bin");
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+    assert_linked_into_store(&proj_dir, &store_dir);
+}
+
+// `create_mutually_waiting_linked_deps` creates a project that depends on two
+// nested projects, `a` and `b`, which each define a `link=true` dependency
+// that can only be fetched while the other is being fetched. It returns the
+// directory of the project and the directory that should be used as the
+// store.
+fn create_mutually_waiting_linked_deps(test_name: &str) -> (String, String) {
+    let root_test_dir = test_setup::create_root_dir(test_name);
+    let srcs_dir = test_setup::create_dir(root_test_dir.clone(), "srcs");
+    let store_dir = format!("{}/store", root_test_dir);
+    let proj_dir = test_setup::create_dir(root_test_dir.clone(), "proj");
+    let mut deps_file_conts = "deps\n\n".to_string();
+    for (name, other_name) in &[("a", "b"), ("b", "a")] {
+        let nested_proj_dir =
+            test_setup::create_dir(srcs_dir.clone(), name);
+        fs::write(
+            format!("{}/dpnd.txt", nested_proj_dir),
+            format!(
+                "deps\n\n\
+                 {name} custom \"touch {root}/{name}; i=0; while [ ! -f \
+                 {root}/{other_name} ]; do i=$((i+1)); [ $i -gt 100 ] && exit \
+                 1; sleep 0.1; done\" 1.0.0 link=true\n",
+                name = name,
+                other_name = other_name,
+                root = root_test_dir,
+            ),
+        )
+            .expect("couldn't write nested dependency file");
+        let _ = writeln!(
+            deps_file_conts,
+            "{} custom \"cp -R {}/. {{out_dir}}\" 1.0.0",
+            name,
+            nested_proj_dir,
+        );
+    }
+    fs::write(format!("{}/dpnd.txt", proj_dir), &deps_file_conts)
+        .expect("couldn't write dependency file");
+
+    (proj_dir, store_dir)
+}
+
+fn assert_linked_into_store(proj_dir: &str, store_dir: &str) {
+    for name in &["a", "b"] {
+        let dep_dir = format!("{}/deps/{}/deps/{}", proj_dir, name, name);
+        let target = fs::read_link(&dep_dir).expect("couldn't read symlink");
+        assert!(target.starts_with(store_dir), "{}", target.display());
+    }
+}
+
 #[test]
 // Given the dependency file contains nested dependencies
 // When the command is run without recursion
@@ -121,9 +246,9 @@ fn check_nested_deps_not_pulled_without_recursion(test_name: &str)
     fs_check::assert_contents(
         &proj_dir,
         &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
             "dpnd.txt" => Node::File(deps_file_conts),
             "deps" => Node::Dir(hashmap!{
-                "current_dpnd.txt" => Node::AnyFile,
                 "all_scripts" => Node::Dir(hashmap!{
                     ".git" => Node::AnyDir,
                     "dpnd.txt" => Node::AnyFile,
@@ -166,15 +291,15 @@ fn run_with_recursion_after_run_without_recursion() {
     fs_check::assert_contents(
         &proj_dir,
         &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
             "dpnd.txt" => Node::File(&deps_file_conts),
             "deps" => Node::Dir(hashmap!{
-                "current_dpnd.txt" => Node::AnyFile,
                 "all_scripts" => Node::Dir(hashmap!{
                     ".git" => Node::AnyDir,
+                    ".dpnd" => Node::AnyDir,
                     "dpnd.txt" => Node::AnyFile,
                     "script.sh" => Node::File("echo 'hello, all!'"),
                     "deps" => Node::Dir(hashmap!{
-                        "current_dpnd.txt" => Node::AnyFile,
                         "my_scripts" => Node::Dir(hashmap!{
                             ".git" => Node::AnyDir,
                             "script.sh" => Node::File("echo 'hello, world!'"),
@@ -190,6 +315,103 @@ fn run_with_recursion_after_run_without_recursion() {
     );
 }
 
+#[test]
+// Given the tool was run with `--recursive`, and then the version of a
+//     dependency that has nested dependencies was changed
+// When the command is run with `--recursive`
+// Then the new version of the dependency is pulled, because the nested
+//     dependencies and state files in the dependency aren't treated as local
+//     modifications
+fn nested_dep_vsn_changed_after_run_with_recursion() {
+    let mut test_deps = success::test_deps();
+    let all_scripts_v1 = test_deps["all_scripts"][0].clone();
+    let mut all_scripts_v2 = all_scripts_v1.clone();
+    all_scripts_v2.insert("script.sh", "echo 'hello, everyone!'");
+    test_deps.insert("all_scripts", vec![all_scripts_v1, all_scripts_v2]);
+    let Layout{dep_srcs_dir, proj_dir, deps_commit_hashes, deps_file, ..} =
+        test_setup::create(
+            "nested_dep_vsn_changed_after_run_with_recursion",
+            &test_deps,
+            &hashmap!{"all_scripts" => 0},
+        );
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+            cmd.arg("--recursive");
+            cmd.assert()
+                .code(0)
+                .stdout("")
+                .stderr("");
+
+            test_setup::write_test_deps_file(
+                &deps_file,
+                &deps_commit_hashes,
+                &hashmap!{"all_scripts" => 1},
+            );
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+            cmd.arg("--recursive");
+
+            cmd.assert()
+        },
+    );
+
+    cmd_result.code(0).stdout("").stderr("");
+    let script_conts =
+        fs::read_to_string(format!("{}/deps/all_scripts/script.sh", proj_dir))
+            .expect("couldn't read dependency");
+    assert_eq!(script_conts, "echo 'hello, everyone!'");
+}
+
+#[test]
+// Given the tool was run with `--recursive`, where a dependency that has
+//     nested dependencies commits its state file, and then the version of the
+//     dependency was changed
+// When the command is run with `--recursive`
+// Then the new version of the dependency is pulled, because its state file
+//     isn't treated as a local modification
+fn nested_dep_with_committed_state_vsn_changed() {
+    let mut test_deps = success::test_deps();
+    let mut all_scripts_v1 = test_deps["all_scripts"][0].clone();
+    all_scripts_v1.insert(".dpnd/state/deps/current_dpnd.txt", "# Outdated\n");
+    let mut all_scripts_v2 = all_scripts_v1.clone();
+    all_scripts_v2.insert("script.sh", "echo 'hello, everyone!'");
+    test_deps.insert("all_scripts", vec![all_scripts_v1, all_scripts_v2]);
+    let Layout{dep_srcs_dir, proj_dir, deps_commit_hashes, deps_file, ..} =
+        test_setup::create(
+            "nested_dep_with_committed_state_vsn_changed",
+            &test_deps,
+            &hashmap!{"all_scripts" => 0},
+        );
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+            cmd.arg("--recursive");
+            cmd.assert()
+                .code(0)
+                .stdout("")
+                .stderr("");
+
+            test_setup::write_test_deps_file(
+                &deps_file,
+                &deps_commit_hashes,
+                &hashmap!{"all_scripts" => 1},
+            );
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+            cmd.arg("--recursive");
+
+            cmd.assert()
+        },
+    );
+
+    cmd_result.code(0).stdout("").stderr("");
+    let script_conts =
+        fs::read_to_string(format!("{}/deps/all_scripts/script.sh", proj_dir))
+            .expect("couldn't read dependency");
+    assert_eq!(script_conts, "echo 'hello, everyone!'");
+}
+
 #[test]
 // Given the dependency file contains nested dependencies that contain nested
 //     dependencies
@@ -237,21 +459,21 @@ fn double_nested_deps_pulled_correctly() {
     fs_check::assert_contents(
         &proj_dir,
         &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
             "dpnd.txt" => Node::File(deps_file_conts),
             "deps" => Node::Dir(hashmap!{
-                "current_dpnd.txt" => Node::AnyFile,
                 "nested_scripts" => Node::Dir(hashmap!{
                     ".git" => Node::AnyDir,
+                    ".dpnd" => Node::AnyDir,
                     "dpnd.txt" => Node::File(nested_deps_file_conts),
                     "script.sh" => Node::File("echo 'hello!'"),
                     "deps" => Node::Dir(hashmap!{
-                        "current_dpnd.txt" => Node::AnyFile,
                         "all_scripts" => Node::Dir(hashmap!{
                             ".git" => Node::AnyDir,
+                            ".dpnd" => Node::AnyDir,
                             "dpnd.txt" => Node::AnyFile,
                             "script.sh" => Node::File("echo 'hello, all!'"),
                             "deps" => Node::Dir(hashmap!{
-                                "current_dpnd.txt" => Node::AnyFile,
                                 "my_scripts" => Node::Dir(hashmap!{
                                     ".git" => Node::AnyDir,
                                     "script.sh" =>
@@ -270,3 +492,346 @@ fn double_nested_deps_pulled_correctly() {
         }),
     );
 }
+
+#[test]
+// Given the dependency file contains nested dependencies and the ignore file
+//     lists the dependency that contains them
+// When the command is run with `--recursive`
+// Then the nested dependencies are not pulled
+fn ignored_nested_deps_not_pulled() {
+    let test_deps = success::test_deps();
+    let Layout{dep_srcs_dir, proj_dir, ..} = test_setup::create(
+        "ignored_nested_deps_not_pulled",
+        &test_deps,
+        &hashmap!{},
+    );
+    let deps_file_conts = indoc!{"
+        deps
+
+        all_scripts git git://localhost/all_scripts.git master
+    "};
+    let deps_file = format!("{}/dpnd.txt", proj_dir);
+    fs::write(&deps_file, deps_file_conts)
+        .expect("couldn't write dependency file");
+    let ignore_file_conts = "# Only used for developing `all_scripts`.\n\
+                             all_scripts\n";
+    fs::write(format!("{}/.dpndignore", proj_dir), ignore_file_conts)
+        .expect("couldn't write ignore file");
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+            cmd.arg("--recursive");
+
+            cmd.assert()
+        },
+    );
+
+    cmd_result.code(0).stdout("").stderr("");
+    fs_check::assert_contents(
+        &proj_dir,
+        &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
+            "dpnd.txt" => Node::File(deps_file_conts),
+            ".dpndignore" => Node::File(ignore_file_conts),
+            "deps" => Node::Dir(hashmap!{
+                "all_scripts" => Node::Dir(hashmap!{
+                    ".git" => Node::AnyDir,
+                    "dpnd.txt" => Node::AnyFile,
+                    "script.sh" => Node::File("echo 'hello, all!'"),
+                }),
+            }),
+        }),
+    );
+}
+
+#[test]
+// Given nested dependencies have been installed
+// When `sbom --recursive` is run
+// Then a CycloneDX document that lists the source and installed revision of
+//     each dependency, including nested dependencies, is printed
+fn sbom_lists_nested_deps() {
+    let test_deps = success::test_deps();
+    let Layout{dep_srcs_dir, proj_dir, ..} =
+        test_setup::create("sbom_lists_nested_deps", &test_deps, &hashmap!{});
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        indoc!{"
+            deps
+
+            all_scripts git git://localhost/all_scripts.git master
+        "},
+    )
+        .expect("couldn't write dependency file");
+    let install_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+            cmd.arg("--recursive");
+
+            cmd.assert()
+        },
+    );
+    install_result.code(0).stdout("").stderr("");
+    let mut cmd = test_setup::new_test_subcmd(proj_dir, "sbom");
+    cmd.arg("--recursive");
+
+    let cmd_result = cmd.assert();
+
+    let output = cmd_result.code(0).stderr("").get_output().stdout.clone();
+    let sbom: JsonValue = serde_json::from_slice(&output)
+        .expect("couldn't parse SBOM");
+    assert_eq!(sbom["bomFormat"], "CycloneDX");
+    assert_eq!(sbom["metadata"]["component"]["name"], "proj");
+    let components =
+        sbom["components"].as_array()
+            .expect("SBOM doesn't list components");
+    let bom_refs: Vec<&str> =
+        components.iter()
+            .filter_map(|component| component["bom-ref"].as_str())
+            .collect();
+    assert_eq!(
+        bom_refs,
+        vec![
+            "all_scripts",
+            "all_scripts/my_scripts",
+            "all_scripts/your_scripts",
+        ],
+    );
+    let all_scripts = &components[0];
+    assert_eq!(all_scripts["name"], "all_scripts");
+    assert_eq!(
+        all_scripts["externalReferences"][0]["url"],
+        "git://localhost/all_scripts.git",
+    );
+    let revision =
+        all_scripts["version"].as_str()
+            .expect("component doesn't have a version");
+    assert_eq!(revision.len(), 40, "{}", revision);
+}
+
+#[test]
+// Given nested dependencies have been installed
+// When `graph --recursive` is run
+// Then a DOT graph with an edge from each project to each of its
+//     dependencies is printed
+fn graph_prints_nested_deps_as_dot() {
+    let proj_dir = install_all_scripts("graph_prints_nested_deps_as_dot");
+    let mut cmd = test_setup::new_test_subcmd(proj_dir, "graph");
+    cmd.arg("--recursive");
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(0)
+        .stdout(
+            [
+                r#"digraph "proj" {"#,
+                r#"    "" [label="proj"];"#,
+                concat!(
+                    r#"    "all_scripts" [label="all_scripts\n"#,
+                    r#"git git://localhost/all_scripts.git master"];"#,
+                ),
+                concat!(
+                    r#"    "all_scripts/my_scripts" [label="my_scripts\n"#,
+                    r#"git git://localhost/my_scripts.git master"];"#,
+                ),
+                concat!(
+                    r#"    "all_scripts/your_scripts" "#,
+                    r#"[label="your_scripts\n"#,
+                    r#"git git://localhost/your_scripts.git master"];"#,
+                ),
+                r#"    "" -> "all_scripts";"#,
+                r#"    "all_scripts" -> "all_scripts/my_scripts";"#,
+                r#"    "all_scripts" -> "all_scripts/your_scripts";"#,
+                "}\n",
+            ]
+                .join("\n"),
+        )
+        .stderr("");
+}
+
+#[test]
+// Given nested dependencies have been installed
+// When `graph --recursive --format json` is run
+// Then a JSON graph with an edge from each project to each of its
+//     dependencies is printed
+fn graph_prints_nested_deps_as_json() {
+    let proj_dir = install_all_scripts("graph_prints_nested_deps_as_json");
+    let mut cmd = test_setup::new_test_subcmd(proj_dir, "graph");
+    cmd.args(["--recursive", "--format", "json"]);
+
+    let cmd_result = cmd.assert();
+
+    let output = cmd_result.code(0).stderr("").get_output().stdout.clone();
+    let graph: JsonValue = serde_json::from_slice(&output)
+        .expect("couldn't parse graph");
+    assert_eq!(graph["nodes"][0]["name"], "proj");
+    assert_eq!(graph["nodes"][1]["id"], "all_scripts");
+    assert_eq!(
+        graph["nodes"][1]["source"],
+        "git://localhost/all_scripts.git",
+    );
+    assert_eq!(graph["nodes"][1]["version"], "master");
+    let edges: Vec<(&str, &str)> =
+        graph["edges"].as_array()
+            .expect("graph doesn't list edges")
+            .iter()
+            .filter_map(|edge| {
+                Some((edge["from"].as_str()?, edge["to"].as_str()?))
+            })
+            .collect();
+    assert_eq!(
+        edges,
+        vec![
+            ("", "all_scripts"),
+            ("all_scripts", "all_scripts/my_scripts"),
+            ("all_scripts", "all_scripts/your_scripts"),
+        ],
+    );
+}
+
+// `install_all_scripts` recursively installs `all_scripts` into a new project
+// and returns the project directory.
+fn install_all_scripts(root_test_dir_name: &str) -> String {
+    let test_deps = success::test_deps();
+    let Layout{dep_srcs_dir, proj_dir, ..} =
+        test_setup::create(root_test_dir_name, &test_deps, &hashmap!{});
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        indoc!{"
+            deps
+
+            all_scripts git git://localhost/all_scripts.git master
+        "},
+    )
+        .expect("couldn't write dependency file");
+    let install_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+            cmd.arg("--recursive");
+
+            cmd.assert()
+        },
+    );
+    install_result.code(0).stdout("").stderr("");
+
+    proj_dir
+}
+
+#[test]
+// Given the dependency file defines a dependency at one version, and a
+//     nested dependency file defines a dependency with the same source at
+//     another version
+// When the command is run with `--recursive`
+// Then both versions are installed and a warning is printed
+fn conflicting_nested_dep_vsns_warned() {
+    let (Layout{dep_srcs_dir, proj_dir, ..}, old_hash) =
+        create_conflicting_vsns_test_setup(
+            "conflicting_nested_dep_vsns_warned",
+        );
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+            cmd.arg("--recursive");
+
+            cmd.assert()
+        },
+    );
+
+    cmd_result
+        .code(0)
+        .stdout("")
+        .stderr(format!(
+            "warning: deps/all_scripts/dpnd.txt:3: The 'my_scripts' \
+             dependency ('git://localhost/my_scripts.git' at 'master') \
+             conflicts with the 'my_scripts' dependency (at '{}'), which is \
+             defined on line 4 of 'dpnd.txt'\n",
+            old_hash,
+        ));
+    assert_conflicting_vsns_contents(&proj_dir, "echo 'hello, world!'");
+}
+
+#[test]
+// Given the dependency file defines a dependency at one version, and a
+//     nested dependency file defines a dependency with the same source at
+//     another version
+// When the command is run with `--recursive` and `--conflicts unify`
+// Then the version from the root dependency file is installed for both
+fn conflicting_nested_dep_vsns_unified() {
+    let (Layout{dep_srcs_dir, proj_dir, ..}, _) =
+        create_conflicting_vsns_test_setup(
+            "conflicting_nested_dep_vsns_unified",
+        );
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+            cmd.args(["--recursive", "--conflicts", "unify"]);
+
+            cmd.assert()
+        },
+    );
+
+    cmd_result.code(0).stdout("").stderr("");
+    assert_conflicting_vsns_contents(&proj_dir, "echo 'hello world'");
+}
+
+// `create_conflicting_vsns_test_setup` returns a test setup whose dependency
+// file defines `all_scripts`, whose dependency file defines `my_scripts` at
+// `master`, and also defines `my_scripts` at its first commit, which is
+// returned.
+pub fn create_conflicting_vsns_test_setup(root_test_dir_name: &str)
+    -> (Layout, String)
+{
+    let test_deps = success::test_deps();
+    let layout =
+        test_setup::create(root_test_dir_name, &test_deps, &hashmap!{});
+    let old_hash = layout.deps_commit_hashes["my_scripts"][0].clone();
+    fs::write(
+        format!("{}/dpnd.txt", layout.proj_dir),
+        format!(
+            "deps\n\n\
+             all_scripts git git://localhost/all_scripts.git master\n\
+             my_scripts git git://localhost/my_scripts.git {}\n",
+            old_hash,
+        ),
+    )
+        .expect("couldn't write dependency file");
+
+    (layout, old_hash)
+}
+
+fn assert_conflicting_vsns_contents(proj_dir: &str, nested_script: &str) {
+    fs_check::assert_contents(
+        proj_dir,
+        &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
+            "dpnd.txt" => Node::AnyFile,
+            "deps" => Node::Dir(hashmap!{
+                "my_scripts" => Node::Dir(hashmap!{
+                    ".git" => Node::AnyDir,
+                    "script.sh" => Node::File("echo 'hello world'"),
+                }),
+                "all_scripts" => Node::Dir(hashmap!{
+                    ".git" => Node::AnyDir,
+                    ".dpnd" => Node::AnyDir,
+                    "dpnd.txt" => Node::AnyFile,
+                    "script.sh" => Node::File("echo 'hello, all!'"),
+                    "deps" => Node::Dir(hashmap!{
+                        "my_scripts" => Node::Dir(hashmap!{
+                            ".git" => Node::AnyDir,
+                            "script.sh" => Node::File(nested_script),
+                        }),
+                        "your_scripts" => Node::Dir(hashmap!{
+                            ".git" => Node::AnyDir,
+                            "script.sh" => Node::File("echo 'hello, sun!'"),
+                        }),
+                    }),
+                }),
+            }),
+        }),
+    );
+}
@@ -3,7 +3,20 @@
 // licence that can be found in the LICENCE file.
 
 use std::collections::HashMap;
+use std::fmt::Write;
 use std::fs;
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+use std::time::UNIX_EPOCH;
+
+use serde_json::Value as JsonValue;
+
+extern crate assert_cmd;
+
+use self::assert_cmd::Command as AssertCommand;
 
 use crate::fs_check;
 use crate::fs_check::Node;
@@ -37,9 +50,9 @@ fn new_dep_vsn_pulled_correctly() {
     fs_check::assert_contents(
         &proj_dir,
         &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
             "dpnd.txt" => Node::File(&deps_file_conts),
             "deps" => Node::Dir(hashmap!{
-                "current_dpnd.txt" => Node::AnyFile,
                 "my_scripts" => Node::Dir(hashmap!{
                     ".git" => Node::AnyDir,
                     "script.sh" => Node::File("echo 'hello, world!'"),
@@ -107,9 +120,9 @@ fn old_dep_vsn_pulled_correctly() {
     fs_check::assert_contents(
         &proj_dir,
         &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
             "dpnd.txt" => Node::File(&deps_file_conts),
             "deps" => Node::Dir(hashmap!{
-                "current_dpnd.txt" => Node::AnyFile,
                 "my_scripts" => Node::Dir(hashmap!{
                     ".git" => Node::AnyDir,
                     "script.sh" => Node::File("echo 'hello world'"),
@@ -119,6 +132,70 @@ fn old_dep_vsn_pulled_correctly() {
     );
 }
 
+#[test]
+// Given the dependency file defines dependencies whose sources start with the
+//     `git+` scheme prefix, with and without the tool
+// When the command is run
+// Then the dependencies are installed with the `git` tool, and the prefix
+//     isn't recorded in the state file
+fn scheme_prefixed_sources_installed_with_git() {
+    let test_deps = test_deps();
+    let Layout{dep_srcs_dir, proj_dir, deps_commit_hashes, deps_file, ..} =
+        test_setup::create(
+            "scheme_prefixed_sources_installed_with_git",
+            &test_deps,
+            &hashmap!{},
+        );
+    fs::write(
+        &deps_file,
+        format!(
+            "deps\n\n\
+             my_scripts git+git://localhost/my_scripts.git {}\n\
+             your_scripts git git+git://localhost/your_scripts.git master\n",
+            deps_commit_hashes["my_scripts"][0],
+        ),
+    )
+        .expect("couldn't write dependency file");
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+
+            cmd.assert()
+        },
+    );
+
+    cmd_result.code(0).stdout("").stderr("");
+    fs_check::assert_contents(
+        &format!("{}/deps", proj_dir),
+        &Node::Dir(hashmap!{
+            "my_scripts" => Node::Dir(hashmap!{
+                ".git" => Node::AnyDir,
+                "script.sh" => Node::File("echo 'hello world'"),
+            }),
+            "your_scripts" => Node::Dir(hashmap!{
+                ".git" => Node::AnyDir,
+                "script.sh" => Node::File("echo 'hello, sun!'"),
+            }),
+        }),
+    );
+    let state_conts = fs::read_to_string(
+        format!("{}/.dpnd/state/deps/current_dpnd.txt", proj_dir),
+    )
+        .expect("couldn't read state file");
+    for dep_name in &["my_scripts", "your_scripts"] {
+        let line = format!(
+            "{0} git git://localhost/{0}.git ",
+            dep_name,
+        );
+        assert!(
+            state_conts.lines().any(|l| l.starts_with(&line)),
+            "unexpected state file:\n{}",
+            state_conts,
+        );
+    }
+}
+
 #[test]
 // Given the dependency file is in a parent directory of the directory the
 //     command is run in
@@ -147,10 +224,10 @@ fn run_in_proj_subdir() {
     fs_check::assert_contents(
         &proj_dir,
         &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
             "dpnd.txt" => Node::File(&deps_file_conts),
             "sub" => Node::Dir(hashmap!{}),
             "deps" => Node::Dir(hashmap!{
-                "current_dpnd.txt" => Node::AnyFile,
                 "my_scripts" => Node::Dir(hashmap!{
                     ".git" => Node::AnyDir,
                     "script.sh" => Node::File("echo 'hello, world!'"),
@@ -194,10 +271,10 @@ fn output_dir_is_subdir() {
     fs_check::assert_contents(
         &proj_dir,
         &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
             "dpnd.txt" => Node::File(&deps_file_conts),
             "target" => Node::Dir(hashmap!{
                 "deps" => Node::Dir(hashmap!{
-                    "current_dpnd.txt" => Node::AnyFile,
                     "my_scripts" => Node::Dir(hashmap!{
                         ".git" => Node::AnyDir,
                         "script.sh" => Node::File("echo 'hello world'"),
@@ -208,6 +285,242 @@ fn output_dir_is_subdir() {
     );
 }
 
+#[test]
+// Given the dependency file defines a section with its own output directory
+// When the command is run, and then run again after the dependency in the
+//     section is removed
+// Then dependencies are pulled to the output directories of their sections,
+//     and the removed dependency is removed from its section
+fn section_deps_pulled_to_section_output_dirs() {
+    let test_deps = test_deps();
+    let Layout{dep_srcs_dir, proj_dir, deps_commit_hashes, ..} =
+        test_setup::create(
+            "section_deps_pulled_to_section_output_dirs",
+            &test_deps,
+            &hashmap!{},
+        );
+    let deps_file_conts = formatdoc!{
+        "
+            vendor
+
+            my_scripts git git://localhost/my_scripts.git {}
+
+            [tools] tools
+            your_scripts git git://localhost/your_scripts.git {}
+        ",
+        deps_commit_hashes["my_scripts"][0],
+        deps_commit_hashes["your_scripts"][0],
+    };
+    let new_deps_file_conts = formatdoc!{
+        "
+            vendor
+
+            my_scripts git git://localhost/my_scripts.git {}
+
+            [tools] tools
+        ",
+        deps_commit_hashes["my_scripts"][0],
+    };
+    let deps_file = format!("{}/dpnd.txt", proj_dir);
+    fs::write(&deps_file, &deps_file_conts)
+        .expect("couldn't write dependency file");
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+            cmd.assert()
+                .code(0)
+                .stdout("")
+                .stderr("");
+
+            fs_check::assert_contents(
+                &proj_dir,
+                &Node::Dir(hashmap!{
+                    ".dpnd" => Node::AnyDir,
+                    "dpnd.txt" => Node::File(&deps_file_conts),
+                    "vendor" => Node::Dir(hashmap!{
+                        "my_scripts" => Node::Dir(hashmap!{
+                            ".git" => Node::AnyDir,
+                            "script.sh" => Node::File("echo 'hello world'"),
+                        }),
+                    }),
+                    "tools" => Node::Dir(hashmap!{
+                        "your_scripts" => Node::Dir(hashmap!{
+                            ".git" => Node::AnyDir,
+                            "script.sh" => Node::File("echo 'hello, sun!'"),
+                        }),
+                    }),
+                }),
+            );
+
+            fs::write(&deps_file, &new_deps_file_conts)
+                .expect("couldn't write dependency file");
+
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+            cmd.assert()
+        },
+    );
+
+    cmd_result.code(0).stdout("").stderr("");
+    fs_check::assert_contents(
+        &proj_dir,
+        &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
+            "dpnd.txt" => Node::File(&new_deps_file_conts),
+            "vendor" => Node::Dir(hashmap!{
+                "my_scripts" => Node::Dir(hashmap!{
+                    ".git" => Node::AnyDir,
+                    "script.sh" => Node::File("echo 'hello world'"),
+                }),
+            }),
+            "tools" => Node::Dir(hashmap!{
+            }),
+        }),
+    );
+}
+
+#[test]
+// Given the dependency file includes a file in a section
+// When the command is run
+// Then the dependencies in the included file are pulled to the output
+//     directory of the section
+fn included_deps_pulled_to_section_output_dir() {
+    let test_deps = test_deps();
+    let Layout{dep_srcs_dir, proj_dir, deps_commit_hashes, ..} =
+        test_setup::create(
+            "included_deps_pulled_to_section_output_dir",
+            &test_deps,
+            &hashmap!{},
+        );
+    let deps_file_conts = formatdoc!{
+        "
+            vendor
+
+            my_scripts git git://localhost/my_scripts.git {}
+
+            [tools] tools
+            include deps/tools.txt
+        ",
+        deps_commit_hashes["my_scripts"][0],
+    };
+    let included_file_conts = formatdoc!{
+        "
+            your_scripts git git://localhost/your_scripts.git {}
+        ",
+        deps_commit_hashes["your_scripts"][0],
+    };
+    fs::write(format!("{}/dpnd.txt", proj_dir), &deps_file_conts)
+        .expect("couldn't write dependency file");
+    fs::create_dir(format!("{}/deps", proj_dir))
+        .expect("couldn't create directory for included file");
+    fs::write(format!("{}/deps/tools.txt", proj_dir), &included_file_conts)
+        .expect("couldn't write included file");
+
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+
+            cmd.assert()
+        },
+    );
+
+    cmd_result.code(0).stdout("").stderr("");
+    fs_check::assert_contents(
+        &proj_dir,
+        &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
+            "dpnd.txt" => Node::File(&deps_file_conts),
+            "deps" => Node::Dir(hashmap!{
+                "tools.txt" => Node::File(&included_file_conts),
+            }),
+            "vendor" => Node::Dir(hashmap!{
+                "my_scripts" => Node::Dir(hashmap!{
+                    ".git" => Node::AnyDir,
+                    "script.sh" => Node::File("echo 'hello world'"),
+                }),
+            }),
+            "tools" => Node::Dir(hashmap!{
+                "your_scripts" => Node::Dir(hashmap!{
+                    ".git" => Node::AnyDir,
+                    "script.sh" => Node::File("echo 'hello, sun!'"),
+                }),
+            }),
+        }),
+    );
+}
+
+#[test]
+// Given the dependency file maps a group to its own output directory, and
+//     defines dependencies in the group, outside of it and in a section
+// When the command is run
+// Then the dependencies in the group that aren't in a section are pulled to
+//     the output directory of the group
+fn group_deps_pulled_to_group_output_dirs() {
+    let test_deps = test_deps();
+    let Layout{dep_srcs_dir, proj_dir, deps_commit_hashes, ..} =
+        test_setup::create(
+            "group_deps_pulled_to_group_output_dirs",
+            &test_deps,
+            &hashmap!{},
+        );
+    let deps_file_conts = formatdoc!{
+        "
+            vendor
+            group=test test/deps
+
+            my_scripts git git://localhost/my_scripts.git {}
+            your_scripts git git://localhost/your_scripts.git {} group=test
+
+            [tools] tools
+            their_scripts git git://localhost/their_scripts.git {} group=test
+        ",
+        deps_commit_hashes["my_scripts"][0],
+        deps_commit_hashes["your_scripts"][0],
+        deps_commit_hashes["their_scripts"][0],
+    };
+    fs::write(format!("{}/dpnd.txt", proj_dir), &deps_file_conts)
+        .expect("couldn't write dependency file");
+
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+
+            cmd.assert()
+        },
+    );
+
+    cmd_result.code(0).stdout("").stderr("");
+    fs_check::assert_contents(
+        &proj_dir,
+        &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
+            "dpnd.txt" => Node::File(&deps_file_conts),
+            "vendor" => Node::Dir(hashmap!{
+                "my_scripts" => Node::Dir(hashmap!{
+                    ".git" => Node::AnyDir,
+                    "script.sh" => Node::File("echo 'hello world'"),
+                }),
+            }),
+            "test" => Node::Dir(hashmap!{
+                "deps" => Node::Dir(hashmap!{
+                    "your_scripts" => Node::Dir(hashmap!{
+                        ".git" => Node::AnyDir,
+                        "script.sh" => Node::File("echo 'hello, sun!'"),
+                    }),
+                }),
+            }),
+            "tools" => Node::Dir(hashmap!{
+                "their_scripts" => Node::Dir(hashmap!{
+                    ".git" => Node::AnyDir,
+                    "script.sh" => Node::File("echo 'hello, moon!'"),
+                }),
+            }),
+        }),
+    );
+}
+
 #[test]
 // Given the tool was run once and there have been no changes since
 // When the command is run
@@ -238,9 +551,9 @@ fn tool_is_idempotent() {
     fs_check::assert_contents(
         &proj_dir,
         &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
             "dpnd.txt" => Node::File(&deps_file_conts),
             "deps" => Node::Dir(hashmap!{
-                "current_dpnd.txt" => Node::AnyFile,
                 "my_scripts" => Node::Dir(hashmap!{
                     ".git" => Node::AnyDir,
                     "script.sh" => Node::File("echo 'hello, world!'"),
@@ -282,9 +595,9 @@ fn add_first_dep() {
     fs_check::assert_contents(
         &proj_dir,
         &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
             "dpnd.txt" => Node::File(&deps_file_conts),
             "deps" => Node::Dir(hashmap!{
-                "current_dpnd.txt" => Node::AnyFile,
                 "my_scripts" => Node::Dir(hashmap!{
                     ".git" => Node::AnyDir,
                     "script.sh" => Node::File("echo 'hello, world!'"),
@@ -294,7 +607,7 @@ fn add_first_dep() {
     );
 }
 
-fn create_test_setup_and_run_tool(
+pub fn create_test_setup_and_run_tool(
     root_test_dir_name: &str,
     deps: &HashMap<&str, Vec<HashMap<&str, &str>>>,
     deps_commit_nums: HashMap<&str, usize>,
@@ -327,7 +640,7 @@ fn run_tool(
         },
     );
 
-    let mut deps_output_dir = hashmap!{"current_dpnd.txt" => Node::AnyFile};
+    let mut deps_output_dir = hashmap!{};
     for (dep_name, dep_commit_num) in deps_commit_nums {
         let mut dir_conts = hashmap!{".git" => Node::AnyDir};
         for (fname, fconts) in &deps[dep_name][dep_commit_num] {
@@ -339,6 +652,7 @@ fn run_tool(
     fs_check::assert_contents(
         proj_dir,
         &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
             "dpnd.txt" => Node::File(deps_file_conts),
             "deps" => Node::Dir(deps_output_dir),
         }),
@@ -380,9 +694,9 @@ fn add_second_dep() {
     fs_check::assert_contents(
         &proj_dir,
         &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
             "dpnd.txt" => Node::File(&deps_file_conts),
             "deps" => Node::Dir(hashmap!{
-                "current_dpnd.txt" => Node::AnyFile,
                 "my_scripts" => Node::Dir(hashmap!{
                     ".git" => Node::AnyDir,
                     "script.sh" => Node::File("echo 'hello, world!'"),
@@ -435,9 +749,9 @@ fn add_third_dep() {
     fs_check::assert_contents(
         &proj_dir,
         &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
             "dpnd.txt" => Node::File(&deps_file_conts),
             "deps" => Node::Dir(hashmap!{
-                "current_dpnd.txt" => Node::AnyFile,
                 "my_scripts" => Node::Dir(hashmap!{
                     ".git" => Node::AnyDir,
                     "script.sh" => Node::File("echo 'hello, world!'"),
@@ -493,9 +807,9 @@ fn rm_third_dep() {
     fs_check::assert_contents(
         &proj_dir,
         &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
             "dpnd.txt" => Node::File(&deps_file_conts),
             "deps" => Node::Dir(hashmap!{
-                "current_dpnd.txt" => Node::AnyFile,
                 "my_scripts" => Node::Dir(hashmap!{
                     ".git" => Node::AnyDir,
                     "script.sh" => Node::File("echo 'hello, world!'"),
@@ -543,9 +857,9 @@ fn rm_second_dep() {
     fs_check::assert_contents(
         &proj_dir,
         &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
             "dpnd.txt" => Node::File(&deps_file_conts),
             "deps" => Node::Dir(hashmap!{
-                "current_dpnd.txt" => Node::AnyFile,
                 "my_scripts" => Node::Dir(hashmap!{
                     ".git" => Node::AnyDir,
                     "script.sh" => Node::File("echo 'hello, world!'"),
@@ -586,9 +900,9 @@ fn rm_first_dep() {
     fs_check::assert_contents(
         &proj_dir,
         &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
             "dpnd.txt" => Node::File(&deps_file_conts),
             "deps" => Node::Dir(hashmap!{
-                "current_dpnd.txt" => Node::AnyFile,
             }),
         }),
     );
@@ -636,9 +950,9 @@ fn add_after_rm() {
     fs_check::assert_contents(
         &proj_dir,
         &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
             "dpnd.txt" => Node::File(&deps_file_conts),
             "deps" => Node::Dir(hashmap!{
-                "current_dpnd.txt" => Node::AnyFile,
                 "my_scripts" => Node::Dir(hashmap!{
                     ".git" => Node::AnyDir,
                     "script.sh" => Node::File("echo 'hello, world!'"),
@@ -680,9 +994,9 @@ fn upgrade_dep() {
     fs_check::assert_contents(
         &proj_dir,
         &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
             "dpnd.txt" => Node::File(&deps_file_conts),
             "deps" => Node::Dir(hashmap!{
-                "current_dpnd.txt" => Node::AnyFile,
                 "my_scripts" => Node::Dir(hashmap!{
                     ".git" => Node::AnyDir,
                     "script.sh" => Node::File("echo 'hello, world!'"),
@@ -693,28 +1007,71 @@ fn upgrade_dep() {
 }
 
 #[test]
-// Given the tool was just run with a new version of a dependency in the
-//     depencency file and then the dependency was downgraded
+// Given the tool was just run with an old version of a dependency in the
+//     depencency file and then the dependency was upgraded
 // When the command is run
-// Then the older version of the dependency is pulled to the correct location
+// Then the existing clone of the dependency is updated to the newer version,
+//     instead of being replaced by a new clone
+fn upgrade_dep_reuses_clone() {
+    let test_deps = test_deps();
+    let Layout{dep_srcs_dir, proj_dir, deps_commit_hashes, deps_file, ..} =
+        create_test_setup_and_run_tool(
+            "upgrade_dep_reuses_clone",
+            &test_deps,
+            hashmap!{"my_scripts" => 0},
+        );
+    let marker_path =
+        format!("{}/deps/my_scripts/.git/dpnd_test_marker", proj_dir);
+    fs::write(&marker_path, "").expect("couldn't write marker file");
+    test_setup::write_test_deps_file(
+        &deps_file,
+        &deps_commit_hashes,
+        &hashmap!{"my_scripts" => 1},
+    );
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+
+            cmd.assert()
+        },
+    );
+
+    cmd_result.code(0).stdout("").stderr("");
+    assert!(Path::new(&marker_path).exists());
+    let script_conts =
+        fs::read_to_string(format!("{}/deps/my_scripts/script.sh", proj_dir))
+            .expect("couldn't read dependency");
+    assert_eq!(script_conts, "echo 'hello, world!'");
+}
+
+#[test]
+// Given the tool was just run with an old version of a dependency in the
+//     depencency file, the dependency was modified locally and then the
+//     dependency was upgraded
+// When the command is run with `--force`
+// Then the newer version of the dependency is pulled to the correct location
 //     with the correct contents
-fn downgrade_dep() {
+fn upgrade_modified_dep_with_force() {
     let test_deps = test_deps();
     let Layout{dep_srcs_dir, proj_dir, deps_commit_hashes, deps_file, ..} =
         create_test_setup_and_run_tool(
-            "upgrade_dep",
+            "upgrade_modified_dep_with_force",
             &test_deps,
-            hashmap!{"my_scripts" => 1},
+            hashmap!{"my_scripts" => 0},
         );
+    fs::write(format!("{}/deps/my_scripts/script.sh", proj_dir), "local")
+        .expect("couldn't modify dependency");
     let deps_file_conts = test_setup::write_test_deps_file(
         &deps_file,
         &deps_commit_hashes,
-        &hashmap!{"my_scripts" => 0},
+        &hashmap!{"my_scripts" => 1},
     );
     let cmd_result = test_setup::with_git_server(
         dep_srcs_dir,
         || {
             let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+            cmd.arg("--force");
 
             cmd.assert()
         },
@@ -724,12 +1081,12 @@ fn downgrade_dep() {
     fs_check::assert_contents(
         &proj_dir,
         &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
             "dpnd.txt" => Node::File(&deps_file_conts),
             "deps" => Node::Dir(hashmap!{
-                "current_dpnd.txt" => Node::AnyFile,
                 "my_scripts" => Node::Dir(hashmap!{
                     ".git" => Node::AnyDir,
-                    "script.sh" => Node::File("echo 'hello world'"),
+                    "script.sh" => Node::File("echo 'hello, world!'"),
                 }),
             }),
         }),
@@ -737,18 +1094,62 @@ fn downgrade_dep() {
 }
 
 #[test]
-// Given the dependency file contains two versions of the same dependency with
-//     different names
+// Given the tool was just run with a new version of a dependency in the
+//     depencency file and then the dependency was downgraded
 // When the command is run
-// Then dependencies are pulled to the correct locations with the correct
-//     contents
-fn same_dep_diff_vsns() {
+// Then the older version of the dependency is pulled to the correct location
+//     with the correct contents
+fn downgrade_dep() {
     let test_deps = test_deps();
-    let Layout{dep_srcs_dir, proj_dir, deps_commit_hashes, ..} =
-        test_setup::create("same_dep_diff_vsns", &test_deps, &hashmap!{});
-    let deps_file_conts = formatdoc!{
-        "
-            # This is the output directory.
+    let Layout{dep_srcs_dir, proj_dir, deps_commit_hashes, deps_file, ..} =
+        create_test_setup_and_run_tool(
+            "upgrade_dep",
+            &test_deps,
+            hashmap!{"my_scripts" => 1},
+        );
+    let deps_file_conts = test_setup::write_test_deps_file(
+        &deps_file,
+        &deps_commit_hashes,
+        &hashmap!{"my_scripts" => 0},
+    );
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+
+            cmd.assert()
+        },
+    );
+
+    cmd_result.code(0).stdout("").stderr("");
+    fs_check::assert_contents(
+        &proj_dir,
+        &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
+            "dpnd.txt" => Node::File(&deps_file_conts),
+            "deps" => Node::Dir(hashmap!{
+                "my_scripts" => Node::Dir(hashmap!{
+                    ".git" => Node::AnyDir,
+                    "script.sh" => Node::File("echo 'hello world'"),
+                }),
+            }),
+        }),
+    );
+}
+
+#[test]
+// Given the dependency file contains two versions of the same dependency with
+//     different names
+// When the command is run
+// Then dependencies are pulled to the correct locations with the correct
+//     contents
+fn same_dep_diff_vsns() {
+    let test_deps = test_deps();
+    let Layout{dep_srcs_dir, proj_dir, deps_commit_hashes, ..} =
+        test_setup::create("same_dep_diff_vsns", &test_deps, &hashmap!{});
+    let deps_file_conts = formatdoc!{
+        "
+            # This is the output directory.
             deps
 
             # These are the dependencies.
@@ -774,9 +1175,9 @@ fn same_dep_diff_vsns() {
     fs_check::assert_contents(
         &proj_dir,
         &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
             "dpnd.txt" => Node::File(&deps_file_conts),
             "deps" => Node::Dir(hashmap!{
-                "current_dpnd.txt" => Node::AnyFile,
                 "my_scripts_v1" => Node::Dir(hashmap!{
                     ".git" => Node::AnyDir,
                     "script.sh" => Node::File("echo 'hello world'"),
@@ -789,3 +1190,4050 @@ fn same_dep_diff_vsns() {
         }),
     );
 }
+
+#[test]
+// Given the dependency file is in a Git repository and one of its dependencies
+//     has changed since the last commit
+// When the command is run with `--changed-since HEAD`
+// Then only the changed dependency is pulled
+fn changed_since_only_installs_changed_deps() {
+    let test_deps = test_deps();
+    let Layout{dep_srcs_dir, proj_dir, deps_commit_hashes, deps_file, ..} =
+        test_setup::create(
+            "changed_since_only_installs_changed_deps",
+            &test_deps,
+            &hashmap!{"my_scripts" => 0, "your_scripts" => 0},
+        );
+    test_setup::init_git_repo(&proj_dir);
+    test_setup::commit_all(&proj_dir);
+    let deps_file_conts = test_setup::write_test_deps_file(
+        &deps_file,
+        &deps_commit_hashes,
+        &hashmap!{"my_scripts" => 1, "your_scripts" => 0},
+    );
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+            cmd.args(["--changed-since", "HEAD"]);
+
+            cmd.assert()
+        },
+    );
+
+    cmd_result.code(0).stdout("").stderr("");
+    fs_check::assert_contents(
+        &proj_dir,
+        &Node::Dir(hashmap!{
+            ".git" => Node::AnyDir,
+            ".dpnd" => Node::AnyDir,
+            "dpnd.txt" => Node::File(&deps_file_conts),
+            "deps" => Node::Dir(hashmap!{
+                "my_scripts" => Node::Dir(hashmap!{
+                    ".git" => Node::AnyDir,
+                    "script.sh" => Node::File("echo 'hello, world!'"),
+                }),
+            }),
+        }),
+    );
+}
+
+#[test]
+// Given the dependency file is in an empty directory
+// When the command is run with `--vendor`
+// Then dependencies are pulled without their Git metadata and their
+//     provenance is recorded
+fn vendor_mode_records_provenance() {
+    let test_deps = test_deps();
+    let layout = test_setup::create(
+        "vendor_mode_records_provenance",
+        &test_deps,
+        &hashmap!{"my_scripts" => 1},
+    );
+    let Layout{dep_srcs_dir, proj_dir, deps_file_conts, ..} = layout;
+    let deps_commit_hashes = layout.deps_commit_hashes;
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+            cmd.arg("--vendor");
+
+            cmd.assert()
+        },
+    );
+
+    cmd_result.code(0).stdout("").stderr("");
+    let commit_hash = &deps_commit_hashes["my_scripts"][1];
+    let vendor_file_conts = formatdoc!{
+        "
+            tool: git
+            source: git://localhost/my_scripts.git
+            version: {commit_hash}
+            revision: {commit_hash}
+        ",
+        commit_hash = commit_hash,
+    };
+    fs_check::assert_contents(
+        &proj_dir,
+        &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
+            "dpnd.txt" => Node::File(&deps_file_conts),
+            "deps" => Node::Dir(hashmap!{
+                "my_scripts" => Node::Dir(hashmap!{
+                    "VENDOR.txt" => Node::File(&vendor_file_conts),
+                    "script.sh" => Node::File("echo 'hello, world!'"),
+                }),
+            }),
+        }),
+    );
+}
+
+#[test]
+// Given the dependency file specifies a dependency with `archive=true`, whose
+//     repository marks a file with the `export-ignore` attribute
+// When the command is run
+// Then the dependency is installed without Git metadata or the ignored file,
+//     and its provenance is recorded
+fn archive_dep_installed_as_export() {
+    let test_deps = hashmap!{
+        "my_scripts" => vec![
+            hashmap!{
+                ".gitattributes" => "notes.txt export-ignore\n",
+                "notes.txt" => "internal notes",
+                "script.sh" => "echo 'hello, archive!'",
+            },
+        ],
+    };
+    let layout = test_setup::create(
+        "archive_dep_installed_as_export",
+        &test_deps,
+        &hashmap!{},
+    );
+    let Layout{dep_srcs_dir, proj_dir, ..} = layout;
+    let commit_hash = &layout.deps_commit_hashes["my_scripts"][0];
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        format!(
+            "deps\n\nmy_scripts git git://localhost/my_scripts.git {} \
+             archive=true\n",
+            commit_hash,
+        ),
+    )
+        .expect("couldn't write dependency file");
+
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+
+            cmd.assert()
+        },
+    );
+
+    cmd_result.code(0).stdout("").stderr("");
+    let vendor_file_conts = formatdoc!{
+        "
+            tool: git
+            source: git://localhost/my_scripts.git
+            version: {commit_hash}
+            revision: {commit_hash}
+        ",
+        commit_hash = commit_hash,
+    };
+    fs_check::assert_contents(
+        &proj_dir,
+        &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
+            "dpnd.txt" => Node::AnyFile,
+            "deps" => Node::Dir(hashmap!{
+                "my_scripts" => Node::Dir(hashmap!{
+                    ".gitattributes" =>
+                        Node::File("notes.txt export-ignore\n"),
+                    "VENDOR.txt" => Node::File(&vendor_file_conts),
+                    "script.sh" => Node::File("echo 'hello, archive!'"),
+                }),
+            }),
+        }),
+    );
+}
+
+#[test]
+// Given the tool was run with `--vendor` and there have been no changes since
+// When the command is run with `--vendor` and the Git server is unavailable
+// Then the dependencies aren't fetched again
+fn vendor_mode_doesnt_refetch_verified_deps() {
+    let test_deps = test_deps();
+    let Layout{dep_srcs_dir, proj_dir, deps_file_conts, ..} =
+        test_setup::create(
+            "vendor_mode_doesnt_refetch_verified_deps",
+            &test_deps,
+            &hashmap!{"my_scripts" => 1},
+        );
+    test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+            cmd.arg("--vendor");
+            cmd.assert()
+                .code(0)
+                .stdout("")
+                .stderr("");
+        },
+    );
+    let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+    cmd.arg("--vendor");
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+    fs_check::assert_contents(
+        &proj_dir,
+        &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
+            "dpnd.txt" => Node::File(&deps_file_conts),
+            "deps" => Node::Dir(hashmap!{
+                "my_scripts" => Node::Dir(hashmap!{
+                    "VENDOR.txt" => Node::AnyFile,
+                    "script.sh" => Node::File("echo 'hello, world!'"),
+                }),
+            }),
+        }),
+    );
+}
+
+#[test]
+// Given the tool was run without `--vendor` and there have been no changes
+//     since
+// When the command is run with `--vendor`
+// Then the dependencies are fetched again without their Git metadata
+fn vendor_mode_refetches_unverified_deps() {
+    let test_deps = test_deps();
+    let layout = create_test_setup_and_run_tool(
+        "vendor_mode_refetches_unverified_deps",
+        &test_deps,
+        hashmap!{"my_scripts" => 1},
+    );
+    let Layout{dep_srcs_dir, proj_dir, deps_file_conts, ..} = layout;
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+            cmd.arg("--vendor");
+
+            cmd.assert()
+        },
+    );
+
+    cmd_result.code(0).stdout("").stderr("");
+    fs_check::assert_contents(
+        &proj_dir,
+        &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
+            "dpnd.txt" => Node::File(&deps_file_conts),
+            "deps" => Node::Dir(hashmap!{
+                "my_scripts" => Node::Dir(hashmap!{
+                    "VENDOR.txt" => Node::AnyFile,
+                    "script.sh" => Node::File("echo 'hello, world!'"),
+                }),
+            }),
+        }),
+    );
+}
+
+#[test]
+// Given `SOURCE_DATE_EPOCH` is defined
+// When the command is run with `--deterministic-mtimes`
+// Then the modification times of the installed files are set to
+//     `SOURCE_DATE_EPOCH`
+fn deterministic_mtimes_use_source_date_epoch() {
+    let test_deps = test_deps();
+    let Layout{dep_srcs_dir, proj_dir, ..} =
+        test_setup::create(
+            "deterministic_mtimes_use_source_date_epoch",
+            &test_deps,
+            &hashmap!{"my_scripts" => 1},
+        );
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+            cmd.arg("--deterministic-mtimes");
+            cmd.env("SOURCE_DATE_EPOCH", "1000000000");
+
+            cmd.assert()
+        },
+    );
+
+    cmd_result.code(0).stdout("").stderr("");
+    let dep_dir = format!("{}/deps/my_scripts", proj_dir);
+    assert_mtime(&dep_dir, 1_000_000_000);
+    assert_mtime(&format!("{}/script.sh", dep_dir), 1_000_000_000);
+    assert_mtime(&format!("{}/.git/HEAD", dep_dir), 1_000_000_000);
+}
+
+#[test]
+// Given `SOURCE_DATE_EPOCH` isn't a valid number of seconds
+// When the command is run without `--deterministic-mtimes`
+// Then the dependencies are installed, because modification times aren't set
+//     to `SOURCE_DATE_EPOCH`
+fn invalid_source_date_epoch_ignored_without_deterministic_mtimes() {
+    let test_deps = test_deps();
+    let Layout{dep_srcs_dir, proj_dir, ..} =
+        test_setup::create(
+            "invalid_source_date_epoch_ignored_without_deterministic_mtimes",
+            &test_deps,
+            &hashmap!{"my_scripts" => 1},
+        );
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+            cmd.env("SOURCE_DATE_EPOCH", "yesterday");
+
+            cmd.assert()
+        },
+    );
+
+    cmd_result.code(0).stdout("").stderr("");
+    let script_conts =
+        fs::read_to_string(format!("{}/deps/my_scripts/script.sh", proj_dir))
+            .expect("couldn't read dependency");
+    assert_eq!(script_conts, "echo 'hello, world!'");
+}
+
+#[test]
+// Given the project config file enables `deterministic_mtimes` and
+//     `SOURCE_DATE_EPOCH` is defined
+// When the command is run without `--deterministic-mtimes`
+// Then the modification times of the installed files are set to
+//     `SOURCE_DATE_EPOCH`
+fn config_file_sets_default_flags() {
+    let root_test_dir =
+        test_setup::create_root_dir("config_file_sets_default_flags");
+    let files_dir = test_setup::create_dir(root_test_dir.clone(), "files");
+    fs::write(format!("{}/script.sh", files_dir), SCRIPT_CONTS)
+        .expect("couldn't write test file");
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        format!(
+            "deps\n\nscript file file://{}/script.sh {}\n",
+            files_dir,
+            SCRIPT_SHA256,
+        ),
+    )
+        .expect("couldn't write dependency file");
+    let config_dir = test_setup::create_dir(proj_dir.clone(), ".dpnd");
+    fs::write(
+        format!("{}/config.toml", config_dir),
+        indoc!{"
+            # Make builds reproducible by default.
+            [install]
+            deterministic_mtimes = true
+        "},
+    )
+        .expect("couldn't write config file");
+    let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+    cmd.env("SOURCE_DATE_EPOCH", "1000000000");
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+    assert_mtime(
+        &format!("{}/deps/script/script.sh", proj_dir),
+        1_000_000_000,
+    );
+}
+
+#[test]
+// Given the project config file defines `pre_fetch` and `post_fetch` hooks
+// When the command is run
+// Then the hooks are run before and after the dependency is fetched, with
+//     details of the fetch in their environment
+fn fetch_hooks_run_around_fetch() {
+    let root_test_dir =
+        test_setup::create_root_dir("fetch_hooks_run_around_fetch");
+    let files_dir = test_setup::create_dir(root_test_dir.clone(), "files");
+    fs::write(format!("{}/script.sh", files_dir), SCRIPT_CONTS)
+        .expect("couldn't write test file");
+    let proj_dir = test_setup::create_dir(root_test_dir.clone(), "proj");
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        format!(
+            "deps\n\nscript file file://{}/script.sh {}\n",
+            files_dir,
+            SCRIPT_SHA256,
+        ),
+    )
+        .expect("couldn't write dependency file");
+    let config_dir = test_setup::create_dir(proj_dir.clone(), ".dpnd");
+    fs::write(
+        format!("{}/config.toml", config_dir),
+        indoc!{r#"
+            [hooks]
+            pre_fetch = "echo pre $DPND_DEP_NAME $DPND_DEP_DIR >> ../hooks.log"
+            post_fetch = "echo post $DPND_FETCH_RESULT >> ../hooks.log"
+        "#},
+    )
+        .expect("couldn't write config file");
+    let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+    let hooks_log = fs::read_to_string(format!("{}/hooks.log", root_test_dir))
+        .expect("couldn't read hooks log");
+    assert_eq!(
+        hooks_log,
+        format!("pre script {}/deps/script\npost success\n", proj_dir),
+    );
+}
+
+#[test]
+// Given the project config file defines a hook that contains a `#` that
+//     doesn't follow whitespace, followed by a comment
+// When the command is run
+// Then the `#` is kept in the hook, and the comment is ignored
+fn config_file_hash_in_value_kept() {
+    let root_test_dir =
+        test_setup::create_root_dir("config_file_hash_in_value_kept");
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        indoc!{r#"
+            deps
+
+            tool custom "true" 1.0.0
+        "#},
+    )
+        .expect("couldn't write dependency file");
+    let config_dir = test_setup::create_dir(proj_dir.clone(), ".dpnd");
+    fs::write(
+        format!("{}/config.toml", config_dir),
+        indoc!{r#"
+            # Hooks.
+            [hooks]
+            post_fetch = "echo a#b"  # Print a `#`.
+        "#},
+    )
+        .expect("couldn't write config file");
+    let mut cmd = test_setup::new_test_cmd(proj_dir);
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("tool:\na#b\n");
+}
+
+#[test]
+// Given the project config file defines a `post_fetch` hook that writes to
+//     `stdout` and `stderr`
+// When the command is run
+// Then the output of the hook is written to `stderr` under the name of the
+//     dependency, in the order that it was written
+fn fetch_hook_output_written_to_stderr() {
+    let root_test_dir =
+        test_setup::create_root_dir("fetch_hook_output_written_to_stderr");
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        indoc!{r#"
+            deps
+
+            tool custom "true" 1.0.0
+        "#},
+    )
+        .expect("couldn't write dependency file");
+    let config_dir = test_setup::create_dir(proj_dir.clone(), ".dpnd");
+    fs::write(
+        format!("{}/config.toml", config_dir),
+        indoc!{r#"
+            [hooks]
+            post_fetch = "echo out; echo err >&2; echo $DPND_DEP_NAME"
+        "#},
+    )
+        .expect("couldn't write config file");
+    let mut cmd = test_setup::new_test_cmd(proj_dir);
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("tool:\nout\nerr\ntool\n");
+}
+
+#[test]
+// Given the dependency file defines dependencies that need other dependencies
+//     with `needs`, and the project config file defines a `pre_fetch` hook
+// When the command is run
+// Then each dependency is fetched after the dependencies that it needs
+fn needed_deps_installed_first() {
+    let root_test_dir =
+        test_setup::create_root_dir("needed_deps_installed_first");
+    let files_dir = test_setup::create_dir(root_test_dir.clone(), "files");
+    fs::write(format!("{}/script.sh", files_dir), SCRIPT_CONTS)
+        .expect("couldn't write test file");
+    let proj_dir = test_setup::create_dir(root_test_dir.clone(), "proj");
+    let source = format!("file://{}/script.sh", files_dir);
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        formatdoc!{
+            "
+                deps
+
+                alpha file {src} {sha} needs=zeta
+                mid file {src} {sha} needs=alpha,zeta
+                zeta file {src} {sha}
+            ",
+            src = source,
+            sha = SCRIPT_SHA256,
+        },
+    )
+        .expect("couldn't write dependency file");
+    let config_dir = test_setup::create_dir(proj_dir.clone(), ".dpnd");
+    fs::write(
+        format!("{}/config.toml", config_dir),
+        indoc!{r#"
+            [hooks]
+            pre_fetch = "echo $DPND_DEP_NAME >> ../hooks.log"
+        "#},
+    )
+        .expect("couldn't write config file");
+    let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+    let hooks_log = fs::read_to_string(format!("{}/hooks.log", root_test_dir))
+        .expect("couldn't read hooks log");
+    assert_eq!(hooks_log, "zeta\nalpha\nmid\n");
+}
+
+#[test]
+// Given the project config file defines an HTTPS proxy and hosts that don't
+//     use a proxy, and `HTTP_PROXY` is defined
+// When the command is run with a `file` dependency
+// Then `curl` is run with the lowercase and uppercase forms of the proxy
+//     variables, taken from the environment and the config file, and with
+//     the user agent of `dpnd`
+fn proxy_config_passed_to_tools() {
+    let root_test_dir =
+        test_setup::create_root_dir("proxy_config_passed_to_tools");
+    let bin_dir = test_setup::create_dir(root_test_dir.clone(), "bin");
+    test_setup::create_executable(
+        &bin_dir,
+        "curl",
+        indoc!{r#"
+            #!/bin/sh
+            proxies="$http_proxy $HTTP_PROXY $https_proxy $HTTPS_PROXY"
+            proxies="$proxies $no_proxy"
+            expected='http://env:8080 http://env:8080'
+            expected="$expected http://conf:3128 http://conf:3128 localhost"
+            if [ "$proxies" != "$expected" ]; then
+                echo "unexpected proxies: $proxies" >&2
+                exit 1
+            fi
+            while [ "$1" != --user-agent ]; do shift; done
+            case "$2" in
+                dpnd/*) ;;
+                *) echo "unexpected user agent: $2" >&2; exit 1 ;;
+            esac
+            while [ "$1" != --output ]; do shift; done
+            echo 'hello, proxy!' > "$2"
+        "#},
+    );
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        "deps\n\n\
+         page file https://example.com/page.txt \
+         cb389e9a5eff87eeb39ae9d2504d3c627c0a80005aff3bb557ad20bb6d342c56\n",
+    )
+        .expect("couldn't write dependency file");
+    let config_dir = test_setup::create_dir(proj_dir.clone(), ".dpnd");
+    fs::write(
+        format!("{}/config.toml", config_dir),
+        indoc!{r#"
+            [proxy]
+            https = "http://conf:3128"
+            no_proxy = "localhost"
+        "#},
+    )
+        .expect("couldn't write config file");
+    let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+    cmd.env("PATH", format!("{}:/bin:/usr/bin", bin_dir));
+    cmd.env("HTTP_PROXY", "http://env:8080");
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+    fs_check::assert_contents(
+        &proj_dir,
+        &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
+            "dpnd.txt" => Node::AnyFile,
+            ".dpnd" => Node::AnyDir,
+            "deps" => Node::Dir(hashmap!{
+                "page" => Node::Dir(hashmap!{
+                    "page.txt" => Node::File("hello, proxy!\n"),
+                }),
+            }),
+        }),
+    );
+}
+
+#[test]
+// Given the project config file enables `accept_new_host_keys`, and the
+//     dependency is fetched over SSH
+// When the command is run
+// Then `ssh` is run with the option to accept the keys of new hosts
+fn new_host_keys_accepted_over_ssh() {
+    let test_deps = test_deps();
+    let Layout{dep_srcs_dir, proj_dir, deps_commit_hashes, deps_file, ..} =
+        test_setup::create(
+            "new_host_keys_accepted_over_ssh",
+            &test_deps,
+            &hashmap!{},
+        );
+    let bin_dir = test_setup::create_dir(dep_srcs_dir.clone(), "bin");
+    let ssh_log = format!("{}/ssh.log", bin_dir);
+    // The fake `ssh` runs the remote command locally instead of connecting to
+    // the host.
+    test_setup::create_executable(
+        &bin_dir,
+        "ssh",
+        &formatdoc!{
+            r#"
+                #!/bin/sh
+                echo "$@" >> '{}'
+                for arg in "$@"; do remote_cmd="$arg"; done
+                exec sh -c "$remote_cmd"
+            "#,
+            ssh_log,
+        },
+    );
+    fs::write(
+        &deps_file,
+        format!(
+            "deps\n\nmy_scripts git ssh://localhost{}/my_scripts.git {}\n",
+            dep_srcs_dir,
+            deps_commit_hashes["my_scripts"][0],
+        ),
+    )
+        .expect("couldn't write dependency file");
+    let config_dir = test_setup::create_dir(proj_dir.clone(), ".dpnd");
+    fs::write(
+        format!("{}/config.toml", config_dir),
+        indoc!{"
+            [ssh]
+            accept_new_host_keys = true
+        "},
+    )
+        .expect("couldn't write config file");
+    let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+    cmd.env("PATH", format!("{}:/bin:/usr/bin", bin_dir));
+    cmd.env_remove("GIT_SSH_COMMAND");
+    cmd.env_remove("GIT_SSH");
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+    let ssh_args = fs::read_to_string(&ssh_log)
+        .expect("couldn't read ssh log");
+    assert!(
+        ssh_args.contains("-o StrictHostKeyChecking=accept-new"),
+        "unexpected ssh arguments: {}",
+        ssh_args,
+    );
+    fs_check::assert_contents(
+        &format!("{}/deps/my_scripts", proj_dir),
+        &Node::Dir(hashmap!{
+            ".git" => Node::AnyDir,
+            "script.sh" => Node::File("echo 'hello world'"),
+        }),
+    );
+}
+
+fn assert_mtime(path: &str, exp_secs: u64) {
+    let mtime =
+        fs::metadata(path)
+            .and_then(|md| md.modified())
+            .unwrap_or_else(|_| panic!(
+                "couldn't get the modification time of '{}'",
+                path,
+            ));
+    let act_secs =
+        mtime.duration_since(UNIX_EPOCH)
+            .expect("modification time was before the Unix epoch")
+            .as_secs();
+
+    assert_eq!(
+        exp_secs,
+        act_secs,
+        "'{}' has an unexpected modification time",
+        path,
+    );
+}
+
+#[test]
+// Given `SOURCE_DATE_EPOCH` isn't defined
+// When the command is run with `--deterministic-mtimes`
+// Then the modification times of the installed files are set to the time of
+//     the installed commit
+fn deterministic_mtimes_use_revision_time() {
+    let test_deps = test_deps();
+    let Layout{dep_srcs_dir, proj_dir, deps_commit_hashes, ..} =
+        test_setup::create(
+            "deterministic_mtimes_use_revision_time",
+            &test_deps,
+            &hashmap!{"my_scripts" => 0},
+        );
+    let commit_time = test_setup::run_cmd(
+        &format!("{}/my_scripts.git", dep_srcs_dir),
+        "git",
+        [
+            "log",
+            "--max-count=1",
+            "--format=%ct",
+            &deps_commit_hashes["my_scripts"][0],
+        ],
+    );
+    let commit_secs: u64 = commit_time.trim().parse()
+        .expect("couldn't parse commit time");
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+            cmd.arg("--deterministic-mtimes");
+
+            cmd.assert()
+        },
+    );
+
+    cmd_result.code(0).stdout("").stderr("");
+    let dep_dir = format!("{}/deps/my_scripts", proj_dir);
+    assert_mtime(&dep_dir, commit_secs);
+    assert_mtime(&format!("{}/script.sh", dep_dir), commit_secs);
+}
+
+// `create_plugin_test` creates a project directory for the test named
+// `test_name` that depends on `my_dep` using an `echo` plugin, and returns the
+// path to the project directory and the `PATH` that the plugin can be found
+// in.
+#[cfg(unix)]
+fn create_plugin_test(test_name: &str) -> (String, String) {
+    let root_test_dir = test_setup::create_root_dir(test_name);
+    let plugins_dir = test_setup::create_dir(root_test_dir.clone(), "plugins");
+    test_setup::create_plugin(
+        &plugins_dir,
+        "echo",
+        indoc!{r#"
+            #!/bin/sh
+            case "$1" in
+                fetch)
+                    mkdir .echo
+                    echo "$2" > source.txt
+                    ;;
+                change-version)
+                    echo "$2" > version.txt
+                    ;;
+                revision)
+                    echo '{"revision": "rev1", "time": 1000000000}'
+                    ;;
+                metadata-paths)
+                    echo '[".echo"]'
+                    ;;
+            esac
+        "#},
+    );
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        indoc!{"
+            deps
+
+            my_dep echo some_source v1
+        "},
+    )
+        .expect("couldn't write dependency file");
+
+    (proj_dir, format!("{}:/bin:/usr/bin", plugins_dir))
+}
+
+#[test]
+#[cfg(unix)]
+// Given the dependency file specifies a dependency that uses a plugin tool
+// When the command is run
+// Then the dependency is fetched using the plugin
+fn plugin_dep_pulled_correctly() {
+    let (proj_dir, path_var) =
+        create_plugin_test("plugin_dep_pulled_correctly");
+    let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+    cmd.env("PATH", path_var);
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+    fs_check::assert_contents(
+        &proj_dir,
+        &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
+            "dpnd.txt" => Node::AnyFile,
+            "deps" => Node::Dir(hashmap!{
+                "my_dep" => Node::Dir(hashmap!{
+                    ".echo" => Node::AnyDir,
+                    "source.txt" => Node::File("some_source\n"),
+                    "version.txt" => Node::File("v1\n"),
+                }),
+            }),
+        }),
+    );
+}
+
+#[test]
+#[cfg(unix)]
+// Given the dependency file specifies a dependency that uses a plugin tool
+// When the command is run with `--vendor`
+// Then the metadata of the plugin is removed and the revision reported by the
+//     plugin is recorded
+fn plugin_dep_vendored_correctly() {
+    let (proj_dir, path_var) =
+        create_plugin_test("plugin_dep_vendored_correctly");
+    let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+    cmd.env("PATH", path_var);
+    cmd.arg("--vendor");
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+    fs_check::assert_contents(
+        &proj_dir,
+        &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
+            "dpnd.txt" => Node::AnyFile,
+            "deps" => Node::Dir(hashmap!{
+                "my_dep" => Node::Dir(hashmap!{
+                    "VENDOR.txt" => Node::File(indoc!{"
+                        tool: echo
+                        source: some_source
+                        version: v1
+                        revision: rev1
+                    "}),
+                    "source.txt" => Node::File("some_source\n"),
+                    "version.txt" => Node::File("v1\n"),
+                }),
+            }),
+        }),
+    );
+}
+
+#[test]
+// Given the dependency file specifies a `git-file` dependency on a file in a
+//     repository that contains other files
+// When the command is run
+// Then only the selected file is checked out
+fn git_file_dep_pulled_correctly() {
+    let test_deps = test_deps();
+    let layout = test_setup::create(
+        "git_file_dep_pulled_correctly",
+        &test_deps,
+        &hashmap!{},
+    );
+    let Layout{dep_srcs_dir, proj_dir, deps_file, ..} = layout;
+    fs::write(
+        &deps_file,
+        indoc!{"
+            deps
+
+            sh git-file git://localhost/all_scripts.git master path=script.sh
+        "},
+    )
+        .expect("couldn't write dependency file");
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+
+            cmd.assert()
+        },
+    );
+
+    cmd_result.code(0).stdout("").stderr("");
+    fs_check::assert_contents(
+        &proj_dir,
+        &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
+            "dpnd.txt" => Node::AnyFile,
+            "deps" => Node::Dir(hashmap!{
+                "sh" => Node::Dir(hashmap!{
+                    ".git" => Node::AnyDir,
+                    "script.sh" => Node::File("echo 'hello, all!'"),
+                }),
+            }),
+        }),
+    );
+}
+
+// `create_resolve_test` creates a project for the test named `test_name` that
+// depends on a branch and on an abbreviated commit hash of `my_scripts`, and
+// returns the test layout along with the contents of the dependency file.
+fn create_resolve_test(test_name: &str) -> (Layout, String) {
+    let test_deps = test_deps();
+    let layout = test_setup::create(test_name, &test_deps, &hashmap!{});
+    let deps_file_conts = formatdoc!{
+        "
+            deps
+
+            new_scripts git git://localhost/my_scripts.git master
+            old_scripts git git://localhost/my_scripts.git {}
+        ",
+        &layout.deps_commit_hashes["my_scripts"][0][..7],
+    };
+    fs::write(&layout.deps_file, &deps_file_conts)
+        .expect("couldn't write dependency file");
+
+    (layout, deps_file_conts)
+}
+
+#[test]
+// Given the dependency file contains a valid dependency
+// When the command is run with `--output-dir`
+// Then the dependency and the state file are written to the given directory,
+//     instead of to the output directory of the dependency file
+fn output_dir_flag_overrides_output_dir() {
+    let test_deps = test_deps();
+    let Layout{dep_srcs_dir, proj_dir, ..} = test_setup::create(
+        "output_dir_flag_overrides_output_dir",
+        &test_deps,
+        &hashmap!{},
+    );
+    let deps_file_conts = indoc!{"
+        deps
+
+        my_scripts git git://localhost/my_scripts.git master
+    "};
+    fs::write(format!("{}/dpnd.txt", proj_dir), deps_file_conts)
+        .expect("couldn't write dependency file");
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+            cmd.args(["--output-dir", "scratch"]);
+
+            cmd.assert()
+        },
+    );
+
+    cmd_result.code(0).stdout("").stderr("");
+    fs_check::assert_contents(
+        &proj_dir,
+        &Node::Dir(hashmap!{
+            "dpnd.txt" => Node::File(deps_file_conts),
+            "scratch" => Node::Dir(hashmap!{
+                "current_dpnd.txt" => Node::AnyFile,
+                "my_scripts" => Node::Dir(hashmap!{
+                    ".git" => Node::AnyDir,
+                    "script.sh" => Node::File("echo 'hello, world!'"),
+                }),
+            }),
+        }),
+    );
+}
+
+#[test]
+// Given the dependency file contains a dependency that has been installed
+// When the dependency is renamed in the dependency file and the command is
+//     run without access to the source of the dependency
+// Then the output directory of the dependency is renamed
+fn renamed_dep_moved_without_fetching() {
+    let test_deps = test_deps();
+    let Layout{dep_srcs_dir, proj_dir, deps_file, ..} = test_setup::create(
+        "renamed_dep_moved_without_fetching",
+        &test_deps,
+        &hashmap!{},
+    );
+    fs::write(
+        &deps_file,
+        indoc!{"
+            deps
+
+            my_scripts git git://localhost/my_scripts.git master
+        "},
+    )
+        .expect("couldn't write dependency file");
+    let install_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || test_setup::new_test_cmd(proj_dir.clone()).assert(),
+    );
+    install_result.code(0).stdout("").stderr("");
+    let deps_file_conts = indoc!{"
+        deps
+
+        scripts git git://localhost/my_scripts.git master
+    "};
+    fs::write(&deps_file, deps_file_conts)
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+    fs_check::assert_contents(
+        &proj_dir,
+        &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
+            "dpnd.txt" => Node::File(deps_file_conts),
+            "deps" => Node::Dir(hashmap!{
+                "scripts" => Node::Dir(hashmap!{
+                    ".git" => Node::AnyDir,
+                    "script.sh" => Node::File("echo 'hello, world!'"),
+                }),
+            }),
+        }),
+    );
+}
+
+#[test]
+// Given the dependency file defines a Git dependency at the `latest` version
+// When the command is run
+// Then the latest commit on the default branch of the dependency is pulled
+fn latest_vsn_pulls_default_branch() {
+    let test_deps = test_deps();
+    let Layout{dep_srcs_dir, proj_dir, deps_commit_hashes, ..} =
+        test_setup::create(
+            "latest_vsn_pulls_default_branch",
+            &test_deps,
+            &hashmap!{},
+        );
+    let deps_file_conts = indoc!{"
+        deps
+
+        my_scripts git git://localhost/my_scripts.git latest
+    "};
+    fs::write(format!("{}/dpnd.txt", proj_dir), deps_file_conts)
+        .expect("couldn't write dependency file");
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+
+            cmd.assert()
+        },
+    );
+
+    cmd_result.code(0).stdout("").stderr("");
+    fs_check::assert_contents(
+        &proj_dir,
+        &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
+            "dpnd.txt" => Node::File(deps_file_conts),
+            "deps" => Node::Dir(hashmap!{
+                "my_scripts" => Node::Dir(hashmap!{
+                    ".git" => Node::AnyDir,
+                    "script.sh" => Node::File("echo 'hello, world!'"),
+                }),
+            }),
+        }),
+    );
+    let dep_dir = format!("{}/deps/my_scripts", proj_dir);
+    let revision = test_setup::run_cmd(&dep_dir, "git", ["rev-parse", "HEAD"]);
+    assert_eq!(revision.trim(), deps_commit_hashes["my_scripts"][1]);
+}
+
+#[test]
+// Given a directory without a dependency file
+// When the command is run with `--stdin`, and a dependency specification is
+//     written to `stdin`
+// Then the dependencies in the specification are pulled to the output
+//     directory of the specification, relative to the current directory
+fn deps_pulled_from_stdin_spec() {
+    let test_deps = test_deps();
+    let Layout{dep_srcs_dir, proj_dir, deps_file, ..} = test_setup::create(
+        "deps_pulled_from_stdin_spec",
+        &test_deps,
+        &hashmap!{},
+    );
+    fs::remove_file(deps_file)
+        .expect("couldn't remove dependency file");
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+            cmd.arg("--stdin");
+            cmd.write_stdin(indoc!{"
+                fetched
+
+                my_scripts git git://localhost/my_scripts.git master
+            "});
+
+            cmd.assert()
+        },
+    );
+
+    cmd_result.code(0).stdout("").stderr("");
+    fs_check::assert_contents(
+        &proj_dir,
+        &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
+            "fetched" => Node::Dir(hashmap!{
+                "my_scripts" => Node::Dir(hashmap!{
+                    ".git" => Node::AnyDir,
+                    "script.sh" => Node::File("echo 'hello, world!'"),
+                }),
+            }),
+        }),
+    );
+}
+
+#[test]
+// Given the dependency file defines a dependency whose source is only
+//     available if environment variables are set for the dependency
+// When the command is run
+// Then the dependency is pulled using the environment variables, and the
+//     variables aren't recorded in the state file
+fn dep_pulled_with_dep_env() {
+    let test_deps = test_deps();
+    let Layout{dep_srcs_dir, proj_dir, deps_commit_hashes, ..} =
+        test_setup::create("dep_pulled_with_dep_env", &test_deps, &hashmap!{});
+    // `GIT_CONFIG_*` variables add configuration to Git commands; here they
+    // redirect the unavailable source to the test server.
+    let deps_file_conts = formatdoc!{
+        "
+            deps
+
+            my_scripts git git://example.invalid/my_scripts.git {} \
+                env.GIT_CONFIG_COUNT=1 \
+                env.GIT_CONFIG_KEY_0=url.git://localhost/.insteadOf \
+                env.GIT_CONFIG_VALUE_0=git://example.invalid/
+        ",
+        deps_commit_hashes["my_scripts"][1],
+    };
+    fs::write(format!("{}/dpnd.txt", proj_dir), &deps_file_conts)
+        .expect("couldn't write dependency file");
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+
+            cmd.assert()
+        },
+    );
+
+    cmd_result.code(0).stdout("").stderr("");
+    fs_check::assert_contents(
+        &proj_dir,
+        &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
+            "dpnd.txt" => Node::File(&deps_file_conts),
+            "deps" => Node::Dir(hashmap!{
+                "my_scripts" => Node::Dir(hashmap!{
+                    ".git" => Node::AnyDir,
+                    "script.sh" => Node::File("echo 'hello, world!'"),
+                }),
+            }),
+        }),
+    );
+    let state_file_conts = fs::read_to_string(
+        format!("{}/.dpnd/state/deps/current_dpnd.txt", proj_dir),
+    )
+        .expect("couldn't read state file");
+    assert!(
+        !state_file_conts.contains("GIT_CONFIG"),
+        "state file records the environment: {}",
+        state_file_conts,
+    );
+}
+
+#[test]
+// Given the dependency file defines a dependency whose source is unavailable,
+//     and which has a mirror that is available
+// When the command is run
+// Then the dependency is pulled from the mirror, and the mirror is recorded in
+//     the state file
+fn dep_pulled_from_mirror() {
+    let test_deps = test_deps();
+    let Layout{dep_srcs_dir, proj_dir, deps_commit_hashes, ..} =
+        test_setup::create("dep_pulled_from_mirror", &test_deps, &hashmap!{});
+    let deps_file_conts = formatdoc!{
+        "
+            deps
+
+            my_scripts git git://localhost/missing.git {} \
+                mirrors=git://localhost/my_scripts.git
+        ",
+        deps_commit_hashes["my_scripts"][1],
+    };
+    fs::write(format!("{}/dpnd.txt", proj_dir), &deps_file_conts)
+        .expect("couldn't write dependency file");
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+
+            cmd.assert()
+        },
+    );
+
+    cmd_result.code(0).stdout("").stderr("");
+    fs_check::assert_contents(
+        &proj_dir,
+        &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
+            "dpnd.txt" => Node::File(&deps_file_conts),
+            "deps" => Node::Dir(hashmap!{
+                "my_scripts" => Node::Dir(hashmap!{
+                    ".git" => Node::AnyDir,
+                    "script.sh" => Node::File("echo 'hello, world!'"),
+                }),
+            }),
+        }),
+    );
+    let state_file_conts = fs::read_to_string(
+        format!("{}/.dpnd/state/deps/current_dpnd.txt", proj_dir),
+    )
+        .expect("couldn't read state file");
+    assert!(
+        state_file_conts.contains(
+            " fetched_from=git://localhost/my_scripts.git ",
+        ),
+        "state file doesn't record the mirror: {}",
+        state_file_conts,
+    );
+}
+
+#[test]
+// Given the dependency file defines a dependency whose source is quoted and
+//     contains a space, whose definition is continued on the next line, and
+//     which is followed by a comment
+// When the command is run
+// Then the dependency is pulled to the correct location with the correct
+//     contents
+fn quoted_dep_source_pulled_correctly() {
+    let test_deps = test_deps();
+    let Layout{dep_srcs_dir, proj_dir, deps_commit_hashes, ..} =
+        test_setup::create(
+            "quoted_dep_source_pulled_correctly",
+            &test_deps,
+            &hashmap!{},
+        );
+    let src = format!("{}/my scripts.git", dep_srcs_dir);
+    fs::rename(format!("{}/my_scripts.git", dep_srcs_dir), &src)
+        .expect("couldn't rename dependency source");
+    let deps_file_conts = formatdoc!{
+        r#"
+            deps
+
+            my_scripts git "{}" \
+                {} # The first version.
+        "#,
+        src,
+        deps_commit_hashes["my_scripts"][0],
+    };
+    fs::write(format!("{}/dpnd.txt", proj_dir), &deps_file_conts)
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+    fs_check::assert_contents(
+        &proj_dir,
+        &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
+            "dpnd.txt" => Node::File(&deps_file_conts),
+            "deps" => Node::Dir(hashmap!{
+                "my_scripts" => Node::Dir(hashmap!{
+                    ".git" => Node::AnyDir,
+                    "script.sh" => Node::File("echo 'hello world'"),
+                }),
+            }),
+        }),
+    );
+}
+
+#[test]
+// Given the dependency file defines a dependency whose source is available
+// When the `validate` command is run
+// Then the command succeeds without printing anything
+fn validate_valid_deps_file() {
+    let test_deps = test_deps();
+    let Layout{dep_srcs_dir, proj_dir, ..} = create_test_setup_and_run_tool(
+        "validate_valid_deps_file",
+        &test_deps,
+        hashmap!{"my_scripts" => 0},
+    );
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd =
+                test_setup::new_test_subcmd(proj_dir.clone(), "validate");
+            cmd.arg("--recursive");
+
+            cmd.assert()
+        },
+    );
+
+    cmd_result.code(0).stdout("").stderr("");
+}
+
+#[test]
+// Given the tool was just run with an old version of a dependency, and then
+//     another dependency was added to the dependency file
+// When the `upgrade` command is run with the first dependency and a version
+// Then the version of the first dependency is changed in the dependency file
+//     and the new version is installed, and the other dependency isn't
+//     installed
+fn upgrade_to_version_installs_only_upgraded_dep() {
+    let test_deps = test_deps();
+    let Layout{dep_srcs_dir, proj_dir, deps_commit_hashes, deps_file, ..} =
+        create_test_setup_and_run_tool(
+            "upgrade_to_version_installs_only_upgraded_dep",
+            &test_deps,
+            hashmap!{"my_scripts" => 0},
+        );
+    let hashes = &deps_commit_hashes["my_scripts"];
+    let old_deps_file_conts = test_setup::write_test_deps_file(
+        &deps_file,
+        &deps_commit_hashes,
+        &hashmap!{"my_scripts" => 0, "your_scripts" => 0},
+    );
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd =
+                test_setup::new_test_subcmd(proj_dir.clone(), "upgrade");
+            cmd.args(["my_scripts", &hashes[1]]);
+
+            cmd.assert()
+        },
+    );
+
+    cmd_result
+        .code(0)
+        .stdout(format!("my_scripts: {} -> {}\n", hashes[0], hashes[1]))
+        .stderr("");
+    let deps_file_conts =
+        old_deps_file_conts.replace(&hashes[0], &hashes[1]);
+    fs_check::assert_contents(
+        &proj_dir,
+        &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
+            "dpnd.txt" => Node::File(&deps_file_conts),
+            "deps" => Node::Dir(hashmap!{
+                "my_scripts" => Node::Dir(hashmap!{
+                    ".git" => Node::AnyDir,
+                    "script.sh" => Node::File("echo 'hello, world!'"),
+                }),
+            }),
+        }),
+    );
+}
+
+#[test]
+// Given the tool was just run with a dependency that's defined in an included
+//     file
+// When the `upgrade` command is run with the dependency and a version
+// Then the version of the dependency is changed in the included file and the
+//     new version is installed
+fn upgrade_included_dep() {
+    let root_test_dir = test_setup::create_root_dir("upgrade_included_dep");
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    let deps_file_conts = "deps\n\ninclude extra.txt\n";
+    fs::write(format!("{}/dpnd.txt", proj_dir), deps_file_conts)
+        .expect("couldn't write dependency file");
+    let included_file = format!("{}/extra.txt", proj_dir);
+    fs::write(
+        &included_file,
+        indoc!{r#"
+            tool custom "printf %s {version} > {out_dir}/version.txt" 1.0.0
+        "#},
+    )
+        .expect("couldn't write included file");
+    test_setup::new_test_cmd(proj_dir.clone()).assert().code(0);
+    let mut cmd = test_setup::new_test_subcmd(proj_dir.clone(), "upgrade");
+    cmd.args(["tool", "2.0.0"]);
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("tool: 1.0.0 -> 2.0.0\n").stderr("");
+    fs_check::assert_contents(
+        &proj_dir,
+        &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
+            "dpnd.txt" => Node::File(deps_file_conts),
+            "extra.txt" => Node::File(indoc!{r#"
+                tool custom "printf %s {version} > {out_dir}/version.txt" 2.0.0
+            "#}),
+            "deps" => Node::Dir(hashmap!{
+                "tool" => Node::Dir(hashmap!{
+                    ".dpnd_custom" => Node::AnyFile,
+                    "version.txt" => Node::File("2.0.0"),
+                }),
+            }),
+        }),
+    );
+}
+
+#[test]
+// Given the tool was just run with an old version of a dependency, and then
+//     the dependency was upgraded and another dependency was added to the
+//     dependency file
+// When the command is run with the name of the first dependency
+// Then the newer version of the first dependency is installed, and the other
+//     dependency isn't installed
+fn install_named_dep_installs_only_named_dep() {
+    let test_deps = test_deps();
+    let Layout{dep_srcs_dir, proj_dir, deps_commit_hashes, deps_file, ..} =
+        create_test_setup_and_run_tool(
+            "install_named_dep_installs_only_named_dep",
+            &test_deps,
+            hashmap!{"my_scripts" => 0},
+        );
+    test_setup::write_test_deps_file(
+        &deps_file,
+        &deps_commit_hashes,
+        &hashmap!{"my_scripts" => 1, "your_scripts" => 0},
+    );
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+            cmd.arg("my_scripts");
+
+            cmd.assert()
+        },
+    );
+
+    cmd_result.code(0).stdout("").stderr("");
+    fs_check::assert_contents(
+        &proj_dir,
+        &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
+            "dpnd.txt" => Node::AnyFile,
+            "deps" => Node::Dir(hashmap!{
+                "my_scripts" => Node::Dir(hashmap!{
+                    ".git" => Node::AnyDir,
+                    "script.sh" => Node::File("echo 'hello, world!'"),
+                }),
+            }),
+        }),
+    );
+}
+
+#[test]
+// Given the tool was just run with an old version of a dependency whose
+//     source doesn't have any tags
+// When the `upgrade` command is run with the dependency and no version
+// Then the dependency is upgraded to the commit that the default branch of
+//     its source refers to
+fn upgrade_without_version_uses_latest_commit() {
+    let test_deps = test_deps();
+    let Layout{dep_srcs_dir, proj_dir, deps_commit_hashes, ..} =
+        create_test_setup_and_run_tool(
+            "upgrade_without_version_uses_latest_commit",
+            &test_deps,
+            hashmap!{"my_scripts" => 0},
+        );
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd =
+                test_setup::new_test_subcmd(proj_dir.clone(), "upgrade");
+            cmd.arg("my_scripts");
+
+            cmd.assert()
+        },
+    );
+
+    let hashes = &deps_commit_hashes["my_scripts"];
+    cmd_result
+        .code(0)
+        .stdout(format!("my_scripts: {} -> {}\n", hashes[0], hashes[1]))
+        .stderr("");
+    let script = fs::read_to_string(
+        format!("{}/deps/my_scripts/script.sh", proj_dir),
+    )
+        .expect("couldn't read script");
+    assert_eq!(script, "echo 'hello, world!'");
+}
+
+#[test]
+// Given the tool was just run with a dependency whose version is a branch
+// When the `pin` command is run without access to the source of the
+//     dependency
+// Then the version of the dependency in the dependency file is replaced by
+//     the installed commit, other lines are kept, and the dependency is still
+//     treated as installed
+fn pin_replaces_branch_with_installed_commit() {
+    let test_deps = test_deps();
+    let Layout{dep_srcs_dir, proj_dir, deps_commit_hashes, deps_file, ..} =
+        test_setup::create(
+            "pin_replaces_branch_with_installed_commit",
+            &test_deps,
+            &hashmap!{},
+        );
+    fs::write(
+        &deps_file,
+        indoc!{"
+            deps
+
+            # Scripts.
+            my_scripts git git://localhost/my_scripts.git master owner=ops
+        "},
+    )
+        .expect("couldn't write dependency file");
+    let install_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || test_setup::new_test_cmd(proj_dir.clone()).assert(),
+    );
+    install_result.code(0).stdout("").stderr("");
+    let mut cmd = test_setup::new_test_subcmd(proj_dir.clone(), "pin");
+
+    let cmd_result = cmd.assert();
+
+    let hash = &deps_commit_hashes["my_scripts"][1];
+    cmd_result
+        .code(0)
+        .stdout(format!("my_scripts: master -> {}\n", hash))
+        .stderr("");
+    let deps_file_conts =
+        fs::read_to_string(&deps_file).expect("couldn't read dependency file");
+    assert_eq!(
+        deps_file_conts,
+        format!(
+            indoc!{"
+                deps
+
+                # Scripts.
+                my_scripts git git://localhost/my_scripts.git {} owner=ops
+            "},
+            hash,
+        ),
+    );
+    let install_result = test_setup::new_test_cmd(proj_dir).assert();
+    install_result.code(0).stdout("").stderr("");
+}
+
+#[test]
+// Given the dependency file defines a dependency that hasn't been installed
+// When the `fetch` command is run with the dependency
+// Then the dependency is fetched into a temporary directory whose path is
+//     printed, and the output directory isn't created
+fn fetch_pulls_dep_into_tmp_dir() {
+    let test_deps = test_deps();
+    let Layout{dep_srcs_dir, proj_dir, ..} = test_setup::create(
+        "fetch_pulls_dep_into_tmp_dir",
+        &test_deps,
+        &hashmap!{"my_scripts" => 0},
+    );
+
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd =
+                test_setup::new_test_subcmd(proj_dir.clone(), "fetch");
+            cmd.arg("my_scripts");
+
+            cmd.assert()
+        },
+    );
+
+    let output = cmd_result.code(0).stderr("").get_output().stdout.clone();
+    let stdout = String::from_utf8(output).expect("`stdout` wasn't UTF-8");
+    let dir = stdout.trim_end_matches('\n');
+    let script = fs::read_to_string(format!("{}/script.sh", dir))
+        .expect("couldn't read script");
+    fs::remove_dir_all(Path::new(dir).parent().expect("no parent"))
+        .expect("couldn't remove temporary directory");
+    assert_eq!(script, "echo 'hello world'");
+    assert!(!Path::new(&format!("{}/deps", proj_dir)).exists());
+}
+
+#[test]
+// Given the tool was just run
+// When the `exec` command is run with a dependency and a command
+// Then the command is run in the output directory of the dependency and its
+//     exit code is returned
+fn exec_runs_cmd_in_dep_dir() {
+    let test_deps = test_deps();
+    let Layout{proj_dir, ..} = create_test_setup_and_run_tool(
+        "exec_runs_cmd_in_dep_dir",
+        &test_deps,
+        hashmap!{"my_scripts" => 0},
+    );
+    let mut cmd = test_setup::new_test_subcmd(proj_dir, "exec");
+    cmd.args(["my_scripts", "--", "sh", "-c", "sh script.sh; exit 3"]);
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(3).stdout("hello world\n").stderr("");
+}
+
+#[test]
+// Given the tool was just run with an old version of a dependency in the
+//     depencency file and then the dependency was upgraded
+// When the `diff` command is run
+// Then the installed and defined revisions of the dependency, and the commits
+//     between them, are printed and the dependency isn't changed
+fn diff_prints_changes() {
+    let test_deps = test_deps();
+    let Layout{dep_srcs_dir, proj_dir, deps_commit_hashes, deps_file, ..} =
+        create_test_setup_and_run_tool(
+            "diff_prints_changes",
+            &test_deps,
+            hashmap!{"my_scripts" => 0},
+        );
+    test_setup::write_test_deps_file(
+        &deps_file,
+        &deps_commit_hashes,
+        &hashmap!{"my_scripts" => 1},
+    );
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd =
+                test_setup::new_test_subcmd(proj_dir.clone(), "diff");
+
+            cmd.assert()
+        },
+    );
+
+    let hashes = &deps_commit_hashes["my_scripts"];
+    cmd_result
+        .code(0)
+        .stdout(formatdoc!{
+            "
+                my_scripts: {} -> {}
+                    > {} Initial commit
+            ",
+            hashes[0],
+            hashes[1],
+            &hashes[1][..7],
+        })
+        .stderr("");
+    let script_conts =
+        fs::read_to_string(format!("{}/deps/my_scripts/script.sh", proj_dir))
+            .expect("couldn't read dependency");
+    assert_eq!(script_conts, test_deps["my_scripts"][0]["script.sh"]);
+}
+
+#[test]
+// Given the dependency file refers to a branch and an abbreviated commit hash
+// When the `resolve` command is run
+// Then the exact revision of each dependency is printed and no dependencies
+//     are installed
+fn resolve_prints_revisions() {
+    let (layout, deps_file_conts) =
+        create_resolve_test("resolve_prints_revisions");
+    let Layout{dep_srcs_dir, proj_dir, deps_commit_hashes, ..} = layout;
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd =
+                test_setup::new_test_subcmd(proj_dir.clone(), "resolve");
+
+            cmd.assert()
+        },
+    );
+
+    cmd_result
+        .code(0)
+        .stdout(formatdoc!{
+            "
+                new_scripts git git://localhost/my_scripts.git {}
+                old_scripts git git://localhost/my_scripts.git {}
+            ",
+            deps_commit_hashes["my_scripts"][1],
+            deps_commit_hashes["my_scripts"][0],
+        })
+        .stderr("");
+    fs_check::assert_contents(
+        &proj_dir,
+        &Node::Dir(hashmap!{
+            "dpnd.txt" => Node::File(&deps_file_conts),
+        }),
+    );
+}
+
+#[test]
+// Given the dependency file refers to a branch and an abbreviated commit hash
+// When the `resolve` command is run with `--format json`
+// Then the exact revision of each dependency is printed as JSON
+fn resolve_prints_revisions_as_json() {
+    let (layout, _) = create_resolve_test("resolve_prints_revisions_as_json");
+    let Layout{dep_srcs_dir, proj_dir, deps_commit_hashes, ..} = layout;
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd =
+                test_setup::new_test_subcmd(proj_dir.clone(), "resolve");
+            cmd.args(["--format", "json"]);
+
+            cmd.assert()
+        },
+    );
+
+    let hashes = &deps_commit_hashes["my_scripts"];
+    cmd_result
+        .code(0)
+        .stdout(formatdoc!{
+            r#"
+                [
+                  {{
+                    "name": "new_scripts",
+                    "owner": null,
+                    "revision": "{}",
+                    "source": "git://localhost/my_scripts.git",
+                    "tool": "git",
+                    "version": "master"
+                  }},
+                  {{
+                    "name": "old_scripts",
+                    "owner": null,
+                    "revision": "{}",
+                    "source": "git://localhost/my_scripts.git",
+                    "tool": "git",
+                    "version": "{}"
+                  }}
+                ]
+            "#,
+            hashes[1],
+            hashes[0],
+            &hashes[0][..7],
+        })
+        .stderr("");
+}
+
+#[test]
+// Given the dependencies in the dependency file have been installed
+// When the command is run with `--check-only`
+// Then the command succeeds without output
+fn check_only_after_install() {
+    let test_deps = test_deps();
+    let Layout{dep_srcs_dir, proj_dir, ..} =
+        test_setup::create(
+            "check_only_after_install",
+            &test_deps,
+            &hashmap!{"my_scripts" => 1},
+        );
+    test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+
+            cmd.assert().code(0).stdout("").stderr("");
+        },
+    );
+    let mut cmd = test_setup::new_test_cmd(proj_dir);
+    cmd.arg("--check-only");
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+}
+
+// `create_hook_test` creates a project directory for the test named
+// `test_name` and returns its path.
+fn create_hook_test(test_name: &str) -> String {
+    let root_test_dir = test_setup::create_root_dir(test_name);
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        indoc!{"
+            target/deps
+
+            my-scripts git git://localhost/my_scripts.git master
+            your.scripts git git://localhost/your_scripts.git master
+        "},
+    )
+        .expect("couldn't write dependency file");
+
+    proj_dir
+}
+
+#[test]
+// Given a dependency file
+// When the `hook direnv` command is run
+// Then a snippet that checks dependencies and exports their paths is printed
+fn hook_direnv_exports_dep_paths() {
+    let proj_dir = create_hook_test("hook_direnv_exports_dep_paths");
+    let mut cmd = test_setup::new_test_subcmd(proj_dir.clone(), "hook");
+    cmd.arg("direnv");
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(0)
+        .stdout(formatdoc!{
+            "
+                watch_file '{proj_dir}/dpnd.txt'
+                dpnd install --check-only >/dev/null 2>&1 || dpnd install
+                export DPND_DEPS_DIR='{deps_dir}'
+                export DPND_DEP_MY_SCRIPTS='{deps_dir}/my-scripts'
+                export DPND_DEP_YOUR_SCRIPTS='{deps_dir}/your.scripts'
+            ",
+            proj_dir = proj_dir,
+            deps_dir = format!("{}/target/deps", proj_dir),
+        })
+        .stderr("");
+}
+
+#[test]
+// Given a dependency file
+// When the `hook mise` command is run
+// Then a snippet that checks dependencies and exports their paths is printed
+fn hook_mise_exports_dep_paths() {
+    let proj_dir = create_hook_test("hook_mise_exports_dep_paths");
+    let mut cmd = test_setup::new_test_subcmd(proj_dir, "hook");
+    cmd.arg("mise");
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(0)
+        .stdout(indoc!{r#"
+            [env]
+            DPND_DEPS_DIR = "{{config_root}}/target/deps"
+            DPND_DEP_MY_SCRIPTS = "{{config_root}}/target/deps/my-scripts"
+            DPND_DEP_YOUR_SCRIPTS = "{{config_root}}/target/deps/your.scripts"
+
+            [hooks]
+            enter = "dpnd install --check-only >/dev/null 2>&1 || dpnd install"
+        "#})
+        .stderr("");
+}
+
+#[test]
+// Given the dependency file defines an owner for a dependency
+// When the `owners` command is run with the name of the dependency, or with a
+//     path in its output directory
+// Then the owner of the dependency is printed
+fn owners_prints_dep_owner() {
+    let root_test_dir = test_setup::create_root_dir("owners_prints_dep_owner");
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        indoc!{"
+            deps
+
+            my_scripts git git://localhost/my_scripts.git master owner=@build
+            your_scripts git git://localhost/your_scripts.git master
+        "},
+    )
+        .expect("couldn't write dependency file");
+
+    for query in &["my_scripts", "deps/my_scripts/script.sh"] {
+        let mut cmd = test_setup::new_test_subcmd(proj_dir.clone(), "owners");
+        cmd.arg(query);
+
+        let cmd_result = cmd.assert();
+
+        cmd_result.code(0).stdout("@build\n").stderr("");
+    }
+}
+
+// `SCRIPT_CONTS` is longer than a single SHA-256 block, so that the checksum
+// of a multi-block file is checked.
+const SCRIPT_CONTS: &str = "#!/bin/sh
+echo 'hello, world!'
+echo 'hello, world!'
+echo 'hello, world!'
+echo 'hello, world!'
+echo 'hello, world!'
+";
+
+const SCRIPT_SHA256: &str =
+    "46d1764b9c64e9f83e883e5231c7c0dafc81cfbdd56025b45c2627ba67aa034f";
+
+#[test]
+// Given the dependency file specifies a `file` dependency whose version is the
+//     checksum of the file
+// When the command is run
+// Then the file is downloaded into the output directory of the dependency
+fn file_dep_pulled_correctly() {
+    let root_test_dir =
+        test_setup::create_root_dir("file_dep_pulled_correctly");
+    let files_dir = test_setup::create_dir(root_test_dir.clone(), "files");
+    fs::write(format!("{}/script.sh", files_dir), SCRIPT_CONTS)
+        .expect("couldn't write test file");
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        format!(
+            "deps\n\nscript file file://{}/script.sh {}\n",
+            files_dir,
+            SCRIPT_SHA256,
+        ),
+    )
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+    fs_check::assert_contents(
+        &proj_dir,
+        &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
+            "dpnd.txt" => Node::AnyFile,
+            "deps" => Node::Dir(hashmap!{
+                "script" => Node::Dir(hashmap!{
+                    "script.sh" => Node::File(SCRIPT_CONTS),
+                }),
+            }),
+        }),
+    );
+}
+
+#[test]
+// Given two projects whose dependency files define the same `file`
+//     dependency with `link=true`
+// When the command is run in each project
+// Then the output directory of the dependency in each project is a symlink to
+//     the same entry in the store
+fn linked_deps_share_store_entry() {
+    let root_test_dir =
+        test_setup::create_root_dir("linked_deps_share_store_entry");
+    let files_dir = test_setup::create_dir(root_test_dir.clone(), "files");
+    fs::write(format!("{}/script.sh", files_dir), SCRIPT_CONTS)
+        .expect("couldn't write test file");
+    let store_dir = format!("{}/store", root_test_dir);
+    let mut proj_dirs = vec![];
+    for proj_name in &["proj_a", "proj_b"] {
+        let proj_dir =
+            test_setup::create_dir(root_test_dir.clone(), proj_name);
+        fs::write(
+            format!("{}/dpnd.txt", proj_dir),
+            format!(
+                "deps\n\nscript file file://{}/script.sh {} link=true\n",
+                files_dir,
+                SCRIPT_SHA256,
+            ),
+        )
+            .expect("couldn't write dependency file");
+        proj_dirs.push(proj_dir);
+    }
+
+    for proj_dir in &proj_dirs {
+        let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+        cmd.env("DPND_STORE", &store_dir);
+
+        cmd.assert().code(0).stdout("").stderr("");
+    }
+
+    let mut targets = vec![];
+    for proj_dir in &proj_dirs {
+        let dep_dir = format!("{}/deps/script", proj_dir);
+        let target = fs::read_link(&dep_dir).expect("couldn't read symlink");
+        assert!(target.starts_with(&store_dir), "{}", target.display());
+        let state_file =
+            format!("{}/.dpnd/state/deps/current_dpnd.txt", proj_dir);
+        let state = fs::read_to_string(&state_file)
+            .expect("couldn't read state file");
+        assert!(state.contains(" link=true"), "{}", state);
+        targets.push(target);
+    }
+    assert_eq!(targets[0], targets[1]);
+    let store_entries =
+        fs::read_dir(&store_dir)
+            .expect("couldn't read store")
+            .filter(|entry| {
+                entry.as_ref().is_ok_and(|e| e.path().is_dir())
+            });
+    assert_eq!(store_entries.count(), 1);
+}
+
+#[test]
+// Given two projects whose dependency files define the same slow `custom`
+//     dependency with `link=true`
+// When the command is run in each project at the same time
+// Then the dependency is only fetched once, and the output directory of the
+//     dependency in each project is a symlink to the same entry in the store
+fn linked_dep_fetched_once_by_concurrent_runs() {
+    let root_test_dir = test_setup::create_root_dir(
+        "linked_dep_fetched_once_by_concurrent_runs",
+    );
+    let store_dir = format!("{}/store", root_test_dir);
+    let fetches_file = format!("{}/fetches.txt", root_test_dir);
+    let mut children = vec![];
+    let mut proj_dirs = vec![];
+    for proj_name in &["proj_a", "proj_b"] {
+        let proj_dir =
+            test_setup::create_dir(root_test_dir.clone(), proj_name);
+        fs::write(
+            format!("{}/dpnd.txt", proj_dir),
+            format!(
+                "deps\n\ntool custom \"echo x >> {}; sleep 1\" 1.0.0 \
+                 link=true\n",
+                fetches_file,
+            ),
+        )
+            .expect("couldn't write dependency file");
+        let mut cmd =
+            test_setup::new_test_process(proj_dir.clone(), "install");
+        cmd.env("DPND_STORE", &store_dir)
+            .env("PATH", "/bin:/usr/bin")
+            .arg("--quiet");
+        children.push(cmd.spawn().expect("couldn't start command"));
+        proj_dirs.push(proj_dir);
+    }
+
+    for child in children {
+        let output = child.wait_with_output()
+            .expect("couldn't wait for command");
+        assert_eq!(output.status.code(), Some(0));
+    }
+
+    let fetches = fs::read_to_string(&fetches_file)
+        .expect("couldn't read fetches file");
+    assert_eq!(fetches, "x\n");
+    let targets: Vec<_> =
+        proj_dirs.iter()
+            .map(|proj_dir| {
+                fs::read_link(format!("{}/deps/tool", proj_dir))
+                    .expect("couldn't read symlink")
+            })
+            .collect();
+    assert_eq!(targets[0], targets[1]);
+}
+
+#[test]
+// Given the dependency file specifies a `custom` dependency whose command
+//     writes its version to a file in its output directory
+// When the command is run
+// Then the command is run with the placeholders replaced, and the fetched
+//     version is recorded
+fn custom_dep_fetched_by_command() {
+    let root_test_dir =
+        test_setup::create_root_dir("custom_dep_fetched_by_command");
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        indoc!{r#"
+            deps
+
+            tool custom "printf %s {version} > {out_dir}/version.txt" 1.2.3
+        "#},
+    )
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+    fs_check::assert_contents(
+        &format!("{}/deps/tool", proj_dir),
+        &Node::Dir(hashmap!{
+            ".dpnd_custom" => Node::AnyFile,
+            "version.txt" => Node::File("1.2.3"),
+        }),
+    );
+    let revision_file = format!("{}/deps/tool/.dpnd_custom", proj_dir);
+    let revision = fs::read_to_string(&revision_file)
+        .expect("couldn't read revision file");
+    assert!(revision.starts_with("1.2.3\n"), "{}", revision);
+}
+
+#[test]
+// Given the dependency file specifies an output directory that uses a
+//     backslash as a path separator
+// When the command is run
+// Then the dependencies are installed in the nested output directory
+fn output_dir_with_backslash_separator() {
+    let root_test_dir =
+        test_setup::create_root_dir("output_dir_with_backslash_separator");
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        indoc!{r#"
+            target\deps
+
+            tool custom "printf %s {version} > {out_dir}/version.txt" 1.2.3
+        "#},
+    )
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+    fs_check::assert_contents(
+        &format!("{}/target/deps/tool/version.txt", proj_dir),
+        &Node::File("1.2.3"),
+    );
+}
+
+#[test]
+// Given the dependency file specifies a `file` dependency
+// When the command is run with `--link-copy`
+// Then the dependency is fetched into the store, and installed as a copy of
+//     its store entry
+fn link_copy_installs_copy_of_store_entry() {
+    let root_test_dir =
+        test_setup::create_root_dir("link_copy_installs_copy_of_store_entry");
+    let files_dir = test_setup::create_dir(root_test_dir.clone(), "files");
+    fs::write(format!("{}/script.sh", files_dir), SCRIPT_CONTS)
+        .expect("couldn't write test file");
+    let store_dir = format!("{}/store", root_test_dir);
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        format!(
+            "deps\n\nscript file file://{}/script.sh {}\n",
+            files_dir,
+            SCRIPT_SHA256,
+        ),
+    )
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+    cmd.arg("--link-copy");
+    cmd.env("DPND_STORE", &store_dir);
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+    let dep_dir = format!("{}/deps/script", proj_dir);
+    let md = fs::symlink_metadata(&dep_dir)
+        .expect("couldn't get metadata");
+    assert!(md.is_dir(), "'{}' isn't a directory", dep_dir);
+    fs_check::assert_contents(
+        &dep_dir,
+        &Node::Dir(hashmap!{
+            "script.sh" => Node::File(SCRIPT_CONTS),
+        }),
+    );
+    let state_file =
+        format!("{}/.dpnd/state/deps/current_dpnd.txt", proj_dir);
+    let state = fs::read_to_string(&state_file)
+        .expect("couldn't read state file");
+    assert!(state.contains(" link=copy"), "{}", state);
+    let store_entries =
+        fs::read_dir(&store_dir)
+            .expect("couldn't read store")
+            .filter(|entry| {
+                entry.as_ref().is_ok_and(|e| e.path().is_dir())
+            });
+    assert_eq!(store_entries.count(), 1);
+}
+
+#[test]
+// Given a linked dependency was installed
+// When `prune-cache` is run without limits and the command is run again
+// Then the store entry of the dependency is removed, and the dependency is
+//     fetched into the store again
+fn pruned_linked_dep_reinstalled() {
+    let root_test_dir =
+        test_setup::create_root_dir("pruned_linked_dep_reinstalled");
+    let files_dir = test_setup::create_dir(root_test_dir.clone(), "files");
+    fs::write(format!("{}/script.sh", files_dir), SCRIPT_CONTS)
+        .expect("couldn't write test file");
+    let store_dir = format!("{}/store", root_test_dir);
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        format!(
+            "deps\n\nscript file file://{}/script.sh {} link=true\n",
+            files_dir,
+            SCRIPT_SHA256,
+        ),
+    )
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+    cmd.env("DPND_STORE", &store_dir);
+    cmd.assert().code(0).stdout("").stderr("");
+    let dep_dir = format!("{}/deps/script", proj_dir);
+    let entry = fs::read_link(&dep_dir).expect("couldn't read symlink");
+    let mut cmd = test_setup::new_test_subcmd(proj_dir.clone(), "prune-cache");
+    cmd.env("DPND_STORE", &store_dir);
+    let output = cmd.output().expect("couldn't run `prune-cache`");
+    assert!(output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stdout)
+            .starts_with(&format!("Removed '{}' (", entry.display())),
+        "{}",
+        String::from_utf8_lossy(&output.stdout),
+    );
+    assert!(!entry.exists());
+    let mut cmd = test_setup::new_test_cmd(proj_dir);
+    cmd.env("DPND_STORE", &store_dir);
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+    let script_conts = fs::read_to_string(format!("{}/script.sh", dep_dir))
+        .expect("couldn't read installed script");
+    assert_eq!(script_conts, SCRIPT_CONTS);
+}
+
+#[test]
+// Given the dependency file references file dependencies in reverse order of
+//     their names
+// When the command is run, comments and blank lines are added to the state
+//     file, and the command is run again
+// Then the state file starts with a header, records the dependencies in
+//     order of their names, and the second run doesn't change anything
+fn state_file_sorted_and_tolerates_comments() {
+    let root_test_dir = test_setup::create_root_dir(
+        "state_file_sorted_and_tolerates_comments",
+    );
+    let files_dir = test_setup::create_dir(root_test_dir.clone(), "files");
+    fs::write(format!("{}/script.sh", files_dir), SCRIPT_CONTS)
+        .expect("couldn't write test file");
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    let mut deps_file_conts = "deps\n\n".to_string();
+    for dep_name in &["c", "b", "a"] {
+        let _ = writeln!(
+            deps_file_conts,
+            "{} file file://{}/script.sh {}",
+            dep_name,
+            files_dir,
+            SCRIPT_SHA256,
+        );
+    }
+    fs::write(format!("{}/dpnd.txt", proj_dir), deps_file_conts)
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+    cmd.assert().code(0).stdout("").stderr("");
+    let state_file_path =
+        format!("{}/.dpnd/state/deps/current_dpnd.txt", proj_dir);
+    let state_conts = fs::read_to_string(&state_file_path)
+        .expect("couldn't read state file");
+    assert!(state_conts.starts_with("# "), "{}", state_conts);
+    assert!(state_conts.contains("\n# format: 1\n"), "{}", state_conts);
+    let dep_names: Vec<&str> =
+        state_conts.lines()
+            .filter(|l| !l.starts_with('#'))
+            .map(|l| l.split(' ').next().unwrap_or(""))
+            .collect();
+    assert_eq!(dep_names, vec!["a", "b", "c"]);
+    let a_path = format!("{}/deps/a/script.sh", proj_dir);
+    fs::write(&a_path, "modified").expect("couldn't modify dependency");
+    fs::write(
+        &state_file_path,
+        state_conts.replace("\nb ", "\n\n# A comment.\nb "),
+    )
+        .expect("couldn't write state file");
+    let mut cmd = test_setup::new_test_cmd(proj_dir);
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+    let a_conts = fs::read_to_string(&a_path)
+        .expect("couldn't read dependency");
+    assert_eq!(a_conts, "modified");
+}
+
+#[test]
+// Given a file dependency was installed, and its state file was then moved to
+//     the output directory, where older versions of `dpnd` wrote it
+// When the command is run
+// Then the state file is moved back to the state directory, and the
+//     dependency isn't fetched again
+fn legacy_state_file_moved_to_state_dir() {
+    let root_test_dir = test_setup::create_root_dir(
+        "legacy_state_file_moved_to_state_dir",
+    );
+    let files_dir = test_setup::create_dir(root_test_dir.clone(), "files");
+    fs::write(format!("{}/script.sh", files_dir), SCRIPT_CONTS)
+        .expect("couldn't write test file");
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        format!(
+            "deps\n\nscript file file://{}/script.sh {}\n",
+            files_dir,
+            SCRIPT_SHA256,
+        ),
+    )
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+    cmd.assert().code(0).stdout("").stderr("");
+    let state_file_path =
+        format!("{}/.dpnd/state/deps/current_dpnd.txt", proj_dir);
+    let legacy_state_file_path = format!("{}/deps/current_dpnd.txt", proj_dir);
+    let state_conts = fs::read_to_string(&state_file_path)
+        .expect("couldn't read state file");
+    fs::rename(&state_file_path, &legacy_state_file_path)
+        .expect("couldn't move state file");
+    let script_path = format!("{}/deps/script/script.sh", proj_dir);
+    fs::write(&script_path, "modified").expect("couldn't modify dependency");
+    let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+    fs_check::assert_contents(
+        &format!("{}/deps", proj_dir),
+        &Node::Dir(hashmap!{
+            "script" => Node::Dir(hashmap!{
+                "script.sh" => Node::File("modified"),
+            }),
+        }),
+    );
+    let new_state_conts = fs::read_to_string(&state_file_path)
+        .expect("couldn't read state file");
+    assert_eq!(new_state_conts, state_conts);
+}
+
+#[test]
+// Given the dependency file references a file dependency
+// When the command is run, the output directory of the dependency is removed,
+//     and the command is run again
+// Then the dependency is installed again
+fn removed_dep_reinstalled() {
+    let root_test_dir = test_setup::create_root_dir("removed_dep_reinstalled");
+    let files_dir = test_setup::create_dir(root_test_dir.clone(), "files");
+    fs::write(format!("{}/script.sh", files_dir), SCRIPT_CONTS)
+        .expect("couldn't write test file");
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        format!(
+            "deps\n\nscript file file://{}/script.sh {}\n",
+            files_dir,
+            SCRIPT_SHA256,
+        ),
+    )
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+    cmd.assert().code(0).stdout("").stderr("");
+    let dep_dir = format!("{}/deps/script", proj_dir);
+    fs::remove_dir_all(&dep_dir).expect("couldn't remove dependency");
+    let mut cmd = test_setup::new_test_cmd(proj_dir);
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+    let script_conts = fs::read_to_string(format!("{}/script.sh", dep_dir))
+        .expect("couldn't read installed script");
+    assert_eq!(script_conts, SCRIPT_CONTS);
+}
+
+#[test]
+// Given the dependency file references two file dependencies that have been
+//     installed, the output directory of one has been removed, and the state
+//     file has been corrupted
+// When `repair` is run
+// Then the state file only records the dependency that is still installed
+fn repair_rebuilds_state_file() {
+    let root_test_dir =
+        test_setup::create_root_dir("repair_rebuilds_state_file");
+    let files_dir = test_setup::create_dir(root_test_dir.clone(), "files");
+    fs::write(format!("{}/script.sh", files_dir), SCRIPT_CONTS)
+        .expect("couldn't write test file");
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        format!(
+            "deps\n\n\
+             removed file file://{0}/script.sh {1}\n\
+             script file file://{0}/script.sh {1}\n",
+            files_dir,
+            SCRIPT_SHA256,
+        ),
+    )
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+    cmd.assert().code(0).stdout("").stderr("");
+    fs::remove_dir_all(format!("{}/deps/removed", proj_dir))
+        .expect("couldn't remove dependency");
+    let state_file_path =
+        format!("{}/.dpnd/state/deps/current_dpnd.txt", proj_dir);
+    fs::write(&state_file_path, "corrupted\n")
+        .expect("couldn't corrupt state file");
+    let mut cmd = test_setup::new_test_subcmd(proj_dir.clone(), "repair");
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(0)
+        .stdout(format!(
+            "Added 'script' to '.dpnd/state/deps/current_dpnd.txt', because \
+             revision '{}' is installed\n",
+            SCRIPT_SHA256,
+        ))
+        .stderr("");
+    let state_conts = fs::read_to_string(&state_file_path)
+        .expect("couldn't read state file");
+    let dep_lines: Vec<&str> =
+        state_conts.lines()
+            .filter(|l| !l.starts_with('#'))
+            .collect();
+    assert!(
+        dep_lines.len() == 1 && dep_lines[0].starts_with("script file "),
+        "unexpected state file:\n{}",
+        state_conts,
+    );
+    let mut cmd = test_setup::new_test_cmd(proj_dir);
+    cmd.assert().code(0).stdout("").stderr("");
+}
+
+#[test]
+// Given the store contains entries of 100 bytes that were last used at
+//     different times
+// When `prune-cache --max-size 150` is run with and without `--dry-run`
+// Then the least recently used entries are listed, and are only removed if
+//     `--dry-run` isn't used
+fn prune_cache_removes_least_recently_used_entries() {
+    let root_test_dir = test_setup::create_root_dir(
+        "prune_cache_removes_least_recently_used_entries",
+    );
+    let store_dir =
+        test_setup::create_dir(root_test_dir.clone(), "store");
+    for (name, last_used) in &[("a", 2_000), ("b", 1_000), ("c", 3_000)] {
+        let entry = test_setup::create_dir(store_dir.clone(), name);
+        fs::write(format!("{}/file", entry), [0; 100])
+            .expect("couldn't write store file");
+        let last_used_path = format!("{}.last_used", entry);
+        fs::write(&last_used_path, "").expect("couldn't write use record");
+        fs::File::options()
+            .write(true)
+            .open(&last_used_path)
+            .and_then(|f| {
+                f.set_modified(UNIX_EPOCH + Duration::from_secs(*last_used))
+            })
+            .expect("couldn't set last use time");
+    }
+    let expected_stdout = |action: &str| {
+        format!(
+            "{0} '{1}/b' (100 bytes, last used 1970-01-01T00:16:40Z)\n\
+             {0} '{1}/a' (100 bytes, last used 1970-01-01T00:33:20Z)\n",
+            action,
+            store_dir,
+        )
+    };
+    let mut cmd =
+        test_setup::new_test_subcmd(root_test_dir.clone(), "prune-cache");
+    cmd.env("DPND_STORE", &store_dir);
+    cmd.args(["--max-size", "150", "--dry-run"]);
+    cmd.assert()
+        .code(0)
+        .stdout(expected_stdout("Would remove"))
+        .stderr("");
+    let mut cmd = test_setup::new_test_subcmd(root_test_dir, "prune-cache");
+    cmd.env("DPND_STORE", &store_dir);
+    cmd.args(["--max-size", "150"]);
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(0)
+        .stdout(expected_stdout("Removed"))
+        .stderr("");
+    fs_check::assert_contents(
+        &store_dir,
+        &Node::Dir(hashmap!{
+            "c" => Node::Dir(hashmap!{
+                "file" => Node::AnyFile,
+            }),
+            "c.last_used" => Node::File(""),
+        }),
+    );
+}
+
+#[test]
+// Given the dependency file specifies a `file` dependency
+// When the command is run with `--report`
+// Then the size and fetch time of the dependency, and their totals, are
+//     written to the report as JSON
+fn report_records_fetch_stats() {
+    let root_test_dir =
+        test_setup::create_root_dir("report_records_fetch_stats");
+    let files_dir = test_setup::create_dir(root_test_dir.clone(), "files");
+    fs::write(format!("{}/script.sh", files_dir), SCRIPT_CONTS)
+        .expect("couldn't write test file");
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        format!(
+            "deps\n\nscript file file://{}/script.sh {}\n",
+            files_dir,
+            SCRIPT_SHA256,
+        ),
+    )
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+    cmd.args(["--report", "report.json"]);
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+    let report_conts = fs::read_to_string(format!("{}/report.json", proj_dir))
+        .expect("couldn't read report");
+    let report: JsonValue = serde_json::from_str(&report_conts)
+        .expect("couldn't parse report");
+    let script_size = SCRIPT_CONTS.len() as u64;
+    let dep = &report["deps"][0];
+    assert_eq!(report["deps"].as_array().map(Vec::len), Some(1));
+    assert_eq!(dep["name"], "script");
+    assert_eq!(dep["path"], "deps/script");
+    assert_eq!(dep["bytes"], script_size);
+    assert!(dep["seconds"].is_f64(), "{}", report_conts);
+    assert_eq!(report["total_bytes"], script_size);
+    assert_eq!(report["total_seconds"], dep["seconds"]);
+}
+
+#[test]
+// Given the dependency file contains unsorted and unaligned dependencies,
+//     comments, and extra whitespace
+// When the `fmt` command is run, and then the `fmt` command is run with
+//     `--check`
+// Then the dependency file is rewritten in the canonical form, and the
+//     second command succeeds
+fn fmt_formats_deps_file() {
+    let root_test_dir = test_setup::create_root_dir("fmt_formats_deps_file");
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        indoc!{r#"
+              deps
+
+
+            # Scripts.
+            your_scripts git git://localhost/your_scripts.git   master  # sun
+            # dpnd: revision 4c2a9f0, resolved 2021-03-04
+              my_scripts  git  git://localhost/my_scripts.git "v 1"
+
+            [tools]   tools
+            lint git \
+                    https://example.com/lint.git   v2
+
+        "#},
+    )
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_subcmd(proj_dir.clone(), "fmt");
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+    fs_check::assert_contents(
+        &proj_dir,
+        &Node::Dir(hashmap!{
+            "dpnd.txt" => Node::File(indoc!{r#"
+                deps
+
+                # Scripts.
+                # dpnd: revision 4c2a9f0, resolved 2021-03-04
+                my_scripts   git git://localhost/my_scripts.git   "v 1"
+                your_scripts git git://localhost/your_scripts.git master # sun
+
+                [tools] tools
+                lint git \
+                    https://example.com/lint.git v2
+            "#}),
+        }),
+    );
+
+    let mut cmd = test_setup::new_test_subcmd(proj_dir, "fmt");
+    cmd.arg("--check");
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+}
+
+#[test]
+// Given the dependency file is formatted, and contains a Git dependency with
+//     a source that isn't a valid Git source
+// When the `fmt` command is run with `--check` and `--no-validate-sources`
+// Then the command succeeds
+fn no_validate_sources_accepts_invalid_source() {
+    let root_test_dir = test_setup::create_root_dir(
+        "no_validate_sources_accepts_invalid_source",
+    );
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        indoc!{"
+            deps
+
+            dpnd git htps://github.com/eZanmoto/dpnd.git master
+        "},
+    )
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_subcmd(proj_dir, "fmt");
+    cmd.args(["--check", "--no-validate-sources"]);
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+}
+
+#[test]
+// Given the dependency file starts with a version directive that the current
+//     version of `dpnd` satisfies, and specifies a `file` dependency
+// When the command is run
+// Then the dependency is installed
+fn version_directive_satisfied() {
+    let root_test_dir =
+        test_setup::create_root_dir("version_directive_satisfied");
+    let files_dir = test_setup::create_dir(root_test_dir.clone(), "files");
+    fs::write(format!("{}/script.sh", files_dir), SCRIPT_CONTS)
+        .expect("couldn't write test file");
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        format!(
+            "#!dpnd >=0.1\ndeps\n\nscript file file://{}/script.sh {}\n",
+            files_dir,
+            SCRIPT_SHA256,
+        ),
+    )
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+    fs_check::assert_contents(
+        &proj_dir,
+        &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
+            "dpnd.txt" => Node::AnyFile,
+            "deps" => Node::Dir(hashmap!{
+                "script" => Node::Dir(hashmap!{
+                    "script.sh" => Node::File(SCRIPT_CONTS),
+                }),
+            }),
+        }),
+    );
+}
+
+#[test]
+// Given the dependency file of a project specifies a `file` dependency
+// When the command is run in the parent directory of the project with `-C`
+//     naming the project, and with a relative `--log-file`
+// Then the dependency is installed in the project, and the log file is
+//     written relative to the project
+fn cwd_flag_changes_working_dir() {
+    let root_test_dir =
+        test_setup::create_root_dir("cwd_flag_changes_working_dir");
+    let files_dir = test_setup::create_dir(root_test_dir.clone(), "files");
+    fs::write(format!("{}/script.sh", files_dir), SCRIPT_CONTS)
+        .expect("couldn't write test file");
+    let proj_dir = test_setup::create_dir(root_test_dir.clone(), "proj");
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        format!(
+            "deps\n\nscript file file://{}/script.sh {}\n",
+            files_dir,
+            SCRIPT_SHA256,
+        ),
+    )
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_cmd(root_test_dir);
+    cmd.args(["-C", "proj", "--log-file", "dpnd.log"]);
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+    fs_check::assert_contents(
+        &proj_dir,
+        &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
+            "dpnd.txt" => Node::AnyFile,
+            "dpnd.log" => Node::AnyFile,
+            "deps" => Node::Dir(hashmap!{
+                "script" => Node::Dir(hashmap!{
+                    "script.sh" => Node::File(SCRIPT_CONTS),
+                }),
+            }),
+        }),
+    );
+}
+
+#[test]
+// Given the dependency file specifies a `file` dependency
+// When the command is run with `--log-file`
+// Then the actions that were taken are appended to the log file
+fn log_file_records_actions() {
+    let root_test_dir =
+        test_setup::create_root_dir("log_file_records_actions");
+    let files_dir = test_setup::create_dir(root_test_dir.clone(), "files");
+    fs::write(format!("{}/script.sh", files_dir), SCRIPT_CONTS)
+        .expect("couldn't write test file");
+    let proj_dir = test_setup::create_dir(root_test_dir.clone(), "proj");
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        format!(
+            "deps\n\nscript file file://{}/script.sh {}\n",
+            files_dir,
+            SCRIPT_SHA256,
+        ),
+    )
+        .expect("couldn't write dependency file");
+    let log_file = format!("{}/dpnd.log", root_test_dir);
+    let mut cmd = test_setup::new_test_cmd(proj_dir);
+    cmd.args(["--log-file", &log_file, "--log-level", "debug"]);
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+    let log = fs::read_to_string(&log_file).expect("couldn't read log file");
+    let msgs: Vec<&str> =
+        log.lines()
+            .filter_map(|line| line.split_once(' ').map(|(_, msg)| msg))
+            .collect();
+    assert!(
+        msgs.iter().any(|msg| msg.starts_with("INFO Fetching 'script' from")),
+        "{}",
+        log,
+    );
+    assert!(
+        msgs.iter().any(|msg| msg.starts_with("INFO Fetched 'script' in")),
+        "{}",
+        log,
+    );
+}
+
+#[test]
+// Given a workspace file that lists two projects, each of which defines a
+//     dependency
+// When the command is run with `--workspace` in the workspace directory
+// Then the dependencies of both projects are installed and the result for
+//     each project is printed to `stderr`
+fn workspace_members_installed() {
+    let root_test_dir =
+        test_setup::create_root_dir("workspace_members_installed");
+    let files_dir = test_setup::create_dir(root_test_dir.clone(), "files");
+    fs::write(format!("{}/script.sh", files_dir), SCRIPT_CONTS)
+        .expect("couldn't write test file");
+    let workspace_dir = test_setup::create_dir(root_test_dir, "workspace");
+    fs::write(
+        format!("{}/dpnd_workspace.txt", workspace_dir),
+        "# Projects are installed in this order.\napp\nlibs/lib\n",
+    )
+        .expect("couldn't write workspace file");
+    let libs_dir = test_setup::create_dir(workspace_dir.clone(), "libs");
+    let member_dirs = vec![
+        test_setup::create_dir(workspace_dir.clone(), "app"),
+        test_setup::create_dir(libs_dir, "lib"),
+    ];
+    for member_dir in &member_dirs {
+        fs::write(
+            format!("{}/dpnd.txt", member_dir),
+            format!(
+                "deps\n\nscript file file://{}/script.sh {}\n",
+                files_dir,
+                SCRIPT_SHA256,
+            ),
+        )
+            .expect("couldn't write dependency file");
+    }
+    let mut cmd = test_setup::new_test_subcmd(workspace_dir, "install");
+    cmd.arg("--workspace");
+
+    let cmd_result = cmd.assert();
+
+    let stderr = cmd_result.code(0).stdout("").get_output().stderr.clone();
+    let stderr = String::from_utf8(stderr).expect("stderr isn't UTF-8");
+    assert!(stderr.starts_with("app: ok\nlibs/lib: ok\n"), "{}", stderr);
+    for member_dir in &member_dirs {
+        fs_check::assert_contents(
+            member_dir,
+            &Node::Dir(hashmap!{
+                ".dpnd" => Node::AnyDir,
+                "dpnd.txt" => Node::AnyFile,
+                "deps" => Node::Dir(hashmap!{
+                    "script" => Node::Dir(hashmap!{
+                        "script.sh" => Node::File(SCRIPT_CONTS),
+                    }),
+                }),
+            }),
+        );
+    }
+}
+
+#[test]
+// Given the dependency file specifies a dependency in the `dev` group
+// When the command is run with `--skip dev`, and then without it
+// Then the dependency is only installed by the second run, and its group is
+//     recorded in the state file
+fn skipped_group_not_installed() {
+    let root_test_dir =
+        test_setup::create_root_dir("skipped_group_not_installed");
+    let files_dir = test_setup::create_dir(root_test_dir.clone(), "files");
+    fs::write(format!("{}/script.sh", files_dir), SCRIPT_CONTS)
+        .expect("couldn't write test file");
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    let script_src = format!("file://{}/script.sh", files_dir);
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        format!(
+            "deps\n\nscript file {src} {sum}\n\
+             dev_script file {src} {sum} group=dev\n",
+            src = script_src,
+            sum = SCRIPT_SHA256,
+        ),
+    )
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+    cmd.args(["--skip", "dev"]);
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+    fs_check::assert_contents(
+        &proj_dir,
+        &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
+            "dpnd.txt" => Node::AnyFile,
+            "deps" => Node::Dir(hashmap!{
+                "script" => Node::Dir(hashmap!{
+                    "script.sh" => Node::File(SCRIPT_CONTS),
+                }),
+            }),
+        }),
+    );
+
+    let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+    fs_check::assert_contents(
+        &proj_dir,
+        &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
+            "dpnd.txt" => Node::AnyFile,
+            "deps" => Node::Dir(hashmap!{
+                "script" => Node::Dir(hashmap!{
+                    "script.sh" => Node::File(SCRIPT_CONTS),
+                }),
+                "dev_script" => Node::Dir(hashmap!{
+                    "script.sh" => Node::File(SCRIPT_CONTS),
+                }),
+            }),
+        }),
+    );
+    let state = fs::read_to_string(
+        format!("{}/.dpnd/state/deps/current_dpnd.txt", proj_dir),
+    )
+        .expect("couldn't read state file");
+    let dev_dep_line = format!(
+        "dev_script file {} {} group=dev ",
+        script_src,
+        SCRIPT_SHA256,
+    );
+    assert!(
+        state.contains(&dev_dep_line),
+        "state file doesn't record the group: {}",
+        state,
+    );
+}
+
+#[test]
+// Given dependencies have been installed in a project
+// When the `pack` subcommand is run twice, and the `unpack` subcommand is run
+//     in another project with the same dependency file
+// Then the archives are identical, and the installed dependencies, including
+//     the state file, are restored in the other project
+fn pack_and_unpack_restore_deps() {
+    let root_test_dir =
+        test_setup::create_root_dir("pack_and_unpack_restore_deps");
+    let files_dir = test_setup::create_dir(root_test_dir.clone(), "files");
+    fs::write(format!("{}/script.sh", files_dir), SCRIPT_CONTS)
+        .expect("couldn't write test file");
+    let deps_file_conts = format!(
+        "deps\n\nscript file file://{}/script.sh {}\n",
+        files_dir,
+        SCRIPT_SHA256,
+    );
+    let src_proj_dir = test_setup::create_dir(root_test_dir.clone(), "src");
+    fs::write(format!("{}/dpnd.txt", src_proj_dir), &deps_file_conts)
+        .expect("couldn't write dependency file");
+    let dst_proj_dir = test_setup::create_dir(root_test_dir.clone(), "dst");
+    fs::write(format!("{}/dpnd.txt", dst_proj_dir), &deps_file_conts)
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_cmd(src_proj_dir.clone());
+    cmd.assert().code(0).stdout("").stderr("");
+    let archive_paths = &[
+        format!("{}/deps1.tar", root_test_dir),
+        format!("{}/deps2.tar", root_test_dir),
+    ];
+
+    for archive_path in archive_paths {
+        let mut cmd =
+            test_setup::new_test_subcmd(src_proj_dir.clone(), "pack");
+        cmd.arg(archive_path);
+
+        let cmd_result = cmd.assert();
+
+        cmd_result.code(0).stdout("").stderr("");
+    }
+    let mut cmd = test_setup::new_test_subcmd(dst_proj_dir.clone(), "unpack");
+    cmd.arg(&archive_paths[0]);
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+    let archives: Vec<Vec<u8>> =
+        archive_paths.iter()
+            .map(|path| fs::read(path).expect("couldn't read archive"))
+            .collect();
+    assert!(archives[0] == archives[1], "archives aren't identical");
+    fs_check::assert_contents(
+        &dst_proj_dir,
+        &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
+            "dpnd.txt" => Node::AnyFile,
+            "deps" => Node::Dir(hashmap!{
+                "script" => Node::Dir(hashmap!{
+                    "script.sh" => Node::File(SCRIPT_CONTS),
+                }),
+            }),
+        }),
+    );
+    let mut cmd = test_setup::new_test_cmd(dst_proj_dir);
+    cmd.arg("--check-only");
+    cmd.assert().code(0).stdout("").stderr("");
+}
+
+#[test]
+// Given the dependencies of a project are installed
+// When `export` is run in the project and `import` is run in a project with
+//     the same dependency file
+// Then the archive records the provenance of the dependencies, and the
+//     dependencies are restored in the second project
+fn export_and_import_restore_deps_with_provenance() {
+    let root_test_dir = test_setup::create_root_dir(
+        "export_and_import_restore_deps_with_provenance",
+    );
+    let files_dir = test_setup::create_dir(root_test_dir.clone(), "files");
+    fs::write(format!("{}/script.sh", files_dir), SCRIPT_CONTS)
+        .expect("couldn't write test file");
+    let deps_file_conts = format!(
+        "deps\n\nscript file file://{}/script.sh {}\n",
+        files_dir,
+        SCRIPT_SHA256,
+    );
+    let src_proj_dir = test_setup::create_dir(root_test_dir.clone(), "src");
+    fs::write(format!("{}/dpnd.txt", src_proj_dir), &deps_file_conts)
+        .expect("couldn't write dependency file");
+    let dst_proj_dir = test_setup::create_dir(root_test_dir.clone(), "dst");
+    fs::write(format!("{}/dpnd.txt", dst_proj_dir), &deps_file_conts)
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_cmd(src_proj_dir.clone());
+    cmd.assert().code(0).stdout("").stderr("");
+    let archive_path = format!("{}/bundle.tar", root_test_dir);
+    let mut cmd = test_setup::new_test_subcmd(src_proj_dir, "export");
+    cmd.arg(&archive_path);
+    cmd.assert().code(0).stdout("").stderr("");
+
+    let mut cmd = test_setup::new_test_subcmd(dst_proj_dir.clone(), "import");
+    cmd.arg(&archive_path);
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+    let output =
+        Command::new("tar")
+            .args([
+                "--extract",
+                "--to-stdout",
+                "--file",
+                &archive_path,
+                "dpnd_provenance.json",
+            ])
+            .output()
+            .expect("couldn't extract provenance");
+    let provenance: JsonValue =
+        serde_json::from_slice(&output.stdout)
+            .expect("provenance isn't valid JSON");
+    assert_eq!(
+        provenance["dependencies"],
+        serde_json::json!([
+            {
+                "path": "script",
+                "tool": "file",
+                "source": format!("file://{}/script.sh", files_dir),
+                "version": SCRIPT_SHA256,
+                "revision": SCRIPT_SHA256,
+            },
+        ]),
+    );
+    fs_check::assert_contents(
+        &dst_proj_dir,
+        &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
+            "dpnd.txt" => Node::AnyFile,
+            "deps" => Node::Dir(hashmap!{
+                "script" => Node::Dir(hashmap!{
+                    "script.sh" => Node::File(SCRIPT_CONTS),
+                }),
+            }),
+        }),
+    );
+}
+
+#[test]
+// Given the dependencies of a project were installed while `SOURCE_DATE_EPOCH`
+//     was defined
+// When `list` is run with and without `--verbose`
+// Then each installed dependency is printed, along with the time at which it
+//     was installed and the version of `dpnd` that installed it if
+//     `--verbose` is used
+fn list_verbose_prints_install_details() {
+    let root_test_dir =
+        test_setup::create_root_dir("list_verbose_prints_install_details");
+    let files_dir = test_setup::create_dir(root_test_dir.clone(), "files");
+    fs::write(format!("{}/script.sh", files_dir), SCRIPT_CONTS)
+        .expect("couldn't write test file");
+    let script_src = format!("file://{}/script.sh", files_dir);
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        format!("deps\n\nscript file {} {}\n", script_src, SCRIPT_SHA256),
+    )
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+    cmd.env("SOURCE_DATE_EPOCH", "1000000000");
+    cmd.assert().code(0).stdout("").stderr("");
+    let mut cmd = test_setup::new_test_subcmd(proj_dir.clone(), "list");
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(0)
+        .stdout(format!("script file {} {}\n", script_src, SCRIPT_SHA256))
+        .stderr("");
+    let mut cmd = test_setup::new_test_subcmd(proj_dir, "list");
+    cmd.arg("--verbose");
+    let output = cmd.output().expect("couldn't run `list --verbose`");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let expected_prefix = format!(
+        "script file {} {} revision={} installed_at=2001-09-09T01:46:40Z \
+         dpnd_version={}",
+        script_src,
+        SCRIPT_SHA256,
+        SCRIPT_SHA256,
+        env!("CARGO_PKG_VERSION"),
+    );
+    assert!(stdout.starts_with(&expected_prefix), "{}", stdout);
+}
+
+#[test]
+// Given the dependency file contains comments
+// When the command is run with `--annotate` twice, at different times
+// Then the installed revision of each dependency is recorded in a comment
+//     before it, other comments are kept, and the second run doesn't change
+//     the dependency file
+fn annotate_records_revisions() {
+    let root_test_dir =
+        test_setup::create_root_dir("annotate_records_revisions");
+    let files_dir = test_setup::create_dir(root_test_dir.clone(), "files");
+    fs::write(format!("{}/script.sh", files_dir), SCRIPT_CONTS)
+        .expect("couldn't write test file");
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    let deps_file_path = format!("{}/dpnd.txt", proj_dir);
+    let dep_line = format!(
+        "script file file://{}/script.sh {}\n",
+        files_dir,
+        SCRIPT_SHA256,
+    );
+    fs::write(
+        &deps_file_path,
+        format!("deps\n\n# Scripts for CI.\n{}", dep_line),
+    )
+        .expect("couldn't write dependency file");
+    let exp_deps_file_conts = format!(
+        "deps\n\n\
+         # Scripts for CI.\n\
+         # dpnd: revision {}, resolved 2001-09-09\n\
+         {}",
+        SCRIPT_SHA256,
+        dep_line,
+    );
+
+    for source_date_epoch in &["1000000000", "1100000000"] {
+        let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+        cmd.arg("--annotate");
+        cmd.env("SOURCE_DATE_EPOCH", source_date_epoch);
+
+        let cmd_result = cmd.assert();
+
+        cmd_result.code(0).stdout("").stderr("");
+        let deps_file_conts = fs::read_to_string(&deps_file_path)
+            .expect("couldn't read dependency file");
+        assert_eq!(exp_deps_file_conts, deps_file_conts);
+    }
+}
+
+#[test]
+// Given `GITHUB_STEP_SUMMARY` is set
+// When the command is run with `--ci`
+// Then a summary of the dependencies is appended to `GITHUB_STEP_SUMMARY`
+fn ci_preset_writes_step_summary() {
+    let root_test_dir =
+        test_setup::create_root_dir("ci_preset_writes_step_summary");
+    let files_dir = test_setup::create_dir(root_test_dir.clone(), "files");
+    fs::write(format!("{}/script.sh", files_dir), SCRIPT_CONTS)
+        .expect("couldn't write test file");
+    let summary_path = format!("{}/summary.md", root_test_dir);
+    fs::write(&summary_path, "# Build\n\n")
+        .expect("couldn't write summary file");
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    let script_src = format!("file://{}/script.sh", files_dir);
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        format!("deps\n\nscript file {} {}\n", script_src, SCRIPT_SHA256),
+    )
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_cmd(proj_dir);
+    cmd.env("GITHUB_STEP_SUMMARY", &summary_path);
+    cmd.arg("--ci");
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+    let summary = fs::read_to_string(&summary_path)
+        .expect("couldn't read summary file");
+    assert_eq!(
+        summary,
+        format!(
+            "# Build\n\n\
+             ### Dependencies\n\n\
+             | Name | Tool | Source | Version |\n\
+             | --- | --- | --- | --- |\n\
+             | script | file | {} | {} |\n",
+            script_src,
+            SCRIPT_SHA256,
+        ),
+    );
+}
+
+#[test]
+// Given the dependency file contains a slow and a fast dependency
+// When the command is run with `--ci`
+// Then the fetch time of each dependency is printed after the summary,
+//     slowest first
+fn ci_preset_prints_fetch_times() {
+    let root_test_dir =
+        test_setup::create_root_dir("ci_preset_prints_fetch_times");
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        indoc!{r#"
+            deps
+
+            fast custom "true" 1.0.0
+            slow custom "sleep 0.5" 1.0.0
+        "#},
+    )
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_subcmd(proj_dir, "install");
+    cmd.arg("--ci");
+
+    let cmd_result = cmd.assert();
+
+    let stderr = cmd_result.code(0).stdout("").get_output().stderr.clone();
+    let stderr = String::from_utf8(stderr).expect("stderr isn't UTF-8");
+    let lines: Vec<&str> = stderr.lines().collect();
+    assert_eq!(lines.len(), 3, "{}", stderr);
+    assert!(lines[0].starts_with("2 installed, "), "{}", stderr);
+    assert!(lines[1].starts_with("  slow fetched in "), "{}", stderr);
+    assert!(lines[2].starts_with("  fast fetched in "), "{}", stderr);
+}
+
+#[test]
+// Given the dependency file specifies a dependency with a condition on an
+//     environment variable
+// When the `owners` subcommand is run with and without the variable set
+// Then the dependency is only defined when the variable is set
+fn conditional_dep_depends_on_env() {
+    let root_test_dir =
+        test_setup::create_root_dir("conditional_dep_depends_on_env");
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        indoc!{"
+            deps
+
+            gpu git git://localhost/gpu.git master if=USE_GPU=1 owner=@gpu
+        "},
+    )
+        .expect("couldn't write dependency file");
+
+    let mut cmd = test_setup::new_test_subcmd(proj_dir.clone(), "owners");
+    cmd.env("USE_GPU", "1");
+    cmd.arg("gpu");
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("@gpu\n").stderr("");
+
+    let mut cmd = test_setup::new_test_subcmd(proj_dir, "owners");
+    cmd.env("USE_GPU", "0");
+    cmd.arg("gpu");
+
+    let cmd_result = cmd.assert();
+
+    cmd_result
+        .code(3)
+        .stdout("")
+        .stderr(
+            "'gpu' isn't the name of a dependency, or a path in the \
+             output directory of a dependency\n",
+        );
+}
+
+#[test]
+#[cfg(unix)]
+// Given the dependency file specifies signature verification for a dependency
+//     whose version is a tag signed by a trusted key
+// When the command is run
+// Then the dependency is installed
+fn signed_tag_dep_verified() {
+    let root_test_dir = test_setup::create_root_dir("signed_tag_dep_verified");
+    let gpg_home = test_setup::create_dir(root_test_dir.clone(), "gnupg");
+    let gpg_key = test_setup::create_gpg_key(&gpg_home);
+    let dep_srcs_dir = test_setup::create_dir(root_test_dir.clone(), "deps");
+    let scratch_dir = test_setup::create_dir(root_test_dir.clone(), "scratch");
+    test_setup::init_git_repo(&scratch_dir);
+    fs::write(format!("{}/script.sh", scratch_dir), "echo 'hello, world!'")
+        .expect("couldn't write test file");
+    test_setup::commit_all(&scratch_dir);
+    test_setup::run_cmd_with_env(
+        &scratch_dir,
+        "git",
+        ["tag", "--local-user", &gpg_key, "--message", "v1", "v1"],
+        &[("GNUPGHOME", &gpg_home)],
+    );
+    let bare_repo_dir = format!("{}/my_scripts.git", dep_srcs_dir);
+    test_setup::run_cmd(
+        &scratch_dir,
+        "git",
+        ["clone", "--bare", &scratch_dir, &bare_repo_dir],
+    );
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        indoc!{"
+            deps
+
+            my_scripts git git://localhost/my_scripts.git v1 verify=gpg
+        "},
+    )
+        .expect("couldn't write dependency file");
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+            cmd.env("GNUPGHOME", &gpg_home);
+            cmd.env("DPND_TRUSTED_KEYS", &gpg_key);
+
+            cmd.assert()
+        },
+    );
+
+    cmd_result.code(0).stdout("").stderr("");
+    fs_check::assert_contents(
+        &proj_dir,
+        &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
+            "dpnd.txt" => Node::AnyFile,
+            "deps" => Node::Dir(hashmap!{
+                "my_scripts" => Node::Dir(hashmap!{
+                    ".git" => Node::AnyDir,
+                    "script.sh" => Node::File("echo 'hello, world!'"),
+                }),
+            }),
+        }),
+    );
+}
+
+#[cfg(feature = "test-util")]
+#[test]
+// Given the dependency file specifies a `mock` dependency whose revision is
+//     defined in the file named by `DPND_MOCK_SOURCES`
+// When the command is run
+// Then the files of the revision are written to the output directory of the
+//     dependency
+fn mock_dep_pulled_correctly() {
+    let root_test_dir =
+        test_setup::create_root_dir("mock_dep_pulled_correctly");
+    let mock_sources_path = format!("{}/mock_sources.json", root_test_dir);
+    fs::write(
+        &mock_sources_path,
+        r#"{"scripts": {"v1": {"bin/hello.sh": "echo 'hello, world!'\n"}}}"#,
+    )
+        .expect("couldn't write mock sources file");
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        "deps\n\nscripts mock scripts v1\n",
+    )
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+    cmd.env("DPND_MOCK_SOURCES", &mock_sources_path);
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+    let hello_path = format!("{}/deps/scripts/bin/hello.sh", proj_dir);
+    let hello_conts =
+        fs::read_to_string(&hello_path)
+            .expect("couldn't read fetched file");
+    assert_eq!(hello_conts, "echo 'hello, world!'\n");
+}
+
+// `FAKE_SVN` stands in for `svn`, so that the `svn` tool can be tested
+// without a Subversion server. It supports the commands that the tool runs,
+// and serves each revision of a repository from the subdirectory of the
+// repository's `file://` URL that's named after the revision. `svn status`
+// prints the contents of `.svn/status` in the working copy, without
+// unversioned entries if `--quiet` is passed.
+const FAKE_SVN: &str = r#"#!/bin/sh
+shift
+cmd="$1"
+shift
+case "$cmd" in
+    checkout)
+        if [ ! -d "${4#file://}" ]; then
+            echo "svn: E170000: URL '$4' doesn't exist" >&2
+            exit 1
+        fi
+        mkdir .svn
+        echo "${4#file://}" > .svn/src
+        ;;
+    update)
+        src="$(cat .svn/src)"
+        if [ ! -d "$src/$4" ]; then
+            echo "svn: E160006: No such revision $4" >&2
+            exit 1
+        fi
+        cp -R "$src/$4/." .
+        echo "$4" > .svn/rev
+        ;;
+    info)
+        case "$2" in
+            revision)
+                if [ "$3" = --revision ]; then
+                    echo "$4"
+                else
+                    cat .svn/rev
+                fi
+                ;;
+            last-changed-date)
+                echo 2021-01-01T00:00:00.000000Z
+                ;;
+        esac
+        ;;
+    status)
+        if [ ! -f .svn/status ]; then
+            exit 0
+        elif [ "$1" = --quiet ]; then
+            sed '/^?/d' .svn/status
+        else
+            cat .svn/status
+        fi
+        ;;
+esac
+"#;
+
+// `SvnLayout` describes a test setup whose dependency file defines `lib`, an
+// `svn` dependency at revision 1 of a repository with two revisions. `svn` in
+// `bin_dir` is `FAKE_SVN`.
+pub struct SvnLayout {
+    pub proj_dir: String,
+    pub bin_dir: String,
+    pub repo_url: String,
+}
+
+#[cfg(unix)]
+pub fn create_svn_test_setup(root_test_dir_name: &str) -> SvnLayout {
+    let root_test_dir = test_setup::create_root_dir(root_test_dir_name);
+    let bin_dir = test_setup::create_dir(root_test_dir.clone(), "bin");
+    test_setup::create_executable(&bin_dir, "svn", FAKE_SVN);
+    let repo_dir = test_setup::create_dir(root_test_dir.clone(), "repo");
+    for rev in &["1", "2"] {
+        let rev_dir = test_setup::create_dir(repo_dir.clone(), rev);
+        fs::write(format!("{}/lib.txt", rev_dir), format!("r{}", rev))
+            .expect("couldn't write repository file");
+    }
+    let repo_url = format!("file://{}", repo_dir);
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        format!("deps\n\nlib svn {} 1\n", repo_url),
+    )
+        .expect("couldn't write dependency file");
+
+    SvnLayout{proj_dir, bin_dir, repo_url}
+}
+
+// `new_svn_test_cmd` is like `test_setup::new_test_cmd`, but `FAKE_SVN` is
+// used as `svn`.
+pub fn new_svn_test_cmd(layout: &SvnLayout) -> AssertCommand {
+    let mut cmd = test_setup::new_test_cmd(layout.proj_dir.clone());
+    cmd.env("PATH", format!("{}:/bin:/usr/bin", layout.bin_dir));
+
+    cmd
+}
+
+#[test]
+#[cfg(unix)]
+// Given the dependency file specifies an `svn` dependency
+// When the command is run
+// Then the revision of the dependency is checked out to its output directory
+fn svn_dep_pulled_correctly() {
+    let layout = create_svn_test_setup("svn_dep_pulled_correctly");
+    let mut cmd = new_svn_test_cmd(&layout);
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+    fs_check::assert_contents(
+        &layout.proj_dir,
+        &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
+            "dpnd.txt" => Node::AnyFile,
+            "deps" => Node::Dir(hashmap!{
+                "lib" => Node::Dir(hashmap!{
+                    ".svn" => Node::AnyDir,
+                    "lib.txt" => Node::File("r1"),
+                }),
+            }),
+        }),
+    );
+}
+
+#[test]
+#[cfg(unix)]
+// Given the dependency file specifies an `svn` dependency that was installed,
+//     and then the dependency was upgraded
+// When the command is run
+// Then the new revision of the dependency is checked out to its output
+//     directory
+fn svn_dep_upgraded() {
+    let layout = create_svn_test_setup("svn_dep_upgraded");
+    new_svn_test_cmd(&layout).assert().code(0).stdout("").stderr("");
+    fs::write(
+        format!("{}/dpnd.txt", layout.proj_dir),
+        format!("deps\n\nlib svn {} 2\n", layout.repo_url),
+    )
+        .expect("couldn't write dependency file");
+    let mut cmd = new_svn_test_cmd(&layout);
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+    let conts =
+        fs::read_to_string(format!("{}/deps/lib/lib.txt", layout.proj_dir))
+            .expect("couldn't read dependency file");
+    assert_eq!(conts, "r2");
+}
+
+#[test]
+#[cfg(unix)]
+// Given the dependency file specifies an `svn` dependency that was installed,
+//     the working copy of the dependency contains unversioned files and a
+//     changed state file, and then the dependency was upgraded
+// When the command is run
+// Then the dependency isn't treated as modified, and the new revision is
+//     installed
+fn svn_dep_with_unversioned_files_upgraded() {
+    let layout =
+        create_svn_test_setup("svn_dep_with_unversioned_files_upgraded");
+    new_svn_test_cmd(&layout).assert().code(0).stdout("").stderr("");
+    let dep_dir = format!("{}/deps/lib", layout.proj_dir);
+    fs::write(
+        format!("{}/.svn/status", dep_dir),
+        "?       deps\nM       .dpnd/state/deps/current_dpnd.txt\n",
+    )
+        .expect("couldn't write status file");
+    fs::write(
+        format!("{}/dpnd.txt", layout.proj_dir),
+        format!("deps\n\nlib svn {} 2\n", layout.repo_url),
+    )
+        .expect("couldn't write dependency file");
+    let mut cmd = new_svn_test_cmd(&layout);
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+    let conts = fs::read_to_string(format!("{}/lib.txt", dep_dir))
+        .expect("couldn't read dependency file");
+    assert_eq!(conts, "r2");
+}
+
+#[test]
+#[cfg(unix)]
+// Given the dependency file specifies an `object-store` dependency on an S3
+//     object that's a tar archive
+// When the command is run
+// Then the object is downloaded at the given ETag using `aws`, and extracted
+//     into the output directory of the dependency
+fn object_store_dep_pulled_correctly() {
+    let root_test_dir =
+        test_setup::create_root_dir("object_store_dep_pulled_correctly");
+    let bin_dir = test_setup::create_dir(root_test_dir.clone(), "bin");
+    test_setup::create_executable(
+        &bin_dir,
+        "aws",
+        indoc!{r#"
+            #!/bin/sh
+            if [ "$4 $6 $8" != 'builds app/app.tar.gz "abc123"' ]; then
+                echo "unexpected arguments: $*" >&2
+                exit 1
+            fi
+            mkdir -p "$9.d/assets"
+            echo 'hello, object!' > "$9.d/assets/logo.txt"
+            tar --create --file "$9" --directory "$9.d" assets
+            rm -r "$9.d"
+            echo '{"ETag": "\"abc123\"",'
+            echo ' "LastModified": "2021-01-01T00:00:00+00:00"}'
+        "#},
+    );
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        "deps\n\nartifact object-store s3://builds/app/app.tar.gz abc123\n",
+    )
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+    cmd.env("PATH", format!("{}:/bin:/usr/bin", bin_dir));
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+    fs_check::assert_contents(
+        &proj_dir,
+        &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
+            "dpnd.txt" => Node::AnyFile,
+            "deps" => Node::Dir(hashmap!{
+                "artifact" => Node::Dir(hashmap!{
+                    ".dpnd_object" => Node::File(
+                        "abc123\n2021-01-01T00:00:00+00:00\n",
+                    ),
+                    "assets" => Node::Dir(hashmap!{
+                        "logo.txt" => Node::File("hello, object!\n"),
+                    }),
+                }),
+            }),
+        }),
+    );
+}
+
+#[test]
+// Given the dependency file specifies a dependency that tracks a branch, which
+//     has been installed
+// When a commit is pushed to the branch and the command is run again
+// Then the dependency is fast-forwarded to the new commit, which is recorded
+//     in the state file
+fn tracked_dep_fast_forwarded() {
+    let test_deps = test_deps();
+    let Layout{dep_srcs_dir, proj_dir, ..} = test_setup::create(
+        "tracked_dep_fast_forwarded",
+        &test_deps,
+        &hashmap!{},
+    );
+    fs::write(
+        format!("{}/dpnd.txt", proj_dir),
+        "deps\n\n\
+         my_scripts git git://localhost/my_scripts.git master track=branch\n",
+    )
+        .expect("couldn't write dependency file");
+    let scratch_dir = format!("{}/../scratch/my_scripts", dep_srcs_dir);
+    let bare_repo_dir = format!("{}/my_scripts.git", dep_srcs_dir);
+    let (first_result, second_result) = test_setup::with_git_server(
+        dep_srcs_dir.clone(),
+        || {
+            let first_result =
+                test_setup::new_test_cmd(proj_dir.clone()).assert();
+
+            fs::write(
+                format!("{}/script.sh", scratch_dir),
+                "echo 'hello, track!'",
+            )
+                .expect("couldn't write test file");
+            test_setup::commit_all(&scratch_dir);
+            test_setup::run_cmd(
+                &scratch_dir,
+                "git",
+                ["push", &bare_repo_dir, "HEAD:master"],
+            );
+
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+            cmd.args(["--log-file", "dpnd.log"]);
+
+            (first_result, cmd.assert())
+        },
+    );
+
+    first_result.code(0).stdout("").stderr("");
+    second_result.code(0).stdout("").stderr("");
+    let script_conts =
+        fs::read_to_string(format!("{}/deps/my_scripts/script.sh", proj_dir))
+            .expect("couldn't read fetched script");
+    assert_eq!(script_conts, "echo 'hello, track!'");
+    let new_hash =
+        test_setup::run_cmd(&scratch_dir, "git", ["rev-parse", "HEAD"]);
+    let state_conts = fs::read_to_string(
+        format!("{}/.dpnd/state/deps/current_dpnd.txt", proj_dir),
+    )
+        .expect("couldn't read state file");
+    assert!(
+        state_conts.contains(&format!("tracked_revision={}", new_hash.trim())),
+        "{}",
+        state_conts,
+    );
+    let log_conts = fs::read_to_string(format!("{}/dpnd.log", proj_dir))
+        .expect("couldn't read log file");
+    assert!(
+        log_conts.contains("Fast-forwarding 'my_scripts'"),
+        "{}",
+        log_conts,
+    );
+}
+
+#[test]
+#[cfg(unix)]
+// Given the dependency file specifies a `file` dependency
+// When the command is run with `--readonly`, and then run again after the
+//     dependency is removed from the dependency file
+// Then the dependency is installed without write permissions, and is then
+//     removed
+fn readonly_dep_installed_and_removed() {
+    let root_test_dir =
+        test_setup::create_root_dir("readonly_dep_installed_and_removed");
+    let files_dir = test_setup::create_dir(root_test_dir.clone(), "files");
+    fs::write(format!("{}/script.sh", files_dir), SCRIPT_CONTS)
+        .expect("couldn't write test file");
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    let deps_file = format!("{}/dpnd.txt", proj_dir);
+    fs::write(
+        &deps_file,
+        format!(
+            "deps\n\nscript file file://{}/script.sh {}\n",
+            files_dir,
+            SCRIPT_SHA256,
+        ),
+    )
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+    cmd.arg("--readonly");
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+    let dep_dir = format!("{}/deps/script", proj_dir);
+    for path in &[dep_dir.clone(), format!("{}/script.sh", dep_dir)] {
+        let mode =
+            fs::metadata(path)
+                .map(|md| md.permissions().mode())
+                .expect("couldn't get permissions");
+        assert_eq!(mode & 0o222, 0, "'{}' is writable", path);
+    }
+
+    fs::write(&deps_file, "deps\n")
+        .expect("couldn't write dependency file");
+    let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+
+    let cmd_result = cmd.assert();
+
+    cmd_result.code(0).stdout("").stderr("");
+    assert!(!Path::new(&dep_dir).exists());
+}
+
+#[test]
+#[cfg(unix)]
+// Given the dependency file specifies a `file` dependency with a `chmod`
+//     option for shell scripts
+// When the command is run, and then run again after the mode of the option is
+//     changed
+// Then the dependency is installed with the mode of the option each time
+fn chmod_option_sets_file_modes() {
+    let root_test_dir =
+        test_setup::create_root_dir("chmod_option_sets_file_modes");
+    let files_dir = test_setup::create_dir(root_test_dir.clone(), "files");
+    fs::write(format!("{}/script.sh", files_dir), SCRIPT_CONTS)
+        .expect("couldn't write test file");
+    let proj_dir = test_setup::create_dir(root_test_dir, "proj");
+    let deps_file = format!("{}/dpnd.txt", proj_dir);
+    let script_path = format!("{}/deps/script/script.sh", proj_dir);
+    for mode in &[0o755, 0o700] {
+        fs::write(
+            &deps_file,
+            format!(
+                "deps\n\nscript file file://{}/script.sh {} chmod={:o}:*.sh\n",
+                files_dir,
+                SCRIPT_SHA256,
+                mode,
+            ),
+        )
+            .expect("couldn't write dependency file");
+        let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+
+        let cmd_result = cmd.assert();
+
+        cmd_result.code(0).stdout("").stderr("");
+        let act_mode =
+            fs::metadata(&script_path)
+                .map(|md| md.permissions().mode())
+                .expect("couldn't get permissions");
+        assert_eq!(act_mode & 0o7777, *mode);
+    }
+}
+
+#[test]
+// Given dependencies were installed and the store hasn't been created
+// When `doctor` is run
+// Then each check passes
+fn doctor_finds_no_problems() {
+    let test_deps = test_deps();
+    let Layout{dep_srcs_dir, proj_dir, ..} =
+        test_setup::create(
+            "doctor_finds_no_problems",
+            &test_deps,
+            &hashmap!{"my_scripts" => 1},
+        );
+    let store_dir = format!("{}/../store", proj_dir);
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+            cmd.assert()
+                .code(0)
+                .stdout("")
+                .stderr("");
+
+            let mut cmd = test_setup::new_test_subcmd(
+                proj_dir.clone(),
+                "doctor",
+            );
+            cmd.env("DPND_STORE", &store_dir);
+
+            cmd.assert()
+        },
+    );
+
+    let output = cmd_result.code(0).stderr("").get_output().stdout.clone();
+    let stdout = String::from_utf8(output)
+        .expect("couldn't convert STDOUT to a `String`");
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 4, "unexpected STDOUT:\n{}", stdout);
+    assert!(
+        lines[0].starts_with("ok: Found git version "),
+        "unexpected STDOUT:\n{}",
+        stdout,
+    );
+    assert_eq!(
+        lines[1..],
+        [
+            format!(
+                "ok: The store at '{}' will be created when a linked \
+                 dependency is installed",
+                store_dir,
+            ).as_str(),
+            "ok: 'dpnd.txt' is valid, and the sources of its dependencies can \
+             be reached",
+            "ok: The output directories can be written to, and match their \
+             state files",
+        ],
+    );
+}
+
+#[test]
+// Given an installed dependency whose output directory was checked out at a
+//     different revision outside of `dpnd`
+// When the command is run, and then run again with `--check-integrity`
+// Then the first run leaves the dependency unchanged, and the second run
+//     installs the dependency at the revision that the dependency file
+//     selects
+fn check_integrity_repairs_moved_dep() {
+    let test_deps = test_deps();
+    let Layout{dep_srcs_dir, proj_dir, deps_commit_hashes, ..} =
+        test_setup::create(
+            "check_integrity_repairs_moved_dep",
+            &test_deps,
+            &hashmap!{},
+        );
+    let deps_file_conts = formatdoc!{
+        "
+            deps
+
+            my_scripts git git://localhost/my_scripts.git {}
+        ",
+        deps_commit_hashes["my_scripts"][0],
+    };
+    fs::write(format!("{}/dpnd.txt", proj_dir), &deps_file_conts)
+        .expect("couldn't write dependency file");
+    let dep_dir = format!("{}/deps/my_scripts", proj_dir);
+    let script_path = format!("{}/script.sh", dep_dir);
+    test_setup::with_git_server(
+        dep_srcs_dir.clone(),
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+
+            cmd.assert().code(0).stdout("").stderr("")
+        },
+    );
+    test_setup::run_cmd(
+        &dep_dir,
+        "git",
+        ["checkout", "--quiet", &deps_commit_hashes["my_scripts"][1]],
+    );
+
+    let unchecked_result = test_setup::with_git_server(
+        dep_srcs_dir.clone(),
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+
+            cmd.assert()
+        },
+    );
+    let unchecked_conts = fs::read_to_string(&script_path)
+        .expect("couldn't read script");
+    let checked_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+            cmd.arg("--check-integrity");
+
+            cmd.assert()
+        },
+    );
+
+    unchecked_result.code(0).stdout("").stderr("");
+    assert_eq!(unchecked_conts, "echo 'hello, world!'");
+    checked_result.code(0).stdout("").stderr("");
+    fs_check::assert_contents(
+        &proj_dir,
+        &Node::Dir(hashmap!{
+            ".dpnd" => Node::AnyDir,
+            "dpnd.txt" => Node::File(&deps_file_conts),
+            "deps" => Node::Dir(hashmap!{
+                "my_scripts" => Node::Dir(hashmap!{
+                    ".git" => Node::AnyDir,
+                    "script.sh" => Node::File("echo 'hello world'"),
+                }),
+            }),
+        }),
+    );
+}
+
+#[test]
+// Given the dependency file defines a dependency
+// When the command is run with `--no-state-file`, and then run again with
+//     `--no-state-file` after the version of the dependency is changed
+// Then the dependency is installed at each version, and no state file is
+//     written
+fn no_state_file_installs_without_state_file() {
+    let test_deps = test_deps();
+    let Layout{dep_srcs_dir, proj_dir, deps_commit_hashes, ..} =
+        test_setup::create(
+            "no_state_file_installs_without_state_file",
+            &test_deps,
+            &hashmap!{},
+        );
+    let deps_file = format!("{}/dpnd.txt", proj_dir);
+    let deps_file_conts = |commit_num: usize| formatdoc!{
+        "
+            deps
+
+            my_scripts git git://localhost/my_scripts.git {}
+        ",
+        deps_commit_hashes["my_scripts"][commit_num],
+    };
+    fs::write(&deps_file, deps_file_conts(0))
+        .expect("couldn't write dependency file");
+    let script_path = format!("{}/deps/my_scripts/script.sh", proj_dir);
+    test_setup::with_git_server(
+        dep_srcs_dir.clone(),
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+            cmd.arg("--no-state-file");
+
+            cmd.assert().code(0).stdout("").stderr("")
+        },
+    );
+    let first_conts = fs::read_to_string(&script_path)
+        .expect("couldn't read script");
+    let new_deps_file_conts = deps_file_conts(1);
+    fs::write(&deps_file, &new_deps_file_conts)
+        .expect("couldn't write dependency file");
+
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+            cmd.arg("--no-state-file");
+
+            cmd.assert()
+        },
+    );
+
+    assert_eq!(first_conts, "echo 'hello world'");
+    cmd_result.code(0).stdout("").stderr("");
+    fs_check::assert_contents(
+        &proj_dir,
+        &Node::Dir(hashmap!{
+            "dpnd.txt" => Node::File(&new_deps_file_conts),
+            "deps" => Node::Dir(hashmap!{
+                "my_scripts" => Node::Dir(hashmap!{
+                    ".git" => Node::AnyDir,
+                    "script.sh" => Node::File("echo 'hello, world!'"),
+                }),
+            }),
+        }),
+    );
+}
+
+#[test]
+// Given dependencies were installed
+// When the command is run without `--quiet` after one dependency is added,
+//     one is changed and one is removed
+// Then a summary of the changes is written to `stderr`
+fn install_summary_written_to_stderr() {
+    let test_deps = test_deps();
+    let Layout{dep_srcs_dir, proj_dir, deps_commit_hashes, ..} =
+        test_setup::create(
+            "install_summary_written_to_stderr",
+            &test_deps,
+            &hashmap!{},
+        );
+    let deps_file = format!("{}/dpnd.txt", proj_dir);
+    let hashes = |dep_name: &str| &deps_commit_hashes[dep_name];
+    fs::write(
+        &deps_file,
+        formatdoc!{
+            "
+                deps
+
+                my_scripts git git://localhost/my_scripts.git {}
+                your_scripts git git://localhost/your_scripts.git {}
+                their_scripts git git://localhost/their_scripts.git {}
+            ",
+            hashes("my_scripts")[0],
+            hashes("your_scripts")[0],
+            hashes("their_scripts")[0],
+        },
+    )
+        .expect("couldn't write dependency file");
+    test_setup::with_git_server(
+        dep_srcs_dir.clone(),
+        || {
+            let mut cmd = test_setup::new_test_cmd(proj_dir.clone());
+
+            cmd.assert().code(0).stdout("").stderr("")
+        },
+    );
+    fs::write(
+        &deps_file,
+        formatdoc!{
+            "
+                deps
+
+                my_scripts git git://localhost/my_scripts.git {}
+                your_scripts git git://localhost/your_scripts.git {}
+                all_scripts git git://localhost/all_scripts.git {}
+            ",
+            hashes("my_scripts")[1],
+            hashes("your_scripts")[0],
+            hashes("all_scripts")[0],
+        },
+    )
+        .expect("couldn't write dependency file");
+
+    let cmd_result = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            let mut cmd =
+                test_setup::new_test_subcmd(proj_dir.clone(), "install");
+
+            cmd.assert()
+        },
+    );
+
+    let output = cmd_result.code(0).stdout("").get_output().stderr.clone();
+    let stderr = String::from_utf8(output)
+        .expect("couldn't convert STDERR to a `String`");
+    assert!(
+        stderr.starts_with(
+            "1 installed, 1 updated, 1 removed, 1 unchanged in ",
+        ),
+        "unexpected STDERR:\n{}",
+        stderr,
+    );
+    assert!(stderr.ends_with("s\n"), "unexpected STDERR:\n{}", stderr);
+    assert_eq!(stderr.lines().count(), 1, "unexpected STDERR:\n{}", stderr);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+// Given the dependency file specifies a Git dependency
+// When the command is run without `--quiet` on a terminal
+// Then the progress of the clone is shown, labelled with the source of the
+//     dependency, and the dependency is installed
+fn clone_progress_shown_on_terminal() {
+    let test_deps = test_deps();
+    let Layout{dep_srcs_dir, proj_dir, ..} =
+        test_setup::create(
+            "clone_progress_shown_on_terminal",
+            &test_deps,
+            &hashmap!{"my_scripts" => 1},
+        );
+    let bin_path = assert_cmd::cargo::cargo_bin(env!("CARGO_PKG_NAME"));
+
+    // `script` runs the command with a pseudo-terminal as its `stderr`, and
+    // copies what the command writes to the terminal to `stdout`.
+    let output = test_setup::with_git_server(
+        dep_srcs_dir,
+        || {
+            Command::new("script")
+                .args(["--quiet", "--return", "--command"])
+                .arg(format!("{} install", bin_path.display()))
+                .arg("/dev/null")
+                .current_dir(&proj_dir)
+                .env_clear()
+                .env("PATH", "/bin:/usr/bin")
+                .output()
+                .expect("couldn't run `script`")
+        },
+    );
+
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("git://localhost/my_scripts.git: Receiving objects:"),
+        "unexpected output:\n{}",
+        stdout,
+    );
+    fs_check::assert_contents(
+        &format!("{}/deps/my_scripts", proj_dir),
+        &Node::Dir(hashmap!{
+            ".git" => Node::AnyDir,
+            "script.sh" => Node::File("echo 'hello, world!'"),
+        }),
+    );
+}
+
+#[test]
+// Given no dependency file
+// When `dpnd` is run with `--version-output json` and no subcommand
+// Then the version of `dpnd`, its built-in tools and its supported file
+//     formats are printed as JSON
+fn version_output_json() {
+    let root_test_dir = test_setup::create_root_dir("version_output_json");
+    let mut cmd = test_setup::new_test_bin_cmd(root_test_dir);
+    cmd.args(["--version-output", "json"]);
+
+    let cmd_result = cmd.assert();
+
+    let output = cmd_result.code(0).stderr("").get_output().stdout.clone();
+    let version: JsonValue = serde_json::from_slice(&output)
+        .expect("couldn't parse STDOUT as JSON");
+    assert_eq!(version["version"], env!("CARGO_PKG_VERSION"));
+    assert!(
+        version["commit"].is_string() || version["commit"].is_null(),
+        "unexpected commit: {}",
+        version["commit"],
+    );
+    let tools = version["tools"].as_array()
+        .expect("`tools` isn't an array");
+    for tool in &["file", "git", "git-file", "svn"] {
+        assert!(tools.contains(&JsonValue::from(*tool)), "{:?}", tools);
+    }
+    assert_eq!(version["deps_file_formats"], serde_json::json!([1]));
+    assert_eq!(version["state_file_formats"], serde_json::json!([1]));
+}
@@ -2,10 +2,18 @@
 // Use of this source code is governed by an MIT
 // licence that can be found in the LICENCE file.
 
+// The tests pass borrowed arguments to generic functions such as `fs::write`,
+// convert `String`s with `to_string` and name lifetimes that could be elided,
+// which newer versions of Clippy report.
+#![allow(clippy::needless_borrows_for_generic_args)]
+#![allow(clippy::implicit_clone)]
+#![allow(clippy::elidable_lifetime_names)]
+
 #[macro_use]
 extern crate indoc;
 #[macro_use]
 extern crate maplit;
+extern crate serde_json;
 
 mod cli;
 mod fs_check;
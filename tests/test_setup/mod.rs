@@ -5,10 +5,16 @@
 use std::collections::HashMap;
 use std::ffi::OsStr;
 use std::fs;
+use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
 use std::panic;
 use std::panic::UnwindSafe;
+use std::path::Path;
 use std::process::Command;
 use std::process::Stdio;
+use std::thread;
+use std::time::Duration;
 
 extern crate assert_cmd;
 
@@ -104,35 +110,81 @@ pub fn create_bare_git_repo(
     scratch_dir: &str,
     fs_states: &[HashMap<&str, &str>],
 ) {
-    let gits_args = &[
-        vec!["init"],
-        vec!["config", "user.name", "Test"],
-        vec!["config", "user.email", "test@example.com"],
-    ];
-    for git_args in gits_args {
-        run_cmd(scratch_dir, "git", git_args);
-    }
+    init_git_repo(scratch_dir);
 
     for fs_state in fs_states {
         for (fname, fconts) in fs_state {
-            fs::write(format!("{}/{}", &scratch_dir, fname), fconts)
+            let path = format!("{}/{}", &scratch_dir, fname);
+            if let Some(parent) = Path::new(&path).parent() {
+                fs::create_dir_all(parent)
+                    .expect("couldn't create test directory");
+            }
+            fs::write(&path, fconts)
                 .expect("couldn't write test file");
         }
 
-        let gits_args = &[
-            vec!["add", "--all"],
-            vec!["commit", "--message", "Initial commit"],
-        ];
-        for git_args in gits_args {
-            run_cmd(scratch_dir, "git", git_args);
-        }
+        commit_all(scratch_dir);
     }
 
     let git_args = &["clone", "--bare", scratch_dir, repo_dir];
     run_cmd(scratch_dir, "git", git_args);
 }
 
+pub fn init_git_repo(dir: &str) {
+    let gits_args = &[
+        vec!["init"],
+        vec!["config", "user.name", "Test"],
+        vec!["config", "user.email", "test@example.com"],
+    ];
+    for git_args in gits_args {
+        run_cmd(dir, "git", git_args);
+    }
+}
+
+pub fn commit_all(dir: &str) {
+    let gits_args = &[
+        vec!["add", "--all"],
+        vec!["commit", "--message", "Initial commit"],
+    ];
+    for git_args in gits_args {
+        run_cmd(dir, "git", git_args);
+    }
+}
+
+// `create_plugin` creates an executable named `dpnd-tool-<name>` in `dir`,
+// with `script` as its contents.
+#[cfg(unix)]
+pub fn create_plugin(dir: &str, name: &str, script: &str) {
+    create_executable(dir, &format!("dpnd-tool-{}", name), script);
+}
+
+// `create_executable` creates an executable named `name` in `dir`, with
+// `script` as its contents. It's used to stand in for commands that tools run.
+#[cfg(unix)]
+pub fn create_executable(dir: &str, name: &str, script: &str) {
+    let path = format!("{}/{}", dir, name);
+
+    fs::write(&path, script)
+        .expect("couldn't write executable");
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755))
+        .expect("couldn't set the permissions of executable");
+}
+
 pub fn run_cmd<I, S>(dir: &str, prog: &str, args: I) -> String
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    run_cmd_with_env(dir, prog, args, &[])
+}
+
+pub fn run_cmd_with_env<I, S>(
+    dir: &str,
+    prog: &str,
+    args: I,
+    env: &[(&str, &str)],
+)
+    -> String
 where
     I: IntoIterator<Item = S>,
     S: AsRef<OsStr>,
@@ -142,6 +194,7 @@ where
         .args(args)
         .current_dir(dir)
         .env_clear()
+        .envs(env.iter().copied())
         .output()
         .unwrap_or_else(|_|
             panic!("couldn't run `{:?}`", cmd)
@@ -161,6 +214,42 @@ where
         )
 }
 
+// `create_gpg_key` creates a GPG signing key without a passphrase in the GPG
+// home directory `gpg_home`, and returns the fingerprint of the key.
+#[cfg(unix)]
+pub fn create_gpg_key(gpg_home: &str) -> String {
+    fs::set_permissions(gpg_home, fs::Permissions::from_mode(0o700))
+        .expect("couldn't restrict permissions of GPG home directory");
+
+    let env = &[("GNUPGHOME", gpg_home)];
+    run_cmd_with_env(
+        gpg_home,
+        "gpg",
+        [
+            "--batch",
+            "--passphrase",
+            "",
+            "--quick-generate-key",
+            "Test <test@example.com>",
+            "ed25519",
+            "sign",
+            "never",
+        ],
+        env,
+    );
+    let keys = run_cmd_with_env(
+        gpg_home,
+        "gpg",
+        ["--list-keys", "--with-colons"],
+        env,
+    );
+
+    keys.lines()
+        .find_map(|line| line.strip_prefix("fpr:"))
+        .map(|rest| rest.trim_matches(':').to_string())
+        .expect("couldn't find fingerprint of GPG key")
+}
+
 // `get_repo_hashes` returns hashes in chronological order, i.e. the first
 // entry contains the hash of the oldest commit.
 fn get_repo_hashes(repo_dir: &str) -> Vec<String> {
@@ -217,13 +306,13 @@ where
         .strip_suffix('\n')
         .expect("`git --exec-path` output didn't end with a newline");
 
-    let git_exec_path = git_exec_path.to_owned();
+    let git_daemon_path = Path::new(git_exec_path).join("git-daemon");
 
     // We run `git-daemon` directly because `git daemon` spawns `git-daemon`
     // but we lose its PID in the process.
     //
     // TODO Store the output of the standard streams for debugging purposes.
-    let mut daemon = Command::new(git_exec_path + "/git-daemon")
+    let mut daemon = Command::new(git_daemon_path)
         .args(&["--reuseaddr", "--base-path=.", "--export-all", "."])
         .current_dir(dir.as_ref())
         .stderr(Stdio::null())
@@ -232,6 +321,8 @@ where
         .spawn()
         .expect("couldn't spawn Git server");
 
+    wait_for_git_server();
+
     let result = panic::catch_unwind(f);
 
     daemon.kill()
@@ -246,12 +337,57 @@ where
     }
 }
 
+// `wait_for_git_server` blocks until a connection can be made to the default
+// Git daemon port, so that tests don't race against the startup of the Git
+// server.
+fn wait_for_git_server() {
+    for _ in 0..100 {
+        if TcpStream::connect("127.0.0.1:9418").is_ok() {
+            return;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    panic!("couldn't connect to Git server");
+}
+
+// `new_test_cmd` returns a command that runs `dpnd install` with `--quiet`,
+// so that tests can check that nothing else is written to `stderr`.
 pub fn new_test_cmd(root_test_dir: String) -> AssertCommand {
+    let mut cmd = new_test_subcmd(root_test_dir, "install");
+    cmd.arg("--quiet");
+
+    cmd
+}
+
+pub fn new_test_subcmd(root_test_dir: String, subcmd: &str) -> AssertCommand {
+    let mut cmd = new_test_bin_cmd(root_test_dir);
+    cmd.arg(subcmd);
+
+    cmd
+}
+
+// `new_test_bin_cmd` returns a command that runs `dpnd` in `root_test_dir`
+// with an empty environment, without a subcommand.
+pub fn new_test_bin_cmd(root_test_dir: String) -> AssertCommand {
     let mut cmd = AssertCommand::cargo_bin(env!("CARGO_PKG_NAME"))
         .expect("couldn't create command for package binary");
     cmd.current_dir(root_test_dir);
     cmd.env_clear();
-    cmd.arg("install");
+
+    cmd
+}
+
+// `new_test_process` is like `new_test_subcmd`, but it returns a standard
+// `Command`, which can be spawned so that the test can interact with the
+// running process.
+pub fn new_test_process(root_test_dir: String, subcmd: &str) -> Command {
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin(
+        env!("CARGO_PKG_NAME"),
+    ));
+    cmd.current_dir(root_test_dir);
+    cmd.env_clear();
+    cmd.arg(subcmd);
 
     cmd
 }